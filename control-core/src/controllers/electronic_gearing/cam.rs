@@ -0,0 +1,298 @@
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+/// How the slave position is interpolated between the points of a [`CamProfile`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CamInterpolation {
+    /// Straight lines, the slave speed jumps at the points
+    #[default]
+    Linear,
+    /// Cubic Hermite spline through the points (Catmull-Rom tangents), the slave speed is continuous
+    Cubic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CamPoint {
+    pub master: f64,
+    pub slave: f64,
+}
+
+/// A tabulated slave position over the master position
+///
+/// A periodic profile repeats every `last.master - first.master`, each period adds
+/// `last.slave - first.slave` to the slave position, so a profile that ends where it started
+/// oscillates and any other keeps advancing.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CamProfile {
+    points: Vec<CamPoint>,
+    interpolation: CamInterpolation,
+    periodic: bool,
+}
+
+impl CamProfile {
+    /// The master positions must be strictly increasing
+    pub fn new(
+        points: Vec<CamPoint>,
+        interpolation: CamInterpolation,
+        periodic: bool,
+    ) -> Result<Self> {
+        if points.len() < 2 {
+            bail!(
+                "A cam profile needs at least 2 points, got {}",
+                points.len()
+            );
+        }
+        if let Some(point) = points
+            .iter()
+            .find(|point| !point.master.is_finite() || !point.slave.is_finite())
+        {
+            bail!("Cam point {:?} is not finite", point);
+        }
+        if let Some(pair) = points
+            .windows(2)
+            .find(|pair| pair[1].master <= pair[0].master)
+        {
+            bail!(
+                "Cam master positions must be strictly increasing, {} follows {}",
+                pair[1].master,
+                pair[0].master
+            );
+        }
+        Ok(Self {
+            points,
+            interpolation,
+            periodic,
+        })
+    }
+
+    pub fn points(&self) -> &[CamPoint] {
+        &self.points
+    }
+
+    pub const fn interpolation(&self) -> CamInterpolation {
+        self.interpolation
+    }
+
+    pub const fn is_periodic(&self) -> bool {
+        self.periodic
+    }
+
+    fn first(&self) -> CamPoint {
+        self.points[0]
+    }
+
+    fn last(&self) -> CamPoint {
+        self.points[self.points.len() - 1]
+    }
+
+    /// Master distance of one period
+    pub fn master_period(&self) -> f64 {
+        self.last().master - self.first().master
+    }
+
+    /// Slave distance added by one period
+    pub fn slave_rise(&self) -> f64 {
+        self.last().slave - self.first().slave
+    }
+
+    /// Slave position at the master position, outside of a non periodic profile the end points are held
+    pub fn position(&self, master: f64) -> f64 {
+        let (periods, master) = self.wrap(master);
+        let (segment, t) = self.locate(master);
+        periods.mul_add(self.slave_rise(), self.interpolate(segment, t))
+    }
+
+    /// Slave distance per master distance at the master position
+    pub fn slope(&self, master: f64) -> f64 {
+        let (_, master) = self.wrap(master);
+        if !self.periodic && (master <= self.first().master || master >= self.last().master) {
+            return 0.0;
+        }
+        let (segment, t) = self.locate(master);
+        self.interpolate_slope(segment, t)
+    }
+
+    /// Number of whole periods before the master position and the master position within the period
+    fn wrap(&self, master: f64) -> (f64, f64) {
+        if !self.periodic {
+            return (0.0, master.clamp(self.first().master, self.last().master));
+        }
+        let periods = ((master - self.first().master) / self.master_period()).floor();
+        (periods, periods.mul_add(-self.master_period(), master))
+    }
+
+    /// Index of the segment and the position within it from 0 to 1
+    fn locate(&self, master: f64) -> (usize, f64) {
+        let index = self
+            .points
+            .partition_point(|point| point.master <= master)
+            .clamp(1, self.points.len() - 1)
+            - 1;
+        let (a, b) = (self.points[index], self.points[index + 1]);
+        (
+            index,
+            ((master - a.master) / (b.master - a.master)).clamp(0.0, 1.0),
+        )
+    }
+
+    /// Point at the index, the points of the neighbouring periods for indices outside of the table
+    fn point(&self, index: isize) -> Option<CamPoint> {
+        let last = self.points.len() as isize - 1;
+        if (0..=last).contains(&index) {
+            return Some(self.points[index as usize]);
+        }
+        if !self.periodic {
+            return None;
+        }
+        // the last point of a period is the first of the next one
+        let (shift, index) = if index < 0 {
+            (-1.0_f64, index + last)
+        } else {
+            (1.0, index - last)
+        };
+        let point = self.points[index as usize];
+        Some(CamPoint {
+            master: shift.mul_add(self.master_period(), point.master),
+            slave: shift.mul_add(self.slave_rise(), point.slave),
+        })
+    }
+
+    /// Catmull-Rom tangent at the point, one-sided at the ends of a non periodic profile
+    fn tangent(&self, index: usize) -> f64 {
+        let index = index as isize;
+        let current = self.points[index as usize];
+        let previous = self.point(index - 1).unwrap_or(current);
+        let next = self.point(index + 1).unwrap_or(current);
+        (next.slave - previous.slave) / (next.master - previous.master)
+    }
+
+    /// Coefficients of the cubic Hermite polynomial of the segment over t from 0 to 1
+    fn cubic_coefficients(&self, segment: usize) -> [f64; 4] {
+        let (a, b) = (self.points[segment], self.points[segment + 1]);
+        let h = b.master - a.master;
+        let (ta, tb) = (self.tangent(segment) * h, self.tangent(segment + 1) * h);
+        let rise = b.slave - a.slave;
+        [
+            a.slave,
+            ta,
+            3.0f64.mul_add(rise, -2.0f64.mul_add(ta, tb)),
+            (-2.0f64).mul_add(rise, ta + tb),
+        ]
+    }
+
+    fn interpolate(&self, segment: usize, t: f64) -> f64 {
+        let (a, b) = (self.points[segment], self.points[segment + 1]);
+        match self.interpolation {
+            CamInterpolation::Linear => t.mul_add(b.slave - a.slave, a.slave),
+            CamInterpolation::Cubic => {
+                let [c0, c1, c2, c3] = self.cubic_coefficients(segment);
+                c3.mul_add(t, c2).mul_add(t, c1).mul_add(t, c0)
+            }
+        }
+    }
+
+    fn interpolate_slope(&self, segment: usize, t: f64) -> f64 {
+        let (a, b) = (self.points[segment], self.points[segment + 1]);
+        let h = b.master - a.master;
+        match self.interpolation {
+            CamInterpolation::Linear => (b.slave - a.slave) / h,
+            CamInterpolation::Cubic => {
+                let [_, c1, c2, c3] = self.cubic_coefficients(segment);
+                (3.0 * c3).mul_add(t, 2.0 * c2).mul_add(t, c1) / h
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    fn points(points: &[(f64, f64)]) -> Vec<CamPoint> {
+        points
+            .iter()
+            .map(|&(master, slave)| CamPoint { master, slave })
+            .collect()
+    }
+
+    #[test]
+    fn test_invalid_profiles() {
+        let linear = CamInterpolation::Linear;
+        assert!(CamProfile::new(points(&[(0.0, 0.0)]), linear, false).is_err());
+        assert!(CamProfile::new(points(&[(0.0, 0.0), (0.0, 1.0)]), linear, false).is_err());
+        assert!(CamProfile::new(points(&[(0.0, 0.0), (f64::NAN, 1.0)]), linear, false).is_err());
+    }
+
+    #[test]
+    fn test_linear_profile() {
+        let cam = CamProfile::new(
+            points(&[(0.0, 0.0), (1.0, 10.0), (3.0, 0.0)]),
+            CamInterpolation::Linear,
+            false,
+        )
+        .unwrap();
+        assert_relative_eq!(cam.position(0.5), 5.0);
+        assert_relative_eq!(cam.position(2.0), 5.0);
+        assert_relative_eq!(cam.slope(0.5), 10.0);
+        assert_relative_eq!(cam.slope(2.0), -5.0);
+
+        // the ends are held
+        assert_relative_eq!(cam.position(-1.0), 0.0);
+        assert_relative_eq!(cam.position(4.0), 0.0);
+        assert_relative_eq!(cam.slope(4.0), 0.0);
+    }
+
+    #[test]
+    fn test_periodic_profile() {
+        // a back and forth stroke repeats without drifting
+        let stroke = CamProfile::new(
+            points(&[(0.0, 0.0), (2.0, 10.0), (4.0, 0.0)]),
+            CamInterpolation::Linear,
+            true,
+        )
+        .unwrap();
+        assert_relative_eq!(stroke.position(1_000_001.0), 5.0);
+        assert_relative_eq!(stroke.position(-1.0), 5.0);
+        assert_relative_eq!(stroke.slope(1_000_003.0), -5.0);
+
+        // a rising profile keeps advancing
+        let rising = CamProfile::new(
+            points(&[(0.0, 0.0), (1.0, 2.0), (2.0, 3.0)]),
+            CamInterpolation::Linear,
+            true,
+        )
+        .unwrap();
+        assert_relative_eq!(rising.position(5.0), 8.0);
+        assert_relative_eq!(rising.position(-0.5), -3.0 + 2.5);
+    }
+
+    #[test]
+    fn test_cubic_profile() {
+        let cam = CamProfile::new(
+            points(&[(0.0, 0.0), (1.0, 1.0), (2.0, 0.0), (3.0, 1.0)]),
+            CamInterpolation::Cubic,
+            false,
+        )
+        .unwrap();
+
+        // passes through the points
+        for point in cam.points() {
+            assert_relative_eq!(cam.position(point.master), point.slave, epsilon = 1e-12);
+        }
+
+        // the slope is continuous at the points and matches the position
+        let epsilon = 1e-6;
+        for master in [1.0, 2.0] {
+            assert_relative_eq!(
+                cam.slope(master - epsilon),
+                cam.slope(master + epsilon),
+                epsilon = 1e-4
+            );
+        }
+        let numeric = (cam.position(0.7 + epsilon) - cam.position(0.7 - epsilon)) / (2.0 * epsilon);
+        assert_relative_eq!(cam.slope(0.7), numeric, epsilon = 1e-6);
+        // Catmull-Rom tangent at the middle points is the slope between their neighbours
+        assert_relative_eq!(cam.slope(1.0), 0.0, epsilon = 1e-12);
+    }
+}
@@ -0,0 +1,305 @@
+use super::cam::CamProfile;
+
+/// How the slave position follows the master position
+#[derive(Debug, Clone, PartialEq)]
+pub enum GearingLaw {
+    /// Slave distance per master distance
+    Ratio(f64),
+    /// The cam starts at the master position where the gear was engaged
+    Cam(CamProfile),
+}
+
+impl GearingLaw {
+    /// Slave position relative to the engage position
+    pub fn position(&self, master: f64) -> f64 {
+        match self {
+            Self::Ratio(ratio) => ratio * master,
+            Self::Cam(cam) => cam.position(master),
+        }
+    }
+
+    /// Slave distance per master distance
+    pub fn slope(&self, master: f64) -> f64 {
+        match self {
+            Self::Ratio(ratio) => *ratio,
+            Self::Cam(cam) => cam.slope(master),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GearState {
+    /// The slave position doesn't change
+    Disengaged,
+    /// The coupling ramps up over the engage distance of the master
+    Engaging,
+    /// The slave is locked to the master
+    Engaged,
+    /// The coupling ramps down over the disengage distance of the master
+    Disengaging,
+}
+
+/// Electronic gearing between a master and a slave axis
+///
+/// Works on positions only, so the slave doesn't drift from the master no matter how the master
+/// speed changes. The slave position is meant as the target of a position loop, its speed is
+/// the master speed times [`ElectronicGear::ratio`].
+///
+/// The engage and disengage ramps are over a master distance, the slave stays behind the law by
+/// what it missed during the ramp.
+#[derive(Debug, Clone)]
+pub struct ElectronicGear {
+    law: GearingLaw,
+    /// Master distance over which the coupling ramps up
+    engage_distance: f64,
+    /// Master distance over which the coupling ramps down
+    disengage_distance: f64,
+    state: GearState,
+    /// Master position where the law starts
+    master_offset: f64,
+    /// Slave position where the law starts
+    slave_offset: f64,
+    slave_position: f64,
+    /// From 0 (disengaged) to 1 (engaged)
+    coupling: f64,
+    /// Master position and coupling at the start of a ramp
+    ramp_start: (f64, f64),
+    last_master: Option<f64>,
+}
+
+impl ElectronicGear {
+    pub const fn new(law: GearingLaw) -> Self {
+        Self {
+            law,
+            engage_distance: 0.0,
+            disengage_distance: 0.0,
+            state: GearState::Disengaged,
+            master_offset: 0.0,
+            slave_offset: 0.0,
+            slave_position: 0.0,
+            coupling: 0.0,
+            ramp_start: (0.0, 0.0),
+            last_master: None,
+        }
+    }
+
+    /// Master distances of the engage and disengage ramps, 0 switches immediately
+    pub const fn with_ramps(mut self, engage_distance: f64, disengage_distance: f64) -> Self {
+        self.engage_distance = engage_distance.abs();
+        self.disengage_distance = disengage_distance.abs();
+        self
+    }
+
+    pub const fn law(&self) -> &GearingLaw {
+        &self.law
+    }
+
+    /// Bumpless, the new law starts at the current master and slave position
+    pub fn set_law(&mut self, law: GearingLaw) {
+        self.law = law;
+        if let Some(master) = self.last_master {
+            self.anchor(master);
+        }
+    }
+
+    pub const fn state(&self) -> GearState {
+        self.state
+    }
+
+    pub const fn is_engaged(&self) -> bool {
+        matches!(self.state, GearState::Engaged)
+    }
+
+    pub const fn slave_position(&self) -> f64 {
+        self.slave_position
+    }
+
+    /// Current slave distance per master distance, including the ramps
+    pub fn ratio(&self) -> f64 {
+        self.last_master.map_or(0.0, |master| {
+            self.coupling * self.law.slope(master - self.master_offset)
+        })
+    }
+
+    /// Starts following the master, the slave starts at `slave`
+    pub fn engage(&mut self, master: f64, slave: f64) {
+        self.slave_position = slave;
+        self.last_master = Some(master);
+        self.master_offset = master;
+        self.anchor(master);
+        if self.engage_distance > 0.0 {
+            self.coupling = 0.0;
+            self.ramp_start = (master, 0.0);
+            self.state = GearState::Engaging;
+        } else {
+            self.coupling = 1.0;
+            self.state = GearState::Engaged;
+        }
+    }
+
+    pub fn disengage(&mut self) {
+        match self.state {
+            GearState::Disengaged | GearState::Disengaging => {}
+            GearState::Engaging | GearState::Engaged if self.disengage_distance > 0.0 => {
+                self.ramp_start = (self.last_master.unwrap_or_default(), self.coupling);
+                self.state = GearState::Disengaging;
+            }
+            GearState::Engaging | GearState::Engaged => {
+                self.coupling = 0.0;
+                self.state = GearState::Disengaged;
+            }
+        }
+    }
+
+    /// Returns the slave position for the master position
+    pub fn update(&mut self, master: f64) -> f64 {
+        let Some(last_master) = self.last_master else {
+            self.last_master = Some(master);
+            return self.slave_position;
+        };
+
+        match self.state {
+            GearState::Disengaged => {}
+            GearState::Engaged => {
+                let law = self.law.position(master - self.master_offset);
+                self.slave_position = self.slave_offset + law;
+            }
+            GearState::Engaging | GearState::Disengaging => {
+                let engaging = self.state == GearState::Engaging;
+                let (ramp_master, ramp_coupling) = self.ramp_start;
+                let distance = if engaging {
+                    self.engage_distance
+                } else {
+                    self.disengage_distance
+                };
+                let progress = ((master - ramp_master).abs() / distance).min(1.0);
+                let coupling = if engaging {
+                    progress
+                } else {
+                    ramp_coupling * (1.0 - progress)
+                };
+
+                // the slave moves with the mean coupling over the master step
+                let step = self.law.position(master - self.master_offset)
+                    - self.law.position(last_master - self.master_offset);
+                self.slave_position += (self.coupling + coupling) / 2.0 * step;
+                self.coupling = coupling;
+
+                if progress >= 1.0 {
+                    if engaging {
+                        self.state = GearState::Engaged;
+                        self.anchor(master);
+                    } else {
+                        self.state = GearState::Disengaged;
+                    }
+                }
+            }
+        }
+
+        self.last_master = Some(master);
+        self.slave_position
+    }
+
+    /// Keeps the law where it is and moves its slave offset to the current slave position
+    fn anchor(&mut self, master: f64) {
+        self.slave_offset = self.slave_position - self.law.position(master - self.master_offset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::cam::{CamInterpolation, CamPoint};
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_ratio() {
+        let mut gear = ElectronicGear::new(GearingLaw::Ratio(1.75));
+        gear.engage(10.0, 5.0);
+        assert!(gear.is_engaged());
+        assert_relative_eq!(gear.update(12.0), 2.0f64.mul_add(1.75, 5.0));
+        assert_relative_eq!(gear.ratio(), 1.75);
+
+        // the slave doesn't drift however the master moves
+        let mut master = 12.0;
+        for step in 0..100_000 {
+            master += 0.001 * (step % 7) as f64;
+            gear.update(master);
+        }
+        assert_relative_eq!(
+            gear.slave_position(),
+            (master - 10.0).mul_add(1.75, 5.0),
+            epsilon = 1e-9
+        );
+
+        gear.disengage();
+        assert_eq!(gear.state(), GearState::Disengaged);
+        let position = gear.slave_position();
+        assert_relative_eq!(gear.update(master + 10.0), position);
+    }
+
+    #[test]
+    fn test_cam() {
+        let cam = CamProfile::new(
+            vec![
+                CamPoint {
+                    master: 0.0,
+                    slave: 0.0,
+                },
+                CamPoint {
+                    master: 1.0,
+                    slave: 3.0,
+                },
+                CamPoint {
+                    master: 2.0,
+                    slave: 0.0,
+                },
+            ],
+            CamInterpolation::Linear,
+            true,
+        )
+        .unwrap();
+        let mut gear = ElectronicGear::new(GearingLaw::Cam(cam));
+        gear.engage(100.0, 10.0);
+        assert_relative_eq!(gear.update(100.5), 11.5);
+        assert_relative_eq!(gear.update(101.5), 11.5);
+        assert_relative_eq!(gear.ratio(), -3.0);
+        assert_relative_eq!(gear.update(1100.5), 11.5, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_ramps() {
+        let mut gear = ElectronicGear::new(GearingLaw::Ratio(2.0)).with_ramps(1.0, 2.0);
+        gear.engage(0.0, 0.0);
+        assert_eq!(gear.state(), GearState::Engaging);
+
+        // the coupling ramps up linearly, the slave misses half of the ramp distance
+        gear.update(0.5);
+        assert_relative_eq!(gear.ratio(), 1.0);
+        for step in 1..=10 {
+            gear.update((step as f64).mul_add(0.05, 0.5));
+        }
+        assert_eq!(gear.state(), GearState::Engaged);
+        assert_relative_eq!(gear.slave_position(), 1.0, epsilon = 1e-9);
+        assert_relative_eq!(gear.update(2.0), 3.0, epsilon = 1e-9);
+
+        gear.disengage();
+        assert_eq!(gear.state(), GearState::Disengaging);
+        for step in 1..=20 {
+            gear.update((step as f64).mul_add(0.1, 2.0));
+        }
+        assert_eq!(gear.state(), GearState::Disengaged);
+        assert_relative_eq!(gear.ratio(), 0.0);
+        assert_relative_eq!(gear.slave_position(), 5.0, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_set_law_is_bumpless() {
+        let mut gear = ElectronicGear::new(GearingLaw::Ratio(1.0));
+        gear.engage(0.0, 0.0);
+        gear.update(4.0);
+        gear.set_law(GearingLaw::Ratio(-0.5));
+        assert_relative_eq!(gear.update(4.0), 4.0);
+        assert_relative_eq!(gear.update(6.0), 3.0);
+    }
+}
@@ -0,0 +1,2 @@
+pub mod cam;
+pub mod gear;
@@ -1,4 +1,5 @@
-pub mod clamping_timeagnostic_pid;
+pub mod electronic_gearing;
 pub mod first_degree_motion;
 pub mod pid;
+pub mod pid_autotune;
 pub mod second_degree_motion;
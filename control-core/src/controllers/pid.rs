@@ -1,104 +1,481 @@
+use serde::{Deserialize, Serialize};
 use std::time::Instant;
 
-#[derive(Debug)]
-pub struct PidController {
-    // Params
+/// Parameters of a [`PidController`], the defaults give a plain textbook PID
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PidParameters {
     /// Proportional gain
-    kp: f64,
+    pub kp: f64,
     /// Integral gain
-    ki: f64,
+    pub ki: f64,
     /// Derivative gain
-    kd: f64,
-    // State
-    /// Proportional error
-    ep: f64,
-    /// Integral error
-    ei: f64,
-    /// Derivative error
-    ed: f64,
+    pub kd: f64,
+    /// Lower limit of the output
+    #[serde(default)]
+    pub output_min: Option<f64>,
+    /// Upper limit of the output
+    #[serde(default)]
+    pub output_max: Option<f64>,
+    /// Limits of the proportional term, in units of the output
+    #[serde(default)]
+    pub proportional_min: Option<f64>,
+    #[serde(default)]
+    pub proportional_max: Option<f64>,
+    /// Limits of the integral term, in units of the output
+    #[serde(default)]
+    pub integral_min: Option<f64>,
+    #[serde(default)]
+    pub integral_max: Option<f64>,
+    /// Limits of the derivative term, in units of the output
+    #[serde(default)]
+    pub derivative_min: Option<f64>,
+    #[serde(default)]
+    pub derivative_max: Option<f64>,
+    /// Back-calculation anti-windup, the integral tracks the limited output with this time
+    /// constant in s. `None` uses the integral time kp / ki.
+    #[serde(default)]
+    pub tracking_time: Option<f64>,
+    /// Differentiate the measurement instead of the error, so setpoint steps don't kick the output
+    #[serde(default)]
+    pub derivative_on_measurement: bool,
+    /// Time constant in s of the first order low pass on the derivative, 0 disables it
+    #[serde(default)]
+    pub derivative_filter_time: f64,
+    /// Weight of the setpoint in the proportional term, below 1 softens setpoint steps
+    #[serde(default = "default_setpoint_weight")]
+    pub setpoint_weight: f64,
+}
 
-    last: Option<Instant>,
+const fn default_setpoint_weight() -> f64 {
+    1.0
 }
 
-impl PidController {
+impl PidParameters {
     pub const fn new(kp: f64, ki: f64, kd: f64) -> Self {
         Self {
             kp,
             ki,
             kd,
-            ep: 0.0,
-            ei: 0.0,
-            ed: 0.0,
+            output_min: None,
+            output_max: None,
+            proportional_min: None,
+            proportional_max: None,
+            integral_min: None,
+            integral_max: None,
+            derivative_min: None,
+            derivative_max: None,
+            tracking_time: None,
+            derivative_on_measurement: false,
+            derivative_filter_time: 0.0,
+            setpoint_weight: 1.0,
+        }
+    }
+
+    pub const fn with_output_limits(mut self, min: Option<f64>, max: Option<f64>) -> Self {
+        self.output_min = min;
+        self.output_max = max;
+        self
+    }
+
+    const fn clamp_output(&self, value: f64) -> f64 {
+        optional_clamp(value, self.output_min, self.output_max)
+    }
+
+    /// Gain of the back-calculation, 0 without integral action
+    fn tracking_gain(&self) -> f64 {
+        match self.tracking_time {
+            Some(time) if time > 0.0 => 1.0 / time,
+            _ if self.ki != 0.0 && self.kp != 0.0 => (self.ki / self.kp).abs(),
+            _ if self.ki != 0.0 => 1.0,
+            _ => 0.0,
+        }
+    }
+}
+
+const fn optional_clamp(value: f64, min: Option<f64>, max: Option<f64>) -> f64 {
+    match (min, max) {
+        (Some(min), Some(max)) => value.clamp(min, max),
+        (Some(min), None) => value.max(min),
+        (None, Some(max)) => value.min(max),
+        (None, None) => value,
+    }
+}
+
+#[derive(Debug)]
+pub struct PidController {
+    parameters: PidParameters,
+    /// Added to the output, e.g. the power needed to hold the setpoint
+    feed_forward: f64,
+    // State
+    /// Integral term, kept as its share of the output so gain changes don't bump the output
+    integral: f64,
+    /// Filtered derivative of the error or the negated measurement
+    derivative: f64,
+    /// Last input of the derivative
+    last_derivative_input: f64,
+    /// Last input of the proportional term
+    last_proportional_input: f64,
+    /// Last limited output
+    output: f64,
+
+    last: Option<Instant>,
+}
+
+impl PidController {
+    pub const fn new(kp: f64, ki: f64, kd: f64) -> Self {
+        Self::with_parameters(PidParameters::new(kp, ki, kd))
+    }
+
+    pub const fn with_parameters(parameters: PidParameters) -> Self {
+        Self {
+            parameters,
+            feed_forward: 0.0,
+            integral: 0.0,
+            derivative: 0.0,
+            last_derivative_input: 0.0,
+            last_proportional_input: 0.0,
+            output: 0.0,
             last: None,
         }
     }
 
-    pub const fn configure(&mut self, ki: f64, kp: f64, kd: f64) {
-        self.reset();
-        self.kp = kp;
-        self.ki = ki;
-        self.kd = kd;
+    pub const fn parameters(&self) -> &PidParameters {
+        &self.parameters
+    }
+
+    /// Bumpless, the integral absorbs the change of the proportional term
+    ///
+    /// Without integral action the integral is cleared, nothing would ever reduce it.
+    pub fn set_parameters(&mut self, parameters: PidParameters) {
+        if parameters.ki == 0.0 {
+            self.integral = 0.0;
+        } else if self.last.is_some() {
+            self.integral += (self.parameters.kp - parameters.kp) * self.last_proportional_input;
+        }
+        self.parameters = parameters;
+    }
+
+    pub fn set_gains(&mut self, kp: f64, ki: f64, kd: f64) {
+        self.set_parameters(PidParameters {
+            kp,
+            ki,
+            kd,
+            ..self.parameters.clone()
+        });
     }
 
     pub const fn get_kp(&self) -> f64 {
-        self.kp
+        self.parameters.kp
     }
 
     pub const fn get_ki(&self) -> f64 {
-        self.ki
+        self.parameters.ki
     }
 
     pub const fn get_kd(&self) -> f64 {
-        self.kd
+        self.parameters.kd
+    }
+
+    pub const fn set_feed_forward(&mut self, feed_forward: f64) {
+        self.feed_forward = feed_forward;
+    }
+
+    /// The last output
+    pub const fn output(&self) -> f64 {
+        self.output
+    }
+
+    /// Takes over from manual control, the next output continues from `output`
+    pub const fn track(&mut self, output: f64) {
+        let proportional = self.parameters.kp * self.last_proportional_input;
+        let derivative = self.parameters.kd * self.derivative;
+        self.integral = output - proportional - derivative - self.feed_forward;
+        self.output = output;
     }
 
+    /// Controls on the error alone, the derivative is always taken on the error
     pub fn update(&mut self, error: f64, t: Instant) -> f64 {
-        match self.last {
-            // First update
-            None => {
-                // Calculate error
-                let ep = error;
-
-                // Calculate signal
-                let signal = self.kp * ep;
-
-                // Set values
-                self.ep = ep;
-                self.ei = 0.0;
-
-                self.ed = 0.0;
-                self.last = Some(t);
-
-                signal
-            }
-            // Subsequent updates
-            Some(last) => {
-                // Calculate the time delta in seconds
-                let dt = t.duration_since(last).as_secs_f64();
-
-                // Calculate errors
-                let ep = error;
-                let ei = ep.mul_add(dt, self.ei);
-                let ed = (ep - self.ep) / dt;
-
-                // Calculate signal
-                let signal = self.kd.mul_add(ed, self.kp.mul_add(ep, self.ki * ei));
-
-                // Set values
-                self.ep = ep;
-                self.ei = ei;
-                self.ed = ed;
-                self.last = Some(t);
-
-                signal
-            }
+        self.step(error, error, error, t)
+    }
+
+    pub fn compute(&mut self, setpoint: f64, measurement: f64, t: Instant) -> f64 {
+        let error = setpoint - measurement;
+        let proportional_input = self
+            .parameters
+            .setpoint_weight
+            .mul_add(setpoint, -measurement);
+        let derivative_input = if self.parameters.derivative_on_measurement {
+            -measurement
+        } else {
+            error
+        };
+        self.step(error, proportional_input, derivative_input, t)
+    }
+
+    fn step(
+        &mut self,
+        error: f64,
+        proportional_input: f64,
+        derivative_input: f64,
+        t: Instant,
+    ) -> f64 {
+        let parameters = &self.parameters;
+        // On the first update there is no derivative and nothing to integrate yet
+        let dt = self
+            .last
+            .map_or(0.0, |last| t.saturating_duration_since(last).as_secs_f64());
+
+        if dt > 0.0 {
+            let raw_derivative = (derivative_input - self.last_derivative_input) / dt;
+            let alpha = dt / (parameters.derivative_filter_time.max(0.0) + dt);
+            self.derivative += alpha * (raw_derivative - self.derivative);
+            self.integral += parameters.ki * error * dt;
+        } else if self.last.is_none() {
+            self.derivative = 0.0;
         }
+        self.integral = optional_clamp(
+            self.integral,
+            parameters.integral_min,
+            parameters.integral_max,
+        );
+
+        let proportional = optional_clamp(
+            parameters.kp * proportional_input,
+            parameters.proportional_min,
+            parameters.proportional_max,
+        );
+        let derivative = optional_clamp(
+            parameters.kd * self.derivative,
+            parameters.derivative_min,
+            parameters.derivative_max,
+        );
+        let unlimited = proportional + self.integral + derivative + self.feed_forward;
+        let output = parameters.clamp_output(unlimited);
+
+        // Back-calculation, without it the integral keeps growing while the output is limited
+        let tracking = (parameters.tracking_gain() * dt).min(1.0);
+        self.integral = optional_clamp(
+            tracking.mul_add(output - unlimited, self.integral),
+            parameters.integral_min,
+            parameters.integral_max,
+        );
+
+        self.last_derivative_input = derivative_input;
+        self.last_proportional_input = proportional_input;
+        self.output = output;
+        self.last = Some(t);
+
+        output
     }
 
     pub const fn reset(&mut self) {
-        self.ep = 0.0;
-        self.ei = 0.0;
-        self.ed = 0.0;
+        self.integral = 0.0;
+        self.derivative = 0.0;
+        self.last_derivative_input = 0.0;
+        self.last_proportional_input = 0.0;
+        self.output = 0.0;
         self.last = None;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use std::time::Duration;
+
+    const DT: f64 = 0.1;
+
+    /// First order process with gain 1 and time constant 5 s, starting at 0
+    struct Plant {
+        value: f64,
+    }
+
+    impl Plant {
+        fn step(&mut self, input: f64) -> f64 {
+            self.value += (input - self.value) * DT / 5.0;
+            self.value
+        }
+    }
+
+    /// Closed loop response to a setpoint step from 0 to 1, returns the outputs and values
+    fn step_response(pid: &mut PidController, steps: usize) -> (Vec<f64>, Vec<f64>) {
+        let t0 = Instant::now();
+        let mut plant = Plant { value: 0.0 };
+        let mut outputs = Vec::with_capacity(steps);
+        let mut values = Vec::with_capacity(steps);
+        for step in 0..steps {
+            let t = t0 + Duration::from_secs_f64(step as f64 * DT);
+            let output = pid.compute(1.0, plant.value, t);
+            outputs.push(output);
+            values.push(plant.step(output));
+        }
+        (outputs, values)
+    }
+
+    fn max(values: &[f64]) -> f64 {
+        values.iter().copied().fold(f64::MIN, f64::max)
+    }
+
+    #[test]
+    fn test_open_loop_fixture() {
+        // kp + ki * t + kd * de/dt for a constant error of 2 that starts at 0
+        let t0 = Instant::now();
+        let mut pid = PidController::new(1.5, 0.5, 0.2);
+        let expected = [3.0, 3.1, 3.2, 3.3];
+        for (step, expected) in expected.into_iter().enumerate() {
+            let t = t0 + Duration::from_secs_f64(step as f64 * DT);
+            assert_relative_eq!(pid.update(2.0, t), expected, epsilon = 1e-9);
+        }
+
+        // the error jumps from 2 to 3, the derivative kicks by kd * 1 / DT
+        let t = t0 + Duration::from_secs_f64(4.0 * DT);
+        assert_relative_eq!(pid.update(3.0, t), 4.5 + 0.45 + 2.0, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_step_response_fixture() {
+        let mut pid = PidController::new(2.0, 0.5, 0.0);
+        let (outputs, values) = step_response(&mut pid, 600);
+
+        // the first output is kp * error, the next one adds the first integral step
+        assert_relative_eq!(outputs[0], 2.0, epsilon = 1e-9);
+        assert_relative_eq!(values[0], 0.04, epsilon = 1e-9);
+        assert_relative_eq!(
+            outputs[1],
+            2.0f64.mul_add(0.96, 0.5 * 0.096),
+            epsilon = 1e-9
+        );
+
+        // settles on the setpoint without offset
+        assert_relative_eq!(values[599], 1.0, epsilon = 1e-3);
+        assert!(max(&values) < 1.2);
+    }
+
+    #[test]
+    fn test_anti_windup() {
+        let parameters = PidParameters::new(2.0, 2.0, 0.0).with_output_limits(Some(0.0), Some(1.2));
+        let mut limited = PidController::with_parameters(parameters.clone());
+        let (outputs, limited_values) = step_response(&mut limited, 600);
+        assert!(outputs.iter().all(|output| (0.0..=1.2).contains(output)));
+
+        let mut wound_up = PidController::with_parameters(PidParameters {
+            // a very slow tracking behaves like no anti-windup
+            tracking_time: Some(1e9),
+            ..parameters
+        });
+        let (_, wound_up_values) = step_response(&mut wound_up, 600);
+
+        let overshoot = max(&limited_values) - 1.0;
+        let wound_up_overshoot = max(&wound_up_values) - 1.0;
+        assert!(overshoot < 0.05, "overshoot {overshoot}");
+        assert!(wound_up_overshoot > 2.0 * overshoot.max(0.01));
+        assert_relative_eq!(limited_values[599], 1.0, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn test_derivative_on_measurement() {
+        let t0 = Instant::now();
+        let t1 = t0 + Duration::from_secs_f64(DT);
+        let mut on_error = PidController::new(1.0, 0.0, 1.0);
+        let mut on_measurement = PidController::with_parameters(PidParameters {
+            derivative_on_measurement: true,
+            ..PidParameters::new(1.0, 0.0, 1.0)
+        });
+
+        for pid in [&mut on_error, &mut on_measurement] {
+            pid.compute(0.0, 0.0, t0);
+        }
+
+        // a setpoint step kicks only the derivative of the error
+        assert_relative_eq!(on_error.compute(1.0, 0.0, t1), 1.0 + 1.0 / DT);
+        assert_relative_eq!(on_measurement.compute(1.0, 0.0, t1), 1.0);
+
+        // both react to the measurement
+        let t2 = t1 + Duration::from_secs_f64(DT);
+        assert_relative_eq!(on_measurement.compute(1.0, 0.1, t2), 0.9 - 1.0);
+    }
+
+    #[test]
+    fn test_derivative_filter() {
+        let t0 = Instant::now();
+        let mut pid = PidController::with_parameters(PidParameters {
+            derivative_filter_time: 0.3,
+            ..PidParameters::new(0.0, 0.0, 1.0)
+        });
+        pid.update(0.0, t0);
+
+        // the ramp has a derivative of 1, the filter approaches it exponentially
+        let mut expected = 0.0;
+        for step in 1..=20 {
+            let t = t0 + Duration::from_secs_f64(step as f64 * DT);
+            expected += 0.25 * (1.0 - expected);
+            assert_relative_eq!(pid.update(step as f64 * DT, t), expected, epsilon = 1e-9);
+        }
+        assert_relative_eq!(expected, 1.0, epsilon = 0.01);
+    }
+
+    #[test]
+    fn test_setpoint_weight_and_feed_forward() {
+        let t0 = Instant::now();
+        let mut pid = PidController::with_parameters(PidParameters {
+            setpoint_weight: 0.5,
+            ..PidParameters::new(2.0, 0.0, 0.0)
+        });
+        assert_relative_eq!(pid.compute(1.0, 0.0, t0), 1.0);
+
+        pid.set_feed_forward(0.25);
+        assert_relative_eq!(pid.compute(1.0, 0.5, t0), 0.25);
+    }
+
+    #[test]
+    fn test_bumpless_transfer() {
+        let t0 = Instant::now();
+        let mut pid = PidController::new(1.0, 1.0, 0.0);
+        pid.update(1.0, t0);
+        let t1 = t0 + Duration::from_secs_f64(DT);
+        let before = pid.update(1.0, t1);
+
+        // changing the gains keeps the output continuous
+        pid.set_gains(3.0, 1.0, 0.0);
+        let after = pid.update(1.0, t1);
+        assert_relative_eq!(before, after, epsilon = 1e-9);
+
+        // taking over from manual control continues from the manual output
+        pid.track(0.4);
+        assert_relative_eq!(pid.update(1.0, t1), 0.4, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_disabling_integral_clears_it() {
+        let t0 = Instant::now();
+        let mut pid = PidController::new(1.0, 1.0, 0.0);
+        pid.update(1.0, t0);
+        pid.update(1.0, t0 + Duration::from_secs(5));
+
+        pid.set_gains(1.0, 0.0, 0.0);
+        assert_relative_eq!(pid.update(1.0, t0 + Duration::from_secs(6)), 1.0);
+        assert_relative_eq!(pid.update(-1.0, t0 + Duration::from_secs(7)), -1.0);
+    }
+
+    #[test]
+    fn test_term_limits() {
+        let t0 = Instant::now();
+        let mut pid = PidController::with_parameters(PidParameters {
+            proportional_max: Some(1.0),
+            integral_min: Some(-0.5),
+            integral_max: Some(0.5),
+            derivative_min: Some(-1.0),
+            ..PidParameters::new(2.0, 1.0, 1.0)
+        });
+        assert_relative_eq!(pid.update(1.0, t0), 1.0);
+
+        // the integral stops at its limit and recovers right away when the error changes sign
+        for step in 1..=10 {
+            pid.update(1.0, t0 + Duration::from_secs(step));
+        }
+        assert_relative_eq!(pid.update(1.0, t0 + Duration::from_secs(11)), 1.5);
+        // 2 * -1, the integral is back at -0.5 and the derivative of -2 is limited to -1
+        assert_relative_eq!(pid.update(-1.0, t0 + Duration::from_secs(12)), -3.5);
+    }
+}
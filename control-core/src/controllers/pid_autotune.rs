@@ -0,0 +1,465 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::time::Instant;
+
+/// Rule to compute the gains from the ultimate gain and period
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+pub enum TuningRule {
+    #[default]
+    ZieglerNicholsPid,
+    ZieglerNicholsPi,
+    /// Less aggressive than Ziegler-Nichols, for slow processes like heating zones
+    TyreusLuyben,
+    SomeOvershoot,
+    NoOvershoot,
+}
+
+impl TuningRule {
+    /// Returns (kp, ki, kd)
+    pub fn gains(self, ultimate_gain: f64, ultimate_period: f64) -> (f64, f64, f64) {
+        let (kp, ti, td) = match self {
+            Self::ZieglerNicholsPid => (
+                0.6 * ultimate_gain,
+                ultimate_period / 2.0,
+                ultimate_period / 8.0,
+            ),
+            Self::ZieglerNicholsPi => (0.45 * ultimate_gain, ultimate_period / 1.2, 0.0),
+            Self::TyreusLuyben => (
+                ultimate_gain / 2.2,
+                2.2 * ultimate_period,
+                ultimate_period / 6.3,
+            ),
+            Self::SomeOvershoot => (
+                ultimate_gain / 3.0,
+                ultimate_period / 2.0,
+                ultimate_period / 3.0,
+            ),
+            Self::NoOvershoot => (
+                0.2 * ultimate_gain,
+                ultimate_period / 2.0,
+                ultimate_period / 3.0,
+            ),
+        };
+        (kp, kp / ti, kp * td)
+    }
+}
+
+/// Parameters of a relay autotune, set by the operator
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AutotuneParameters {
+    /// The process oscillates around this value
+    pub setpoint: f64,
+    #[serde(default)]
+    pub rule: TuningRule,
+    /// Band around the setpoint in which the relay doesn't switch, against sensor noise
+    #[serde(default = "default_hysteresis")]
+    pub hysteresis: f64,
+    /// Oscillation cycles that are averaged, the first one is not counted
+    #[serde(default = "default_cycles")]
+    pub cycles: usize,
+    /// The autotune fails when the process goes this far above the setpoint
+    #[serde(default = "default_max_overshoot")]
+    pub max_overshoot: f64,
+    /// The autotune fails when the process goes this far below the setpoint, never if `None`
+    #[serde(default)]
+    pub max_undershoot: Option<f64>,
+    /// The autotune fails when it takes longer, in s
+    #[serde(default = "default_timeout")]
+    pub timeout: f64,
+}
+
+const fn default_hysteresis() -> f64 {
+    0.5
+}
+
+const fn default_cycles() -> usize {
+    4
+}
+
+const fn default_max_overshoot() -> f64 {
+    20.0
+}
+
+const fn default_timeout() -> f64 {
+    3600.0
+}
+
+/// More cycles don't average out any more noise, the measurements are kept for each cycle
+const MAX_CYCLES: usize = 100;
+
+/// A day, in s
+const MAX_TIMEOUT: f64 = 86400.0;
+
+impl AutotuneParameters {
+    /// Checked before the autotune starts, the values come from the operator
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !self.setpoint.is_finite() {
+            anyhow::bail!("Autotune setpoint must be finite, got {}", self.setpoint);
+        }
+        if !self.hysteresis.is_finite() || self.hysteresis < 0.0 {
+            anyhow::bail!(
+                "Autotune hysteresis must not be negative, got {}",
+                self.hysteresis
+            );
+        }
+        if !(1..=MAX_CYCLES).contains(&self.cycles) {
+            anyhow::bail!(
+                "Autotune cycles must be between 1 and {}, got {}",
+                MAX_CYCLES,
+                self.cycles
+            );
+        }
+        if self.timeout > MAX_TIMEOUT {
+            anyhow::bail!(
+                "Autotune timeout must not exceed {} s, got {}",
+                MAX_TIMEOUT,
+                self.timeout
+            );
+        }
+        let limits = [
+            ("max_overshoot", Some(self.max_overshoot)),
+            ("max_undershoot", self.max_undershoot),
+            ("timeout", Some(self.timeout)),
+        ];
+        for (name, value) in limits {
+            if let Some(value) = value
+                && (!value.is_finite() || value <= 0.0)
+            {
+                anyhow::bail!("Autotune {} must be positive, got {}", name, value);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct AutotuneResult {
+    pub ultimate_gain: f64,
+    /// in s
+    pub ultimate_period: f64,
+    pub kp: f64,
+    pub ki: f64,
+    pub kd: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum AutotuneStatus {
+    /// `cycle` full oscillations were measured so far
+    Running {
+        cycle: usize,
+        cycles: usize,
+    },
+    Finished(AutotuneResult),
+    Failed(String),
+}
+
+/// Relay feedback autotune (Åström–Hägglund)
+///
+/// The output switches between high and low whenever the process crosses the setpoint,
+/// which makes it oscillate at its ultimate period. The ultimate gain follows from the
+/// oscillation amplitude.
+#[derive(Debug, Clone)]
+pub struct RelayAutotuner {
+    parameters: AutotuneParameters,
+    output_high: f64,
+    output_low: f64,
+    started_at: Instant,
+    relay_high: bool,
+    /// Start of the current cycle, a cycle starts when the relay switches to high
+    cycle_started_at: Option<Instant>,
+    cycle_max: f64,
+    cycle_min: f64,
+    /// Period in s and amplitude of each measured cycle
+    measured: Vec<(f64, f64)>,
+    /// Cycles that are not counted yet
+    skip_cycles: usize,
+    status: AutotuneStatus,
+}
+
+impl RelayAutotuner {
+    pub fn new(
+        parameters: AutotuneParameters,
+        output_high: f64,
+        output_low: f64,
+        now: Instant,
+    ) -> Self {
+        let cycles = parameters.cycles.clamp(1, MAX_CYCLES);
+        Self {
+            parameters: AutotuneParameters {
+                cycles,
+                ..parameters
+            },
+            output_high,
+            output_low,
+            started_at: now,
+            relay_high: true,
+            cycle_started_at: None,
+            cycle_max: f64::MIN,
+            cycle_min: f64::MAX,
+            measured: Vec::with_capacity(cycles),
+            skip_cycles: 1,
+            status: AutotuneStatus::Running { cycle: 0, cycles },
+        }
+    }
+
+    pub const fn parameters(&self) -> &AutotuneParameters {
+        &self.parameters
+    }
+
+    pub const fn status(&self) -> &AutotuneStatus {
+        &self.status
+    }
+
+    pub const fn is_running(&self) -> bool {
+        matches!(self.status, AutotuneStatus::Running { .. })
+    }
+
+    pub fn fail(&mut self, reason: impl Into<String>) {
+        if self.is_running() {
+            self.status = AutotuneStatus::Failed(reason.into());
+        }
+    }
+
+    /// Returns the output to apply, the low output once the autotune is done
+    pub fn update(&mut self, value: f64, now: Instant) -> f64 {
+        if !self.is_running() {
+            return self.output_low;
+        }
+
+        let parameters = &self.parameters;
+        if value > parameters.setpoint + parameters.max_overshoot {
+            self.fail(format!(
+                "Value {:.1} exceeded the limit of {:.1}",
+                value,
+                parameters.setpoint + parameters.max_overshoot
+            ));
+            return self.output_low;
+        }
+        if let Some(max_undershoot) = parameters.max_undershoot
+            && value < parameters.setpoint - max_undershoot
+        {
+            self.fail(format!(
+                "Value {:.1} fell below the limit of {:.1}",
+                value,
+                parameters.setpoint - max_undershoot
+            ));
+            return self.output_low;
+        }
+        if now.saturating_duration_since(self.started_at).as_secs_f64() > parameters.timeout {
+            self.fail(format!("Timed out after {:.0} s", parameters.timeout));
+            return self.output_low;
+        }
+
+        self.cycle_max = self.cycle_max.max(value);
+        self.cycle_min = self.cycle_min.min(value);
+
+        if self.relay_high && value > parameters.setpoint + parameters.hysteresis {
+            self.relay_high = false;
+        } else if !self.relay_high && value < parameters.setpoint - parameters.hysteresis {
+            self.relay_high = true;
+            self.end_cycle(now);
+        }
+
+        if !self.is_running() {
+            return self.output_low;
+        }
+        if self.relay_high {
+            self.output_high
+        } else {
+            self.output_low
+        }
+    }
+
+    fn end_cycle(&mut self, now: Instant) {
+        if let Some(cycle_started_at) = self.cycle_started_at {
+            if self.skip_cycles > 0 {
+                // the first cycle starts from an arbitrary state
+                self.skip_cycles -= 1;
+            } else {
+                let period = now
+                    .saturating_duration_since(cycle_started_at)
+                    .as_secs_f64();
+                let amplitude = (self.cycle_max - self.cycle_min) / 2.0;
+                self.measured.push((period, amplitude));
+            }
+        }
+        self.cycle_started_at = Some(now);
+        self.cycle_max = f64::MIN;
+        self.cycle_min = f64::MAX;
+
+        let cycles = self.parameters.cycles;
+        self.status = AutotuneStatus::Running {
+            cycle: self.measured.len(),
+            cycles,
+        };
+        if self.measured.len() >= cycles {
+            self.finish();
+        }
+    }
+
+    fn finish(&mut self) {
+        let count = self.measured.len() as f64;
+        let period = self.measured.iter().map(|(p, _)| p).sum::<f64>() / count;
+        let amplitude = self.measured.iter().map(|(_, a)| a).sum::<f64>() / count;
+        let hysteresis = self.parameters.hysteresis;
+
+        if amplitude <= hysteresis || period <= 0.0 {
+            self.status = AutotuneStatus::Failed(format!(
+                "Oscillation amplitude {:.2} is not above the hysteresis {:.2}",
+                amplitude, hysteresis
+            ));
+            return;
+        }
+
+        // describing function of a relay with hysteresis
+        let relay_amplitude = (self.output_high - self.output_low) / 2.0;
+        let ultimate_gain = 4.0 * relay_amplitude
+            / (PI
+                * amplitude
+                    .mul_add(amplitude, -hysteresis * hysteresis)
+                    .sqrt());
+        let (kp, ki, kd) = self.parameters.rule.gains(ultimate_gain, period);
+
+        self.status = AutotuneStatus::Finished(AutotuneResult {
+            ultimate_gain,
+            ultimate_period: period,
+            kp,
+            ki,
+            kd,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use std::time::Duration;
+
+    fn parameters() -> AutotuneParameters {
+        AutotuneParameters {
+            setpoint: 100.0,
+            rule: TuningRule::ZieglerNicholsPid,
+            hysteresis: 0.0,
+            cycles: 3,
+            max_overshoot: 20.0,
+            max_undershoot: None,
+            timeout: 1000.0,
+        }
+    }
+
+    #[test]
+    fn test_relay_autotune() {
+        let t0 = Instant::now();
+        let mut tuner = RelayAutotuner::new(parameters(), 1.0, 0.0, t0);
+
+        // a triangle wave of amplitude 2 around the setpoint with a period of 20 s
+        let mut output = 1.0;
+        for step in 0..=1000 {
+            let t = step as f64 * 0.1;
+            let phase = (t % 20.0) / 20.0;
+            let value = if phase < 0.5 {
+                8.0f64.mul_add(phase, 98.0)
+            } else {
+                8.0f64.mul_add(-phase, 106.0)
+            };
+            output = tuner.update(value, t0 + Duration::from_secs_f64(t));
+            if !tuner.is_running() {
+                break;
+            }
+        }
+
+        let AutotuneStatus::Finished(result) = tuner.status().clone() else {
+            panic!("autotune didn't finish: {:?}", tuner.status());
+        };
+        assert_eq!(output, 0.0);
+        assert_relative_eq!(result.ultimate_period, 20.0, epsilon = 0.2);
+        assert_relative_eq!(result.ultimate_gain, 4.0 * 0.5 / (PI * 2.0), epsilon = 0.01);
+        assert_relative_eq!(result.kp, 0.6 * result.ultimate_gain);
+        assert_relative_eq!(result.ki, result.kp / (result.ultimate_period / 2.0));
+        assert_relative_eq!(result.kd, result.kp * result.ultimate_period / 8.0);
+    }
+
+    #[test]
+    fn test_relay_autotune_limits() {
+        let t0 = Instant::now();
+        let mut tuner = RelayAutotuner::new(parameters(), 1.0, -1.0, t0);
+        assert_eq!(tuner.update(90.0, t0), 1.0);
+        assert_eq!(tuner.update(101.0, t0), -1.0);
+        assert_eq!(tuner.update(121.0, t0), -1.0);
+        assert!(matches!(tuner.status(), AutotuneStatus::Failed(_)));
+
+        let mut tuner = RelayAutotuner::new(parameters(), 1.0, 0.0, t0);
+        tuner.update(90.0, t0 + Duration::from_secs(1001));
+        assert!(matches!(tuner.status(), AutotuneStatus::Failed(_)));
+
+        let parameters = AutotuneParameters {
+            max_undershoot: Some(10.0),
+            ..parameters()
+        };
+        let mut tuner = RelayAutotuner::new(parameters, 1.0, -1.0, t0);
+        assert_eq!(tuner.update(91.0, t0), 1.0);
+        assert_eq!(tuner.update(89.0, t0), -1.0);
+        assert!(matches!(tuner.status(), AutotuneStatus::Failed(_)));
+    }
+
+    #[test]
+    fn test_validate_parameters() {
+        parameters().validate().unwrap();
+        let invalid = [
+            AutotuneParameters {
+                timeout: f64::NAN,
+                ..parameters()
+            },
+            AutotuneParameters {
+                timeout: -1.0,
+                ..parameters()
+            },
+            AutotuneParameters {
+                hysteresis: -0.5,
+                ..parameters()
+            },
+            AutotuneParameters {
+                max_overshoot: 0.0,
+                ..parameters()
+            },
+            AutotuneParameters {
+                max_undershoot: Some(f64::INFINITY),
+                ..parameters()
+            },
+            AutotuneParameters {
+                setpoint: f64::NAN,
+                ..parameters()
+            },
+            AutotuneParameters {
+                timeout: 1e20,
+                ..parameters()
+            },
+            AutotuneParameters {
+                cycles: 0,
+                ..parameters()
+            },
+            AutotuneParameters {
+                cycles: usize::MAX,
+                ..parameters()
+            },
+        ];
+        for parameters in invalid {
+            assert!(parameters.validate().is_err(), "{:?}", parameters);
+        }
+    }
+
+    #[test]
+    fn test_tuning_rules() {
+        let (kp, ki, kd) = TuningRule::ZieglerNicholsPi.gains(10.0, 60.0);
+        assert_relative_eq!(kp, 4.5);
+        assert_relative_eq!(ki, 0.09);
+        assert_relative_eq!(kd, 0.0);
+
+        let (kp, ki, kd) = TuningRule::TyreusLuyben.gains(2.2, 10.0);
+        assert_relative_eq!(kp, 1.0);
+        assert_relative_eq!(ki, 1.0 / 22.0);
+        assert_relative_eq!(kd, 10.0 / 6.3);
+    }
+}
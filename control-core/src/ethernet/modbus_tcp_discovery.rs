@@ -1,33 +1,148 @@
 use crate::ethernet::get_interfaces;
 use crate::{futures::FutureIteratorExt, modbus::tcp::ModbusTcpDevice};
-use anyhow::{Result, bail};
+use anyhow::{Result, anyhow, bail};
 use interfaces::Interface;
-use std::cmp::min;
+use std::collections::HashSet;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
-pub struct ModbusTcpProbe {
-    pub addr: SocketAddr,
-    pub serial: u16,
+pub const MODBUS_TCP_PORT: u16 = 502;
+
+/// Subnets with a shorter prefix are not scanned, to keep a discovery run short
+const MIN_SUBNET_PREFIX: u8 = 22;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ipv4Subnet {
+    pub network: Ipv4Addr,
+    pub prefix: u8,
 }
 
-pub async fn probe_modbus_tcp() -> Vec<ModbusTcpProbe> {
-    let interfaces = match get_interfaces() {
-        Ok(x) => x,
-        Err(_) => return vec![],
-    };
+impl Ipv4Subnet {
+    pub fn new(addr: Ipv4Addr, prefix: u8) -> Result<Self> {
+        if prefix > 32 {
+            bail!("Invalid prefix length {}", prefix);
+        }
+        let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+        Ok(Self {
+            network: Ipv4Addr::from(u32::from(addr) & mask),
+            prefix,
+        })
+    }
 
-    interfaces
-        .into_iter()
-        .map(probe_modbus_tcp_addresses)
-        .join_all()
-        .await
-        .into_iter()
-        .flatten()
-        .collect()
+    /// All host addresses of the subnet, without the network and broadcast address
+    pub fn hosts(&self) -> impl Iterator<Item = Ipv4Addr> {
+        let network = u32::from(self.network);
+        let size = 1u64 << (32 - self.prefix);
+        let range = if size > 2 { 1..size - 1 } else { 0..size };
+        range.map(move |i| Ipv4Addr::from(network + i as u32))
+    }
 }
 
-async fn probe_modbus_tcp_addresses(interface: Interface) -> Vec<ModbusTcpProbe> {
-    let out: Vec<ModbusTcpProbe> = interface
+impl FromStr for Ipv4Subnet {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (addr, prefix) = s
+            .trim()
+            .split_once('/')
+            .ok_or_else(|| anyhow!("Subnet {:?} is missing the prefix length", s))?;
+        Self::new(addr.parse()?, prefix.parse()?)
+    }
+}
+
+/// Parses "192.168.10.5" or "192.168.10.5:5020"
+pub fn parse_modbus_tcp_address(s: &str) -> Result<SocketAddr> {
+    let s = s.trim();
+    if let Ok(addr) = s.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+    let ip: IpAddr = s.parse()?;
+    Ok(SocketAddr::new(ip, MODBUS_TCP_PORT))
+}
+
+#[derive(Debug, Clone)]
+pub struct ModbusTcpDiscoveryConfig {
+    /// Scan the /24 around the address of every ethernet interface
+    pub scan_interfaces: bool,
+    pub subnets: Vec<Ipv4Subnet>,
+    pub static_addresses: Vec<SocketAddr>,
+    /// Time between two discovery runs
+    pub interval: Duration,
+}
+
+impl Default for ModbusTcpDiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            scan_interfaces: true,
+            subnets: vec![],
+            static_addresses: vec![],
+            interval: Duration::from_secs(5),
+        }
+    }
+}
+
+impl ModbusTcpDiscoveryConfig {
+    /// Parses the subnets, for example "192.168.10.0/24", and the device addresses, for example
+    /// "192.168.10.5" or "10.0.0.2:5020"
+    pub fn new(
+        scan_interfaces: bool,
+        subnets: &[String],
+        addresses: &[String],
+        interval: Duration,
+    ) -> Result<Self> {
+        let subnets = subnets
+            .iter()
+            .map(|s| {
+                let subnet: Ipv4Subnet = s.parse()?;
+                if subnet.prefix < MIN_SUBNET_PREFIX {
+                    bail!("Subnet {} is larger than /{}", s, MIN_SUBNET_PREFIX);
+                }
+                Ok(subnet)
+            })
+            .collect::<Result<_>>()?;
+        let static_addresses = addresses
+            .iter()
+            .map(|s| {
+                parse_modbus_tcp_address(s)
+                    .map_err(|e| anyhow!("Invalid modbus tcp address {:?}: {}", s, e))
+            })
+            .collect::<Result<_>>()?;
+        if interval.is_zero() {
+            bail!("The modbus tcp discovery interval must not be 0");
+        }
+        Ok(Self {
+            scan_interfaces,
+            subnets,
+            static_addresses,
+            interval,
+        })
+    }
+
+    /// All addresses a discovery run connects to
+    fn addresses(&self) -> Vec<SocketAddr> {
+        let mut subnets = self.subnets.clone();
+        if self.scan_interfaces {
+            if let Ok(interfaces) = get_interfaces() {
+                subnets.extend(interfaces.iter().flat_map(interface_subnets));
+            }
+        }
+
+        let mut seen = HashSet::new();
+        subnets
+            .iter()
+            .flat_map(|subnet| subnet.hosts())
+            .map(|ip| SocketAddr::new(ip.into(), MODBUS_TCP_PORT))
+            .chain(self.static_addresses.iter().copied())
+            .filter(|addr| seen.insert(*addr))
+            .collect()
+    }
+}
+
+/// The /24 (or smaller, if the interface is in a smaller network) around every IPv4 address of the interface
+fn interface_subnets(interface: &Interface) -> Vec<Ipv4Subnet> {
+    interface
         .addresses
         .iter()
         .filter_map(|addr| {
@@ -35,44 +150,156 @@ async fn probe_modbus_tcp_addresses(interface: Interface) -> Vec<ModbusTcpProbe>
             let m = addr.mask.map(|a| a.ip());
 
             match (a, m) {
-                (Some(IpAddr::V4(addr)), Some(IpAddr::V4(mask))) => Some((addr, mask)),
+                (Some(IpAddr::V4(addr)), Some(IpAddr::V4(mask))) => {
+                    let prefix = u32::from(mask).count_ones().max(24) as u8;
+                    Ipv4Subnet::new(addr, prefix).ok()
+                }
                 _ => None,
             }
         })
-        .flat_map(|(addr, mask)| {
-            let a = u32::from(addr);
-            let m = u32::from(mask);
-            let network = a & m;
+        .collect()
+}
+
+/// Describes how to recognize a device type on the bus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModbusTcpDeviceProfile {
+    pub name: &'static str,
+    /// Holding registers (read as u32) and the values they need to contain
+    pub identification: &'static [(u16, u32)],
+    /// Holding register containing the serial number (read as u32)
+    pub serial_register: u16,
+}
 
-            let prefix = min(24, m.count_ones()); // mask → prefix length
-            let size = 1u32 << (32 - prefix); // number of addresses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModbusTcpProbe {
+    pub addr: SocketAddr,
+    pub serial: u16,
+    pub profile: ModbusTcpDeviceProfile,
+}
 
-            (0..size).map(move |i| SocketAddr::new(Ipv4Addr::from(network + i).into(), 502))
-        })
-        .map(|addr| smol::spawn(ping_modbus_device(addr)))
+/// Connects to every configured address and returns the devices matching one of `profiles`
+pub async fn probe_modbus_tcp(
+    config: &ModbusTcpDiscoveryConfig,
+    profiles: &[ModbusTcpDeviceProfile],
+) -> Vec<ModbusTcpProbe> {
+    let profiles: Arc<[ModbusTcpDeviceProfile]> = profiles.into();
+    config
+        .addresses()
+        .into_iter()
+        .map(|addr| smol::spawn(ping_modbus_device(addr, profiles.clone())))
         .join_all()
         .await
         .into_iter()
         .filter_map(|x| x.ok())
-        .collect();
+        .collect()
+}
+
+async fn ping_modbus_device(
+    addr: SocketAddr,
+    profiles: Arc<[ModbusTcpDeviceProfile]>,
+) -> Result<ModbusTcpProbe> {
+    for profile in profiles.iter() {
+        // every profile gets a fresh connection, a failed read can leave the old one out of sync
+        let mut device = ModbusTcpDevice::new(addr).await?;
+        if let Ok(serial) = identify_device(&mut device, profile).await {
+            return Ok(ModbusTcpProbe {
+                addr,
+                serial,
+                profile: *profile,
+            });
+        }
+    }
+
+    bail!("Unknown modbus tcp device!");
+}
+
+async fn identify_device(
+    device: &mut ModbusTcpDevice,
+    profile: &ModbusTcpDeviceProfile,
+) -> Result<u16> {
+    for (register, expected) in profile.identification {
+        if device.get_u32(*register).await? != *expected {
+            bail!("Device is not a {}", profile.name);
+        }
+    }
 
-    out
+    let serial = device.get_u32(profile.serial_register).await?;
+    Ok(serial as u16)
 }
 
-async fn ping_modbus_device(addr: SocketAddr) -> Result<ModbusTcpProbe> {
-    let mut device = ModbusTcpDevice::new(addr).await?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subnet_from_str() {
+        let subnet: Ipv4Subnet = "192.168.10.77/24".parse().unwrap();
+        assert_eq!(subnet.network, Ipv4Addr::new(192, 168, 10, 0));
+        assert_eq!(subnet.prefix, 24);
+        assert_eq!(subnet.hosts().count(), 254);
+        assert_eq!(subnet.hosts().next(), Some(Ipv4Addr::new(192, 168, 10, 1)));
 
-    let module_number1 = device.get_u32(0x2).await?;
-    let module_number2 = device.get_u32(0x4).await?;
+        let single: Ipv4Subnet = "10.0.0.5/32".parse().unwrap();
+        assert_eq!(
+            single.hosts().collect::<Vec<_>>(),
+            [Ipv4Addr::new(10, 0, 0, 5)]
+        );
+
+        assert!("10.0.0.0".parse::<Ipv4Subnet>().is_err());
+        assert!("10.0.0.0/33".parse::<Ipv4Subnet>().is_err());
+    }
 
-    if module_number1 != 0x2787_2144 || module_number2 != 0x0000_0000 {
-        bail!("Unknown modbus tcp device!");
+    #[test]
+    fn test_parse_modbus_tcp_address() {
+        assert_eq!(
+            parse_modbus_tcp_address("192.168.10.5").unwrap(),
+            "192.168.10.5:502".parse().unwrap()
+        );
+        assert_eq!(
+            parse_modbus_tcp_address(" 10.0.0.2:5020 ").unwrap(),
+            "10.0.0.2:5020".parse().unwrap()
+        );
+        assert!(parse_modbus_tcp_address("wago").is_err());
     }
 
-    let serial = device.get_u32(0x000A).await?;
+    #[test]
+    fn test_config_new() {
+        let strings = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        let config = ModbusTcpDiscoveryConfig::new(
+            false,
+            &strings(&["192.168.10.0/24"]),
+            &strings(&["10.0.0.2:5020", " 10.0.0.3"]),
+            Duration::from_secs(5),
+        )
+        .unwrap();
+        assert_eq!(config.subnets[0].network, Ipv4Addr::new(192, 168, 10, 0));
+        assert_eq!(
+            config.static_addresses[1],
+            "10.0.0.3:502".parse::<SocketAddr>().unwrap()
+        );
 
-    Ok(ModbusTcpProbe {
-        addr,
-        serial: serial as u16,
-    })
+        let interval = Duration::from_secs(5);
+        assert!(
+            ModbusTcpDiscoveryConfig::new(true, &strings(&["10.0.0.0/8"]), &[], interval).is_err()
+        );
+        assert!(ModbusTcpDiscoveryConfig::new(true, &[], &strings(&["wago"]), interval).is_err());
+        assert!(ModbusTcpDiscoveryConfig::new(true, &[], &[], Duration::ZERO).is_err());
+    }
+
+    #[test]
+    fn test_config_addresses_are_unique() {
+        let config = ModbusTcpDiscoveryConfig {
+            scan_interfaces: false,
+            subnets: vec!["10.0.0.0/30".parse().unwrap()],
+            static_addresses: vec!["10.0.0.1:502".parse().unwrap()],
+            interval: Duration::from_secs(1),
+        };
+        assert_eq!(
+            config.addresses(),
+            vec![
+                "10.0.0.1:502".parse::<SocketAddr>().unwrap(),
+                "10.0.0.2:502".parse().unwrap()
+            ]
+        );
+    }
 }
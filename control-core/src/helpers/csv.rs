@@ -0,0 +1,57 @@
+/// A type that is exported as one CSV row
+pub trait CsvRow {
+    /// Column names, comma separated
+    const CSV_HEADER: &'static str;
+
+    /// The columns of [`Self::CSV_HEADER`], text fields quoted with [`csv_field`]
+    fn csv_row(&self) -> String;
+}
+
+/// Quotes a CSV field if needed
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Header and one row per entry
+pub fn to_csv<T: CsvRow>(rows: &[T]) -> String {
+    let mut csv = format!("{}\n", T::CSV_HEADER);
+    for row in rows {
+        csv.push_str(&row.csv_row());
+        csv.push('\n');
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Row(&'static str);
+
+    impl CsvRow for Row {
+        const CSV_HEADER: &'static str = "text";
+
+        fn csv_row(&self) -> String {
+            csv_field(self.0)
+        }
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+        assert_eq!(csv_field("carriage\rreturn"), "\"carriage\rreturn\"");
+    }
+
+    #[test]
+    fn test_to_csv() {
+        assert_eq!(to_csv(&[Row("a"), Row("b,c")]), "text\na\n\"b,c\"\n");
+        assert_eq!(to_csv::<Row>(&[]), "text\n");
+    }
+}
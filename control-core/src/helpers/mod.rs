@@ -1,6 +1,8 @@
 pub mod compare_lists;
+pub mod csv;
 pub mod hasher_serializer;
 pub mod hashing;
 pub mod interpolation;
 pub mod moving_time_window;
 pub mod retry;
+pub mod spc;
@@ -0,0 +1,595 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Rules are only evaluated once the rolling window contains this many samples,
+/// before that the estimated sigma is too unreliable
+pub const MIN_SAMPLES_FOR_RULES: usize = 25;
+
+/// Lower and upper specification limit of a measured characteristic
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SpecificationLimits {
+    pub lower: Option<f64>,
+    pub upper: Option<f64>,
+}
+
+impl SpecificationLimits {
+    pub fn contains(&self, value: f64) -> bool {
+        self.lower.is_none_or(|lower| value >= lower)
+            && self.upper.is_none_or(|upper| value <= upper)
+    }
+}
+
+/// Statistics of a set of samples
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default, JsonSchema)]
+pub struct SpcSummary {
+    pub count: u64,
+    pub mean: Option<f64>,
+    /// Sample standard deviation
+    pub std_dev: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Process capability, needs both specification limits
+    pub cp: Option<f64>,
+    /// Process capability accounting for the offset of the mean
+    pub cpk: Option<f64>,
+    pub out_of_tolerance_percent: f64,
+}
+
+/// Accumulates statistics without storing the samples (Welford's algorithm)
+#[derive(Debug, Clone, Default)]
+pub struct SpcAccumulator {
+    count: u64,
+    mean: f64,
+    m2: f64,
+    min: f64,
+    max: f64,
+    out_of_tolerance: u64,
+}
+
+impl SpcAccumulator {
+    pub fn add(&mut self, value: f64, in_tolerance: bool) {
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+
+        if !in_tolerance {
+            self.out_of_tolerance += 1;
+        }
+    }
+
+    pub const fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.mean)
+    }
+
+    pub fn std_dev(&self) -> Option<f64> {
+        (self.count > 1).then(|| (self.m2 / (self.count - 1) as f64).sqrt())
+    }
+
+    pub fn summary(&self, limits: &SpecificationLimits) -> SpcSummary {
+        if self.count == 0 {
+            return SpcSummary::default();
+        }
+
+        let mean = self.mean;
+        let std_dev = self.std_dev();
+        let (cp, cpk) = std_dev.map_or((None, None), |sigma| capability(mean, sigma, limits));
+
+        SpcSummary {
+            count: self.count,
+            mean: Some(mean),
+            std_dev,
+            min: Some(self.min),
+            max: Some(self.max),
+            cp,
+            cpk,
+            out_of_tolerance_percent: self.out_of_tolerance as f64 / self.count as f64 * 100.0,
+        }
+    }
+}
+
+/// Returns (Cp, Cpk), Cp needs both limits, Cpk at least one
+pub fn capability(
+    mean: f64,
+    sigma: f64,
+    limits: &SpecificationLimits,
+) -> (Option<f64>, Option<f64>) {
+    if sigma <= 0.0 {
+        return (None, None);
+    }
+
+    let cp = match (limits.lower, limits.upper) {
+        (Some(lower), Some(upper)) => Some((upper - lower) / (6.0 * sigma)),
+        _ => None,
+    };
+    let cpu = limits.upper.map(|upper| (upper - mean) / (3.0 * sigma));
+    let cpl = limits.lower.map(|lower| (mean - lower) / (3.0 * sigma));
+    let cpk = match (cpu, cpl) {
+        (Some(cpu), Some(cpl)) => Some(cpu.min(cpl)),
+        (cpu, cpl) => cpu.or(cpl),
+    };
+
+    (cp, cpk)
+}
+
+/// Statistics over the last `capacity` samples
+///
+/// The sums are updated with every sample, relative to a shift near the data to keep the
+/// cancellation small. They are recomputed once per window length to drop the rounding drift,
+/// min and max come from monotonic queues.
+#[derive(Debug, Clone)]
+pub struct RollingSpc {
+    capacity: usize,
+    samples: VecDeque<(f64, bool)>,
+    shift: f64,
+    sum: f64,
+    sum_squares: f64,
+    out_of_tolerance: usize,
+    /// (sequence number, value) of the window minimum and the later samples above it
+    min: VecDeque<(u64, f64)>,
+    /// (sequence number, value) of the window maximum and the later samples below it
+    max: VecDeque<(u64, f64)>,
+    /// Sequence number of the next sample
+    next: u64,
+    evicted_since_recompute: usize,
+}
+
+impl RollingSpc {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            samples: VecDeque::with_capacity(capacity),
+            shift: 0.0,
+            sum: 0.0,
+            sum_squares: 0.0,
+            out_of_tolerance: 0,
+            min: VecDeque::new(),
+            max: VecDeque::new(),
+            next: 0,
+            evicted_since_recompute: 0,
+        }
+    }
+
+    pub fn add(&mut self, value: f64, in_tolerance: bool) {
+        if self.samples.len() >= self.capacity {
+            self.evict();
+        }
+        if self.samples.is_empty() {
+            self.shift = value;
+        }
+
+        let shifted = value - self.shift;
+        self.sum += shifted;
+        self.sum_squares += shifted * shifted;
+        if !in_tolerance {
+            self.out_of_tolerance += 1;
+        }
+        self.samples.push_back((value, in_tolerance));
+
+        while self.min.back().is_some_and(|(_, min)| *min >= value) {
+            self.min.pop_back();
+        }
+        self.min.push_back((self.next, value));
+        while self.max.back().is_some_and(|(_, max)| *max <= value) {
+            self.max.pop_back();
+        }
+        self.max.push_back((self.next, value));
+        self.next += 1;
+
+        if self.evicted_since_recompute >= self.capacity {
+            self.recompute();
+        }
+    }
+
+    fn evict(&mut self) {
+        let oldest = self.next - self.samples.len() as u64;
+        let Some((value, in_tolerance)) = self.samples.pop_front() else {
+            return;
+        };
+
+        let shifted = value - self.shift;
+        self.sum -= shifted;
+        self.sum_squares -= shifted * shifted;
+        if !in_tolerance {
+            self.out_of_tolerance -= 1;
+        }
+        if self.min.front().is_some_and(|(sequence, _)| *sequence == oldest) {
+            self.min.pop_front();
+        }
+        if self.max.front().is_some_and(|(sequence, _)| *sequence == oldest) {
+            self.max.pop_front();
+        }
+        self.evicted_since_recompute += 1;
+    }
+
+    fn recompute(&mut self) {
+        self.shift = self.samples.front().map_or(0.0, |(value, _)| *value);
+        self.sum = 0.0;
+        self.sum_squares = 0.0;
+        for (value, _) in &self.samples {
+            let shifted = value - self.shift;
+            self.sum += shifted;
+            self.sum_squares += shifted * shifted;
+        }
+        self.evicted_since_recompute = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new(self.capacity);
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        let count = self.samples.len() as f64;
+        (!self.is_empty()).then(|| self.shift + self.sum / count)
+    }
+
+    pub fn std_dev(&self) -> Option<f64> {
+        let count = self.samples.len() as f64;
+        (self.samples.len() > 1).then(|| {
+            let m2 = self.sum_squares - self.sum * self.sum / count;
+            (m2.max(0.0) / (count - 1.0)).sqrt()
+        })
+    }
+
+    pub fn summary(&self, limits: &SpecificationLimits) -> SpcSummary {
+        let Some(mean) = self.mean() else {
+            return SpcSummary::default();
+        };
+
+        let count = self.samples.len();
+        let std_dev = self.std_dev();
+        let (cp, cpk) = std_dev.map_or((None, None), |sigma| capability(mean, sigma, limits));
+
+        SpcSummary {
+            count: count as u64,
+            mean: Some(mean),
+            std_dev,
+            min: self.min.front().map(|(_, min)| *min),
+            max: self.max.front().map(|(_, max)| *max),
+            cp,
+            cpk,
+            out_of_tolerance_percent: self.out_of_tolerance as f64 / count as f64 * 100.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+pub enum WesternElectricRule {
+    /// One sample beyond 3 sigma
+    BeyondThreeSigma,
+    /// Two of three consecutive samples beyond 2 sigma on the same side
+    TwoOfThreeBeyondTwoSigma,
+    /// Four of five consecutive samples beyond 1 sigma on the same side
+    FourOfFiveBeyondOneSigma,
+    /// Eight consecutive samples on the same side of the center line
+    EightOnOneSide,
+}
+
+/// Number of samples that violated each rule
+#[derive(Debug, Clone, Serialize, PartialEq, Eq, Default, JsonSchema)]
+pub struct WesternElectricViolations {
+    pub beyond_three_sigma: u64,
+    pub two_of_three_beyond_two_sigma: u64,
+    pub four_of_five_beyond_one_sigma: u64,
+    pub eight_on_one_side: u64,
+}
+
+impl WesternElectricViolations {
+    const fn count(&mut self, rule: WesternElectricRule) {
+        let counter = match rule {
+            WesternElectricRule::BeyondThreeSigma => &mut self.beyond_three_sigma,
+            WesternElectricRule::TwoOfThreeBeyondTwoSigma => {
+                &mut self.two_of_three_beyond_two_sigma
+            }
+            WesternElectricRule::FourOfFiveBeyondOneSigma => {
+                &mut self.four_of_five_beyond_one_sigma
+            }
+            WesternElectricRule::EightOnOneSide => &mut self.eight_on_one_side,
+        };
+        *counter += 1;
+    }
+}
+
+/// Checks the Western Electric rules on a stream of samples
+#[derive(Debug, Clone, Default)]
+pub struct WesternElectricRules {
+    /// Distance of the last 8 samples from the center line in sigma
+    recent: VecDeque<f64>,
+}
+
+impl WesternElectricRules {
+    /// Adds a sample and returns the rules it violates
+    /// A rule is only reported if the new sample is part of the violating pattern
+    pub fn check(&mut self, value: f64, center: f64, sigma: f64) -> Vec<WesternElectricRule> {
+        if sigma <= 0.0 {
+            return vec![];
+        }
+
+        let z = (value - center) / sigma;
+        if self.recent.len() >= 8 {
+            self.recent.pop_back();
+        }
+        self.recent.push_front(z);
+
+        let side = z.signum();
+        let beyond = |n: usize, limit: f64| {
+            self.recent
+                .iter()
+                .take(n)
+                .filter(|z| z.signum() == side && z.abs() > limit)
+                .count()
+        };
+
+        let mut violations = vec![];
+        if z.abs() > 3.0 {
+            violations.push(WesternElectricRule::BeyondThreeSigma);
+        }
+        if z.abs() > 2.0 && beyond(3, 2.0) >= 2 {
+            violations.push(WesternElectricRule::TwoOfThreeBeyondTwoSigma);
+        }
+        if z.abs() > 1.0 && beyond(5, 1.0) >= 4 {
+            violations.push(WesternElectricRule::FourOfFiveBeyondOneSigma);
+        }
+        if self.recent.len() >= 8 && z != 0.0 && beyond(8, 0.0) == 8 {
+            violations.push(WesternElectricRule::EightOnOneSide);
+        }
+        violations
+    }
+
+    pub fn clear(&mut self) {
+        self.recent.clear();
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Default, JsonSchema)]
+pub struct SpcCharacteristicSummary {
+    /// Statistics of the rolling window
+    pub rolling: SpcSummary,
+    /// Statistics since the last reset, for example of the current spool
+    pub total: SpcSummary,
+    /// Rule violations since the last reset
+    pub violations: WesternElectricViolations,
+    /// Rules violated by the latest sample
+    pub active_violations: Vec<WesternElectricRule>,
+}
+
+/// Rolling and total statistics plus rule checking for one measured characteristic
+#[derive(Debug, Clone)]
+pub struct SpcCharacteristic {
+    limits: SpecificationLimits,
+    /// Center line for the rules, the rolling mean is used if there is no target
+    target: Option<f64>,
+    rolling: RollingSpc,
+    total: SpcAccumulator,
+    rules: WesternElectricRules,
+    violations: WesternElectricViolations,
+    active_violations: Vec<WesternElectricRule>,
+}
+
+impl SpcCharacteristic {
+    pub fn new(rolling_samples: usize) -> Self {
+        Self {
+            limits: SpecificationLimits::default(),
+            target: None,
+            rolling: RollingSpc::new(rolling_samples),
+            total: SpcAccumulator::default(),
+            rules: WesternElectricRules::default(),
+            violations: WesternElectricViolations::default(),
+            active_violations: vec![],
+        }
+    }
+
+    pub const fn set_limits(&mut self, limits: SpecificationLimits, target: Option<f64>) {
+        self.limits = limits;
+        self.target = target;
+    }
+
+    pub fn add(&mut self, value: f64) {
+        let in_tolerance = self.limits.contains(value);
+
+        // sigma and center are estimated before the new sample is added
+        self.active_violations = if self.rolling.len() >= MIN_SAMPLES_FOR_RULES {
+            let rolling = &self.rolling;
+            match (self.target.or_else(|| rolling.mean()), rolling.std_dev()) {
+                (Some(center), Some(sigma)) => self.rules.check(value, center, sigma),
+                _ => vec![],
+            }
+        } else {
+            vec![]
+        };
+        for rule in &self.active_violations {
+            self.violations.count(*rule);
+        }
+
+        self.rolling.add(value, in_tolerance);
+        self.total.add(value, in_tolerance);
+    }
+
+    /// Resets the total statistics and rule violations, the rolling window is kept
+    pub fn reset_total(&mut self) {
+        self.total = SpcAccumulator::default();
+        self.violations = WesternElectricViolations::default();
+        self.active_violations.clear();
+        self.rules.clear();
+    }
+
+    pub fn summary(&self) -> SpcCharacteristicSummary {
+        SpcCharacteristicSummary {
+            rolling: self.rolling.summary(&self.limits),
+            total: self.total.summary(&self.limits),
+            violations: self.violations.clone(),
+            active_violations: self.active_violations.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    const LIMITS: SpecificationLimits = SpecificationLimits {
+        lower: Some(1.70),
+        upper: Some(1.80),
+    };
+
+    #[test]
+    fn test_accumulator_summary() {
+        let mut accumulator = SpcAccumulator::default();
+        for value in [1.74, 1.75, 1.76, 1.75, 1.81] {
+            accumulator.add(value, LIMITS.contains(value));
+        }
+
+        let summary = accumulator.summary(&LIMITS);
+        assert_eq!(summary.count, 5);
+        assert_relative_eq!(summary.mean.unwrap(), 1.762, epsilon = 1e-9);
+        assert_relative_eq!(summary.std_dev.unwrap(), 0.027_748_873, epsilon = 1e-6);
+        assert_eq!(summary.min, Some(1.74));
+        assert_eq!(summary.max, Some(1.81));
+        assert_relative_eq!(summary.out_of_tolerance_percent, 20.0);
+
+        let sigma = summary.std_dev.unwrap();
+        assert_relative_eq!(summary.cp.unwrap(), 0.1 / (6.0 * sigma), epsilon = 1e-9);
+        assert_relative_eq!(
+            summary.cpk.unwrap(),
+            (1.80 - 1.762) / (3.0 * sigma),
+            epsilon = 1e-9
+        );
+    }
+
+    #[test]
+    fn test_capability_one_sided() {
+        let limits = SpecificationLimits {
+            lower: None,
+            upper: Some(0.1),
+        };
+        let (cp, cpk) = capability(0.04, 0.01, &limits);
+        assert_eq!(cp, None);
+        assert_relative_eq!(cpk.unwrap(), 2.0, epsilon = 1e-9);
+        assert_eq!(capability(0.04, 0.0, &limits), (None, None));
+    }
+
+    #[test]
+    fn test_rolling_window() {
+        let mut rolling = RollingSpc::new(3);
+        for value in [1.0, 2.0, 3.0, 4.0] {
+            rolling.add(value, true);
+        }
+        let summary = rolling.summary(&SpecificationLimits::default());
+        assert_eq!(summary.count, 3);
+        assert_eq!(summary.mean, Some(3.0));
+        assert_eq!(summary.min, Some(2.0));
+    }
+
+    #[test]
+    fn test_rolling_window_matches_accumulator() {
+        let mut rolling = RollingSpc::new(50);
+        let values: Vec<f64> = (0..1000)
+            .map(|i| (((i * 37) % 101) as f64).mul_add(0.002, 1.70))
+            .collect();
+
+        for (i, value) in values.iter().enumerate() {
+            rolling.add(*value, LIMITS.contains(*value));
+
+            let mut accumulator = SpcAccumulator::default();
+            for value in &values[(i + 1).saturating_sub(50)..=i] {
+                accumulator.add(*value, LIMITS.contains(*value));
+            }
+            let expected = accumulator.summary(&LIMITS);
+            let summary = rolling.summary(&LIMITS);
+            assert_eq!(summary.count, expected.count);
+            assert_eq!(summary.min, expected.min);
+            assert_eq!(summary.max, expected.max);
+            assert_relative_eq!(
+                summary.out_of_tolerance_percent,
+                expected.out_of_tolerance_percent
+            );
+            assert_relative_eq!(summary.mean.unwrap(), expected.mean.unwrap(), epsilon = 1e-9);
+            if let Some(std_dev) = expected.std_dev {
+                assert_relative_eq!(summary.std_dev.unwrap(), std_dev, epsilon = 1e-9);
+            }
+        }
+
+        rolling.clear();
+        assert_eq!(rolling.summary(&LIMITS), SpcSummary::default());
+    }
+
+    #[test]
+    fn test_western_electric_rules() {
+        let mut rules = WesternElectricRules::default();
+        assert_eq!(
+            rules.check(3.5, 0.0, 1.0),
+            [WesternElectricRule::BeyondThreeSigma]
+        );
+        assert_eq!(
+            rules.check(2.5, 0.0, 1.0),
+            [WesternElectricRule::TwoOfThreeBeyondTwoSigma]
+        );
+        // the other side doesn't count
+        assert!(rules.check(-2.5, 0.0, 1.0).is_empty());
+
+        let mut rules = WesternElectricRules::default();
+        for _ in 0..3 {
+            assert!(rules.check(1.5, 0.0, 1.0).is_empty());
+        }
+        assert_eq!(
+            rules.check(1.5, 0.0, 1.0),
+            [WesternElectricRule::FourOfFiveBeyondOneSigma]
+        );
+
+        let mut rules = WesternElectricRules::default();
+        for _ in 0..7 {
+            assert!(rules.check(0.5, 0.0, 1.0).is_empty());
+        }
+        assert_eq!(
+            rules.check(0.5, 0.0, 1.0),
+            [WesternElectricRule::EightOnOneSide]
+        );
+    }
+
+    #[test]
+    fn test_characteristic_reset() {
+        let mut characteristic = SpcCharacteristic::new(100);
+        characteristic.set_limits(LIMITS, Some(1.75));
+        for i in 0..MIN_SAMPLES_FOR_RULES {
+            characteristic.add(1.75 + if i % 2 == 0 { 0.01 } else { -0.01 });
+        }
+        characteristic.add(1.90);
+
+        let summary = characteristic.summary();
+        assert!(
+            summary
+                .active_violations
+                .contains(&WesternElectricRule::BeyondThreeSigma)
+        );
+        assert_eq!(summary.violations.beyond_three_sigma, 1);
+        assert_eq!(summary.total.count, MIN_SAMPLES_FOR_RULES as u64 + 1);
+
+        characteristic.reset_total();
+        let summary = characteristic.summary();
+        assert_eq!(summary.total, SpcSummary::default());
+        assert_eq!(summary.violations, WesternElectricViolations::default());
+        assert_eq!(summary.rolling.count, MIN_SAMPLES_FOR_RULES as u64 + 1);
+    }
+}
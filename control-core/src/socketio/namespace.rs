@@ -1,12 +1,21 @@
 use crate::socketio::event::GenericEvent;
 use smol::channel::Sender;
 use socketioxide::extract::SocketRef;
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
 use tracing::instrument;
 
 #[derive(Debug, Clone)]
 pub struct Namespace {
     pub sockets: Vec<SocketRef>,
+    /// Subscribers outside of socket.io, like the SSE and WebSocket streams of the REST API
+    pub streams: Vec<EventStream>,
     pub events: HashMap<String, Vec<Arc<GenericEvent>>>,
     pub socket_queue_tx: Sender<(SocketRef, Arc<GenericEvent>)>,
 }
@@ -15,12 +24,59 @@ impl Namespace {
     pub fn new(socket_queue_tx: Sender<(SocketRef, Arc<GenericEvent>)>) -> Self {
         Self {
             sockets: vec![],
+            streams: vec![],
             events: HashMap::new(),
             socket_queue_tx,
         }
     }
 }
 
+/// Subscriber that receives the events of a namespace over a channel
+///
+/// Dropping the receiving end unsubscribes it with the next emitted event.
+#[derive(Debug, Clone)]
+pub struct EventStream {
+    /// Names of the events the stream gets, all if `None`
+    pub event_names: Option<HashSet<String>>,
+    pub sender: Sender<Arc<GenericEvent>>,
+    /// Events dropped because the channel was full
+    ///
+    /// Counted instead of logged, the machines emit from the RT loop. The receiving side reports
+    /// them.
+    pub dropped: Arc<AtomicU64>,
+}
+
+impl EventStream {
+    pub fn new(event_names: Option<HashSet<String>>, sender: Sender<Arc<GenericEvent>>) -> Self {
+        Self {
+            event_names,
+            sender,
+            dropped: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    fn wants(&self, event: &GenericEvent) -> bool {
+        self.event_names
+            .as_ref()
+            .is_none_or(|names| names.contains(&event.name))
+    }
+
+    /// Returns `false` once the receiver is gone
+    fn send(&self, event: &Arc<GenericEvent>) -> bool {
+        if !self.wants(event) {
+            return !self.sender.is_closed();
+        }
+        match self.sender.try_send(event.clone()) {
+            Ok(_) => true,
+            Err(smol::channel::TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            Err(smol::channel::TrySendError::Closed(_)) => false,
+        }
+    }
+}
+
 impl Namespace {
     /// Adds a socket to the namespace.
     ///
@@ -70,6 +126,47 @@ impl Namespace {
     /// * `socket` - A reference to the socket that will receive the cached events
     #[instrument(skip_all)]
     pub fn reemit(&mut self, socket: SocketRef) {
+        for event in self.cached_events() {
+            // Send to global queue instead of per-socket queue
+            self.send_to_queue(&socket, &event, "reemit");
+        }
+    }
+
+    /// Adds a stream to the namespace.
+    ///
+    /// Returns the cached events the stream wants, to bring it up to date before the
+    /// emitted ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The stream to be added
+    #[instrument(skip_all)]
+    pub fn subscribe_stream(&mut self, stream: EventStream) -> Vec<Arc<GenericEvent>> {
+        let cached = self
+            .cached_events()
+            .into_iter()
+            .filter(|event| stream.wants(event))
+            .collect();
+        self.streams.retain(|stream| !stream.sender.is_closed());
+        self.streams.push(stream);
+        cached
+    }
+
+    /// Whether any socket or stream is still subscribed
+    pub fn has_subscribers(&self) -> bool {
+        !self.sockets.is_empty() || self.streams.iter().any(|stream| !stream.sender.is_closed())
+    }
+
+    /// The latest cached event with this name
+    pub fn latest_event(&self, name: &str) -> Option<Arc<GenericEvent>> {
+        self.events
+            .get(name)
+            .and_then(|events| events.last())
+            .cloned()
+    }
+
+    /// Cached events grouped by name, the names with the fewest events first
+    fn cached_events(&self) -> Vec<Arc<GenericEvent>> {
         // Collect events grouped by name/kind with their counts for sorting
         let mut event_groups: Vec<(&String, &Vec<Arc<GenericEvent>>)> =
             self.events.iter().collect();
@@ -77,13 +174,10 @@ impl Namespace {
         // Sort by event count (ascending - lowest count first)
         event_groups.sort_by(|a, b| a.1.len().cmp(&b.1.len()));
 
-        // Emit events in order of lowest count first
-        for (_event_name, events) in event_groups {
-            for event in events {
-                // Send to global queue instead of per-socket queue
-                self.send_to_queue(&socket, event, "reemit");
-            }
-        }
+        event_groups
+            .into_iter()
+            .flat_map(|(_event_name, events)| events.iter().cloned())
+            .collect()
     }
 
     /// Caches an event with a specific key for later retrieval.
@@ -94,7 +188,7 @@ impl Namespace {
     /// * `event` - The event to be cached
     /// * `buffer_fn` - A function that defines how the event should be added to the cache buffer
     #[instrument(skip_all)]
-    fn cache(
+    pub fn cache(
         &mut self,
         event: Arc<GenericEvent>,
         buffer_fn: &Box<dyn Fn(&mut Vec<Arc<GenericEvent>>, &Arc<GenericEvent>)>,
@@ -123,6 +217,7 @@ impl Namespace {
         for socket in self.sockets.clone() {
             self.send_to_queue(&socket, &event, "emit");
         }
+        self.streams.retain(|stream| stream.send(&event));
     }
 
     /// Sends an event to the global queue for a specific socket.
@@ -328,6 +423,45 @@ mod tests {
         assert_eq!(namespace.events.get("test_event").unwrap()[1].ts, 2);
     }
 
+    #[test]
+    fn test_subscribe_stream() {
+        let (queue_tx, _queue_rx) = smol::channel::unbounded();
+        let mut namespace = Namespace::new(queue_tx);
+        let event = |name: &str, ts| {
+            Arc::new(GenericEvent {
+                name: name.to_string(),
+                data: Box::new(TestEventData { value: 0 }),
+                ts,
+            })
+        };
+        let cache_fn = cache_one_event();
+        namespace.emit(event("StateEvent", 0), &cache_fn);
+        namespace.emit(event("LiveValuesEvent", 1), &cache_fn);
+
+        let (sender, receiver) = smol::channel::bounded(1);
+        let stream = EventStream::new(Some(HashSet::from(["StateEvent".to_string()])), sender);
+        let dropped = stream.dropped.clone();
+        let cached = namespace.subscribe_stream(stream);
+        assert_eq!(cached.len(), 1);
+        assert_eq!(cached[0].name, "StateEvent");
+        assert!(namespace.has_subscribers());
+
+        // filtered, then one event per name
+        namespace.emit(event("LiveValuesEvent", 2), &cache_fn);
+        namespace.emit(event("StateEvent", 3), &cache_fn);
+        // full channel, the event is dropped but the stream stays
+        namespace.emit(event("StateEvent", 4), &cache_fn);
+        assert_eq!(receiver.try_recv().unwrap().ts, 3);
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(namespace.streams.len(), 1);
+        assert_eq!(dropped.load(Ordering::Relaxed), 1);
+
+        drop(receiver);
+        assert!(!namespace.has_subscribers());
+        namespace.emit(event("StateEvent", 5), &cache_fn);
+        assert!(namespace.streams.is_empty());
+    }
+
     #[test]
     /// duration: 10 seconds, bucket_size: 1 second
     /// use a for loop that tries to add an event every 100ms
@@ -1,6 +1,6 @@
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crate::{MachineAct, analog_input_test_machine::AnalogInputTestMachine};
+use crate::{MachineAct, ShutdownPolicy, analog_input_test_machine::AnalogInputTestMachine};
 
 impl MachineAct for AnalogInputTestMachine {
     fn act_machine_message(&mut self, msg: crate::MachineMessage) {
@@ -10,9 +10,9 @@ impl MachineAct for AnalogInputTestMachine {
                 self.emit_measurement_rate();
             }
             crate::MachineMessage::UnsubscribeNamespace => self.namespace.namespace = None,
-            crate::MachineMessage::HttpApiJsonRequest(value) => {
+            crate::MachineMessage::HttpApiJsonRequest(value, reply) => {
                 use crate::MachineApi;
-                let _res = self.api_mutate(value);
+                reply.send(self.api_mutate(value));
             }
             crate::MachineMessage::ConnectToMachine(_machine_connection) => {}
             crate::MachineMessage::DisconnectMachine(_machine_connection) => {}
@@ -45,4 +45,9 @@ impl MachineAct for AnalogInputTestMachine {
             self.last_measurement = Instant::now();
         }
     }
+
+    /// Only measures, there is nothing to switch off
+    fn shutdown(&mut self, _now: Instant, _policy: &ShutdownPolicy) -> bool {
+        true
+    }
 }
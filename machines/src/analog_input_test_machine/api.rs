@@ -6,8 +6,10 @@ use control_core::socketio::{
         CacheFn, CacheableEvents, Namespace, NamespaceCacheingLogic, cache_first_and_last_event,
     },
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use crate::api_schema::{MachineApiSchema, MachineSchema};
 use crate::{MachineApi, analog_input_test_machine::AnalogInputTestMachine};
 
 #[derive(Debug, Clone)]
@@ -15,7 +17,7 @@ pub struct AnalogInputTestMachineNamespace {
     pub namespace: Option<Namespace>,
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, JsonSchema)]
 pub enum MeasurementEvent {
     MeasurementRateHz(f64),
     Measurement(f64, String),
@@ -47,11 +49,19 @@ pub enum AnalogInputTestMachineEvents {
     State(Event<MeasurementEvent>),
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 pub struct Mutation {
     measurement_rate_hz: i32,
 }
 
+impl MachineSchema for AnalogInputTestMachine {
+    fn api_schema() -> MachineApiSchema {
+        MachineApiSchema::new::<Mutation>("AnalogInputTestMachine", Self::MACHINE_IDENTIFICATION)
+            .with_event::<MeasurementEvent>("Measurement")
+            .with_event::<MeasurementEvent>("MeasurementRateHz")
+    }
+}
+
 impl MachineApi for AnalogInputTestMachine {
     fn api_get_sender(&self) -> smol::channel::Sender<crate::MachineMessage> {
         self.api_sender.clone()
@@ -0,0 +1,89 @@
+use crate::machine_identification::MachineIdentification;
+use schemars::{JsonSchema, Schema, SchemaGenerator, generate::SchemaSettings};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// JSON Schemas of the mutations and events a machine type exchanges over the API
+///
+/// Units are part of the field descriptions, ranges are only set where the machine enforces them.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct MachineApiSchema {
+    pub name: &'static str,
+    pub machine_identification: MachineIdentification,
+    /// Payload of `/api/v1/machine/mutate`
+    pub mutation: Schema,
+    /// Data of the events on the machine namespace by event name
+    pub events: BTreeMap<&'static str, Schema>,
+}
+
+impl MachineApiSchema {
+    pub fn new<M: JsonSchema>(
+        name: &'static str,
+        machine_identification: MachineIdentification,
+    ) -> Self {
+        Self {
+            name,
+            machine_identification,
+            mutation: SchemaSettings::draft2020_12()
+                .for_deserialize()
+                .into_generator()
+                .into_root_schema_for::<M>(),
+            events: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_event<E: JsonSchema>(mut self, event: &'static str) -> Self {
+        self.events
+            .insert(event, event_generator().into_root_schema_for::<E>());
+        self
+    }
+}
+
+/// Events are only sent by the server, so their schema describes what is serialized
+fn event_generator() -> SchemaGenerator {
+    SchemaSettings::draft2020_12()
+        .for_serialize()
+        .into_generator()
+}
+
+/// A machine type that can describe its API
+pub trait MachineSchema {
+    fn api_schema() -> MachineApiSchema;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::registry::MACHINE_REGISTRY;
+
+    #[test]
+    fn test_registered_schemas() {
+        let schemas = MACHINE_REGISTRY.schemas();
+        let winder = schemas
+            .iter()
+            .find(|schema| schema.name == "Winder2")
+            .unwrap();
+        assert!(winder.events.contains_key("LiveValuesEvent"));
+        assert!(winder.events.contains_key("StateEvent"));
+
+        let mutation = serde_json::to_value(&winder.mutation).unwrap();
+        let variants = mutation["oneOf"].as_array().unwrap();
+        // unit variants are strings, the others objects with one property
+        assert!(variants.iter().any(|variant| {
+            variant["enum"]
+                .as_array()
+                .is_some_and(|names| names.contains(&"GotoTraverseLimitOuter".into()))
+        }));
+        assert!(
+            variants
+                .iter()
+                .any(|variant| variant["required"] == serde_json::json!(["SetMode"]))
+        );
+
+        let power = schemas
+            .iter()
+            .find(|schema| schema.name == "WagoPower")
+            .unwrap();
+        let mutation = serde_json::to_value(&power.mutation).unwrap();
+        assert!(mutation.to_string().contains("\"maximum\":40000.0"));
+    }
+}
@@ -1,5 +1,5 @@
 use super::{AquaPathV1, AquaPathV1Mode};
-use crate::{MachineAct, MachineMessage};
+use crate::{MachineAct, MachineMessage, ShutdownPolicy};
 use std::time::{Duration, Instant};
 
 impl MachineAct for AquaPathV1 {
@@ -28,10 +28,20 @@ impl MachineAct for AquaPathV1 {
 
         if now.duration_since(self.last_measurement_emit) > Duration::from_secs_f64(1.0 / 30.0) {
             self.emit_live_values();
+            self.maybe_emit_autotune_state();
             self.last_measurement_emit = now;
         }
     }
 
+    fn shutdown(&mut self, now: Instant, _policy: &ShutdownPolicy) -> bool {
+        if self.mode != AquaPathV1Mode::Standby {
+            self.set_mode_state(AquaPathV1Mode::Standby);
+        }
+        self.front_controller.update(now);
+        self.back_controller.update(now);
+        true
+    }
+
     fn act_machine_message(&mut self, msg: MachineMessage) {
         match msg {
             MachineMessage::SubscribeNamespace(namespace) => {
@@ -39,10 +49,10 @@ impl MachineAct for AquaPathV1 {
                 self.emit_state();
             }
             MachineMessage::UnsubscribeNamespace => self.namespace.namespace = None,
-            MachineMessage::HttpApiJsonRequest(value) => {
+            MachineMessage::HttpApiJsonRequest(value, reply) => {
                 use crate::MachineApi;
 
-                let _res = self.api_mutate(value);
+                reply.send(self.api_mutate(value));
             }
             MachineMessage::ConnectToMachine(_machine_connection) =>
             /*Doesnt connect to any Machine so do nothing*/
@@ -1,18 +1,22 @@
 use super::{AquaPathV1, AquaPathV1Mode};
+use crate::api_schema::{MachineApiSchema, MachineSchema};
+use crate::extruder1::api::PidSettings;
 use crate::{MachineApi, MachineMessage};
+use control_core::controllers::pid_autotune::{AutotuneParameters, AutotuneStatus};
 use control_core::socketio::{
     event::{Event, GenericEvent},
     namespace::{
         CacheFn, CacheableEvents, Namespace, NamespaceCacheingLogic, cache_first_and_last_event,
     },
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use smol::channel::Sender;
 use std::sync::Arc;
 use tracing::instrument;
 
-#[derive(Serialize, Debug, Clone, Default)]
+#[derive(Serialize, Debug, Clone, Default, JsonSchema)]
 pub struct LiveValuesEvent {
     pub front_flow: f64,
     pub back_flow: f64,
@@ -28,13 +32,15 @@ impl LiveValuesEvent {
     }
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, JsonSchema)]
 pub struct StateEvent {
     pub is_default_state: bool,
     /// mode state
     pub mode_state: ModeState,
     pub flow_states: FlowStates,
     pub temperature_states: TempStates,
+    pub pid_states: PidStates,
+    pub autotune_states: AutotuneStates,
 }
 
 impl StateEvent {
@@ -43,28 +49,40 @@ impl StateEvent {
     }
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, JsonSchema)]
 pub struct TempStates {
     pub front: TempState,
     pub back: TempState,
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, JsonSchema)]
 pub struct TempState {
     pub temperature: f64,
     pub target_temperature: f64,
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, JsonSchema)]
+pub struct PidStates {
+    pub front: PidSettings,
+    pub back: PidSettings,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq, JsonSchema)]
+pub struct AutotuneStates {
+    pub front: Option<AutotuneStatus>,
+    pub back: Option<AutotuneStatus>,
+}
+
+#[derive(Serialize, Debug, Clone, JsonSchema)]
 pub struct ModeState {
     pub mode: AquaPathV1Mode,
 }
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, JsonSchema)]
 pub struct FlowStates {
     pub front: FlowState,
     pub back: FlowState,
 }
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, JsonSchema)]
 pub struct FlowState {
     pub flow: f64,
     pub should_flow: bool,
@@ -75,7 +93,7 @@ pub enum AquaPathV1Events {
     State(Event<StateEvent>),
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 enum Mutation {
     //Mode
     SetAquaPathMode(AquaPathV1Mode),
@@ -85,6 +103,11 @@ enum Mutation {
 
     SetFrontFlow(bool),
     SetBackFlow(bool),
+
+    StartFrontAutotune(AutotuneParameters),
+    StartBackAutotune(AutotuneParameters),
+    StopFrontAutotune,
+    StopBackAutotune,
 }
 
 #[derive(Debug, Clone)]
@@ -122,6 +145,14 @@ impl CacheableEvents<AquaPathV1Events> for AquaPathV1Events {
     }
 }
 
+impl MachineSchema for AquaPathV1 {
+    fn api_schema() -> MachineApiSchema {
+        MachineApiSchema::new::<Mutation>("AquaPathV1", Self::MACHINE_IDENTIFICATION)
+            .with_event::<LiveValuesEvent>("LiveValuesEvent")
+            .with_event::<StateEvent>("StateEvent")
+    }
+}
+
 impl MachineApi for AquaPathV1 {
     fn api_get_sender(&self) -> Sender<MachineMessage> {
         self.api_sender.clone()
@@ -145,6 +176,14 @@ impl MachineApi for AquaPathV1 {
             Mutation::SetFrontFlow(should_pump) => {
                 self.set_should_pump(should_pump, super::AquaPathSideType::Front)
             }
+            Mutation::StartFrontAutotune(parameters) => {
+                self.start_autotune(parameters, super::AquaPathSideType::Front)?
+            }
+            Mutation::StartBackAutotune(parameters) => {
+                self.start_autotune(parameters, super::AquaPathSideType::Back)?
+            }
+            Mutation::StopFrontAutotune => self.stop_autotune(super::AquaPathSideType::Front),
+            Mutation::StopBackAutotune => self.stop_autotune(super::AquaPathSideType::Back),
         }
         Ok(())
     }
@@ -1,6 +1,8 @@
 use crate::aquapath1::VolumeRate;
 use crate::aquapath1::{Flow, Temperature};
-use control_core::controllers::pid::PidController;
+use crate::extruder1::api::PidSettings;
+use control_core::controllers::pid::{PidController, PidParameters};
+use control_core::controllers::pid_autotune::{AutotuneParameters, AutotuneStatus, RelayAutotuner};
 use ethercat_hal::io::encoder_input::EncoderInput;
 use ethercat_hal::io::{
     analog_output::AnalogOutput, digital_output::DigitalOutput, temperature_input::TemperatureInput,
@@ -41,6 +43,11 @@ pub struct Controller {
     pub pump_allowed: bool,
     pub current_flow: VolumeRate,
     pub max_flow: VolumeRate,
+
+    /// Replaces the PID while running, positive outputs heat and negative outputs cool
+    autotune: Option<RelayAutotuner>,
+    /// Status of the last autotune
+    autotune_status: Option<AutotuneStatus>,
 }
 
 impl Controller {
@@ -62,7 +69,10 @@ impl Controller {
         flow_sensor: EncoderInput,
     ) -> Self {
         Self {
-            pid: PidController::new(kp, ki, kd),
+            // heating duty cycle when positive, cooling when negative
+            pid: PidController::with_parameters(
+                PidParameters::new(kp, ki, kd).with_output_limits(Some(-1.0), Some(1.0)),
+            ),
             temperature_pid_output: 0.0,
             window_start: Instant::now(),
             pwm_period: pwm_duration,
@@ -88,9 +98,59 @@ impl Controller {
             current_flow: VolumeRate::new::<liter_per_minute>(0.0),
             pump_allowed: false,
             max_flow: VolumeRate::new::<liter_per_minute>(10.0),
+            autotune: None,
+            autotune_status: None,
+        }
+    }
+
+    /// Oscillates the water temperature around the setpoint by heating and cooling
+    pub fn start_autotune(
+        &mut self,
+        mut parameters: AutotuneParameters,
+        now: Instant,
+    ) -> Result<(), anyhow::Error> {
+        parameters.validate()?;
+        // the relay cools as well, so the water must stay within both limits
+        let upper = self.max_temperature.get::<degree_celsius>() - parameters.setpoint;
+        let lower = parameters.setpoint - self.min_temperature.get::<degree_celsius>();
+        if upper <= 0.0 || lower <= 0.0 {
+            anyhow::bail!(
+                "Autotune setpoint {} is not between the minimum and maximum temperature",
+                parameters.setpoint
+            );
+        }
+        parameters.max_overshoot = parameters.max_overshoot.min(upper);
+        parameters.max_undershoot = Some(
+            parameters
+                .max_undershoot
+                .unwrap_or(parameters.max_overshoot)
+                .min(lower),
+        );
+        let autotune = RelayAutotuner::new(parameters, 1.0, -1.0, now);
+        self.autotune_status = Some(autotune.status().clone());
+        self.autotune = Some(autotune);
+        Ok(())
+    }
+
+    pub fn get_pid_settings(&self) -> PidSettings {
+        PidSettings {
+            ki: self.pid.get_ki(),
+            kp: self.pid.get_kp(),
+            kd: self.pid.get_kd(),
         }
     }
 
+    pub fn stop_autotune(&mut self) {
+        if let Some(mut autotune) = self.autotune.take() {
+            autotune.fail("Stopped");
+            self.autotune_status = Some(autotune.status().clone());
+        }
+    }
+
+    pub const fn get_autotune_status(&self) -> Option<&AutotuneStatus> {
+        self.autotune_status.as_ref()
+    }
+
     pub fn turn_pump_off(&mut self) {
         self.flow.pump = false;
         self.pump_relais.set(false);
@@ -166,6 +226,7 @@ impl Controller {
 
     pub fn disallow_heating(&mut self) {
         self.heating_allowed = false;
+        self.stop_autotune();
     }
 
     pub fn allow_heating(&mut self) {
@@ -247,26 +308,50 @@ impl Controller {
             self.turn_heating_off();
         }
 
-        // Calculate PID error once
-        let error = self.target_temperature.get::<degree_celsius>()
-            - self.current_temperature.get::<degree_celsius>();
-        let control = self.pid.update(error, now);
-        self.temperature_pid_output = control;
-
         let elapsed = now.duration_since(self.window_start);
         if elapsed >= self.pwm_period {
             self.window_start = now;
         }
 
+        if let Some(autotune) = &mut self.autotune {
+            let output = autotune.update(self.current_temperature.get::<degree_celsius>(), now);
+            self.autotune_status = Some(autotune.status().clone());
+            if let AutotuneStatus::Finished(result) = autotune.status() {
+                tracing::info!("AquaPath autotune finished {:?}", result);
+                self.pid.set_gains(result.kp, result.ki, result.kd);
+            }
+            if !autotune.is_running() {
+                self.autotune = None;
+            }
+            self.apply_output(output, elapsed, current_flow);
+            return;
+        }
+
+        // Calculate PID error once
+        let error = self.target_temperature.get::<degree_celsius>()
+            - self.current_temperature.get::<degree_celsius>();
+        let control = self.pid.compute(
+            self.target_temperature.get::<degree_celsius>(),
+            self.current_temperature.get::<degree_celsius>(),
+            now,
+        );
+        self.temperature_pid_output = control;
+
         // Decide whether to heat or cool based on error
-        if error > 0.0 {
+        let output = if error > 0.0 { control.max(0.0) } else { -1.0 };
+        self.apply_output(output, elapsed, current_flow);
+    }
+
+    /// Heats with the output as duty cycle when positive, cools when negative
+    fn apply_output(&mut self, output: f64, elapsed: Duration, current_flow: VolumeRate) {
+        if output > 0.0 {
             // Need heating (current < target)
             if self.temperature.cooling {
                 self.turn_cooling_off();
             }
             if self.heating_allowed && current_flow > VolumeRate::new::<liter_per_minute>(0.0) {
                 // Only start heating if pump is on
-                let duty = control.clamp(0.0, 1.0);
+                let duty = output.clamp(0.0, 1.0);
                 let on_time = self.pwm_period.mul_f64(duty);
                 let on = elapsed < on_time;
                 if on && !self.temperature.heating {
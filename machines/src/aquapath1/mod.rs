@@ -1,4 +1,6 @@
+use control_core::controllers::pid_autotune::AutotuneParameters;
 use control_core::socketio::namespace::NamespaceCacheingLogic;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::time::Instant;
 use units::f64::*;
@@ -9,8 +11,8 @@ use crate::{
     MACHINE_AQUAPATH_V1, VENDOR_QITECH,
     aquapath1::{
         api::{
-            AquaPathV1Events, AquaPathV1Namespace, FlowState, FlowStates, LiveValuesEvent,
-            ModeState, StateEvent, TempState, TempStates,
+            AquaPathV1Events, AquaPathV1Namespace, AutotuneStates, FlowState, FlowStates,
+            LiveValuesEvent, ModeState, PidStates, StateEvent, TempState, TempStates,
         },
         controller::Controller,
     },
@@ -25,7 +27,7 @@ pub mod api;
 pub mod controller;
 pub mod new;
 
-#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, JsonSchema)]
 pub enum AquaPathV1Mode {
     Standby,
     Auto,
@@ -92,6 +94,7 @@ pub struct AquaPathV1 {
     last_measurement_emit: Instant,
     front_controller: Controller,
     back_controller: Controller,
+    last_autotune_states: AutotuneStates,
     main_sender: Option<Sender<AsyncThreadMessage>>,
 }
 
@@ -167,11 +170,32 @@ impl AquaPathV1 {
                     should_flow: self.back_controller.should_pump,
                 },
             },
+            pid_states: PidStates {
+                front: self.front_controller.get_pid_settings(),
+                back: self.back_controller.get_pid_settings(),
+            },
+            autotune_states: self.build_autotune_states(),
         };
 
         let event = state.build();
         self.namespace.emit(AquaPathV1Events::State(event));
     }
+
+    fn build_autotune_states(&self) -> AutotuneStates {
+        AutotuneStates {
+            front: self.front_controller.get_autotune_status().cloned(),
+            back: self.back_controller.get_autotune_status().cloned(),
+        }
+    }
+
+    /// Emits the state when an autotune progressed, finished or failed
+    pub fn maybe_emit_autotune_state(&mut self) {
+        let autotune_states = self.build_autotune_states();
+        if autotune_states != self.last_autotune_states {
+            self.last_autotune_states = autotune_states;
+            self.emit_state();
+        }
+    }
 }
 impl AquaPathV1 {
     fn turn_cooling_off(&mut self) {
@@ -269,3 +293,30 @@ impl AquaPathV1 {
         self.emit_state();
     }
 }
+
+impl AquaPathV1 {
+    fn start_autotune(
+        &mut self,
+        parameters: AutotuneParameters,
+        side: AquaPathSideType,
+    ) -> Result<(), anyhow::Error> {
+        if self.mode != AquaPathV1Mode::Auto {
+            anyhow::bail!("Autotune is only possible in auto mode");
+        }
+        let controller = match side {
+            AquaPathSideType::Back => &mut self.back_controller,
+            AquaPathSideType::Front => &mut self.front_controller,
+        };
+        controller.start_autotune(parameters, Instant::now())?;
+        self.emit_state();
+        Ok(())
+    }
+
+    fn stop_autotune(&mut self, side: AquaPathSideType) {
+        match side {
+            AquaPathSideType::Back => self.back_controller.stop_autotune(),
+            AquaPathSideType::Front => self.front_controller.stop_autotune(),
+        }
+        self.emit_state();
+    }
+}
@@ -173,6 +173,7 @@ impl MachineNewTrait for AquaPathV1 {
                 last_measurement_emit: Instant::now(),
                 front_controller,
                 back_controller,
+                last_autotune_states: Default::default(),
             };
             water_cooling.emit_state();
 
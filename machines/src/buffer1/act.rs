@@ -1,5 +1,5 @@
-use super::BufferV1;
-use crate::{MachineAct, MachineMessage};
+use super::{BufferV1, BufferV1Mode};
+use crate::{MachineAct, MachineMessage, ShutdownPolicy};
 use std::time::{Duration, Instant};
 
 impl MachineAct for BufferV1 {
@@ -20,6 +20,13 @@ impl MachineAct for BufferV1 {
         }
     }
 
+    fn shutdown(&mut self, _now: Instant, _policy: &ShutdownPolicy) -> bool {
+        if self.mode != BufferV1Mode::Standby {
+            self.set_mode_state(BufferV1Mode::Standby);
+        }
+        true
+    }
+
     fn act_machine_message(&mut self, msg: MachineMessage) {
         match msg {
             MachineMessage::SubscribeNamespace(namespace) => {
@@ -27,10 +34,10 @@ impl MachineAct for BufferV1 {
                 self.emit_state();
             }
             MachineMessage::UnsubscribeNamespace => self.namespace.namespace = None,
-            MachineMessage::HttpApiJsonRequest(value) => {
+            MachineMessage::HttpApiJsonRequest(value, reply) => {
                 use crate::MachineApi;
 
-                let _res = self.api_mutate(value);
+                reply.send(self.api_mutate(value));
             }
             MachineMessage::ConnectToMachine(_machine_connection) =>
             /*Doesnt connect to any Machine so do nothing*/
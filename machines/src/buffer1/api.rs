@@ -1,16 +1,18 @@
 use super::{BufferV1, BufferV1Mode};
+use crate::api_schema::{MachineApiSchema, MachineSchema};
 use crate::{MachineApi, MachineMessage, machine_identification::MachineIdentificationUnique};
 use control_core::socketio::{
     event::{Event, GenericEvent},
     namespace::{CacheFn, CacheableEvents, Namespace, NamespaceCacheingLogic, cache_one_event},
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use smol::channel::Sender;
 use std::sync::Arc;
 use tracing::instrument;
 
-#[derive(Serialize, Debug, Clone, Default)]
+#[derive(Serialize, Debug, Clone, Default, JsonSchema)]
 pub struct LiveValuesEvent {}
 
 impl LiveValuesEvent {
@@ -19,7 +21,7 @@ impl LiveValuesEvent {
     }
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, JsonSchema)]
 pub struct StateEvent {
     /// mode state
     pub mode_state: ModeState, // connected machine state
@@ -36,7 +38,7 @@ pub enum BufferV1Events {
     State(Event<StateEvent>),
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, JsonSchema)]
 pub struct ModeState {
     pub mode: BufferV1Mode,
 }
@@ -55,7 +57,7 @@ pub struct ConnectedMachineState {
     pub is_available: bool,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 enum Mutation {
     // Mode
     SetBufferMode(BufferV1Mode),
@@ -103,6 +105,14 @@ impl CacheableEvents<Self> for BufferV1Events {
     }
 }
 
+impl MachineSchema for BufferV1 {
+    fn api_schema() -> MachineApiSchema {
+        MachineApiSchema::new::<Mutation>("BufferV1", Self::MACHINE_IDENTIFICATION)
+            .with_event::<LiveValuesEvent>("LiveValuesEvent")
+            .with_event::<StateEvent>("StateEvent")
+    }
+}
+
 impl MachineApi for BufferV1 {
     fn api_get_sender(&self) -> Sender<MachineMessage> {
         self.api_sender.clone()
@@ -10,6 +10,7 @@ use crate::{MACHINE_BUFFER_V1, VENDOR_QITECH};
 use api::{Buffer1Namespace, BufferV1Events, LiveValuesEvent, ModeState, StateEvent};
 use buffer_tower_controller::BufferTowerController;
 use control_core::socketio::namespace::NamespaceCacheingLogic;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use smol::channel::{Receiver, Sender};
 use std::time::Instant;
@@ -125,7 +126,7 @@ impl BufferV1 {
     }
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
 pub enum BufferV1Mode {
     Standby,
     FillingBuffer,
@@ -1,7 +1,7 @@
 #[cfg(not(feature = "mock-machine"))]
 use crate::extruder1::ExtruderV2;
 #[cfg(not(feature = "mock-machine"))]
-use crate::{MachineAct, MachineMessage};
+use crate::{MachineAct, MachineMessage, ShutdownPolicy};
 #[cfg(not(feature = "mock-machine"))]
 use std::time::{Duration, Instant};
 
@@ -26,6 +26,7 @@ impl MachineAct for ExtruderV2 {
         } else {
             self.screw_speed_controller.update(now, false);
         }
+        self.send_finished_inverter_backup();
 
         if self.mode == super::ExtruderV2Mode::Standby {
             self.turn_heating_off();
@@ -48,6 +49,15 @@ impl MachineAct for ExtruderV2 {
         }
     }
 
+    fn shutdown(&mut self, now: Instant, _policy: &ShutdownPolicy) -> bool {
+        if self.mode != super::ExtruderV2Mode::Standby {
+            self.set_mode_state(super::ExtruderV2Mode::Standby);
+        }
+        self.turn_heating_off();
+        self.screw_speed_controller.update(now, false);
+        self.screw_speed_controller.is_motor_stopped()
+    }
+
     fn act_machine_message(&mut self, msg: MachineMessage) {
         match msg {
             MachineMessage::SubscribeNamespace(namespace) => {
@@ -56,10 +66,10 @@ impl MachineAct for ExtruderV2 {
                 tracing::info!("extruder1 received subscribe");
             }
             MachineMessage::UnsubscribeNamespace => self.namespace.namespace = None,
-            MachineMessage::HttpApiJsonRequest(value) => {
+            MachineMessage::HttpApiJsonRequest(value, reply) => {
                 use crate::MachineApi;
 
-                let _res = self.api_mutate(value);
+                reply.send(self.api_mutate(value));
             }
             MachineMessage::ConnectToMachine(_machine_connection) => (),
             MachineMessage::DisconnectMachine(_machine_connection) =>
@@ -1,4 +1,8 @@
-use super::{ExtruderV2Mode, mitsubishi_cs80::MotorStatus};
+use super::ExtruderV2Mode;
+use crate::frequency_inverter::{
+    InverterFault, InverterStatus, MotorStatus,
+    parameters::{InverterParameter, ParameterBackup, ParameterClear, ParameterTransfer},
+};
 
 #[cfg(not(feature = "mock-machine"))]
 use super::ExtruderV2;
@@ -8,6 +12,8 @@ use crate::{MachineMessage, extruder1::HeatingType};
 
 #[cfg(not(feature = "mock-machine"))]
 use crate::MachineApi;
+#[cfg(not(feature = "mock-machine"))]
+use crate::api_schema::{MachineApiSchema, MachineSchema};
 use control_core::socketio::{
     event::{Event, GenericEvent},
     namespace::{
@@ -15,11 +21,13 @@ use control_core::socketio::{
     },
 };
 use control_core_derive::BuildEvent;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 #[cfg(not(feature = "mock-machine"))]
 use serde_json::Value;
 #[cfg(not(feature = "mock-machine"))]
 use smol::channel::Sender;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use tracing::instrument;
 use units::angular_velocity::revolution_per_minute;
@@ -27,7 +35,7 @@ use units::electric_current::ampere;
 use units::electric_potential::volt;
 use units::frequency::hertz;
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, JsonSchema)]
 pub struct MotorStatusValues {
     pub screw_rpm: f64, // rpm of motor
     pub frequency: f64, // frequency of motor
@@ -51,7 +59,7 @@ impl From<MotorStatus> for MotorStatusValues {
     }
 }
 
-#[derive(Serialize, Debug, Clone, Default)]
+#[derive(Serialize, Debug, Clone, Default, JsonSchema)]
 pub struct LiveValuesEvent {
     /// screw rpm
     pub motor_status: MotorStatusValues,
@@ -85,7 +93,7 @@ impl LiveValuesEvent {
     }
 }
 
-#[derive(Serialize, Debug, Clone, PartialEq, BuildEvent)]
+#[derive(Serialize, Debug, Clone, PartialEq, BuildEvent, JsonSchema)]
 pub struct StateEvent {
     pub is_default_state: bool,
     /// rotation state
@@ -104,37 +112,39 @@ pub struct StateEvent {
     pub extruder_settings_state: ExtruderSettingsState,
     /// inverter status state
     pub inverter_status_state: InverterStatusState,
+    /// inverter parameters and fault history
+    pub inverter_parameters_state: InverterParametersState,
     /// pid settings
     pub pid_settings: PidSettingsStates,
 }
 
-#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
 pub struct RotationState {
     pub forward: bool,
 }
 
-#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
 pub struct ModeState {
     pub mode: ExtruderV2Mode,
 }
 
-#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
 pub struct RegulationState {
     pub uses_rpm: bool,
 }
 
-#[derive(Serialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct PressureState {
     pub target_bar: f64,
     pub wiring_error: bool,
 }
 
-#[derive(Serialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct ScrewState {
     pub target_rpm: f64,
 }
 
-#[derive(Serialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct HeatingStates {
     pub nozzle: HeatingState,
     pub front: HeatingState,
@@ -142,19 +152,19 @@ pub struct HeatingStates {
     pub middle: HeatingState,
 }
 
-#[derive(Serialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct HeatingState {
     pub target_temperature: f64,
     pub wiring_error: bool,
 }
 
-#[derive(Serialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct ExtruderSettingsState {
     pub pressure_limit: f64,
     pub pressure_limit_enabled: bool,
 }
 
-#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
 pub struct InverterStatusState {
     /// RUN (Inverter running)
     pub running: bool,
@@ -174,16 +184,45 @@ pub struct InverterStatusState {
     pub abc_fault: bool,
     /// is True when a fault occured
     pub fault_occurence: bool,
+    /// the active fault as reported by the inverter
+    pub fault: Option<InverterFault>,
+}
+
+impl InverterStatusState {
+    pub fn new(status: InverterStatus, fault: Option<InverterFault>) -> Self {
+        Self {
+            running: status.running,
+            forward_running: status.forward_running,
+            reverse_running: status.reverse_running,
+            up_to_frequency: status.up_to_frequency,
+            overload_warning: status.overload_warning,
+            no_function: status.no_function,
+            output_frequency_detection: status.output_frequency_detection,
+            abc_fault: status.abc_fault,
+            fault_occurence: status.fault_occurence,
+            fault,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Default, JsonSchema)]
+pub struct InverterParametersState {
+    /// parameters read from or written to the inverter, Pr. number -> raw value
+    pub parameters: BTreeMap<u16, u16>,
+    /// faults stored in the inverter, latest first
+    pub fault_history: Vec<InverterFault>,
+    /// progress of the running or last parameter backup/restore
+    pub transfer: Option<ParameterTransfer>,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct PidSettings {
     pub ki: f64,
     pub kp: f64,
     pub kd: f64,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct TemperaturePidStates {
     pub front: TemperaturePid,
     pub middle: TemperaturePid,
@@ -191,7 +230,7 @@ pub struct TemperaturePidStates {
     pub nozzle: TemperaturePid,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct TemperaturePid {
     pub ki: f64,
     pub kp: f64,
@@ -199,7 +238,7 @@ pub struct TemperaturePid {
     pub zone: String,
 }
 
-#[derive(Serialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq, JsonSchema)]
 pub struct PidSettingsStates {
     pub temperature: TemperaturePidStates,
     pub pressure: PidSettings,
@@ -210,7 +249,7 @@ pub enum ExtruderV2Events {
     State(Event<StateEvent>),
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 pub enum Mutation {
     /// INVERTER
     /// Frequency Control
@@ -237,6 +276,16 @@ pub enum Mutation {
 
     // Reset
     ResetInverter(bool),
+
+    // Inverter Parameters
+    ReadInverterParameter(u16),
+    WriteInverterParameter(InverterParameter),
+    ClearInverterParameters(ParameterClear),
+    /// Name the backup is stored under by the server
+    BackupInverterParameters(String),
+    /// Backups are loaded by the server, see `/api/v1/inverter_backups`
+    RestoreInverterParameters(ParameterBackup),
+    ReadInverterFaultHistory(bool),
 }
 
 #[derive(Debug)]
@@ -274,6 +323,15 @@ impl CacheableEvents<Self> for ExtruderV2Events {
     }
 }
 
+#[cfg(not(feature = "mock-machine"))]
+impl MachineSchema for ExtruderV2 {
+    fn api_schema() -> MachineApiSchema {
+        MachineApiSchema::new::<Mutation>("ExtruderV2", Self::MACHINE_IDENTIFICATION)
+            .with_event::<LiveValuesEvent>("LiveValuesEvent")
+            .with_event::<StateEvent>("StateEvent")
+    }
+}
+
 #[cfg(not(feature = "mock-machine"))]
 impl MachineApi for ExtruderV2 {
     fn api_get_sender(&self) -> Sender<MachineMessage> {
@@ -290,6 +348,16 @@ impl MachineApi for ExtruderV2 {
             Mutation::SetInverterTargetPressure(bar) => self.set_target_pressure(bar),
             Mutation::SetInverterTargetRpm(rpm) => self.set_target_rpm(rpm),
             Mutation::ResetInverter(_) => self.reset_inverter(),
+            Mutation::ReadInverterParameter(number) => self.read_inverter_parameter(number)?,
+            Mutation::WriteInverterParameter(parameter) => {
+                self.write_inverter_parameter(parameter)?
+            }
+            Mutation::ClearInverterParameters(clear) => self.clear_inverter_parameters(clear)?,
+            Mutation::BackupInverterParameters(name) => self.backup_inverter_parameters(&name)?,
+            Mutation::RestoreInverterParameters(backup) => {
+                self.restore_inverter_parameters(backup)?
+            }
+            Mutation::ReadInverterFaultHistory(_) => self.read_inverter_fault_history(),
 
             Mutation::SetFrontHeatingTargetTemperature(temp) => {
                 self.set_target_temperature(temp, HeatingType::Front)
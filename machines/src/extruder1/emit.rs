@@ -3,18 +3,22 @@
 use crate::extruder1::{
     ExtruderV2, ExtruderV2Mode, HeatingType,
     api::{
-        ExtruderSettingsState, ExtruderV2Events, HeatingState, HeatingStates, InverterStatusState,
-        LiveValuesEvent, ModeState, PidSettings, PidSettingsStates, PressureState, RegulationState,
-        RotationState, ScrewState, StateEvent, TemperaturePid,
+        ExtruderSettingsState, ExtruderV2Events, HeatingState, HeatingStates,
+        InverterParametersState, InverterStatusState, LiveValuesEvent, ModeState, PidSettings,
+        PidSettingsStates, PressureState, RegulationState, RotationState, ScrewState, StateEvent,
+        TemperaturePid,
     },
 };
 #[cfg(not(feature = "mock-machine"))]
-use control_core::helpers::hasher_serializer::hash_with_serde_model;
+use crate::AsyncThreadMessage;
+#[cfg(not(feature = "mock-machine"))]
+use crate::frequency_inverter::parameters::{InverterParameter, ParameterBackup, ParameterClear};
 #[cfg(not(feature = "mock-machine"))]
 use control_core::socketio::event::BuildEvent;
 #[cfg(not(feature = "mock-machine"))]
 use control_core::socketio::namespace::NamespaceCacheingLogic;
 #[cfg(not(feature = "mock-machine"))]
+#[cfg(not(feature = "mock-machine"))]
 use units::angular_velocity::AngularVelocity;
 #[cfg(not(feature = "mock-machine"))]
 use units::pressure::{Pressure, bar};
@@ -95,16 +99,16 @@ impl ExtruderV2 {
                     .screw_speed_controller
                     .get_nozzle_pressure_limit_enabled(),
             },
-            inverter_status_state: InverterStatusState {
-                running: self.screw_speed_controller.inverter.status.running,
-                forward_running: self.screw_speed_controller.inverter.status.forward_running,
-                reverse_running: self.screw_speed_controller.inverter.status.reverse_running,
-                up_to_frequency: self.screw_speed_controller.inverter.status.su,
-                overload_warning: self.screw_speed_controller.inverter.status.ol,
-                no_function: self.screw_speed_controller.inverter.status.no_function,
-                output_frequency_detection: self.screw_speed_controller.inverter.status.fu,
-                abc_fault: self.screw_speed_controller.inverter.status.abc_,
-                fault_occurence: self.screw_speed_controller.inverter.status.fault_occurence,
+            inverter_status_state: InverterStatusState::new(
+                self.screw_speed_controller.get_inverter_status(),
+                self.screw_speed_controller.get_inverter_fault(),
+            ),
+            inverter_parameters_state: InverterParametersState {
+                parameters: self.screw_speed_controller.get_inverter_parameters(),
+                fault_history: self.screw_speed_controller.get_inverter_fault_history(),
+                transfer: self
+                    .screw_speed_controller
+                    .get_inverter_parameter_transfer(),
             },
             pid_settings: PidSettingsStates {
                 temperature: TemperaturePidStates {
@@ -147,7 +151,7 @@ impl ExtruderV2 {
 impl ExtruderV2 {
     pub fn emit_state(&mut self) {
         let state = self.build_state_event();
-        let hash = hash_with_serde_model(self.screw_speed_controller.get_inverter_status());
+        let hash = self.screw_speed_controller.get_inverter_status_hash();
         self.last_status_hash = Some(hash);
         let event = state.build();
         self.namespace.emit(ExtruderV2Events::State(event));
@@ -161,8 +165,7 @@ impl ExtruderV2 {
                 return;
             }
         };
-        let status = self.screw_speed_controller.get_inverter_status();
-        let new_status_hash = hash_with_serde_model(status);
+        let new_status_hash = self.screw_speed_controller.get_inverter_status_hash();
         if new_status_hash != old_status_hash {
             self.emit_state();
         }
@@ -281,6 +284,75 @@ impl ExtruderV2 {
         self.emit_state();
     }
 
+    pub fn read_inverter_parameter(&mut self, number: u16) -> Result<(), anyhow::Error> {
+        self.screw_speed_controller
+            .read_inverter_parameter(number)?;
+        self.emit_state();
+        Ok(())
+    }
+
+    pub fn write_inverter_parameter(
+        &mut self,
+        parameter: InverterParameter,
+    ) -> Result<(), anyhow::Error> {
+        self.screw_speed_controller
+            .write_inverter_parameter(parameter.number, parameter.value)?;
+        self.emit_state();
+        Ok(())
+    }
+
+    pub fn clear_inverter_parameters(
+        &mut self,
+        clear: ParameterClear,
+    ) -> Result<(), anyhow::Error> {
+        self.screw_speed_controller
+            .clear_inverter_parameters(clear)?;
+        self.emit_state();
+        Ok(())
+    }
+
+    pub fn backup_inverter_parameters(&mut self, name: &str) -> Result<(), anyhow::Error> {
+        self.screw_speed_controller
+            .backup_inverter_parameters(name)?;
+        self.emit_state();
+        Ok(())
+    }
+
+    pub fn restore_inverter_parameters(
+        &mut self,
+        backup: ParameterBackup,
+    ) -> Result<(), anyhow::Error> {
+        self.screw_speed_controller
+            .restore_inverter_parameters(backup)?;
+        self.emit_state();
+        Ok(())
+    }
+
+    /// Hands a finished inverter backup to the main thread, which stores it
+    pub fn send_finished_inverter_backup(&mut self) {
+        let Some(backup) = self.screw_speed_controller.take_finished_inverter_backup() else {
+            return;
+        };
+        let message = AsyncThreadMessage::InverterBackupFinished(
+            self.machine_identification_unique.clone(),
+            Box::new(backup),
+        );
+        match &self.main_sender {
+            Some(sender) => {
+                if let Err(e) = sender.try_send(message) {
+                    tracing::error!("Inverter backup lost: {}", e);
+                }
+            }
+            None => tracing::error!("Inverter backup lost, no main thread channel"),
+        }
+        self.emit_state();
+    }
+
+    pub fn read_inverter_fault_history(&mut self) {
+        self.screw_speed_controller.read_inverter_fault_history();
+        self.emit_state();
+    }
+
     pub fn set_target_temperature(&mut self, target_temperature: f64, heating_type: HeatingType) {
         let target_temp = ThermodynamicTemperature::new::<degree_celsius>(target_temperature);
 
@@ -304,37 +376,37 @@ impl ExtruderV2 {
     pub fn configure_pressure_pid(&mut self, settings: PidSettings) {
         self.screw_speed_controller
             .pid
-            .configure(settings.ki, settings.kp, settings.kd);
+            .set_gains(settings.kp, settings.ki, settings.kd);
         self.emit_state();
     }
 
     pub fn configure_temperature_pid(&mut self, settings: TemperaturePid) {
         match settings.zone.as_str() {
             "front" => {
-                self.temperature_controller_front.pid.configure(
-                    settings.ki,
+                self.temperature_controller_front.pid.set_gains(
                     settings.kp,
+                    settings.ki,
                     settings.kd,
                 );
             }
             "middle" => {
-                self.temperature_controller_middle.pid.configure(
-                    settings.ki,
+                self.temperature_controller_middle.pid.set_gains(
                     settings.kp,
+                    settings.ki,
                     settings.kd,
                 );
             }
             "back" => {
-                self.temperature_controller_back.pid.configure(
-                    settings.ki,
+                self.temperature_controller_back.pid.set_gains(
                     settings.kp,
+                    settings.ki,
                     settings.kd,
                 );
             }
             "nozzle" => {
-                self.temperature_controller_nozzle.pid.configure(
-                    settings.ki,
+                self.temperature_controller_nozzle.pid.set_gains(
                     settings.kp,
+                    settings.ki,
                     settings.kd,
                 );
             }
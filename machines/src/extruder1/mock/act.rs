@@ -1,6 +1,7 @@
-use super::ExtruderV2;
+use super::{ExtruderV2, ExtruderV2Mode};
 use crate::MachineAct;
 use crate::MachineMessage;
+use crate::ShutdownPolicy;
 use std::time::{Duration, Instant};
 
 impl MachineAct for ExtruderV2 {
@@ -21,6 +22,13 @@ impl MachineAct for ExtruderV2 {
         }
     }
 
+    fn shutdown(&mut self, _now: Instant, _policy: &ShutdownPolicy) -> bool {
+        if self.mode_state.mode != ExtruderV2Mode::Standby {
+            self.set_mode_state(ExtruderV2Mode::Standby);
+        }
+        true
+    }
+
     fn act_machine_message(&mut self, msg: crate::MachineMessage) {
         match msg {
             MachineMessage::SubscribeNamespace(namespace) => {
@@ -29,10 +37,10 @@ impl MachineAct for ExtruderV2 {
                 tracing::info!("extruder1 received subscribe");
             }
             MachineMessage::UnsubscribeNamespace => self.namespace.namespace = None,
-            MachineMessage::HttpApiJsonRequest(value) => {
+            MachineMessage::HttpApiJsonRequest(value, reply) => {
                 use crate::MachineApi;
 
-                let _res = self.api_mutate(value);
+                reply.send(self.api_mutate(value));
             }
             MachineMessage::ConnectToMachine(_machine_connection) => (),
             MachineMessage::DisconnectMachine(_machine_connection) =>
@@ -1,9 +1,21 @@
 use super::ExtruderV2;
 use crate::{
     MachineApi,
-    extruder1::{HeatingType, api::Mutation},
+    api_schema::{MachineApiSchema, MachineSchema},
+    extruder1::{
+        HeatingType,
+        api::{LiveValuesEvent, Mutation, StateEvent},
+    },
 };
 
+impl MachineSchema for ExtruderV2 {
+    fn api_schema() -> MachineApiSchema {
+        MachineApiSchema::new::<Mutation>("ExtruderV2", Self::MACHINE_IDENTIFICATION)
+            .with_event::<LiveValuesEvent>("LiveValuesEvent")
+            .with_event::<StateEvent>("StateEvent")
+    }
+}
+
 impl MachineApi for ExtruderV2 {
     fn api_mutate(&mut self, request_body: serde_json::Value) -> Result<(), anyhow::Error> {
         // there are multiple Modbus Frames that are "prebuilt"
@@ -15,6 +27,12 @@ impl MachineApi for ExtruderV2 {
             Mutation::SetInverterTargetPressure(bar) => self.set_target_pressure(bar),
             Mutation::SetInverterTargetRpm(rpm) => self.set_target_rpm(rpm),
             Mutation::ResetInverter(_) => (),
+            Mutation::ReadInverterParameter(_)
+            | Mutation::WriteInverterParameter(_)
+            | Mutation::ClearInverterParameters(_)
+            | Mutation::BackupInverterParameters(_)
+            | Mutation::RestoreInverterParameters(_)
+            | Mutation::ReadInverterFaultHistory(_) => (),
             Mutation::SetFrontHeatingTargetTemperature(temp) => {
                 self.set_target_temperature(temp, HeatingType::Front)
             }
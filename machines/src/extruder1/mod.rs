@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 #[cfg(not(feature = "mock-machine"))]
 use std::time::Instant;
 
@@ -32,13 +33,12 @@ use crate::{
 pub mod act;
 pub mod api;
 pub mod emit;
-pub mod mitsubishi_cs80;
 pub mod mock;
 pub mod new;
 pub mod screw_speed_controller;
 pub mod temperature_controller;
 
-#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
 pub enum ExtruderV2Mode {
     Standby,
     Heat,
@@ -64,6 +64,7 @@ impl Default for Heating {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
 pub enum HeatingType {
     Nozzle,
     Front,
@@ -131,7 +132,7 @@ impl ExtruderV2 {
     /// Calculate combined power consumption in watts
     fn calculate_combined_power(&mut self) -> f64 {
         let motor_power = {
-            let motor_status = &self.screw_speed_controller.inverter.motor_status;
+            let motor_status = self.screw_speed_controller.inverter.get_motor_status();
             let voltage = motor_status.voltage.get::<volt>();
             let current = motor_status.current.get::<ampere>();
             voltage * current
@@ -231,6 +232,6 @@ impl ExtruderV2 {
     }
 
     fn reset_inverter(&mut self) {
-        self.screw_speed_controller.inverter.reset_inverter();
+        self.screw_speed_controller.reset_inverter();
     }
 }
@@ -51,9 +51,11 @@ use crate::extruder1::temperature_controller::TemperatureController;
 
 #[cfg(not(feature = "mock-machine"))]
 use super::{
-    ExtruderV2, ExtruderV2Mode, Heating, api::ExtruderV2Namespace, mitsubishi_cs80::MitsubishiCS80,
+    ExtruderV2, ExtruderV2Mode, Heating, api::ExtruderV2Namespace,
     screw_speed_controller::ScrewSpeedController,
 };
+#[cfg(not(feature = "mock-machine"))]
+use crate::frequency_inverter::frequency_inverter_config;
 
 #[cfg(not(feature = "mock-machine"))]
 impl MachineNewTrait for ExtruderV2 {
@@ -209,7 +211,9 @@ impl MachineNewTrait for ExtruderV2 {
                 0.95,
             );
 
-            let inverter = MitsubishiCS80::new(SerialInterface::new(el6021, EL6021Port::SI1));
+            let inverter_config = frequency_inverter_config();
+            tracing::info!("Building extruder with {:?}", inverter_config);
+            let inverter = inverter_config.build(SerialInterface::new(el6021, EL6021Port::SI1));
 
             let target_pressure = Pressure::new::<bar>(0.0);
             let target_rpm = AngularVelocity::new::<revolution_per_minute>(0.0);
@@ -1,7 +1,9 @@
+use std::collections::BTreeMap;
 use std::time::Instant;
 
 use control_core::{
-    controllers::clamping_timeagnostic_pid::ClampingTimeagnosticPidController,
+    controllers::pid::PidController,
+    helpers::hasher_serializer::hash_with_serde_model,
     helpers::interpolation::normalize,
     transmission::{Transmission, fixed::FixedTransmission},
 };
@@ -12,16 +14,17 @@ use units::f64::*;
 use units::frequency::{cycle_per_minute, hertz};
 use units::pressure::bar;
 
-use crate::extruder1::mitsubishi_cs80::MitsubishiCS80Status;
-
-use super::mitsubishi_cs80::{MitsubishiCS80, MotorStatus};
+use crate::frequency_inverter::{
+    FrequencyInverter, InverterFault, InverterStatus, MotorStatus, RotationDirection,
+    parameters::{FinishedBackup, ParameterBackup, ParameterClear, ParameterTransfer},
+};
 
 #[derive(Debug)]
 pub struct ScrewSpeedController {
-    pub pid: ClampingTimeagnosticPidController,
+    pub pid: PidController,
     pub target_pressure: Pressure,
     pub target_rpm: AngularVelocity,
-    pub inverter: MitsubishiCS80,
+    pub inverter: Box<dyn FrequencyInverter>,
     pressure_sensor: AnalogInput,
     last_update: Instant,
     uses_rpm: bool,
@@ -37,7 +40,7 @@ pub struct ScrewSpeedController {
 
 impl ScrewSpeedController {
     pub fn new(
-        inverter: MitsubishiCS80,
+        inverter: Box<dyn FrequencyInverter>,
         target_pressure: Pressure,
         target_rpm: AngularVelocity,
         pressure_sensor: AnalogInput,
@@ -47,7 +50,7 @@ impl ScrewSpeedController {
         Self {
             inverter,
             // need to tune
-            pid: ClampingTimeagnosticPidController::simple_new(0.01, 0.0, 0.02),
+            pid: PidController::new(0.01, 0.0, 0.02),
             last_update: now,
             target_pressure,
             target_rpm,
@@ -65,8 +68,86 @@ impl ScrewSpeedController {
         }
     }
 
-    pub const fn get_inverter_status(&mut self) -> MitsubishiCS80Status {
-        self.inverter.status
+    pub fn get_inverter_status(&mut self) -> InverterStatus {
+        self.inverter.get_status()
+    }
+
+    pub fn get_inverter_fault(&self) -> Option<InverterFault> {
+        self.inverter.get_fault()
+    }
+
+    pub fn get_inverter_fault_history(&self) -> Vec<InverterFault> {
+        self.inverter.get_fault_history()
+    }
+
+    pub fn get_inverter_parameters(&self) -> BTreeMap<u16, u16> {
+        self.inverter.get_parameters()
+    }
+
+    pub fn get_inverter_parameter_transfer(&self) -> Option<ParameterTransfer> {
+        self.inverter.get_parameter_transfer()
+    }
+
+    /// Hash of the inverter status, faults and parameters, used to detect changes worth emitting
+    pub fn get_inverter_status_hash(&self) -> u64 {
+        hash_with_serde_model((
+            self.inverter.get_status(),
+            self.inverter.get_fault(),
+            self.inverter.get_fault_history(),
+            self.inverter.get_parameters(),
+            self.inverter.get_parameter_transfer(),
+        ))
+    }
+
+    pub fn reset_inverter(&mut self) {
+        self.inverter.reset();
+    }
+
+    pub fn read_inverter_parameter(&mut self, number: u16) -> Result<(), anyhow::Error> {
+        self.inverter.read_parameter(number)
+    }
+
+    pub fn write_inverter_parameter(
+        &mut self,
+        number: u16,
+        value: u16,
+    ) -> Result<(), anyhow::Error> {
+        self.inverter.write_parameter(number, value)
+    }
+
+    pub fn clear_inverter_parameters(
+        &mut self,
+        clear: ParameterClear,
+    ) -> Result<(), anyhow::Error> {
+        self.inverter.clear_parameters(clear)
+    }
+
+    pub fn backup_inverter_parameters(&mut self, name: &str) -> Result<(), anyhow::Error> {
+        self.inverter.backup_parameters(name)
+    }
+
+    pub fn take_finished_inverter_backup(&mut self) -> Option<FinishedBackup> {
+        self.inverter.take_finished_backup()
+    }
+
+    pub fn restore_inverter_parameters(
+        &mut self,
+        backup: ParameterBackup,
+    ) -> Result<(), anyhow::Error> {
+        self.inverter.restore_parameters(backup)
+    }
+
+    pub fn read_inverter_fault_history(&mut self) {
+        self.inverter.read_fault_history();
+    }
+
+    // Gearbox is inverted!
+    const fn motor_direction(&self) -> RotationDirection {
+        if self.forward_rotation {
+            RotationDirection::Reverse
+        } else {
+            RotationDirection::Forward
+        }
     }
 
     pub const fn get_motor_enabled(&mut self) -> bool {
@@ -100,7 +181,7 @@ impl ScrewSpeedController {
     pub fn set_rotation_direction(&mut self, forward: bool) {
         self.forward_rotation = forward;
         if self.motor_on {
-            self.inverter.set_rotation(self.forward_rotation);
+            self.inverter.start(self.motor_direction());
         }
     }
 
@@ -139,28 +220,38 @@ impl ScrewSpeedController {
 
     // Send Motor Turn Off Request to the Inverter
     pub fn turn_motor_off(&mut self) {
-        self.inverter.stop_motor();
+        self.inverter.stop();
         self.motor_on = false;
     }
 
     pub fn turn_motor_on(&mut self) {
-        self.inverter.set_rotation(self.forward_rotation);
+        self.inverter.start(self.motor_direction());
         self.motor_on = true;
     }
 
     pub fn get_motor_status(&mut self) -> MotorStatus {
-        let frequency = self.inverter.motor_status.frequency;
-        let rpm =
-            AngularVelocity::new::<revolution_per_minute>(frequency.get::<cycle_per_minute>());
-
-        let screw_rpm = self.transmission.calculate_angular_velocity_output(rpm);
+        let mut status = self.inverter.get_motor_status();
+        let rpm = AngularVelocity::new::<revolution_per_minute>(
+            status.frequency.get::<cycle_per_minute>(),
+        );
 
-        let mut status = self.inverter.motor_status;
-        status.rpm = screw_rpm;
+        status.rpm = self.transmission.calculate_angular_velocity_output(rpm);
 
         status
     }
 
+    /// The inverter ramps the screw down by itself, it counts as stopped below 0.1 Hz
+    pub fn is_motor_stopped(&mut self) -> bool {
+        !self.motor_on
+            && self
+                .inverter
+                .get_motor_status()
+                .frequency
+                .get::<hertz>()
+                .abs()
+                < 0.1
+    }
+
     pub fn get_target_pressure(&self) -> Pressure {
         self.target_pressure
     }
@@ -213,8 +304,7 @@ impl ScrewSpeedController {
     }
 
     pub fn update(&mut self, now: Instant, is_extruding: bool) {
-        // TODO: move this logic elsewhere or make non async
-        smol::block_on(self.inverter.act(now));
+        self.inverter.act(now);
         let measured_pressure = self.get_pressure();
         if !self.uses_rpm && !is_extruding && self.motor_on {
             let frequency = Frequency::new::<hertz>(0.0);
@@ -239,9 +329,7 @@ impl ScrewSpeedController {
 
         if !self.uses_rpm && is_extruding {
             let error = self.target_pressure - measured_pressure;
-            let freq_change = self.pid.update(error.get::<bar>(), now);
-
-            self.frequency += Frequency::new::<hertz>(freq_change);
+            self.frequency += Self::frequency_change(&mut self.pid, error, now, self.last_update);
             self.frequency = Self::clamp_frequency(
                 self.frequency,
                 self.minimum_frequency,
@@ -253,9 +341,21 @@ impl ScrewSpeedController {
         self.last_update = now;
     }
 
+    /// The PID output is the rate of change of the frequency in Hz/s, so the change per cycle
+    /// doesn't depend on the cycle time
+    fn frequency_change(
+        pid: &mut PidController,
+        error: Pressure,
+        now: Instant,
+        last_update: Instant,
+    ) -> Frequency {
+        let dt = now.saturating_duration_since(last_update).as_secs_f64();
+        Frequency::new::<hertz>(pid.update(error.get::<bar>(), now) * dt)
+    }
+
     pub fn start_pressure_regulation(&mut self) {
         self.last_update = Instant::now();
-        self.frequency = self.inverter.motor_status.frequency;
+        self.frequency = self.inverter.get_motor_status().frequency;
         self.pid.reset();
     }
 
@@ -264,3 +364,56 @@ impl ScrewSpeedController {
         self.last_update = Instant::now();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Frequency change over one second of the clamping time agnostic PID this replaced, which
+    /// scaled kp, ki and kd with dt
+    fn baseline_change(kp: f64, kd: f64, errors: &[f64], dt: f64) -> f64 {
+        errors
+            .windows(2)
+            .map(|e| (kp * dt).mul_add(e[1], kd * (e[1] - e[0])))
+            .sum()
+    }
+
+    #[test]
+    fn test_frequency_change_per_second() {
+        for cycle in [Duration::from_micros(300), Duration::from_millis(10)] {
+            let cycles = (1.0 / cycle.as_secs_f64()) as usize;
+            // a constant error, then one that ramps up
+            let errors: Vec<f64> = (0..=cycles)
+                .map(|i| 10.0 + 5.0 * i as f64 / cycles as f64)
+                .collect();
+
+            let mut pid = PidController::new(0.01, 0.0, 0.02);
+            let start = Instant::now();
+            let mut last_update = start;
+            let mut change = 0.0;
+            for (i, error) in errors.iter().enumerate() {
+                let now = start + cycle * i as u32;
+                let step = ScrewSpeedController::frequency_change(
+                    &mut pid,
+                    Pressure::new::<bar>(*error),
+                    now,
+                    last_update,
+                );
+                change += step.get::<hertz>();
+                last_update = now;
+            }
+
+            let expected = baseline_change(0.01, 0.02, &errors, cycle.as_secs_f64());
+            assert!(
+                (change - expected).abs() < 1e-9,
+                "{:?}: {} Hz instead of {} Hz",
+                cycle,
+                change,
+                expected
+            );
+            // kp·12.5 bar·1 s + kd·5 bar, not per cycle
+            assert!((0.22..0.23).contains(&change), "{} Hz", change);
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use super::Heating;
-use control_core::controllers::pid::PidController;
+use control_core::controllers::pid::{PidController, PidParameters};
+use control_core::controllers::pid_autotune::{AutotuneParameters, AutotuneStatus, RelayAutotuner};
 use ethercat_hal::io::{digital_output::DigitalOutput, temperature_input::TemperatureInput};
 use std::time::{Duration, Instant};
 use units::f64::*;
@@ -13,6 +14,8 @@ pub struct TemperatureController {
     relais: DigitalOutput,
     pub heating: Heating,
     pub target_temp: ThermodynamicTemperature,
+    /// Controlled to instead of the target temperature, e.g. while ramping up
+    setpoint: Option<ThermodynamicTemperature>,
     window_start: Instant,
     heating_allowed: bool,
     pwm_period: Duration,
@@ -20,6 +23,10 @@ pub struct TemperatureController {
     temperature_pid_output: f64,
     heating_element_wattage: f64,
     max_clamp: f64,
+    /// Replaces the PID while running
+    autotune: Option<RelayAutotuner>,
+    /// Status of the last autotune
+    autotune_status: Option<AutotuneStatus>,
 }
 
 impl TemperatureController {
@@ -27,6 +34,7 @@ impl TemperatureController {
         self.relais.set(false);
         self.heating.heating = false;
         self.disallow_heating();
+        self.stop_autotune();
     }
 
     pub fn new(
@@ -43,8 +51,11 @@ impl TemperatureController {
         max_clamp: f64,
     ) -> Self {
         Self {
-            pid: PidController::new(kp, ki, kd),
+            pid: PidController::with_parameters(
+                PidParameters::new(kp, ki, kd).with_output_limits(Some(0.0), Some(max_clamp)),
+            ),
             target_temp,
+            setpoint: None,
             window_start: Instant::now(),
             temperature_sensor,
             relais,
@@ -55,13 +66,52 @@ impl TemperatureController {
             temperature_pid_output: 0.0,
             heating_element_wattage,
             max_clamp,
+            autotune: None,
+            autotune_status: None,
         }
     }
 
+    /// Oscillates the zone around the setpoint, heating has to be allowed
+    pub fn start_autotune(
+        &mut self,
+        mut parameters: AutotuneParameters,
+        now: Instant,
+    ) -> Result<(), anyhow::Error> {
+        parameters.validate()?;
+        // never heat beyond the limit of the zone
+        let limit = self.max_temperature.get::<degree_celsius>() - parameters.setpoint;
+        if limit <= 0.0 {
+            anyhow::bail!(
+                "Autotune setpoint {} is not below the maximum temperature",
+                parameters.setpoint
+            );
+        }
+        parameters.max_overshoot = parameters.max_overshoot.min(limit);
+        let autotune = RelayAutotuner::new(parameters, self.max_clamp, 0.0, now);
+        self.autotune_status = Some(autotune.status().clone());
+        self.autotune = Some(autotune);
+        Ok(())
+    }
+
+    pub fn stop_autotune(&mut self) {
+        if let Some(mut autotune) = self.autotune.take() {
+            autotune.fail("Stopped");
+            self.autotune_status = Some(autotune.status().clone());
+        }
+    }
+
+    pub const fn get_autotune_status(&self) -> Option<&AutotuneStatus> {
+        self.autotune_status.as_ref()
+    }
+
     pub fn set_target_temperature(&mut self, temp: ThermodynamicTemperature) {
         self.heating.target_temperature = temp;
     }
 
+    pub const fn set_setpoint(&mut self, setpoint: Option<ThermodynamicTemperature>) {
+        self.setpoint = setpoint;
+    }
+
     pub const fn disallow_heating(&mut self) {
         self.heating_allowed = false;
     }
@@ -92,29 +142,53 @@ impl TemperatureController {
             return;
         }
 
-        if self.heating_allowed {
-            let error: f64 = self.heating.target_temperature.get::<degree_celsius>()
-                - self.heating.temperature.get::<degree_celsius>();
-
-            let control = self.pid.update(error, now); // PID output
-            // Clamp PID output to 0.0 – 1.0 (as duty cycle)
-            let duty = control.clamp(0.0, self.max_clamp);
+        if self.heating_allowed
+            && let Some(autotune) = &mut self.autotune
+        {
+            if self.heating.wiring_error {
+                autotune.fail("Temperature sensor wiring error");
+            }
+            let duty = autotune.update(self.heating.temperature.get::<degree_celsius>(), now);
+            self.autotune_status = Some(autotune.status().clone());
+            if let AutotuneStatus::Finished(result) = autotune.status() {
+                tracing::info!("Temperature autotune finished {:?}", result);
+                self.pid.set_gains(result.kp, result.ki, result.kd);
+            }
+            if !autotune.is_running() {
+                self.autotune = None;
+            }
+            self.set_pwm(duty, now);
+            return;
+        }
 
-            self.temperature_pid_output = duty;
+        if self.heating_allowed {
+            let setpoint = self.setpoint.unwrap_or(self.heating.target_temperature);
+            // PID output, limited to 0.0 – max_clamp (as duty cycle)
+            let duty = self.pid.compute(
+                setpoint.get::<degree_celsius>(),
+                self.heating.temperature.get::<degree_celsius>(),
+                now,
+            );
+
+            self.set_pwm(duty, now);
+        }
+    }
 
-            let elapsed = now.duration_since(self.window_start);
+    fn set_pwm(&mut self, duty: f64, now: Instant) {
+        self.temperature_pid_output = duty;
 
-            // Restart window if needed
-            if elapsed >= self.pwm_period {
-                self.window_start = now;
-            }
-            // Compare duty cycle to elapsed time
-            let on_time = self.pwm_period.mul_f64(duty);
+        let elapsed = now.duration_since(self.window_start);
 
-            // Relay is ON if within duty cycle window
-            let on = elapsed < on_time;
-            self.relais.set(on);
-            self.heating.heating = on;
+        // Restart window if needed
+        if elapsed >= self.pwm_period {
+            self.window_start = now;
         }
+        // Compare duty cycle to elapsed time
+        let on_time = self.pwm_period.mul_f64(duty);
+
+        // Relay is ON if within duty cycle window
+        let on = elapsed < on_time;
+        self.relais.set(on);
+        self.heating.heating = on;
     }
 }
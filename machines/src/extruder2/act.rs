@@ -2,7 +2,7 @@
 use std::time::Instant;
 
 #[cfg(not(feature = "mock-machine"))]
-use crate::{MachineAct, MachineMessage};
+use crate::{MachineAct, MachineMessage, ShutdownPolicy};
 
 #[cfg(not(feature = "mock-machine"))]
 use super::ExtruderV3;
@@ -20,6 +20,8 @@ impl MachineAct for ExtruderV3 {
             Err(_) => (),
         };
 
+        self.update_heat_up(now);
+
         self.temperature_controller_back.update(now);
         self.temperature_controller_nozzle.update(now);
         self.temperature_controller_front.update(now);
@@ -30,6 +32,7 @@ impl MachineAct for ExtruderV3 {
         } else {
             self.screw_speed_controller.update(now, false);
         }
+        self.send_finished_inverter_backup();
 
         if self.mode == super::ExtruderV3Mode::Standby {
             self.turn_heating_off();
@@ -46,12 +49,23 @@ impl MachineAct for ExtruderV3 {
         // more than 33ms have passed since last emit (30 "fps" target)
         if now.duration_since(self.last_measurement_emit) > Duration::from_secs_f64(1.0 / 30.0) {
             self.maybe_emit_state_event();
+            self.maybe_emit_autotune_state();
             // Emit live values at 30 FPS
             self.emit_live_values();
             self.last_measurement_emit = now;
         }
     }
 
+    fn shutdown(&mut self, now: Instant, _policy: &ShutdownPolicy) -> bool {
+        if self.mode != super::ExtruderV3Mode::Standby {
+            self.switch_mode(super::ExtruderV3Mode::Standby);
+            self.emit_state();
+        }
+        self.turn_heating_off();
+        self.screw_speed_controller.update(now, false);
+        self.screw_speed_controller.is_motor_stopped()
+    }
+
     fn act_machine_message(&mut self, msg: MachineMessage) {
         match msg {
             MachineMessage::SubscribeNamespace(namespace) => {
@@ -60,10 +74,10 @@ impl MachineAct for ExtruderV3 {
                 tracing::info!("extruder1 received subscribe");
             }
             MachineMessage::UnsubscribeNamespace => self.namespace.namespace = None,
-            MachineMessage::HttpApiJsonRequest(value) => {
+            MachineMessage::HttpApiJsonRequest(value, reply) => {
                 use crate::MachineApi;
 
-                let _res = self.api_mutate(value);
+                reply.send(self.api_mutate(value));
             }
             MachineMessage::ConnectToMachine(_machine_connection) => (),
             MachineMessage::DisconnectMachine(_machine_connection) =>
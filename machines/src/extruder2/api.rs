@@ -1,14 +1,21 @@
 use std::sync::Arc;
 
-use crate::extruder1::{
-    api::{
-        ExtruderSettingsState, HeatingStates, InverterStatusState, PidSettings, PidSettingsStates,
-        PressureState, RegulationState, RotationState, ScrewState, TemperaturePid,
-    },
-    mitsubishi_cs80::MotorStatus,
+use crate::extruder1::HeatingType;
+use crate::extruder1::api::{
+    ExtruderSettingsState, HeatingStates, InverterParametersState, InverterStatusState,
+    PidSettings, PidSettingsStates, PressureState, RegulationState, RotationState, ScrewState,
+    TemperaturePid,
+};
+use crate::frequency_inverter::{
+    MotorStatus,
+    parameters::{InverterParameter, ParameterBackup, ParameterClear},
 };
 #[cfg(not(feature = "mock-machine"))]
-use crate::{MachineApi, MachineMessage};
+use crate::{
+    MachineApi, MachineMessage,
+    api_schema::{MachineApiSchema, MachineSchema},
+};
+use control_core::controllers::pid_autotune::{AutotuneParameters, AutotuneStatus};
 use control_core::socketio::{
     event::{Event, GenericEvent},
     namespace::{
@@ -16,6 +23,7 @@ use control_core::socketio::{
     },
 };
 use control_core_derive::BuildEvent;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 #[cfg(not(feature = "mock-machine"))]
 use serde_json::Value;
@@ -30,8 +38,9 @@ use units::{
 #[cfg(not(feature = "mock-machine"))]
 use super::ExtruderV3;
 use super::ExtruderV3Mode;
+use super::heat_up::{HeatUpPhase, HeatUpSettings};
 
-#[derive(Debug, Clone, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, JsonSchema)]
 pub struct MotorStatusValues {
     pub screw_rpm: f64, // rpm of motor
     pub frequency: f64, // frequency of motor
@@ -55,7 +64,7 @@ impl From<MotorStatus> for MotorStatusValues {
     }
 }
 
-#[derive(Serialize, Debug, Clone, Default)]
+#[derive(Serialize, Debug, Clone, Default, JsonSchema)]
 pub struct LiveValuesEvent {
     /// screw rpm
     pub motor_status: MotorStatusValues,
@@ -89,7 +98,7 @@ impl LiveValuesEvent {
     }
 }
 
-#[derive(Serialize, Debug, Clone, PartialEq, BuildEvent)]
+#[derive(Serialize, Debug, Clone, PartialEq, BuildEvent, JsonSchema)]
 pub struct StateEvent {
     pub is_default_state: bool,
     /// rotation state
@@ -108,21 +117,52 @@ pub struct StateEvent {
     pub extruder_settings_state: ExtruderSettingsState,
     /// inverter status state
     pub inverter_status_state: InverterStatusState,
+    /// inverter parameters and fault history
+    pub inverter_parameters_state: InverterParametersState,
     /// pid settings
     pub pid_settings: PidSettingsStates,
+    /// heat-up progress and settings
+    pub heat_up_state: HeatUpState,
+    /// progress and result of the last autotune of each zone
+    pub autotune_states: TemperatureAutotuneStates,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Default, JsonSchema)]
+pub struct TemperatureAutotuneStates {
+    pub front: Option<AutotuneStatus>,
+    pub middle: Option<AutotuneStatus>,
+    pub back: Option<AutotuneStatus>,
+    pub nozzle: Option<AutotuneStatus>,
 }
 
-#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct TemperatureAutotune {
+    pub zone: HeatingType,
+    #[serde(flatten)]
+    pub parameters: AutotuneParameters,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
 pub struct ModeState {
     pub mode: ExtruderV3Mode,
 }
 
+#[derive(Serialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct HeatUpState {
+    pub phase: HeatUpPhase,
+    /// progress of the current phase from 0 to 1
+    pub progress: f64,
+    /// s until extruding is allowed, `None` if it can't be estimated
+    pub remaining_time: Option<f64>,
+    pub settings: HeatUpSettings,
+}
+
 pub enum ExtruderV3Events {
     LiveValues(Event<LiveValuesEvent>),
     State(Event<StateEvent>),
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 pub enum Mutation {
     /// INVERTER
     /// Frequency Control
@@ -133,7 +173,9 @@ pub enum Mutation {
     SetInverterRegulation(bool),
 
     //Mode
+    /// Extrude is refused until the extruder is heated up
     SetExtruderMode(ExtruderV3Mode),
+    SetHeatUpSettings(HeatUpSettings),
     SetFrontHeatingTargetTemperature(f64),
     SetBackHeatingTargetTemperature(f64),
     SetMiddleHeatingTemperature(f64),
@@ -146,9 +188,22 @@ pub enum Mutation {
     // Pid Configure
     SetPressurePidSettings(PidSettings),
     SetTemperaturePidSettings(TemperaturePid),
+    /// The gains are applied when the autotune finished, the extruder has to be heating
+    StartTemperatureAutotune(TemperatureAutotune),
+    StopTemperatureAutotune(HeatingType),
 
     // Reset
     ResetInverter(bool),
+
+    // Inverter Parameters
+    ReadInverterParameter(u16),
+    WriteInverterParameter(InverterParameter),
+    ClearInverterParameters(ParameterClear),
+    /// Name the backup is stored under by the server
+    BackupInverterParameters(String),
+    /// Backups are loaded by the server, see `/api/v1/inverter_backups`
+    RestoreInverterParameters(ParameterBackup),
+    ReadInverterFaultHistory(bool),
 }
 
 #[derive(Debug)]
@@ -186,6 +241,15 @@ impl CacheableEvents<Self> for ExtruderV3Events {
     }
 }
 
+#[cfg(not(feature = "mock-machine"))]
+impl MachineSchema for ExtruderV3 {
+    fn api_schema() -> MachineApiSchema {
+        MachineApiSchema::new::<Mutation>("ExtruderV3", Self::MACHINE_IDENTIFICATION)
+            .with_event::<LiveValuesEvent>("LiveValuesEvent")
+            .with_event::<StateEvent>("StateEvent")
+    }
+}
+
 #[cfg(not(feature = "mock-machine"))]
 impl MachineApi for ExtruderV3 {
     fn api_get_sender(&self) -> Sender<MachineMessage> {
@@ -194,15 +258,25 @@ impl MachineApi for ExtruderV3 {
 
     fn api_mutate(&mut self, request_body: Value) -> Result<(), anyhow::Error> {
         // there are multiple Modbus Frames that are "prebuilt"
-        use crate::extruder1::HeatingType;
         let control: Mutation = serde_json::from_value(request_body)?;
         match control {
-            Mutation::SetExtruderMode(mode) => self.set_mode_state(mode),
+            Mutation::SetExtruderMode(mode) => self.set_mode_state(mode)?,
+            Mutation::SetHeatUpSettings(settings) => self.set_heat_up_settings(settings)?,
             Mutation::SetInverterRotationDirection(forward) => self.set_rotation_state(forward),
             Mutation::SetInverterRegulation(uses_rpm) => self.set_regulation(uses_rpm),
             Mutation::SetInverterTargetPressure(bar) => self.set_target_pressure(bar),
-            Mutation::SetInverterTargetRpm(rpm) => self.set_target_rpm(rpm),
+            Mutation::SetInverterTargetRpm(rpm) => self.set_target_rpm(rpm)?,
             Mutation::ResetInverter(_) => self.reset_inverter(),
+            Mutation::ReadInverterParameter(number) => self.read_inverter_parameter(number)?,
+            Mutation::WriteInverterParameter(parameter) => {
+                self.write_inverter_parameter(parameter)?
+            }
+            Mutation::ClearInverterParameters(clear) => self.clear_inverter_parameters(clear)?,
+            Mutation::BackupInverterParameters(name) => self.backup_inverter_parameters(&name)?,
+            Mutation::RestoreInverterParameters(backup) => {
+                self.restore_inverter_parameters(backup)?
+            }
+            Mutation::ReadInverterFaultHistory(_) => self.read_inverter_fault_history(),
 
             Mutation::SetFrontHeatingTargetTemperature(temp) => {
                 self.set_target_temperature(temp, HeatingType::Front)
@@ -230,6 +304,10 @@ impl MachineApi for ExtruderV3 {
             Mutation::SetTemperaturePidSettings(settings) => {
                 self.configure_temperature_pid(settings);
             }
+            Mutation::StartTemperatureAutotune(autotune) => {
+                self.start_temperature_autotune(autotune)?;
+            }
+            Mutation::StopTemperatureAutotune(zone) => self.stop_temperature_autotune(zone),
         }
         Ok(())
     }
@@ -1,20 +1,23 @@
 #[cfg(not(feature = "mock-machine"))]
+use crate::AsyncThreadMessage;
+#[cfg(not(feature = "mock-machine"))]
 // Contains Implementations for All functions that use emit_state
 use crate::extruder1::{
     HeatingType,
     api::{
-        ExtruderSettingsState, HeatingState, HeatingStates, InverterStatusState, PidSettings,
-        PidSettingsStates, PressureState, RegulationState, RotationState, ScrewState,
-        TemperaturePid,
+        ExtruderSettingsState, HeatingState, HeatingStates, InverterParametersState,
+        InverterStatusState, PidSettings, PidSettingsStates, PressureState, RegulationState,
+        RotationState, ScrewState, TemperaturePid,
     },
 };
 #[cfg(not(feature = "mock-machine"))]
-use control_core::helpers::hasher_serializer::hash_with_serde_model;
+use crate::frequency_inverter::parameters::{InverterParameter, ParameterBackup, ParameterClear};
 #[cfg(not(feature = "mock-machine"))]
 use control_core::socketio::event::BuildEvent;
 #[cfg(not(feature = "mock-machine"))]
 use control_core::socketio::namespace::NamespaceCacheingLogic;
 #[cfg(not(feature = "mock-machine"))]
+#[cfg(not(feature = "mock-machine"))]
 use units::angular_velocity::AngularVelocity;
 #[cfg(not(feature = "mock-machine"))]
 use units::pressure::{Pressure, bar};
@@ -24,7 +27,11 @@ use units::thermodynamic_temperature::ThermodynamicTemperature;
 use units::{angular_velocity::revolution_per_minute, thermodynamic_temperature::degree_celsius};
 
 #[cfg(not(feature = "mock-machine"))]
-use super::{ExtruderV3, ExtruderV3Mode, api::StateEvent};
+use super::{
+    ExtruderV3, ExtruderV3Mode,
+    api::{HeatUpState, StateEvent, TemperatureAutotune},
+    heat_up::HeatUpSettings,
+};
 
 #[cfg(not(feature = "mock-machine"))]
 impl ExtruderV3 {
@@ -101,16 +108,16 @@ impl ExtruderV3 {
                     .screw_speed_controller
                     .get_nozzle_pressure_limit_enabled(),
             },
-            inverter_status_state: InverterStatusState {
-                running: self.screw_speed_controller.inverter.status.running,
-                forward_running: self.screw_speed_controller.inverter.status.forward_running,
-                reverse_running: self.screw_speed_controller.inverter.status.reverse_running,
-                up_to_frequency: self.screw_speed_controller.inverter.status.su,
-                overload_warning: self.screw_speed_controller.inverter.status.ol,
-                no_function: self.screw_speed_controller.inverter.status.no_function,
-                output_frequency_detection: self.screw_speed_controller.inverter.status.fu,
-                abc_fault: self.screw_speed_controller.inverter.status.abc_,
-                fault_occurence: self.screw_speed_controller.inverter.status.fault_occurence,
+            inverter_status_state: InverterStatusState::new(
+                self.screw_speed_controller.get_inverter_status(),
+                self.screw_speed_controller.get_inverter_fault(),
+            ),
+            inverter_parameters_state: InverterParametersState {
+                parameters: self.screw_speed_controller.get_inverter_parameters(),
+                fault_history: self.screw_speed_controller.get_inverter_fault_history(),
+                transfer: self
+                    .screw_speed_controller
+                    .get_inverter_parameter_transfer(),
             },
             pid_settings: PidSettingsStates {
                 temperature: TemperaturePidStates {
@@ -145,6 +152,13 @@ impl ExtruderV3 {
                     kd: self.screw_speed_controller.pid.get_kd(),
                 },
             },
+            autotune_states: self.build_autotune_states(),
+            heat_up_state: HeatUpState {
+                phase: self.heat_up.phase(),
+                progress: self.heat_up.progress(),
+                remaining_time: self.heat_up.remaining_time(),
+                settings: self.heat_up.settings.clone(),
+            },
         }
     }
 }
@@ -155,7 +169,7 @@ impl ExtruderV3 {
         use super::api::ExtruderV3Events;
 
         let state = self.build_state_event();
-        let hash = hash_with_serde_model(self.screw_speed_controller.get_inverter_status());
+        let hash = self.screw_speed_controller.get_inverter_status_hash();
         self.last_status_hash = Some(hash);
         let event = state.build();
         self.namespace.emit(ExtruderV3Events::State(event));
@@ -169,8 +183,7 @@ impl ExtruderV3 {
                 return;
             }
         };
-        let status = self.screw_speed_controller.get_inverter_status();
-        let new_status_hash = hash_with_serde_model(status);
+        let new_status_hash = self.screw_speed_controller.get_inverter_status_hash();
         if new_status_hash != old_status_hash {
             self.emit_state();
         }
@@ -254,9 +267,37 @@ impl ExtruderV3 {
         self.emit_state();
     }
 
-    pub fn set_mode_state(&mut self, mode: ExtruderV3Mode) {
+    pub fn set_mode_state(&mut self, mode: ExtruderV3Mode) -> Result<(), anyhow::Error> {
+        if mode == ExtruderV3Mode::Extrude && self.mode != ExtruderV3Mode::Extrude {
+            self.check_heated_up()?;
+        }
+        if mode == ExtruderV3Mode::Heat {
+            self.heat_up
+                .wake_up(self.zone_temperatures(), std::time::Instant::now());
+        }
         self.switch_mode(mode);
         self.emit_state();
+        Ok(())
+    }
+
+    /// Protects the screw from being turned in cold material
+    fn check_heated_up(&self) -> Result<(), anyhow::Error> {
+        if !self.heat_up.is_ready() {
+            let phase = self.heat_up.phase();
+            tracing::warn!("Refusing to extrude, heat-up phase is {:?}", phase);
+            anyhow::bail!(
+                "Extruding is not allowed before heating up, phase is {:?}",
+                phase
+            );
+        }
+        Ok(())
+    }
+
+    pub fn set_heat_up_settings(&mut self, settings: HeatUpSettings) -> Result<(), anyhow::Error> {
+        settings.validate()?;
+        self.heat_up.settings = settings;
+        self.emit_state();
+        Ok(())
     }
 
     pub fn set_regulation(&mut self, uses_rpm: bool) {
@@ -282,13 +323,94 @@ impl ExtruderV3 {
         self.emit_state();
     }
 
-    pub fn set_target_rpm(&mut self, rpm: f64) {
+    /// Slowing down or stopping the screw is always allowed, speeding it up only when heated up
+    pub fn set_target_rpm(&mut self, rpm: f64) -> Result<(), anyhow::Error> {
+        if !rpm.is_finite() {
+            anyhow::bail!("Invalid target rpm {}", rpm);
+        }
+        let current = self
+            .screw_speed_controller
+            .target_rpm
+            .get::<revolution_per_minute>();
+        if rpm.abs() > current.abs() {
+            self.check_heated_up()?;
+        }
         self.screw_speed_controller.set_target_screw_rpm(
             AngularVelocity::new::<revolution_per_minute>(rpm),
             AngularVelocity::new::<revolution_per_minute>(3000.0),
             2,
         );
         self.emit_state();
+        Ok(())
+    }
+
+    pub fn read_inverter_parameter(&mut self, number: u16) -> Result<(), anyhow::Error> {
+        self.screw_speed_controller
+            .read_inverter_parameter(number)?;
+        self.emit_state();
+        Ok(())
+    }
+
+    pub fn write_inverter_parameter(
+        &mut self,
+        parameter: InverterParameter,
+    ) -> Result<(), anyhow::Error> {
+        self.screw_speed_controller
+            .write_inverter_parameter(parameter.number, parameter.value)?;
+        self.emit_state();
+        Ok(())
+    }
+
+    pub fn clear_inverter_parameters(
+        &mut self,
+        clear: ParameterClear,
+    ) -> Result<(), anyhow::Error> {
+        self.screw_speed_controller
+            .clear_inverter_parameters(clear)?;
+        self.emit_state();
+        Ok(())
+    }
+
+    pub fn backup_inverter_parameters(&mut self, name: &str) -> Result<(), anyhow::Error> {
+        self.screw_speed_controller
+            .backup_inverter_parameters(name)?;
+        self.emit_state();
+        Ok(())
+    }
+
+    pub fn restore_inverter_parameters(
+        &mut self,
+        backup: ParameterBackup,
+    ) -> Result<(), anyhow::Error> {
+        self.screw_speed_controller
+            .restore_inverter_parameters(backup)?;
+        self.emit_state();
+        Ok(())
+    }
+
+    /// Hands a finished inverter backup to the main thread, which stores it
+    pub fn send_finished_inverter_backup(&mut self) {
+        let Some(backup) = self.screw_speed_controller.take_finished_inverter_backup() else {
+            return;
+        };
+        let message = AsyncThreadMessage::InverterBackupFinished(
+            self.machine_identification_unique.clone(),
+            Box::new(backup),
+        );
+        match &self.main_sender {
+            Some(sender) => {
+                if let Err(e) = sender.try_send(message) {
+                    tracing::error!("Inverter backup lost: {}", e);
+                }
+            }
+            None => tracing::error!("Inverter backup lost, no main thread channel"),
+        }
+        self.emit_state();
+    }
+
+    pub fn read_inverter_fault_history(&mut self) {
+        self.screw_speed_controller.read_inverter_fault_history();
+        self.emit_state();
     }
 
     pub fn set_target_temperature(&mut self, target_temperature: f64, heating_type: HeatingType) {
@@ -311,40 +433,58 @@ impl ExtruderV3 {
         self.emit_state();
     }
 
+    pub fn start_temperature_autotune(
+        &mut self,
+        autotune: TemperatureAutotune,
+    ) -> Result<(), anyhow::Error> {
+        if self.mode == ExtruderV3Mode::Standby {
+            anyhow::bail!("The extruder has to be heating to autotune");
+        }
+        self.zone_controller_mut(autotune.zone)
+            .start_autotune(autotune.parameters, std::time::Instant::now())?;
+        self.emit_state();
+        Ok(())
+    }
+
+    pub fn stop_temperature_autotune(&mut self, zone: HeatingType) {
+        self.zone_controller_mut(zone).stop_autotune();
+        self.emit_state();
+    }
+
     pub fn configure_pressure_pid(&mut self, settings: PidSettings) {
         self.screw_speed_controller
             .pid
-            .configure(settings.ki, settings.kp, settings.kd);
+            .set_gains(settings.kp, settings.ki, settings.kd);
         self.emit_state();
     }
 
     pub fn configure_temperature_pid(&mut self, settings: TemperaturePid) {
         match settings.zone.as_str() {
             "front" => {
-                self.temperature_controller_front.pid.configure(
-                    settings.ki,
+                self.temperature_controller_front.pid.set_gains(
                     settings.kp,
+                    settings.ki,
                     settings.kd,
                 );
             }
             "middle" => {
-                self.temperature_controller_middle.pid.configure(
-                    settings.ki,
+                self.temperature_controller_middle.pid.set_gains(
                     settings.kp,
+                    settings.ki,
                     settings.kd,
                 );
             }
             "back" => {
-                self.temperature_controller_back.pid.configure(
-                    settings.ki,
+                self.temperature_controller_back.pid.set_gains(
                     settings.kp,
+                    settings.ki,
                     settings.kd,
                 );
             }
             "nozzle" => {
-                self.temperature_controller_nozzle.pid.configure(
-                    settings.ki,
+                self.temperature_controller_nozzle.pid.set_gains(
                     settings.kp,
+                    settings.ki,
                     settings.kd,
                 );
             }
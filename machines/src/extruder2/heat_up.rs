@@ -0,0 +1,469 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+/// Number of heating zones, ordered nozzle, front, middle, back
+pub const ZONES: usize = 4;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+pub enum RampMode {
+    /// All zones ramp at the same time
+    #[default]
+    Simultaneous,
+    /// A zone starts ramping when the zone before it reached its setpoint,
+    /// starting at the nozzle so expanding melt always has a way out
+    ZoneByZone,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct HeatUpSettings {
+    pub ramp_mode: RampMode,
+    /// °C per minute, `None` applies the target temperature immediately
+    pub ramp_rate: Option<f64>,
+    /// s the zones have to stay at their setpoint before extruding is allowed
+    pub soak_time: f64,
+    /// °C a zone may be away from its setpoint to count as reached
+    pub tolerance: f64,
+    /// s without extruding after which the temperatures are reduced, `None` never reduces
+    pub standby_after: Option<f64>,
+    /// °C the temperatures are reduced by in standby
+    pub standby_reduction: f64,
+}
+
+impl Default for HeatUpSettings {
+    fn default() -> Self {
+        Self {
+            ramp_mode: RampMode::Simultaneous,
+            ramp_rate: None,
+            soak_time: 0.0,
+            tolerance: 5.0,
+            standby_after: None,
+            standby_reduction: 50.0,
+        }
+    }
+}
+
+/// A day, in s, no soak or standby time is longer
+const MAX_TIME: f64 = 86400.0;
+
+impl HeatUpSettings {
+    /// Times and temperatures must be finite and not negative, a ramp rate positive
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        let values = [
+            ("soak_time", Some(self.soak_time)),
+            ("tolerance", Some(self.tolerance)),
+            ("standby_after", self.standby_after),
+            ("standby_reduction", Some(self.standby_reduction)),
+        ];
+        for (name, value) in values {
+            if let Some(value) = value
+                && (!value.is_finite() || value < 0.0)
+            {
+                anyhow::bail!(
+                    "Heat-up setting {} must not be negative, got {}",
+                    name,
+                    value
+                );
+            }
+        }
+        if let Some(rate) = self.ramp_rate
+            && (!rate.is_finite() || rate <= 0.0)
+        {
+            anyhow::bail!("Heat-up ramp rate must be positive, got {}", rate);
+        }
+        let times = [
+            ("soak_time", Some(self.soak_time)),
+            ("standby_after", self.standby_after),
+        ];
+        for (name, value) in times {
+            if let Some(value) = value
+                && value > MAX_TIME
+            {
+                anyhow::bail!(
+                    "Heat-up setting {} must not exceed {} s, got {}",
+                    name,
+                    MAX_TIME,
+                    value
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+pub enum HeatUpPhase {
+    /// Heating is off
+    Off,
+    /// Zones are heating up to their setpoint
+    Ramping,
+    /// All zones reached their setpoint, waiting for the soak time
+    Soaking,
+    /// Extruding is allowed
+    Ready,
+    /// Idle for too long, heating at the reduced standby temperature
+    Standby,
+}
+
+/// Heats the barrel up before extruding is allowed, to protect the screw from cold material
+#[derive(Debug, Clone)]
+pub struct HeatUpManager {
+    pub settings: HeatUpSettings,
+    phase: HeatUpPhase,
+    /// Temperatures the zones are controlled to, following the ramp
+    setpoints: [f64; ZONES],
+    /// Temperatures when ramping started, for the progress
+    start_temperatures: [f64; ZONES],
+    soak_started_at: Option<Instant>,
+    idle_since: Option<Instant>,
+    last_update: Option<Instant>,
+    progress: f64,
+    remaining_time: Option<f64>,
+}
+
+impl HeatUpManager {
+    pub const fn new(settings: HeatUpSettings) -> Self {
+        Self {
+            settings,
+            phase: HeatUpPhase::Off,
+            setpoints: [0.0; ZONES],
+            start_temperatures: [0.0; ZONES],
+            soak_started_at: None,
+            idle_since: None,
+            last_update: None,
+            progress: 0.0,
+            remaining_time: None,
+        }
+    }
+
+    pub const fn phase(&self) -> HeatUpPhase {
+        self.phase
+    }
+
+    /// Extruding and screw setpoints are only allowed when ready
+    pub fn is_ready(&self) -> bool {
+        self.phase == HeatUpPhase::Ready
+    }
+
+    /// Progress of the current phase from 0 to 1
+    pub const fn progress(&self) -> f64 {
+        self.progress
+    }
+
+    /// s until extruding is allowed, `None` if it can't be estimated
+    pub const fn remaining_time(&self) -> Option<f64> {
+        self.remaining_time
+    }
+
+    /// Temperatures the zones should be controlled to, `None` when off
+    pub const fn setpoints(&self) -> Option<[f64; ZONES]> {
+        match self.phase {
+            HeatUpPhase::Off => None,
+            _ => Some(self.setpoints),
+        }
+    }
+
+    /// Starts heating up from the current temperatures
+    pub fn start(&mut self, temperatures: [f64; ZONES], now: Instant) {
+        if matches!(self.phase, HeatUpPhase::Off) {
+            self.setpoints = temperatures;
+        }
+        self.start_temperatures = temperatures;
+        self.phase = HeatUpPhase::Ramping;
+        self.soak_started_at = None;
+        self.idle_since = None;
+        self.last_update = Some(now);
+        self.progress = 0.0;
+        self.remaining_time = None;
+    }
+
+    /// Leaves the standby temperature, extruding is allowed after heating up again
+    pub fn wake_up(&mut self, temperatures: [f64; ZONES], now: Instant) {
+        if self.phase == HeatUpPhase::Standby {
+            self.start(temperatures, now);
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.phase = HeatUpPhase::Off;
+        self.soak_started_at = None;
+        self.idle_since = None;
+        self.last_update = None;
+        self.progress = 0.0;
+        self.remaining_time = None;
+    }
+
+    pub fn update(
+        &mut self,
+        now: Instant,
+        temperatures: [f64; ZONES],
+        targets: [f64; ZONES],
+        extruding: bool,
+    ) {
+        if self.phase == HeatUpPhase::Off {
+            return;
+        }
+        let dt = self.last_update.map_or(0.0, |last| {
+            now.saturating_duration_since(last).as_secs_f64()
+        });
+        self.last_update = Some(now);
+
+        let targets = if self.phase == HeatUpPhase::Standby {
+            targets.map(|target| (target - self.settings.standby_reduction).max(0.0))
+        } else {
+            targets
+        };
+        self.ramp_setpoints(dt, &temperatures, &targets);
+
+        let tolerance = self.settings.tolerance;
+        let at_setpoint = (0..ZONES).all(|zone| {
+            self.setpoints[zone] >= targets[zone]
+                && (temperatures[zone] - targets[zone]).abs() <= tolerance
+        });
+
+        match self.phase {
+            HeatUpPhase::Off | HeatUpPhase::Standby => (),
+            HeatUpPhase::Ramping if at_setpoint => {
+                self.phase = HeatUpPhase::Soaking;
+                self.soak_started_at = Some(now);
+                self.update_soaking(now);
+            }
+            HeatUpPhase::Ramping => (),
+            // cooling down during the soak time starts heating up again
+            HeatUpPhase::Soaking if !at_setpoint => {
+                self.phase = HeatUpPhase::Ramping;
+                self.soak_started_at = None;
+            }
+            HeatUpPhase::Soaking => self.update_soaking(now),
+            HeatUpPhase::Ready => self.update_idle(now, extruding),
+        }
+
+        match self.phase {
+            HeatUpPhase::Ramping => self.update_ramping(&temperatures, &targets),
+            HeatUpPhase::Ready | HeatUpPhase::Standby => {
+                self.progress = 1.0;
+                self.remaining_time = Some(0.0);
+            }
+            HeatUpPhase::Off | HeatUpPhase::Soaking => (),
+        }
+    }
+
+    /// Moves the setpoints towards the targets, lowering a target applies immediately
+    fn ramp_setpoints(&mut self, dt: f64, temperatures: &[f64; ZONES], targets: &[f64; ZONES]) {
+        let step = self.settings.ramp_rate.map(|rate| rate / 60.0 * dt);
+        let mut previous_reached = true;
+
+        for zone in 0..ZONES {
+            let target = targets[zone];
+            let may_ramp = match self.settings.ramp_mode {
+                RampMode::Simultaneous => true,
+                RampMode::ZoneByZone => previous_reached,
+            };
+
+            if self.setpoints[zone] > target {
+                self.setpoints[zone] = target;
+            } else if may_ramp {
+                self.setpoints[zone] = match step {
+                    Some(step) => (self.setpoints[zone] + step).min(target),
+                    None => target,
+                };
+            }
+
+            previous_reached = self.setpoints[zone] >= target
+                && temperatures[zone] >= target - self.settings.tolerance;
+        }
+    }
+
+    fn update_ramping(&mut self, temperatures: &[f64; ZONES], targets: &[f64; ZONES]) {
+        let fractions = (0..ZONES).map(|zone| {
+            let rise = targets[zone] - self.start_temperatures[zone];
+            if rise <= self.settings.tolerance {
+                1.0
+            } else {
+                ((temperatures[zone] - self.start_temperatures[zone]) / rise).clamp(0.0, 1.0)
+            }
+        });
+        self.progress = fractions.sum::<f64>() / ZONES as f64;
+
+        // only the ramp itself can be estimated, heating may be slower than the ramp
+        self.remaining_time = self
+            .settings
+            .ramp_rate
+            .filter(|rate| *rate > 0.0)
+            .map(|rate| {
+                let remaining =
+                    (0..ZONES).map(|zone| (targets[zone] - self.setpoints[zone]).max(0.0));
+                let remaining = match self.settings.ramp_mode {
+                    RampMode::Simultaneous => remaining.fold(0.0, f64::max),
+                    RampMode::ZoneByZone => remaining.sum(),
+                };
+                remaining / rate * 60.0 + self.settings.soak_time
+            });
+    }
+
+    fn update_soaking(&mut self, now: Instant) {
+        let soaked = self.soak_started_at.map_or(0.0, |start| {
+            now.saturating_duration_since(start).as_secs_f64()
+        });
+        if soaked >= self.settings.soak_time {
+            tracing::info!("Extruder heated up, extruding is allowed");
+            self.phase = HeatUpPhase::Ready;
+            self.soak_started_at = None;
+            self.idle_since = Some(now);
+            return;
+        }
+        self.progress = soaked / self.settings.soak_time;
+        self.remaining_time = Some(self.settings.soak_time - soaked);
+    }
+
+    fn update_idle(&mut self, now: Instant, extruding: bool) {
+        if extruding {
+            self.idle_since = None;
+            return;
+        }
+        let idle_since = *self.idle_since.get_or_insert(now);
+        if let Some(standby_after) = self.settings.standby_after
+            && now.saturating_duration_since(idle_since).as_secs_f64() >= standby_after
+        {
+            tracing::info!("Extruder idle, reducing to standby temperature");
+            self.phase = HeatUpPhase::Standby;
+            self.idle_since = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use std::time::Duration;
+
+    const TARGETS: [f64; ZONES] = [200.0, 200.0, 190.0, 180.0];
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn test_ramp_and_soak() {
+        let mut manager = HeatUpManager::new(HeatUpSettings {
+            ramp_rate: Some(60.0),
+            soak_time: 60.0,
+            ..Default::default()
+        });
+        let t0 = Instant::now();
+        let cold = [20.0; ZONES];
+
+        assert_eq!(manager.setpoints(), None);
+        manager.start(cold, t0);
+        manager.update(t0 + secs(10), cold, TARGETS, false);
+        assert_eq!(manager.phase(), HeatUpPhase::Ramping);
+        assert_eq!(manager.setpoints(), Some([30.0; ZONES]));
+        assert_relative_eq!(manager.progress(), 0.0);
+        // 170 °C left on the longest ramp at 1 °C/s, plus the soak time
+        assert_relative_eq!(manager.remaining_time().unwrap(), 230.0);
+
+        manager.update(t0 + secs(200), [198.0, 201.0, 190.0, 179.0], TARGETS, false);
+        assert_eq!(manager.setpoints(), Some(TARGETS));
+        assert_eq!(manager.phase(), HeatUpPhase::Soaking);
+        assert!(!manager.is_ready());
+
+        manager.update(t0 + secs(230), [198.0, 201.0, 190.0, 179.0], TARGETS, false);
+        assert_relative_eq!(manager.progress(), 0.5);
+        assert_relative_eq!(manager.remaining_time().unwrap(), 30.0);
+
+        // a zone cooling down during the soak time
+        manager.update(t0 + secs(240), [198.0, 201.0, 180.0, 179.0], TARGETS, false);
+        assert_eq!(manager.phase(), HeatUpPhase::Ramping);
+        manager.update(t0 + secs(250), TARGETS, TARGETS, false);
+        assert_eq!(manager.phase(), HeatUpPhase::Soaking);
+        manager.update(t0 + secs(310), TARGETS, TARGETS, false);
+        assert!(manager.is_ready());
+
+        manager.stop();
+        assert_eq!(manager.phase(), HeatUpPhase::Off);
+        assert!(!manager.is_ready());
+    }
+
+    #[test]
+    fn test_validate_settings() {
+        HeatUpSettings::default().validate().unwrap();
+        let invalid = [
+            HeatUpSettings {
+                ramp_rate: Some(-10.0),
+                ..Default::default()
+            },
+            HeatUpSettings {
+                ramp_rate: Some(0.0),
+                ..Default::default()
+            },
+            HeatUpSettings {
+                standby_after: Some(f64::NAN),
+                ..Default::default()
+            },
+            HeatUpSettings {
+                standby_after: Some(-1.0),
+                ..Default::default()
+            },
+            HeatUpSettings {
+                soak_time: f64::INFINITY,
+                ..Default::default()
+            },
+            HeatUpSettings {
+                standby_after: Some(1e20),
+                ..Default::default()
+            },
+        ];
+        for settings in invalid {
+            assert!(settings.validate().is_err(), "{:?}", settings);
+        }
+    }
+
+    #[test]
+    fn test_zone_by_zone() {
+        let mut manager = HeatUpManager::new(HeatUpSettings {
+            ramp_mode: RampMode::ZoneByZone,
+            ..Default::default()
+        });
+        let t0 = Instant::now();
+        let cold = [20.0; ZONES];
+
+        manager.start(cold, t0);
+        manager.update(t0, cold, TARGETS, false);
+        assert_eq!(manager.setpoints(), Some([200.0, 20.0, 20.0, 20.0]));
+
+        let nozzle_hot = [197.0, 20.0, 20.0, 20.0];
+        manager.update(t0 + secs(1), nozzle_hot, TARGETS, false);
+        assert_eq!(manager.setpoints(), Some([200.0, 200.0, 20.0, 20.0]));
+        assert_relative_eq!(manager.progress(), 177.0 / 180.0 / 4.0);
+        assert_eq!(manager.remaining_time(), None);
+    }
+
+    #[test]
+    fn test_standby_reduction() {
+        let mut manager = HeatUpManager::new(HeatUpSettings {
+            standby_after: Some(60.0),
+            standby_reduction: 50.0,
+            ..Default::default()
+        });
+        let t0 = Instant::now();
+
+        manager.start(TARGETS, t0);
+        manager.update(t0, TARGETS, TARGETS, false);
+        assert!(manager.is_ready());
+
+        manager.update(t0 + secs(50), TARGETS, TARGETS, true);
+        manager.update(t0 + secs(100), TARGETS, TARGETS, false);
+        assert!(manager.is_ready());
+        manager.update(t0 + secs(160), TARGETS, TARGETS, false);
+        assert_eq!(manager.phase(), HeatUpPhase::Standby);
+        manager.update(t0 + secs(161), TARGETS, TARGETS, false);
+        assert_eq!(manager.setpoints(), Some([150.0, 150.0, 140.0, 130.0]));
+
+        let reduced = [150.0, 150.0, 140.0, 130.0];
+        manager.wake_up(reduced, t0 + secs(200));
+        manager.update(t0 + secs(200), reduced, TARGETS, false);
+        assert_eq!(manager.phase(), HeatUpPhase::Ramping);
+        assert_eq!(manager.setpoints(), Some(TARGETS));
+    }
+}
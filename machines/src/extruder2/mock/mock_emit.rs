@@ -22,6 +22,7 @@ impl ExtruderV2 {
             heating_states: self.heating_states.clone(),
             extruder_settings_state: self.extruder_settings_state.clone(),
             inverter_status_state: self.inverter_status_state.clone(),
+            inverter_parameters_state: self.inverter_parameters_state.clone(),
             pid_settings: self.pid_settings.clone(),
         }
     }
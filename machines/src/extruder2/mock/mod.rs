@@ -20,9 +20,9 @@ use crate::{
     extruder1::{
         ExtruderV2Mode,
         api::{
-            ExtruderSettingsState, ExtruderV2Namespace, HeatingStates, InverterStatusState,
-            ModeState, MotorStatusValues, PidSettingsStates, PressureState, RegulationState,
-            RotationState, ScrewState,
+            ExtruderSettingsState, ExtruderV2Namespace, HeatingStates, InverterParametersState,
+            InverterStatusState, ModeState, MotorStatusValues, PidSettingsStates, PressureState,
+            RegulationState, RotationState, ScrewState,
         },
     },
 };
@@ -86,6 +86,8 @@ pub struct ExtruderV2 {
     pub extruder_settings_state: ExtruderSettingsState,
     /// inverter status state
     pub inverter_status_state: InverterStatusState,
+    /// inverter parameters and fault history
+    pub inverter_parameters_state: InverterParametersState,
     /// pid settings
     pub pid_settings: PidSettingsStates,
 
@@ -4,9 +4,9 @@ use crate::{
         ExtruderV2Mode,
         api::{
             ExtruderSettingsState, ExtruderV2Namespace, HeatingState, HeatingStates,
-            InverterStatusState, ModeState, MotorStatusValues, PidSettings, PidSettingsStates,
-            PressureState, RegulationState, RotationState, ScrewState, TemperaturePid,
-            TemperaturePidStates,
+            InverterParametersState, InverterStatusState, ModeState, MotorStatusValues,
+            PidSettings, PidSettingsStates, PressureState, RegulationState, RotationState,
+            ScrewState, TemperaturePid, TemperaturePidStates,
         },
     },
     extruder2::mock::ExtruderV2,
@@ -92,7 +92,9 @@ impl MachineNewTrait for ExtruderV2 {
                 output_frequency_detection: false,
                 abc_fault: false,
                 fault_occurence: false,
+                fault: None,
             },
+            inverter_parameters_state: InverterParametersState::default(),
             pid_settings: PidSettingsStates {
                 temperature: TemperaturePidStates {
                     front: TemperaturePid {
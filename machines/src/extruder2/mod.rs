@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 #[cfg(not(feature = "mock-machine"))]
 use std::time::Instant;
 
@@ -22,6 +23,14 @@ use crate::MACHINE_EXTRUDER_V2;
 #[cfg(not(feature = "mock-machine"))]
 use crate::{AsyncThreadMessage, Machine};
 
+#[cfg(not(feature = "mock-machine"))]
+use api::TemperatureAutotuneStates;
+#[cfg(not(feature = "mock-machine"))]
+use heat_up::{HeatUpManager, HeatUpPhase, ZONES};
+
+#[cfg(not(feature = "mock-machine"))]
+use units::thermodynamic_temperature::{ThermodynamicTemperature, degree_celsius};
+
 #[cfg(not(feature = "mock-machine"))]
 use crate::{
     MachineMessage, VENDOR_QITECH,
@@ -34,11 +43,12 @@ use crate::{
 pub mod act;
 pub mod api;
 pub mod emit;
+pub mod heat_up;
 pub mod mock;
 pub mod new;
 pub mod temperature_controller;
 
-#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
 pub enum ExtruderV3Mode {
     Standby,
     Heat,
@@ -79,6 +89,13 @@ pub struct ExtruderV3 {
     /// will be initalized as false and set to true by `emit_state`
     /// This way we can signal to the client that the first state emission is a default state
     emitted_default_state: bool,
+
+    /// Blocks extruding until the barrel is heated up
+    heat_up: HeatUpManager,
+    last_heat_up_emit: Instant,
+
+    /// Autotune states of the last state event
+    last_autotune_states: TemperatureAutotuneStates,
 }
 
 #[cfg(not(feature = "mock-machine"))]
@@ -112,7 +129,7 @@ impl ExtruderV3 {
     /// Calculate combined power consumption in watts
     fn calculate_combined_power(&mut self) -> f64 {
         let motor_power = {
-            let motor_status = &self.screw_speed_controller.inverter.motor_status;
+            let motor_status = self.screw_speed_controller.inverter.get_motor_status();
             let voltage = motor_status.voltage.get::<volt>();
             let current = motor_status.current.get::<ampere>();
             voltage * current
@@ -154,6 +171,105 @@ impl ExtruderV3 {
         self.temperature_controller_nozzle.disable();
     }
 
+    /// Temperature controllers in the zone order of the heat-up
+    const fn zone_controllers(&self) -> [&TemperatureController; ZONES] {
+        [
+            &self.temperature_controller_nozzle,
+            &self.temperature_controller_front,
+            &self.temperature_controller_middle,
+            &self.temperature_controller_back,
+        ]
+    }
+
+    const fn zone_controllers_mut(&mut self) -> [&mut TemperatureController; ZONES] {
+        [
+            &mut self.temperature_controller_nozzle,
+            &mut self.temperature_controller_front,
+            &mut self.temperature_controller_middle,
+            &mut self.temperature_controller_back,
+        ]
+    }
+
+    const fn zone_controller_mut(
+        &mut self,
+        zone: crate::extruder1::HeatingType,
+    ) -> &mut TemperatureController {
+        use crate::extruder1::HeatingType;
+        match zone {
+            HeatingType::Front => &mut self.temperature_controller_front,
+            HeatingType::Middle => &mut self.temperature_controller_middle,
+            HeatingType::Back => &mut self.temperature_controller_back,
+            HeatingType::Nozzle => &mut self.temperature_controller_nozzle,
+        }
+    }
+
+    fn build_autotune_states(&self) -> TemperatureAutotuneStates {
+        TemperatureAutotuneStates {
+            front: self
+                .temperature_controller_front
+                .get_autotune_status()
+                .cloned(),
+            middle: self
+                .temperature_controller_middle
+                .get_autotune_status()
+                .cloned(),
+            back: self
+                .temperature_controller_back
+                .get_autotune_status()
+                .cloned(),
+            nozzle: self
+                .temperature_controller_nozzle
+                .get_autotune_status()
+                .cloned(),
+        }
+    }
+
+    /// Emits the state when the autotune of a zone made progress
+    fn maybe_emit_autotune_state(&mut self) {
+        let autotune_states = self.build_autotune_states();
+        if autotune_states != self.last_autotune_states {
+            self.last_autotune_states = autotune_states;
+            self.emit_state();
+        }
+    }
+
+    fn zone_temperatures(&self) -> [f64; ZONES] {
+        self.zone_controllers()
+            .map(|controller| controller.heating.temperature.get::<degree_celsius>())
+    }
+
+    /// Ramps the setpoints of the zones and emits the progress while heating up
+    fn update_heat_up(&mut self, now: Instant) {
+        let phase = self.heat_up.phase();
+        let temperatures = self.zone_temperatures();
+        let targets = self.zone_controllers().map(|controller| {
+            controller
+                .heating
+                .target_temperature
+                .get::<degree_celsius>()
+        });
+        let extruding = self.mode == ExtruderV3Mode::Extrude;
+        self.heat_up.update(now, temperatures, targets, extruding);
+
+        let setpoints = self.heat_up.setpoints();
+        for (zone, controller) in self.zone_controllers_mut().into_iter().enumerate() {
+            controller.set_setpoint(
+                setpoints.map(|s| ThermodynamicTemperature::new::<degree_celsius>(s[zone])),
+            );
+        }
+
+        let heating_up = matches!(
+            self.heat_up.phase(),
+            HeatUpPhase::Ramping | HeatUpPhase::Soaking
+        );
+        if self.heat_up.phase() != phase
+            || (heating_up && now.duration_since(self.last_heat_up_emit).as_secs_f64() >= 1.0)
+        {
+            self.last_heat_up_emit = now;
+            self.emit_state();
+        }
+    }
+
     fn switch_to_standby(&mut self) {
         match self.mode {
             ExtruderV3Mode::Standby => (),
@@ -167,12 +283,16 @@ impl ExtruderV3 {
                 self.screw_speed_controller.reset_pid();
             }
         };
+        self.heat_up.stop();
         self.mode = ExtruderV3Mode::Standby;
     }
 
     fn switch_to_heat(&mut self) {
         match self.mode {
-            ExtruderV3Mode::Standby => self.enable_heating(),
+            ExtruderV3Mode::Standby => {
+                self.heat_up.start(self.zone_temperatures(), Instant::now());
+                self.enable_heating();
+            }
             ExtruderV3Mode::Heat => (),
             ExtruderV3Mode::Extrude => {
                 self.screw_speed_controller.turn_motor_off();
@@ -212,6 +332,6 @@ impl ExtruderV3 {
     }
 
     fn reset_inverter(&mut self) {
-        self.screw_speed_controller.inverter.reset_inverter();
+        self.screw_speed_controller.reset_inverter();
     }
 }
@@ -84,11 +84,13 @@ impl MachineNewTrait for ExtruderV3 {
             use control_core::transmission::fixed::FixedTransmission;
 
             use crate::{
-                extruder1::{
-                    Heating, mitsubishi_cs80::MitsubishiCS80,
-                    screw_speed_controller::ScrewSpeedController,
+                extruder1::{Heating, screw_speed_controller::ScrewSpeedController},
+                extruder2::{
+                    ExtruderV3Mode,
+                    api::ExtruderV3Namespace,
+                    heat_up::{HeatUpManager, HeatUpSettings},
                 },
-                extruder2::{ExtruderV3Mode, api::ExtruderV3Namespace},
+                frequency_inverter::frequency_inverter_config,
             };
             let _ek1100 =
                 get_ethercat_device::<EK1100>(hardware, params, 0, [EK1100_IDENTITY_A].to_vec());
@@ -208,7 +210,9 @@ impl MachineNewTrait for ExtruderV3 {
                 0.95,
             );
 
-            let inverter = MitsubishiCS80::new(SerialInterface::new(el6021, EL6021Port::SI1));
+            let inverter_config = frequency_inverter_config();
+            tracing::info!("Building extruder with {:?}", inverter_config);
+            let inverter = inverter_config.build(SerialInterface::new(el6021, EL6021Port::SI1));
 
             let target_pressure = Pressure::new::<bar>(0.0);
             let target_rpm = AngularVelocity::new::<revolution_per_minute>(0.0);
@@ -241,6 +245,9 @@ impl MachineNewTrait for ExtruderV3 {
                 screw_speed_controller,
                 emitted_default_state: false,
                 last_status_hash: None,
+                heat_up: HeatUpManager::new(HeatUpSettings::default()),
+                last_heat_up_emit: Instant::now(),
+                last_autotune_states: Default::default(),
             };
             extruder.emit_state();
             Ok(extruder)
@@ -1,5 +1,5 @@
 use crate::extruder1::Heating;
-use control_core::controllers::pid::PidController;
+use control_core::controllers::pid::{PidController, PidParameters};
 use ethercat_hal::io::{digital_output::DigitalOutput, temperature_input::TemperatureInput};
 use std::time::{Duration, Instant};
 use units::f64::*;
@@ -19,7 +19,6 @@ pub struct TemperatureController {
     max_temperature: ThermodynamicTemperature,
     temperature_pid_output: f64,
     heating_element_wattage: f64,
-    max_clamp: f64,
 }
 
 impl TemperatureController {
@@ -43,7 +42,9 @@ impl TemperatureController {
         max_clamp: f64,
     ) -> Self {
         Self {
-            pid: PidController::new(kp, ki, kd),
+            pid: PidController::with_parameters(
+                PidParameters::new(kp, ki, kd).with_output_limits(Some(0.0), Some(max_clamp)),
+            ),
             target_temp,
             window_start: Instant::now(),
             temperature_sensor,
@@ -54,7 +55,6 @@ impl TemperatureController {
             max_temperature,
             temperature_pid_output: 0.0,
             heating_element_wattage,
-            max_clamp,
         }
     }
 
@@ -93,12 +93,12 @@ impl TemperatureController {
         }
 
         if self.heating_allowed {
-            let error: f64 = self.heating.target_temperature.get::<degree_celsius>()
-                - self.heating.temperature.get::<degree_celsius>();
-
-            let control = self.pid.update(error, now); // PID output
-            // Clamp PID output to 0.0 – 1.0 (as duty cycle)
-            let duty = control.clamp(0.0, self.max_clamp);
+            // PID output, limited to 0.0 – max_clamp (as duty cycle)
+            let duty = self.pid.compute(
+                self.heating.target_temperature.get::<degree_celsius>(),
+                self.heating.temperature.get::<degree_celsius>(),
+                now,
+            );
 
             self.temperature_pid_output = duty;
 
@@ -0,0 +1,377 @@
+use control_core::modbus::{
+    ModbusFunctionCode, ModbusRequest, ModbusResponse,
+    modbus_serial_interface::ModbusSerialInterface,
+};
+use ethercat_hal::io::serial_interface::SerialInterface;
+use std::time::{Duration, Instant};
+use units::electric_current::deciampere;
+use units::electric_potential::decivolt;
+use units::f64::*;
+use units::frequency::centihertz;
+
+use super::{FrequencyInverter, InverterFault, InverterStatus, MotorStatus, RotationDirection};
+
+/// Communication parameters of the Delta VFD-E series (Modbus RTU, Pr. 09-00 .. 09-04)
+/// Register addresses are used as is, Delta does not use the 4xxxx offset
+#[derive(Debug, Clone, Copy)]
+enum DeltaVfdERegister {
+    /// Register 2000H, Run/Stop and direction command
+    Command,
+    /// Register 2001H, Frequency command in 0.01 Hz
+    FrequencyCommand,
+    /// Register 2002H, External fault and reset
+    FaultControl,
+    /// Register 2100H, Error code, followed by the status of the drive (2101H)
+    ErrorCode,
+    /// Register 2103H, Output frequency, followed by output current (2104H), DC-bus voltage (2105H) and output voltage (2106H)
+    OutputFrequency,
+}
+
+impl DeltaVfdERegister {
+    const fn address(self) -> u16 {
+        match self {
+            Self::Command => 0x2000,
+            Self::FrequencyCommand => 0x2001,
+            Self::FaultControl => 0x2002,
+            Self::ErrorCode => 0x2100,
+            Self::OutputFrequency => 0x2103,
+        }
+    }
+
+    const fn address_be_bytes(self) -> [u8; 2] {
+        self.address().to_be_bytes()
+    }
+}
+
+// Register 2000H: bit 0-1 = 01 stop, 10 run; bit 4-5 = 01 forward, 10 reverse
+const COMMAND_STOP: u16 = 0b0000_0001;
+const COMMAND_RUN_FORWARD: u16 = 0b0001_0010;
+const COMMAND_RUN_REVERSE: u16 = 0b0010_0010;
+// Register 2002H: bit 1 = reset
+const FAULT_CONTROL_RESET: u16 = 0b0000_0010;
+
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub enum DeltaVfdERequests {
+    /// Register 2002H, Reset a fault
+    Reset,
+    /// Register 2000H, Stops the motor
+    StopMotor,
+    /// Register 2000H, Starts the motor in forward rotation
+    StartForwardRotation,
+    /// Register 2000H, Starts the motor in reverse rotation
+    StartReverseRotation,
+    /// Register 2001H, Write the frequency
+    WriteFrequency,
+    /// Register 2100H and 2101H, Read the error code and the drive status
+    ReadStatus,
+    /// Read Register 2103H - 2106H frequency, current, dc-bus voltage and output voltage
+    ReadMotorStatus,
+}
+
+impl From<DeltaVfdERequests> for u32 {
+    fn from(request: DeltaVfdERequests) -> Self {
+        request as Self
+    }
+}
+
+impl TryFrom<u32> for DeltaVfdERequests {
+    type Error = ();
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Reset),
+            1 => Ok(Self::StopMotor),
+            2 => Ok(Self::StartForwardRotation),
+            3 => Ok(Self::StartReverseRotation),
+            4 => Ok(Self::WriteFrequency),
+            5 => Ok(Self::ReadStatus),
+            6 => Ok(Self::ReadMotorStatus),
+            _ => Err(()),
+        }
+    }
+}
+
+impl DeltaVfdERequests {
+    /// Priorities follow the same scheme as the MitsubishiCS80,
+    /// stopping and resetting is more important than monitoring
+    const fn priority(self) -> u16 {
+        match self {
+            Self::Reset | Self::StopMotor => u16::MAX,
+            Self::StartForwardRotation | Self::StartReverseRotation | Self::WriteFrequency => {
+                u16::MAX - 1
+            }
+            Self::ReadMotorStatus => u16::MAX - 2,
+            Self::ReadStatus => u16::MAX - 6,
+        }
+    }
+
+    fn modbus_request(self, slave_id: u8) -> ModbusRequest {
+        let write = |register: DeltaVfdERegister, value: u16| {
+            let reg_bytes = register.address_be_bytes();
+            let value_bytes = value.to_be_bytes();
+            ModbusRequest {
+                slave_id,
+                function_code: ModbusFunctionCode::PresetHoldingRegister,
+                data: vec![reg_bytes[0], reg_bytes[1], value_bytes[0], value_bytes[1]],
+            }
+        };
+        let read = |register: DeltaVfdERegister, count: u16| {
+            let reg_bytes = register.address_be_bytes();
+            let count_bytes = count.to_be_bytes();
+            ModbusRequest {
+                slave_id,
+                function_code: ModbusFunctionCode::ReadHoldingRegister,
+                data: vec![reg_bytes[0], reg_bytes[1], count_bytes[0], count_bytes[1]],
+            }
+        };
+
+        match self {
+            Self::Reset => write(DeltaVfdERegister::FaultControl, FAULT_CONTROL_RESET),
+            Self::StopMotor => write(DeltaVfdERegister::Command, COMMAND_STOP),
+            Self::StartForwardRotation => write(DeltaVfdERegister::Command, COMMAND_RUN_FORWARD),
+            Self::StartReverseRotation => write(DeltaVfdERegister::Command, COMMAND_RUN_REVERSE),
+            // the value is filled in by set_frequency_target
+            Self::WriteFrequency => write(DeltaVfdERegister::FrequencyCommand, 0),
+            Self::ReadStatus => read(DeltaVfdERegister::ErrorCode, 2),
+            Self::ReadMotorStatus => read(DeltaVfdERegister::OutputFrequency, 4),
+        }
+    }
+}
+
+/// Decodes the error code in register 2100H
+pub const fn decode_error_code(code: u16) -> Option<(&'static str, &'static str)> {
+    match code {
+        1 => Some(("oc", "Over-current")),
+        2 => Some(("ov", "Over-voltage")),
+        3 => Some(("oH", "Overheat")),
+        4 => Some(("oL", "Drive overload")),
+        5 => Some(("oL1", "Motor overload (electronic thermal relay)")),
+        6 => Some(("EF", "External fault")),
+        7 => Some(("occ", "IGBT protection")),
+        8 => Some(("cF3", "CPU failure")),
+        9 => Some(("HPF", "Hardware protection failure")),
+        10 => Some(("ocA", "Over-current during acceleration")),
+        11 => Some(("ocd", "Over-current during deceleration")),
+        12 => Some(("ocn", "Over-current during steady state operation")),
+        13 => Some(("GFF", "Ground fault")),
+        14 => Some(("Lv", "Low voltage")),
+        15 => Some(("cF1", "CPU READ failure")),
+        16 => Some(("cF2", "CPU WRITE failure")),
+        17 => Some(("bb", "Base block")),
+        18 => Some(("oL2", "Motor over load")),
+        21 => Some(("PHL", "Phase loss")),
+        _ => None,
+    }
+}
+
+/// Delta VFD-E series inverter controlled via Modbus RTU
+///
+/// The inverter needs Pr. 02-00 = 4 (frequency from RS485) and Pr. 02-01 = 3 (operation from RS485)
+#[derive(Debug)]
+pub struct DeltaVfdE {
+    pub status: InverterStatus,
+    pub motor_status: MotorStatus,
+    pub modbus_serial_interface: ModbusSerialInterface,
+    /// Content of register 2100H, 0 if there is no error
+    pub error_code: u16,
+    slave_id: u8,
+}
+
+impl DeltaVfdE {
+    /// `slave_id` has to match the communication address set in Pr. 09-00
+    pub fn new(serial_interface: SerialInterface, slave_id: u8) -> Self {
+        Self {
+            status: InverterStatus::default(),
+            motor_status: MotorStatus::default(),
+            modbus_serial_interface: ModbusSerialInterface::new(serial_interface),
+            error_code: 0,
+            slave_id,
+        }
+    }
+
+    fn add_request(&mut self, request: DeltaVfdERequests, modbus_request: ModbusRequest) {
+        self.modbus_serial_interface.add_request(
+            request.into(),
+            request.priority() as u32,
+            modbus_request,
+            false,
+            // the VFD-E answers within 10ms
+            Some(Duration::from_millis(10).as_nanos() as u32),
+        );
+    }
+
+    fn queue(&mut self, request: DeltaVfdERequests) {
+        let modbus_request = request.modbus_request(self.slave_id);
+        self.add_request(request, modbus_request);
+    }
+
+    fn handle_status(&mut self, resp: &ModbusResponse) {
+        // byte count followed by 2 registers
+        if resp.data.len() < 5 {
+            return;
+        }
+
+        self.error_code = u16::from_be_bytes([resp.data[1], resp.data[2]]) & 0x00FF;
+        let drive_status = u16::from_be_bytes([resp.data[3], resp.data[4]]);
+
+        // bit 0-1: 00 stop, 01 decelerating, 10 standby, 11 operating
+        let running = drive_status & 0b11 == 0b11;
+        // bit 3-4: 00 forward, 01 reverse to forward, 10 forward to reverse, 11 reverse
+        let direction = (drive_status >> 3) & 0b11;
+        let fault = self.error_code != 0;
+
+        self.status = InverterStatus {
+            running,
+            forward_running: running && direction == 0b00,
+            reverse_running: running && direction == 0b11,
+            up_to_frequency: running
+                && self.motor_status.frequency > Frequency::new::<centihertz>(0.0),
+            overload_warning: false,
+            no_function: false,
+            output_frequency_detection: false,
+            abc_fault: fault,
+            fault_occurence: fault,
+        };
+    }
+
+    fn handle_motor_status(&mut self, resp: &ModbusResponse) {
+        // byte count followed by 4 registers
+        if resp.data.len() < 9 {
+            return;
+        }
+
+        let register = |index: usize| {
+            f64::from(u16::from_be_bytes([
+                resp.data[1 + index * 2],
+                resp.data[2 + index * 2],
+            ]))
+        };
+
+        self.motor_status.frequency = Frequency::new::<centihertz>(register(0));
+        self.motor_status.current = ElectricCurrent::new::<deciampere>(register(1));
+        // register 2 is the DC-bus voltage
+        self.motor_status.voltage = ElectricPotential::new::<decivolt>(register(3));
+    }
+
+    fn handle_response(&mut self, control_request_type: u32) {
+        let Ok(response_type) = DeltaVfdERequests::try_from(control_request_type) else {
+            return;
+        };
+
+        let Some(response) = self.modbus_serial_interface.get_response().cloned() else {
+            return;
+        };
+
+        match response_type {
+            DeltaVfdERequests::ReadStatus => self.handle_status(&response),
+            DeltaVfdERequests::ReadMotorStatus => self.handle_motor_status(&response),
+            _ => {}
+        }
+    }
+
+    async fn act_async(&mut self, now: Instant) {
+        if !self.modbus_serial_interface.is_initialized() {
+            if self.modbus_serial_interface.initialize().await {
+                self.queue(DeltaVfdERequests::StopMotor);
+            }
+            return;
+        }
+
+        self.queue(DeltaVfdERequests::ReadStatus);
+        self.queue(DeltaVfdERequests::ReadMotorStatus);
+        self.modbus_serial_interface.act(now).await;
+        self.handle_response(self.modbus_serial_interface.last_message_id);
+    }
+}
+
+impl FrequencyInverter for DeltaVfdE {
+    fn act(&mut self, now: Instant) {
+        // Only poll once, the machine loop must not wait for the serial device.
+        // Every await in act_async is a lock of the EL6021, if it is held elsewhere nothing
+        // has been sent or read yet and the same step is repeated in the next cycle.
+        let _done = smol::future::block_on(smol::future::poll_once(self.act_async(now)));
+    }
+
+    fn start(&mut self, direction: RotationDirection) {
+        match direction {
+            RotationDirection::Forward => self.queue(DeltaVfdERequests::StartForwardRotation),
+            RotationDirection::Reverse => self.queue(DeltaVfdERequests::StartReverseRotation),
+        }
+    }
+
+    fn stop(&mut self) {
+        self.queue(DeltaVfdERequests::StopMotor);
+    }
+
+    fn set_frequency_target(&mut self, frequency: Frequency) {
+        let mut modbus_request = DeltaVfdERequests::WriteFrequency.modbus_request(self.slave_id);
+        let value = frequency.get::<centihertz>().round() as u16;
+        let bytes = value.to_be_bytes();
+        modbus_request.data[2] = bytes[0];
+        modbus_request.data[3] = bytes[1];
+        self.add_request(DeltaVfdERequests::WriteFrequency, modbus_request);
+    }
+
+    fn get_motor_status(&self) -> MotorStatus {
+        self.motor_status
+    }
+
+    fn get_status(&self) -> InverterStatus {
+        self.status
+    }
+
+    fn get_fault(&self) -> Option<InverterFault> {
+        if self.error_code == 0 {
+            return None;
+        }
+
+        let (name, description) =
+            decode_error_code(self.error_code).unwrap_or(("---", "Unknown fault"));
+        Some(InverterFault {
+            code: self.error_code,
+            name: name.to_string(),
+            description: description.to_string(),
+        })
+    }
+
+    fn reset(&mut self) {
+        self.queue(DeltaVfdERequests::Reset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_requests() {
+        let request = DeltaVfdERequests::StartReverseRotation.modbus_request(1);
+        assert_eq!(
+            request.function_code,
+            ModbusFunctionCode::PresetHoldingRegister
+        );
+        assert_eq!(request.data, vec![0x20, 0x00, 0x00, 0x22]);
+
+        let request = DeltaVfdERequests::ReadMotorStatus.modbus_request(1);
+        assert_eq!(
+            request.function_code,
+            ModbusFunctionCode::ReadHoldingRegister
+        );
+        assert_eq!(request.data, vec![0x21, 0x03, 0x00, 0x04]);
+    }
+
+    #[test]
+    fn test_request_ids_roundtrip() {
+        for request in [
+            DeltaVfdERequests::Reset,
+            DeltaVfdERequests::StopMotor,
+            DeltaVfdERequests::StartForwardRotation,
+            DeltaVfdERequests::StartReverseRotation,
+            DeltaVfdERequests::WriteFrequency,
+            DeltaVfdERequests::ReadStatus,
+            DeltaVfdERequests::ReadMotorStatus,
+        ] {
+            assert_eq!(DeltaVfdERequests::try_from(u32::from(request)), Ok(request));
+        }
+    }
+}
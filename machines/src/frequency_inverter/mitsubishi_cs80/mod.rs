@@ -0,0 +1,1095 @@
+use bitvec::{order::Lsb0, slice::BitSlice};
+use control_core::modbus::{
+    ModbusFunctionCode, ModbusRequest, ModbusResponse,
+    modbus_serial_interface::ModbusSerialInterface,
+};
+use ethercat_hal::io::serial_interface::SerialInterface;
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, Instant};
+use units::electric_current::centiampere;
+use units::electric_potential::centivolt;
+use units::f64::*;
+use units::frequency::centihertz;
+
+use super::{
+    FrequencyInverter, FrequencyInverterType, InverterFault, InverterStatus, MotorStatus,
+    RotationDirection,
+    parameters::{
+        FinishedBackup, ParameterBackup, ParameterClear, ParameterTransfer, ParameterTransferKind,
+        validate_backup_name,
+    },
+};
+
+/// Highest parameter number reachable through the 41000+ register range
+pub const MAX_PARAMETER_NUMBER: u16 = 999;
+
+/// Number of entries in the fault history (Register 40501 - 40508)
+pub const FAULT_HISTORY_LENGTH: usize = 8;
+
+/// Parameters saved by a backup, these are the ones we change when commissioning an extruder
+/// Communication parameters (Pr. 117 - 124, 340, 549, 551) are included so a replacement inverter can be set up without its keypad
+pub const BACKUP_PARAMETERS: &[u16] = &[
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 22, 23, 24, 25, 26,
+    27, 29, 30, 31, 32, 33, 34, 35, 36, 37, 40, 41, 42, 43, 44, 45, 46, 47, 48, 51, 52, 55, 56, 57,
+    58, 59, 60, 65, 66, 67, 68, 69, 71, 72, 73, 74, 75, 77, 78, 79, 80, 117, 118, 119, 120, 121,
+    122, 123, 124, 125, 126, 127, 160, 340, 549, 551,
+];
+
+/// Writing these changes how we talk to the inverter, so a restore writes them last
+const COMMUNICATION_PARAMETERS: &[u16] = &[117, 118, 119, 120, 121, 122, 123, 124, 340, 549, 551];
+
+/// A parameter operation that did not get a response within this time is dropped
+const PARAMETER_OPERATION_TIMEOUT: Duration = Duration::from_secs(6);
+
+/// Specifies all System environment Variables
+/// Register addresses are calculated as follows: Register-value 40002 -> address: 40002-40001 -> actual address in request:0x1
+#[derive(Debug, Clone, Copy)]
+enum MitsubishiCS80Register {
+    /// Register 40002
+    InverterReset,
+    /// Register 40003
+    ParameterClear,
+    /// Register 40004
+    AllParameterClear,
+    /// Register 40006
+    ParamClearNonCommunication,
+    /// Register 40007
+    AllParameterClearNonCommunication,
+    /// Register 40009
+    InverterStatusAndControl,
+    /// Register 40010
+    //OperationModeAndSetting,
+    /// Register 40014
+    RunningFrequencyRAM,
+    /// Register 40015
+    //RunningFrequencyEEPROM,
+    /// Register 40201
+    MotorStatus,
+    /// Register 40501 - 40508, latest fault first
+    FaultHistory,
+    /// Register 41000 + Pr. number
+    Parameter(u16),
+}
+
+impl MitsubishiCS80Register {
+    const fn address(self) -> u16 {
+        match self {
+            Self::InverterReset => 0x1,
+            Self::ParameterClear => 0x2,
+            Self::AllParameterClear => 0x3,
+            Self::ParamClearNonCommunication => 0x5,
+            Self::AllParameterClearNonCommunication => 0x6,
+            Self::InverterStatusAndControl => 0x8,
+            Self::RunningFrequencyRAM => 0x0d,
+            Self::MotorStatus => 0x00C8, // a0x00C8 = frequency , 0x00C9 = current ,0x00C10 = voltage
+            Self::FaultHistory => 0x01F4,
+            Self::Parameter(number) => 0x03E7 + number, // 41000 - 40001 = 999
+        }
+    }
+
+    const fn address_be_bytes(self) -> [u8; 2] {
+        self.address().to_be_bytes()
+    }
+}
+
+/// Values that have to be written to the parameter clear registers to trigger the clear
+const PARAMETER_CLEAR_VALUE: u16 = 0x965A;
+const ALL_PARAMETER_CLEAR_VALUE: u16 = 0x99AA;
+const PARAMETER_CLEAR_NON_COMMUNICATION_VALUE: u16 = 0x5A96;
+const ALL_PARAMETER_CLEAR_NON_COMMUNICATION_VALUE: u16 = 0xAA99;
+
+/// These Requests Serve as Templates for controlling the inverter
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub enum MitsubishiCS80Requests {
+    None,
+    /// Register 40002, Reset/Restart the Inverter
+    ResetInverter,
+    /// Register 40004, Clear ALL parameters
+    ClearAllParameters,
+    /// Register 40006, Clear a non communication parameter
+    ClearNonCommunicationParameter,
+    /// Register 40007, Clear all Non Communication related Parameters
+    ClearNonCommunicationParameters,
+    /// Register 40009, Read Inverter Status
+    ReadInverterStatus,
+    /// Register 40009, Stops the Motor
+    StopMotor,
+    /// Register 40009, Starts the Motor in Forward Rotation
+    StartForwardRotation,
+    /// Register 40009, Starts the Motor in Reverse Rotation
+    StartReverseRotation,
+    /// Register 40014, Read the current frequency the motor runs at (RAM)
+    ReadRunningFrequency,
+    /// Register 40014, Write the frequency
+    WriteRunningFrequency,
+    /// Read Register 40201, 40202 and 40203 frequency,current and voltage
+    ReadMotorStatus,
+    /// Register 41000 + Pr. number, Write a parameter
+    WriteParameter,
+    /// Register 40501 - 40508, Read the fault history
+    ReadFaultHistory,
+    /// Register 40003, Clear parameters including communication parameters
+    ClearParameters,
+    /// Register 41000 + Pr. number, Read a parameter
+    ReadParameter,
+}
+
+impl From<MitsubishiCS80Requests> for u32 {
+    fn from(request: MitsubishiCS80Requests) -> Self {
+        request as Self
+    }
+}
+
+impl TryFrom<u32> for MitsubishiCS80Requests {
+    type Error = ();
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::None),
+            1 => Ok(Self::ResetInverter),
+            2 => Ok(Self::ClearAllParameters),
+            3 => Ok(Self::ClearNonCommunicationParameter),
+            4 => Ok(Self::ClearNonCommunicationParameters),
+            5 => Ok(Self::ReadInverterStatus),
+            6 => Ok(Self::StopMotor),
+            7 => Ok(Self::StartForwardRotation),
+            8 => Ok(Self::StartReverseRotation),
+            9 => Ok(Self::ReadRunningFrequency),
+            10 => Ok(Self::WriteRunningFrequency),
+            11 => Ok(Self::ReadMotorStatus),
+            12 => Ok(Self::WriteParameter),
+            13 => Ok(Self::ReadFaultHistory),
+            14 => Ok(Self::ClearParameters),
+            15 => Ok(Self::ReadParameter),
+            _ => Err(()),
+        }
+    }
+}
+
+/*
+    MitsubishiModbusRequest get executed by their priority
+    Start and StopMotor are highest priority while writeRunningFrequency and readMotorFrequency are one lower
+    lets say we had StartMotor and readMotorFrequency the order of execution is:
+    1. StartMotor
+    2. readMotorFrequency
+
+    this is because StartMotor is higher priority
+    Since the events do not need to be pushed into a queue this makes the inverter operation more stable
+
+    Lets say one request "A" with priority 1 and one with 2 "B" are queued up, assume that request B is frequently used
+    1. Request "B" is executed due to higher priority
+    2. When B is added again request A has the same priority because it was ignored. B is executed once again
+    3. B is added again, now A has an effective priority of 3, which is higher then B
+*/
+impl From<MitsubishiCS80Requests> for MitsubishiCS80Request {
+    fn from(request: MitsubishiCS80Requests) -> Self {
+        match request {
+            MitsubishiCS80Requests::WriteRunningFrequency => {
+                let reg_bytes = MitsubishiCS80Register::RunningFrequencyRAM.address_be_bytes();
+                Self::new(
+                    ModbusRequest {
+                        slave_id: 1,
+                        function_code: ModbusFunctionCode::PresetHoldingRegister,
+                        data: vec![reg_bytes[0], reg_bytes[1], 0x0, 0x0],
+                    },
+                    request,
+                    RequestType::OperationCommand,
+                    u16::MAX - 1,
+                )
+            }
+            MitsubishiCS80Requests::ReadInverterStatus => {
+                let reg_bytes = MitsubishiCS80Register::InverterStatusAndControl.address_be_bytes();
+                Self::new(
+                    ModbusRequest {
+                        slave_id: 1,
+                        function_code: ModbusFunctionCode::ReadHoldingRegister,
+                        data: vec![reg_bytes[0], reg_bytes[1], 0x00, 0x01], // Read 1 register
+                    },
+                    request,
+                    RequestType::OperationCommand,
+                    u16::MAX - 6, // Priority is -6 because we do not want to know the status as frequently as the frequency
+                )
+            }
+            MitsubishiCS80Requests::StopMotor => {
+                let reg_bytes = MitsubishiCS80Register::InverterStatusAndControl.address_be_bytes();
+                Self::new(
+                    ModbusRequest {
+                        slave_id: 1,
+                        function_code: ModbusFunctionCode::PresetHoldingRegister,
+                        data: vec![reg_bytes[0], reg_bytes[1], 0x00, 0x01], // Value 1 to stop
+                    },
+                    request,
+                    RequestType::OperationCommand,
+                    u16::MAX, // StopMotor should have highest priority
+                )
+            }
+            MitsubishiCS80Requests::StartForwardRotation => {
+                let reg_bytes = MitsubishiCS80Register::InverterStatusAndControl.address_be_bytes();
+                Self::new(
+                    ModbusRequest {
+                        slave_id: 1,
+                        function_code: ModbusFunctionCode::PresetHoldingRegister,
+                        data: vec![reg_bytes[0], reg_bytes[1], 0, 0b00000010], // Value 2 for forward rotation
+                    },
+                    request,
+                    RequestType::OperationCommand,
+                    u16::MAX - 1,
+                )
+            }
+            MitsubishiCS80Requests::StartReverseRotation => {
+                let reg_bytes = MitsubishiCS80Register::InverterStatusAndControl.address_be_bytes();
+                Self::new(
+                    ModbusRequest {
+                        slave_id: 1,
+                        function_code: ModbusFunctionCode::PresetHoldingRegister,
+                        data: vec![reg_bytes[0], reg_bytes[1], 0, 0b00000100], // Value 4 for reverse rotation
+                    },
+                    request,
+                    RequestType::OperationCommand,
+                    u16::MAX - 1,
+                )
+            }
+            MitsubishiCS80Requests::ReadRunningFrequency => {
+                let reg_bytes = MitsubishiCS80Register::RunningFrequencyRAM.address_be_bytes();
+                Self::new(
+                    ModbusRequest {
+                        slave_id: 1,
+                        function_code: ModbusFunctionCode::ReadHoldingRegister,
+                        data: vec![reg_bytes[0], reg_bytes[1], 0x00, 0x01], // Read 1 register
+                    },
+                    request,
+                    RequestType::OperationCommand,
+                    u16::MAX - 4,
+                )
+            }
+            MitsubishiCS80Requests::ReadMotorStatus => {
+                let reg_bytes = MitsubishiCS80Register::MotorStatus.address_be_bytes();
+                Self::new(
+                    ModbusRequest {
+                        slave_id: 1,
+                        function_code: ModbusFunctionCode::ReadHoldingRegister,
+                        data: vec![reg_bytes[0], reg_bytes[1], 0x0, 0x3], // read 3 registers: 0x00C8 = frequency , 0x00C9 = current ,0x00C10 = voltage
+                    },
+                    request,
+                    RequestType::OperationCommand,
+                    u16::MAX - 2,
+                )
+            }
+            MitsubishiCS80Requests::ResetInverter => {
+                let reg_bytes = MitsubishiCS80Register::InverterReset.address_be_bytes();
+                Self::new(
+                    ModbusRequest {
+                        slave_id: 1,
+                        function_code: ModbusFunctionCode::PresetHoldingRegister,
+                        data: vec![reg_bytes[0], reg_bytes[1], 0x0, 0b00000001],
+                    },
+                    request,
+                    RequestType::Reset,
+                    u16::MAX,
+                )
+            }
+            MitsubishiCS80Requests::ReadFaultHistory => {
+                let reg_bytes = MitsubishiCS80Register::FaultHistory.address_be_bytes();
+                Self::new(
+                    ModbusRequest {
+                        slave_id: 1,
+                        function_code: ModbusFunctionCode::ReadHoldingRegister,
+                        data: vec![reg_bytes[0], reg_bytes[1], 0x00, FAULT_HISTORY_LENGTH as u8],
+                    },
+                    request,
+                    RequestType::ReadWrite,
+                    u16::MAX - 8, // Only needed after a fault occured
+                )
+            }
+            // The register address and value are filled in by `ParameterOperation::request`
+            MitsubishiCS80Requests::WriteParameter => Self::new(
+                ModbusRequest {
+                    slave_id: 1,
+                    function_code: ModbusFunctionCode::PresetHoldingRegister,
+                    data: vec![0x0, 0x0, 0x0, 0x0],
+                },
+                request,
+                RequestType::ReadWrite,
+                u16::MAX - 8,
+            ),
+            MitsubishiCS80Requests::ReadParameter => Self::new(
+                ModbusRequest {
+                    slave_id: 1,
+                    function_code: ModbusFunctionCode::ReadHoldingRegister,
+                    data: vec![0x0, 0x0, 0x00, 0x01], // Read 1 register
+                },
+                request,
+                RequestType::ReadWrite,
+                u16::MAX - 8,
+            ),
+            MitsubishiCS80Requests::ClearParameters => Self::parameter_clear(
+                request,
+                MitsubishiCS80Register::ParameterClear,
+                PARAMETER_CLEAR_VALUE,
+            ),
+            MitsubishiCS80Requests::ClearAllParameters => Self::parameter_clear(
+                request,
+                MitsubishiCS80Register::AllParameterClear,
+                ALL_PARAMETER_CLEAR_VALUE,
+            ),
+            MitsubishiCS80Requests::ClearNonCommunicationParameter => Self::parameter_clear(
+                request,
+                MitsubishiCS80Register::ParamClearNonCommunication,
+                PARAMETER_CLEAR_NON_COMMUNICATION_VALUE,
+            ),
+            MitsubishiCS80Requests::ClearNonCommunicationParameters => Self::parameter_clear(
+                request,
+                MitsubishiCS80Register::AllParameterClearNonCommunication,
+                ALL_PARAMETER_CLEAR_NON_COMMUNICATION_VALUE,
+            ),
+
+            // For unimplemented variants, return a default request
+            _ => Self::new(
+                ModbusRequest {
+                    slave_id: 1,
+                    function_code: ModbusFunctionCode::ReadHoldingRegister,
+                    data: vec![0x0, 0x0, 0x0, 0x1],
+                },
+                request,
+                RequestType::None,
+                0,
+            ),
+        }
+    }
+}
+
+// Serialize is needed so we can hash it
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct MitsubishiCS80Status {
+    pub running: bool,
+    pub forward_running: bool,
+    pub reverse_running: bool,
+    pub su: bool,
+    pub ol: bool,
+    pub no_function: bool,
+    pub fu: bool,
+    pub abc_: bool,
+    pub fault_occurence: bool,
+}
+
+impl From<MitsubishiCS80Status> for InverterStatus {
+    fn from(status: MitsubishiCS80Status) -> Self {
+        Self {
+            running: status.running,
+            forward_running: status.forward_running,
+            reverse_running: status.reverse_running,
+            up_to_frequency: status.su,
+            overload_warning: status.ol,
+            no_function: status.no_function,
+            output_frequency_detection: status.fu,
+            abc_fault: status.abc_,
+            fault_occurence: status.fault_occurence,
+        }
+    }
+}
+
+/// Decodes the alarm codes stored in the fault history registers
+/// Returns the name shown on the operation panel and a short description
+pub const fn decode_alarm_code(code: u16) -> Option<(&'static str, &'static str)> {
+    match code {
+        0x10 => Some(("E.OC1", "Overcurrent trip during acceleration")),
+        0x11 => Some(("E.OC2", "Overcurrent trip during constant speed")),
+        0x12 => Some(("E.OC3", "Overcurrent trip during deceleration or stop")),
+        0x20 => Some(("E.OV1", "Regenerative overvoltage trip during acceleration")),
+        0x21 => Some((
+            "E.OV2",
+            "Regenerative overvoltage trip during constant speed",
+        )),
+        0x22 => Some((
+            "E.OV3",
+            "Regenerative overvoltage trip during deceleration or stop",
+        )),
+        0x30 => Some(("E.THT", "Inverter overload trip (electronic thermal relay)")),
+        0x31 => Some(("E.THM", "Motor overload trip (electronic thermal relay)")),
+        0x40 => Some(("E.FIN", "Heat sink overheat")),
+        0x52 => Some(("E.ILF", "Input phase loss")),
+        0x60 => Some(("E.OLT", "Stall prevention stop")),
+        0x70 => Some(("E.BE", "Brake transistor alarm detection")),
+        0x80 => Some(("E.GF", "Output side earth (ground) fault overcurrent")),
+        0x81 => Some(("E.LF", "Output phase loss")),
+        0x90 => Some(("E.OHT", "External thermal relay operation")),
+        0xB0 => Some(("E.PE", "Parameter storage device fault")),
+        0xB1 => Some(("E.PUE", "PU disconnection")),
+        0xB2 => Some(("E.RET", "Retry count excess")),
+        0xC0 => Some(("E.CPU", "CPU fault")),
+        0xC5 => Some(("E.IOH", "Inrush current limit circuit fault")),
+        0xC7 => Some(("E.AIE", "Analog input fault")),
+        _ => None,
+    }
+}
+
+fn decode_fault(code: u16) -> InverterFault {
+    let (name, description) = decode_alarm_code(code).unwrap_or(("E.---", "Unknown fault"));
+    InverterFault {
+        code,
+        name: name.to_string(),
+        description: description.to_string(),
+    }
+}
+
+#[derive(Debug)]
+pub struct MitsubishiCS80 {
+    // Communication
+    pub status: MitsubishiCS80Status,
+    pub motor_status: MotorStatus,
+    pub modbus_serial_interface: ModbusSerialInterface,
+    pub last_ts: Instant,
+    /// Alarm code of the latest fault, 0 if there is none
+    pub latest_fault_code: u16,
+    /// Alarm codes of the fault history, latest first
+    pub fault_history: [u16; FAULT_HISTORY_LENGTH],
+    /// Parameters read or written so far, Pr. number -> raw value
+    pub parameters: BTreeMap<u16, u16>,
+    pending_parameter_operations: VecDeque<ParameterOperation>,
+    parameter_operation_in_flight: Option<(ParameterOperation, Instant)>,
+    parameter_transfer: Option<ParameterTransfer>,
+    /// Name of the running backup
+    backup_name: Option<String>,
+    finished_backup: Option<FinishedBackup>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum RequestType {
+    None,
+    /// Monitoring, Operation (start,stop etc) command, frequency setting (RAM), less than 12 milliseconds timeout for Response
+    OperationCommand,
+    /// Parameter Read/Write and Frequency (EEPROM), Less than 30 milliseconds timeout for Response
+    ReadWrite,
+    /// Less than 5 seconds timeout for Response
+    ParamClear,
+    /// Supposedly no waiting time, however inverter takes a while to start ~300ms should be more than enough
+    Reset,
+}
+
+impl RequestType {
+    const fn timeout_duration(self) -> Duration {
+        match self {
+            Self::OperationCommand => Duration::from_millis(12),
+            Self::ReadWrite => Duration::from_millis(30),
+            Self::ParamClear => Duration::from_millis(5000),
+            Self::Reset => Duration::from_millis(900),
+            Self::None => Duration::from_millis(12),
+        }
+    }
+}
+
+// We need to know from the request queue which events are of what operation type, so that the correct timeout can be used
+#[derive(Debug, Clone)]
+pub struct MitsubishiCS80Request {
+    request: ModbusRequest,
+    control_request_type: MitsubishiCS80Requests,
+    request_type: RequestType,
+    priority: u16,
+}
+
+impl MitsubishiCS80Request {
+    const fn new(
+        request: ModbusRequest,
+        control_request_type: MitsubishiCS80Requests,
+        request_type: RequestType,
+        priority: u16,
+    ) -> Self {
+        Self {
+            request,
+            control_request_type,
+            request_type,
+            priority,
+        }
+    }
+
+    fn parameter_clear(
+        request: MitsubishiCS80Requests,
+        register: MitsubishiCS80Register,
+        value: u16,
+    ) -> Self {
+        let reg_bytes = register.address_be_bytes();
+        let value_bytes = value.to_be_bytes();
+        Self::new(
+            ModbusRequest {
+                slave_id: 1,
+                function_code: ModbusFunctionCode::PresetHoldingRegister,
+                data: vec![reg_bytes[0], reg_bytes[1], value_bytes[0], value_bytes[1]],
+            },
+            request,
+            RequestType::ParamClear,
+            u16::MAX - 8,
+        )
+    }
+}
+
+/// Parameter accesses are queued and sent one at a time,
+/// because the serial interface only keeps one request per request type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParameterOperation {
+    Read(u16),
+    Write(u16, u16),
+    Clear(ParameterClear),
+}
+
+impl ParameterOperation {
+    fn request(self) -> MitsubishiCS80Request {
+        match self {
+            Self::Read(number) => {
+                let mut request: MitsubishiCS80Request =
+                    MitsubishiCS80Requests::ReadParameter.into();
+                let reg_bytes = MitsubishiCS80Register::Parameter(number).address_be_bytes();
+                request.request.data[0] = reg_bytes[0];
+                request.request.data[1] = reg_bytes[1];
+                request
+            }
+            Self::Write(number, value) => {
+                let mut request: MitsubishiCS80Request =
+                    MitsubishiCS80Requests::WriteParameter.into();
+                let reg_bytes = MitsubishiCS80Register::Parameter(number).address_be_bytes();
+                let value_bytes = value.to_be_bytes();
+                request.request.data =
+                    vec![reg_bytes[0], reg_bytes[1], value_bytes[0], value_bytes[1]];
+                request
+            }
+            Self::Clear(clear) => match clear {
+                ParameterClear::Parameters => {
+                    MitsubishiCS80Requests::ClearNonCommunicationParameter
+                }
+                ParameterClear::AllParameters => {
+                    MitsubishiCS80Requests::ClearNonCommunicationParameters
+                }
+                ParameterClear::ParametersIncludingCommunication => {
+                    MitsubishiCS80Requests::ClearParameters
+                }
+                ParameterClear::AllParametersIncludingCommunication => {
+                    MitsubishiCS80Requests::ClearAllParameters
+                }
+            }
+            .into(),
+        }
+    }
+}
+
+impl MitsubishiCS80 {
+    pub fn new(serial_interface: SerialInterface) -> Self {
+        Self {
+            modbus_serial_interface: ModbusSerialInterface::new(serial_interface),
+            last_ts: Instant::now(),
+            motor_status: MotorStatus::default(),
+            status: MitsubishiCS80Status::default(),
+            latest_fault_code: 0,
+            fault_history: [0; FAULT_HISTORY_LENGTH],
+            parameters: BTreeMap::new(),
+            pending_parameter_operations: VecDeque::new(),
+            parameter_operation_in_flight: None,
+            parameter_transfer: None,
+            backup_name: None,
+            finished_backup: None,
+        }
+    }
+
+    fn handle_fault_history(&mut self, resp: &ModbusResponse) {
+        if resp.data.len() < 1 + FAULT_HISTORY_LENGTH * 2 {
+            return;
+        }
+        // the alarm code is stored in the lower byte of every register
+        for (i, code) in self.fault_history.iter_mut().enumerate() {
+            *code = u16::from(resp.data[2 + i * 2]);
+        }
+        if self.status.fault_occurence {
+            self.latest_fault_code = self.fault_history[0];
+        }
+    }
+
+    fn handle_read_parameter(&mut self, resp: &ModbusResponse) {
+        let Some((ParameterOperation::Read(number), _)) = self.parameter_operation_in_flight else {
+            return;
+        };
+        if resp.data.len() < 3 {
+            return;
+        }
+        let value = u16::from_be_bytes([resp.data[1], resp.data[2]]);
+        self.parameters.insert(number, value);
+        self.finish_parameter_operation();
+    }
+
+    fn handle_write_parameter(&mut self) {
+        let Some((ParameterOperation::Write(number, value), _)) =
+            self.parameter_operation_in_flight
+        else {
+            return;
+        };
+        self.parameters.insert(number, value);
+        self.finish_parameter_operation();
+    }
+
+    fn handle_parameter_clear(&mut self) {
+        let Some((ParameterOperation::Clear(_), _)) = self.parameter_operation_in_flight else {
+            return;
+        };
+        // the cached values are stale now
+        self.parameters.clear();
+        self.finish_parameter_operation();
+    }
+
+    /// Called once the inverter answered the parameter operation in flight
+    fn finish_parameter_operation(&mut self) {
+        self.parameter_operation_in_flight = None;
+        self.advance_parameter_transfer();
+    }
+
+    /// Counts the finished operation towards a running backup/restore and hands out the backup once complete
+    fn advance_parameter_transfer(&mut self) {
+        let Some(transfer) = self.parameter_transfer.as_mut() else {
+            return;
+        };
+        if transfer.is_finished() {
+            return;
+        }
+        transfer.done += 1;
+        if transfer.done < transfer.total {
+            return;
+        }
+
+        if let (ParameterTransferKind::Backup, Some(name)) =
+            (transfer.kind, self.backup_name.take())
+        {
+            let backup = ParameterBackup {
+                inverter: FrequencyInverterType::MitsubishiCS80,
+                parameters: BACKUP_PARAMETERS
+                    .iter()
+                    .filter_map(|number| Some((*number, *self.parameters.get(number)?)))
+                    .collect(),
+            };
+            self.finished_backup = Some(FinishedBackup {
+                name,
+                result: Ok(backup),
+            });
+        }
+    }
+
+    /// Sends the next queued parameter operation once the previous one was answered
+    fn act_parameter_operations(&mut self, now: Instant) {
+        if let Some((operation, started)) = self.parameter_operation_in_flight {
+            if now.duration_since(started) < PARAMETER_OPERATION_TIMEOUT {
+                return;
+            }
+            // Parameters the inverter does not have are answered with an exception, which we can't parse
+            tracing::warn!("Inverter did not answer {:?}", operation);
+            self.parameter_operation_in_flight = None;
+
+            if self.is_parameter_transfer_running() {
+                match operation {
+                    // missing parameters are left out of the backup
+                    ParameterOperation::Read(_) => self.advance_parameter_transfer(),
+                    _ => {
+                        let error = format!("Inverter did not answer {operation:?}");
+                        if let Some(transfer) = self.parameter_transfer.as_mut() {
+                            transfer.error = Some(error.clone());
+                        }
+                        self.pending_parameter_operations.clear();
+                        if let Some(name) = self.backup_name.take() {
+                            self.finished_backup = Some(FinishedBackup {
+                                name,
+                                result: Err(error),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        let Some(operation) = self.pending_parameter_operations.pop_front() else {
+            return;
+        };
+        self.add_request(operation.request());
+        self.parameter_operation_in_flight = Some((operation, now));
+    }
+
+    fn is_parameter_transfer_running(&self) -> bool {
+        self.parameter_transfer
+            .as_ref()
+            .is_some_and(|transfer| !transfer.is_finished())
+    }
+
+    fn queue_parameter_operation(
+        &mut self,
+        operation: ParameterOperation,
+    ) -> Result<(), anyhow::Error> {
+        if self.is_parameter_transfer_running() {
+            return Err(anyhow::anyhow!(
+                "A parameter backup or restore is still running"
+            ));
+        }
+        match operation {
+            ParameterOperation::Read(number) | ParameterOperation::Write(number, _)
+                if number > MAX_PARAMETER_NUMBER =>
+            {
+                return Err(anyhow::anyhow!("Pr. {} is out of range", number));
+            }
+            _ => {}
+        }
+        self.pending_parameter_operations.push_back(operation);
+        Ok(())
+    }
+
+    fn handle_motor_status(&mut self, resp: &ModbusResponse) {
+        if resp.data.len() >= 7 {
+            let freq_bytes = &resp.data[1..3]; // bytes 1 and 2 are needed
+            let raw_frequency = u16::from_be_bytes([freq_bytes[0], freq_bytes[1]]) as f64;
+            self.motor_status.frequency = Frequency::new::<centihertz>(raw_frequency);
+
+            let electric_current_bytes = &resp.data[3..5];
+            let raw_current =
+                u16::from_be_bytes([electric_current_bytes[0], electric_current_bytes[1]]) as f64;
+            self.motor_status.current = ElectricCurrent::new::<centiampere>(raw_current);
+
+            let voltage_current_bytes = &resp.data[5..7];
+            let raw_voltage =
+                u16::from_be_bytes([voltage_current_bytes[0], voltage_current_bytes[1]]) as f64;
+            self.motor_status.voltage = ElectricPotential::new::<centivolt>(raw_voltage);
+        }
+    }
+
+    fn handle_read_inverter_status(&mut self, resp: &ModbusResponse) {
+        if resp.data.len() < 3 {
+            return;
+        }
+
+        let status_bytes: [u8; 2] = match resp.data[1..3].try_into() {
+            Ok(bytes) => bytes,
+            Err(_) => return,
+        };
+
+        let bits: &BitSlice<u8, Lsb0> = BitSlice::<_, Lsb0>::from_slice(&status_bytes);
+        if bits.len() >= 16 {
+            self.status = MitsubishiCS80Status {
+                fault_occurence: bits[7],
+                running: bits[8],
+                forward_running: bits[9],
+                reverse_running: bits[10],
+                su: bits[11],
+                ol: bits[12],
+                no_function: bits[13],
+                fu: bits[14],
+                abc_: bits[15],
+            };
+        }
+    }
+
+    fn handle_response(&mut self, control_request_type: u32) {
+        let response_type = match MitsubishiCS80Requests::try_from(control_request_type) {
+            Ok(request_type) => request_type,
+            Err(_) => return,
+        };
+
+        let Some(response) = self.modbus_serial_interface.get_response().cloned() else {
+            return;
+        };
+
+        match response_type {
+            MitsubishiCS80Requests::ReadInverterStatus => {
+                self.handle_read_inverter_status(&response);
+            }
+            MitsubishiCS80Requests::ReadMotorStatus => {
+                self.handle_motor_status(&response);
+            }
+            MitsubishiCS80Requests::ReadFaultHistory => {
+                self.handle_fault_history(&response);
+            }
+            MitsubishiCS80Requests::ReadParameter => {
+                self.handle_read_parameter(&response);
+            }
+            MitsubishiCS80Requests::WriteParameter => {
+                self.handle_write_parameter();
+            }
+            MitsubishiCS80Requests::ClearParameters
+            | MitsubishiCS80Requests::ClearAllParameters
+            | MitsubishiCS80Requests::ClearNonCommunicationParameter
+            | MitsubishiCS80Requests::ClearNonCommunicationParameters => {
+                self.handle_parameter_clear();
+            }
+            // Other request types don't need response handling
+            _ => {}
+        }
+    }
+
+    fn convert_frequency_to_word(&self, frequency: Frequency) -> u16 {
+        let scaled = frequency.get::<centihertz>(); // Convert Hz to 0.01 Hz units
+        scaled.round() as u16
+    }
+
+    fn add_request(&mut self, request: MitsubishiCS80Request) {
+        let no_response_expected = matches!(
+            request.control_request_type,
+            MitsubishiCS80Requests::None | MitsubishiCS80Requests::ResetInverter
+        );
+
+        self.modbus_serial_interface.add_request(
+            request.control_request_type.into(),
+            request.priority as u32,
+            request.request,
+            no_response_expected,
+            // 5 seconds for a parameter clear don't fit into u32 nanoseconds,
+            // this is fine since the serial interface keeps waiting for the response afterwards
+            Some(
+                u32::try_from(request.request_type.timeout_duration().as_nanos())
+                    .unwrap_or(u32::MAX),
+            ),
+        );
+    }
+
+    pub fn stop_motor(&mut self) {
+        self.add_request(MitsubishiCS80Requests::StopMotor.into());
+    }
+
+    pub fn set_frequency_target(&mut self, frequency: Frequency) {
+        let mut request: MitsubishiCS80Request =
+            MitsubishiCS80Requests::WriteRunningFrequency.into();
+        let result = self.convert_frequency_to_word(frequency);
+        let bytes = result.to_le_bytes();
+        request.request.data[2] = bytes[1];
+        request.request.data[3] = bytes[0];
+
+        self.add_request(request);
+    }
+
+    pub fn set_rotation(&mut self, direction: RotationDirection) {
+        let request = match direction {
+            RotationDirection::Forward => MitsubishiCS80Requests::StartForwardRotation,
+            RotationDirection::Reverse => MitsubishiCS80Requests::StartReverseRotation,
+        };
+        self.add_request(request.into());
+    }
+
+    pub fn reset_inverter(&mut self) {
+        self.add_request(MitsubishiCS80Requests::ResetInverter.into());
+    }
+
+    pub fn read_fault_history(&mut self) {
+        self.add_request(MitsubishiCS80Requests::ReadFaultHistory.into());
+    }
+
+    pub fn backup_parameters(&mut self, name: &str) -> Result<(), anyhow::Error> {
+        validate_backup_name(name)?;
+        if self.is_parameter_transfer_running() {
+            return Err(anyhow::anyhow!(
+                "A parameter backup or restore is still running"
+            ));
+        }
+        // parameters the inverter does not answer must not end up in the backup with a stale value
+        for number in BACKUP_PARAMETERS {
+            self.parameters.remove(number);
+        }
+        self.pending_parameter_operations.extend(
+            BACKUP_PARAMETERS
+                .iter()
+                .map(|number| ParameterOperation::Read(*number)),
+        );
+        self.parameter_transfer = Some(ParameterTransfer::new(
+            ParameterTransferKind::Backup,
+            BACKUP_PARAMETERS.len(),
+        ));
+        self.backup_name = Some(name.to_string());
+        self.finished_backup = None;
+        Ok(())
+    }
+
+    pub fn restore_parameters(&mut self, backup: ParameterBackup) -> Result<(), anyhow::Error> {
+        if self.is_parameter_transfer_running() {
+            return Err(anyhow::anyhow!(
+                "A parameter backup or restore is still running"
+            ));
+        }
+        // most parameters can only be written while the motor is stopped
+        if self.status.running {
+            return Err(anyhow::anyhow!(
+                "Inverter needs to be stopped to restore parameters"
+            ));
+        }
+
+        if backup.inverter != FrequencyInverterType::MitsubishiCS80 {
+            return Err(anyhow::anyhow!(
+                "Backup was made from a {:?}, not a MitsubishiCS80",
+                backup.inverter
+            ));
+        }
+        if let Some(number) = backup
+            .parameters
+            .keys()
+            .find(|number| **number > MAX_PARAMETER_NUMBER)
+        {
+            return Err(anyhow::anyhow!("Pr. {} is out of range", number));
+        }
+
+        let mut parameters: Vec<(u16, u16)> = backup.parameters.into_iter().collect();
+        parameters.sort_by_key(|(number, _)| (COMMUNICATION_PARAMETERS.contains(number), *number));
+
+        self.parameter_transfer = Some(ParameterTransfer::new(
+            ParameterTransferKind::Restore,
+            parameters.len(),
+        ));
+        self.pending_parameter_operations.extend(
+            parameters
+                .into_iter()
+                .map(|(number, value)| ParameterOperation::Write(number, value)),
+        );
+        Ok(())
+    }
+
+    pub async fn act(&mut self, now: Instant) {
+        if !self.modbus_serial_interface.is_initialized() {
+            if self.modbus_serial_interface.initialize().await {
+                self.add_request(MitsubishiCS80Requests::ResetInverter.into());
+                self.read_fault_history();
+            }
+            return;
+        }
+
+        self.add_request(MitsubishiCS80Requests::ReadInverterStatus.into());
+        self.add_request(MitsubishiCS80Requests::ReadMotorStatus.into());
+        if self.status.fault_occurence {
+            self.read_fault_history();
+        } else {
+            self.latest_fault_code = 0;
+        }
+        self.act_parameter_operations(now);
+        self.modbus_serial_interface.act(now).await;
+        self.handle_response(self.modbus_serial_interface.last_message_id);
+    }
+}
+
+impl FrequencyInverter for MitsubishiCS80 {
+    fn act(&mut self, now: Instant) {
+        // TODO: move this logic elsewhere or make non async
+        smol::block_on(Self::act(self, now));
+    }
+
+    fn start(&mut self, direction: RotationDirection) {
+        self.set_rotation(direction);
+    }
+
+    fn stop(&mut self) {
+        self.stop_motor();
+    }
+
+    fn set_frequency_target(&mut self, frequency: Frequency) {
+        Self::set_frequency_target(self, frequency);
+    }
+
+    fn get_motor_status(&self) -> MotorStatus {
+        self.motor_status
+    }
+
+    fn get_status(&self) -> InverterStatus {
+        self.status.into()
+    }
+
+    fn get_fault(&self) -> Option<InverterFault> {
+        if !self.status.fault_occurence || self.latest_fault_code == 0 {
+            return None;
+        }
+        Some(decode_fault(self.latest_fault_code))
+    }
+
+    fn reset(&mut self) {
+        self.reset_inverter();
+    }
+
+    fn read_parameter(&mut self, number: u16) -> Result<(), anyhow::Error> {
+        self.queue_parameter_operation(ParameterOperation::Read(number))
+    }
+
+    fn write_parameter(&mut self, number: u16, value: u16) -> Result<(), anyhow::Error> {
+        self.queue_parameter_operation(ParameterOperation::Write(number, value))
+    }
+
+    fn clear_parameters(&mut self, clear: ParameterClear) -> Result<(), anyhow::Error> {
+        if self.status.running {
+            return Err(anyhow::anyhow!(
+                "Inverter needs to be stopped to clear parameters"
+            ));
+        }
+        self.queue_parameter_operation(ParameterOperation::Clear(clear))
+    }
+
+    fn get_parameters(&self) -> BTreeMap<u16, u16> {
+        self.parameters.clone()
+    }
+
+    fn backup_parameters(&mut self, name: &str) -> Result<(), anyhow::Error> {
+        Self::backup_parameters(self, name)
+    }
+
+    fn take_finished_backup(&mut self) -> Option<FinishedBackup> {
+        self.finished_backup.take()
+    }
+
+    fn restore_parameters(&mut self, backup: ParameterBackup) -> Result<(), anyhow::Error> {
+        Self::restore_parameters(self, backup)
+    }
+
+    fn get_parameter_transfer(&self) -> Option<ParameterTransfer> {
+        self.parameter_transfer.clone()
+    }
+
+    fn read_fault_history(&mut self) {
+        Self::read_fault_history(self);
+    }
+
+    fn get_fault_history(&self) -> Vec<InverterFault> {
+        self.fault_history
+            .iter()
+            .filter(|code| **code != 0)
+            .map(|code| decode_fault(*code))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parameter_register_address() {
+        // Pr. 0 -> 41000, Pr. 117 -> 41117
+        assert_eq!(
+            MitsubishiCS80Register::Parameter(0).address(),
+            41000 - 40001
+        );
+        assert_eq!(
+            MitsubishiCS80Register::Parameter(117).address(),
+            41117 - 40001
+        );
+    }
+
+    #[test]
+    fn test_parameter_requests() {
+        let request = ParameterOperation::Write(1, 6000).request();
+        assert_eq!(
+            request.request.function_code,
+            ModbusFunctionCode::PresetHoldingRegister
+        );
+        assert_eq!(request.request.data, vec![0x03, 0xE8, 0x17, 0x70]);
+
+        let request = ParameterOperation::Read(7).request();
+        assert_eq!(
+            request.request.function_code,
+            ModbusFunctionCode::ReadHoldingRegister
+        );
+        assert_eq!(request.request.data, vec![0x03, 0xEE, 0x00, 0x01]);
+
+        let request = ParameterOperation::Clear(ParameterClear::AllParameters).request();
+        assert_eq!(request.request.data, vec![0x00, 0x06, 0xAA, 0x99]);
+    }
+
+    #[test]
+    fn test_request_ids_roundtrip() {
+        for id in 0..16 {
+            let request = MitsubishiCS80Requests::try_from(id).unwrap();
+            assert_eq!(u32::from(request), id);
+        }
+        assert!(MitsubishiCS80Requests::try_from(16).is_err());
+    }
+}
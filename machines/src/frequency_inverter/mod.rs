@@ -0,0 +1,255 @@
+use anyhow::{Result, bail};
+use schemars::JsonSchema;
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::sync::OnceLock;
+use std::time::Instant;
+
+use ethercat_hal::io::serial_interface::SerialInterface;
+use serde::{Deserialize, Serialize};
+use units::f64::*;
+
+use self::{
+    delta_vfd_e::DeltaVfdE,
+    mitsubishi_cs80::MitsubishiCS80,
+    parameters::{FinishedBackup, ParameterBackup, ParameterClear, ParameterTransfer},
+};
+
+pub mod delta_vfd_e;
+pub mod mitsubishi_cs80;
+pub mod parameters;
+
+/// Slave address the inverters ship with
+const DEFAULT_SLAVE_ID: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RotationDirection {
+    Forward,
+    Reverse,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MotorStatus {
+    pub rpm: AngularVelocity,
+    pub frequency: Frequency,
+    pub current: ElectricCurrent,
+    pub voltage: ElectricPotential,
+}
+
+/// Vendor independent view of the inverter status word
+/// Fields that a specific inverter cannot report stay `false`
+// Serialize is needed so we can hash it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct InverterStatus {
+    pub running: bool,
+    pub forward_running: bool,
+    pub reverse_running: bool,
+    /// Output frequency reached the set frequency
+    pub up_to_frequency: bool,
+    pub overload_warning: bool,
+    pub no_function: bool,
+    pub output_frequency_detection: bool,
+    /// Fault relay output
+    pub abc_fault: bool,
+    pub fault_occurence: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct InverterFault {
+    /// Raw alarm code as reported by the inverter
+    pub code: u16,
+    /// Short name as printed on the inverter display, for example "E.OC1"
+    pub name: String,
+    pub description: String,
+}
+
+/// Common interface for frequency inverters (VFDs) driving a motor
+///
+/// Implementations queue their requests internally and only communicate with the inverter in `act`,
+/// so all setters are cheap and can be called from the machine loop.
+pub trait FrequencyInverter: Debug + Send + Sync {
+    /// Drive the communication with the inverter, needs to be called every cycle
+    fn act(&mut self, now: Instant);
+
+    /// Start the motor in the given direction
+    fn start(&mut self, direction: RotationDirection);
+
+    fn stop(&mut self);
+
+    fn set_frequency_target(&mut self, frequency: Frequency);
+
+    /// Last frequency, current and voltage read from the inverter
+    /// `rpm` is left at zero, since the inverter does not know the motor and transmission
+    fn get_motor_status(&self) -> MotorStatus;
+
+    fn get_status(&self) -> InverterStatus;
+
+    /// The currently active fault, if the inverter reports one
+    fn get_fault(&self) -> Option<InverterFault>;
+
+    /// Reset the inverter, this also acknowledges faults
+    fn reset(&mut self);
+
+    /// Queue reading a parameter, the value shows up in `get_parameters` once read
+    fn read_parameter(&mut self, _number: u16) -> Result<(), anyhow::Error> {
+        Err(anyhow::anyhow!(
+            "Inverter does not support parameter access"
+        ))
+    }
+
+    /// Queue writing a raw value to a parameter
+    fn write_parameter(&mut self, _number: u16, _value: u16) -> Result<(), anyhow::Error> {
+        Err(anyhow::anyhow!(
+            "Inverter does not support parameter access"
+        ))
+    }
+
+    /// Reset parameters to their factory defaults
+    fn clear_parameters(&mut self, _clear: ParameterClear) -> Result<(), anyhow::Error> {
+        Err(anyhow::anyhow!(
+            "Inverter does not support parameter access"
+        ))
+    }
+
+    /// Parameters read or written so far, parameter number -> raw value
+    fn get_parameters(&self) -> BTreeMap<u16, u16> {
+        BTreeMap::new()
+    }
+
+    /// Read all parameters relevant for commissioning, the result is handed out by
+    /// `take_finished_backup` under `name` once done
+    fn backup_parameters(&mut self, _name: &str) -> Result<(), anyhow::Error> {
+        Err(anyhow::anyhow!(
+            "Inverter does not support parameter backups"
+        ))
+    }
+
+    /// The backup started by `backup_parameters`, once all parameters were read
+    fn take_finished_backup(&mut self) -> Option<FinishedBackup> {
+        None
+    }
+
+    /// Write all parameters of the backup to the inverter
+    fn restore_parameters(&mut self, _backup: ParameterBackup) -> Result<(), anyhow::Error> {
+        Err(anyhow::anyhow!(
+            "Inverter does not support parameter backups"
+        ))
+    }
+
+    /// Progress of the running or last backup/restore
+    fn get_parameter_transfer(&self) -> Option<ParameterTransfer> {
+        None
+    }
+
+    /// Queue reading the fault history from the inverter
+    fn read_fault_history(&mut self) {}
+
+    /// Faults stored in the inverter, latest first
+    fn get_fault_history(&self) -> Vec<InverterFault> {
+        Vec::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum FrequencyInverterType {
+    #[default]
+    #[serde(alias = "mitsubishi_cs80", alias = "mitsubishi", alias = "cs80")]
+    MitsubishiCS80,
+    #[serde(alias = "delta_vfd_e", alias = "delta", alias = "vfd_e")]
+    DeltaVfdE,
+}
+
+/// Inverter an extruder is built with, `[extruder_inverter]` in the server config
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct FrequencyInverterConfig {
+    pub inverter: FrequencyInverterType,
+    /// Modbus slave address, only used by the Delta VFD-E (Pr. 09-00)
+    /// The Mitsubishi CS80 is always addressed as slave 1
+    pub slave_id: u8,
+}
+
+impl Default for FrequencyInverterConfig {
+    fn default() -> Self {
+        Self {
+            inverter: FrequencyInverterType::default(),
+            slave_id: DEFAULT_SLAVE_ID,
+        }
+    }
+}
+
+impl FrequencyInverterConfig {
+    /// Modbus RTU slave addresses go from 1 to 247, 0 is the broadcast address
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=247).contains(&self.slave_id) {
+            bail!("extruder_inverter.slave_id must be between 1 and 247");
+        }
+        Ok(())
+    }
+
+    pub fn build(self, serial_interface: SerialInterface) -> Box<dyn FrequencyInverter> {
+        match self.inverter {
+            FrequencyInverterType::MitsubishiCS80 => {
+                Box::new(MitsubishiCS80::new(serial_interface))
+            }
+            FrequencyInverterType::DeltaVfdE => {
+                Box::new(DeltaVfdE::new(serial_interface, self.slave_id))
+            }
+        }
+    }
+}
+
+static FREQUENCY_INVERTER_CONFIG: OnceLock<FrequencyInverterConfig> = OnceLock::new();
+
+/// Sets the inverter every extruder is built with from now on, called once at startup
+pub fn set_frequency_inverter_config(config: FrequencyInverterConfig) -> Result<()> {
+    config.validate()?;
+    FREQUENCY_INVERTER_CONFIG
+        .set(config)
+        .map_err(|_| anyhow::anyhow!("The frequency inverter config is already set"))
+}
+
+/// The config set at startup, a Mitsubishi CS80 otherwise
+pub fn frequency_inverter_config() -> FrequencyInverterConfig {
+    *FREQUENCY_INVERTER_CONFIG.get_or_init(FrequencyInverterConfig::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Config {
+        extruder_inverter: FrequencyInverterConfig,
+    }
+
+    fn parse(json: serde_json::Value) -> Result<FrequencyInverterConfig> {
+        let config: Config = serde_json::from_value(json)?;
+        config.extruder_inverter.validate()?;
+        Ok(config.extruder_inverter)
+    }
+
+    #[test]
+    fn test_inverter_config() {
+        assert_eq!(
+            parse(serde_json::json!({ "extruder_inverter": {} })).unwrap(),
+            FrequencyInverterConfig::default()
+        );
+        let config = parse(
+            serde_json::json!({ "extruder_inverter": { "inverter": "delta", "slave_id": 12 } }),
+        )
+        .unwrap();
+        assert_eq!(config.inverter, FrequencyInverterType::DeltaVfdE);
+        assert_eq!(config.slave_id, 12);
+        assert_eq!(
+            parse(serde_json::json!({ "extruder_inverter": { "inverter": "MitsubishiCS80" } }))
+                .unwrap()
+                .inverter,
+            FrequencyInverterType::MitsubishiCS80
+        );
+
+        assert!(parse(serde_json::json!({ "extruder_inverter": { "inverter": "abb" } })).is_err());
+        assert!(parse(serde_json::json!({ "extruder_inverter": { "slave_id": 0 } })).is_err());
+        assert!(parse(serde_json::json!({ "extruder_inverter": { "slave_id": 248 } })).is_err());
+    }
+}
@@ -0,0 +1,112 @@
+use schemars::JsonSchema;
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::FrequencyInverterType;
+
+/// A single inverter parameter with its raw register value
+/// The unit of `value` depends on the parameter, for example Pr. 1 (maximum frequency) is in 0.01 Hz
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct InverterParameter {
+    pub number: u16,
+    pub value: u16,
+}
+
+/// Which parameters should be reset to their factory defaults
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+pub enum ParameterClear {
+    /// All parameters except calibration and communication parameters
+    Parameters,
+    /// All parameters including calibration parameters, except communication parameters
+    AllParameters,
+    /// Same as `Parameters` but also resets the communication parameters
+    ParametersIncludingCommunication,
+    /// Same as `AllParameters` but also resets the communication parameters
+    AllParametersIncludingCommunication,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum ParameterTransferKind {
+    Backup,
+    Restore,
+}
+
+/// Progress of a running or the last finished backup/restore
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct ParameterTransfer {
+    pub kind: ParameterTransferKind,
+    /// Number of parameters already read or written
+    pub done: usize,
+    pub total: usize,
+    /// Set when the transfer was aborted
+    pub error: Option<String>,
+}
+
+impl ParameterTransfer {
+    pub const fn new(kind: ParameterTransferKind, total: usize) -> Self {
+        Self {
+            kind,
+            done: 0,
+            total,
+            error: None,
+        }
+    }
+
+    pub const fn is_finished(&self) -> bool {
+        self.done >= self.total || self.error.is_some()
+    }
+}
+
+/// Contents of a parameter backup
+///
+/// Backups are stored by the server, the inverter only reads and writes the parameters.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ParameterBackup {
+    /// The inverter the backup was made from, restoring to another model is refused
+    pub inverter: FrequencyInverterType,
+    /// Parameter number -> raw value
+    pub parameters: BTreeMap<u16, u16>,
+}
+
+/// A backup that finished reading, or failed to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FinishedBackup {
+    /// Name the backup is stored under
+    pub name: String,
+    pub result: Result<ParameterBackup, String>,
+}
+
+/// Backup names end up as file names, so only letters, digits, `-` and `_` are allowed
+pub fn validate_backup_name(name: &str) -> Result<(), anyhow::Error> {
+    if name.is_empty() || name.len() > 64 {
+        return Err(anyhow::anyhow!(
+            "Backup name must have between 1 and 64 characters"
+        ));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(anyhow::anyhow!(
+            "Backup name {:?} may only contain letters, digits, '-' and '_'",
+            name
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_backup_name() {
+        assert!(validate_backup_name("extruder_2-2026").is_ok());
+        assert!(validate_backup_name("").is_err());
+        assert!(validate_backup_name("../etc/passwd").is_err());
+        assert!(validate_backup_name("/tmp/backup").is_err());
+        assert!(validate_backup_name("backup.json").is_err());
+        assert!(validate_backup_name(&"a".repeat(65)).is_err());
+    }
+}
@@ -1,5 +1,5 @@
 use super::IP20TestMachine;
-use crate::{MachineAct, MachineMessage};
+use crate::{MachineAct, MachineMessage, ShutdownPolicy};
 use std::time::{Duration, Instant};
 
 impl MachineAct for IP20TestMachine {
@@ -24,6 +24,13 @@ impl MachineAct for IP20TestMachine {
         }
     }
 
+    fn shutdown(&mut self, _now: Instant, _policy: &ShutdownPolicy) -> bool {
+        if self.outputs.iter().any(|on| *on) {
+            self.set_all_outputs(false);
+        }
+        true
+    }
+
     fn act_machine_message(&mut self, msg: MachineMessage) {
         match msg {
             MachineMessage::SubscribeNamespace(namespace) => {
@@ -32,9 +39,9 @@ impl MachineAct for IP20TestMachine {
                 self.emit_live_values();
             }
             MachineMessage::UnsubscribeNamespace => self.namespace.namespace = None,
-            MachineMessage::HttpApiJsonRequest(value) => {
+            MachineMessage::HttpApiJsonRequest(value, reply) => {
                 use crate::MachineApi;
-                let _res = self.api_mutate(value);
+                reply.send(self.api_mutate(value));
             }
             MachineMessage::ConnectToMachine(_machine_connection) => {
                 // Does not connect to any Machine; do nothing
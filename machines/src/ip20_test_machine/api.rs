@@ -1,4 +1,5 @@
 use super::IP20TestMachine;
+use crate::api_schema::{MachineApiSchema, MachineSchema};
 use crate::{MachineApi, MachineMessage};
 use control_core::socketio::{
     event::{Event, GenericEvent},
@@ -6,11 +7,12 @@ use control_core::socketio::{
         CacheFn, CacheableEvents, Namespace, NamespaceCacheingLogic, cache_first_and_last_event,
     },
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::sync::Arc;
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, JsonSchema)]
 pub struct StateEvent {
     pub outputs: [bool; 8],
 }
@@ -21,7 +23,7 @@ impl StateEvent {
     }
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, JsonSchema)]
 pub struct LiveValuesEvent {
     pub inputs: [bool; 8],
 }
@@ -37,7 +39,7 @@ pub enum IP20TestMachineEvents {
     LiveValues(Event<LiveValuesEvent>),
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(tag = "action", content = "value")]
 pub enum Mutation {
     SetOutput { index: usize, on: bool },
@@ -72,6 +74,14 @@ impl CacheableEvents<IP20TestMachineEvents> for IP20TestMachineEvents {
     }
 }
 
+impl MachineSchema for IP20TestMachine {
+    fn api_schema() -> MachineApiSchema {
+        MachineApiSchema::new::<Mutation>("IP20TestMachine", Self::MACHINE_IDENTIFICATION)
+            .with_event::<LiveValuesEvent>("LiveValuesEvent")
+            .with_event::<StateEvent>("StateEvent")
+    }
+}
+
 impl MachineApi for IP20TestMachine {
     fn api_get_sender(&self) -> smol::channel::Sender<MachineMessage> {
         self.api_sender.clone()
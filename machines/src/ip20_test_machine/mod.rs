@@ -46,19 +46,19 @@ impl IP20TestMachine {
 
 impl IP20TestMachine {
     pub fn emit_state(&mut self) {
-        let event = StateEvent {
+        let state = StateEvent {
             outputs: self.outputs,
-        }
-        .build();
+        };
+        let event = state.build();
 
         self.namespace.emit(IP20TestMachineEvents::State(event));
     }
 
     pub fn emit_live_values(&mut self) {
-        let event = LiveValuesEvent {
+        let live_values = LiveValuesEvent {
             inputs: self.inputs,
-        }
-        .build();
+        };
+        let event = live_values.build();
 
         self.namespace
             .emit(IP20TestMachineEvents::LiveValues(event));
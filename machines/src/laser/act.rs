@@ -1,6 +1,7 @@
 use super::LaserMachine;
 use crate::MachineAct;
 use crate::MachineMessage;
+use crate::ShutdownPolicy;
 use std::time::{Duration, Instant};
 
 /// Implements the `MachineAct` trait for the `LaserMachine`.
@@ -38,6 +39,16 @@ impl MachineAct for LaserMachine {
             self.emit_live_values();
             self.last_measurement_emit = now;
         }
+
+        if now.duration_since(self.last_statistics_emit) > Duration::from_secs(1) {
+            self.emit_statistics(now);
+            self.last_statistics_emit = now;
+        }
+    }
+
+    /// Only measures, there is nothing to switch off
+    fn shutdown(&mut self, _now: Instant, _policy: &ShutdownPolicy) -> bool {
+        true
     }
 
     fn act_machine_message(&mut self, msg: MachineMessage) {
@@ -56,9 +67,9 @@ impl MachineAct for LaserMachine {
                 }
                 None => todo!(),
             },
-            MachineMessage::HttpApiJsonRequest(value) => {
+            MachineMessage::HttpApiJsonRequest(value, reply) => {
                 use crate::MachineApi;
-                let _res = self.api_mutate(value);
+                reply.send(self.api_mutate(value));
             }
             MachineMessage::ConnectToMachine(_machine_connection) =>
             /*Doesnt connect to any Machine so do nothing*/
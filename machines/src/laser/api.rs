@@ -1,24 +1,32 @@
+use crate::api_schema::{MachineApiSchema, MachineSchema};
 use crate::{MachineApi, MachineMessage};
 
 use super::LaserMachine;
+use super::statistics::StatisticsEvent;
 use control_core::socketio::{
     event::{Event, GenericEvent},
     namespace::{
         CacheFn, CacheableEvents, Namespace, NamespaceCacheingLogic, cache_first_and_last_event,
+        cache_one_event,
     },
 };
 use control_core_derive::BuildEvent;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::sync::Arc;
 use tracing::instrument;
 
-#[derive(Serialize, Debug, Clone, Default)]
+#[derive(Serialize, Debug, Clone, Default, JsonSchema)]
 pub struct LiveValuesEvent {
     /// diameter measurement in mm
     pub diameter: f64,
+    /// first axis in mm
     pub x_diameter: Option<f64>,
+    /// second axis in mm
     pub y_diameter: Option<f64>,
+    /// every measuring head in mm
+    pub axes: Vec<f64>,
     pub roundness: Option<f64>,
 }
 
@@ -28,7 +36,7 @@ impl LiveValuesEvent {
     }
 }
 
-#[derive(Serialize, Debug, Clone, BuildEvent)]
+#[derive(Serialize, Debug, Clone, BuildEvent, JsonSchema)]
 pub struct StateEvent {
     pub is_default_state: bool,
     /// laser state
@@ -41,7 +49,7 @@ impl StateEvent {
     }
 }
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, JsonSchema)]
 pub struct LaserState {
     /// higher tolerance in mm
     pub higher_tolerance: f64,
@@ -49,13 +57,25 @@ pub struct LaserState {
     pub lower_tolerance: f64,
     /// target diameter in mm
     pub target_diameter: f64,
-    /// tolerance bool
+    /// tolerance bool, includes all axes
+    pub in_tolerance: bool,
+    /// tolerance of every measuring head
+    pub axes: Vec<AxisState>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct AxisState {
+    /// higher tolerance in mm, the diameter tolerance unless overridden
+    pub higher_tolerance: f64,
+    /// lower tolerance in mm, the diameter tolerance unless overridden
+    pub lower_tolerance: f64,
     pub in_tolerance: bool,
 }
 
 pub enum LaserEvents {
     LiveValues(Event<LiveValuesEvent>),
     State(Event<StateEvent>),
+    Statistics(Box<Event<StatisticsEvent>>),
 }
 
 #[derive(Debug)]
@@ -68,6 +88,7 @@ impl CacheableEvents<Self> for LaserEvents {
         match self {
             Self::LiveValues(event) => event.into(),
             Self::State(event) => event.into(),
+            Self::Statistics(event) => event.as_ref().into(),
         }
     }
 
@@ -77,11 +98,12 @@ impl CacheableEvents<Self> for LaserEvents {
         match self {
             Self::LiveValues(_) => cache_first_and_last,
             Self::State(_) => cache_first_and_last,
+            Self::Statistics(_) => cache_one_event(),
         }
     }
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 /// All values in the Mutation enum should be positive.
 /// This ensures that the parameters for setting tolerances and target diameter
 /// are valid and meaningful within the context of the LaserMachine's operation.
@@ -89,6 +111,14 @@ enum Mutation {
     SetTargetDiameter(f64),
     SetLowerTolerance(f64),
     SetHigherTolerance(f64),
+    /// Overrides the tolerance of a single axis, `None` falls back to the diameter tolerance
+    SetAxisTolerance {
+        axis: usize,
+        lower_tolerance: Option<f64>,
+        higher_tolerance: Option<f64>,
+    },
+    /// Starts the statistics of a new spool
+    ResetSpoolStatistics,
 }
 
 impl NamespaceCacheingLogic<LaserEvents> for LaserMachineNamespace {
@@ -104,6 +134,15 @@ impl NamespaceCacheingLogic<LaserEvents> for LaserMachineNamespace {
     }
 }
 
+impl MachineSchema for LaserMachine {
+    fn api_schema() -> MachineApiSchema {
+        MachineApiSchema::new::<Mutation>("LaserMachine", Self::MACHINE_IDENTIFICATION)
+            .with_event::<LiveValuesEvent>("LiveValuesEvent")
+            .with_event::<StateEvent>("StateEvent")
+            .with_event::<StatisticsEvent>("StatisticsEvent")
+    }
+}
+
 impl MachineApi for LaserMachine {
     fn api_mutate(&mut self, request_body: Value) -> Result<(), anyhow::Error> {
         let mutation: Mutation = serde_json::from_value(request_body)?;
@@ -117,6 +156,12 @@ impl MachineApi for LaserMachine {
             Mutation::SetTargetDiameter(target_diameter) => {
                 self.set_target_diameter(target_diameter);
             }
+            Mutation::SetAxisTolerance {
+                axis,
+                lower_tolerance,
+                higher_tolerance,
+            } => self.set_axis_tolerance(axis, lower_tolerance, higher_tolerance)?,
+            Mutation::ResetSpoolStatistics => self.reset_spool_statistics(),
         }
         Ok(())
     }
@@ -4,13 +4,14 @@ use crate::{
     machine_identification::{MachineIdentification, MachineIdentificationUnique},
 };
 use crate::{Machine, MachineMessage};
-use api::{LaserEvents, LaserMachineNamespace, LaserState, LiveValuesEvent, StateEvent};
+use api::{AxisState, LaserEvents, LaserMachineNamespace, LaserState, LiveValuesEvent, StateEvent};
 use control_core::socketio::namespace::NamespaceCacheingLogic;
 use smol::{
     channel::{Receiver, Sender},
     lock::RwLock,
 };
 use socketioxide::extract::SocketRef;
+use statistics::LaserStatistics;
 use units::Length;
 
 use crate::AsyncThreadMessage;
@@ -20,6 +21,7 @@ use units::length::millimeter;
 pub mod act;
 pub mod api;
 pub mod new;
+pub mod statistics;
 
 #[derive(Debug)]
 pub struct LaserMachine {
@@ -30,21 +32,31 @@ pub struct LaserMachine {
 
     // drivers
     laser: Arc<RwLock<Laser>>,
+    /// Index of the gauge on the laser's bus
+    gauge: usize,
 
     // socketio
     namespace: LaserMachineNamespace,
     last_measurement_emit: Instant,
+    last_statistics_emit: Instant,
 
     // laser values
     diameter: Length,
-    x_diameter: Option<Length>,
-    y_diameter: Option<Length>,
+    /// One value per measuring head
+    axes: Vec<Length>,
     roundness: Option<f64>,
 
     target_diameter: Length,
     higher_tolerance: Length,
     lower_tolerance: Length,
     in_tolerance: bool,
+    axis_tolerances: Vec<AxisTolerance>,
+    axes_in_tolerance: Vec<bool>,
+
+    // statistical process control
+    statistics: LaserStatistics,
+    /// Timestamp of the last measurement added to the statistics
+    last_sample_timestamp: Option<Instant>,
 
     //laser target configuration
     laser_target: LaserTarget,
@@ -87,6 +99,7 @@ impl Drop for LaserMachine {
             "[LaserMachine::{:?}] Dropping machine and disconnecting clients...",
             self.machine_identification_unique
         );
+        statistics::remove_latest_statistics(&self.machine_identification_unique);
         smol::block_on(self.namespace.disconnect_all());
     }
 }
@@ -100,14 +113,14 @@ impl LaserMachine {
     ///diameter in mm
     pub fn emit_live_values(&mut self) {
         let diameter = self.diameter.get::<millimeter>();
-        let x_diameter = self.x_diameter.map(|x| x.get::<millimeter>());
-        let y_diameter = self.y_diameter.map(|y| y.get::<millimeter>());
+        let axes: Vec<f64> = self.axes.iter().map(|a| a.get::<millimeter>()).collect();
         let roundness = self.roundness;
 
         let live_values = LiveValuesEvent {
             diameter,
-            x_diameter,
-            y_diameter,
+            x_diameter: axes.first().copied(),
+            y_diameter: axes.get(1).copied(),
+            axes,
             roundness,
         };
 
@@ -115,12 +128,20 @@ impl LaserMachine {
             .emit(LaserEvents::LiveValues(live_values.build()));
     }
 
+    pub fn emit_statistics(&mut self, now: Instant) {
+        let event = self.statistics.build_event(now);
+        statistics::set_latest_statistics(&self.machine_identification_unique, &event);
+        self.namespace
+            .emit(LaserEvents::Statistics(Box::new(event.build())));
+    }
+
     pub fn build_state_event(&self) -> StateEvent {
         let laser = LaserState {
             higher_tolerance: self.higher_tolerance.get::<millimeter>(),
             lower_tolerance: self.lower_tolerance.get::<millimeter>(),
             target_diameter: self.laser_target.diameter.get::<millimeter>(),
             in_tolerance: self.in_tolerance,
+            axes: self.build_axis_states(),
         };
 
         StateEvent {
@@ -137,6 +158,7 @@ impl LaserMachine {
                 lower_tolerance: self.laser_target.lower_tolerance.get::<millimeter>(),
                 target_diameter: self.laser_target.diameter.get::<millimeter>(),
                 in_tolerance: self.in_tolerance,
+                axes: self.build_axis_states(),
             },
         };
 
@@ -147,68 +169,174 @@ impl LaserMachine {
     pub fn set_higher_tolerance(&mut self, higher_tolerance: f64) {
         self.higher_tolerance = Length::new::<millimeter>(higher_tolerance);
         self.laser_target.higher_tolerance = self.higher_tolerance;
+        self.update_statistics_tolerance();
         self.emit_state();
     }
 
     pub fn set_lower_tolerance(&mut self, lower_tolerance: f64) {
         self.lower_tolerance = Length::new::<millimeter>(lower_tolerance);
         self.laser_target.lower_tolerance = self.lower_tolerance;
+        self.update_statistics_tolerance();
         self.emit_state();
     }
 
     pub fn set_target_diameter(&mut self, target_diameter: f64) {
         self.target_diameter = Length::new::<millimeter>(target_diameter);
         self.laser_target.diameter = Length::new::<millimeter>(target_diameter);
+        self.update_statistics_tolerance();
+        self.emit_state();
+    }
+
+    /// Overrides the tolerance of a single axis, `None` falls back to the diameter tolerance
+    pub fn set_axis_tolerance(
+        &mut self,
+        axis: usize,
+        lower_tolerance: Option<f64>,
+        higher_tolerance: Option<f64>,
+    ) -> Result<(), anyhow::Error> {
+        let Some(axis_tolerance) = self.axis_tolerances.get_mut(axis) else {
+            return Err(anyhow::anyhow!(
+                "Axis {} doesn't exist, the laser has {} axes",
+                axis,
+                self.axis_tolerances.len()
+            ));
+        };
+        axis_tolerance.lower = lower_tolerance.map(Length::new::<millimeter>);
+        axis_tolerance.higher = higher_tolerance.map(Length::new::<millimeter>);
+        self.update_statistics_tolerance();
         self.emit_state();
+        Ok(())
+    }
+
+    /// (lower, higher) tolerance of an axis
+    fn axis_tolerance(&self, axis: usize) -> (Length, Length) {
+        let axis_tolerance = self.axis_tolerances.get(axis).copied().unwrap_or_default();
+        (
+            axis_tolerance
+                .lower
+                .unwrap_or(self.laser_target.lower_tolerance),
+            axis_tolerance
+                .higher
+                .unwrap_or(self.laser_target.higher_tolerance),
+        )
+    }
+
+    fn build_axis_states(&self) -> Vec<AxisState> {
+        (0..self.axis_tolerances.len())
+            .map(|axis| {
+                let (lower_tolerance, higher_tolerance) = self.axis_tolerance(axis);
+                AxisState {
+                    higher_tolerance: higher_tolerance.get::<millimeter>(),
+                    lower_tolerance: lower_tolerance.get::<millimeter>(),
+                    in_tolerance: self.axes_in_tolerance.get(axis).copied().unwrap_or(true),
+                }
+            })
+            .collect()
+    }
+
+    fn update_statistics_tolerance(&mut self) {
+        let target = self.laser_target.diameter.get::<millimeter>();
+        self.statistics.set_tolerance(
+            target,
+            self.laser_target.lower_tolerance.get::<millimeter>(),
+            self.laser_target.higher_tolerance.get::<millimeter>(),
+        );
+        for axis in 0..self.axis_tolerances.len() {
+            let (lower_tolerance, higher_tolerance) = self.axis_tolerance(axis);
+            self.statistics.set_axis_tolerance(
+                axis,
+                target,
+                lower_tolerance.get::<millimeter>(),
+                higher_tolerance.get::<millimeter>(),
+            );
+        }
+    }
+
+    /// Starts the statistics of a new spool, the rolling statistics are kept
+    pub fn reset_spool_statistics(&mut self) {
+        self.statistics.reset_spool(Instant::now());
+        self.emit_statistics(Instant::now());
+    }
+
+    /// Adds the latest measurement to the statistics, every measurement of the laser is only added once
+    fn update_statistics(&mut self, timestamp: Option<Instant>) {
+        let diameter_epsilon: f64 = 0.0001; // 0.0001 mm
+        if timestamp.is_none() || timestamp == self.last_sample_timestamp {
+            return;
+        }
+        self.last_sample_timestamp = timestamp;
+
+        // no filament in the laser
+        let diameter = self.diameter.get::<millimeter>();
+        if diameter < diameter_epsilon {
+            return;
+        }
+
+        let axes: Vec<f64> = self.axes.iter().map(|a| a.get::<millimeter>()).collect();
+        self.statistics.add(diameter, &axes);
     }
 
     ///
-    /// Roundness = min(x, y) / max(x, y)
+    /// Roundness = min(axes) / max(axes)
     ///
     fn calculate_roundness(&mut self) -> Option<f64> {
-        match (self.x_diameter, self.y_diameter) {
-            (Some(x), Some(y)) => {
-                let x_val = x.get::<millimeter>();
-                let y_val = y.get::<millimeter>();
-
-                if x_val > 0.0 && y_val > 0.0 {
-                    let roundness = f64::min(x_val, y_val) / f64::max(x_val, y_val);
-                    Some(roundness)
-                } else if x_val == 0.0 && y_val == 0.0 {
-                    Some(0.0)
-                } else {
-                    None
-                }
-            }
-            _ => None,
+        if self.axes.len() < 2 {
+            return None;
+        }
+
+        let values = self.axes.iter().map(|a| a.get::<millimeter>());
+        let min = values.clone().fold(f64::MAX, f64::min);
+        let max = values.fold(f64::MIN, f64::max);
+
+        if min > 0.0 {
+            Some(min / max)
+        } else if max == 0.0 {
+            Some(0.0)
+        } else {
+            None
         }
     }
 
     ///
-    /// Calculates if the current diameter is inside of the tolerance
+    /// Calculates if the current diameter and all axes are inside of their tolerance
     ///
     fn calculate_in_tolerance(&mut self) -> bool {
         let diameter_epsilon: f64 = 0.0001; // 0.0001 mm
         // early return true if the diameter is 0 to prevent warning happening before start
         if self.diameter.get::<millimeter>() < diameter_epsilon {
             self.in_tolerance = true;
+            self.axes_in_tolerance.fill(true);
             return true;
         }
 
         let top = self.target_diameter + self.higher_tolerance;
         let bottom = self.target_diameter - self.lower_tolerance;
-
-        if self.diameter > top || self.diameter < bottom {
-            self.in_tolerance = false;
-        } else {
-            self.in_tolerance = true;
-        }
+        let diameter_in_tolerance = !(self.diameter > top || self.diameter < bottom);
+
+        self.axes_in_tolerance = (0..self.axis_tolerances.len())
+            .map(|axis| {
+                let Some(value) = self.axes.get(axis) else {
+                    return true;
+                };
+                let (lower_tolerance, higher_tolerance) = self.axis_tolerance(axis);
+                let top = self.target_diameter + higher_tolerance;
+                let bottom = self.target_diameter - lower_tolerance;
+                !(*value > top || *value < bottom)
+            })
+            .collect();
+
+        self.in_tolerance = diameter_in_tolerance
+            && self
+                .axes_in_tolerance
+                .iter()
+                .all(|in_tolerance| *in_tolerance);
 
         self.in_tolerance
     }
 
     pub fn update(&mut self) {
-        let laser_data = smol::block_on(async { self.laser.read().await.get_data().await });
+        let gauge = self.gauge;
+        let laser_data = smol::block_on(async { self.laser.read().await.get_data(gauge).await });
         self.diameter = Length::new::<millimeter>(
             laser_data
                 .as_ref()
@@ -216,24 +344,30 @@ impl LaserMachine {
                 .unwrap_or(0.0),
         );
 
-        self.x_diameter = laser_data
-            .as_ref()
-            .and_then(|data| data.x_axis.as_ref())
-            .cloned();
-
-        self.y_diameter = laser_data
+        self.axes = laser_data
             .as_ref()
-            .and_then(|data| data.y_axis.as_ref())
-            .cloned();
+            .map(|data| data.axes.clone())
+            .unwrap_or_default();
 
         self.roundness = self.calculate_roundness();
+        self.update_statistics(laser_data.as_ref().map(|data| data.last_timestamp));
 
-        if self.in_tolerance != self.calculate_in_tolerance() {
+        let axes_in_tolerance = self.axes_in_tolerance.clone();
+        if self.in_tolerance != self.calculate_in_tolerance()
+            || axes_in_tolerance != self.axes_in_tolerance
+        {
             self.did_change_state = true;
         }
     }
 }
 
+/// Tolerance override of a single measuring head
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AxisTolerance {
+    lower: Option<Length>,
+    higher: Option<Length>,
+}
+
 #[derive(Debug, Clone)]
 pub struct LaserTarget {
     diameter: Length,
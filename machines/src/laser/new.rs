@@ -3,7 +3,10 @@ use std::time::Instant;
 use crate::serial::{devices::laser::Laser, registry::SERIAL_DEVICE_REGISTRY};
 use crate::{MachineNewHardware, MachineNewTrait};
 
-use super::{LaserMachine, LaserTarget, api::LaserMachineNamespace};
+use super::{
+    AxisTolerance, LaserMachine, LaserTarget, api::LaserMachineNamespace,
+    statistics::LaserStatistics,
+};
 use anyhow::Error;
 use units::ConstZero;
 use units::length::{Length, millimeter};
@@ -26,37 +29,55 @@ impl MachineNewTrait for LaserMachine {
             Ok(laser) => laser,
             Err(_) => return Err(Error::msg("Failed to downcast to Laser")),
         };
+        // every gauge on the bus is its own machine, the role is the index of the gauge
+        let gauge = params.device_group.first().map_or(0, |device| {
+            device.device_machine_identification.role as usize
+        });
+        let axes = smol::block_on(laser.read())
+            .config
+            .gauges
+            .get(gauge)
+            .map_or(0, |gauge| gauge.register_map.axes as usize);
+
         // set laser target configuration
         let laser_target = LaserTarget {
             higher_tolerance: Length::new::<millimeter>(0.05),
             lower_tolerance: Length::new::<millimeter>(0.05),
             diameter: Length::new::<millimeter>(1.75),
         };
+        let statistics = LaserStatistics::new(axes);
         let (sender, receiver) = smol::channel::unbounded();
 
-        let laser_machine = Self {
+        let mut laser_machine = Self {
             main_sender: params.main_thread_channel.clone(),
             api_receiver: receiver,
             api_sender: sender,
             machine_identification_unique: params.get_machine_identification_unique(),
             laser,
+            gauge,
             namespace: LaserMachineNamespace {
                 namespace: params.namespace.clone(),
             },
             last_measurement_emit: Instant::now(),
+            last_statistics_emit: Instant::now(),
             laser_target,
             emitted_default_state: false,
             diameter: Length::ZERO,
             target_diameter: Length::ZERO,
-            x_diameter: None,
-            y_diameter: None,
+            axes: vec![],
             roundness: None,
             lower_tolerance: Length::new::<millimeter>(0.05),
             higher_tolerance: Length::new::<millimeter>(0.05),
             in_tolerance: true,
+            axis_tolerances: vec![AxisTolerance::default(); axes],
+            axes_in_tolerance: vec![true; axes],
+            statistics,
+            last_sample_timestamp: None,
             did_change_state: true,
         };
 
+        laser_machine.update_statistics_tolerance();
+
         Ok(laser_machine)
     }
 }
@@ -0,0 +1,187 @@
+use crate::machine_identification::MachineIdentificationUnique;
+use control_core::helpers::spc::{
+    SpcCharacteristic, SpcCharacteristicSummary, SpecificationLimits,
+};
+use control_core::socketio::event::Event;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+    time::Instant,
+};
+
+/// Number of samples in the rolling window, a few minutes of production at the laser's update rate
+pub const ROLLING_WINDOW_SAMPLES: usize = 1000;
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq, JsonSchema)]
+pub struct StatisticsEvent {
+    /// diameter in mm
+    pub diameter: SpcCharacteristicSummary,
+    /// diameter of every measuring head in mm, empty for single axis lasers
+    pub axes: Vec<SpcCharacteristicSummary>,
+    /// difference between the largest and smallest axis in mm
+    pub ovality: SpcCharacteristicSummary,
+    /// seconds since the spool statistics were reset
+    pub spool_duration: f64,
+}
+
+impl StatisticsEvent {
+    pub fn build(&self) -> Event<Self> {
+        Event::new("StatisticsEvent", self.clone())
+    }
+}
+
+/// SPC of all characteristics measured by the laser
+#[derive(Debug)]
+pub struct LaserStatistics {
+    diameter: SpcCharacteristic,
+    axes: Vec<SpcCharacteristic>,
+    ovality: SpcCharacteristic,
+    spool_start: Instant,
+}
+
+impl Default for LaserStatistics {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl LaserStatistics {
+    pub fn new(axes: usize) -> Self {
+        Self {
+            diameter: SpcCharacteristic::new(ROLLING_WINDOW_SAMPLES),
+            axes: (0..axes)
+                .map(|_| SpcCharacteristic::new(ROLLING_WINDOW_SAMPLES))
+                .collect(),
+            ovality: SpcCharacteristic::new(ROLLING_WINDOW_SAMPLES),
+            spool_start: Instant::now(),
+        }
+    }
+
+    /// Sets the specification limits of the diameter, all values in mm
+    pub fn set_tolerance(&mut self, target: f64, lower_tolerance: f64, higher_tolerance: f64) {
+        self.diameter.set_limits(
+            limits(target, lower_tolerance, higher_tolerance),
+            Some(target),
+        );
+    }
+
+    /// Sets the specification limits of a single axis, all values in mm
+    pub fn set_axis_tolerance(
+        &mut self,
+        axis: usize,
+        target: f64,
+        lower_tolerance: f64,
+        higher_tolerance: f64,
+    ) {
+        if let Some(characteristic) = self.axes.get_mut(axis) {
+            characteristic.set_limits(
+                limits(target, lower_tolerance, higher_tolerance),
+                Some(target),
+            );
+        }
+    }
+
+    /// Adds a measurement, all values in mm
+    pub fn add(&mut self, diameter: f64, axes: &[f64]) {
+        self.diameter.add(diameter);
+        for (characteristic, value) in self.axes.iter_mut().zip(axes) {
+            characteristic.add(*value);
+        }
+        if axes.len() >= 2 {
+            let max = axes.iter().copied().fold(f64::MIN, f64::max);
+            let min = axes.iter().copied().fold(f64::MAX, f64::min);
+            self.ovality.add(max - min);
+        }
+    }
+
+    /// Starts the statistics of a new spool
+    pub fn reset_spool(&mut self, now: Instant) {
+        self.diameter.reset_total();
+        self.ovality.reset_total();
+        for characteristic in &mut self.axes {
+            characteristic.reset_total();
+        }
+        self.spool_start = now;
+    }
+
+    pub fn build_event(&self, now: Instant) -> StatisticsEvent {
+        StatisticsEvent {
+            diameter: self.diameter.summary(),
+            axes: self.axes.iter().map(SpcCharacteristic::summary).collect(),
+            ovality: self.ovality.summary(),
+            spool_duration: now.duration_since(self.spool_start).as_secs_f64(),
+        }
+    }
+}
+
+const fn limits(target: f64, lower_tolerance: f64, higher_tolerance: f64) -> SpecificationLimits {
+    SpecificationLimits {
+        lower: Some(target - lower_tolerance),
+        upper: Some(target + higher_tolerance),
+    }
+}
+
+/// Latest statistics of every laser, used by the REST API
+static LATEST_STATISTICS: OnceLock<Mutex<HashMap<MachineIdentificationUnique, StatisticsEvent>>> =
+    OnceLock::new();
+
+fn latest_statistics() -> &'static Mutex<HashMap<MachineIdentificationUnique, StatisticsEvent>> {
+    LATEST_STATISTICS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub fn set_latest_statistics(machine: &MachineIdentificationUnique, statistics: &StatisticsEvent) {
+    let mut guard = latest_statistics().lock().unwrap();
+    guard.insert(machine.clone(), statistics.clone());
+}
+
+pub fn remove_latest_statistics(machine: &MachineIdentificationUnique) {
+    let mut guard = latest_statistics().lock().unwrap();
+    guard.remove(machine);
+}
+
+/// Get the latest statistics of a laser, if it is connected
+pub fn get_latest_statistics(machine: &MachineIdentificationUnique) -> Option<StatisticsEvent> {
+    let guard = latest_statistics().lock().unwrap();
+    guard.get(machine).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_laser_statistics() {
+        let mut statistics = LaserStatistics::new(3);
+        statistics.set_tolerance(1.75, 0.05, 0.05);
+        for axis in 0..3 {
+            statistics.set_axis_tolerance(axis, 1.75, 0.05, 0.05);
+        }
+        // a tighter tolerance on the third head
+        statistics.set_axis_tolerance(2, 1.75, 0.005, 0.005);
+
+        statistics.add(1.75, &[]);
+        statistics.add(1.74, &[1.76, 1.72, 1.74]);
+        statistics.add(1.81, &[1.78, 1.84, 1.81]);
+
+        let start = Instant::now();
+        let event = statistics.build_event(start);
+        assert_eq!(event.diameter.total.count, 3);
+        assert_eq!(event.axes.len(), 3);
+        assert_eq!(event.axes[0].total.count, 2);
+        assert_eq!(event.ovality.total.count, 2);
+        assert!((event.diameter.total.out_of_tolerance_percent - 100.0 / 3.0).abs() < 1e-9);
+        assert_eq!(event.axes[0].total.out_of_tolerance_percent, 0.0);
+        assert!((event.axes[1].total.out_of_tolerance_percent - 50.0).abs() < 1e-9);
+        assert!((event.axes[2].total.out_of_tolerance_percent - 100.0).abs() < 1e-9);
+        assert!((event.ovality.total.max.unwrap() - 0.06).abs() < 1e-9);
+
+        statistics.reset_spool(start);
+        let event = statistics.build_event(start);
+        assert_eq!(event.diameter.total.count, 0);
+        assert_eq!(event.axes[1].total.count, 0);
+        assert_eq!(event.diameter.rolling.count, 3);
+        assert_eq!(event.spool_duration, 0.0);
+    }
+}
@@ -10,21 +10,26 @@ use machine_identification::{
     DeviceHardwareIdentification, DeviceHardwareIdentificationEthercat, DeviceIdentification,
     DeviceIdentificationIdentified, MachineIdentificationUnique,
 };
+use schemars::JsonSchema;
 use serde::Serialize;
 use smol::channel::{Receiver, Sender};
 use socketioxide::extract::SocketRef;
 use std::fmt::Debug;
 use std::{any::Any, sync::Arc, time::Instant};
 pub mod analog_input_test_machine;
+pub mod api_schema;
 pub mod aquapath1;
 #[cfg(not(feature = "mock-machine"))]
 pub mod buffer1;
 pub mod extruder1;
 pub mod extruder2;
+pub mod frequency_inverter;
 pub mod ip20_test_machine;
 pub mod laser;
 pub mod machine_identification;
+pub mod machine_settings;
 pub mod mock;
+pub mod production_record;
 pub mod registry;
 pub mod serial;
 pub mod test_machine;
@@ -48,7 +53,7 @@ pub const ANALOG_INPUT_TEST_MACHINE: u16 = 0x0035;
 use serde_json::Value;
 use smol::lock::RwLock;
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, JsonSchema)]
 pub struct MachineCrossConnectionState {
     machine_identification_unique: Option<MachineIdentificationUnique>,
     is_available: bool,
@@ -59,10 +64,36 @@ pub struct CrossConnection {
     pub dest: MachineIdentificationUnique,
 }
 
+/// Sequences every production line has
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, serde::Deserialize)]
+pub enum LineSequence {
+    Start,
+    Stop,
+    Emergency,
+}
+
+/// Runs a sequence of the production line with the given name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineCommand {
+    pub line: String,
+    pub sequence: LineSequence,
+}
+
 pub enum AsyncThreadMessage {
     NoMsg,
     ConnectOneWayRequest(CrossConnection),
     DisconnectMachines(CrossConnection),
+    /// A winder finished a spool, the record is stored by the main thread
+    SpoolCompleted(Box<production_record::ProductionRecord>),
+    /// Start, stop or emergency stop a production line
+    RunLineSequence(LineCommand),
+    /// The machine panicked and was taken out of the RT loop, with the panic message
+    MachineQuarantined(MachineIdentificationUnique, String),
+    /// An extruder finished reading an inverter parameter backup, it is stored by the main thread
+    InverterBackupFinished(
+        MachineIdentificationUnique,
+        Box<frequency_inverter::parameters::FinishedBackup>,
+    ),
 }
 
 pub struct MachineNewParams<
@@ -133,9 +164,10 @@ pub struct MachineNewHardwareEthercat<'maindevice, 'subdevices, 'ethercat_device
 pub trait SerialDevice: Any + Send + Sync + SerialDeviceNew + Debug {}
 
 pub trait SerialDeviceNew {
+    /// Returns one identification per machine behind the device, e.g. every gauge on an RS485 bus
     fn new_serial(
         params: &SerialDeviceNewParams,
-    ) -> Result<(DeviceIdentification, Arc<RwLock<Self>>), anyhow::Error>
+    ) -> Result<(Vec<DeviceIdentification>, Arc<RwLock<Self>>), anyhow::Error>
     where
         Self: Sized;
 }
@@ -146,6 +178,16 @@ pub trait SerialDeviceThread {
 
 pub struct SerialDeviceNewParams {
     pub path: String,
+    /// Serial number of the USB adapter, stays the same when the device is plugged into another port
+    pub usb_serial_number: Option<String>,
+}
+
+impl SerialDeviceNewParams {
+    /// Stable identity of the device, used to derive the machine serial
+    /// Falls back to the port path for adapters without a USB serial number
+    pub fn identity(&self) -> &str {
+        self.usb_serial_number.as_deref().unwrap_or(&self.path)
+    }
 }
 
 #[derive(PartialEq, Eq, Clone, Debug, Hash)]
@@ -271,9 +313,22 @@ pub struct MachineConnection {
     pub connection: Sender<MachineMessage>,
 }
 
+/// What the machines do with their outputs when the server shuts down
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownPolicy {
+    /// Switch off the outputs of power supplies, else they keep powering the devices
+    pub power_off: bool,
+}
+
 pub trait MachineAct {
     fn act_machine_message(&mut self, msg: MachineMessage);
     fn act(&mut self, now: Instant);
+
+    /// Moves the machine towards its safe state: heaters and pumps off, motors decelerated to zero
+    ///
+    /// Called instead of [`MachineAct::act`] in every cycle while the server shuts down, API
+    /// messages are not handled anymore. Returns `true` once the safe state is reached.
+    fn shutdown(&mut self, now: Instant, policy: &ShutdownPolicy) -> bool;
 }
 
 // generic MachineMessage allows us to implement actions
@@ -284,11 +339,49 @@ pub trait MachineAct {
 pub enum MachineMessage {
     SubscribeNamespace(Namespace),
     UnsubscribeNamespace,
-    HttpApiJsonRequest(serde_json::Value),
+    /// A mutation for `api_mutate`, its result is sent back over the reply
+    HttpApiJsonRequest(serde_json::Value, MutationReply),
     ConnectToMachine(MachineConnection),
     DisconnectMachine(MachineConnection),
 }
 
+/// Result of a mutation as seen by whoever sent it
+pub type MutationResult = Result<(), String>;
+
+/// Sends the result of `api_mutate` back to the sender of a mutation
+///
+/// Replying never blocks, so machines can reply from the RT loop.
+#[derive(Debug)]
+pub struct MutationReply(Option<Sender<MutationResult>>);
+
+impl MutationReply {
+    /// For mutations nobody waits for, errors are only logged
+    pub const fn none() -> Self {
+        Self(None)
+    }
+
+    /// A reply and the receiver the result arrives on
+    pub fn channel() -> (Self, Receiver<MutationResult>) {
+        let (sender, receiver) = smol::channel::bounded(1);
+        (Self(Some(sender)), receiver)
+    }
+
+    pub fn send(self, result: Result<(), anyhow::Error>) {
+        let result = result.map_err(|e| format!("{:#}", e));
+        match self.0 {
+            // the receiver may be gone if the sender stopped waiting
+            Some(sender) => {
+                let _ = sender.try_send(result);
+            }
+            None => {
+                if let Err(e) = result {
+                    tracing::warn!("Mutation failed: {}", e);
+                }
+            }
+        }
+    }
+}
+
 pub trait MachineApi {
     fn api_get_sender(&self) -> Sender<MachineMessage>;
     fn api_mutate(&mut self, value: Value) -> Result<(), anyhow::Error>;
@@ -430,6 +523,10 @@ impl MachineChannel {
             namespace: None,
         }
     }
+
+    pub const fn machine_identification_unique(&self) -> &MachineIdentificationUnique {
+        &self.machine_identification_unique
+    }
 }
 
 impl<E> NamespaceCacheingLogic<E> for MachineChannel
@@ -454,6 +551,9 @@ pub trait MachineWithChannel: Send + Debug + Sync {
 
     fn update(&mut self, now: std::time::Instant) -> Result<()>;
     fn mutate(&mut self, value: Value) -> Result<()>;
+
+    /// See [`MachineAct::shutdown`]
+    fn shutdown(&mut self, now: std::time::Instant, policy: &ShutdownPolicy) -> Result<bool>;
 }
 
 impl<C> MachineApi for C
@@ -493,6 +593,13 @@ where
         }
     }
 
+    fn shutdown(&mut self, now: Instant, policy: &ShutdownPolicy) -> bool {
+        MachineWithChannel::shutdown(self, now, policy).unwrap_or_else(|e| {
+            tracing::error!("Machine errored while shutting down: {}, ", e);
+            false
+        })
+    }
+
     fn act_machine_message(&mut self, msg: MachineMessage) {
         let channel = self.get_machine_channel_mut();
 
@@ -504,8 +611,8 @@ where
             MachineMessage::UnsubscribeNamespace => {
                 channel.namespace = None;
             }
-            MachineMessage::HttpApiJsonRequest(value) => {
-                let _ = self.api_mutate(value);
+            MachineMessage::HttpApiJsonRequest(value, reply) => {
+                reply.send(self.api_mutate(value));
             }
             MachineMessage::ConnectToMachine(_machine_connection) => {
                 todo!();
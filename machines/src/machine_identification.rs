@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use std::fmt::Display;
 
 use ethercat_hal::devices::wago_750_354::WAGO_750_354_IDENTITY_A;
@@ -6,7 +7,7 @@ use serde::Deserialize;
 use serde::Serialize;
 
 /// Identifies a spacifi machine
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, JsonSchema)]
 pub struct MachineIdentificationUnique {
     pub machine_identification: MachineIdentification,
     pub serial: u16,
@@ -30,7 +31,7 @@ impl Display for MachineIdentificationUnique {
 }
 
 /// Identifies a machine
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, JsonSchema)]
 pub struct MachineIdentification {
     pub vendor: u16,
     pub machine: u16,
@@ -43,7 +44,7 @@ impl MachineIdentification {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 pub struct DeviceMachineIdentification {
     pub machine_identification_unique: MachineIdentificationUnique,
     pub role: u16,
@@ -61,7 +62,7 @@ impl DeviceMachineIdentification {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 pub struct DeviceIdentification {
     pub device_machine_identification: Option<DeviceMachineIdentification>,
     pub device_hardware_identification: DeviceHardwareIdentification,
@@ -99,17 +100,17 @@ impl TryFrom<DeviceIdentification> for DeviceIdentificationIdentified {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 pub enum DeviceHardwareIdentification {
     Ethercat(DeviceHardwareIdentificationEthercat),
     Serial(DeviceHardwareIdentificationSerial),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 pub struct DeviceHardwareIdentificationEthercat {
     pub subdevice_index: usize,
 }
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 pub struct DeviceHardwareIdentificationSerial {
     pub path: String,
 }
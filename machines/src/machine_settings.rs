@@ -0,0 +1,68 @@
+use crate::machine_identification::MachineIdentificationUnique;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+
+type Snapshots = Mutex<HashMap<MachineIdentificationUnique, Value>>;
+
+/// Latest state of every machine, used to record the settings a product was made with and
+/// served by the REST API
+///
+/// The snapshots are taken by the server from the emitted events, off the RT loop
+static LATEST_SETTINGS: OnceLock<Snapshots> = OnceLock::new();
+
+/// Latest live values of every machine, used to check the conditions of line sequences and
+/// served by the REST API
+static LATEST_LIVE_VALUES: OnceLock<Snapshots> = OnceLock::new();
+
+fn latest_settings() -> &'static Snapshots {
+    LATEST_SETTINGS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn latest_live_values() -> &'static Snapshots {
+    LATEST_LIVE_VALUES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn insert(snapshots: &Snapshots, machine: &MachineIdentificationUnique, value: Value) {
+    let mut guard = snapshots.lock().unwrap();
+    guard.insert(machine.clone(), value);
+}
+
+/// Stores the settings of a machine, the data of its state event
+pub fn set_latest_settings(machine: &MachineIdentificationUnique, settings: Value) {
+    insert(latest_settings(), machine, settings);
+}
+
+/// Removes the settings and live values of a machine
+pub fn remove_latest_settings(machine: &MachineIdentificationUnique) {
+    latest_settings().lock().unwrap().remove(machine);
+    latest_live_values().lock().unwrap().remove(machine);
+}
+
+/// Get the latest settings of a machine, if it has published any
+pub fn get_latest_settings(machine: &MachineIdentificationUnique) -> Option<Value> {
+    let guard = latest_settings().lock().unwrap();
+    guard.get(machine).cloned()
+}
+
+/// Stores the live values of a machine, the data of its live values event
+pub fn set_latest_live_values(machine: &MachineIdentificationUnique, live_values: Value) {
+    insert(latest_live_values(), machine, live_values);
+}
+
+/// Get the latest live values of a machine, if it has published any
+pub fn get_latest_live_values(machine: &MachineIdentificationUnique) -> Option<Value> {
+    let guard = latest_live_values().lock().unwrap();
+    guard.get(machine).cloned()
+}
+
+/// Get the latest live values of all machines that have published any
+pub fn all_latest_live_values() -> Vec<(MachineIdentificationUnique, Value)> {
+    let guard = latest_live_values().lock().unwrap();
+    guard
+        .iter()
+        .map(|(machine, live_values)| (machine.clone(), live_values.clone()))
+        .collect()
+}
@@ -1,5 +1,5 @@
-use super::MockMachine;
-use crate::{MachineAct, MachineMessage};
+use super::{MockMachine, api::Mode};
+use crate::{MachineAct, MachineMessage, ShutdownPolicy};
 use std::time::{Duration, Instant};
 
 /// Implements the `MachineAct` trait for the `MockMachine`.
@@ -35,6 +35,13 @@ impl MachineAct for MockMachine {
         }
     }
 
+    fn shutdown(&mut self, _now: Instant, _policy: &ShutdownPolicy) -> bool {
+        if self.mode != Mode::Standby {
+            self.set_mode(Mode::Standby);
+        }
+        true
+    }
+
     fn act_machine_message(&mut self, msg: MachineMessage) {
         match msg {
             MachineMessage::SubscribeNamespace(namespace) => {
@@ -42,10 +49,10 @@ impl MachineAct for MockMachine {
                 self.emit_state();
             }
             MachineMessage::UnsubscribeNamespace => self.namespace.namespace = None,
-            MachineMessage::HttpApiJsonRequest(value) => {
+            MachineMessage::HttpApiJsonRequest(value, reply) => {
                 use crate::MachineApi;
 
-                let _res = self.api_mutate(value);
+                reply.send(self.api_mutate(value));
             }
             MachineMessage::ConnectToMachine(_machine_connection) =>
             /*Doesnt connect to any Machine so do nothing*/
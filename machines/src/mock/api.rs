@@ -1,3 +1,4 @@
+use crate::api_schema::{MachineApiSchema, MachineSchema};
 use crate::{MachineApi, MachineMessage};
 
 use super::MockMachine;
@@ -8,19 +9,20 @@ use control_core::socketio::{
     },
 };
 use control_core_derive::BuildEvent;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use smol::channel::Sender;
 use std::sync::Arc;
 use tracing::instrument;
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
 pub enum Mode {
     Standby,
     Running,
 }
 
-#[derive(Serialize, Debug, Clone, Default)]
+#[derive(Serialize, Debug, Clone, Default, JsonSchema)]
 pub struct LiveValuesEvent {
     pub amplitude_sum: f64,
     pub amplitude1: f64,
@@ -34,7 +36,7 @@ impl LiveValuesEvent {
     }
 }
 
-#[derive(Serialize, Debug, Clone, PartialEq, BuildEvent)]
+#[derive(Serialize, Debug, Clone, PartialEq, BuildEvent, JsonSchema)]
 pub struct StateEvent {
     pub is_default_state: bool,
     /// sine wave frequencies in millihertz
@@ -45,7 +47,7 @@ pub struct StateEvent {
     pub mode_state: ModeState,
 }
 
-#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
 pub struct ModeState {
     /// current mode
     pub mode: Mode,
@@ -79,7 +81,7 @@ impl CacheableEvents<Self> for MockEvents {
     }
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 /// Mutation for controlling the mock machine
 enum Mutation {
     /// Set the frequency of the sine wave in millihertz
@@ -102,6 +104,14 @@ impl NamespaceCacheingLogic<MockEvents> for MockMachineNamespace {
     }
 }
 
+impl MachineSchema for MockMachine {
+    fn api_schema() -> MachineApiSchema {
+        MachineApiSchema::new::<Mutation>("MockMachine", Self::MACHINE_IDENTIFICATION)
+            .with_event::<LiveValuesEvent>("LiveValuesEvent")
+            .with_event::<StateEvent>("StateEvent")
+    }
+}
+
 impl MachineApi for MockMachine {
     fn api_get_sender(&self) -> Sender<MachineMessage> {
         self.api_sender.clone()
@@ -0,0 +1,155 @@
+use crate::machine_identification::MachineIdentificationUnique;
+use control_core::helpers::csv::{CsvRow, csv_field};
+use control_core::helpers::spc::SpcSummary;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How a spool was finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpoolCompletion {
+    /// The automatic action reached the target length
+    Automatic,
+    /// The operator reset the spool progress
+    Manual,
+    /// The server shut down while the spool was wound
+    Shutdown,
+}
+
+/// Settings of a machine at the end of a spool
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MachineSettingsRecord {
+    pub machine_identification_unique: MachineIdentificationUnique,
+    /// Latest state of the machine, `None` if it didn't publish one
+    pub settings: Option<Value>,
+}
+
+/// Diameter statistics of the laser that measured the spool
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiameterRecord {
+    pub laser: MachineIdentificationUnique,
+    /// diameter in mm
+    pub diameter: SpcSummary,
+    /// difference between the largest and smallest axis in mm
+    pub ovality: SpcSummary,
+}
+
+/// Record of a finished spool
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProductionRecord {
+    /// Assigned when the record is stored, 0 before
+    pub id: u64,
+    /// Assigned from the spool id when the operator didn't set one
+    pub lot_number: String,
+    pub winder: MachineIdentificationUnique,
+    /// unix timestamp in ms
+    pub started_at: u64,
+    /// unix timestamp in ms
+    pub finished_at: u64,
+    /// length in m
+    pub length: f64,
+    pub completion: SpoolCompletion,
+    /// The winder and all machines connected to it
+    pub machines: Vec<MachineSettingsRecord>,
+    pub diameter: Option<DiameterRecord>,
+}
+
+fn opt_f64(v: Option<f64>) -> String {
+    v.map(|x| format!("{:.4}", x)).unwrap_or_default()
+}
+
+impl CsvRow for ProductionRecord {
+    /// The settings are only part of the JSON export
+    const CSV_HEADER: &'static str = "id,lot_number,winder,started_at_ms,finished_at_ms,\
+         length_m,completion,laser,diameter_mean_mm,diameter_std_dev_mm,diameter_min_mm,\
+         diameter_max_mm,diameter_cpk,diameter_out_of_tolerance_percent,ovality_mean_mm,\
+         ovality_max_mm";
+
+    fn csv_row(&self) -> String {
+        let diameter = self.diameter.as_ref();
+        format!(
+            "{},{},{},{},{},{:.3},{:?},{},{},{},{},{},{},{},{},{}",
+            self.id,
+            csv_field(&self.lot_number),
+            self.winder,
+            self.started_at,
+            self.finished_at,
+            self.length,
+            self.completion,
+            diameter.map(|d| d.laser.to_string()).unwrap_or_default(),
+            opt_f64(diameter.and_then(|d| d.diameter.mean)),
+            opt_f64(diameter.and_then(|d| d.diameter.std_dev)),
+            opt_f64(diameter.and_then(|d| d.diameter.min)),
+            opt_f64(diameter.and_then(|d| d.diameter.max)),
+            opt_f64(diameter.and_then(|d| d.diameter.cpk)),
+            opt_f64(diameter.map(|d| d.diameter.out_of_tolerance_percent)),
+            opt_f64(diameter.and_then(|d| d.ovality.mean)),
+            opt_f64(diameter.and_then(|d| d.ovality.max)),
+        )
+    }
+}
+
+/// unix timestamp in ms
+pub fn unix_timestamp_ms(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        MACHINE_LASER_V1, MACHINE_WINDER_V1, VENDOR_QITECH,
+        machine_identification::MachineIdentification,
+    };
+    use control_core::helpers::csv::to_csv;
+
+    #[test]
+    fn test_production_records_to_csv() {
+        let winder = MachineIdentificationUnique {
+            machine_identification: MachineIdentification {
+                vendor: VENDOR_QITECH,
+                machine: MACHINE_WINDER_V1,
+            },
+            serial: 7,
+        };
+        let laser = MachineIdentificationUnique {
+            machine_identification: MachineIdentification {
+                vendor: VENDOR_QITECH,
+                machine: MACHINE_LASER_V1,
+            },
+            serial: 8,
+        };
+        let record = ProductionRecord {
+            id: 3,
+            lot_number: "PLA, black".to_string(),
+            winder,
+            started_at: 1000,
+            finished_at: 61000,
+            length: 330.5,
+            completion: SpoolCompletion::Automatic,
+            machines: vec![],
+            diameter: Some(DiameterRecord {
+                laser,
+                diameter: SpcSummary {
+                    count: 10,
+                    mean: Some(1.75),
+                    max: Some(1.8),
+                    ..Default::default()
+                },
+                ovality: SpcSummary::default(),
+            }),
+        };
+
+        let csv = to_csv(&[record]);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0].split(',').count(),
+            lines[1].replace("\"PLA, black\"", "lot").split(',').count()
+        );
+        assert!(lines[1].starts_with("3,\"PLA, black\","));
+        assert!(lines[1].contains(",330.500,Automatic,"));
+        assert!(lines[1].contains(",1.7500,,,1.8000,,0.0000,,"));
+    }
+}
@@ -7,7 +7,10 @@ use crate::{
 };
 
 use crate::{
-    Machine, MachineNewParams, MachineNewTrait, machine_identification::MachineIdentification,
+    Machine, MachineNewParams, MachineNewTrait,
+    api_schema::{MachineApiSchema, MachineSchema},
+    machine_identification::MachineIdentification,
+    wago_power::WagoPower,
 };
 
 #[cfg(not(feature = "mock-machine"))]
@@ -30,6 +33,7 @@ pub type MachineNewClosure =
 
 pub struct MachineRegistry {
     type_map: HashMap<TypeId, (MachineIdentification, MachineNewClosure)>,
+    schema_map: HashMap<TypeId, fn() -> MachineApiSchema>,
 }
 
 impl Default for MachineRegistry {
@@ -42,10 +46,11 @@ impl MachineRegistry {
     pub fn new() -> Self {
         Self {
             type_map: HashMap::new(),
+            schema_map: HashMap::new(),
         }
     }
 
-    pub fn register<T: MachineNewTrait + 'static>(
+    pub fn register<T: MachineNewTrait + MachineSchema + 'static>(
         &mut self,
         machine_identficiation: MachineIdentification,
     ) {
@@ -57,6 +62,25 @@ impl MachineRegistry {
                 Box::new(|machine_new_params| Ok(Box::new(T::new(machine_new_params)?))),
             ),
         );
+        self.register_schema::<T>();
+    }
+
+    /// Only the API schema, for machines that aren't constructed by the registry
+    pub fn register_schema<T: MachineSchema + 'static>(&mut self) {
+        self.schema_map.insert(TypeId::of::<T>(), T::api_schema);
+    }
+
+    /// API schemas of all registered machine types, ordered by machine identification
+    pub fn schemas(&self) -> Vec<MachineApiSchema> {
+        let mut schemas: Vec<MachineApiSchema> =
+            self.schema_map.values().map(|schema| schema()).collect();
+        schemas.sort_by_key(|schema| {
+            (
+                schema.machine_identification.vendor,
+                schema.machine_identification.machine,
+            )
+        });
+        schemas
     }
 
     pub fn new_machine(
@@ -128,6 +152,9 @@ lazy_static! {
 
         mc.register::<AnalogInputTestMachine>(AnalogInputTestMachine::MACHINE_IDENTIFICATION);
 
+        // created by the modbus tcp discovery of the server
+        mc.register_schema::<WagoPower>();
+
         mc
     };
 }
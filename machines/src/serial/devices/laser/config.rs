@@ -0,0 +1,261 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serialport::{Parity, StopBits};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::Duration,
+};
+
+/// JSON file with the laser configuration, see `LaserConfigFile`
+static LASER_CONFIG_PATH: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// Sets the file the lasers are configured with, `[laser] config_path` in the server config,
+/// called once at startup
+pub fn set_laser_config_path(path: Option<PathBuf>) -> Result<()> {
+    LASER_CONFIG_PATH
+        .set(path)
+        .map_err(|_| anyhow::anyhow!("The laser config path is already set"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LaserParity {
+    None,
+    Even,
+    Odd,
+}
+
+impl From<LaserParity> for Parity {
+    fn from(parity: LaserParity) -> Self {
+        match parity {
+            LaserParity::None => Self::None,
+            LaserParity::Even => Self::Even,
+            LaserParity::Odd => Self::Odd,
+        }
+    }
+}
+
+/// Input registers of a gauge
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LaserRegisterMap {
+    /// Register holding the (average) diameter, the axis registers follow directly after it
+    pub start_register: u16,
+    /// Number of measuring heads, 0 for gauges that only report a diameter
+    pub axes: u16,
+    /// mm per raw register value
+    pub scale: f64,
+}
+
+impl Default for LaserRegisterMap {
+    fn default() -> Self {
+        Self {
+            start_register: 0x000E,
+            axes: 2,
+            scale: 0.001,
+        }
+    }
+}
+
+/// Registers one Modbus read may request
+const MAX_REGISTER_COUNT: u16 = 125;
+
+impl LaserRegisterMap {
+    /// Diameter register plus one register per axis
+    pub const fn register_count(&self) -> u16 {
+        1 + self.axes
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.axes >= MAX_REGISTER_COUNT {
+            anyhow::bail!(
+                "A gauge can have at most {} axes, got {}",
+                MAX_REGISTER_COUNT - 1,
+                self.axes
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A gauge on the RS485 bus
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LaserGaugeConfig {
+    pub slave_id: u8,
+    pub register_map: LaserRegisterMap,
+}
+
+impl Default for LaserGaugeConfig {
+    fn default() -> Self {
+        Self {
+            slave_id: 1,
+            register_map: LaserRegisterMap::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LaserConfig {
+    pub baudrate: u32,
+    pub parity: LaserParity,
+    /// 1 or 2
+    pub stop_bits: u8,
+    /// Time to wait for a response
+    pub timeout_ms: u64,
+    /// Time between two polls of all gauges, 0 polls as fast as the gauges answer
+    pub poll_interval_ms: u64,
+    /// Gauges sharing the bus, every gauge becomes its own machine
+    pub gauges: Vec<LaserGaugeConfig>,
+}
+
+impl Default for LaserConfig {
+    fn default() -> Self {
+        Self {
+            baudrate: 38_400,
+            parity: LaserParity::None,
+            stop_bits: 1,
+            timeout_ms: 500,
+            poll_interval_ms: 0,
+            gauges: vec![LaserGaugeConfig::default()],
+        }
+    }
+}
+
+impl LaserConfig {
+    pub const fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    pub const fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_ms)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !matches!(self.stop_bits, 1 | 2) {
+            anyhow::bail!("Stop bits must be 1 or 2, got {}", self.stop_bits);
+        }
+        for gauge in &self.gauges {
+            gauge.register_map.validate()?;
+        }
+        Ok(())
+    }
+
+    pub const fn stop_bits(&self) -> StopBits {
+        match self.stop_bits {
+            2 => StopBits::Two,
+            _ => StopBits::One,
+        }
+    }
+
+    /// Reads the configuration of a device from the file set at startup
+    /// Falls back to the default configuration if there is no file or the file is invalid
+    pub fn for_device(identity: &str) -> Self {
+        let Some(path) = LASER_CONFIG_PATH.get().and_then(Option::as_ref) else {
+            return Self::default();
+        };
+
+        match LaserConfigFile::load(path) {
+            Ok(file) => file.config_for(identity),
+            Err(e) => {
+                tracing::warn!("Invalid laser configuration {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+}
+
+/// Contents of the laser configuration file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LaserConfigFile {
+    /// Used for all devices without an entry in `devices`
+    pub default: LaserConfig,
+    /// USB serial number (or port path for adapters without one) -> configuration
+    pub devices: HashMap<String, LaserConfig>,
+}
+
+impl LaserConfigFile {
+    pub fn load(path: &Path) -> Result<Self, anyhow::Error> {
+        let json = std::fs::read_to_string(path)?;
+        let file: Self = serde_json::from_str(&json)?;
+        file.validate()?;
+        Ok(file)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        self.default.validate()?;
+        for (identity, config) in &self.devices {
+            config
+                .validate()
+                .map_err(|e| anyhow::anyhow!("Laser {}: {}", identity, e))?;
+        }
+        Ok(())
+    }
+
+    pub fn config_for(&self, identity: &str) -> LaserConfig {
+        self.devices.get(identity).unwrap_or(&self.default).clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_file() {
+        let file: LaserConfigFile = serde_json::from_str(
+            r#"{
+                "default": { "baudrate": 19200 },
+                "devices": {
+                    "FT1234": {
+                        "parity": "Even",
+                        "poll_interval_ms": 50,
+                        "gauges": [
+                            { "slave_id": 1 },
+                            { "slave_id": 2, "register_map": { "axes": 3 } }
+                        ]
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let default = file.config_for("/dev/ttyUSB0");
+        assert_eq!(default.baudrate, 19200);
+        assert_eq!(default.gauges, [LaserGaugeConfig::default()]);
+
+        let config = file.config_for("FT1234");
+        assert_eq!(config.baudrate, 38_400);
+        assert_eq!(config.parity, LaserParity::Even);
+        assert_eq!(config.poll_interval(), Duration::from_millis(50));
+        assert_eq!(config.gauges[1].slave_id, 2);
+        assert_eq!(config.gauges[1].register_map.register_count(), 4);
+        assert_eq!(config.gauges[1].register_map.start_register, 0x000E);
+        file.validate().unwrap();
+    }
+
+    #[test]
+    fn test_validate_config() {
+        LaserConfig::default().validate().unwrap();
+        let mut config = LaserConfig {
+            stop_bits: 3,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+
+        config.stop_bits = 2;
+        config.gauges[0].register_map.axes = 124;
+        config.validate().unwrap();
+        config.gauges[0].register_map.axes = 125;
+        assert!(config.validate().is_err());
+
+        let file = LaserConfigFile {
+            devices: HashMap::from([("FT1234".to_owned(), config)]),
+            ..Default::default()
+        };
+        assert!(file.validate().is_err());
+    }
+}
@@ -1,14 +1,13 @@
 use anyhow::anyhow;
+use config::{LaserConfig, LaserGaugeConfig, LaserRegisterMap};
 use control_core::helpers::hashing::{byte_folding_u16, hash_djb2};
-use control_core::helpers::retry::retry_n_times;
 use control_core::modbus::ModbusResponse;
 use control_core::modbus::{self, ModbusRequest};
 use serialport::SerialPort;
-use serialport::{ClearBuffer, DataBits, FlowControl, Parity, StopBits};
+use serialport::{ClearBuffer, DataBits, FlowControl};
 use smol::lock::RwLock;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::{
-    io::Write,
     sync::Arc,
     thread,
     time::{Duration, Instant},
@@ -24,53 +23,62 @@ use crate::{
 };
 use units::length::millimeter;
 
+pub mod config;
+
+/// A gauge that doesn't answer is only asked again after this time, each unanswered request
+/// blocks the bus for the whole timeout
+const OFFLINE_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
 /// The struct of Laser Device
 #[derive(Debug)]
 pub struct Laser {
-    pub data: Option<LaserData>,
+    /// Latest measurement of every gauge, same order as `config.gauges`
+    pub data: Vec<Option<LaserData>>,
     pub path: String,
+    pub config: LaserConfig,
     pub shutdown_flag: Arc<AtomicBool>,
 }
 
 impl SerialDevice for Laser {}
 
 enum LaserModbusRequsts {
-    ReadDiameter,
+    ReadDiameter(LaserGaugeConfig),
 }
 
 struct LaserDiameterResponse {
     pub diameter: Length,
-    pub x_axis: Option<Length>,
-    pub y_axis: Option<Length>,
+    pub axes: Vec<Length>,
 }
 
-impl TryFrom<ModbusResponse> for LaserDiameterResponse {
-    type Error = anyhow::Error;
-
-    fn try_from(value: ModbusResponse) -> Result<Self, Self::Error> {
+impl LaserDiameterResponse {
+    fn parse(
+        value: ModbusResponse,
+        register_map: &LaserRegisterMap,
+    ) -> Result<Self, anyhow::Error> {
         if value.data.len() < 3 {
             return Err(anyhow!(
                 "Invalid response data length: {}",
                 value.data.len()
             ));
         }
-        let diameter = u16::from_be_bytes([value.data[1], value.data[2]]) as f64 / 1000.0;
-        // Depending on if its a 2 axis Laser we get more values out of the data
-        let (x_axis, y_axis) = if value.data.len() >= 7 {
-            let x = u16::from_be_bytes([value.data[3], value.data[4]]) as f64 / 1000.0;
-            let y = u16::from_be_bytes([value.data[5], value.data[6]]) as f64 / 1000.0;
-            (
-                Some(Length::new::<millimeter>(x)),
-                Some(Length::new::<millimeter>(y)),
-            )
-        } else {
-            (None, None)
-        };
 
+        // data[0] is the byte count, followed by the diameter and the axis registers
+        let mut registers = value.data[1..]
+            .chunks_exact(2)
+            .map(|bytes| {
+                let raw = u16::from_be_bytes([bytes[0], bytes[1]]);
+                Length::new::<millimeter>(f64::from(raw) * register_map.scale)
+            })
+            .take(register_map.register_count() as usize);
+
+        let diameter = registers
+            .next()
+            .ok_or_else(|| anyhow!("Response contains no diameter"))?;
+
+        // Depending on the number of measuring heads we get more values out of the data
         Ok(Self {
-            diameter: Length::new::<millimeter>(diameter),
-            x_axis,
-            y_axis,
+            diameter,
+            axes: registers.collect(),
         })
     }
 }
@@ -78,14 +86,18 @@ impl TryFrom<ModbusResponse> for LaserDiameterResponse {
 impl From<LaserModbusRequsts> for modbus::ModbusRequest {
     fn from(request: LaserModbusRequsts) -> Self {
         match request {
-            LaserModbusRequsts::ReadDiameter => Self {
-                slave_id: 1,
-                function_code: modbus::ModbusFunctionCode::ReadInputRegister,
-                data: vec![
-                    0x00, 0x0E, // Start register = 0x000E
-                    0x00, 0x03, // Read 3 registers (AvgDiameter, X, Y)
-                ],
-            },
+            LaserModbusRequsts::ReadDiameter(gauge) => {
+                let [start_high, start_low] = gauge.register_map.start_register.to_be_bytes();
+                let [count_high, count_low] = gauge.register_map.register_count().to_be_bytes();
+                Self {
+                    slave_id: gauge.slave_id,
+                    function_code: modbus::ModbusFunctionCode::ReadInputRegister,
+                    data: vec![
+                        start_high, start_low, // Start register (AvgDiameter)
+                        count_high, count_low, // Diameter and one register per axis
+                    ],
+                }
+            }
         }
     }
 }
@@ -93,38 +105,47 @@ impl From<LaserModbusRequsts> for modbus::ModbusRequest {
 impl SerialDeviceNew for Laser {
     fn new_serial(
         params: &SerialDeviceNewParams,
-    ) -> Result<(DeviceIdentification, Arc<RwLock<Self>>), anyhow::Error> {
-        let laser_data = Some(LaserData {
-            diameter: Length::new::<millimeter>(0.0),
-            x_axis: None,
-            y_axis: None,
-            last_timestamp: Instant::now(),
-        });
-        let hash = hash_djb2(params.path.as_bytes());
-        let serial = byte_folding_u16(&hash.to_le_bytes());
-        let device_identification = DeviceIdentification {
-            device_machine_identification: Some(DeviceMachineIdentification {
-                machine_identification_unique: MachineIdentificationUnique {
-                    machine_identification: MachineIdentification {
-                        vendor: VENDOR_QITECH,
-                        machine: MACHINE_LASER_V1,
-                    },
-                    serial,
-                },
-                role: 0,
-            }),
-            device_hardware_identification: DeviceHardwareIdentification::Serial(
-                DeviceHardwareIdentificationSerial {
-                    path: params.path.clone(),
-                },
-            ),
-        };
+    ) -> Result<(Vec<DeviceIdentification>, Arc<RwLock<Self>>), anyhow::Error> {
+        let config = LaserConfig::for_device(params.identity());
+
+        // every gauge on the bus is its own machine, the role is the index of the gauge
+        let device_identifications = config
+            .gauges
+            .iter()
+            .enumerate()
+            .map(|(index, gauge)| {
+                // the first gauge keeps the serial of single gauge setups
+                let hash = match index {
+                    0 => hash_djb2(params.identity().as_bytes()),
+                    _ => hash_djb2(format!("{}/{}", params.identity(), gauge.slave_id).as_bytes()),
+                };
+                let serial = byte_folding_u16(&hash.to_le_bytes());
+                DeviceIdentification {
+                    device_machine_identification: Some(DeviceMachineIdentification {
+                        machine_identification_unique: MachineIdentificationUnique {
+                            machine_identification: MachineIdentification {
+                                vendor: VENDOR_QITECH,
+                                machine: MACHINE_LASER_V1,
+                            },
+                            serial,
+                        },
+                        role: index as u16,
+                    }),
+                    device_hardware_identification: DeviceHardwareIdentification::Serial(
+                        DeviceHardwareIdentificationSerial {
+                            path: params.path.clone(),
+                        },
+                    ),
+                }
+            })
+            .collect();
 
         let shutdown_flag: Arc<AtomicBool> = AtomicBool::new(false).into();
         // Create a new Laser instance
         let _self = Arc::new(RwLock::new(Self {
-            data: laser_data,
+            data: vec![None; config.gauges.len()],
             path: params.path.clone(),
+            config,
             shutdown_flag: shutdown_flag.clone(),
         }));
         //// Spawn the device thread
@@ -138,7 +159,7 @@ impl SerialDeviceNew for Laser {
                 });
             })?;
 
-        Ok((device_identification, _self))
+        Ok((device_identifications, _self))
     }
 }
 
@@ -153,53 +174,75 @@ impl Drop for Laser {
 #[derive(Debug, Clone)]
 pub struct LaserData {
     pub diameter: Length,
-    pub x_axis: Option<Length>,
-    pub y_axis: Option<Length>,
+    /// One value per measuring head
+    pub axes: Vec<Length>,
     pub last_timestamp: Instant,
 }
 
 impl Laser {
-    pub async fn get_diameter(&self) -> Result<Length, String> {
-        match &self.data {
-            Some(data) => Ok(data.diameter),
-            None => Err("No data from Laser".to_string()),
+    pub async fn get_diameter(&self, gauge: usize) -> Result<Length, String> {
+        match self.data.get(gauge) {
+            Some(Some(data)) => Ok(data.diameter),
+            _ => Err("No data from Laser".to_string()),
         }
     }
 
-    pub async fn get_x(&self) -> Result<Option<Length>, String> {
-        match &self.data {
-            Some(data) => Ok(data.x_axis),
-            None => Err("No data from Laser".to_string()),
+    pub async fn get_axes(&self, gauge: usize) -> Result<Vec<Length>, String> {
+        match self.data.get(gauge) {
+            Some(Some(data)) => Ok(data.axes.clone()),
+            _ => Err("No data from Laser".to_string()),
         }
     }
 
-    pub async fn get_y(&self) -> Result<Option<Length>, String> {
-        match &self.data {
-            Some(data) => Ok(data.y_axis),
-            None => Err("No data from Laser".to_string()),
-        }
+    pub async fn get_data(&self, gauge: usize) -> Option<LaserData> {
+        self.data.get(gauge).cloned().flatten()
     }
 
-    pub async fn get_data(&self) -> Option<LaserData> {
-        self.data.clone()
+    fn read_gauge(
+        port: &mut dyn SerialPort,
+        gauge: &LaserGaugeConfig,
+        baudrate: u32,
+    ) -> Result<Option<LaserDiameterResponse>, anyhow::Error> {
+        let request: ModbusRequest = LaserModbusRequsts::ReadDiameter(*gauge).into();
+        let request_buffer: Vec<u8> = request.into();
+
+        // send diameter request, once per poll so a silent gauge doesn't hold up the others
+        port.write_all(&request_buffer)
+            .map_err(|e| anyhow!("Failed to write to port: {}", e))?;
+
+        // wait for the response
+        std::thread::sleep(modbus::calculate_modbus_rtu_timeout(
+            8,
+            Duration::from_millis(10),
+            baudrate,
+            8,
+        ));
+        let response = modbus::receive_data_modbus(port)?
+            .map(ModbusResponse::try_from)
+            .transpose()?;
+
+        match response {
+            // on a shared bus a late answer of another gauge is ignored
+            Some(response) if response.slave_id != gauge.slave_id => Ok(None),
+            // try to convert it to a LaserDiameterResponse
+            Some(response) => LaserDiameterResponse::parse(response, &gauge.register_map).map(Some),
+            None => Ok(None),
+        }
     }
 
     async fn process(_self: Arc<RwLock<Self>>) -> Result<(), anyhow::Error> {
-        let path = {
+        let (path, config) = {
             let read_guard = _self.read().await;
-            read_guard.path.clone()
+            (read_guard.path.clone(), read_guard.config.clone())
         };
 
-        let request: ModbusRequest = LaserModbusRequsts::ReadDiameter.into();
-        let request_buffer: Vec<u8> = request.into();
-
         // port configuration
-        let mut port: Box<dyn SerialPort> = serialport::new(&path, 38_400)
+        let mut port: Box<dyn SerialPort> = serialport::new(&path, config.baudrate)
             .data_bits(DataBits::Eight)
-            .parity(Parity::None)
-            .stop_bits(StopBits::One)
+            .parity(config.parity.into())
+            .stop_bits(config.stop_bits())
             .flow_control(FlowControl::None)
-            .timeout(Duration::from_millis(500)) // start with something forgiving
+            .timeout(config.timeout())
             .open()
             .map_err(|e| anyhow!("Failed to open port {}: {}", path, e))?;
 
@@ -208,38 +251,121 @@ impl Laser {
 
         port.clear(ClearBuffer::All).ok();
 
+        // when an offline gauge is asked again, `None` while it answers
+        let mut retry_at: Vec<Option<Instant>> = vec![None; config.gauges.len()];
+
         while !_self.read().await.shutdown_flag.load(Ordering::SeqCst) {
-            // send diameter request
-            let response = retry_n_times(10, || {
-                if let Err(e) = port.write_all(&request_buffer) {
-                    return Err(anyhow!("Failed to write to port: {}", e));
+            let poll_start = Instant::now();
+
+            for (index, gauge) in config.gauges.iter().enumerate() {
+                if retry_at[index].is_some_and(|retry_at| poll_start < retry_at) {
+                    continue;
                 }
 
-                // wait for the response
-                std::thread::sleep(modbus::calculate_modbus_rtu_timeout(
-                    8,
-                    Duration::from_millis(10),
-                    38400,
-                    8,
-                ));
-                modbus::receive_data_modbus(&mut *port)?
-                    .map(ModbusResponse::try_from)
-                    .transpose()
-            })?;
+                let response = match Self::read_gauge(&mut *port, gauge, config.baudrate) {
+                    Ok(response) => {
+                        if retry_at[index].take().is_some() {
+                            tracing::info!(
+                                "Laser gauge {} on {} is answering again",
+                                gauge.slave_id,
+                                path
+                            );
+                        }
+                        response
+                    }
+                    // a failing gauge must not stop the other gauges on the bus
+                    Err(e) => {
+                        let was_online = retry_at[index]
+                            .replace(Instant::now() + OFFLINE_RETRY_INTERVAL)
+                            .is_none();
+                        if was_online {
+                            tracing::warn!(
+                                "Laser gauge {} on {} is not answering: {}",
+                                gauge.slave_id,
+                                path,
+                                e
+                            );
+                        }
+                        port.clear(ClearBuffer::All).ok();
+                        continue;
+                    }
+                };
 
-            if let Some(diameter_response) = response {
-                // try to convert it to a LaserDiameterResponse
-                let diameter_response = LaserDiameterResponse::try_from(diameter_response)?;
-                // save the diameter
-                let mut self_guard = _self.write().await;
-                self_guard.data = Some(LaserData {
-                    diameter: diameter_response.diameter,
-                    x_axis: diameter_response.x_axis,
-                    y_axis: diameter_response.y_axis,
-                    last_timestamp: Instant::now(),
-                });
+                if let Some(diameter_response) = response {
+                    // save the diameter
+                    let mut self_guard = _self.write().await;
+                    self_guard.data[index] = Some(LaserData {
+                        diameter: diameter_response.diameter,
+                        axes: diameter_response.axes,
+                        last_timestamp: Instant::now(),
+                    });
+                }
             }
+
+            // `None` sorts first, so this is only set while every gauge is offline
+            let next_poll = retry_at.iter().min().copied().flatten();
+            let next_poll = next_poll.map_or(poll_start + config.poll_interval(), |retry_at| {
+                retry_at.max(poll_start + config.poll_interval())
+            });
+            thread::sleep(next_poll.saturating_duration_since(Instant::now()));
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use control_core::modbus::ModbusFunctionCode;
+
+    fn response(data: Vec<u8>) -> ModbusResponse {
+        ModbusResponse {
+            slave_id: 1,
+            function_code: ModbusFunctionCode::ReadInputRegister,
+            data,
+            crc: 0,
+        }
+    }
+
+    #[test]
+    fn test_parse_diameter_response() {
+        let register_map = LaserRegisterMap {
+            axes: 3,
+            ..Default::default()
+        };
+        let parsed = LaserDiameterResponse::parse(
+            response(vec![8, 0x06, 0xD6, 0x06, 0xD0, 0x06, 0xDC, 0x06, 0xD6]),
+            &register_map,
+        )
+        .unwrap();
+        assert_relative_eq!(parsed.diameter.get::<millimeter>(), 1.75);
+        let axes: Vec<f64> = parsed.axes.iter().map(|a| a.get::<millimeter>()).collect();
+        assert_eq!(axes.len(), 3);
+        for (axis, expected) in axes.iter().zip([1.744, 1.756, 1.75]) {
+            assert_relative_eq!(*axis, expected);
+        }
+
+        // single axis gauges only answer with the diameter
+        let parsed =
+            LaserDiameterResponse::parse(response(vec![2, 0x06, 0xD6]), &register_map).unwrap();
+        assert!(parsed.axes.is_empty());
+
+        assert!(LaserDiameterResponse::parse(response(vec![0]), &register_map).is_err());
+    }
+
+    #[test]
+    fn test_read_diameter_request() {
+        let gauge = LaserGaugeConfig {
+            slave_id: 3,
+            register_map: LaserRegisterMap {
+                start_register: 0x0010,
+                axes: 3,
+                scale: 0.001,
+            },
+        };
+        let request: ModbusRequest = LaserModbusRequsts::ReadDiameter(gauge).into();
+        assert_eq!(request.slave_id, 3);
+        assert_eq!(request.data, [0x00, 0x10, 0x00, 0x04]);
+    }
+}
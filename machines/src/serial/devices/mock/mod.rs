@@ -23,7 +23,7 @@ impl SerialDevice for MockSerialDevice {}
 impl SerialDeviceNew for MockSerialDevice {
     fn new_serial(
         params: &SerialDeviceNewParams,
-    ) -> Result<(DeviceIdentification, Arc<RwLock<Self>>), anyhow::Error>
+    ) -> Result<(Vec<DeviceIdentification>, Arc<RwLock<Self>>), anyhow::Error>
     where
         Self: Sized,
     {
@@ -53,6 +53,6 @@ impl SerialDeviceNew for MockSerialDevice {
             path: params.path.clone(),
         }));
 
-        Ok((device_identification, mock_serial_device))
+        Ok((vec![device_identification], mock_serial_device))
     }
 }
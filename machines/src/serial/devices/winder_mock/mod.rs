@@ -21,7 +21,7 @@ impl SerialDevice for WinderMockSerialDevice {}
 impl SerialDeviceNew for WinderMockSerialDevice {
     fn new_serial(
         params: &SerialDeviceNewParams,
-    ) -> Result<(DeviceIdentification, Arc<RwLock<Self>>), anyhow::Error>
+    ) -> Result<(Vec<DeviceIdentification>, Arc<RwLock<Self>>), anyhow::Error>
     where
         Self: Sized,
     {
@@ -51,6 +51,6 @@ impl SerialDeviceNew for WinderMockSerialDevice {
             path: params.path.clone(),
         }));
 
-        Ok((device_identification, mock_serial_device))
+        Ok((vec![device_identification], mock_serial_device))
     }
 }
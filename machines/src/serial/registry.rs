@@ -10,16 +10,16 @@ use crate::serial::devices::mock::MockSerialDevice;
 
 use crate::SerialDeviceNewParams;
 use anyhow::{Error, Result};
+use serialport::UsbPortInfo;
 use smol::lock::RwLock;
 use std::{any::TypeId, collections::HashMap, sync::Arc};
 
-pub type SerialDeviceNewClosure = Arc<
-    dyn Fn(
-            &SerialDeviceNewParams,
-        ) -> Result<(DeviceIdentification, Arc<RwLock<dyn SerialDevice>>), Error>
-        + Send
-        + Sync,
->;
+/// One identification per machine behind the device and the device itself
+pub type SerialDeviceNewResult =
+    Result<(Vec<DeviceIdentification>, Arc<RwLock<dyn SerialDevice>>), Error>;
+
+pub type SerialDeviceNewClosure =
+    Arc<dyn Fn(&SerialDeviceNewParams) -> SerialDeviceNewResult + Send + Sync>;
 
 #[derive(Clone)]
 pub struct SerialDeviceRegistry {
@@ -48,18 +48,26 @@ impl SerialDeviceRegistry {
             (
                 serial_device_identification,
                 Arc::new(move |params| {
-                    let (identification, device) = T::new_serial(params)?;
-                    Ok((identification, device))
+                    let (identifications, device) = T::new_serial(params)?;
+                    Ok((identifications, device))
                 }),
             ),
         );
     }
 
+    /// Finds the registered device type of a USB serial port by its VID/PID
+    pub fn identify_usb_port(&self, port: &UsbPortInfo) -> Option<&SerialDeviceIdentification> {
+        self.type_map
+            .values()
+            .map(|(sdi, _)| sdi)
+            .find(|sdi| sdi.vendor_id == port.vid && sdi.product_id == port.pid)
+    }
+
     pub fn new_serial_device(
         &self,
         serial_device_new_params: &SerialDeviceNewParams,
         serial_device_identification: &SerialDeviceIdentification,
-    ) -> Result<(DeviceIdentification, Arc<RwLock<dyn SerialDevice>>), anyhow::Error> {
+    ) -> SerialDeviceNewResult {
         // find serial new function by comparing ProdutConfig
         let (_, serial_new_fn) = self
             .type_map
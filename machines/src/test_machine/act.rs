@@ -1,5 +1,5 @@
 use super::TestMachine;
-use crate::{MachineAct, MachineMessage};
+use crate::{MachineAct, MachineMessage, ShutdownPolicy};
 use ethercat_hal::devices::el2522::EL2522Port;
 use ethercat_hal::io::pulse_train_output::{PulseTrainOutputDevice, PulseTrainOutputOutput};
 use std::time::{Duration, Instant};
@@ -91,6 +91,27 @@ impl MachineAct for TestMachine {
         }
     }
 
+    fn shutdown(&mut self, _now: Instant, _policy: &ShutdownPolicy) -> bool {
+        if self.led_on.iter().any(|on| *on) {
+            self.set_all_leds(false);
+        }
+
+        self.motor_running = false;
+        self.motor_was_running = false;
+        let mut pto = smol::block_on(self.pto.write());
+        let current_output = pto.get_output(EL2522Port::PTO2);
+        pto.set_output(
+            EL2522Port::PTO2,
+            PulseTrainOutputOutput {
+                frequency_value: 0,
+                go_counter: false,
+                set_counter: false,
+                ..current_output
+            },
+        );
+        true
+    }
+
     fn act_machine_message(&mut self, msg: MachineMessage) {
         match msg {
             MachineMessage::SubscribeNamespace(namespace) => {
@@ -98,9 +119,9 @@ impl MachineAct for TestMachine {
                 self.emit_state();
             }
             MachineMessage::UnsubscribeNamespace => self.namespace.namespace = None,
-            MachineMessage::HttpApiJsonRequest(value) => {
+            MachineMessage::HttpApiJsonRequest(value, reply) => {
                 use crate::MachineApi;
-                let _res = self.api_mutate(value);
+                reply.send(self.api_mutate(value));
             }
             MachineMessage::ConnectToMachine(_machine_connection) => {
                 // Does not connect to any Machine; do nothing
@@ -1,4 +1,5 @@
 use super::TestMachine;
+use crate::api_schema::{MachineApiSchema, MachineSchema};
 use crate::{MachineApi, MachineMessage};
 use control_core::socketio::{
     event::{Event, GenericEvent},
@@ -6,11 +7,12 @@ use control_core::socketio::{
         CacheFn, CacheableEvents, Namespace, NamespaceCacheingLogic, cache_first_and_last_event,
     },
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::sync::Arc;
 
-#[derive(Serialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, JsonSchema)]
 pub struct StateEvent {
     pub led_on: [bool; 8],
     pub motor_running: bool,
@@ -31,7 +33,7 @@ pub enum TestMachineEvents {
     State(Event<StateEvent>),
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(tag = "action", content = "value")]
 pub enum Mutation {
     SetLed { index: usize, on: bool },
@@ -66,6 +68,13 @@ impl CacheableEvents<TestMachineEvents> for TestMachineEvents {
     }
 }
 
+impl MachineSchema for TestMachine {
+    fn api_schema() -> MachineApiSchema {
+        MachineApiSchema::new::<Mutation>("TestMachine", Self::MACHINE_IDENTIFICATION)
+            .with_event::<StateEvent>("StateEvent")
+    }
+}
+
 impl MachineApi for TestMachine {
     fn api_get_sender(&self) -> smol::channel::Sender<MachineMessage> {
         self.api_sender.clone()
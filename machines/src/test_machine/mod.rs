@@ -63,7 +63,7 @@ impl TestMachine {
 
 impl TestMachine {
     pub fn emit_state(&mut self) {
-        let event = StateEvent {
+        let state = StateEvent {
             led_on: self.led_on,
             motor_running: self.motor_running,
             motor_pos: self.pto_counter_value,
@@ -71,8 +71,8 @@ impl TestMachine {
             motor_freq: (self.motor_speed_mm_s * 20.0 * 100.0) as i32,
             motor_error: self.pto_error,
             motor_ramp_active: self.pto_ramp_active,
-        }
-        .build();
+        };
+        let event = state.build();
 
         self.namespace.emit(TestMachineEvents::State(event));
     }
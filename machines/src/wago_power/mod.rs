@@ -1,7 +1,11 @@
-use crate::{MachineChannel, MachineWithChannel};
-use anyhow::Result;
+use crate::api_schema::{MachineApiSchema, MachineSchema};
+use crate::machine_identification::MachineIdentification;
+use crate::{
+    MACHINE_WAGO_POWER_V1, MachineChannel, MachineWithChannel, ShutdownPolicy, VENDOR_QITECH,
+};
+use anyhow::{Result, bail};
 use control_core::{
-    modbus::tcp::ModbusTcpDevice,
+    ethernet::modbus_tcp_discovery::ModbusTcpDeviceProfile,
     socketio::{
         event::{BuildEvent, GenericEvent},
         namespace::{
@@ -11,26 +15,107 @@ use control_core::{
     },
 };
 use control_core_derive::BuildEvent;
-use serde::*;
-use smol::lock::Mutex;
-use std::{
-    net::SocketAddr,
-    time::{Duration, Instant},
+use registers::{
+    MODBUS_DC_OFF, MODBUS_DC_ON, MODBUS_HICCUP_POWER, OUTPUT_MEASUREMENT_LEN, OUTPUT_SETTINGS_LEN,
+    OutputStatus, PowerSupplyRegisters, RegisterMap,
 };
+use schemars::JsonSchema;
+use serde::*;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 use units::{
     electric_current::milliampere,
     electric_potential::{millivolt, volt},
     *,
 };
 
-const MODBUS_DC_OFF: u16 = 0;
-const MODBUS_DC_ON: u16 = 1;
-const MODBUS_HICCUP_POWER: u16 = 1 << 8;
+pub mod registers;
+#[cfg(any(test, feature = "mock-machine"))]
+pub mod simulation;
+
+/// WAGO 2787-2144 power supply, identified by its module number in register 0x2 - 0x5
+pub const MODBUS_TCP_PROFILE: ModbusTcpDeviceProfile = ModbusTcpDeviceProfile {
+    name: "WAGO 2787-2144",
+    identification: &[(0x2, 0x2787_2144), (0x4, 0x0000_0000)],
+    serial_register: 0x000A,
+};
+
+/// Number of outputs of the WAGO 2787-2144
+pub const MODBUS_TCP_PROFILE_OUTPUTS: usize = 1;
+
+const MAX_OUTPUTS: usize = 8;
+
+/// The mock simulates a multi-output unit
+#[cfg(feature = "mock-machine")]
+const MOCK_OUTPUTS: usize = 4;
+
+const DEFAULT_VOLTAGE_MV: u16 = 24000;
+const DEFAULT_WARNING_THRESHOLD_MA: u16 = 5000;
+const DEFAULT_CURRENT_LIMIT_MA: u16 = 40000;
+/// Written to the power-on delay register of every output, sequencing across outputs is done by us
+const POWER_ON_DELAY_MS: u16 = 100;
+/// Delay between two outputs in the default power-on sequence
+const DEFAULT_SEQUENCE_DELAY_MS: u64 = 100;
+
+/// Adjustable output voltage range
+const MIN_VOLTAGE_MV: f64 = 22500.0;
+const MAX_VOLTAGE_MV: f64 = 28500.0;
+const MAX_CURRENT_MA: f64 = 40000.0;
+
+/// Outputs and register layout of the discovered power supplies, `[wago_power]` in the server config
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct WagoPowerConfig {
+    pub outputs: usize,
+    pub registers: RegisterMap,
+}
+
+impl Default for WagoPowerConfig {
+    fn default() -> Self {
+        Self {
+            outputs: MODBUS_TCP_PROFILE_OUTPUTS,
+            registers: RegisterMap::default(),
+        }
+    }
+}
+
+impl WagoPowerConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=MAX_OUTPUTS).contains(&self.outputs) {
+            bail!("wago_power.outputs must be between 1 and {}", MAX_OUTPUTS);
+        }
+        self.registers.validate(self.outputs)
+    }
+}
+
+static WAGO_POWER_CONFIG: OnceLock<WagoPowerConfig> = OnceLock::new();
+
+/// Sets the config used for every power supply found from now on, called once at startup
+pub fn set_wago_power_config(config: WagoPowerConfig) -> Result<()> {
+    config.validate()?;
+    WAGO_POWER_CONFIG
+        .set(config)
+        .map_err(|_| anyhow::anyhow!("The WAGO power config is already set"))
+}
+
+/// The config set at startup, the 2787-2144 defaults otherwise
+pub fn wago_power_config() -> &'static WagoPowerConfig {
+    WAGO_POWER_CONFIG.get_or_init(WagoPowerConfig::default)
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct OutputLiveValues {
+    voltage: f64,
+    current: f64,
+}
 
-#[derive(Serialize, Debug, Clone, BuildEvent)]
+#[derive(Serialize, Debug, Clone, BuildEvent, JsonSchema)]
 pub struct LiveValuesEvent {
+    /// Voltage of the first output
     voltage: f64,
+    /// Sum of the current of all outputs
     current: f64,
+    outputs: Vec<OutputLiveValues>,
 }
 
 impl CacheableEvents<Self> for LiveValuesEvent {
@@ -43,24 +128,117 @@ impl CacheableEvents<Self> for LiveValuesEvent {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
 pub enum Mode {
     Off,
     On24V,
 }
 
-impl Mode {
-    pub fn as_u16(&self) -> u16 {
-        match self {
-            Mode::Off => MODBUS_DC_OFF,
-            Mode::On24V => MODBUS_HICCUP_POWER | MODBUS_DC_ON,
+/// Setpoints of one output, as written to its settings registers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct OutputSettings {
+    enabled: bool,
+    /// mV
+    voltage: u16,
+    /// mA, above this current the overload status bit is set
+    warning_threshold: u16,
+    /// mA, above this current the output goes into hiccup mode
+    current_limit: u16,
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            voltage: DEFAULT_VOLTAGE_MV,
+            warning_threshold: DEFAULT_WARNING_THRESHOLD_MA,
+            current_limit: DEFAULT_CURRENT_LIMIT_MA,
+        }
+    }
+}
+
+impl OutputSettings {
+    fn control_bits(&self) -> u16 {
+        match self.enabled {
+            true => MODBUS_HICCUP_POWER | MODBUS_DC_ON,
+            false => MODBUS_DC_OFF,
         }
     }
+
+    fn registers(&self) -> [u16; OUTPUT_SETTINGS_LEN as usize] {
+        [
+            self.voltage,
+            self.warning_threshold,
+            self.control_bits(),
+            POWER_ON_DELAY_MS,
+            self.current_limit,
+        ]
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct OutputState {
+    enabled: bool,
+    /// V
+    voltage: f64,
+    /// mA
+    warning_threshold: f64,
+    /// mA
+    current_limit: f64,
+    status: OutputStatus,
+}
+
+/// Output `output` is switched on `delay_ms` after the previous step
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+pub struct PowerOnStep {
+    pub output: usize,
+    pub delay_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RunningSequence {
+    next_step: usize,
+    last_step: Instant,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+pub enum AlarmKind {
+    Overload,
+    Hiccup,
+    OverTemperature,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+pub struct Alarm {
+    output: usize,
+    kind: AlarmKind,
+}
+
+impl Alarm {
+    fn from_status(output: usize, status: &OutputStatus) -> impl Iterator<Item = Self> {
+        [
+            (status.overload, AlarmKind::Overload),
+            (status.hiccup, AlarmKind::Hiccup),
+            (status.over_temperature, AlarmKind::OverTemperature),
+        ]
+        .into_iter()
+        .filter(|(active, _)| *active)
+        .map(move |(_, kind)| Self { output, kind })
+    }
+
+    /// The supply can't deliver power on the output, further outputs should not be switched on
+    const fn is_fault(&self) -> bool {
+        matches!(self.kind, AlarmKind::Hiccup | AlarmKind::OverTemperature)
+    }
 }
 
-#[derive(Serialize, Debug, Clone, BuildEvent)]
+#[derive(Serialize, Debug, Clone, BuildEvent, JsonSchema)]
 pub struct StateEvent {
     mode: Mode,
+    outputs: Vec<OutputState>,
+    power_on_sequence: Vec<PowerOnStep>,
+    power_on_sequence_running: bool,
+    alarms: Vec<Alarm>,
     is_default_state: bool,
 }
 
@@ -74,111 +252,367 @@ impl CacheableEvents<Self> for StateEvent {
     }
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 pub enum Mutation {
+    /// `On24V` switches the outputs on in the order of the power-on sequence, `Off` switches all outputs off
     SetMode(Mode),
+    SetOutputEnabled {
+        output: usize,
+        enabled: bool,
+    },
+    /// V
+    SetOutputVoltage {
+        output: usize,
+        #[schemars(range(min = MIN_VOLTAGE_MV / 1000.0, max = MAX_VOLTAGE_MV / 1000.0))]
+        voltage: f64,
+    },
+    /// mA
+    SetOutputCurrentLimit {
+        output: usize,
+        #[schemars(range(min = 0.0, max = MAX_CURRENT_MA))]
+        current_limit: f64,
+    },
+    /// mA
+    SetOutputWarningThreshold {
+        output: usize,
+        #[schemars(range(min = 0.0, max = MAX_CURRENT_MA))]
+        warning_threshold: f64,
+    },
+    /// Has to contain every output exactly once
+    SetPowerOnSequence {
+        steps: Vec<PowerOnStep>,
+    },
 }
 
 #[derive(Debug)]
 pub struct WagoPower {
-    mode: Mode,
     channel: MachineChannel,
-    #[cfg(not(feature = "mock-machine"))]
-    device: Mutex<ModbusTcpDevice>,
+    registers: Box<dyn PowerSupplyRegisters>,
+    register_map: RegisterMap,
+    outputs: Vec<OutputSettings>,
+    status: Vec<OutputStatus>,
+    power_on_sequence: Vec<PowerOnStep>,
+    running_sequence: Option<RunningSequence>,
+    alarms: Vec<Alarm>,
     last_emit: Instant,
     emitted_default_state: bool,
 }
 
 impl WagoPower {
-    pub async fn new(
+    pub const MACHINE_IDENTIFICATION: MachineIdentification = MachineIdentification {
+        vendor: VENDOR_QITECH,
+        machine: MACHINE_WAGO_POWER_V1,
+    };
+
+    #[cfg(not(feature = "mock-machine"))]
+    pub async fn new(channel: MachineChannel, addr: std::net::SocketAddr) -> Result<Self> {
+        let device = control_core::modbus::tcp::ModbusTcpDevice::new(addr).await?;
+        Ok(Self::from_registers(
+            channel,
+            Box::new(device),
+            wago_power_config(),
+        ))
+    }
+
+    #[cfg(feature = "mock-machine")]
+    pub async fn new(channel: MachineChannel) -> Result<Self> {
+        let config = WagoPowerConfig {
+            outputs: MOCK_OUTPUTS,
+            ..wago_power_config().clone()
+        };
+        let simulation =
+            simulation::SimulatedWagoPower::with_register_map(config.outputs, config.registers);
+        Ok(Self::from_registers(channel, Box::new(simulation), &config))
+    }
+
+    pub fn from_registers(
         channel: MachineChannel,
-        #[cfg(not(feature = "mock-machine"))] addr: SocketAddr,
-    ) -> Result<Self> {
-        Ok(Self {
-            mode: Mode::Off,
+        registers: Box<dyn PowerSupplyRegisters>,
+        config: &WagoPowerConfig,
+    ) -> Self {
+        let outputs = config.outputs;
+        let power_on_sequence = (0..outputs)
+            .map(|output| PowerOnStep {
+                output,
+                delay_ms: if output == 0 {
+                    0
+                } else {
+                    DEFAULT_SEQUENCE_DELAY_MS
+                },
+            })
+            .collect();
+
+        Self {
             channel,
-            #[cfg(not(feature = "mock-machine"))]
-            device: Mutex::new(ModbusTcpDevice::new(addr).await?),
+            registers,
+            register_map: config.registers,
+            outputs: vec![OutputSettings::default(); outputs],
+            status: vec![OutputStatus::default(); outputs],
+            power_on_sequence,
+            running_sequence: None,
+            alarms: vec![],
             last_emit: Instant::now(),
             emitted_default_state: false,
-        })
+        }
     }
 
-    #[cfg(feature = "mock-machine")]
-    fn get_live_values(&mut self) -> Result<LiveValuesEvent> {
-        match self.mode {
-            Mode::Off => Ok(LiveValuesEvent {
-                voltage: 0.0,
-                current: 0.0,
-            }),
-            Mode::On24V => Ok(LiveValuesEvent {
-                voltage: 24.0,
-                current: 5000.0,
-            }),
+    fn mode(&self) -> Mode {
+        match self.running_sequence.is_some() || self.outputs.iter().all(|o| o.enabled) {
+            true => Mode::On24V,
+            false => Mode::Off,
         }
     }
 
-    #[cfg(not(feature = "mock-machine"))]
     fn get_live_values(&mut self) -> Result<LiveValuesEvent> {
-        let electric = smol::block_on(async {
-            let mut dev = self.device.lock().await;
-            dev.get_holding_registers(0x0500, 2).await
-        })?;
+        let mut outputs = Vec::with_capacity(self.outputs.len());
+        let mut status = Vec::with_capacity(self.outputs.len());
+
+        for output in 0..self.outputs.len() {
+            let values = self.registers.read(
+                self.register_map.output_measurement_register(output),
+                OUTPUT_MEASUREMENT_LEN,
+            )?;
+
+            let voltage = ElectricPotential::new::<millivolt>(f64::from(values[0]));
+            let current = ElectricCurrent::new::<milliampere>(f64::from(values[1]));
+
+            outputs.push(OutputLiveValues {
+                voltage: voltage.get::<volt>(),
+                current: current.get::<milliampere>(),
+            });
+            status.push(OutputStatus::from(values[2]));
+        }
 
-        let voltage = ElectricPotential::new::<millivolt>(f64::from(electric[0]));
-        let current = ElectricCurrent::new::<milliampere>(f64::from(electric[1]));
+        self.update_status(status);
 
         Ok(LiveValuesEvent {
-            voltage: voltage.get::<volt>(),
-            current: current.get::<milliampere>(),
+            voltage: outputs.first().map_or(0.0, |o| o.voltage),
+            current: outputs.iter().map(|o| o.current).sum(),
+            outputs,
         })
     }
 
+    /// Raises alarms for new faults and stops a running power-on sequence on a fault
+    fn update_status(&mut self, status: Vec<OutputStatus>) {
+        if status == self.status {
+            return;
+        }
+
+        let alarms: Vec<Alarm> = status
+            .iter()
+            .enumerate()
+            .flat_map(|(output, status)| Alarm::from_status(output, status))
+            .collect();
+
+        for alarm in alarms.iter().filter(|a| !self.alarms.contains(a)) {
+            tracing::warn!("WAGO power supply alarm {:?}", alarm);
+        }
+
+        if self.running_sequence.is_some() && alarms.iter().any(Alarm::is_fault) {
+            tracing::warn!("Aborting power-on sequence because of a fault");
+            self.running_sequence = None;
+        }
+
+        self.status = status;
+        self.alarms = alarms;
+        self.emit_state();
+    }
+
     fn emit_state(&mut self) {
+        let outputs = self
+            .outputs
+            .iter()
+            .zip(&self.status)
+            .map(|(settings, status)| OutputState {
+                enabled: settings.enabled,
+                voltage: ElectricPotential::new::<millivolt>(f64::from(settings.voltage))
+                    .get::<volt>(),
+                warning_threshold: f64::from(settings.warning_threshold),
+                current_limit: f64::from(settings.current_limit),
+                status: *status,
+            })
+            .collect();
+
         let event = StateEvent {
-            mode: self.mode.clone(),
+            mode: self.mode(),
+            outputs,
+            power_on_sequence: self.power_on_sequence.clone(),
+            power_on_sequence_running: self.running_sequence.is_some(),
+            alarms: self.alarms.clone(),
             is_default_state: !self.emitted_default_state,
         };
         self.channel.emit(event);
     }
 
+    fn check_output(&self, output: usize) -> Result<()> {
+        if output >= self.outputs.len() {
+            bail!(
+                "Output {} does not exist, the power supply has {} outputs",
+                output,
+                self.outputs.len()
+            );
+        }
+        Ok(())
+    }
+
+    fn transmit_output(&mut self, output: usize) -> Result<()> {
+        let registers = self.outputs[output].registers();
+        self.registers.write(
+            self.register_map.output_settings_register(output),
+            &registers,
+        )
+    }
+
+    /// The last read status shows a fault on an output
+    fn check_no_fault(&self) -> Result<()> {
+        if let Some(alarm) = self.alarms.iter().find(|alarm| alarm.is_fault()) {
+            bail!(
+                "Output {} has a {:?} fault, no outputs are switched on",
+                alarm.output,
+                alarm.kind
+            );
+        }
+        Ok(())
+    }
+
     fn set_mode(&mut self, mode: Mode) -> Result<()> {
-        self.mode = mode;
+        match mode {
+            Mode::Off => {
+                self.running_sequence = None;
+                for output in 0..self.outputs.len() {
+                    self.outputs[output].enabled = false;
+                    self.transmit_output(output)?;
+                }
+            }
+            Mode::On24V => {
+                self.check_no_fault()?;
+                self.running_sequence = Some(RunningSequence {
+                    next_step: 0,
+                    last_step: Instant::now(),
+                });
+            }
+        }
+
+        self.emit_state();
+        Ok(())
+    }
+
+    /// Switches on the outputs of the running power-on sequence whose delay has passed
+    fn update_power_on_sequence(&mut self, now: Instant) -> Result<()> {
+        while let Some(mut sequence) = self.running_sequence {
+            let Some(step) = self.power_on_sequence.get(sequence.next_step).copied() else {
+                self.running_sequence = None;
+                self.emit_state();
+                break;
+            };
+
+            if now.duration_since(sequence.last_step) < Duration::from_millis(step.delay_ms) {
+                break;
+            }
+
+            if let Err(e) = self.check_no_fault() {
+                tracing::warn!("Aborting power-on sequence: {}", e);
+                self.running_sequence = None;
+                self.emit_state();
+                break;
+            }
+
+            self.outputs[step.output].enabled = true;
+            self.transmit_output(step.output)?;
+
+            sequence.next_step += 1;
+            sequence.last_step = now;
+            self.running_sequence = Some(sequence);
+            self.emit_state();
+        }
 
-        #[cfg(not(feature = "mock-machine"))]
-        smol::block_on(self.transmit_voltage())?;
+        Ok(())
+    }
 
+    fn set_output_enabled(&mut self, output: usize, enabled: bool) -> Result<()> {
+        self.check_output(output)?;
+        self.outputs[output].enabled = enabled;
+        self.transmit_output(output)?;
         self.emit_state();
         Ok(())
     }
 
-    #[cfg(not(feature = "mock-machine"))]
-    async fn transmit_voltage(&mut self) -> Result<()> {
-        let mut dev = self.device.lock().await;
+    fn set_output_voltage(&mut self, output: usize, voltage: f64) -> Result<()> {
+        self.check_output(output)?;
+        let voltage = ElectricPotential::new::<volt>(voltage).get::<millivolt>();
+        if !(MIN_VOLTAGE_MV..=MAX_VOLTAGE_MV).contains(&voltage) {
+            bail!(
+                "Output voltage {} mV is outside of {} - {} mV",
+                voltage,
+                MIN_VOLTAGE_MV,
+                MAX_VOLTAGE_MV
+            );
+        }
 
-        let voltage = 24000;
-        let warning_threshold = 5000; // For now
-        let control_bits = self.mode.as_u16();
-        let delay_ms = 100; // For now
+        self.outputs[output].voltage = voltage.round() as u16;
+        self.transmit_output(output)?;
+        self.emit_state();
+        Ok(())
+    }
 
-        dev.set_holding_registers(
-            0x0088,
-            &[voltage, warning_threshold, control_bits, delay_ms],
-        )
-        .await?;
+    fn set_output_current_limit(&mut self, output: usize, current_limit: f64) -> Result<()> {
+        self.check_output(output)?;
+        self.outputs[output].current_limit = Self::current_register(current_limit)?;
+        self.transmit_output(output)?;
+        self.emit_state();
+        Ok(())
+    }
 
+    fn set_output_warning_threshold(
+        &mut self,
+        output: usize,
+        warning_threshold: f64,
+    ) -> Result<()> {
+        self.check_output(output)?;
+        self.outputs[output].warning_threshold = Self::current_register(warning_threshold)?;
+        self.transmit_output(output)?;
+        self.emit_state();
         Ok(())
     }
 
-    #[cfg(feature = "mock-machine")]
-    pub async fn get_serial(&mut self) -> Result<u16> {
-        Ok(0xbeef)
+    /// Validates a current in mA
+    fn current_register(current: f64) -> Result<u16> {
+        if !(current > 0.0 && current <= MAX_CURRENT_MA) {
+            bail!(
+                "Current {} mA is outside of 0 - {} mA",
+                current,
+                MAX_CURRENT_MA
+            );
+        }
+        Ok(current.round() as u16)
     }
 
-    #[cfg(not(feature = "mock-machine"))]
-    pub async fn get_serial(&mut self) -> Result<u16> {
-        let mut dev = self.device.lock().await;
-        dev.get_u16(0x000B).await
+    fn set_power_on_sequence(&mut self, steps: Vec<PowerOnStep>) -> Result<()> {
+        if self.running_sequence.is_some() {
+            bail!("Can't change the power-on sequence while it is running");
+        }
+
+        let mut outputs: Vec<usize> = steps.iter().map(|step| step.output).collect();
+        outputs.sort_unstable();
+        if !outputs.iter().copied().eq(0..self.outputs.len()) {
+            bail!("The power-on sequence has to contain every output exactly once");
+        }
+
+        self.power_on_sequence = steps;
+        self.emit_state();
+        Ok(())
+    }
+
+    pub fn get_serial(&mut self) -> Result<u16> {
+        Ok(self.registers.read(self.register_map.serial, 1)?[0])
+    }
+}
+
+impl MachineSchema for WagoPower {
+    fn api_schema() -> MachineApiSchema {
+        MachineApiSchema::new::<Mutation>("WagoPower", Self::MACHINE_IDENTIFICATION)
+            .with_event::<LiveValuesEvent>("LiveValuesEvent")
+            .with_event::<StateEvent>("StateEvent")
     }
 }
 
@@ -196,6 +630,21 @@ impl MachineWithChannel for WagoPower {
 
         match mutation {
             Mutation::SetMode(mode) => self.set_mode(mode)?,
+            Mutation::SetOutputEnabled { output, enabled } => {
+                self.set_output_enabled(output, enabled)?
+            }
+            Mutation::SetOutputVoltage { output, voltage } => {
+                self.set_output_voltage(output, voltage)?
+            }
+            Mutation::SetOutputCurrentLimit {
+                output,
+                current_limit,
+            } => self.set_output_current_limit(output, current_limit)?,
+            Mutation::SetOutputWarningThreshold {
+                output,
+                warning_threshold,
+            } => self.set_output_warning_threshold(output, warning_threshold)?,
+            Mutation::SetPowerOnSequence { steps } => self.set_power_on_sequence(steps)?,
         }
 
         Ok(())
@@ -213,6 +662,8 @@ impl MachineWithChannel for WagoPower {
             self.emitted_default_state = true;
         }
 
+        self.update_power_on_sequence(now)?;
+
         if now.duration_since(self.last_emit) > Duration::from_secs_f64(1.0 / 30.0) {
             if let Ok(event) = self.get_live_values() {
                 self.channel.emit(event);
@@ -223,4 +674,268 @@ impl MachineWithChannel for WagoPower {
 
         Ok(())
     }
+
+    /// Only switches off if the policy says so, the outputs may power the EtherCAT couplers
+    fn shutdown(&mut self, _now: Instant, policy: &ShutdownPolicy) -> Result<bool> {
+        if !policy.power_off {
+            return Ok(true);
+        }
+        // `mode()` is only `On24V` with all outputs on, single outputs can be on as well
+        self.running_sequence = None;
+        for output in 0..self.outputs.len() {
+            if self.outputs[output].enabled {
+                self.outputs[output].enabled = false;
+                self.transmit_output(output)?;
+            }
+        }
+        self.emit_state();
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::simulation::SimulatedWagoPower;
+    use super::*;
+    use crate::machine_identification::{MachineIdentification, MachineIdentificationUnique};
+    use crate::{MACHINE_WAGO_POWER_V1, VENDOR_QITECH};
+    use serde_json::json;
+
+    fn power(outputs: usize) -> (WagoPower, SimulatedWagoPower) {
+        let channel = MachineChannel::new(MachineIdentificationUnique {
+            machine_identification: MachineIdentification {
+                vendor: VENDOR_QITECH,
+                machine: MACHINE_WAGO_POWER_V1,
+            },
+            serial: 0xbeef,
+        });
+        let simulation = SimulatedWagoPower::new(outputs);
+        let config = WagoPowerConfig {
+            outputs,
+            ..Default::default()
+        };
+        let power = WagoPower::from_registers(channel, Box::new(simulation.clone()), &config);
+        (power, simulation)
+    }
+
+    #[test]
+    fn test_power_on_sequence() {
+        let (mut power, _) = power(3);
+        let start = Instant::now();
+        power.update(start).unwrap();
+
+        power
+            .mutate(json!({"SetPowerOnSequence": {"steps": [
+                {"output": 2, "delay_ms": 0},
+                {"output": 0, "delay_ms": 500},
+                {"output": 1, "delay_ms": 500},
+            ]}}))
+            .unwrap();
+        power.mutate(json!({"SetMode": "On24V"})).unwrap();
+
+        let enabled =
+            |power: &WagoPower| power.outputs.iter().map(|o| o.enabled).collect::<Vec<_>>();
+
+        power.update(Instant::now()).unwrap();
+        assert_eq!(enabled(&power), [false, false, true]);
+
+        power
+            .update(Instant::now() + Duration::from_millis(600))
+            .unwrap();
+        assert_eq!(enabled(&power), [true, false, true]);
+
+        power
+            .update(Instant::now() + Duration::from_millis(1200))
+            .unwrap();
+        assert_eq!(enabled(&power), [true, true, true]);
+        assert!(power.running_sequence.is_none());
+        assert_eq!(power.mode(), Mode::On24V);
+
+        // every output has to be in the sequence
+        assert!(
+            power
+                .mutate(json!({"SetPowerOnSequence": {"steps": [{"output": 0, "delay_ms": 0}]}}))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_shutdown_policy() {
+        let (mut power, _) = power(2);
+        power.update(Instant::now()).unwrap();
+        power.mutate(json!({"SetMode": "On24V"})).unwrap();
+        power.update(Instant::now()).unwrap();
+        assert_eq!(power.mode(), Mode::On24V);
+
+        let keep = ShutdownPolicy { power_off: false };
+        assert!(MachineWithChannel::shutdown(&mut power, Instant::now(), &keep).unwrap());
+        assert_eq!(power.mode(), Mode::On24V);
+
+        let off = ShutdownPolicy { power_off: true };
+        assert!(MachineWithChannel::shutdown(&mut power, Instant::now(), &off).unwrap());
+        assert_eq!(power.mode(), Mode::Off);
+    }
+
+    #[test]
+    fn test_shutdown_single_output() {
+        let (mut power, mut simulation) = power(2);
+        power.update(Instant::now()).unwrap();
+        power
+            .mutate(json!({"SetOutputEnabled": {"output": 1, "enabled": true}}))
+            .unwrap();
+        assert_eq!(power.mode(), Mode::Off);
+
+        let off = ShutdownPolicy { power_off: true };
+        assert!(MachineWithChannel::shutdown(&mut power, Instant::now(), &off).unwrap());
+        assert!(power.outputs.iter().all(|output| !output.enabled));
+        let settings = simulation
+            .read(power.register_map.output_settings_register(1), 5)
+            .unwrap();
+        assert_eq!(settings[2] & MODBUS_DC_ON, 0);
+    }
+
+    #[test]
+    fn test_output_settings() {
+        let (mut power, mut simulation) = power(2);
+        power.update(Instant::now()).unwrap();
+
+        power
+            .mutate(json!({"SetOutputVoltage": {"output": 1, "voltage": 26.5}}))
+            .unwrap();
+        power
+            .mutate(json!({"SetOutputCurrentLimit": {"output": 1, "current_limit": 10000.0}}))
+            .unwrap();
+        power
+            .mutate(json!({"SetOutputEnabled": {"output": 1, "enabled": true}}))
+            .unwrap();
+
+        assert_eq!(
+            simulation
+                .read(power.register_map.output_settings_register(1), 5)
+                .unwrap(),
+            [
+                26500,
+                DEFAULT_WARNING_THRESHOLD_MA,
+                MODBUS_HICCUP_POWER | MODBUS_DC_ON,
+                POWER_ON_DELAY_MS,
+                10000
+            ]
+        );
+
+        let live_values = power.get_live_values().unwrap();
+        assert_eq!(live_values.outputs[1].voltage, 26.5);
+        assert_eq!(live_values.outputs[0].voltage, 0.0);
+
+        assert!(
+            power
+                .mutate(json!({"SetOutputVoltage": {"output": 1, "voltage": 48.0}}))
+                .is_err()
+        );
+        assert!(
+            power
+                .mutate(json!({"SetOutputEnabled": {"output": 2, "enabled": true}}))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_register_map() {
+        let config = WagoPowerConfig {
+            outputs: 2,
+            registers: RegisterMap {
+                serial: 0x0001,
+                output_settings: 0x1000,
+                output_measurement: 0x2000,
+                output_stride: 0x08,
+            },
+        };
+        config.validate().unwrap();
+        let simulation = SimulatedWagoPower::with_register_map(2, config.registers);
+        let channel = MachineChannel::new(MachineIdentificationUnique {
+            machine_identification: WagoPower::MACHINE_IDENTIFICATION,
+            serial: 0xbeef,
+        });
+        let mut power = WagoPower::from_registers(channel, Box::new(simulation.clone()), &config);
+        power.update(Instant::now()).unwrap();
+        power
+            .mutate(json!({"SetOutputEnabled": {"output": 1, "enabled": true}}))
+            .unwrap();
+
+        assert_eq!(power.get_serial().unwrap(), 0xbeef);
+        let live_values = power.get_live_values().unwrap();
+        assert_eq!(live_values.outputs[1].voltage, 24.0);
+        assert_eq!(live_values.outputs[0].voltage, 0.0);
+
+        assert!(
+            WagoPowerConfig {
+                outputs: 0,
+                ..Default::default()
+            }
+            .validate()
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_alarms() {
+        let (mut power, simulation) = power(2);
+        power.update(Instant::now()).unwrap();
+        power
+            .mutate(json!({"SetOutputEnabled": {"output": 0, "enabled": true}}))
+            .unwrap();
+
+        simulation.set_load(0, 6000);
+        power.get_live_values().unwrap();
+        assert_eq!(
+            power.alarms,
+            [Alarm {
+                output: 0,
+                kind: AlarmKind::Overload
+            }]
+        );
+        simulation.set_load(0, 2000);
+
+        // a fault stops the power-on sequence
+        power
+            .mutate(json!({"SetOutputEnabled": {"output": 0, "enabled": false}}))
+            .unwrap();
+        simulation.set_over_temperature(1, true);
+        power.mutate(json!({"SetMode": "On24V"})).unwrap();
+        power.update(Instant::now()).unwrap();
+        power.get_live_values().unwrap();
+        assert!(power.alarms.contains(&Alarm {
+            output: 1,
+            kind: AlarmKind::OverTemperature
+        }));
+        assert!(power.running_sequence.is_none());
+
+        // with a fault present the sequence doesn't start
+        assert!(power.mutate(json!({"SetMode": "On24V"})).is_err());
+        assert!(power.running_sequence.is_none());
+
+        simulation.set_over_temperature(1, false);
+        power.get_live_values().unwrap();
+        assert!(power.alarms.is_empty());
+
+        // a fault between two steps stops the sequence before the next output
+        power
+            .mutate(json!({"SetPowerOnSequence": {"steps": [
+                {"output": 0, "delay_ms": 0},
+                {"output": 1, "delay_ms": 500},
+            ]}}))
+            .unwrap();
+        power.mutate(json!({"SetMode": "Off"})).unwrap();
+        power.mutate(json!({"SetMode": "On24V"})).unwrap();
+        power.update(Instant::now()).unwrap();
+        assert!(power.outputs[0].enabled);
+        power.alarms.push(Alarm {
+            output: 0,
+            kind: AlarmKind::Hiccup,
+        });
+        power
+            .update(Instant::now() + Duration::from_millis(600))
+            .unwrap();
+        assert!(!power.outputs[1].enabled);
+        assert!(power.running_sequence.is_none());
+    }
 }
@@ -0,0 +1,189 @@
+use anyhow::{Result, bail};
+use control_core::modbus::tcp::ModbusTcpDevice;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+/// Register 0x000B, serial number of the power supply
+pub const SERIAL_REGISTER: u16 = 0x000B;
+
+/// First output settings block, every output has `OUTPUT_BLOCK_STRIDE` registers
+/// voltage (mV), warning threshold (mA), control bits, power-on delay (ms), current limit (mA)
+pub const OUTPUT_SETTINGS_REGISTER: u16 = 0x0088;
+pub const OUTPUT_SETTINGS_LEN: u16 = 5;
+
+/// First output measurement block, every output has `OUTPUT_BLOCK_STRIDE` registers
+/// voltage (mV), current (mA), status bits
+pub const OUTPUT_MEASUREMENT_REGISTER: u16 = 0x0500;
+pub const OUTPUT_MEASUREMENT_LEN: u16 = 3;
+
+pub const OUTPUT_BLOCK_STRIDE: u16 = 0x10;
+
+// Control bits
+pub const MODBUS_DC_OFF: u16 = 0;
+pub const MODBUS_DC_ON: u16 = 1;
+pub const MODBUS_HICCUP_POWER: u16 = 1 << 8;
+
+// Status bits
+pub const STATUS_DC_OK: u16 = 1 << 0;
+pub const STATUS_OVERLOAD: u16 = 1 << 1;
+pub const STATUS_HICCUP: u16 = 1 << 2;
+pub const STATUS_OVER_TEMPERATURE: u16 = 1 << 3;
+
+/// Holding register layout of the power supply
+///
+/// The defaults are the layout of the single output 2787-2144 we ship with. Other firmware
+/// versions or multi-output units can move the blocks, so the layout can be set in the
+/// `[wago_power.registers]` section of the server config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct RegisterMap {
+    pub serial: u16,
+    /// First register of the settings block of output 0
+    pub output_settings: u16,
+    /// First register of the measurement block of output 0
+    pub output_measurement: u16,
+    /// Distance between the blocks of two outputs
+    pub output_stride: u16,
+}
+
+impl Default for RegisterMap {
+    fn default() -> Self {
+        Self {
+            serial: SERIAL_REGISTER,
+            output_settings: OUTPUT_SETTINGS_REGISTER,
+            output_measurement: OUTPUT_MEASUREMENT_REGISTER,
+            output_stride: OUTPUT_BLOCK_STRIDE,
+        }
+    }
+}
+
+impl RegisterMap {
+    pub const fn output_settings_register(&self, output: usize) -> u16 {
+        self.output_settings + output as u16 * self.output_stride
+    }
+
+    pub const fn output_measurement_register(&self, output: usize) -> u16 {
+        self.output_measurement + output as u16 * self.output_stride
+    }
+
+    /// Checks that the blocks of `outputs` outputs don't overlap or leave the register range
+    pub fn validate(&self, outputs: usize) -> Result<()> {
+        if self.output_stride < OUTPUT_SETTINGS_LEN.max(OUTPUT_MEASUREMENT_LEN) {
+            bail!(
+                "output_stride {} is shorter than an output block",
+                self.output_stride
+            );
+        }
+        let span = (outputs as u32) * u32::from(self.output_stride);
+        for (name, start) in [
+            ("output_settings", self.output_settings),
+            ("output_measurement", self.output_measurement),
+        ] {
+            if u32::from(start) + span > u32::from(u16::MAX) + 1 {
+                bail!("{} blocks of {} outputs exceed the register range", name, outputs);
+            }
+        }
+        let settings = u32::from(self.output_settings)..u32::from(self.output_settings) + span;
+        let measurement =
+            u32::from(self.output_measurement)..u32::from(self.output_measurement) + span;
+        if settings.start < measurement.end && measurement.start < settings.end {
+            bail!("output_settings and output_measurement blocks overlap");
+        }
+        Ok(())
+    }
+}
+
+/// Decoded status register of one output
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, Eq, JsonSchema)]
+pub struct OutputStatus {
+    pub dc_ok: bool,
+    /// Output current is above the warning threshold
+    pub overload: bool,
+    /// Output current reached the current limit, the output is pulsed
+    pub hiccup: bool,
+    pub over_temperature: bool,
+}
+
+impl From<u16> for OutputStatus {
+    fn from(bits: u16) -> Self {
+        Self {
+            dc_ok: bits & STATUS_DC_OK != 0,
+            overload: bits & STATUS_OVERLOAD != 0,
+            hiccup: bits & STATUS_HICCUP != 0,
+            over_temperature: bits & STATUS_OVER_TEMPERATURE != 0,
+        }
+    }
+}
+
+impl From<OutputStatus> for u16 {
+    fn from(status: OutputStatus) -> Self {
+        let mut bits = 0;
+        if status.dc_ok {
+            bits |= STATUS_DC_OK;
+        }
+        if status.overload {
+            bits |= STATUS_OVERLOAD;
+        }
+        if status.hiccup {
+            bits |= STATUS_HICCUP;
+        }
+        if status.over_temperature {
+            bits |= STATUS_OVER_TEMPERATURE;
+        }
+        bits
+    }
+}
+
+/// Register access to the power supply, implemented by the real device and the simulation
+pub trait PowerSupplyRegisters: Debug + Send + Sync {
+    fn read(&mut self, addr: u16, count: u16) -> Result<Vec<u16>>;
+    fn write(&mut self, addr: u16, values: &[u16]) -> Result<()>;
+}
+
+impl PowerSupplyRegisters for ModbusTcpDevice {
+    fn read(&mut self, addr: u16, count: u16) -> Result<Vec<u16>> {
+        smol::block_on(self.get_holding_registers(addr, count))
+    }
+
+    fn write(&mut self, addr: u16, values: &[u16]) -> Result<()> {
+        smol::block_on(self.set_holding_registers(addr, values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_roundtrip() {
+        let status = OutputStatus {
+            dc_ok: false,
+            overload: true,
+            hiccup: true,
+            over_temperature: false,
+        };
+        assert_eq!(u16::from(status), STATUS_OVERLOAD | STATUS_HICCUP);
+        assert_eq!(OutputStatus::from(u16::from(status)), status);
+    }
+
+    #[test]
+    fn test_output_registers() {
+        let map = RegisterMap::default();
+        assert_eq!(map.output_settings_register(0), 0x0088);
+        assert_eq!(map.output_settings_register(2), 0x00A8);
+        assert_eq!(map.output_measurement_register(1), 0x0510);
+
+        assert!(map.validate(4).is_ok());
+        // the settings of output 72 would run into the measurement block
+        assert!(map.validate(80).is_err());
+        assert!(
+            RegisterMap {
+                output_stride: 2,
+                ..map
+            }
+            .validate(1)
+            .is_err()
+        );
+    }
+}
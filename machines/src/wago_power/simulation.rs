@@ -0,0 +1,144 @@
+use super::registers::{MODBUS_DC_ON, OutputStatus, PowerSupplyRegisters, RegisterMap};
+use anyhow::Result;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Debug, Default)]
+struct SimulationState {
+    map: RegisterMap,
+    registers: HashMap<u16, u16>,
+    /// Current drawn by the load on every output in mA
+    load: Vec<u16>,
+    over_temperature: Vec<bool>,
+}
+
+/// Simulated power supply register set, used by the mock machine and in tests
+/// Clones share the same registers, so a test can keep a handle to change the load
+#[derive(Debug, Clone)]
+pub struct SimulatedWagoPower {
+    state: Arc<Mutex<SimulationState>>,
+}
+
+impl SimulatedWagoPower {
+    pub fn new(outputs: usize) -> Self {
+        Self::with_register_map(outputs, RegisterMap::default())
+    }
+
+    pub fn with_register_map(outputs: usize, map: RegisterMap) -> Self {
+        let mut registers = HashMap::new();
+        registers.insert(map.serial, 0xbeef);
+
+        Self {
+            state: Arc::new(Mutex::new(SimulationState {
+                map,
+                registers,
+                load: vec![2000; outputs],
+                over_temperature: vec![false; outputs],
+            })),
+        }
+    }
+
+    /// Sets the current in mA the load on `output` would draw
+    pub fn set_load(&self, output: usize, current: u16) {
+        let mut state = self.state.lock().expect("Simulation lock poisoned");
+        state.load[output] = current;
+    }
+
+    pub fn set_over_temperature(&self, output: usize, over_temperature: bool) {
+        let mut state = self.state.lock().expect("Simulation lock poisoned");
+        state.over_temperature[output] = over_temperature;
+    }
+}
+
+impl SimulationState {
+    fn register(&self, addr: u16) -> u16 {
+        self.registers.get(&addr).copied().unwrap_or(0)
+    }
+
+    /// Derives the measurement registers of every output from its settings and load
+    fn update_measurements(&mut self) {
+        for output in 0..self.load.len() {
+            let settings = self.map.output_settings_register(output);
+            let voltage = self.register(settings);
+            let warning_threshold = self.register(settings + 1);
+            let enabled = self.register(settings + 2) & MODBUS_DC_ON != 0;
+            let current_limit = self.register(settings + 4);
+            let load = self.load[output];
+
+            let mut status = OutputStatus {
+                over_temperature: self.over_temperature[output],
+                ..Default::default()
+            };
+            let (voltage, current) = if !enabled || status.over_temperature {
+                (0, 0)
+            } else if load >= current_limit {
+                status.hiccup = true;
+                (0, 0)
+            } else {
+                status.dc_ok = true;
+                status.overload = load > warning_threshold;
+                (voltage, load)
+            };
+
+            let measurement = self.map.output_measurement_register(output);
+            self.registers.insert(measurement, voltage);
+            self.registers.insert(measurement + 1, current);
+            self.registers.insert(measurement + 2, status.into());
+        }
+    }
+}
+
+impl PowerSupplyRegisters for SimulatedWagoPower {
+    fn read(&mut self, addr: u16, count: u16) -> Result<Vec<u16>> {
+        let mut state = self.state.lock().expect("Simulation lock poisoned");
+        state.update_measurements();
+        Ok((addr..addr + count).map(|a| state.register(a)).collect())
+    }
+
+    fn write(&mut self, addr: u16, values: &[u16]) -> Result<()> {
+        let mut state = self.state.lock().expect("Simulation lock poisoned");
+        for (a, value) in (addr..).zip(values) {
+            state.registers.insert(a, *value);
+        }
+        state.update_measurements();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simulated_status() {
+        let mut sim = SimulatedWagoPower::new(2);
+        let map = RegisterMap::default();
+        let settings = map.output_settings_register(1);
+        let measurement = map.output_measurement_register(1);
+
+        sim.write(settings, &[24000, 3000, MODBUS_DC_ON, 0, 10000])
+            .unwrap();
+        assert_eq!(sim.read(measurement, 3).unwrap(), [24000, 2000, 1]);
+
+        sim.set_load(1, 4000);
+        let status = OutputStatus::from(sim.read(measurement + 2, 1).unwrap()[0]);
+        assert!(status.dc_ok && status.overload && !status.hiccup);
+
+        sim.set_load(1, 12000);
+        let status = OutputStatus::from(sim.read(measurement + 2, 1).unwrap()[0]);
+        assert!(!status.dc_ok && status.hiccup);
+
+        sim.set_over_temperature(1, true);
+        let values = sim.read(measurement, 3).unwrap();
+        assert_eq!(values[0], 0);
+        assert!(OutputStatus::from(values[2]).over_temperature);
+
+        // output 0 was never switched on
+        assert_eq!(
+            sim.read(map.output_measurement_register(0), 3).unwrap(),
+            [0, 0, 0]
+        );
+    }
+}
@@ -1,7 +1,11 @@
 #[cfg(not(feature = "mock-machine"))]
 use super::Winder2;
 #[cfg(not(feature = "mock-machine"))]
-use crate::{MachineAct, MachineMessage};
+use super::Winder2Mode;
+#[cfg(not(feature = "mock-machine"))]
+use crate::production_record::SpoolCompletion;
+#[cfg(not(feature = "mock-machine"))]
+use crate::{MachineAct, MachineMessage, ShutdownPolicy};
 #[cfg(not(feature = "mock-machine"))]
 use std::time::{Duration, Instant};
 
@@ -22,7 +26,10 @@ impl MachineAct for Winder2 {
         // sync the traverse speed
         self.sync_traverse_speed();
 
-        // automatically stops or pulls after N Meters if enabled
+        // estimate the wound diameter of the spool
+        self.sync_spool_model(now);
+
+        // automatically stops or pulls after N Meters or a full spool if enabled
         self.stop_or_pull_spool(now);
 
         if self.traverse_controller.did_change_state() {
@@ -36,6 +43,27 @@ impl MachineAct for Winder2 {
         }
     }
 
+    fn shutdown(&mut self, now: Instant, _policy: &ShutdownPolicy) -> bool {
+        // only records if a spool is being wound
+        self.finish_spool_record(SpoolCompletion::Shutdown);
+
+        // the speed controllers decelerate in hold, standby disables the motors right away
+        if matches!(self.mode, Winder2Mode::Wind | Winder2Mode::Pull) {
+            self.set_mode(&Winder2Mode::Hold);
+        }
+        self.sync_spool_speed(now);
+        self.sync_puller_speed(now);
+        self.sync_traverse_speed();
+
+        if self.mode == Winder2Mode::Hold
+            && self.spool.get_speed() == 0
+            && self.puller.get_speed() == 0
+        {
+            self.set_mode(&Winder2Mode::Standby);
+        }
+        self.mode == Winder2Mode::Standby
+    }
+
     fn act_machine_message(&mut self, msg: MachineMessage) {
         match msg {
             MachineMessage::SubscribeNamespace(namespace) => {
@@ -43,9 +71,9 @@ impl MachineAct for Winder2 {
                 self.emit_state();
             }
             MachineMessage::UnsubscribeNamespace => self.namespace.namespace = None,
-            MachineMessage::HttpApiJsonRequest(value) => {
+            MachineMessage::HttpApiJsonRequest(value, reply) => {
                 use crate::MachineApi;
-                let _res = self.api_mutate(value);
+                reply.send(self.api_mutate(value));
             }
             MachineMessage::ConnectToMachine(machine_connection) => {
                 if self.connected_machines.len() >= self.max_connected_machines {
@@ -48,11 +48,16 @@ mod winder2_imports {
 use smol::channel::Sender;
 pub use winder2_imports::*;
 
+use super::winding_pattern::{WindingPattern, WindingSettings};
 #[cfg(not(feature = "mock-machine"))]
-use crate::{MachineApi, MachineMessage};
+use crate::{
+    MachineApi, MachineMessage,
+    api_schema::{MachineApiSchema, MachineSchema},
+};
 use crate::{MachineCrossConnectionState, machine_identification::MachineIdentificationUnique};
+use schemars::JsonSchema;
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 pub enum Mode {
     #[default]
     Standby,
@@ -83,7 +88,7 @@ impl From<Mode> for Winder2Mode {
     }
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 pub enum Mutation {
     // Traverse
     /// Position in mm from home point
@@ -94,6 +99,21 @@ pub enum Mutation {
     SetTraverseStepSize(f64),
     /// Padding in mm for traverse movement limits
     SetTraversePadding(f64),
+    SetTraverseWindingPattern(WindingPattern),
+    /// Diameter of the empty spool core in mm
+    SetTraverseCoreDiameter(f64),
+    /// Layers a step precision wind keeps the pitch
+    SetTraverseStepLayers(u32),
+    /// Fraction the pitch is modulated by to break ribbons, 0 disables it
+    SetTraverseRibbonBreakingAmplitude(f64),
+    /// Strokes of one ribbon breaking period
+    SetTraverseRibbonBreakingPeriod(u32),
+    /// Spool revolutions the traverse dwells at each end
+    SetTraverseEndDwell(f64),
+    /// Distance in mm the turnaround points are pulled in by
+    SetTraverseEdgeCompensation(f64),
+    /// Distance in mm both limits move inwards per layer
+    SetTraverseTaper(f64),
     GotoTraverseLimitOuter,
     GotoTraverseLimitInner,
     /// Find home point
@@ -103,18 +123,23 @@ pub enum Mutation {
     // Puller
     /// on = speed, off = stop
     SetPullerRegulationMode(PullerRegulationMode),
+    /// Speed in m/min
     SetPullerTargetSpeed(f64),
+    /// Diameter in mm
     SetPullerTargetDiameter(f64),
     SetPullerForward(bool),
     SetPullerGearRatio(GearRatio),
 
     // Spool Speed Controller
     SetSpoolRegulationMode(super::spool_speed_controller::SpoolSpeedControllerType),
+    /// Speed in rpm
     SetSpoolMinMaxMinSpeed(f64),
+    /// Speed in rpm
     SetSpoolMinMaxMaxSpeed(f64),
     SetSpoolForward(bool),
 
     // Adaptive Spool Speed Controller Parameters
+    /// Tension target from 0 to 1
     SetSpoolAdaptiveTensionTarget(f64),
     SetSpoolAdaptiveRadiusLearningRate(f64),
     SetSpoolAdaptiveMaxSpeedMultiplier(f64),
@@ -122,9 +147,17 @@ pub enum Mutation {
     SetSpoolAdaptiveDeaccelerationUrgencyMultiplier(f64),
 
     // Spool Auto Stop/Pull
+    /// Length in m after which the automatic action is taken
     SetSpoolAutomaticRequiredMeters(f64),
     SetSpoolAutomaticAction(SpoolAutomaticActionMode),
+    /// Largest wound diameter in mm the spool flanges allow
+    SetSpoolMaxDiameter(f64),
+    /// Also finish the spool with the automatic action once the max diameter is reached
+    SetSpoolAutomaticStopWhenFull(bool),
+    /// Finishes the current spool and records it
     ResetSpoolProgress,
+    /// Lot number of the following spools, none or empty numbers them by their record id
+    SetLotNumber(Option<String>),
 
     // Tension Arm
     ZeroTensionArmAngle,
@@ -139,10 +172,12 @@ pub enum Mutation {
     DisconnectMachine(MachineIdentificationUnique),
 }
 
-#[derive(Serialize, Debug, Clone, Default)]
+#[derive(Serialize, Debug, Clone, Default, JsonSchema)]
 pub struct LiveValuesEvent {
     /// traverse position in mm
     pub traverse_position: Option<f64>,
+    /// layer the traverse is winding, `None` if not traversing
+    pub traverse_layer: Option<u32>,
     /// puller speed in m/min
     pub puller_speed: f64,
     /// spool rpm
@@ -151,6 +186,16 @@ pub struct LiveValuesEvent {
     pub tension_arm_angle: f64,
     // spool progress in meters (pulled distance of filament)
     pub spool_progress: f64,
+    /// estimated wound diameter of the spool in mm
+    pub spool_diameter: f64,
+    /// wound share of the spool up to the max diameter in percent
+    pub spool_fill: f64,
+    /// filament in meters until the spool is done, the target length or the max diameter
+    pub spool_remaining_length: Option<f64>,
+    /// seconds until the spool is done at the current speed
+    pub spool_time_to_full: Option<f64>,
+    /// seconds until the max diameter is reached at the current speed
+    pub spool_time_to_limit: Option<f64>,
 }
 
 impl LiveValuesEvent {
@@ -159,7 +204,7 @@ impl LiveValuesEvent {
     }
 }
 
-#[derive(Serialize, Debug, Clone, BuildEvent)]
+#[derive(Serialize, Debug, Clone, BuildEvent, JsonSchema)]
 pub struct StateEvent {
     pub is_default_state: bool,
     /// traverse state
@@ -176,9 +221,11 @@ pub struct StateEvent {
     pub spool_speed_controller_state: SpoolSpeedControllerState,
     /// Is a Machine Connected?
     pub connected_machine_state: MachineCrossConnectionState,
+    /// production record of the current spool
+    pub production_state: ProductionState,
 }
 
-#[derive(Serialize, Debug, Clone, Default)]
+#[derive(Serialize, Debug, Clone, Default, JsonSchema)]
 pub struct TraverseState {
     /// min position in mm
     pub limit_inner: f64,
@@ -204,6 +251,8 @@ pub struct TraverseState {
     pub step_size: f64,
     /// padding in mm
     pub padding: f64,
+    /// winding pattern, end dwell, edge compensation and tapering
+    pub winding: WindingSettings,
     /// can go in (to inner limit)
     pub can_go_in: bool,
     /// can go out (to outer limit)
@@ -212,7 +261,7 @@ pub struct TraverseState {
     pub can_go_home: bool,
 }
 
-#[derive(Serialize, Debug, Clone, Default)]
+#[derive(Serialize, Debug, Clone, Default, JsonSchema)]
 pub struct PullerState {
     /// regulation type
     pub regulation: PullerRegulationMode,
@@ -226,7 +275,7 @@ pub struct PullerState {
     pub gear_ratio: GearRatio,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[derive(Deserialize, Serialize, Debug, Clone, Default, JsonSchema)]
 pub enum SpoolAutomaticActionMode {
     #[default]
     NoAction,
@@ -234,13 +283,25 @@ pub enum SpoolAutomaticActionMode {
     Hold,
 }
 
-#[derive(Serialize, Debug, Clone, Default)]
+#[derive(Serialize, Debug, Clone, Default, JsonSchema)]
 pub struct SpoolAutomaticActionState {
     pub spool_required_meters: f64,
     pub spool_automatic_action_mode: SpoolAutomaticActionMode,
+    /// largest wound diameter in mm
+    pub spool_max_diameter: f64,
+    /// also finish the spool once the max diameter is reached
+    pub stop_when_full: bool,
 }
 
-#[derive(Serialize, Debug, Clone, Default)]
+#[derive(Serialize, Debug, Clone, Default, JsonSchema)]
+pub struct ProductionState {
+    /// lot number of the next records, none numbers them by their record id
+    pub lot_number: Option<String>,
+    /// unix timestamp in ms when winding of the current spool started
+    pub spool_started_at: Option<u64>,
+}
+
+#[derive(Serialize, Debug, Clone, Default, JsonSchema)]
 pub struct ModeState {
     /// mode
     pub mode: Mode,
@@ -248,13 +309,13 @@ pub struct ModeState {
     pub can_wind: bool,
 }
 
-#[derive(Serialize, Debug, Clone, Default)]
+#[derive(Serialize, Debug, Clone, Default, JsonSchema)]
 pub struct TensionArmState {
     /// is zeroed
     pub zeroed: bool,
 }
 
-#[derive(Serialize, Debug, Clone, Default)]
+#[derive(Serialize, Debug, Clone, Default, JsonSchema)]
 pub struct SpoolSpeedControllerState {
     /// regulation mode
     pub regulation_mode: super::spool_speed_controller::SpoolSpeedControllerType,
@@ -315,6 +376,15 @@ impl CacheableEvents<Self> for Winder2Events {
     }
 }
 
+#[cfg(not(feature = "mock-machine"))]
+impl MachineSchema for Winder2 {
+    fn api_schema() -> MachineApiSchema {
+        MachineApiSchema::new::<Mutation>("Winder2", Self::MACHINE_IDENTIFICATION)
+            .with_event::<LiveValuesEvent>("LiveValuesEvent")
+            .with_event::<StateEvent>("StateEvent")
+    }
+}
+
 #[cfg(not(feature = "mock-machine"))]
 impl MachineApi for Winder2 {
     fn api_get_sender(&self) -> Sender<MachineMessage> {
@@ -332,6 +402,32 @@ impl MachineApi for Winder2 {
             Mutation::SetTraverseLimitInner(limit) => self.traverse_set_limit_inner(limit),
             Mutation::SetTraverseStepSize(size) => self.traverse_set_step_size(size),
             Mutation::SetTraversePadding(padding) => self.traverse_set_padding(padding),
+            Mutation::SetTraverseWindingPattern(pattern) => {
+                self.traverse_update_winding(|winding| winding.pattern = pattern)?
+            }
+            Mutation::SetTraverseCoreDiameter(diameter) => {
+                self.traverse_update_winding(|winding| winding.core_diameter = diameter)?
+            }
+            Mutation::SetTraverseStepLayers(layers) => {
+                self.traverse_update_winding(|winding| winding.step_layers = layers)?
+            }
+            Mutation::SetTraverseRibbonBreakingAmplitude(amplitude) => {
+                self.traverse_update_winding(|winding| {
+                    winding.ribbon_breaking_amplitude = amplitude;
+                })?;
+            }
+            Mutation::SetTraverseRibbonBreakingPeriod(period) => {
+                self.traverse_update_winding(|winding| winding.ribbon_breaking_period = period)?
+            }
+            Mutation::SetTraverseEndDwell(revolutions) => {
+                self.traverse_update_winding(|winding| winding.end_dwell = revolutions)?
+            }
+            Mutation::SetTraverseEdgeCompensation(distance) => {
+                self.traverse_update_winding(|winding| winding.edge_compensation = distance)?
+            }
+            Mutation::SetTraverseTaper(distance) => {
+                self.traverse_update_winding(|winding| winding.taper = distance)?
+            }
             Mutation::GotoTraverseLimitOuter => self.traverse_goto_limit_outer(),
             Mutation::GotoTraverseLimitInner => self.traverse_goto_limit_inner(),
             Mutation::GotoTraverseHome => self.traverse_goto_home(),
@@ -363,7 +459,14 @@ impl MachineApi for Winder2 {
                 self.set_spool_automatic_required_meters(meters)
             }
             Mutation::SetSpoolAutomaticAction(mode) => self.set_spool_automatic_mode(mode),
-            Mutation::ResetSpoolProgress => self.stop_or_pull_spool_reset(Instant::now()),
+            Mutation::SetSpoolMaxDiameter(max_diameter) => {
+                self.set_spool_max_diameter(max_diameter)?
+            }
+            Mutation::SetSpoolAutomaticStopWhenFull(stop_when_full) => {
+                self.set_spool_automatic_stop_when_full(stop_when_full)
+            }
+            Mutation::ResetSpoolProgress => self.reset_spool_progress(Instant::now()),
+            Mutation::SetLotNumber(lot_number) => self.set_lot_number(lot_number),
             Mutation::ZeroTensionArmAngle => self.tension_arm_zero(),
             Mutation::SetConnectedMachine(machine_identification_unique) => {
                 let main_sender = match &self.main_sender {
@@ -1,16 +1,20 @@
 #[cfg(not(feature = "mock-machine"))]
 mod winder2_imports {
     pub use super::super::puller_speed_controller::PullerRegulationMode;
+    pub use super::super::winding_pattern::WindingSettings;
     pub use super::super::{TraverseMode, Winder2, Winder2Mode, api, spool_speed_controller};
     pub use crate::buffer1::BufferV1;
+    pub use crate::laser::LaserMachine;
+    pub use crate::production_record::{SpoolCompletion, unix_timestamp_ms};
+    pub use crate::{AsyncThreadMessage, Machine, MachineMessage, MutationReply};
     pub use api::{
-        LiveValuesEvent, ModeState, PullerState, SpoolAutomaticActionMode,
+        LiveValuesEvent, ModeState, ProductionState, PullerState, SpoolAutomaticActionMode,
         SpoolAutomaticActionState, SpoolSpeedControllerState, StateEvent, TensionArmState,
         TraverseState, Winder2Events,
     };
     pub use control_core::socketio::event::BuildEvent;
     pub use control_core::socketio::namespace::NamespaceCacheingLogic;
-    pub use std::time::Instant;
+    pub use std::time::{Instant, SystemTime};
     pub use units::{
         angle::degree,
         angular_velocity::revolution_per_minute,
@@ -65,14 +69,18 @@ impl Winder2 {
             }
         }
 
-        if self.spool_automatic_action.progress >= self.spool_automatic_action.target_length {
+        if self.spool_automatic_action.progress >= self.spool_automatic_action.target_length
+            || self.is_spool_full()
+        {
             match self.spool_automatic_action.mode {
                 SpoolAutomaticActionMode::NoAction => (),
                 SpoolAutomaticActionMode::Pull => {
+                    self.finish_spool_record(SpoolCompletion::Automatic);
                     self.stop_or_pull_spool_reset(now);
                     self.set_mode(&Winder2Mode::Pull);
                 }
                 SpoolAutomaticActionMode::Hold => {
+                    self.finish_spool_record(SpoolCompletion::Automatic);
                     self.stop_or_pull_spool_reset(now);
                     self.set_mode(&Winder2Mode::Hold);
                 }
@@ -92,6 +100,80 @@ impl Winder2 {
             self.set_puller_mode(mode);
             self.set_traverse_mode(mode);
         }
+        if self.mode == Winder2Mode::Wind {
+            self.start_spool_record();
+        }
+        self.emit_state();
+    }
+
+    /// Starts recording a new spool, unless one is already being wound
+    fn start_spool_record(&mut self) {
+        if !self.spool_recorder.start(SystemTime::now()) {
+            return;
+        }
+
+        // the statistics of the laser should only cover this spool
+        for machine in &self.connected_machines {
+            if machine.ident.machine_identification == LaserMachine::MACHINE_IDENTIFICATION {
+                let _ = machine
+                    .connection
+                    .try_send(MachineMessage::HttpApiJsonRequest(
+                        serde_json::json!("ResetSpoolStatistics"),
+                        MutationReply::none(),
+                    ));
+            }
+        }
+    }
+
+    /// Sends the record of the current spool to the main thread to be stored
+    pub(super) fn finish_spool_record(&mut self, completion: SpoolCompletion) {
+        let mut machines = vec![self.machine_identification_unique.clone()];
+        machines.extend(
+            self.connected_machines
+                .iter()
+                .map(|machine| machine.ident.clone()),
+        );
+
+        let Some(record) = self.spool_recorder.finish(
+            SystemTime::now(),
+            &self.machine_identification_unique,
+            &machines,
+            self.spool_automatic_action.progress,
+            completion,
+        ) else {
+            return;
+        };
+
+        match self.get_main_sender() {
+            Some(sender) => {
+                if let Err(e) =
+                    sender.try_send(AsyncThreadMessage::SpoolCompleted(Box::new(record)))
+                {
+                    tracing::error!("Failed to send production record: {:?}", e);
+                }
+            }
+            None => tracing::warn!("No main thread to store the production record"),
+        }
+    }
+
+    /// Finishes the current spool and starts counting the next one
+    pub fn reset_spool_progress(&mut self, now: Instant) {
+        if self.spool_automatic_action.progress.get::<meter>() > 0.0 {
+            self.finish_spool_record(SpoolCompletion::Manual);
+        } else {
+            // nothing was wound, the spool is recorded from now
+            self.spool_recorder.started_at = None;
+        }
+        self.stop_or_pull_spool_reset(now);
+        if self.mode == Winder2Mode::Wind {
+            self.start_spool_record();
+        }
+        self.emit_state();
+    }
+
+    /// Lot number of the following spools, `None` numbers them by their record id
+    pub fn set_lot_number(&mut self, lot_number: Option<String>) {
+        self.spool_recorder.lot_number = lot_number.filter(|lot_number| !lot_number.is_empty());
         self.emit_state();
     }
 
@@ -140,6 +222,19 @@ impl Winder2 {
         self.emit_state();
     }
 
+    /// Changes the winding settings, invalid settings are ignored
+    pub fn traverse_update_winding(
+        &mut self,
+        update: impl FnOnce(&mut WindingSettings),
+    ) -> Result<(), anyhow::Error> {
+        let mut winding = self.traverse_controller.get_winding().clone();
+        update(&mut winding);
+        winding.validate()?;
+        self.traverse_controller.set_winding(winding);
+        self.emit_state();
+        Ok(())
+    }
+
     pub fn traverse_goto_limit_inner(&mut self) {
         if self.can_go_in() {
             self.traverse_controller.goto_limit_inner();
@@ -192,15 +287,26 @@ impl Winder2 {
             .get::<revolution_per_minute>()
             .abs();
 
+        let spool = self.estimate_spool();
+
         let live_values = LiveValuesEvent {
             traverse_position: self
                 .traverse_controller
                 .get_current_position()
                 .map(|x| x.get::<millimeter>()),
+            traverse_layer: self
+                .traverse_controller
+                .is_traversing()
+                .then(|| self.traverse_controller.get_layer()),
             puller_speed: puller_speed.get::<meter_per_minute>().abs(),
             spool_rpm,
             tension_arm_angle: angle_deg,
             spool_progress: self.spool_automatic_action.progress.get::<meter>(),
+            spool_diameter: spool.diameter.get::<millimeter>(),
+            spool_fill: spool.fill * 100.0,
+            spool_remaining_length: spool.remaining.map(|length| length.get::<meter>()),
+            spool_time_to_full: spool.time_to_full.map(|time| time.as_secs_f64()),
+            spool_time_to_limit: spool.time_to_limit.map(|time| time.as_secs_f64()),
         };
 
         let event = live_values.build();
@@ -247,6 +353,7 @@ impl Winder2 {
                 laserpointer: self.laser.get(),
                 step_size: self.traverse_controller.get_step_size().get::<millimeter>(),
                 padding: self.traverse_controller.get_padding().get::<millimeter>(),
+                winding: self.traverse_controller.get_winding().clone(),
                 can_go_in: self.can_go_in(),
                 can_go_out: self.can_go_out(),
                 can_go_home: self.can_go_home(),
@@ -299,8 +406,14 @@ impl Winder2 {
             spool_automatic_action_state: SpoolAutomaticActionState {
                 spool_required_meters: self.spool_automatic_action.target_length.get::<meter>(),
                 spool_automatic_action_mode: self.spool_automatic_action.mode.clone(),
+                spool_max_diameter: self.spool_model.get_max_diameter().get::<millimeter>(),
+                stop_when_full: self.spool_model.get_stop_when_full(),
             },
             connected_machine_state: cross_conn,
+            production_state: ProductionState {
+                lot_number: self.spool_recorder.lot_number.clone(),
+                spool_started_at: self.spool_recorder.started_at.map(unix_timestamp_ms),
+            },
         }
     }
 
@@ -397,6 +510,27 @@ impl Winder2 {
         self.emit_state();
     }
 
+    /// The max diameter in mm has to lie above the core, else the spool is full right away
+    pub fn set_spool_max_diameter(&mut self, max_diameter: f64) -> Result<(), anyhow::Error> {
+        let core_diameter = self.spool_core_diameter().get::<millimeter>();
+        if !max_diameter.is_finite() || max_diameter <= core_diameter {
+            anyhow::bail!(
+                "Spool max diameter {} mm must be above the core diameter {} mm",
+                max_diameter,
+                core_diameter
+            );
+        }
+        self.spool_model
+            .set_max_diameter(Length::new::<millimeter>(max_diameter));
+        self.emit_state();
+        Ok(())
+    }
+
+    pub fn set_spool_automatic_stop_when_full(&mut self, stop_when_full: bool) {
+        self.spool_model.set_stop_when_full(stop_when_full);
+        self.emit_state();
+    }
+
     pub fn puller_set_regulation(&mut self, puller_regulation_mode: PullerRegulationMode) {
         self.puller_speed_controller
             .set_regulation_mode(puller_regulation_mode);
@@ -1,6 +1,8 @@
 use super::Winder2;
 use crate::MachineAct;
 use crate::MachineMessage;
+use crate::ShutdownPolicy;
+use crate::winder2::{Winder2Mode, api::Mode};
 use std::time::{Duration, Instant};
 
 impl MachineAct for Winder2 {
@@ -19,6 +21,13 @@ impl MachineAct for Winder2 {
         }
     }
 
+    fn shutdown(&mut self, _now: Instant, _policy: &ShutdownPolicy) -> bool {
+        if !matches!(self.mode_state.mode, Mode::Standby) {
+            self.set_mode(&Winder2Mode::Standby);
+        }
+        true
+    }
+
     fn act_machine_message(&mut self, msg: crate::MachineMessage) {
         match msg {
             MachineMessage::SubscribeNamespace(namespace) => {
@@ -27,10 +36,10 @@ impl MachineAct for Winder2 {
                 tracing::info!("extruder1 received subscribe");
             }
             MachineMessage::UnsubscribeNamespace => self.namespace.namespace = None,
-            MachineMessage::HttpApiJsonRequest(value) => {
+            MachineMessage::HttpApiJsonRequest(value, reply) => {
                 use crate::MachineApi;
 
-                let _res = self.api_mutate(value);
+                reply.send(self.api_mutate(value));
             }
             MachineMessage::ConnectToMachine(_machine_connection) => (),
             MachineMessage::DisconnectMachine(_machine_connection) =>
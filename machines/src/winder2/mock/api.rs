@@ -1,8 +1,20 @@
 use super::Winder2;
-use crate::{MachineApi, winder2::api::Mutation};
+use crate::api_schema::{MachineApiSchema, MachineSchema};
+use crate::{
+    MachineApi,
+    winder2::api::{LiveValuesEvent, Mutation, StateEvent},
+};
 use serde_json::Value;
 use std::time::Instant;
 
+impl MachineSchema for Winder2 {
+    fn api_schema() -> MachineApiSchema {
+        MachineApiSchema::new::<Mutation>("Winder2", Self::MACHINE_IDENTIFICATION)
+            .with_event::<LiveValuesEvent>("LiveValuesEvent")
+            .with_event::<StateEvent>("StateEvent")
+    }
+}
+
 impl MachineApi for Winder2 {
     fn api_mutate(&mut self, request_body: Value) -> Result<(), anyhow::Error> {
         let mutation: Mutation = serde_json::from_value(request_body)?;
@@ -13,6 +25,32 @@ impl MachineApi for Winder2 {
             Mutation::SetTraverseLimitInner(limit) => self.traverse_set_limit_inner(limit),
             Mutation::SetTraverseStepSize(size) => self.traverse_set_step_size(size),
             Mutation::SetTraversePadding(padding) => self.traverse_set_padding(padding),
+            Mutation::SetTraverseWindingPattern(pattern) => {
+                self.traverse_update_winding(|winding| winding.pattern = pattern)?
+            }
+            Mutation::SetTraverseCoreDiameter(diameter) => {
+                self.traverse_update_winding(|winding| winding.core_diameter = diameter)?
+            }
+            Mutation::SetTraverseStepLayers(layers) => {
+                self.traverse_update_winding(|winding| winding.step_layers = layers)?
+            }
+            Mutation::SetTraverseRibbonBreakingAmplitude(amplitude) => {
+                self.traverse_update_winding(|winding| {
+                    winding.ribbon_breaking_amplitude = amplitude;
+                })?;
+            }
+            Mutation::SetTraverseRibbonBreakingPeriod(period) => {
+                self.traverse_update_winding(|winding| winding.ribbon_breaking_period = period)?
+            }
+            Mutation::SetTraverseEndDwell(revolutions) => {
+                self.traverse_update_winding(|winding| winding.end_dwell = revolutions)?
+            }
+            Mutation::SetTraverseEdgeCompensation(distance) => {
+                self.traverse_update_winding(|winding| winding.edge_compensation = distance)?
+            }
+            Mutation::SetTraverseTaper(distance) => {
+                self.traverse_update_winding(|winding| winding.taper = distance)?
+            }
             Mutation::GotoTraverseLimitOuter => self.traverse_goto_limit_outer(),
             Mutation::GotoTraverseLimitInner => self.traverse_goto_limit_inner(),
             Mutation::GotoTraverseHome => self.traverse_goto_home(),
@@ -44,7 +82,20 @@ impl MachineApi for Winder2 {
                 self.set_spool_automatic_required_meters(meters)
             }
             Mutation::SetSpoolAutomaticAction(mode) => self.set_spool_automatic_mode(mode),
+            Mutation::SetSpoolMaxDiameter(max_diameter) => {
+                self.spool_automatic_action_state.spool_max_diameter = max_diameter;
+                self.emit_state();
+            }
+            Mutation::SetSpoolAutomaticStopWhenFull(stop_when_full) => {
+                self.spool_automatic_action_state.stop_when_full = stop_when_full;
+                self.emit_state();
+            }
             Mutation::ResetSpoolProgress => self.stop_or_pull_spool_reset(Instant::now()),
+            Mutation::SetLotNumber(lot_number) => {
+                self.production_state.lot_number =
+                    lot_number.filter(|lot_number| !lot_number.is_empty());
+                self.emit_state();
+            }
             Mutation::ZeroTensionArmAngle => self.tension_arm_zero(),
             Mutation::SetConnectedMachine(machine_identification_unique) => {
                 self.set_connected_buffer(machine_identification_unique)
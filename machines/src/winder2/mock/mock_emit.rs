@@ -7,6 +7,7 @@ use crate::winder2::api::LiveValuesEvent;
 use crate::winder2::api::{ModeState, SpoolAutomaticActionMode, StateEvent, Winder2Events};
 use crate::winder2::puller_speed_controller::{GearRatio, PullerRegulationMode};
 use crate::winder2::spool_speed_controller::SpoolSpeedControllerType;
+use crate::winder2::winding_pattern::WindingSettings;
 use crate::{MACHINE_WINDER_V1, VENDOR_QITECH};
 use control_core::socketio::event::BuildEvent;
 use control_core::socketio::namespace::NamespaceCacheingLogic;
@@ -53,6 +54,18 @@ impl Winder2 {
         self.emit_state();
     }
 
+    pub fn traverse_update_winding(
+        &mut self,
+        update: impl FnOnce(&mut WindingSettings),
+    ) -> Result<(), anyhow::Error> {
+        let mut winding = self.traverse_state.winding.clone();
+        update(&mut winding);
+        winding.validate()?;
+        self.traverse_state.winding = winding;
+        self.emit_state();
+        Ok(())
+    }
+
     pub fn traverse_goto_limit_inner(&mut self) {
         self.traverse_state.position_in = self.traverse_state.limit_inner;
         self.emit_state();
@@ -71,10 +84,16 @@ impl Winder2 {
     pub fn emit_live_values(&mut self) {
         let event = LiveValuesEvent {
             traverse_position: Some(0.0),
+            traverse_layer: None,
             puller_speed: 0.0,
             spool_rpm: 0.0,
             tension_arm_angle: 0.0,
             spool_progress: 0.0,
+            spool_diameter: 0.0,
+            spool_fill: 0.0,
+            spool_remaining_length: None,
+            spool_time_to_full: None,
+            spool_time_to_limit: None,
         };
 
         let event = event.build();
@@ -106,6 +125,7 @@ impl Winder2 {
             tension_arm_state: self.tension_arm_state.clone(),
             spool_speed_controller_state: self.spool_speed_controller_state.clone(),
             connected_machine_state: cross_conn,
+            production_state: self.production_state.clone(),
         }
     }
 
@@ -6,8 +6,8 @@ pub mod mock_emit;
 pub mod new;
 
 use super::api::{
-    ModeState, PullerState, SpoolAutomaticActionState, SpoolSpeedControllerState, TensionArmState,
-    TraverseState, Winder2Namespace,
+    ModeState, ProductionState, PullerState, SpoolAutomaticActionState, SpoolSpeedControllerState,
+    TensionArmState, TraverseState, Winder2Namespace,
 };
 use crate::{
     AsyncThreadMessage, Machine, MachineConnection, MachineMessage,
@@ -27,6 +27,7 @@ pub struct Winder2 {
     pub puller_state: PullerState,
     /// spool automatic action state and progress
     pub spool_automatic_action_state: SpoolAutomaticActionState,
+    pub production_state: ProductionState,
     /// mode state
     pub mode_state: ModeState,
     /// tension arm state
@@ -1,8 +1,8 @@
 use crate::{
     MachineNewParams, MachineNewTrait,
     winder2::api::{
-        ModeState, PullerState, SpoolAutomaticActionState, SpoolSpeedControllerState,
-        TensionArmState, TraverseState, Winder2Namespace,
+        ModeState, ProductionState, PullerState, SpoolAutomaticActionState,
+        SpoolSpeedControllerState, TensionArmState, TraverseState, Winder2Namespace,
     },
 };
 
@@ -44,6 +44,7 @@ impl MachineNewTrait for Winder2 {
             traverse_state: TraverseState::default(),
             puller_state: PullerState::default(),
             spool_automatic_action_state: SpoolAutomaticActionState::default(),
+            production_state: ProductionState::default(),
             mode_state: ModeState::default(),
             tension_arm_state: TensionArmState::default(),
             spool_speed_controller_state: SpoolSpeedControllerState::default(),
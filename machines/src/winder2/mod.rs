@@ -7,9 +7,12 @@ pub mod filament_tension;
 pub mod minmax_spool_speed_controller;
 pub mod new;
 pub mod puller_speed_controller;
+pub mod spool_model;
+pub mod spool_record;
 pub mod spool_speed_controller;
 pub mod tension_arm;
 pub mod traverse_controller;
+pub mod winding_pattern;
 
 #[cfg(feature = "mock-machine")]
 pub mod mock;
@@ -27,6 +30,8 @@ mod winder2_imports {
     pub use super::api::SpoolAutomaticActionMode;
     pub use super::api::Winder2Namespace;
     pub use super::puller_speed_controller::PullerSpeedController;
+    pub use super::spool_model::{SpoolEstimate, SpoolModel};
+    pub use super::spool_record::SpoolRecorder;
     pub use super::spool_speed_controller::SpoolSpeedController;
     pub use super::tension_arm::TensionArm;
     pub use super::traverse_controller::TraverseController;
@@ -122,6 +127,12 @@ pub struct Winder2 {
     // spool automatic action state
     pub spool_automatic_action: SpoolAutomaticAction,
 
+    // wound diameter and fill level of the spool
+    pub spool_model: SpoolModel,
+
+    // production record of the current spool
+    pub spool_recorder: SpoolRecorder,
+
     // control circuit puller
     pub puller_speed_controller: PullerSpeedController,
 
@@ -148,6 +159,7 @@ impl Winder2 {
         self.traverse_controller.update_speed(
             &mut self.traverse,
             &self.traverse_end_stop,
+            &self.spool,
             self.spool_speed_controller.get_speed(),
         )
     }
@@ -302,6 +314,53 @@ impl Winder2 {
     pub const fn stop_or_pull_spool_reset(&mut self, now: Instant) {
         self.spool_automatic_action.progress = Length::ZERO;
         self.spool_automatic_action.progress_last_check = now;
+        self.spool_model.reset();
+    }
+
+    /// Feeds the spool model while winding
+    /// called by `act`
+    pub fn sync_spool_model(&mut self, now: Instant) {
+        if self.mode != Winder2Mode::Wind {
+            self.spool_model.pause();
+            return;
+        }
+        self.spool_model.update(
+            now,
+            self.puller_speed_controller.last_speed,
+            self.spool_speed_controller.get_speed(),
+            self.traverse_controller
+                .is_traversing()
+                .then(|| self.traverse_controller.get_layer()),
+        );
+    }
+
+    fn spool_core_diameter(&self) -> Length {
+        Length::new::<millimeter>(self.traverse_controller.get_winding().core_diameter)
+    }
+
+    /// Whether the spool reached its max diameter and should be finished
+    pub fn is_spool_full(&self) -> bool {
+        self.spool_model.get_stop_when_full()
+            && self.spool_model.is_full(
+                self.spool_core_diameter(),
+                self.traverse_controller.get_step_size(),
+            )
+    }
+
+    pub fn estimate_spool(&self) -> SpoolEstimate {
+        let action = &self.spool_automatic_action;
+        let target_remaining = match action.mode {
+            SpoolAutomaticActionMode::NoAction => None,
+            SpoolAutomaticActionMode::Pull | SpoolAutomaticActionMode::Hold => {
+                Some(action.target_length - action.progress)
+            }
+        };
+        self.spool_model.estimate(
+            self.spool_core_diameter(),
+            self.traverse_controller.get_step_size(),
+            self.puller_speed_controller.last_speed,
+            target_remaining,
+        )
     }
 
     pub fn calculate_spool_auto_progress_(&mut self, now: Instant) {
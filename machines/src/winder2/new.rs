@@ -244,6 +244,8 @@ impl MachineNewTrait for Winder2 {
                     target_length: Length::new::<meter>(250.0),
                     mode: super::api::SpoolAutomaticActionMode::NoAction,
                 },
+                spool_model: super::SpoolModel::default(),
+                spool_recorder: super::SpoolRecorder::default(),
                 machine_identification_unique: machine_id,
                 connected_machines: vec![],
             };
@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use std::time::Instant;
 
 use control_core::{
@@ -11,7 +12,7 @@ use units::f64::*;
 use units::jerk::meter_per_minute_per_second_squared;
 use units::velocity::meter_per_minute;
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, JsonSchema)]
 pub enum GearRatio {
     OneToOne,
     OneToFive,
@@ -147,7 +148,7 @@ impl PullerSpeedController {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
 pub enum PullerRegulationMode {
     #[default]
     Speed,
@@ -0,0 +1,353 @@
+use std::time::{Duration, Instant};
+use units::ConstZero;
+use units::angular_velocity::revolution_per_second;
+use units::f64::*;
+use units::length::{meter, millimeter};
+use units::velocity::meter_per_second;
+
+/// Time constant in seconds of the low pass on the speed based diameter
+const SPEED_DIAMETER_TIME_CONSTANT: f64 = 10.0;
+
+/// Below this spool speed in rev/s the speed based diameter is too noisy to learn from
+const MIN_SPOOL_SPEED: f64 = 0.02;
+
+/// Below this line speed in m/s the speed based diameter is too noisy to learn from
+const MIN_LINE_SPEED: f64 = 0.001;
+
+/// Share of the speed based diameter in the estimate, the rest is the layer based diameter
+///
+/// The speed based diameter follows the real packing of the filament, but it lags and carries
+/// the slip of the tension arm loop. The layer count is exact but assumes one step size per layer.
+const SPEED_DIAMETER_WEIGHT: f64 = 0.5;
+
+/// Wound diameter in mm above the core the estimate needs before extrapolating the length
+const MIN_WOUND_DIAMETER: f64 = 1.0;
+
+/// What the spool model predicts about the spool being wound
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpoolEstimate {
+    pub diameter: Length,
+    /// Fraction of the wound cross section from 0 (empty core) to 1 (max diameter)
+    pub fill: f64,
+    /// Filament that still fits until the max diameter is reached, `None` until enough is wound
+    pub remaining_to_limit: Option<Length>,
+    /// Filament until the spool is done, the target length or the max diameter, whichever is first
+    pub remaining: Option<Length>,
+    /// Time until the max diameter is reached at the current line speed
+    pub time_to_limit: Option<Duration>,
+    /// Time until the spool is done at the current line speed
+    pub time_to_full: Option<Duration>,
+}
+
+/// Estimates the wound diameter of the spool and how much filament still fits on it
+///
+/// Two diameters are combined: the line speed over the spool speed, which is what the spool
+/// really looks like, and the core plus two step sizes per wound layer. The wound length is
+/// scaled by the cross section that is still free, so the prediction follows how tightly the
+/// filament is actually packed.
+#[derive(Debug)]
+pub struct SpoolModel {
+    /// Largest diameter the flanges allow
+    max_diameter: Length,
+    /// Finish the spool with the automatic action once the max diameter is reached
+    stop_when_full: bool,
+    /// Low pass filtered line speed over spool speed
+    speed_diameter: Option<Length>,
+    /// Layers wound on this spool
+    layers: u32,
+    /// Layer of the traverse at the last update, `None` while it isn't traversing
+    last_traverse_layer: Option<u32>,
+    /// Filament wound on this spool
+    wound_length: Length,
+    last_update: Option<Instant>,
+}
+
+impl Default for SpoolModel {
+    fn default() -> Self {
+        Self::new(Length::new::<millimeter>(200.0))
+    }
+}
+
+impl SpoolModel {
+    pub const fn new(max_diameter: Length) -> Self {
+        Self {
+            max_diameter,
+            stop_when_full: false,
+            speed_diameter: None,
+            layers: 0,
+            last_traverse_layer: None,
+            wound_length: Length::ZERO,
+            last_update: None,
+        }
+    }
+
+    pub const fn get_max_diameter(&self) -> Length {
+        self.max_diameter
+    }
+
+    pub fn set_max_diameter(&mut self, max_diameter: Length) {
+        self.max_diameter = max_diameter;
+    }
+
+    pub const fn get_stop_when_full(&self) -> bool {
+        self.stop_when_full
+    }
+
+    pub const fn set_stop_when_full(&mut self, stop_when_full: bool) {
+        self.stop_when_full = stop_when_full;
+    }
+
+    pub const fn get_wound_length(&self) -> Length {
+        self.wound_length
+    }
+
+    /// Starts over with an empty spool
+    pub const fn reset(&mut self) {
+        self.speed_diameter = None;
+        self.layers = 0;
+        self.last_traverse_layer = None;
+        self.wound_length = Length::ZERO;
+        self.last_update = None;
+    }
+
+    /// Nothing is wound, the next update doesn't count the time in between
+    pub const fn pause(&mut self) {
+        self.last_update = None;
+        self.last_traverse_layer = None;
+    }
+
+    /// Learns from one cycle of winding
+    ///
+    /// `traverse_layer` is the layer counter of the traverse, which starts over whenever the
+    /// traverse starts traversing, so only its increments are counted.
+    pub fn update(
+        &mut self,
+        now: Instant,
+        line_speed: Velocity,
+        spool_speed: AngularVelocity,
+        traverse_layer: Option<u32>,
+    ) {
+        if let (Some(layer), Some(last_layer)) = (traverse_layer, self.last_traverse_layer) {
+            self.layers += layer.saturating_sub(last_layer);
+        }
+        self.last_traverse_layer = traverse_layer;
+
+        let Some(last_update) = self.last_update.replace(now) else {
+            return;
+        };
+        let dt = now.duration_since(last_update).as_secs_f64();
+        let line_speed = line_speed.get::<meter_per_second>().abs();
+        let spool_speed = spool_speed.get::<revolution_per_second>().abs();
+        self.wound_length += Length::new::<meter>(line_speed * dt);
+
+        if spool_speed < MIN_SPOOL_SPEED || line_speed < MIN_LINE_SPEED {
+            return;
+        }
+        let diameter = Length::new::<meter>(line_speed / (std::f64::consts::PI * spool_speed));
+        self.speed_diameter = Some(match self.speed_diameter {
+            Some(filtered) => {
+                let alpha = 1.0 - (-dt / SPEED_DIAMETER_TIME_CONSTANT).exp();
+                filtered + (diameter - filtered) * alpha
+            }
+            None => diameter,
+        });
+    }
+
+    /// Diameter of the core plus two step sizes per wound layer
+    pub fn layer_diameter(&self, core_diameter: Length, step_size: Length) -> Length {
+        core_diameter + step_size * (2.0 * self.layers as f64)
+    }
+
+    /// Current wound diameter, never below the core
+    pub fn diameter(&self, core_diameter: Length, step_size: Length) -> Length {
+        let layer_diameter = self.layer_diameter(core_diameter, step_size);
+        let diameter = self
+            .speed_diameter
+            .map_or(layer_diameter, |speed_diameter| {
+                speed_diameter * SPEED_DIAMETER_WEIGHT
+                    + layer_diameter * (1.0 - SPEED_DIAMETER_WEIGHT)
+            });
+        diameter.max(core_diameter)
+    }
+
+    /// Whether the estimated diameter reached the max diameter
+    pub fn is_full(&self, core_diameter: Length, step_size: Length) -> bool {
+        self.diameter(core_diameter, step_size) >= self.max_diameter
+    }
+
+    /// `target_remaining` is what is left of the target length of the automatic action, if any
+    pub fn estimate(
+        &self,
+        core_diameter: Length,
+        step_size: Length,
+        line_speed: Velocity,
+        target_remaining: Option<Length>,
+    ) -> SpoolEstimate {
+        let diameter = self.diameter(core_diameter, step_size);
+        let [core, current, max] =
+            [core_diameter, diameter, self.max_diameter].map(|d| d.get::<millimeter>());
+
+        let capacity = max.mul_add(max, -core * core);
+        let wound = current.mul_add(current, -core * core);
+        let fill = if capacity > 0.0 {
+            (wound / capacity).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+
+        // the wound length fills the wound cross section, the rest fills the free one alike
+        let remaining_to_limit = (current - core >= MIN_WOUND_DIAMETER).then(|| {
+            let free = max.mul_add(max, -current * current).max(0.0);
+            self.wound_length * (free / wound)
+        });
+        let remaining = match (remaining_to_limit, target_remaining) {
+            (Some(limit), Some(target)) => Some(limit.min(target.max(Length::ZERO))),
+            (limit, target) => limit.or_else(|| target.map(|target| target.max(Length::ZERO))),
+        };
+
+        let line_speed = line_speed.get::<meter_per_second>().abs();
+        let time_to = |length: Option<Length>| {
+            length
+                .filter(|_| line_speed >= MIN_LINE_SPEED)
+                .and_then(|length| {
+                    Duration::try_from_secs_f64(length.get::<meter>() / line_speed).ok()
+                })
+        };
+
+        SpoolEstimate {
+            diameter,
+            fill,
+            remaining_to_limit,
+            remaining,
+            time_to_limit: time_to(remaining_to_limit),
+            time_to_full: time_to(remaining),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use units::velocity::meter_per_minute;
+
+    fn mm(value: f64) -> Length {
+        Length::new::<millimeter>(value)
+    }
+
+    #[test]
+    fn test_speed_diameter() {
+        let mut model = SpoolModel::new(mm(200.0));
+        let now = Instant::now();
+        // 1 m/s on 2 rev/s is a circumference of 0.5 m
+        let line_speed = Velocity::new::<meter_per_second>(1.0);
+        let spool_speed = AngularVelocity::new::<revolution_per_second>(2.0);
+        for step in 0..=100 {
+            model.update(
+                now + Duration::from_millis(100 * step),
+                line_speed,
+                spool_speed,
+                None,
+            );
+        }
+        let expected = (0.5 / std::f64::consts::PI * 1000.0).mul_add(0.5, 100.0 * 0.5);
+        assert_relative_eq!(
+            model.diameter(mm(100.0), mm(1.0)).get::<millimeter>(),
+            expected,
+            epsilon = 1e-9
+        );
+        assert_relative_eq!(
+            model.get_wound_length().get::<meter>(),
+            10.0,
+            epsilon = 1e-9
+        );
+    }
+
+    #[test]
+    fn test_layer_counting() {
+        let mut model = SpoolModel::new(mm(200.0));
+        let now = Instant::now();
+        let stopped = Velocity::ZERO;
+        let spool = AngularVelocity::ZERO;
+        for layer in [0, 1, 2, 3] {
+            model.update(now, stopped, spool, Some(layer));
+        }
+        // the traverse starts over, the layers already wound are kept
+        model.pause();
+        for layer in [0, 1, 2] {
+            model.update(now, stopped, spool, Some(layer));
+        }
+        assert_relative_eq!(
+            model.layer_diameter(mm(100.0), mm(2.0)).get::<millimeter>(),
+            120.0
+        );
+
+        model.reset();
+        assert_relative_eq!(
+            model.diameter(mm(100.0), mm(2.0)).get::<millimeter>(),
+            100.0
+        );
+    }
+
+    #[test]
+    fn test_estimate() {
+        let mut model = SpoolModel::new(mm(200.0));
+        let now = Instant::now();
+        // 10 layers of 5 mm wound within 100 s at 60 m/min, the spool is not turning
+        let line_speed = Velocity::new::<meter_per_minute>(60.0);
+        for layer in 0..=10 {
+            model.update(
+                now + Duration::from_secs(10 * layer as u64),
+                line_speed,
+                AngularVelocity::ZERO,
+                Some(layer),
+            );
+        }
+        assert_relative_eq!(
+            model.get_wound_length().get::<meter>(),
+            100.0,
+            epsilon = 1e-9
+        );
+
+        let estimate = model.estimate(mm(100.0), mm(5.0), line_speed, None);
+        assert_relative_eq!(estimate.diameter.get::<millimeter>(), 200.0);
+        assert_relative_eq!(estimate.fill, 1.0);
+        assert!(model.is_full(mm(100.0), mm(5.0)));
+        assert_relative_eq!(estimate.remaining_to_limit.unwrap().get::<meter>(), 0.0);
+
+        // half the diameter range is a quarter of the cross section
+        model.set_max_diameter(mm(100.0f64.hypot(200.0)));
+        let estimate = model.estimate(
+            mm(100.0),
+            mm(5.0),
+            line_speed,
+            Some(Length::new::<meter>(30.0)),
+        );
+        assert_relative_eq!(estimate.fill, 0.75, epsilon = 1e-9);
+        assert_relative_eq!(
+            estimate.remaining_to_limit.unwrap().get::<meter>(),
+            100.0 / 3.0,
+            epsilon = 1e-9
+        );
+        assert_relative_eq!(estimate.remaining.unwrap().get::<meter>(), 30.0);
+        assert_relative_eq!(estimate.time_to_full.unwrap().as_secs_f64(), 30.0);
+        assert_relative_eq!(
+            estimate.time_to_limit.unwrap().as_secs_f64(),
+            100.0 / 3.0,
+            epsilon = 1e-6
+        );
+
+        // nothing is predicted while standing still
+        let estimate = model.estimate(mm(100.0), mm(5.0), Velocity::ZERO, None);
+        assert!(estimate.time_to_limit.is_none());
+
+        // a remaining time beyond what a duration holds is no estimate
+        let estimate = SpoolModel::new(mm(200.0)).estimate(
+            mm(100.0),
+            mm(5.0),
+            Velocity::new::<meter_per_second>(MIN_LINE_SPEED),
+            Some(Length::new::<meter>(1e300)),
+        );
+        assert!(estimate.time_to_full.is_none());
+    }
+}
@@ -0,0 +1,143 @@
+use crate::laser::{LaserMachine, statistics::get_latest_statistics};
+use crate::machine_identification::MachineIdentificationUnique;
+use crate::machine_settings::get_latest_settings;
+use crate::production_record::{
+    DiameterRecord, MachineSettingsRecord, ProductionRecord, SpoolCompletion, unix_timestamp_ms,
+};
+use std::time::SystemTime;
+use units::f64::Length;
+use units::length::meter;
+
+/// Tracks the spool that is currently wound to create its production record
+#[derive(Debug, Default)]
+pub struct SpoolRecorder {
+    /// Set when winding of the spool started
+    pub started_at: Option<SystemTime>,
+    /// Lot number of the next records, set by the operator
+    pub lot_number: Option<String>,
+}
+
+impl SpoolRecorder {
+    /// Returns true if a new spool was started
+    pub fn start(&mut self, now: SystemTime) -> bool {
+        if self.started_at.is_some() {
+            return false;
+        }
+        self.started_at = Some(now);
+        true
+    }
+
+    /// Creates the record of the current spool
+    /// `machines` are the winder and all machines connected to it
+    pub fn finish(
+        &mut self,
+        now: SystemTime,
+        winder: &MachineIdentificationUnique,
+        machines: &[MachineIdentificationUnique],
+        length: Length,
+        completion: SpoolCompletion,
+    ) -> Option<ProductionRecord> {
+        let started_at = self.started_at.take()?;
+
+        let diameter = machines
+            .iter()
+            .filter(|machine| {
+                machine.machine_identification == LaserMachine::MACHINE_IDENTIFICATION
+            })
+            .find_map(|laser| {
+                let statistics = get_latest_statistics(laser)?;
+                Some(DiameterRecord {
+                    laser: laser.clone(),
+                    diameter: statistics.diameter.total,
+                    ovality: statistics.ovality.total,
+                })
+            });
+
+        Some(ProductionRecord {
+            id: 0,
+            lot_number: self.lot_number.clone().unwrap_or_default(),
+            winder: winder.clone(),
+            started_at: unix_timestamp_ms(started_at),
+            finished_at: unix_timestamp_ms(now),
+            length: length.get::<meter>(),
+            completion,
+            machines: machines
+                .iter()
+                .map(|machine| MachineSettingsRecord {
+                    machine_identification_unique: machine.clone(),
+                    settings: get_latest_settings(machine),
+                })
+                .collect(),
+            diameter,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine_identification::MachineIdentification;
+    use crate::machine_settings::set_latest_settings;
+    use crate::{MACHINE_EXTRUDER_V1, MACHINE_WINDER_V1, VENDOR_QITECH};
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn machine(machine: u16, serial: u16) -> MachineIdentificationUnique {
+        MachineIdentificationUnique {
+            machine_identification: MachineIdentification {
+                vendor: VENDOR_QITECH,
+                machine,
+            },
+            serial,
+        }
+    }
+
+    #[test]
+    fn test_spool_recorder() {
+        let winder = machine(MACHINE_WINDER_V1, 0xF001);
+        let extruder = machine(MACHINE_EXTRUDER_V1, 0xF002);
+        set_latest_settings(&winder, json!({ "target_speed": 10.0 }));
+
+        let mut recorder = SpoolRecorder::default();
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+        let machines = [winder.clone(), extruder.clone()];
+
+        // nothing is recorded without a started spool
+        assert!(
+            recorder
+                .finish(
+                    start,
+                    &winder,
+                    &machines,
+                    Length::new::<meter>(1.0),
+                    SpoolCompletion::Manual
+                )
+                .is_none()
+        );
+
+        assert!(recorder.start(start));
+        assert!(!recorder.start(start + Duration::from_secs(5)));
+        recorder.lot_number = Some("L-42".to_string());
+
+        let record = recorder
+            .finish(
+                start + Duration::from_secs(60),
+                &winder,
+                &machines,
+                Length::new::<meter>(250.0),
+                SpoolCompletion::Automatic,
+            )
+            .unwrap();
+        assert_eq!(record.lot_number, "L-42");
+        assert_eq!(record.started_at, 100_000);
+        assert_eq!(record.finished_at, 160_000);
+        assert_eq!(record.length, 250.0);
+        assert_eq!(
+            record.machines[0].settings,
+            Some(json!({ "target_speed": 10.0 }))
+        );
+        assert_eq!(record.machines[1].settings, None);
+        assert_eq!(record.diameter, None);
+        assert!(recorder.started_at.is_none());
+    }
+}
@@ -4,13 +4,14 @@ use crate::winder2::{
     puller_speed_controller::PullerSpeedController,
 };
 use control_core::controllers::second_degree_motion::acceleration_position_controller::MotionControllerError;
+use schemars::JsonSchema;
 
 use super::tension_arm::TensionArm;
 use serde::{Deserialize, Serialize};
 use std::time::Instant;
 use units::f64::*;
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, JsonSchema)]
 pub enum SpoolSpeedControllerType {
     #[default]
     Adaptive,
@@ -1,15 +1,26 @@
 use std::time::Instant;
 
+use control_core::controllers::electronic_gearing::gear::{ElectronicGear, GearingLaw};
+use control_core::converters::angular_step_converter::AngularStepConverter;
 use control_core::converters::linear_step_converter::LinearStepConverter;
 use ethercat_hal::io::{
     digital_input::DigitalInput, stepper_velocity_el70x1::StepperVelocityEL70x1,
 };
 use units::ConstZero;
+use units::angle::revolution;
 use units::angular_velocity::revolution_per_second;
 use units::f64::{AngularVelocity, Length, Velocity};
 use units::length::millimeter;
 use units::velocity::millimeter_per_second;
 
+use super::winding_pattern::WindingSettings;
+
+/// Gain in 1/s of the position loop that keeps the traverse on the geared position
+const GEAR_POSITION_GAIN: f64 = 10.0;
+
+/// Fastest the traverse moves in mm/s, also while geared to the spool
+const MAX_SPEED: f64 = 100.0;
+
 #[derive(Debug)]
 pub struct TraverseController {
     enabled: bool,
@@ -21,6 +32,18 @@ pub struct TraverseController {
     state: State,
     fullstep_converter: LinearStepConverter,
     microstep_converter: LinearStepConverter,
+    /// Locks the traverse position to the spool rotation while traversing
+    gear: ElectronicGear,
+    /// Converts the position of the spool stepper like the spool speed controller does
+    spool_step_converter: AngularStepConverter,
+    last_spool_steps: Option<i128>,
+    /// Revolutions the spool turned in either direction, the master of the gear
+    spool_revolutions: f64,
+    winding: WindingSettings,
+    /// Strokes since traversing started
+    layer: u32,
+    /// Spool revolutions at which the end dwell of the current turnaround is over
+    dwell_until: Option<f64>,
     // A sticky flag if the [`State`] changed (not the sub states)
     // Needed to send state updates to the UI
     did_change_state: bool,
@@ -62,12 +85,12 @@ pub enum TraversingState {
 
     /// Like [`State::GoingIn`] but
     /// - will go into [`State::GoingOut`] after reaching the inner limit
-    /// - speed is synced to spool speed
+    /// - position is geared to the spool rotation
     TraversingIn,
 
     /// Like [`State::GoingOut`] but
     /// - will go into [`State::GoingIn`] after reaching the outer limit
-    /// - speed is synced to spool speed
+    /// - position is geared to the spool rotation
     TraversingOut,
 }
 
@@ -115,6 +138,13 @@ impl TraverseController {
                 200 * microsteps as i16,
                 Length::new::<millimeter>(35.0),
             ),
+            gear: ElectronicGear::new(GearingLaw::Ratio(0.0)),
+            spool_step_converter: AngularStepConverter::new(200),
+            last_spool_steps: None,
+            spool_revolutions: 0.0,
+            winding: WindingSettings::default(),
+            layer: 0,
+            dwell_until: None,
         }
     }
 }
@@ -135,6 +165,12 @@ impl TraverseController {
 
     pub fn set_step_size(&mut self, step_size: Length) {
         self.step_size = step_size;
+        self.update_gear_law();
+    }
+
+    pub fn set_winding(&mut self, winding: WindingSettings) {
+        self.winding = winding;
+        self.update_gear_law();
     }
 
     pub fn set_padding(&mut self, padding: Length) {
@@ -157,6 +193,27 @@ impl TraverseController {
         self.padding
     }
 
+    pub const fn get_winding(&self) -> &WindingSettings {
+        &self.winding
+    }
+
+    pub const fn get_layer(&self) -> u32 {
+        self.layer
+    }
+
+    /// Inner and outer turnaround point of the current layer
+    pub fn get_stroke_limits(&self) -> (Length, Length) {
+        let (inner, outer) = self.winding.stroke_limits(
+            self.layer,
+            (self.limit_inner + self.padding).get::<millimeter>(),
+            (self.limit_outer - self.padding).get::<millimeter>(),
+        );
+        (
+            Length::new::<millimeter>(inner),
+            Length::new::<millimeter>(outer),
+        )
+    }
+
     pub fn get_current_position(&self) -> Option<Length> {
         match self.is_homed() {
             true => Some(self.position),
@@ -192,6 +249,8 @@ impl TraverseController {
 
     pub const fn start_traversing(&mut self) {
         self.state = State::Traversing(TraversingState::GoingOut);
+        self.layer = 0;
+        self.dwell_until = None;
     }
 
     pub const fn is_homed(&self) -> bool {
@@ -257,6 +316,92 @@ impl TraverseController {
         self.position = self.microstep_converter.steps_to_distance(steps as f64);
     }
 
+    /// Counts the spool revolutions, the direction doesn't matter for the traverse
+    fn sync_spool_position(&mut self, spool: &StepperVelocityEL70x1) {
+        let steps = spool.get_position();
+        if let Some(last_steps) = self.last_spool_steps {
+            let angle = self
+                .spool_step_converter
+                .steps_to_angle((steps - last_steps).abs() as f64);
+            self.spool_revolutions += angle.get::<revolution>();
+        }
+        self.last_spool_steps = Some(steps);
+    }
+
+    /// Traverse millimeters per spool revolution of the current stroke
+    fn gear_law(&self) -> Option<GearingLaw> {
+        let step_size = self
+            .winding
+            .step(self.layer, self.step_size.get::<millimeter>());
+        match self.state {
+            State::Traversing(TraversingState::TraversingIn) => Some(GearingLaw::Ratio(-step_size)),
+            State::Traversing(TraversingState::TraversingOut) => Some(GearingLaw::Ratio(step_size)),
+            _ => None,
+        }
+    }
+
+    /// Applies a changed step size or winding pattern to the running stroke from where it is
+    fn update_gear_law(&mut self) {
+        if self.dwell_until.is_some() {
+            // the next stroke picks it up
+            return;
+        }
+        if let Some(law) = self.gear_law() {
+            self.gear.set_law(law);
+        }
+    }
+
+    /// Dwells at the end for [`WindingSettings::end_dwell`] and starts the next layer
+    fn turn_around(&mut self, next: TraversingState, turn: Length) {
+        if self.winding.end_dwell > 0.0 {
+            let dwell_until = match self.dwell_until {
+                Some(dwell_until) => dwell_until,
+                None => {
+                    // hold the position while the spool keeps turning
+                    self.gear.set_law(GearingLaw::Ratio(0.0));
+                    let dwell_until = self.spool_revolutions + self.winding.end_dwell;
+                    self.dwell_until = Some(dwell_until);
+                    dwell_until
+                }
+            };
+            if self.spool_revolutions < dwell_until {
+                return;
+            }
+        }
+        self.dwell_until = None;
+        self.layer += 1;
+        self.state = State::Traversing(next);
+        self.engage_gear(turn);
+    }
+
+    /// Starts a stroke at the point where the traverse turned around
+    ///
+    /// The stroke starts at the nominal turnaround point and not at the measured position, so the
+    /// strokes don't accumulate the overshoot of each turn.
+    fn engage_gear(&mut self, start: Length) {
+        let Some(law) = self.gear_law() else {
+            self.gear.disengage();
+            return;
+        };
+        self.gear.set_law(law);
+        self.gear
+            .engage(self.spool_revolutions, start.get::<millimeter>());
+    }
+
+    /// Follows the geared target position
+    ///
+    /// The spool speed is fed forward, the position error is corrected with [`GEAR_POSITION_GAIN`].
+    /// The speed is limited to [`MAX_SPEED`], a spool too fast for the step size lets the traverse
+    /// fall behind.
+    fn geared_speed(&mut self, spool_speed: AngularVelocity) -> Velocity {
+        let target = self.gear.update(self.spool_revolutions);
+        let feed_forward = self.gear.ratio() * spool_speed.get::<revolution_per_second>().abs();
+        let correction = GEAR_POSITION_GAIN * (target - self.position.get::<millimeter>());
+        Velocity::new::<millimeter_per_second>(
+            (feed_forward + correction).clamp(-MAX_SPEED, MAX_SPEED),
+        )
+    }
+
     /// Update the [`did_change_state`] flag
     /// Only considers the major state not the sub states
     const fn update_did_change_state(&mut self, old_state: &State) -> bool {
@@ -364,23 +509,27 @@ impl TraverseController {
             State::Traversing(traversing_state) => match traversing_state {
                 TraversingState::GoingOut => {
                     // If outer limit is reached
-                    if self.position >= self.limit_outer - self.padding {
+                    let (_, outer) = self.get_stroke_limits();
+                    if self.position >= outer {
                         // Turn around
                         self.state = State::Traversing(TraversingState::TraversingIn);
+                        self.engage_gear(outer);
                     }
                 }
                 TraversingState::TraversingIn => {
                     // If inner limit is reached
-                    if self.position <= self.limit_inner + self.padding {
+                    let (inner, _) = self.get_stroke_limits();
+                    if self.position <= inner {
                         // Turn around
-                        self.state = State::Traversing(TraversingState::TraversingOut);
+                        self.turn_around(TraversingState::TraversingOut, inner);
                     }
                 }
                 TraversingState::TraversingOut => {
                     // If outer limit is reached
-                    if self.position >= self.limit_outer - self.padding {
+                    let (_, outer) = self.get_stroke_limits();
+                    if self.position >= outer {
                         // Turn around
-                        self.state = State::Traversing(TraversingState::TraversingIn);
+                        self.turn_around(TraversingState::TraversingIn, outer);
                     }
                 }
             },
@@ -403,7 +552,7 @@ impl TraverseController {
                     match self.distance_to_position(self.limit_inner).abs()
                         > Length::new::<millimeter>(1.0)
                     {
-                        true => Velocity::new::<millimeter_per_second>(100.0),
+                        true => Velocity::new::<millimeter_per_second>(MAX_SPEED),
                         false => Velocity::new::<millimeter_per_second>(10.0),
                     },
                 )
@@ -415,7 +564,7 @@ impl TraverseController {
                     match self.distance_to_position(self.limit_outer).abs()
                         > Length::new::<millimeter>(1.0)
                     {
-                        true => Velocity::new::<millimeter_per_second>(100.0),
+                        true => Velocity::new::<millimeter_per_second>(MAX_SPEED),
                         false => Velocity::new::<millimeter_per_second>(10.0),
                     },
                 )
@@ -448,47 +597,24 @@ impl TraverseController {
                     // Move out at a speed of 100 mm/s
                     self.speed_to_position(
                         self.limit_outer - self.padding + Length::new::<millimeter>(0.01),
-                        Velocity::new::<millimeter_per_second>(100.0),
+                        Velocity::new::<millimeter_per_second>(MAX_SPEED),
                     )
                 }
-                TraversingState::TraversingIn => self.speed_to_position(
-                    self.limit_inner + self.padding - Length::new::<millimeter>(0.01),
-                    Self::calculate_traverse_speed(spool_speed, self.step_size),
-                ),
-                TraversingState::TraversingOut => self.speed_to_position(
-                    self.limit_outer - self.padding + Length::new::<millimeter>(0.01),
-                    Self::calculate_traverse_speed(spool_speed, self.step_size),
-                ),
+                TraversingState::TraversingIn | TraversingState::TraversingOut => {
+                    self.geared_speed(spool_speed)
+                }
             },
         }
     }
 
-    /// Calculate the traverse speed
-    ///
-    /// The traverse speed is the linear speed at which the winding mechanism moves along the spool.
-    /// It's directly proportional to how fast the spool rotates and how far the traverse moves per rotation.
-    ///
-    /// - Traverse Distance per Revolution [mm] = Step Size [mm]
-    /// - Traverse Speed [mm/s] = Spool Speed [rev/s or rad/s] * Step Size [mm]
-    ///
-    /// Note: While the traverse range (from outer limit minus padding to inner limit plus padding)
-    /// determines the total area to be covered, the traverse speed itself depends only on
-    /// the step size and spool rotation speed.
-    pub fn calculate_traverse_speed(spool_speed: AngularVelocity, step_size: Length) -> Velocity {
-        // Calculate the traverse speed directly from spool speed and step size
-        let traverse_speed: Velocity = Velocity::new::<millimeter_per_second>(
-            spool_speed.get::<revolution_per_second>() * step_size.get::<millimeter>(),
-        );
-
-        traverse_speed
-    }
-
     pub fn update_speed(
         &mut self,
         traverse: &mut StepperVelocityEL70x1,
         traverse_end_stop: &DigitalInput,
+        spool: &StepperVelocityEL70x1,
         spool_speed: AngularVelocity,
     ) {
+        self.sync_spool_position(spool);
         let speed = self.get_speed(traverse, traverse_end_stop, spool_speed);
         let steps_per_second = self.fullstep_converter.velocity_to_steps(speed);
         // ignore if we can't set speed
@@ -502,16 +628,81 @@ mod tests {
 
     use super::*;
 
+    /// Traversing out from `position` with the gear engaged at spool revolution 0
+    fn traversing_out(position: f64) -> TraverseController {
+        let mut controller = TraverseController::new(
+            Length::new::<millimeter>(10.0),
+            Length::new::<millimeter>(100.0),
+            64,
+        );
+        controller.set_enabled(true);
+        controller.state = State::Traversing(TraversingState::TraversingOut);
+        controller.position = Length::new::<millimeter>(position);
+        controller.engage_gear(controller.position);
+        controller
+    }
+
     #[test]
-    fn test_calculate_traverse_speed() {
-        let spool_speed = AngularVelocity::new::<revolution_per_second>(1.0); // 1 revolution per second
-        let step_size = Length::new::<millimeter>(1.75); // 1.75 mm step size
+    fn test_geared_speed() {
+        let mut controller = traversing_out(50.0);
+        let spool_speed = AngularVelocity::new::<revolution_per_second>(1.0);
 
-        let traverse_speed = TraverseController::calculate_traverse_speed(spool_speed, step_size);
+        // one spool revolution moves the target by the step size, the lag is corrected
+        controller.spool_revolutions = 1.0;
+        let speed = controller.geared_speed(spool_speed);
         assert_relative_eq!(
-            traverse_speed.get::<millimeter_per_second>(),
-            1.75,
-            epsilon = f64::EPSILON
+            speed.get::<millimeter_per_second>(),
+            1.75 + 10.0 * 1.75,
+            epsilon = 1e-9
         );
+
+        controller.position = Length::new::<millimeter>(51.75);
+        let speed = controller.geared_speed(spool_speed);
+        assert_relative_eq!(speed.get::<millimeter_per_second>(), 1.75, epsilon = 1e-9);
+
+        // never faster than the traverse can go
+        let spool_speed = AngularVelocity::new::<revolution_per_second>(100.0);
+        let speed = controller.geared_speed(spool_speed);
+        assert_relative_eq!(speed.get::<millimeter_per_second>(), MAX_SPEED);
+
+        // the spool position is counted in full steps like in the spool speed controller
+        let angle = controller.spool_step_converter.steps_to_angle(200.0);
+        assert_relative_eq!(angle.get::<revolution>(), 1.0);
+    }
+
+    #[test]
+    fn test_turn_around_with_dwell() {
+        let mut controller = traversing_out(99.12);
+        controller.set_winding(WindingSettings {
+            end_dwell: 0.5,
+            ..Default::default()
+        });
+        let (_, outer) = controller.get_stroke_limits();
+
+        // holds the position until the spool turned the dwell
+        controller.turn_around(TraversingState::TraversingIn, outer);
+        assert_eq!(
+            controller.state,
+            State::Traversing(TraversingState::TraversingOut)
+        );
+        assert_relative_eq!(controller.gear.ratio(), 0.0);
+        controller.spool_revolutions = 0.4;
+        controller.turn_around(TraversingState::TraversingIn, outer);
+        assert_eq!(controller.get_layer(), 0);
+
+        controller.spool_revolutions = 0.5;
+        controller.turn_around(TraversingState::TraversingIn, outer);
+        assert_eq!(controller.get_layer(), 1);
+        assert_eq!(
+            controller.state,
+            State::Traversing(TraversingState::TraversingIn)
+        );
+        assert_relative_eq!(controller.gear.ratio(), -1.75);
+
+        // the stroke starts at the turnaround point and moves in with the spool
+        controller.spool_revolutions = 1.5;
+        controller.position = outer - Length::new::<millimeter>(1.75);
+        let speed = controller.geared_speed(AngularVelocity::new::<revolution_per_second>(1.0));
+        assert_relative_eq!(speed.get::<millimeter_per_second>(), -1.75, epsilon = 1e-9);
     }
 }
@@ -0,0 +1,219 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+/// Narrowest stroke the limit tapering leaves, in mm
+const MIN_STROKE_WIDTH: f64 = 1.0;
+
+/// Largest spool core, in mm
+const MAX_CORE_DIAMETER: f64 = 1000.0;
+
+/// Longest dwell at the ends, in spool revolutions
+const MAX_END_DWELL: f64 = 100.0;
+
+/// Largest pull-in of the turnaround points by the edge compensation or per layer by the taper,
+/// in mm, wider than any traverse
+const MAX_PULL_IN: f64 = 500.0;
+
+/// How the traverse pitch (mm per spool revolution) develops while the spool fills
+///
+/// One stroke lays one layer, the layer thickness is taken as the step size.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+pub enum WindingPattern {
+    /// Constant pitch, so constant spool revolutions per stroke (crossing ratio)
+    #[default]
+    Precision,
+    /// Pitch grows with the wound diameter, so the crossing angle stays the one of the core
+    Random,
+    /// Like [`WindingPattern::Random`], but the pitch is only adjusted every `step_layers` layers
+    /// and held in between like a precision wind
+    StepPrecision,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+pub struct WindingSettings {
+    pub pattern: WindingPattern,
+    /// Diameter of the empty spool core in mm, for the random and step precision wind
+    pub core_diameter: f64,
+    /// Layers a step precision wind keeps the pitch
+    pub step_layers: u32,
+    /// Ribbon breaking, the pitch is modulated by up to this fraction, 0 disables it
+    pub ribbon_breaking_amplitude: f64,
+    /// Strokes of one ribbon breaking modulation period
+    pub ribbon_breaking_period: u32,
+    /// Spool revolutions the traverse stays at each end before turning around
+    pub end_dwell: f64,
+    /// The turnaround points are pulled in by up to this distance in mm, alternating over three
+    /// strokes, so the filament doesn't pile up at the flanges
+    pub edge_compensation: f64,
+    /// Both limits move inwards by this distance in mm per layer
+    pub taper: f64,
+}
+
+impl Default for WindingSettings {
+    fn default() -> Self {
+        Self {
+            pattern: WindingPattern::Precision,
+            core_diameter: 100.0,
+            step_layers: 10,
+            ribbon_breaking_amplitude: 0.0,
+            ribbon_breaking_period: 8,
+            end_dwell: 0.0,
+            edge_compensation: 0.0,
+            taper: 0.0,
+        }
+    }
+}
+
+impl WindingSettings {
+    /// Values come from the operator, they must be finite and within the ranges below
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        let ranges = [
+            ("core_diameter", self.core_diameter, MAX_CORE_DIAMETER),
+            ("end_dwell", self.end_dwell, MAX_END_DWELL),
+            ("edge_compensation", self.edge_compensation, MAX_PULL_IN),
+            ("taper", self.taper, MAX_PULL_IN),
+        ];
+        for (name, value, max) in ranges {
+            if !(0.0..=max).contains(&value) {
+                anyhow::bail!(
+                    "Winding {} must be between 0 and {}, got {}",
+                    name,
+                    max,
+                    value
+                );
+            }
+        }
+        if self.core_diameter == 0.0 {
+            anyhow::bail!("Winding core_diameter must be positive");
+        }
+        if !(0.0..0.5).contains(&self.ribbon_breaking_amplitude) {
+            anyhow::bail!(
+                "Winding ribbon_breaking_amplitude must be at least 0 and below 0.5, got {}",
+                self.ribbon_breaking_amplitude
+            );
+        }
+        if self.step_layers == 0 || self.ribbon_breaking_period == 0 {
+            anyhow::bail!("Winding step_layers and ribbon_breaking_period must not be 0");
+        }
+        Ok(())
+    }
+
+    /// Traverse distance in mm per spool revolution during the layer
+    pub fn step(&self, layer: u32, step_size: f64) -> f64 {
+        let diameter_layer = match self.pattern {
+            WindingPattern::Precision => None,
+            WindingPattern::Random => Some(layer),
+            WindingPattern::StepPrecision => Some(layer - layer % self.step_layers),
+        };
+        let step = match diameter_layer {
+            Some(diameter_layer) => {
+                let diameter = (2.0 * diameter_layer as f64).mul_add(step_size, self.core_diameter);
+                step_size * diameter / self.core_diameter
+            }
+            None => step_size,
+        };
+
+        // A slowly varying pitch never stays on a ratio where the turns lie on top of each other
+        let phase = 2.0 * PI * layer as f64 / self.ribbon_breaking_period as f64;
+        step * self.ribbon_breaking_amplitude.mul_add(phase.sin(), 1.0)
+    }
+
+    /// Inner and outer turnaround point in mm of the layer
+    pub fn stroke_limits(&self, layer: u32, inner: f64, outer: f64) -> (f64, f64) {
+        let edge = self.edge_compensation * (layer % 3) as f64 / 2.0;
+        let pull_in = self.taper.mul_add(layer as f64, edge);
+        let center = (inner + outer) / 2.0;
+        let half_width = ((outer - inner) / 2.0 - pull_in).max(MIN_STROKE_WIDTH / 2.0);
+        if half_width * 2.0 >= outer - inner {
+            return (inner, outer);
+        }
+        (center - half_width, center + half_width)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn test_step() {
+        let mut settings = WindingSettings::default();
+        assert_relative_eq!(settings.step(50, 2.0), 2.0);
+
+        // after 25 layers of 2 mm the diameter grew from 100 to 200 mm
+        settings.pattern = WindingPattern::Random;
+        assert_relative_eq!(settings.step(0, 2.0), 2.0);
+        assert_relative_eq!(settings.step(25, 2.0), 4.0);
+
+        settings.pattern = WindingPattern::StepPrecision;
+        assert_relative_eq!(settings.step(29, 2.0), 2.0 * 1.8);
+        assert_relative_eq!(settings.step(30, 2.0), 2.0 * 2.2);
+
+        settings.pattern = WindingPattern::Precision;
+        settings.ribbon_breaking_amplitude = 0.1;
+        settings.ribbon_breaking_period = 4;
+        assert_relative_eq!(settings.step(1, 2.0), 2.2);
+        assert_relative_eq!(settings.step(3, 2.0), 1.8);
+    }
+
+    #[test]
+    fn test_stroke_limits() {
+        let mut settings = WindingSettings::default();
+        assert_eq!(settings.stroke_limits(7, 10.0, 60.0), (10.0, 60.0));
+
+        settings.edge_compensation = 1.0;
+        assert_eq!(settings.stroke_limits(0, 10.0, 60.0), (10.0, 60.0));
+        assert_eq!(settings.stroke_limits(1, 10.0, 60.0), (10.5, 59.5));
+        assert_eq!(settings.stroke_limits(2, 10.0, 60.0), (11.0, 59.0));
+
+        settings.edge_compensation = 0.0;
+        settings.taper = 0.5;
+        assert_eq!(settings.stroke_limits(10, 10.0, 60.0), (15.0, 55.0));
+        // never narrower than the minimum stroke
+        assert_eq!(settings.stroke_limits(1000, 10.0, 60.0), (34.5, 35.5));
+    }
+
+    #[test]
+    fn test_validate() {
+        WindingSettings::default().validate().unwrap();
+        let invalid = [
+            WindingSettings {
+                step_layers: 0,
+                ..Default::default()
+            },
+            WindingSettings {
+                ribbon_breaking_amplitude: 0.5,
+                ..Default::default()
+            },
+            WindingSettings {
+                core_diameter: 0.0,
+                ..Default::default()
+            },
+            WindingSettings {
+                core_diameter: f64::INFINITY,
+                ..Default::default()
+            },
+            WindingSettings {
+                end_dwell: f64::NAN,
+                ..Default::default()
+            },
+            WindingSettings {
+                end_dwell: 1e9,
+                ..Default::default()
+            },
+            WindingSettings {
+                edge_compensation: f64::INFINITY,
+                ..Default::default()
+            },
+            WindingSettings {
+                taper: -1.0,
+                ..Default::default()
+            },
+        ];
+        for settings in invalid {
+            assert!(settings.validate().is_err(), "{:?}", settings);
+        }
+    }
+}
@@ -1,4 +1,7 @@
+use crate::auth::Auth;
+use crate::config::ServerConfig;
 use crate::ethercat::config::{MAX_SUBDEVICES, PDI_LEN};
+use crate::line::Lines;
 use crate::rest::handlers::write_machine_device_identification::MachineDeviceInfoRequest;
 use crate::socketio::main_namespace::MainNamespaceEvents;
 use crate::socketio::main_namespace::machines_event::{MachineObj, MachinesEventBuilder};
@@ -10,6 +13,7 @@ use ethercrab::{MainDevice, SubDeviceGroup, subdevice_group::Op};
 use machines::machine_identification::{DeviceIdentification, MachineIdentificationUnique};
 use machines::serial::registry::SERIAL_DEVICE_REGISTRY;
 use machines::{Machine, MachineMessage};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use smol::channel::{Receiver, Sender};
 use smol::lock::{Mutex, RwLock};
@@ -18,10 +22,11 @@ use socketioxide::extract::SocketRef;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::sync::Arc;
+use std::time::Instant;
 
 pub struct SocketioSetup {
     pub socketio: RwLock<Option<SocketIo>>,
-    pub namespaces: RwLock<Namespaces>,
+    pub namespaces: Arc<RwLock<Namespaces>>,
     pub socket_queue_tx: Sender<(SocketRef, Arc<GenericEvent>)>,
     pub socket_queue_rx: Receiver<(SocketRef, Arc<GenericEvent>)>,
 }
@@ -43,6 +48,18 @@ pub enum HotThreadMessage {
     AddEtherCatSetup(EthercatSetup),
     WriteMachineDeviceInfo(MachineDeviceInfoRequest),
     DeleteMachine(MachineIdentificationUnique),
+    /// Bring all machines into their safe state until the deadline and stop the loop
+    Shutdown(Instant),
+    /// Create a quarantined machine again from its EtherCAT devices
+    RecreateMachine(RecreateMachineRequest),
+}
+
+pub struct RecreateMachineRequest {
+    pub machine_identification_unique: MachineIdentificationUnique,
+    pub socket_queue_tx: Sender<(SocketRef, Arc<GenericEvent>)>,
+    pub main_channel: Sender<AsyncThreadMessage>,
+    /// Gets the API sender of the new machine or why it wasn't created
+    pub response: Sender<Result<Sender<MachineMessage>, String>>,
 }
 
 use crate::AsyncThreadMessage;
@@ -50,7 +67,7 @@ use crate::AsyncThreadMessage;
 /*
     Instead of locking, etc only capture metadata on setup
 */
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct EtherCatDeviceMetaData {
     pub configured_address: u16,
     pub name: String,
@@ -83,6 +100,10 @@ pub struct SharedState {
     pub rt_machine_creation_channel: Sender<HotThreadMessage>,
     pub main_channel: Sender<AsyncThreadMessage>,
     pub ethercat_meta_data: RwLock<Vec<EtherCatDeviceMetaData>>,
+    pub lines: Mutex<Lines>,
+    pub auth: Mutex<Auth>,
+    /// Effective config the server was started with
+    pub config: ServerConfig,
 }
 
 impl fmt::Debug for EthercatSetup {
@@ -105,6 +126,8 @@ pub struct EthercatSetup {
     /// The Ethercat main device
     /// Needed to interface with the devices
     pub maindevice: MainDevice<'static>,
+    /// The system time has to be synced before the group goes into OP
+    pub has_dc: bool,
 }
 
 impl EthercatSetup {
@@ -112,11 +135,13 @@ impl EthercatSetup {
         devices: Vec<(DeviceIdentification, Arc<RwLock<dyn EthercatDevice>>)>,
         group: SubDeviceGroup<MAX_SUBDEVICES, PDI_LEN, Op>,
         maindevice: MainDevice<'static>,
+        has_dc: bool,
     ) -> Self {
         Self {
             devices,
             group,
             maindevice,
+            has_dc,
         }
     }
 }
@@ -142,6 +167,20 @@ impl SharedState {
         );
     }
 
+    /// Removes a machine from the api, the machine list and the RT loop
+    pub async fn delete_machine(&self, machine_id: &MachineIdentificationUnique) {
+        self.api_machines.lock().await.remove(machine_id);
+        self.remove_machine(machine_id).await;
+        machines::machine_settings::remove_latest_settings(machine_id);
+
+        let _ = self
+            .rt_machine_creation_channel
+            .send(HotThreadMessage::DeleteMachine(machine_id.clone()))
+            .await;
+
+        self.send_machines_event().await;
+    }
+
     pub async fn add_machines_if_not_exists(&self, machines: Vec<MachineObj>) {
         let mut current_machines = self.current_machines_meta.lock().await;
         tracing::info!("add_machines_if_not_exists: {:?}", current_machines);
@@ -181,15 +220,93 @@ impl SharedState {
         });
     }
 
+    /// Takes a machine the RT loop quarantined out of the api and reports the panic
+    pub async fn quarantine_machine(
+        &self,
+        machine_identification_unique: MachineIdentificationUnique,
+        panic_message: String,
+    ) {
+        tracing::error!(
+            "Machine {} quarantined: {}",
+            machine_identification_unique,
+            panic_message
+        );
+        self.api_machines
+            .lock()
+            .await
+            .remove(&machine_identification_unique);
+        self.report_machine_error(
+            machine_identification_unique,
+            format!("Quarantined after a panic: {}", panic_message),
+        )
+        .await;
+        self.send_machines_event().await;
+    }
+
+    /// Creates a quarantined machine again and puts it back into the api
+    pub async fn recreate_machine(
+        &self,
+        machine_identification_unique: &MachineIdentificationUnique,
+    ) -> Result<(), String> {
+        let (response, response_rx) = smol::channel::bounded(1);
+        self.rt_machine_creation_channel
+            .send(HotThreadMessage::RecreateMachine(RecreateMachineRequest {
+                machine_identification_unique: machine_identification_unique.clone(),
+                socket_queue_tx: self.socketio_setup.socket_queue_tx.clone(),
+                main_channel: self.main_channel.clone(),
+                response,
+            }))
+            .await
+            .map_err(|e| e.to_string())?;
+        let api_sender = response_rx
+            .recv()
+            .await
+            .map_err(|_| "The RT loop stopped".to_string())??;
+
+        self.insert_api_machine(machine_identification_unique.clone(), api_sender)
+            .await;
+        for machine in self.current_machines_meta.lock().await.iter_mut() {
+            if &machine.machine_identification_unique == machine_identification_unique {
+                machine.error = None;
+            }
+        }
+        self.send_machines_event().await;
+        Ok(())
+    }
+
+    /// Puts a machine into the api and subscribes it to its namespace
+    ///
+    /// The namespace has the snapshot collector, so the machine emits its events even
+    /// without a client.
+    pub async fn insert_api_machine(
+        &self,
+        machine_identification_unique: MachineIdentificationUnique,
+        sender: Sender<MachineMessage>,
+    ) {
+        let namespace = self
+            .socketio_setup
+            .namespaces
+            .write()
+            .await
+            .machine_namespace(&machine_identification_unique)
+            .clone();
+        let _ = sender
+            .send(MachineMessage::SubscribeNamespace(namespace))
+            .await;
+        self.api_machines
+            .lock()
+            .await
+            .insert(machine_identification_unique, sender);
+    }
+
     pub async fn add_machines(&self, machines: Vec<Box<dyn Machine>>) {
-        let mut api_machines = self.api_machines.lock().await;
         for machine in machines.iter() {
-            api_machines.insert(
+            self.insert_api_machine(
                 machine.get_machine_identification_unique(),
                 machine.api_get_sender(),
-            );
+            )
+            .await;
         }
-        drop(api_machines);
 
         let objs = machines
             .iter()
@@ -211,6 +328,7 @@ impl SharedState {
     pub fn new(
         sender: Sender<HotThreadMessage>,
         main_async_channel: Sender<AsyncThreadMessage>,
+        config: ServerConfig,
     ) -> Self {
         let (socket_queue_tx, socket_queue_rx) = smol::channel::unbounded();
         Self {
@@ -218,13 +336,16 @@ impl SharedState {
             ethercat_meta_data: vec![].into(),
             socketio_setup: SocketioSetup {
                 socketio: RwLock::new(None),
-                namespaces: RwLock::new(Namespaces::new(socket_queue_tx.clone())),
+                namespaces: Namespaces::new_shared(socket_queue_tx.clone()),
                 socket_queue_tx,
                 socket_queue_rx,
             },
             api_machines: Mutex::new(HashMap::new()),
             rt_machine_creation_channel: sender,
             main_channel: main_async_channel,
+            lines: Mutex::new(Lines::from_path(&config.lines.path)),
+            auth: Mutex::new(Auth::from_config(&config.auth)),
+            config,
         }
     }
 }
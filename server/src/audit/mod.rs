@@ -0,0 +1,372 @@
+use crate::config::AuditConfig;
+use crate::jsonl_store::{JsonlEntry, JsonlStore, SharedJsonlStore};
+use anyhow::Result;
+use control_core::helpers::csv::{CsvRow, csv_field};
+use machines::machine_identification::MachineIdentificationUnique;
+use machines::machine_settings::get_latest_settings;
+use machines::production_record::unix_timestamp_ms;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use smol::channel::Sender;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+
+/// How long to wait for a machine to publish the settings a mutation changed
+///
+/// Machines publish their settings with their state event after a mutation, the changes of a
+/// machine that publishes later are missing from the entry.
+const SETTINGS_WAIT: Duration = Duration::from_secs(1);
+
+const SETTINGS_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// What was changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// A mutation sent to a machine, by a user or a line
+    MachineMutation,
+    /// Machine identification written to the EEPROM of a device
+    DeviceIdentificationWrite,
+    /// A start, stop or emergency sequence of a production line
+    LineSequence,
+    /// A machine quarantined after a panic created again
+    MachineRecreate,
+}
+
+/// A setting the request changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettingChange {
+    /// JSON pointer into the state of the machine
+    pub pointer: String,
+    /// `None` if the setting didn't exist before
+    pub previous: Option<Value>,
+    /// `None` if the setting doesn't exist anymore
+    pub new: Option<Value>,
+}
+
+/// One entry of the audit trail
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Assigned when the entry is stored, 0 before
+    pub id: u64,
+    /// unix timestamp in ms
+    pub timestamp: u64,
+    /// Logged in user, empty while authentication is disabled
+    pub user: String,
+    /// Address of the REST client or the line that sent the request
+    pub client: String,
+    pub action: AuditAction,
+    pub machine: Option<MachineIdentificationUnique>,
+    /// Variant of the machine mutation or the line sequence
+    pub mutation: Option<String>,
+    /// Body of the request
+    pub value: Value,
+    /// Settings of the machine that changed, taken from the settings the machine publishes. They
+    /// may include changes from other sources published at the same time and are empty if the
+    /// machine published nothing new within a second.
+    pub changes: Vec<SettingChange>,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+impl AuditEntry {
+    pub fn new(user: String, client: String, action: AuditAction, value: Value) -> Self {
+        Self {
+            id: 0,
+            timestamp: unix_timestamp_ms(SystemTime::now()),
+            user,
+            client,
+            action,
+            machine: None,
+            mutation: None,
+            value,
+            changes: vec![],
+            success: true,
+            error: None,
+        }
+    }
+
+    pub const fn with_machine(mut self, machine: MachineIdentificationUnique) -> Self {
+        self.machine = Some(machine);
+        self
+    }
+
+    pub fn with_mutation(mut self, mutation: Option<&str>) -> Self {
+        self.mutation = mutation.map(str::to_string);
+        self
+    }
+
+    pub fn with_result<T, E: ToString>(mut self, result: &Result<T, E>) -> Self {
+        if let Err(e) = result {
+            self.success = false;
+            self.error = Some(e.to_string());
+        }
+        self
+    }
+}
+
+impl CsvRow for AuditEntry {
+    const CSV_HEADER: &'static str =
+        "id,timestamp_ms,user,client,action,machine,mutation,value,changes,success,error";
+
+    fn csv_row(&self) -> String {
+        let changes = self
+            .changes
+            .iter()
+            .map(|change| {
+                let value = |value: &Option<Value>| {
+                    value.as_ref().map(Value::to_string).unwrap_or_default()
+                };
+                format!(
+                    "{}: {} -> {}",
+                    change.pointer,
+                    value(&change.previous),
+                    value(&change.new)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("; ");
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            self.id,
+            self.timestamp,
+            csv_field(&self.user),
+            csv_field(&self.client),
+            serde_json::to_value(self.action)
+                .ok()
+                .and_then(|action| action.as_str().map(str::to_string))
+                .unwrap_or_default(),
+            self.machine
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default(),
+            csv_field(self.mutation.as_deref().unwrap_or_default()),
+            csv_field(&self.value.to_string()),
+            csv_field(&changes),
+            self.success,
+            csv_field(self.error.as_deref().unwrap_or_default()),
+        )
+    }
+}
+
+/// The settings that differ between two states, objects are compared field by field
+pub fn diff_settings(previous: Option<&Value>, new: Option<&Value>) -> Vec<SettingChange> {
+    let mut changes = vec![];
+    diff_values(String::new(), previous, new, &mut changes);
+    changes
+}
+
+fn diff_values(
+    pointer: String,
+    previous: Option<&Value>,
+    new: Option<&Value>,
+    changes: &mut Vec<SettingChange>,
+) {
+    match (previous, new) {
+        (Some(Value::Object(previous)), Some(Value::Object(new))) => {
+            let mut keys: Vec<&String> = previous.keys().chain(new.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let escaped = key.replace('~', "~0").replace('/', "~1");
+                diff_values(
+                    format!("{}/{}", pointer, escaped),
+                    previous.get(key),
+                    new.get(key),
+                    changes,
+                );
+            }
+        }
+        (previous, new) if previous != new => changes.push(SettingChange {
+            pointer,
+            previous: previous.cloned(),
+            new: new.cloned(),
+        }),
+        _ => {}
+    }
+}
+
+impl JsonlEntry for AuditEntry {
+    const NAME: &'static str = "audit entry";
+
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn assign_id(&mut self, id: u64) {
+        self.id = id;
+    }
+}
+
+/// Append only store of the audit trail
+pub type AuditLog = JsonlStore<AuditEntry>;
+
+static AUDIT_LOG: SharedJsonlStore<AuditEntry> =
+    SharedJsonlStore::new(|| AuditConfig::default().log_path);
+
+/// Sets the file of the audit log, `audit.log_path` in the server config, called once at startup
+pub fn set_audit_log_path(path: impl Into<PathBuf>) -> Result<()> {
+    AUDIT_LOG.set_path(path)
+}
+
+/// The log at the path set at startup, `None` if the file can't be read
+pub fn audit_log() -> Option<&'static Mutex<AuditLog>> {
+    AUDIT_LOG.get()
+}
+
+/// Entries waiting for the audit writer thread
+static AUDIT_WRITER: OnceLock<Sender<AuditEntry>> = OnceLock::new();
+
+/// Appends the entries in its own thread, the file writes would block the async threads
+fn audit_writer() -> &'static Sender<AuditEntry> {
+    AUDIT_WRITER.get_or_init(|| {
+        let (sender, receiver) = smol::channel::unbounded::<AuditEntry>();
+        let spawned = std::thread::Builder::new()
+            .name("audit_writer".to_string())
+            .spawn(move || {
+                while let Ok(entry) = receiver.recv_blocking() {
+                    append_entry(entry);
+                }
+            });
+        if let Err(e) = spawned {
+            tracing::error!("Failed to start the audit writer: {}", e);
+        }
+        sender
+    })
+}
+
+fn append_entry(entry: AuditEntry) {
+    let Some(log) = audit_log() else {
+        tracing::error!("Audit entry {:?} lost, no audit log", entry);
+        return;
+    };
+    let mut guard = log.lock().unwrap();
+    let result = guard.append(entry);
+    let path = guard.path().to_path_buf();
+    drop(guard);
+
+    if let Err(e) = result {
+        tracing::error!("Failed to store audit entry in {}: {}", path.display(), e);
+    }
+}
+
+/// Stores the entry in the audit trail, without blocking the caller
+pub fn record_audit(entry: AuditEntry) {
+    if let Err(e) = audit_writer().try_send(entry) {
+        tracing::error!("Audit entry {:?} lost, no audit writer", e.into_inner());
+    }
+}
+
+/// Records the entry with the settings its machine changed
+///
+/// `previous` are the settings before the request was sent. The new settings are the first ones
+/// the machine publishes that differ from them, within [`SETTINGS_WAIT`].
+pub fn record_settings_change(mut entry: AuditEntry, previous: Option<Value>) {
+    let Some(machine) = entry.machine.clone().filter(|_| entry.success) else {
+        record_audit(entry);
+        return;
+    };
+    smol::spawn(async move {
+        let mut waited = Duration::ZERO;
+        let mut new = get_latest_settings(&machine);
+        while new == previous && waited < SETTINGS_WAIT {
+            smol::Timer::after(SETTINGS_POLL_INTERVAL).await;
+            waited += SETTINGS_POLL_INTERVAL;
+            new = get_latest_settings(&machine);
+        }
+        entry.changes = diff_settings(previous.as_ref(), new.as_ref())
+            .into_iter()
+            .filter(|change| change.pointer != "/is_default_state")
+            .collect();
+        record_audit(entry);
+    })
+    .detach();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    fn entry(user: &str) -> AuditEntry {
+        AuditEntry::new(
+            user.to_string(),
+            "127.0.0.1:5000".to_string(),
+            AuditAction::MachineMutation,
+            json!({"SetTargetSpeed": 1.5}),
+        )
+        .with_mutation(Some("SetTargetSpeed"))
+    }
+
+    #[test]
+    fn test_diff_settings() {
+        let previous = json!({"puller": {"target_speed": 1.0, "forward": true}, "mode": "Hold"});
+        let new = json!({"puller": {"target_speed": 1.5, "forward": true}, "a/b": 1});
+        assert_eq!(
+            diff_settings(Some(&previous), Some(&new)),
+            [
+                SettingChange {
+                    pointer: "/a~1b".to_string(),
+                    previous: None,
+                    new: Some(json!(1)),
+                },
+                SettingChange {
+                    pointer: "/mode".to_string(),
+                    previous: Some(json!("Hold")),
+                    new: None,
+                },
+                SettingChange {
+                    pointer: "/puller/target_speed".to_string(),
+                    previous: Some(json!(1.0)),
+                    new: Some(json!(1.5)),
+                },
+            ]
+        );
+        assert!(diff_settings(Some(&previous), Some(&previous)).is_empty());
+    }
+
+    #[test]
+    fn test_csv_row() {
+        let mut entry = entry("ada").with_result(&Err::<(), _>("No machine, sorry"));
+        entry.changes = vec![SettingChange {
+            pointer: "/target_speed".to_string(),
+            previous: Some(json!(1.0)),
+            new: Some(json!(1.5)),
+        }];
+        assert_eq!(
+            entry.csv_row(),
+            format!(
+                "0,{},ada,127.0.0.1:5000,machine_mutation,,SetTargetSpeed,\
+                 \"{{\"\"SetTargetSpeed\"\":1.5}}\",/target_speed: 1.0 -> 1.5,false,\
+                 \"No machine, sorry\"",
+                entry.timestamp
+            )
+        );
+    }
+
+    #[test]
+    fn test_audit_log() {
+        let path =
+            std::env::temp_dir().join(format!("audit_log_test_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut log = AuditLog::open(&path).unwrap();
+        assert!(log.load().unwrap().is_empty());
+        let first = log.append(entry("ada")).unwrap();
+        assert_eq!(first.id, 1);
+
+        // a partially written line is skipped and ids continue after reopening
+        let file = OpenOptions::new().append(true).open(&path).unwrap();
+        (&file).write_all(b"{\"id\": 2, \"time").unwrap();
+        let mut log = AuditLog::open(&path).unwrap();
+        assert_eq!(log.append(entry("bob")).unwrap().id, 2);
+        assert_eq!(log.load().unwrap()[0], first);
+        assert_eq!(log.load().unwrap().len(), 2);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
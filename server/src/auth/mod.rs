@@ -0,0 +1,696 @@
+use anyhow::{Result, anyhow, bail};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::config::AuthConfig;
+
+mod password;
+
+pub use password::constant_time_eq;
+
+/// How long a login stays valid
+const SESSION_LIFETIME: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Failed logins in a row after which a user is locked out
+const MAX_FAILED_LOGINS: u32 = 5;
+
+/// How long a user is locked out after too many failed logins
+const LOGIN_LOCKOUT: Duration = Duration::from_secs(60);
+
+/// What a user may do, each role may do everything the roles before it may
+#[derive(
+    Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Sees machines, metrics and records
+    Viewer,
+    /// Runs machines and lines
+    Operator,
+    /// Tunes controllers and connects machines
+    Maintenance,
+    /// Writes devices and manages users
+    Admin,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Viewer => "viewer",
+            Self::Operator => "operator",
+            Self::Maintenance => "maintenance",
+            Self::Admin => "admin",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Role needed for each machine mutation by name, mutations that are not listed need admin
+const MUTATION_ROLES: &[(&str, Role)] = &[
+    // extruder
+    ("SetExtruderMode", Role::Operator),
+    ("SetInverterRotationDirection", Role::Operator),
+    ("SetInverterTargetPressure", Role::Operator),
+    ("SetInverterTargetRpm", Role::Operator),
+    ("SetInverterRegulation", Role::Operator),
+    ("SetFrontHeatingTargetTemperature", Role::Operator),
+    ("SetMiddleHeatingTemperature", Role::Operator),
+    ("SetBackHeatingTargetTemperature", Role::Operator),
+    ("SetNozzleHeatingTemperature", Role::Operator),
+    ("SetExtruderPressureLimit", Role::Operator),
+    ("SetExtruderPressureLimitIsEnabled", Role::Operator),
+    ("ReadInverterParameter", Role::Operator),
+    ("ReadInverterFaultHistory", Role::Operator),
+    ("SetHeatUpSettings", Role::Maintenance),
+    ("SetPressurePidSettings", Role::Maintenance),
+    ("SetTemperaturePidSettings", Role::Maintenance),
+    ("StartTemperatureAutotune", Role::Maintenance),
+    ("StopTemperatureAutotune", Role::Maintenance),
+    ("ResetInverter", Role::Maintenance),
+    ("WriteInverterParameter", Role::Admin),
+    ("ClearInverterParameters", Role::Admin),
+    ("BackupInverterParameters", Role::Admin),
+    ("RestoreInverterParameters", Role::Admin),
+    // winder
+    ("SetMode", Role::Operator),
+    ("EnableTraverseLaserpointer", Role::Operator),
+    ("GotoTraverseHome", Role::Operator),
+    ("GotoTraverseLimitInner", Role::Operator),
+    ("GotoTraverseLimitOuter", Role::Operator),
+    ("ResetSpoolProgress", Role::Operator),
+    ("SetLotNumber", Role::Operator),
+    ("SetPullerForward", Role::Operator),
+    ("SetPullerGearRatio", Role::Operator),
+    ("SetPullerRegulationMode", Role::Operator),
+    ("SetPullerTargetDiameter", Role::Operator),
+    ("SetPullerTargetSpeed", Role::Operator),
+    ("SetSpoolAutomaticAction", Role::Operator),
+    ("SetSpoolAutomaticRequiredMeters", Role::Operator),
+    ("SetSpoolAutomaticStopWhenFull", Role::Operator),
+    ("SetSpoolForward", Role::Operator),
+    ("SetSpoolMaxDiameter", Role::Operator),
+    ("SetSpoolMinMaxMaxSpeed", Role::Operator),
+    ("SetSpoolMinMaxMinSpeed", Role::Operator),
+    ("SetSpoolRegulationMode", Role::Operator),
+    ("SetTraverseCoreDiameter", Role::Operator),
+    ("SetTraverseEdgeCompensation", Role::Operator),
+    ("SetTraverseEndDwell", Role::Operator),
+    ("SetTraverseLimitInner", Role::Operator),
+    ("SetTraverseLimitOuter", Role::Operator),
+    ("SetTraversePadding", Role::Operator),
+    ("SetTraverseRibbonBreakingAmplitude", Role::Operator),
+    ("SetTraverseRibbonBreakingPeriod", Role::Operator),
+    ("SetTraverseStepLayers", Role::Operator),
+    ("SetTraverseStepSize", Role::Operator),
+    ("SetTraverseTaper", Role::Operator),
+    ("SetTraverseWindingPattern", Role::Operator),
+    ("SetSpoolAdaptiveAccelerationFactor", Role::Maintenance),
+    (
+        "SetSpoolAdaptiveDeaccelerationUrgencyMultiplier",
+        Role::Maintenance,
+    ),
+    ("SetSpoolAdaptiveMaxSpeedMultiplier", Role::Maintenance),
+    ("SetSpoolAdaptiveRadiusLearningRate", Role::Maintenance),
+    ("SetSpoolAdaptiveTensionTarget", Role::Maintenance),
+    ("ZeroTensionArmAngle", Role::Maintenance),
+    ("SetConnectedMachine", Role::Maintenance),
+    ("DisconnectMachine", Role::Maintenance),
+    // buffer
+    ("SetBufferMode", Role::Operator),
+    // aquapath
+    ("SetAquaPathMode", Role::Operator),
+    ("SetFrontFlow", Role::Operator),
+    ("SetBackFlow", Role::Operator),
+    ("SetFrontTemperature", Role::Operator),
+    ("SetBackTemperature", Role::Operator),
+    ("StartFrontAutotune", Role::Maintenance),
+    ("StartBackAutotune", Role::Maintenance),
+    ("StopFrontAutotune", Role::Maintenance),
+    ("StopBackAutotune", Role::Maintenance),
+    // laser
+    ("SetTargetDiameter", Role::Operator),
+    ("SetLowerTolerance", Role::Operator),
+    ("SetHigherTolerance", Role::Operator),
+    ("SetAxisTolerance", Role::Operator),
+    ("ResetSpoolStatistics", Role::Operator),
+    // power supply
+    ("SetPowerOnSequence", Role::Operator),
+    ("SetOutputEnabled", Role::Maintenance),
+    ("SetOutputVoltage", Role::Maintenance),
+    ("SetOutputCurrentLimit", Role::Maintenance),
+    ("SetOutputWarningThreshold", Role::Maintenance),
+    // test machines
+    ("SetLed", Role::Operator),
+    ("SetAllLeds", Role::Operator),
+    ("MoveMotor", Role::Operator),
+    ("SetFrequency1", Role::Operator),
+    ("SetFrequency2", Role::Operator),
+    ("SetFrequency3", Role::Operator),
+    ("measurement_rate_hz", Role::Operator),
+    ("SetOutput", Role::Maintenance),
+    ("SetAllOutputs", Role::Maintenance),
+];
+
+/// Variant of a machine mutation, `"Name"`, `{"Name": value}` or `{"action": "Name", "value": value}`
+pub fn mutation_name(mutation: &Value) -> Option<&str> {
+    match mutation {
+        Value::String(name) => Some(name.as_str()),
+        Value::Object(object) => object
+            .get("action")
+            .map_or_else(|| object.keys().next().map(String::as_str), Value::as_str),
+        _ => None,
+    }
+}
+
+/// Role needed for a machine mutation, admin for mutations that are not in [`MUTATION_ROLES`]
+pub fn mutation_role(mutation: &Value) -> Role {
+    mutation_name(mutation)
+        .and_then(|name| MUTATION_ROLES.iter().find(|(m, _)| *m == name))
+        .map_or(Role::Admin, |(_, role)| *role)
+}
+
+/// An account as stored in the users file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct User {
+    pub username: String,
+    pub role: Role,
+    /// Hex encoded random salt
+    pub salt: String,
+    /// `pbkdf2-sha256$<iterations>$<hex hash>` of the password with the salt
+    pub password_hash: String,
+}
+
+impl User {
+    pub fn new(username: String, password: &str, role: Role) -> Result<Self> {
+        let mut salt = [0u8; 16];
+        getrandom::fill(&mut salt).map_err(|e| anyhow!("No randomness for the salt: {}", e))?;
+        let salt = hex(&salt);
+        Ok(Self {
+            password_hash: password::hash_password(&salt, password),
+            username,
+            role,
+            salt,
+        })
+    }
+
+    pub fn verify_password(&self, password: &str) -> bool {
+        password::verify_password(&self.salt, password, &self.password_hash)
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Who sent a request
+#[derive(Serialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub username: String,
+    pub role: Role,
+}
+
+#[derive(Debug, Default)]
+struct FailedLogins {
+    count: u32,
+    locked_until: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct Session {
+    identity: Identity,
+    expires_at: Instant,
+}
+
+/// A successful login
+#[derive(Serialize, Debug, Clone)]
+pub struct Login {
+    pub token: String,
+    pub username: String,
+    pub role: Role,
+    /// unix timestamp in ms
+    pub expires_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthError {
+    /// No or an unknown token
+    Unauthenticated,
+    /// The user doesn't have the role
+    Forbidden { needed: Role },
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unauthenticated => write!(f, "Not logged in"),
+            Self::Forbidden { needed } => write!(f, "Needs the {} role", needed),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+/// User accounts and their sessions
+///
+/// Without any user nobody can log in. Only `auth.disabled` in the server config lets everyone
+/// act as admin, like before there were accounts.
+#[derive(Debug, Default)]
+pub struct Auth {
+    /// `None` keeps the users in memory only
+    path: Option<PathBuf>,
+    users: Vec<User>,
+    sessions: HashMap<String, Session>,
+    /// Failed logins of existing users since their last successful one
+    failed_logins: HashMap<String, FailedLogins>,
+    disabled: bool,
+}
+
+impl Auth {
+    /// A missing file means there are no users
+    pub fn load(path: &Path) -> Result<Self> {
+        let users: Vec<User> = match std::fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            users,
+            ..Default::default()
+        })
+    }
+
+    /// Everyone acts as admin without logging in
+    pub fn disabled() -> Self {
+        Self {
+            disabled: true,
+            ..Default::default()
+        }
+    }
+
+    /// The users at `auth.users_path`, an `admin` is created from `auth.admin_password` if there
+    /// are none
+    pub fn from_config(config: &AuthConfig) -> Self {
+        if config.disabled {
+            tracing::error!(
+                "Authentication is disabled by auth.disabled, every client acts as admin"
+            );
+            return Self::disabled();
+        }
+
+        let path = &config.users_path;
+        let mut auth = match Self::load(Path::new(path)) {
+            Ok(auth) => auth,
+            Err(e) => {
+                tracing::error!("Failed to load users from {}: {}", path, e);
+                return Self::default();
+            }
+        };
+
+        if auth.users.is_empty()
+            && let Some(password) = &config.admin_password
+        {
+            match auth.set_user("admin".to_string(), password.expose(), Role::Admin) {
+                Ok(()) => tracing::info!("Created the admin user in {}", path),
+                Err(e) => tracing::error!("Failed to create the admin user: {}", e),
+            }
+        }
+
+        if auth.users.is_empty() {
+            tracing::error!(
+                "No users in {}, nobody can log in, set auth.admin_password to create an admin",
+                path
+            );
+        } else {
+            tracing::info!("Loaded {} users from {}", auth.users.len(), path);
+        }
+        auth
+    }
+
+    pub const fn is_enabled(&self) -> bool {
+        !self.disabled
+    }
+
+    pub fn users(&self) -> &[User] {
+        &self.users
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        std::fs::write(path, serde_json::to_string_pretty(&self.users)?)?;
+        Ok(())
+    }
+
+    /// Creates the user or changes its password and role, its sessions end
+    pub fn set_user(&mut self, username: String, password: &str, role: Role) -> Result<()> {
+        if username.is_empty() {
+            bail!("The username is empty");
+        }
+        if password.is_empty() {
+            bail!("The password is empty");
+        }
+        let user = User::new(username, password, role)?;
+        self.sessions
+            .retain(|_, session| session.identity.username != user.username);
+        match self.users.iter_mut().find(|u| u.username == user.username) {
+            Some(existing) => *existing = user,
+            None => self.users.push(user),
+        }
+        self.save()
+    }
+
+    /// Removes the user and ends its sessions, the last admin can't be removed
+    pub fn remove_user(&mut self, username: &str) -> Result<()> {
+        let Some(index) = self.users.iter().position(|u| u.username == username) else {
+            bail!("No user {}", username);
+        };
+        let admins = self.users.iter().filter(|u| u.role == Role::Admin).count();
+        if self.users[index].role == Role::Admin && admins == 1 {
+            bail!("{} is the last admin", username);
+        }
+        self.users.remove(index);
+        self.sessions
+            .retain(|_, session| session.identity.username != username);
+        self.save()
+    }
+
+    /// Hashing the password takes long, call it off the async threads
+    ///
+    /// A user is locked out for [`LOGIN_LOCKOUT`] after [`MAX_FAILED_LOGINS`] failed logins.
+    pub fn login(&mut self, username: &str, password: &str, now: Instant) -> Result<Login> {
+        if let Some(locked_until) = self
+            .failed_logins
+            .get(username)
+            .and_then(|failed| failed.locked_until)
+            .filter(|locked_until| *locked_until > now)
+        {
+            bail!(
+                "Too many failed logins, try again in {} s",
+                (locked_until - now).as_secs().max(1)
+            );
+        }
+
+        let Some(index) = self.users.iter().position(|user| user.username == username) else {
+            bail!("Wrong username or password");
+        };
+        if !self.users[index].verify_password(password) {
+            let failed = self.failed_logins.entry(username.to_string()).or_default();
+            failed.count += 1;
+            if failed.count >= MAX_FAILED_LOGINS {
+                tracing::warn!("Locking out user {} after failed logins", username);
+                failed.count = 0;
+                failed.locked_until = Some(now + LOGIN_LOCKOUT);
+            }
+            bail!("Wrong username or password");
+        }
+        self.failed_logins.remove(username);
+
+        if password::needs_rehash(&self.users[index].password_hash) {
+            let user = &self.users[index];
+            let rehashed = User::new(user.username.clone(), password, user.role)?;
+            self.users[index] = rehashed;
+            if let Err(e) = self.save() {
+                tracing::error!(
+                    "Failed to save the new password hash of {}: {}",
+                    username,
+                    e
+                );
+            }
+        }
+        let user = &self.users[index];
+
+        let mut token = [0u8; 32];
+        getrandom::fill(&mut token).map_err(|e| anyhow!("No randomness for the token: {}", e))?;
+        let token = hex(&token);
+        let identity = Identity {
+            username: user.username.clone(),
+            role: user.role,
+        };
+
+        self.sessions.retain(|_, session| session.expires_at > now);
+        self.sessions.insert(
+            token.clone(),
+            Session {
+                identity: identity.clone(),
+                expires_at: now + SESSION_LIFETIME,
+            },
+        );
+
+        let expires_at = SystemTime::now() + SESSION_LIFETIME;
+        Ok(Login {
+            token,
+            username: identity.username,
+            role: identity.role,
+            expires_at: machines::production_record::unix_timestamp_ms(expires_at),
+        })
+    }
+
+    pub fn logout(&mut self, token: &str) {
+        self.sessions.remove(token);
+    }
+
+    /// Who the token belongs to, everyone is admin while authentication is disabled
+    pub fn authenticate(&self, token: Option<&str>, now: Instant) -> Option<Identity> {
+        if self.disabled {
+            return Some(Identity {
+                username: String::new(),
+                role: Role::Admin,
+            });
+        }
+        self.sessions
+            .get(token?)
+            .filter(|session| session.expires_at > now)
+            .map(|session| session.identity.clone())
+    }
+
+    /// The identity behind the token if it has the role
+    pub fn authorize(
+        &self,
+        token: Option<&str>,
+        role: Role,
+        now: Instant,
+    ) -> Result<Identity, AuthError> {
+        let identity = self
+            .authenticate(token, now)
+            .ok_or(AuthError::Unauthenticated)?;
+        check_role(&identity, role)?;
+        Ok(identity)
+    }
+}
+
+pub fn check_role(identity: &Identity, role: Role) -> Result<(), AuthError> {
+    if identity.role >= role {
+        Ok(())
+    } else {
+        Err(AuthError::Forbidden { needed: role })
+    }
+}
+
+/// The token of an `Authorization: Bearer <token>` header
+pub fn bearer_token(headers: &axum::http::HeaderMap) -> Option<&str> {
+    headers
+        .get(axum::http::header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_mutation_role() {
+        assert_eq!(mutation_role(&json!({"SetMode": "Wind"})), Role::Operator);
+        assert_eq!(mutation_role(&json!("ResetSpoolProgress")), Role::Operator);
+        assert_eq!(
+            mutation_role(&json!({"SetTemperaturePidSettings": {}})),
+            Role::Maintenance
+        );
+        assert_eq!(
+            mutation_role(&json!("StopFrontAutotune")),
+            Role::Maintenance
+        );
+        assert_eq!(
+            mutation_role(&json!({"WriteInverterParameter": {}})),
+            Role::Admin
+        );
+        assert_eq!(
+            mutation_role(&json!({"BackupInverterParameters": "a"})),
+            Role::Admin
+        );
+        // unknown mutations need admin
+        assert_eq!(mutation_role(&json!({"SetPidOutput": 1})), Role::Admin);
+        assert_eq!(mutation_role(&json!(1)), Role::Admin);
+        assert_eq!(
+            mutation_role(&json!({"action": "SetLed", "value": {"index": 0, "on": true}})),
+            Role::Operator
+        );
+    }
+
+    /// Names of the variants of a mutation schema
+    fn schema_mutations(schema: &Value) -> Vec<String> {
+        let variants = schema["oneOf"].as_array().cloned().unwrap_or_default();
+        let mut names = vec![];
+        for variant in variants.iter().chain(std::iter::once(schema)) {
+            let action = &variant["properties"]["action"];
+            let values = if action.is_object() {
+                // adjacently tagged
+                [&action["enum"], &action["const"]]
+            } else {
+                [&variant["enum"], &variant["const"]]
+            };
+            for value in values {
+                match value {
+                    Value::String(name) => names.push(name.clone()),
+                    Value::Array(values) => {
+                        names.extend(values.iter().filter_map(|v| v.as_str().map(str::to_string)))
+                    }
+                    _ => {}
+                }
+            }
+            if !action.is_object()
+                && let Some(required) = variant["required"].as_array()
+                && let [name] = required.as_slice()
+            {
+                names.extend(name.as_str().map(str::to_string));
+            }
+        }
+        names
+    }
+
+    #[test]
+    fn test_every_mutation_has_a_role() {
+        let schemas = machines::registry::MACHINE_REGISTRY.schemas();
+        let mut names = vec![];
+        for schema in &schemas {
+            names.extend(schema_mutations(
+                &serde_json::to_value(&schema.mutation).unwrap(),
+            ));
+        }
+        for name in ["SetTraverseLimitOuter", "ResetInverter", "SetLed"] {
+            assert!(names.iter().any(|n| n == name), "{} is missing", name);
+        }
+        for name in names {
+            assert!(
+                MUTATION_ROLES.iter().any(|(m, _)| *m == name),
+                "{} has no role",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_login_and_roles() {
+        let now = Instant::now();
+        let mut auth = Auth::default();
+
+        // without users nobody gets in, unless authentication is disabled
+        assert_eq!(
+            auth.authorize(None, Role::Viewer, now),
+            Err(AuthError::Unauthenticated)
+        );
+        assert_eq!(
+            Auth::disabled()
+                .authorize(None, Role::Admin, now)
+                .unwrap()
+                .role,
+            Role::Admin
+        );
+
+        auth.set_user("ada".to_string(), "secret", Role::Operator)
+            .unwrap();
+        assert_eq!(
+            auth.authorize(None, Role::Viewer, now),
+            Err(AuthError::Unauthenticated)
+        );
+        assert!(auth.login("ada", "wrong", now).is_err());
+
+        let login = auth.login("ada", "secret", now).unwrap();
+        let token = Some(login.token.as_str());
+        assert_eq!(
+            auth.authorize(token, Role::Operator, now).unwrap().username,
+            "ada"
+        );
+        assert_eq!(
+            auth.authorize(token, Role::Maintenance, now),
+            Err(AuthError::Forbidden {
+                needed: Role::Maintenance
+            })
+        );
+
+        // sessions expire
+        assert!(auth.authenticate(token, now + SESSION_LIFETIME).is_none());
+
+        auth.logout(&login.token);
+        assert!(auth.authenticate(token, now).is_none());
+    }
+
+    #[test]
+    fn test_failed_logins() {
+        let now = Instant::now();
+        let mut auth = Auth::default();
+        auth.set_user("ada".to_string(), "secret", Role::Operator)
+            .unwrap();
+
+        for _ in 0..MAX_FAILED_LOGINS {
+            assert!(auth.login("ada", "wrong", now).is_err());
+        }
+        // locked out, even with the right password
+        let error = auth.login("ada", "secret", now).unwrap_err();
+        assert!(error.to_string().starts_with("Too many failed logins"));
+
+        let later = now + LOGIN_LOCKOUT;
+        assert!(auth.login("ada", "secret", later).is_ok());
+        // a successful login resets the count
+        for _ in 1..MAX_FAILED_LOGINS {
+            assert!(auth.login("ada", "wrong", later).is_err());
+        }
+        assert!(auth.login("ada", "secret", later).is_ok());
+    }
+
+    #[test]
+    fn test_legacy_hash_is_replaced() {
+        let mut auth = Auth::default();
+        auth.users.push(User {
+            username: "ada".to_string(),
+            role: Role::Operator,
+            salt: "00ff".to_string(),
+            password_hash: password::legacy_hash("00ff", "secret"),
+        });
+        assert!(auth.login("ada", "secret", Instant::now()).is_ok());
+        assert!(auth.users()[0].password_hash.starts_with("pbkdf2-sha256$"));
+        assert!(auth.login("ada", "secret", Instant::now()).is_ok());
+    }
+
+    #[test]
+    fn test_users_file() {
+        let path = std::env::temp_dir().join(format!("users_test_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut auth = Auth::load(&path).unwrap();
+        assert!(auth.is_enabled());
+        auth.set_user("root".to_string(), "pw", Role::Admin)
+            .unwrap();
+        auth.set_user("op".to_string(), "pw", Role::Operator)
+            .unwrap();
+        assert!(auth.remove_user("root").is_err());
+
+        let mut auth = Auth::load(&path).unwrap();
+        assert_eq!(auth.users().len(), 2);
+        assert!(auth.login("op", "pw", Instant::now()).is_ok());
+        auth.remove_user("op").unwrap();
+        assert_eq!(Auth::load(&path).unwrap().users().len(), 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+use super::hex;
+use sha2::{Digest, Sha256};
+
+/// Prefix of the hashes, followed by `$<iterations>$<hex hash>`
+const SCHEME: &str = "pbkdf2-sha256";
+
+/// PBKDF2 iterations of new hashes, as recommended by OWASP for HMAC-SHA256
+#[cfg(not(test))]
+const ITERATIONS: u32 = 600_000;
+/// The iterations are stored with the hash, so the tests can hash fast
+#[cfg(test)]
+const ITERATIONS: u32 = 1_000;
+
+/// Rounds of the stretched SHA-256 the hashes were created with before PBKDF2
+const LEGACY_ROUNDS: u32 = 10_000;
+
+const BLOCK_SIZE: usize = 64;
+
+/// Hashes the password with PBKDF2-HMAC-SHA256, `salt` is the hex encoded salt of the user
+pub fn hash_password(salt: &str, password: &str) -> String {
+    let hash = pbkdf2_sha256(password.as_bytes(), salt.as_bytes(), ITERATIONS);
+    format!("{}${}${}", SCHEME, ITERATIONS, hex(&hash))
+}
+
+pub fn verify_password(salt: &str, password: &str, hash: &str) -> bool {
+    let expected = match parse(hash) {
+        Some((iterations, _)) => {
+            let hash = pbkdf2_sha256(password.as_bytes(), salt.as_bytes(), iterations);
+            format!("{}${}${}", SCHEME, iterations, hex(&hash))
+        }
+        None => legacy_hash(salt, password),
+    };
+    constant_time_eq(expected.as_bytes(), hash.as_bytes())
+}
+
+/// Hashes of the legacy scheme or with fewer iterations are replaced on the next login
+pub fn needs_rehash(hash: &str) -> bool {
+    parse(hash).is_none_or(|(iterations, _)| iterations < ITERATIONS)
+}
+
+/// Iterations and hex hash of a PBKDF2 hash, `None` for a legacy hash
+fn parse(hash: &str) -> Option<(u32, &str)> {
+    let mut parts = hash.split('$');
+    if parts.next()? != SCHEME {
+        return None;
+    }
+    let iterations = parts.next()?.parse().ok().filter(|i| *i > 0)?;
+    Some((iterations, parts.next()?))
+}
+
+pub(super) fn legacy_hash(salt: &str, password: &str) -> String {
+    let mut hash = Sha256::new()
+        .chain_update(salt)
+        .chain_update(password)
+        .finalize();
+    for _ in 1..LEGACY_ROUNDS {
+        hash = Sha256::new()
+            .chain_update(hash)
+            .chain_update(salt)
+            .chain_update(password)
+            .finalize();
+    }
+    hex(&hash)
+}
+
+/// PBKDF2 (RFC 8018) with HMAC-SHA256 and a single block of output
+fn pbkdf2_sha256(password: &[u8], salt: &[u8], iterations: u32) -> [u8; 32] {
+    let mut key = [0u8; BLOCK_SIZE];
+    if password.len() > BLOCK_SIZE {
+        key[..32].copy_from_slice(&Sha256::digest(password));
+    } else {
+        key[..password.len()].copy_from_slice(password);
+    }
+    // the padded keys are hashed once, every iteration continues from them
+    let inner = Sha256::new().chain_update(key.map(|byte| byte ^ 0x36));
+    let outer = Sha256::new().chain_update(key.map(|byte| byte ^ 0x5c));
+    let hmac = |message: &[&[u8]]| -> [u8; 32] {
+        let mut hasher = inner.clone();
+        for part in message {
+            hasher.update(part);
+        }
+        outer
+            .clone()
+            .chain_update(hasher.finalize())
+            .finalize()
+            .into()
+    };
+
+    let mut block = hmac(&[salt, &1u32.to_be_bytes()]);
+    let mut result = block;
+    for _ in 1..iterations {
+        block = hmac(&[&block]);
+        for (result, byte) in result.iter_mut().zip(block) {
+            *result ^= byte;
+        }
+    }
+    result
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pbkdf2_vectors() {
+        // PBKDF2-HMAC-SHA256 test vectors for P = "password" and S = "salt"
+        let vectors = [
+            (
+                1,
+                "120fb6cffcf8b32c43e7225256c4f837a86548c92ccc35480805987cb70be17b",
+            ),
+            (
+                2,
+                "ae4d0c95af6b46d32d0adff928f06dd02a303f8ef3c251dfd6e2d85a95474c43",
+            ),
+            (
+                4096,
+                "c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134a",
+            ),
+        ];
+        for (iterations, expected) in vectors {
+            assert_eq!(
+                hex(&pbkdf2_sha256(b"password", b"salt", iterations)),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_verify_and_rehash() {
+        let hash = hash_password("00ff", "secret");
+        assert!(hash.starts_with("pbkdf2-sha256$"));
+        assert!(verify_password("00ff", "secret", &hash));
+        assert!(!verify_password("00ff", "Secret", &hash));
+        assert!(!verify_password("00fe", "secret", &hash));
+        assert!(!needs_rehash(&hash));
+
+        // users of the legacy scheme can still log in
+        let legacy = legacy_hash("00ff", "secret");
+        assert!(verify_password("00ff", "secret", &legacy));
+        assert!(!verify_password("00ff", "wrong", &legacy));
+        assert!(needs_rehash(&legacy));
+        assert!(needs_rehash("pbkdf2-sha256$10$00"));
+    }
+}
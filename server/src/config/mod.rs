@@ -0,0 +1,833 @@
+use crate::ethercat::config::{MAX_FRAMES, MAX_SUBDEVICES, PDI_LEN};
+use anyhow::{Context, Result, anyhow, bail};
+use control_core::ethernet::modbus_tcp_discovery::ModbusTcpDiscoveryConfig;
+use machines::ShutdownPolicy;
+use machines::frequency_inverter::FrequencyInverterConfig;
+use machines::machine_identification::MachineIdentificationUnique;
+use machines::wago_power::WagoPowerConfig;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+use toml::{Table, Value};
+
+/// Path of the TOML file with the [`ServerConfig`]
+pub const CONFIG_ENV: &str = "QITECH_CONFIG";
+
+const DEFAULT_CONFIG_PATH: &str = "server.toml";
+
+/// Environment variables that override a key of the config file
+const ENV_OVERRIDES: &[(&str, &str)] = &[
+    ("QITECH_CYCLE_TARGET_US", "rt_loop.cycle_target_us"),
+    ("QITECH_RT_CORE", "rt_loop.core"),
+    ("QITECH_API_PORT", "api.port"),
+    ("QITECH_LOCAL_ONLY", "api.local_only"),
+    ("QITECH_METRICS_TOKEN", "api.metrics_token"),
+    ("QITECH_AUTH_DISABLED", "auth.disabled"),
+    ("QITECH_USERS", "auth.users_path"),
+    ("QITECH_ADMIN_PASSWORD", "auth.admin_password"),
+    ("QITECH_AUDIT_LOG", "audit.log_path"),
+    ("QITECH_LINES", "lines.path"),
+    ("QITECH_PRODUCTION_RECORDS", "production.records_path"),
+    ("QITECH_INVERTER_BACKUPS", "inverter_backups.dir"),
+    ("QITECH_LASER_CONFIG", "laser.config_path"),
+    ("QITECH_EXTRUDER_INVERTER", "extruder_inverter.inverter"),
+    (
+        "QITECH_EXTRUDER_INVERTER_SLAVE_ID",
+        "extruder_inverter.slave_id",
+    ),
+    ("QITECH_MODBUS_TCP_SUBNETS", "modbus_tcp.subnets"),
+    ("QITECH_MODBUS_TCP_ADDRESSES", "modbus_tcp.addresses"),
+    (
+        "QITECH_MODBUS_TCP_SCAN_INTERFACES",
+        "modbus_tcp.scan_interfaces",
+    ),
+    ("QITECH_MODBUS_TCP_INTERVAL", "modbus_tcp.interval_s"),
+    ("QITECH_RUNTIME_METRICS_CSV", "runtime_metrics.csv_path"),
+    (
+        "QITECH_RUNTIME_METRICS_INTERVAL_MS",
+        "runtime_metrics.interval_ms",
+    ),
+    ("QITECH_ETHERCAT_INTERFACE", "ethercat.interface"),
+    ("QITECH_SHUTDOWN_DEADLINE_MS", "shutdown.deadline_ms"),
+    ("QITECH_WAGO_POWER_OUTPUTS", "wago_power.outputs"),
+];
+
+/// Keys whose overrides are taken as they are, a password may look like a number
+const VERBATIM_KEYS: &[&str] = &[
+    "api.metrics_token",
+    "auth.admin_password",
+    "auth.users_path",
+    "audit.log_path",
+    "lines.path",
+    "production.records_path",
+    "inverter_backups.dir",
+    "laser.config_path",
+];
+
+const MIN_CYCLE_TARGET_US: u64 = 100;
+const MAX_CYCLE_TARGET_US: u64 = 100_000;
+const MIN_RUNTIME_METRICS_INTERVAL_MS: u64 = 100;
+const MIN_SHUTDOWN_DEADLINE_MS: u64 = 100;
+const MAX_SHUTDOWN_DEADLINE_MS: u64 = 60_000;
+const MAX_OVERRUN_LOG_LEN: usize = 100_000;
+
+/// Runtime parameters of the server
+///
+/// Read from `QITECH_CONFIG` (default `server.toml`), a missing file means all defaults.
+/// Single keys can be overridden with `QITECH_*` environment variables and those again
+/// with `--section.key=value` command line arguments.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub rt_loop: RtLoopConfig,
+    pub api: ApiConfig,
+    pub auth: AuthConfig,
+    pub runtime_metrics: RuntimeMetricsFileConfig,
+    pub ethercat: EthercatConfig,
+    pub shutdown: ShutdownConfig,
+    pub cycle_budget: CycleBudgetConfig,
+    pub wago_power: WagoPowerConfig,
+    pub audit: AuditConfig,
+    pub lines: LinesConfig,
+    pub production: ProductionConfig,
+    pub inverter_backups: InverterBackupsConfig,
+    pub laser: LaserFileConfig,
+    pub extruder_inverter: FrequencyInverterConfig,
+    pub modbus_tcp: ModbusTcpConfig,
+}
+
+/// A password or token, left out of the logs
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(transparent)]
+pub struct Secret(pub String);
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(..)")
+    }
+}
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct RtLoopConfig {
+    /// Target time of one cycle of the real-time loop in µs
+    pub cycle_target_us: u64,
+    /// CPU core the real-time loop is pinned to
+    pub core: usize,
+}
+
+impl Default for RtLoopConfig {
+    fn default() -> Self {
+        Self {
+            cycle_target_us: 700,
+            core: 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiConfig {
+    pub port: u16,
+    /// Only listen on the loopback interface, for a panel PC that runs the UI itself
+    #[serde(deserialize_with = "deserialize_flag")]
+    pub local_only: bool,
+    /// Bearer token a Prometheus scraper sends to `/metrics`, which is open to everyone without
+    /// it. Never served by the config endpoint.
+    #[serde(skip_serializing)]
+    pub metrics_token: Option<Secret>,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            port: 3001,
+            local_only: false,
+            metrics_token: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Every client acts as admin without logging in, only for machines on an isolated network
+    #[serde(deserialize_with = "deserialize_flag")]
+    pub disabled: bool,
+    /// JSON file with the user accounts
+    pub users_path: String,
+    /// Password of the `admin` created if there are no users. Never served by the config
+    /// endpoint.
+    #[serde(skip_serializing)]
+    pub admin_password: Option<Secret>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            disabled: false,
+            users_path: "users.json".to_string(),
+            admin_password: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditConfig {
+    /// File the audit trail is stored in, one JSON entry per line
+    pub log_path: String,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            log_path: "audit_log.jsonl".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinesConfig {
+    /// JSON file with the production lines
+    pub path: String,
+}
+
+impl Default for LinesConfig {
+    fn default() -> Self {
+        Self {
+            path: "lines.json".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProductionConfig {
+    /// File the records of the finished spools are stored in, one JSON record per line
+    pub records_path: String,
+}
+
+impl Default for ProductionConfig {
+    fn default() -> Self {
+        Self {
+            records_path: "production_records.jsonl".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct InverterBackupsConfig {
+    /// Directory the inverter parameter backups are stored in
+    pub dir: String,
+}
+
+impl Default for InverterBackupsConfig {
+    fn default() -> Self {
+        Self {
+            dir: "inverter_backups".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct LaserFileConfig {
+    /// JSON file with the serial settings and gauges of the lasers, the defaults without it
+    pub config_path: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModbusTcpConfig {
+    /// Scan the /24 around the address of every ethernet interface
+    #[serde(deserialize_with = "deserialize_flag")]
+    pub scan_interfaces: bool,
+    /// Subnets to scan, for example "192.168.10.0/24"
+    #[serde(deserialize_with = "deserialize_list")]
+    pub subnets: Vec<String>,
+    /// Device addresses, the port defaults to 502, for example "192.168.10.5" or "10.0.0.2:5020"
+    #[serde(deserialize_with = "deserialize_list")]
+    pub addresses: Vec<String>,
+    /// Time in s between two discovery runs
+    pub interval_s: u64,
+}
+
+impl Default for ModbusTcpConfig {
+    fn default() -> Self {
+        Self {
+            scan_interfaces: true,
+            subnets: vec![],
+            addresses: vec![],
+            interval_s: 5,
+        }
+    }
+}
+
+impl ModbusTcpConfig {
+    pub fn discovery(&self) -> Result<ModbusTcpDiscoveryConfig> {
+        ModbusTcpDiscoveryConfig::new(
+            self.scan_interfaces,
+            &self.subnets,
+            &self.addresses,
+            Duration::from_secs(self.interval_s),
+        )
+        .context("Invalid modbus_tcp")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuntimeMetricsFileConfig {
+    pub csv_path: String,
+    pub interval_ms: u64,
+}
+
+impl Default for RuntimeMetricsFileConfig {
+    fn default() -> Self {
+        Self {
+            csv_path: "runtime_metrics.csv".to_string(),
+            interval_ms: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct EthercatConfig {
+    /// Network interface of the EtherCAT bus, probed when missing
+    pub interface: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownConfig {
+    /// Time in ms the machines get to reach their safe state before the server exits anyway
+    pub deadline_ms: u64,
+    /// Switch off the outputs of the power supplies on shutdown
+    #[serde(deserialize_with = "deserialize_flag")]
+    pub power_supplies_off: bool,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            deadline_ms: 3000,
+            power_supplies_off: false,
+        }
+    }
+}
+
+impl ShutdownConfig {
+    pub const fn deadline(&self) -> Duration {
+        Duration::from_millis(self.deadline_ms)
+    }
+
+    pub const fn policy(&self) -> ShutdownPolicy {
+        ShutdownPolicy {
+            power_off: self.power_supplies_off,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct CycleBudgetConfig {
+    /// Time in µs one `act` call of a machine may take
+    pub machine_act_us: u64,
+    /// Time in µs the input post processing or output pre processing of one device may take
+    pub device_us: u64,
+    /// `act` budgets in µs of single machines by `vendor/machine/serial`
+    pub machines: BTreeMap<String, u64>,
+    /// Number of overruns kept in the overrun log
+    pub overrun_log_len: usize,
+}
+
+impl Default for CycleBudgetConfig {
+    fn default() -> Self {
+        Self {
+            machine_act_us: 100,
+            device_us: 20,
+            machines: BTreeMap::new(),
+            overrun_log_len: 1000,
+        }
+    }
+}
+
+impl CycleBudgetConfig {
+    pub fn machine_act_budget(&self, machine: &MachineIdentificationUnique) -> Duration {
+        let budget_us = self
+            .machines
+            .get(&machine.to_string())
+            .copied()
+            .unwrap_or(self.machine_act_us);
+        Duration::from_micros(budget_us)
+    }
+
+    pub const fn device_budget(&self) -> Duration {
+        Duration::from_micros(self.device_us)
+    }
+}
+
+/// Sizes the EtherCAT buffers are compiled with, reported next to the config
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EthercatLimits {
+    pub max_subdevices: usize,
+    pub pdi_len: usize,
+    pub max_frames: usize,
+}
+
+impl EthercatLimits {
+    pub const fn compiled() -> Self {
+        Self {
+            max_subdevices: MAX_SUBDEVICES,
+            pdi_len: PDI_LEN,
+            max_frames: MAX_FRAMES,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Reads the config file, applies the overrides and validates the result
+    ///
+    /// `args` are the command line arguments without the program name. `--config=<path>`
+    /// takes precedence over `QITECH_CONFIG`.
+    pub fn load(args: &[String]) -> Result<Self> {
+        let mut path = None;
+        let mut cli_overrides = vec![];
+        for arg in args {
+            let Some((key, value)) = arg.strip_prefix("--").and_then(|arg| arg.split_once('='))
+            else {
+                bail!("Invalid argument {:?}, expected --key=value", arg);
+            };
+            if key == "config" {
+                path = Some(value.to_string());
+            } else {
+                cli_overrides.push((key, value));
+            }
+        }
+        let path = path
+            .or_else(|| std::env::var(CONFIG_ENV).ok())
+            .unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_string());
+
+        let mut table = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents
+                .parse::<Table>()
+                .with_context(|| format!("Failed to parse {}", path))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Table::new(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path)),
+        };
+
+        for (env, key) in ENV_OVERRIDES {
+            if let Ok(value) = std::env::var(env) {
+                set_key(&mut table, key, &value).with_context(|| format!("Invalid {}", env))?;
+            }
+        }
+        for (key, value) in cli_overrides {
+            set_key(&mut table, key, value).with_context(|| format!("Invalid --{}", key))?;
+        }
+
+        Self::from_table(table)
+    }
+
+    fn from_table(table: Table) -> Result<Self> {
+        let config: Self = Value::Table(table).try_into()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !(MIN_CYCLE_TARGET_US..=MAX_CYCLE_TARGET_US).contains(&self.rt_loop.cycle_target_us) {
+            bail!(
+                "rt_loop.cycle_target_us must be between {} and {}",
+                MIN_CYCLE_TARGET_US,
+                MAX_CYCLE_TARGET_US
+            );
+        }
+        if let Ok(cores) = std::thread::available_parallelism()
+            && self.rt_loop.core >= cores.get()
+        {
+            tracing::warn!(
+                "rt_loop.core {} does not exist, only {} cores are available",
+                self.rt_loop.core,
+                cores
+            );
+        }
+        if self.api.port == 0 {
+            bail!("api.port must not be 0");
+        }
+        if self
+            .api
+            .metrics_token
+            .as_ref()
+            .is_some_and(|token| token.expose().trim().is_empty())
+        {
+            bail!("api.metrics_token must not be empty");
+        }
+        if self
+            .auth
+            .admin_password
+            .as_ref()
+            .is_some_and(|password| password.expose().is_empty())
+        {
+            bail!("auth.admin_password must not be empty");
+        }
+        for (key, path) in [
+            ("auth.users_path", &self.auth.users_path),
+            ("audit.log_path", &self.audit.log_path),
+            ("lines.path", &self.lines.path),
+            ("production.records_path", &self.production.records_path),
+            ("inverter_backups.dir", &self.inverter_backups.dir),
+        ]
+        .into_iter()
+        .chain(
+            self.laser
+                .config_path
+                .iter()
+                .map(|path| ("laser.config_path", path)),
+        ) {
+            if path.trim().is_empty() {
+                bail!("{} must not be empty", key);
+            }
+        }
+        if self.runtime_metrics.csv_path.trim().is_empty() {
+            bail!("runtime_metrics.csv_path must not be empty");
+        }
+        if self.runtime_metrics.interval_ms < MIN_RUNTIME_METRICS_INTERVAL_MS {
+            bail!(
+                "runtime_metrics.interval_ms must be at least {}",
+                MIN_RUNTIME_METRICS_INTERVAL_MS
+            );
+        }
+        if self
+            .ethercat
+            .interface
+            .as_ref()
+            .is_some_and(|interface| interface.trim().is_empty())
+        {
+            bail!("ethercat.interface must not be empty, leave it out to probe");
+        }
+        if !(MIN_SHUTDOWN_DEADLINE_MS..=MAX_SHUTDOWN_DEADLINE_MS)
+            .contains(&self.shutdown.deadline_ms)
+        {
+            bail!(
+                "shutdown.deadline_ms must be between {} and {}",
+                MIN_SHUTDOWN_DEADLINE_MS,
+                MAX_SHUTDOWN_DEADLINE_MS
+            );
+        }
+        if self.cycle_budget.machine_act_us == 0 || self.cycle_budget.device_us == 0 {
+            bail!("cycle_budget.machine_act_us and cycle_budget.device_us must not be 0");
+        }
+        for (machine, budget_us) in &self.cycle_budget.machines {
+            let parts: Vec<&str> = machine.split('/').collect();
+            if parts.len() != 3 || parts.iter().any(|part| part.parse::<u16>().is_err()) {
+                bail!(
+                    "cycle_budget.machines key {:?} is not vendor/machine/serial",
+                    machine
+                );
+            }
+            if *budget_us == 0 {
+                bail!("cycle_budget.machines.\"{}\" must not be 0", machine);
+            }
+        }
+        if !(1..=MAX_OVERRUN_LOG_LEN).contains(&self.cycle_budget.overrun_log_len) {
+            bail!(
+                "cycle_budget.overrun_log_len must be between 1 and {}",
+                MAX_OVERRUN_LOG_LEN
+            );
+        }
+        self.wago_power.validate()?;
+        self.extruder_inverter.validate()?;
+        self.modbus_tcp.discovery()?;
+        Ok(())
+    }
+
+    pub const fn cycle_target(&self) -> Duration {
+        Duration::from_micros(self.rt_loop.cycle_target_us)
+    }
+
+    pub const fn runtime_metrics_interval(&self) -> Duration {
+        Duration::from_millis(self.runtime_metrics.interval_ms)
+    }
+
+    pub fn bind_address(&self) -> String {
+        let host = if self.api.local_only {
+            "127.0.0.1"
+        } else {
+            "0.0.0.0"
+        };
+        format!("{}:{}", host, self.api.port)
+    }
+}
+
+/// A bool that also takes `1` and `0`, like the `QITECH_LOCAL_ONLY` environment variable always did
+fn deserialize_flag<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Flag {
+        Bool(bool),
+        Int(i64),
+        Str(String),
+    }
+    match Flag::deserialize(deserializer)? {
+        Flag::Bool(flag) => Ok(flag),
+        Flag::Int(flag) => Ok(flag != 0),
+        Flag::Str(flag) => Ok(flag.eq_ignore_ascii_case("true")),
+    }
+}
+
+/// A list of strings, or a comma separated string for environment variables
+fn deserialize_list<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum List {
+        List(Vec<String>),
+        Str(String),
+    }
+    match List::deserialize(deserializer)? {
+        List::List(list) => Ok(list),
+        List::Str(list) => Ok(list
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect()),
+    }
+}
+
+/// Sets the dotted `key` to `value`, which is parsed as a TOML value and taken as a string
+/// if it isn't one or the key is in [`VERBATIM_KEYS`]
+fn set_key(table: &mut Table, key: &str, value: &str) -> Result<()> {
+    let value = Some(value)
+        .filter(|_| !VERBATIM_KEYS.contains(&key))
+        .and_then(|value| format!("value = {}", value).parse::<Table>().ok())
+        .and_then(|mut parsed| parsed.remove("value"))
+        .unwrap_or_else(|| Value::String(value.to_string()));
+
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let last = parts.pop().filter(|last| !last.is_empty());
+    let last = last.ok_or_else(|| anyhow!("Empty key"))?;
+    let mut table = table;
+    for part in parts {
+        table = table
+            .entry(part)
+            .or_insert_with(|| Value::Table(Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| anyhow!("{} is not a section", part))?;
+    }
+    table.insert(last.to_string(), value);
+    Ok(())
+}
+
+/// Loads the config at startup, exits if it is invalid
+pub fn load_server_config() -> ServerConfig {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match ServerConfig::load(&args) {
+        Ok(config) => {
+            tracing::info!("Server config {:?}", config);
+            config
+        }
+        Err(e) => {
+            tracing::error!("Invalid server config: {:#}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(contents: &str) -> Result<ServerConfig> {
+        ServerConfig::from_table(contents.parse::<Table>()?)
+    }
+
+    #[test]
+    fn test_defaults_and_file() {
+        assert_eq!(parse("").unwrap(), ServerConfig::default());
+        assert_eq!(ServerConfig::default().bind_address(), "0.0.0.0:3001");
+
+        let config = parse(
+            r#"
+            [rt_loop]
+            cycle_target_us = 500
+
+            [api]
+            port = 3002
+            local_only = true
+            metrics_token = "scrape"
+
+            [auth]
+            disabled = true
+
+            [ethercat]
+            interface = "enp1s0"
+
+            [shutdown]
+            deadline_ms = 5000
+            power_supplies_off = true
+
+            [cycle_budget]
+            machine_act_us = 50
+            machines = { "1/2/3" = 200 }
+
+            [wago_power]
+            outputs = 4
+
+            [wago_power.registers]
+            output_stride = 0x20
+
+            [audit]
+            log_path = "/var/lib/qitech/audit_log.jsonl"
+
+            [laser]
+            config_path = "laser.json"
+
+            [extruder_inverter]
+            inverter = "DeltaVfdE"
+            slave_id = 3
+
+            [modbus_tcp]
+            scan_interfaces = false
+            subnets = ["192.168.10.0/24"]
+            interval_s = 10
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.cycle_target(), Duration::from_micros(500));
+        assert_eq!(config.rt_loop.core, 2);
+        assert_eq!(config.bind_address(), "127.0.0.1:3002");
+        assert_eq!(
+            config.api.metrics_token.as_ref().map(Secret::expose),
+            Some("scrape")
+        );
+        assert!(config.auth.disabled);
+        assert!(!ServerConfig::default().auth.disabled);
+        assert_eq!(config.ethercat.interface.as_deref(), Some("enp1s0"));
+        assert_eq!(config.shutdown.deadline(), Duration::from_secs(5));
+        assert!(config.shutdown.policy().power_off);
+
+        let machine = |serial| MachineIdentificationUnique {
+            machine_identification: machines::machine_identification::MachineIdentification {
+                vendor: 1,
+                machine: 2,
+            },
+            serial,
+        };
+        assert_eq!(
+            config.cycle_budget.machine_act_budget(&machine(3)),
+            Duration::from_micros(200)
+        );
+        assert_eq!(
+            config.cycle_budget.machine_act_budget(&machine(4)),
+            Duration::from_micros(50)
+        );
+        assert_eq!(
+            config.cycle_budget.device_budget(),
+            Duration::from_micros(20)
+        );
+        assert_eq!(config.audit.log_path, "/var/lib/qitech/audit_log.jsonl");
+        assert_eq!(config.lines.path, "lines.json");
+        assert_eq!(config.laser.config_path.as_deref(), Some("laser.json"));
+        assert_eq!(config.extruder_inverter.slave_id, 3);
+        let discovery = config.modbus_tcp.discovery().unwrap();
+        assert!(!discovery.scan_interfaces);
+        assert_eq!(discovery.subnets.len(), 1);
+        assert_eq!(discovery.interval, Duration::from_secs(10));
+        assert_eq!(config.wago_power.outputs, 4);
+        assert_eq!(
+            config.wago_power.registers.output_measurement_register(1),
+            0x0520
+        );
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(parse("[rt_loop]\ncycle_target_us = 10").is_err());
+        assert!(parse("[api]\nport = 0").is_err());
+        assert!(parse("[api]\nmetrics_token = \" \"").is_err());
+        assert!(parse("[runtime_metrics]\ninterval_ms = 1").is_err());
+        assert!(parse("[ethercat]\ninterface = \"\"").is_err());
+        assert!(parse("[shutdown]\ndeadline_ms = 0").is_err());
+        assert!(parse("[shutdown]\ndeadline_ms = 600000").is_err());
+        assert!(parse("[cycle_budget]\ndevice_us = 0").is_err());
+        assert!(parse("[cycle_budget]\nmachines = { \"1/2\" = 100 }").is_err());
+        assert!(parse("[cycle_budget]\nmachines = { \"1/2/x\" = 100 }").is_err());
+        assert!(parse("[cycle_budget]\noverrun_log_len = 0").is_err());
+        assert!(parse("[wago_power]\noutputs = 0").is_err());
+        assert!(parse("[wago_power.registers]\noutput_stride = 1").is_err());
+        assert!(parse("[auth]\nusers_path = \"\"").is_err());
+        assert!(parse("[auth]\nadmin_password = \"\"").is_err());
+        assert!(parse("[lines]\npath = \" \"").is_err());
+        assert!(parse("[extruder_inverter]\nslave_id = 0").is_err());
+        assert!(parse("[extruder_inverter]\ninverter = \"abb\"").is_err());
+        assert!(parse("[modbus_tcp]\nsubnets = [\"10.0.0.0/8\"]").is_err());
+        assert!(parse("[modbus_tcp]\naddresses = [\"wago\"]").is_err());
+        assert!(parse("[modbus_tcp]\ninterval_s = 0").is_err());
+        // typos are not silently ignored
+        assert!(parse("[api]\nprot = 3002").is_err());
+        assert!(parse("[api]\nport = \"3002\"").is_err());
+    }
+
+    #[test]
+    fn test_overrides() {
+        let path = std::env::temp_dir().join(format!("server_config_{}.toml", std::process::id()));
+        std::fs::write(&path, "[api]\nport = 3002\n[rt_loop]\ncore = 1").unwrap();
+
+        let config = ServerConfig::load(&[
+            format!("--config={}", path.display()),
+            "--api.local_only=true".to_string(),
+            "--auth.disabled=1".to_string(),
+            "--auth.admin_password=1234".to_string(),
+            "--api.metrics_token=true".to_string(),
+            "--extruder_inverter.inverter=delta".to_string(),
+            "--extruder_inverter.slave_id=12".to_string(),
+            "--modbus_tcp.addresses=10.0.0.2:5020, 10.0.0.3".to_string(),
+            "--modbus_tcp.scan_interfaces=false".to_string(),
+            "--ethercat.interface=eth0".to_string(),
+            "--rt_loop.cycle_target_us=1000".to_string(),
+        ])
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.api.port, 3002);
+        assert!(config.api.local_only);
+        assert!(config.auth.disabled);
+        // secrets are taken as they are and kept out of the logs
+        assert_eq!(
+            config.auth.admin_password.as_ref().map(Secret::expose),
+            Some("1234")
+        );
+        assert_eq!(
+            config.api.metrics_token.as_ref().map(Secret::expose),
+            Some("true")
+        );
+        assert!(!format!("{:?}", config).contains("1234"));
+        assert_eq!(
+            config.extruder_inverter.inverter,
+            machines::frequency_inverter::FrequencyInverterType::DeltaVfdE
+        );
+        assert_eq!(config.extruder_inverter.slave_id, 12);
+        assert_eq!(config.modbus_tcp.addresses, ["10.0.0.2:5020", "10.0.0.3"]);
+        assert!(!config.modbus_tcp.scan_interfaces);
+        assert_eq!(config.rt_loop.core, 1);
+        assert_eq!(config.rt_loop.cycle_target_us, 1000);
+        assert_eq!(config.ethercat.interface.as_deref(), Some("eth0"));
+
+        assert!(ServerConfig::load(&["--api.port".to_string()]).is_err());
+        assert!(ServerConfig::load(&["--api.port=-1".to_string()]).is_err());
+    }
+}
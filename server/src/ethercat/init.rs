@@ -4,11 +4,18 @@ use std::{sync::Arc, time::Duration};
 
 use crate::{
     app_state::SharedState,
+    config::ServerConfig,
     ethercat::{ethercat_discovery_info::send_ethercat_found, setup::setup_loop},
     metrics::io::set_ethercat_iface,
 };
 
-pub async fn find_ethercat_interface() -> String {
+/// The configured interface, otherwise the first one an EtherCAT bus answers on
+pub async fn find_ethercat_interface(config: &ServerConfig) -> String {
+    if let Some(interface) = &config.ethercat.interface {
+        tracing::info!("Using configured EtherCAT Interface: {}", interface);
+        set_ethercat_iface(interface);
+        return interface.clone();
+    }
     loop {
         match discover_ethercat_interface().await {
             Ok(interface) => {
@@ -25,7 +32,7 @@ pub async fn find_ethercat_interface() -> String {
 }
 
 pub async fn start_interface_discovery(app_state: Arc<SharedState>) {
-    let interface = find_ethercat_interface().await;
+    let interface = find_ethercat_interface(&app_state.config).await;
 
     tracing::info!("Calling setup_loop");
     let res = setup_loop(&interface, app_state.clone()).await;
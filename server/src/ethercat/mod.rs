@@ -1,4 +1,5 @@
 pub mod config;
 pub mod ethercat_discovery_info;
 pub mod init;
+pub mod recreate;
 pub mod setup;
@@ -0,0 +1,152 @@
+use crate::app_state::{EthercatSetup, RecreateMachineRequest};
+use crate::ethercat::setup::group_devices_by_identification;
+use anyhow::anyhow;
+use ethercat_hal::devices::device_from_subdevice_identity;
+use machines::machine_identification::{
+    DeviceIdentification, DeviceIdentificationIdentified, MachineIdentificationUnique,
+};
+use machines::registry::MACHINE_REGISTRY;
+use machines::{Machine, MachineNewHardware, MachineNewHardwareEthercat, MachineNewParams};
+
+/// Devices of the machine on the bus, like they were grouped on the setup
+pub fn machine_device_group(
+    setup: &EthercatSetup,
+    machine_identification_unique: &MachineIdentificationUnique,
+) -> Option<Vec<DeviceIdentificationIdentified>> {
+    let identifications = setup
+        .devices
+        .iter()
+        .map(|(identification, _)| identification.clone())
+        .collect();
+    group_devices_by_identification(&identifications)
+        .device_groups
+        .into_iter()
+        .find(|group| {
+            group.first().is_some_and(|device| {
+                &device
+                    .device_machine_identification
+                    .machine_identification_unique
+                    == machine_identification_unique
+            })
+        })
+}
+
+/// Whether the device belongs to the machine
+pub fn is_machine_device(
+    identification: &DeviceIdentification,
+    machine_identification_unique: &MachineIdentificationUnique,
+) -> bool {
+    identification
+        .device_machine_identification
+        .as_ref()
+        .is_some_and(|device| {
+            &device.machine_identification_unique == machine_identification_unique
+        })
+}
+
+/// New devices for the subdevices of the machine, the devices of the other machines are kept
+///
+/// The devices of the quarantined machine still hold the outputs and configuration the panicked
+/// machine left behind.
+fn rebuild_machine_devices(
+    setup: &mut EthercatSetup,
+    machine_identification_unique: &MachineIdentificationUnique,
+) -> Result<(), anyhow::Error> {
+    let rebuilt = setup
+        .group
+        .iter(&setup.maindevice)
+        .zip(&setup.devices)
+        .enumerate()
+        .filter(|(_, (_, (identification, _)))| {
+            is_machine_device(identification, machine_identification_unique)
+        })
+        .map(|(i, (subdevice, _))| Ok((i, device_from_subdevice_identity(&subdevice.identity())?)))
+        .collect::<Result<Vec<_>, anyhow::Error>>()?;
+    for (i, device) in rebuilt {
+        setup.devices[i].1 = device;
+    }
+    Ok(())
+}
+
+/// Creates a machine from its devices again
+///
+/// Only the devices of the machine are built anew. The machines write the configuration of their
+/// devices when they are created, which ethercrab and the PDO assignment only allow in PRE-OP, so
+/// the group still goes from OP back to PRE-OP and the outputs of all machines are off for a
+/// moment.
+///
+/// The inner `Err` is why the machine couldn't be created, the setup is kept then. The outer `Err`
+/// means the group didn't get back into OP, the setup is gone and the bus is lost.
+pub fn recreate_ethercat_machine(
+    ethercat_setup: &mut Option<Box<EthercatSetup>>,
+    device_group: &Vec<DeviceIdentificationIdentified>,
+    request: &RecreateMachineRequest,
+) -> Result<Result<Box<dyn Machine>, anyhow::Error>, anyhow::Error> {
+    let Some(setup) = ethercat_setup.as_deref_mut() else {
+        return Ok(Err(anyhow!("No EtherCAT bus")));
+    };
+    if let Err(e) = rebuild_machine_devices(setup, &request.machine_identification_unique) {
+        return Ok(Err(e));
+    }
+
+    let EthercatSetup {
+        devices,
+        group,
+        maindevice,
+        has_dc,
+    } = *ethercat_setup.take().expect("checked above");
+
+    let group_preop = smol::block_on(async {
+        group
+            .into_safe_op(&maindevice)
+            .await?
+            .into_pre_op(&maindevice)
+            .await
+    })
+    .map_err(|e| anyhow!("Failed to put group in PRE-OP state: {:?}", e))?;
+
+    let machine = {
+        // same filtering as on the setup, the machines index into the identified devices
+        let subdevices = group_preop.iter(&maindevice).collect::<Vec<_>>();
+        let (identified_devices, identified_subdevices): (Vec<_>, Vec<_>) = devices
+            .iter()
+            .zip(&subdevices)
+            .filter(|((identification, _), _)| {
+                identification.device_machine_identification.is_some()
+            })
+            .map(|((_, device), subdevice)| (device.clone(), subdevice))
+            .unzip();
+
+        MACHINE_REGISTRY.new_machine(&MachineNewParams {
+            device_group,
+            hardware: &MachineNewHardware::Ethercat(&MachineNewHardwareEthercat {
+                subdevices: &identified_subdevices,
+                ethercat_devices: &identified_devices,
+            }),
+            socket_queue_tx: request.socket_queue_tx.clone(),
+            namespace: None,
+            main_thread_channel: Some(request.main_channel.clone()),
+        })
+    };
+
+    let group = smol::block_on(async {
+        let group_safe = group_preop.into_safe_op(&maindevice).await?;
+        if has_dc {
+            for _ in 1..1000 {
+                if let Err(e) = group_safe.tx_rx_sync_system_time(&maindevice).await {
+                    tracing::error!("Failed to sync dc time: {:?}", e);
+                }
+            }
+        }
+        group_safe.into_op(&maindevice).await
+    })
+    .map_err(|e| anyhow!("Failed to put group back in OP state: {:?}", e))?;
+
+    *ethercat_setup = Some(Box::new(EthercatSetup {
+        devices,
+        group,
+        maindevice,
+        has_dc,
+    }));
+    Ok(machine)
+}
@@ -139,10 +139,12 @@ pub async fn set_ethercat_devices<const MAX_SUBDEVICES: usize, const MAX_PDI: us
 
         match new_machine {
             Ok(machine) => {
-                shared_state.clone().api_machines.lock().await.insert(
-                    machine_identification_unique.clone(),
-                    machine.api_get_sender(),
-                );
+                shared_state
+                    .insert_api_machine(
+                        machine_identification_unique.clone(),
+                        machine.api_get_sender(),
+                    )
+                    .await;
                 machine_objs.push(MachineObj {
                     machine_identification_unique,
                     error: None,
@@ -281,46 +283,56 @@ pub async fn setup_loop(
         .zip(&subdevices)
         .enumerate()
         .map(|(i, (result, subdevice))| {
-             let mut id_opt = result.ok();
-             
-             // Check if ID is missing OR if it is just zeros (unprogrammed)
-             let needs_bypass = match &id_opt {
-                 None => true,
-                 Some(id) => id.machine_identification_unique.machine_identification.vendor == 0
-             };
-
-             // BYPASSS FOR EMPTY EEPROM
-             if needs_bypass {
-                 let name = subdevice.name();
-                 tracing::warn!("Device {} has no/zero ID, applying BYPASS for TestMachine", name);
-                 
-                 // Default Fake ID for TestMachine
-                 let fake_machine_id = machines::machine_identification::MachineIdentification {
-                     vendor: 0x0001, // VENDOR_QITECH
-                     machine: 0x0033, // TEST_MACHINE
-                 };
-                 let fake_unique = machines::machine_identification::MachineIdentificationUnique {
-                     machine_identification: fake_machine_id,
-                     serial: 1,
-                 };
-                 
-                 let role = if name == "EL1008" {
-                     0
-                 } else if name == "EL2008" {
-                     1
-                 } else {
-                     // EK1100 or others
-                     99
-                 };
-
-                 id_opt = Some(machines::machine_identification::DeviceMachineIdentification {
-                     machine_identification_unique: fake_unique,
-                     role: role,
-                 });
-             }
-             // END BYPASS
-
-             (i, id_opt)
+            let mut id_opt = result.ok();
+
+            // Check if ID is missing OR if it is just zeros (unprogrammed)
+            let needs_bypass = match &id_opt {
+                None => true,
+                Some(id) => {
+                    id.machine_identification_unique
+                        .machine_identification
+                        .vendor
+                        == 0
+                }
+            };
+
+            // BYPASSS FOR EMPTY EEPROM
+            if needs_bypass {
+                let name = subdevice.name();
+                tracing::warn!(
+                    "Device {} has no/zero ID, applying BYPASS for TestMachine",
+                    name
+                );
+
+                // Default Fake ID for TestMachine
+                let fake_machine_id = machines::machine_identification::MachineIdentification {
+                    vendor: 0x0001,  // VENDOR_QITECH
+                    machine: 0x0033, // TEST_MACHINE
+                };
+                let fake_unique = machines::machine_identification::MachineIdentificationUnique {
+                    machine_identification: fake_machine_id,
+                    serial: 1,
+                };
+
+                let role = if name == "EL1008" {
+                    0
+                } else if name == "EL2008" {
+                    1
+                } else {
+                    // EK1100 or others
+                    99
+                };
+
+                id_opt = Some(
+                    machines::machine_identification::DeviceMachineIdentification {
+                        machine_identification_unique: fake_unique,
+                        role: role,
+                    },
+                );
+            }
+            // END BYPASS
+
+            (i, id_opt)
         })
         .map(
             |(subdevice_index, device_machine_identification)| DeviceIdentification {
@@ -527,5 +539,6 @@ pub async fn setup_loop(
         devices,
         group: group_op,
         maindevice,
+        has_dc,
     })
 }
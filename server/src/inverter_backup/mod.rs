@@ -0,0 +1,185 @@
+use crate::config::InverterBackupsConfig;
+use anyhow::{Result, anyhow};
+use machines::frequency_inverter::parameters::{
+    FinishedBackup, ParameterBackup, validate_backup_name,
+};
+use machines::machine_identification::MachineIdentificationUnique;
+use machines::production_record::unix_timestamp_ms;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+/// Number of finished backups whose outcome is kept for the API
+const MAX_RESULTS: usize = 20;
+
+/// Outcome of a backup started with `BackupInverterParameters`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct InverterBackupResult {
+    pub name: String,
+    pub machine: MachineIdentificationUnique,
+    /// unix timestamp in ms
+    pub timestamp: u64,
+    /// `None` if the backup was read and saved
+    pub error: Option<String>,
+}
+
+/// Backups by name in a fixed directory, names never reach the file system unchecked
+#[derive(Debug)]
+pub struct InverterBackupStore {
+    dir: PathBuf,
+    results: Mutex<VecDeque<InverterBackupResult>>,
+}
+
+impl InverterBackupStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            results: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, name: &str) -> Result<PathBuf> {
+        validate_backup_name(name)?;
+        Ok(self.dir.join(format!("{}.json", name)))
+    }
+
+    pub fn save(&self, name: &str, backup: &ParameterBackup) -> Result<()> {
+        let path = self.path(name)?;
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(path, serde_json::to_string_pretty(backup)?)?;
+        Ok(())
+    }
+
+    pub fn load(&self, name: &str) -> Result<ParameterBackup> {
+        let path = self.path(name)?;
+        let json = match std::fs::read_to_string(&path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(anyhow!("No inverter backup {}", name));
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Names of all stored backups, sorted
+    pub fn list(&self) -> Result<Vec<String>> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut names = vec![];
+        for entry in entries {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+                && let Some(name) = path.file_stem().and_then(|stem| stem.to_str())
+                && validate_backup_name(name).is_ok()
+            {
+                names.push(name.to_string());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    fn push_result(&self, result: InverterBackupResult) {
+        let mut results = self.results.lock().unwrap();
+        results.push_front(result);
+        results.truncate(MAX_RESULTS);
+    }
+
+    /// Outcomes of the latest backups, latest first
+    pub fn results(&self) -> Vec<InverterBackupResult> {
+        self.results.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+static INVERTER_BACKUPS_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+static INVERTER_BACKUPS: OnceLock<InverterBackupStore> = OnceLock::new();
+
+/// Sets the directory of the backups, `inverter_backups.dir` in the server config, called once
+/// at startup
+pub fn set_inverter_backups_dir(dir: impl Into<PathBuf>) -> Result<()> {
+    INVERTER_BACKUPS_DIR
+        .set(dir.into())
+        .map_err(|_| anyhow!("The inverter backups directory is already set"))
+}
+
+/// The store in the directory set at startup, one `<name>.json` per backup
+pub fn inverter_backups() -> &'static InverterBackupStore {
+    INVERTER_BACKUPS.get_or_init(|| {
+        let dir = INVERTER_BACKUPS_DIR
+            .get_or_init(|| InverterBackupsConfig::default().dir.into())
+            .clone();
+        InverterBackupStore::new(dir)
+    })
+}
+
+/// Saves a backup an extruder finished reading, the file is written off the async executor
+pub async fn store_inverter_backup(machine: MachineIdentificationUnique, backup: FinishedBackup) {
+    let store = inverter_backups();
+    let name = backup.name.clone();
+    let result = match backup.result {
+        Ok(parameters) => smol::unblock(move || store.save(&backup.name, &parameters))
+            .await
+            .map_err(|e| format!("{:#}", e)),
+        Err(e) => Err(e),
+    };
+
+    match &result {
+        Ok(()) => tracing::info!(
+            "Stored inverter backup {} of {} in {}",
+            name,
+            machine,
+            store.dir().display()
+        ),
+        Err(e) => tracing::error!("Inverter backup {} of {} failed: {}", name, machine, e),
+    }
+    store.push_result(InverterBackupResult {
+        name,
+        machine,
+        timestamp: unix_timestamp_ms(SystemTime::now()),
+        error: result.err(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use machines::frequency_inverter::FrequencyInverterType;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_backup_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("inverter_backups_{}", std::process::id()));
+        let store = InverterBackupStore::new(&dir);
+        let backup = ParameterBackup {
+            inverter: FrequencyInverterType::MitsubishiCS80,
+            parameters: BTreeMap::from([(1, 6000), (7, 50), (117, 1)]),
+        };
+
+        assert_eq!(store.list().unwrap(), Vec::<String>::new());
+        store.save("extruder-2", &backup).unwrap();
+        store.save("extruder-1", &backup).unwrap();
+        assert_eq!(store.load("extruder-2").unwrap(), backup);
+        assert_eq!(store.list().unwrap(), vec!["extruder-1", "extruder-2"]);
+        assert!(store.load("extruder-3").is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // names can't point outside of the directory
+        assert!(store.save("../backup", &backup).is_err());
+        assert!(store.load("/etc/passwd").is_err());
+    }
+}
@@ -0,0 +1,154 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// An entry of a [`JsonlStore`], one JSON line in the file
+pub trait JsonlEntry: Serialize + DeserializeOwned {
+    /// Name of the entries in messages, e.g. "audit entry"
+    const NAME: &'static str;
+
+    fn id(&self) -> u64;
+
+    /// Called once when the entry is stored
+    fn assign_id(&mut self, id: u64);
+}
+
+/// Append only store of entries with increasing ids
+#[derive(Debug)]
+pub struct JsonlStore<T> {
+    path: PathBuf,
+    next_id: u64,
+    entries: PhantomData<T>,
+}
+
+impl<T: JsonlEntry> JsonlStore<T> {
+    /// Continues the ids of the entries already in the file
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let mut store = Self {
+            path: path.into(),
+            next_id: 1,
+            entries: PhantomData,
+        };
+        store.next_id = store.load()?.iter().map(T::id).max().unwrap_or(0) + 1;
+        store.terminate_last_line()?;
+        Ok(store)
+    }
+
+    /// Ends a partially written last line, so the next entry starts on its own line
+    fn terminate_last_line(&self) -> Result<()> {
+        let contents = match std::fs::read(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        if contents.last().is_some_and(|byte| *byte != b'\n') {
+            let file = OpenOptions::new().append(true).open(&self.path)?;
+            (&file).write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Assigns the id and stores the entry
+    pub fn append(&mut self, mut entry: T) -> Result<T> {
+        entry.assign_id(self.next_id);
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+        (&file).write_all(line.as_bytes())?;
+
+        self.next_id += 1;
+        Ok(entry)
+    }
+
+    /// All entries, oldest first
+    pub fn load(&self) -> Result<Vec<T>> {
+        let file = match OpenOptions::new().read(true).open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut entries = vec![];
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            // a broken line (e.g. power loss while writing) must not hide the other entries
+            match serde_json::from_str(&line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => tracing::warn!(
+                    "Skipping invalid {} in {} line {}: {}",
+                    T::NAME,
+                    self.path.display(),
+                    index + 1,
+                    e
+                ),
+            }
+        }
+        Ok(entries)
+    }
+
+    pub fn get(&self, id: u64) -> Result<Option<T>> {
+        Ok(self.load()?.into_iter().find(|entry| entry.id() == id))
+    }
+}
+
+/// A store shared by the whole server, opened on first use at the path set at startup
+pub struct SharedJsonlStore<T> {
+    path: OnceLock<PathBuf>,
+    default_path: fn() -> String,
+    store: OnceLock<Option<Mutex<JsonlStore<T>>>>,
+}
+
+impl<T: JsonlEntry> SharedJsonlStore<T> {
+    /// `default_path` is used if no path is set before the first use
+    pub const fn new(default_path: fn() -> String) -> Self {
+        Self {
+            path: OnceLock::new(),
+            default_path,
+            store: OnceLock::new(),
+        }
+    }
+
+    /// Called once at startup
+    pub fn set_path(&self, path: impl Into<PathBuf>) -> Result<()> {
+        self.path
+            .set(path.into())
+            .map_err(|_| anyhow::anyhow!("The {} store path is already set", T::NAME))
+    }
+
+    /// `None` if the file can't be read
+    pub fn get(&self) -> Option<&Mutex<JsonlStore<T>>> {
+        self.store
+            .get_or_init(|| {
+                let path = self.path.get_or_init(|| (self.default_path)().into());
+                match JsonlStore::open(path) {
+                    Ok(store) => Some(Mutex::new(store)),
+                    Err(e) => {
+                        tracing::error!(
+                            "Failed to open the {} store {}: {}",
+                            T::NAME,
+                            path.display(),
+                            e
+                        );
+                        None
+                    }
+                }
+            })
+            .as_ref()
+    }
+}
@@ -0,0 +1,236 @@
+use anyhow::{Result, bail};
+use machines::LineSequence;
+use machines::machine_identification::MachineIdentificationUnique;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// A named group of machines that are started and stopped together
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LineConfig {
+    pub name: String,
+    /// Machines of the line by role, e.g. `"extruder"`, steps refer to them by role
+    pub machines: BTreeMap<String, MachineIdentificationUnique>,
+    #[serde(default)]
+    pub start: Sequence,
+    #[serde(default)]
+    pub stop: Sequence,
+    #[serde(default)]
+    pub emergency: Sequence,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Sequence {
+    pub steps: Vec<LineStep>,
+    /// Timeout in s of the steps that don't set their own
+    #[serde(default)]
+    pub step_timeout: Option<f64>,
+    /// The sequence is aborted as soon as one of them holds
+    #[serde(default)]
+    pub abort_conditions: Vec<LineCondition>,
+    /// Sequence to run when this one is aborted or a step timed out
+    #[serde(default)]
+    pub on_abort: Option<LineSequence>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LineStep {
+    /// Shown to the operator while the step runs
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Timeout in s, overrides the timeout of the sequence
+    #[serde(default)]
+    pub timeout: Option<f64>,
+    #[serde(flatten)]
+    pub action: StepAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum StepAction {
+    /// Sends the mutation to the machine, like `/api/v1/machine/mutate`
+    Mutate { machine: String, mutation: Value },
+    /// Waits until all conditions hold
+    WaitUntil { conditions: Vec<LineCondition> },
+    /// Waits for the given time in s
+    Delay { seconds: f64 },
+}
+
+/// Where the value of a condition is read from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ValueSource {
+    #[default]
+    LiveValues,
+    State,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LineCondition {
+    pub machine: String,
+    #[serde(default)]
+    pub source: ValueSource,
+    /// JSON pointer into the event data, e.g. `"/nozzle_temperature"`
+    pub pointer: String,
+    #[serde(flatten)]
+    pub comparison: Comparison,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Comparison {
+    Eq { value: Value },
+    Ne { value: Value },
+    Gt { value: f64 },
+    Ge { value: f64 },
+    Lt { value: f64 },
+    Le { value: f64 },
+    Within { value: f64, tolerance: f64 },
+}
+
+/// Numbers are compared by value, so `1` equals `1.0`
+fn values_equal(a: &Value, b: &Value) -> bool {
+    a == b || a.as_f64().is_some_and(|a| b.as_f64() == Some(a))
+}
+
+impl Comparison {
+    pub fn matches(&self, actual: &Value) -> bool {
+        let number = actual.as_f64();
+        match self {
+            Self::Eq { value } => values_equal(actual, value),
+            Self::Ne { value } => !values_equal(actual, value),
+            Self::Gt { value } => number.is_some_and(|n| n > *value),
+            Self::Ge { value } => number.is_some_and(|n| n >= *value),
+            Self::Lt { value } => number.is_some_and(|n| n < *value),
+            Self::Le { value } => number.is_some_and(|n| n <= *value),
+            Self::Within { value, tolerance } => {
+                number.is_some_and(|n| (n - value).abs() <= *tolerance)
+            }
+        }
+    }
+}
+
+impl LineCondition {
+    /// `data` is the latest event data of the machine, a missing value never matches
+    pub fn matches(&self, data: Option<&Value>) -> bool {
+        data.and_then(|data| data.pointer(&self.pointer))
+            .is_some_and(|actual| self.comparison.matches(actual))
+    }
+}
+
+impl LineConfig {
+    pub const fn sequence(&self, sequence: LineSequence) -> &Sequence {
+        match sequence {
+            LineSequence::Start => &self.start,
+            LineSequence::Stop => &self.stop,
+            LineSequence::Emergency => &self.emergency,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for kind in [
+            LineSequence::Start,
+            LineSequence::Stop,
+            LineSequence::Emergency,
+        ] {
+            let sequence = self.sequence(kind);
+            // an abort must end in a sequence that doesn't abort into another
+            let mut chain = vec![kind];
+            while let Some(on_abort) = self.sequence(chain[chain.len() - 1]).on_abort {
+                if chain.contains(&on_abort) {
+                    bail!(
+                        "Line {}: on_abort of {:?} runs in a cycle {:?}",
+                        self.name,
+                        kind,
+                        chain
+                    );
+                }
+                chain.push(on_abort);
+            }
+            let timeouts = sequence.steps.iter().filter_map(|step| step.timeout);
+            for seconds in timeouts.chain(sequence.step_timeout) {
+                check_seconds(&self.name, kind, seconds)?;
+            }
+            let conditions = sequence.steps.iter().flat_map(|step| match &step.action {
+                StepAction::WaitUntil { conditions } => conditions.as_slice(),
+                _ => &[],
+            });
+            for condition in conditions.chain(&sequence.abort_conditions) {
+                self.check_machine(kind, &condition.machine)?;
+                if !condition.pointer.is_empty() && !condition.pointer.starts_with('/') {
+                    bail!(
+                        "Line {}: {:?} pointer {:?} must start with '/'",
+                        self.name,
+                        kind,
+                        condition.pointer
+                    );
+                }
+            }
+            for step in &sequence.steps {
+                match &step.action {
+                    StepAction::Mutate { machine, .. } => self.check_machine(kind, machine)?,
+                    StepAction::Delay { seconds } => check_seconds(&self.name, kind, *seconds)?,
+                    StepAction::WaitUntil { .. } => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_machine(&self, kind: LineSequence, role: &str) -> Result<()> {
+        if !self.machines.contains_key(role) {
+            bail!(
+                "Line {}: {:?} refers to unknown machine {:?}",
+                self.name,
+                kind,
+                role
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Durations are converted with `Duration::from_secs_f64`, which panics on them
+fn check_seconds(line: &str, kind: LineSequence, seconds: f64) -> Result<()> {
+    if !seconds.is_finite() || seconds < 0.0 {
+        bail!(
+            "Line {}: {:?} has an invalid duration of {} s",
+            line,
+            kind,
+            seconds
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_condition_matches() {
+        let condition: LineCondition = serde_json::from_value(json!({
+            "machine": "extruder",
+            "pointer": "/nozzle_temperature",
+            "op": "within",
+            "value": 200.0,
+            "tolerance": 5.0,
+        }))
+        .unwrap();
+        assert_eq!(condition.source, ValueSource::LiveValues);
+
+        assert!(condition.matches(Some(&json!({ "nozzle_temperature": 197.5 }))));
+        assert!(!condition.matches(Some(&json!({ "nozzle_temperature": 190.0 }))));
+        assert!(!condition.matches(Some(&json!({ "front_temperature": 200.0 }))));
+        assert!(!condition.matches(None));
+
+        let mode = Comparison::Eq {
+            value: json!("Standby"),
+        };
+        assert!(mode.matches(&json!("Standby")));
+        assert!(!mode.matches(&json!("Hold")));
+        assert!(Comparison::Eq { value: json!(1) }.matches(&json!(1.0)));
+        assert!(Comparison::Ge { value: 1.0 }.matches(&json!(1)));
+        assert!(!Comparison::Lt { value: 1.0 }.matches(&json!("0")));
+    }
+}
@@ -0,0 +1,224 @@
+use crate::app_state::SharedState;
+use crate::audit::{AuditAction, AuditEntry, record_audit};
+use crate::auth::mutation_name;
+use crate::rest::handlers::machine_mutation::{audited_mutation_result, send_mutation};
+use crate::socketio::main_namespace::MainNamespaceEvents;
+use crate::socketio::main_namespace::lines_event::LinesEventBuilder;
+use anyhow::{Result, anyhow};
+use config::{LineConfig, ValueSource};
+use control_core::socketio::namespace::NamespaceCacheingLogic;
+use machines::LineCommand;
+use machines::machine_identification::MachineIdentificationUnique;
+use machines::machine_settings::{get_latest_live_values, get_latest_settings};
+use runner::{LineMachines, LineMutation, LineProgress, LineRunner};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub mod config;
+pub mod runner;
+
+const LINE_UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// All production lines of this server
+#[derive(Debug, Default)]
+pub struct Lines {
+    runners: Vec<LineRunner>,
+}
+
+impl Lines {
+    /// A missing file means there are no lines
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let configs: Vec<LineConfig> = serde_json::from_str(&contents)?;
+
+        let mut names = HashSet::new();
+        for config in &configs {
+            config.validate()?;
+            if !names.insert(config.name.as_str()) {
+                return Err(anyhow!("Line {} is defined twice", config.name));
+            }
+        }
+
+        Ok(Self {
+            runners: configs.into_iter().map(LineRunner::new).collect(),
+        })
+    }
+
+    /// The lines in the JSON file at `path`, a list of [`LineConfig`], none if the file is
+    /// invalid
+    pub fn from_path(path: &str) -> Self {
+        match Self::load(Path::new(path)) {
+            Ok(lines) => {
+                tracing::info!(
+                    "Loaded {} production lines from {}",
+                    lines.runners.len(),
+                    path
+                );
+                lines
+            }
+            Err(e) => {
+                tracing::error!("Failed to load production lines from {}: {}", path, e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.runners
+            .iter()
+            .any(|runner| runner.config().name == name)
+    }
+
+    pub fn run(&mut self, command: &LineCommand, now: Instant) -> Result<()> {
+        let runner = self
+            .runners
+            .iter_mut()
+            .find(|runner| runner.config().name == command.line)
+            .ok_or_else(|| anyhow!("No production line {}", command.line))?;
+        runner.run(command.sequence, now)
+    }
+
+    /// Reports the result of a mutation the line sent, see [`LineRunner::mutation_result`]
+    pub fn mutation_result(
+        &mut self,
+        line: &str,
+        id: u64,
+        result: Result<(), String>,
+        now: Instant,
+    ) {
+        if let Some(runner) = self
+            .runners
+            .iter_mut()
+            .find(|runner| runner.config().name == line)
+        {
+            runner.mutation_result(id, result, now);
+        }
+    }
+
+    pub fn progress(&self, now: Instant) -> Vec<LineProgress> {
+        self.runners
+            .iter()
+            .map(|runner| runner.progress(now))
+            .collect()
+    }
+}
+
+/// Reads the snapshots the machines publish from their emit functions
+struct PublishedMachines {
+    connected: HashSet<MachineIdentificationUnique>,
+}
+
+impl LineMachines for PublishedMachines {
+    fn is_connected(&self, machine: &MachineIdentificationUnique) -> bool {
+        self.connected.contains(machine)
+    }
+
+    fn value(&self, machine: &MachineIdentificationUnique, source: ValueSource) -> Option<Value> {
+        match source {
+            ValueSource::LiveValues => get_latest_live_values(machine),
+            ValueSource::State => get_latest_settings(machine),
+        }
+    }
+}
+
+/// Handles `AsyncThreadMessage::RunLineSequence`, the progress is sent by the orchestration task
+pub async fn run_line_sequence(app_state: &SharedState, command: LineCommand) {
+    let result = app_state.lines.lock().await.run(&command, Instant::now());
+    if let Err(e) = result {
+        tracing::warn!("{}", e);
+    }
+}
+
+/// Advances the sequences of all lines and sends their mutations to the machines
+pub async fn start_line_orchestration(app_state: Arc<SharedState>) {
+    let mut last_progress = None;
+    loop {
+        smol::Timer::after(LINE_UPDATE_INTERVAL).await;
+
+        let machines = PublishedMachines {
+            connected: app_state
+                .api_machines
+                .lock()
+                .await
+                .keys()
+                .cloned()
+                .collect(),
+        };
+
+        let now = Instant::now();
+        let mut lines = app_state.lines.lock().await;
+        let mutations: Vec<_> = lines
+            .runners
+            .iter_mut()
+            .flat_map(|runner| {
+                let line = runner.config().name.clone();
+                runner
+                    .update(now, &machines)
+                    .into_iter()
+                    .map(move |mutation| (line.clone(), mutation))
+            })
+            .collect();
+        let progress = lines.progress(now);
+        drop(lines);
+
+        // sent in order, the results are awaited in the background
+        for (line, mutation) in mutations {
+            let LineMutation {
+                id,
+                machine,
+                mutation,
+            } = mutation;
+            tracing::info!("Line mutating machine={} data={:?}", machine, mutation);
+            let entry = AuditEntry::new(
+                String::new(),
+                format!("line {}", line),
+                AuditAction::MachineMutation,
+                mutation.clone(),
+            )
+            .with_machine(machine.clone())
+            .with_mutation(mutation_name(&mutation));
+            let previous = get_latest_settings(&machine);
+
+            let sent = send_mutation(&app_state, &machine, mutation).await;
+            let app_state = app_state.clone();
+            smol::spawn(async move {
+                let result = match sent {
+                    Ok(result) => audited_mutation_result(&machine, result, entry, previous).await,
+                    Err(e) => {
+                        record_audit(entry.with_result(&Err::<(), _>(&e)));
+                        Err(e)
+                    }
+                };
+                if let Err(e) = &result {
+                    tracing::error!("Line mutation of {} failed: {}", machine, e);
+                }
+                app_state.lines.lock().await.mutation_result(
+                    &line,
+                    id,
+                    result.map_err(|e| format!("{:#}", e)),
+                    Instant::now(),
+                );
+            })
+            .detach();
+        }
+
+        if last_progress.as_ref() != Some(&progress) {
+            let event = LinesEventBuilder().build(progress.clone());
+            let main_namespace = &mut app_state
+                .socketio_setup
+                .namespaces
+                .write()
+                .await
+                .main_namespace;
+            main_namespace.emit(MainNamespaceEvents::LinesEvent(event));
+            last_progress = Some(progress);
+        }
+    }
+}
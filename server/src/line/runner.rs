@@ -0,0 +1,591 @@
+use super::config::{LineCondition, LineConfig, StepAction, ValueSource};
+use anyhow::{Result, bail};
+use machines::LineSequence;
+use machines::machine_identification::MachineIdentificationUnique;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// What the runner knows about the machines of the line
+pub trait LineMachines {
+    fn is_connected(&self, machine: &MachineIdentificationUnique) -> bool;
+    /// Latest event data of the machine
+    fn value(&self, machine: &MachineIdentificationUnique, source: ValueSource) -> Option<Value>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SequenceStatus {
+    Idle,
+    Running,
+    Completed,
+    Aborted,
+}
+
+/// Progress of a line, sent to the frontend in the `LinesEvent`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LineProgress {
+    pub name: String,
+    pub machines: BTreeMap<String, MachineIdentificationUnique>,
+    /// Running sequence, or the last one that ran
+    pub sequence: Option<LineSequence>,
+    pub status: SequenceStatus,
+    /// Index of the current step, or of the step the sequence was aborted in
+    pub step: usize,
+    pub steps: usize,
+    pub step_name: Option<String>,
+    /// Time the current step is running in s
+    pub step_elapsed: f64,
+    /// Why the sequence was aborted
+    pub message: Option<String>,
+}
+
+/// A mutation of a `Mutate` step, its result is reported with [`LineRunner::mutation_result`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineMutation {
+    pub id: u64,
+    pub machine: MachineIdentificationUnique,
+    pub mutation: Value,
+}
+
+#[derive(Debug)]
+struct RunningSequence {
+    sequence: LineSequence,
+    step: usize,
+    step_started_at: Instant,
+    /// Id of the mutation the current step waits for
+    pending_mutation: Option<u64>,
+}
+
+#[derive(Debug)]
+struct FinishedSequence {
+    sequence: LineSequence,
+    step: usize,
+    aborted: bool,
+}
+
+/// Runs the sequences of one line, a step at a time
+#[derive(Debug)]
+pub struct LineRunner {
+    config: LineConfig,
+    running: Option<RunningSequence>,
+    finished: Option<FinishedSequence>,
+    /// Why the last sequence was aborted, kept while the `on_abort` sequence runs
+    message: Option<String>,
+    next_mutation_id: u64,
+}
+
+impl LineRunner {
+    pub const fn new(config: LineConfig) -> Self {
+        Self {
+            config,
+            running: None,
+            finished: None,
+            message: None,
+            next_mutation_id: 0,
+        }
+    }
+
+    pub const fn config(&self) -> &LineConfig {
+        &self.config
+    }
+
+    /// An emergency interrupts every other sequence and stop interrupts start
+    pub fn run(&mut self, sequence: LineSequence, now: Instant) -> Result<()> {
+        self.begin(sequence, now)?;
+        self.message = None;
+        Ok(())
+    }
+
+    fn begin(&mut self, sequence: LineSequence, now: Instant) -> Result<()> {
+        if let Some(running) = &self.running {
+            match (running.sequence, sequence) {
+                (running, requested) if running == requested => return Ok(()),
+                (_, LineSequence::Emergency) | (LineSequence::Start, LineSequence::Stop) => {
+                    tracing::warn!(
+                        "Line {}: {:?} interrupts {:?}",
+                        self.config.name,
+                        sequence,
+                        running
+                    );
+                }
+                (running, requested) => bail!(
+                    "Line {}: can't run {:?} while {:?} is running",
+                    self.config.name,
+                    requested,
+                    running
+                ),
+            }
+        }
+
+        tracing::info!("Line {}: running {:?}", self.config.name, sequence);
+        self.running = Some(RunningSequence {
+            sequence,
+            step: 0,
+            step_started_at: now,
+            pending_mutation: None,
+        });
+        self.finished = None;
+        Ok(())
+    }
+
+    /// Advances the running sequence as far as possible
+    /// Returns the mutations to send to the machines, in order
+    ///
+    /// A `Mutate` step is done once the machine applied the mutation. Only the emergency
+    /// sequence goes on as soon as it is sent, its failures are logged.
+    pub fn update(&mut self, now: Instant, machines: &impl LineMachines) -> Vec<LineMutation> {
+        let mut mutations = vec![];
+
+        while let Some(running) = &mut self.running {
+            let sequence = self.config.sequence(running.sequence);
+            let is_emergency = running.sequence == LineSequence::Emergency;
+
+            if !is_emergency
+                && let Some(condition) = sequence
+                    .abort_conditions
+                    .iter()
+                    .find(|condition| condition_holds(&self.config, condition, machines))
+            {
+                let reason = format!(
+                    "Abort condition {} {} {:?} holds",
+                    condition.machine, condition.pointer, condition.comparison
+                );
+                self.abort(reason, now);
+                continue;
+            }
+
+            let Some(step) = sequence.steps.get(running.step) else {
+                tracing::info!(
+                    "Line {}: {:?} completed",
+                    self.config.name,
+                    running.sequence
+                );
+                self.finished = Some(FinishedSequence {
+                    sequence: running.sequence,
+                    step: running.step,
+                    aborted: false,
+                });
+                self.running = None;
+                break;
+            };
+
+            let elapsed = now.saturating_duration_since(running.step_started_at);
+            let done = match &step.action {
+                StepAction::Mutate { machine, mutation } => {
+                    let machine = &self.config.machines[machine];
+                    if running.pending_mutation.is_some() {
+                        break;
+                    } else if machines.is_connected(machine) {
+                        let id = self.next_mutation_id;
+                        self.next_mutation_id += 1;
+                        mutations.push(LineMutation {
+                            id,
+                            machine: machine.clone(),
+                            mutation: mutation.clone(),
+                        });
+                        if !is_emergency {
+                            running.pending_mutation = Some(id);
+                            break;
+                        }
+                        Ok(())
+                    } else {
+                        Err(format!("Machine {} is not connected", machine))
+                    }
+                }
+                StepAction::WaitUntil { conditions } => {
+                    let timeout = step.timeout.or(sequence.step_timeout);
+                    if conditions
+                        .iter()
+                        .all(|condition| condition_holds(&self.config, condition, machines))
+                    {
+                        Ok(())
+                    } else if timeout.is_some_and(|t| elapsed >= Duration::from_secs_f64(t)) {
+                        Err(format!(
+                            "Step {} timed out",
+                            step.name.as_deref().unwrap_or("WaitUntil")
+                        ))
+                    } else {
+                        break;
+                    }
+                }
+                StepAction::Delay { seconds } => {
+                    if elapsed >= Duration::from_secs_f64(*seconds) {
+                        Ok(())
+                    } else {
+                        break;
+                    }
+                }
+            };
+
+            match done {
+                Ok(()) => {
+                    running.step += 1;
+                    running.step_started_at = now;
+                }
+                // the emergency sequence does as much as it can
+                Err(reason) if is_emergency => {
+                    tracing::error!("Line {}: {}, continuing", self.config.name, reason);
+                    running.step += 1;
+                    running.step_started_at = now;
+                }
+                Err(reason) => self.abort(reason, now),
+            }
+        }
+
+        mutations
+    }
+
+    /// Finishes the `Mutate` step that waits for this mutation, or aborts the sequence if it failed
+    pub fn mutation_result(&mut self, id: u64, result: Result<(), String>, now: Instant) {
+        let Some(running) = self
+            .running
+            .as_mut()
+            .filter(|running| running.pending_mutation == Some(id))
+        else {
+            // sent by the emergency sequence or by a sequence that was interrupted since
+            if let Err(e) = result {
+                tracing::error!("Line {}: mutation failed: {}", self.config.name, e);
+            }
+            return;
+        };
+
+        match result {
+            Ok(()) => {
+                running.step += 1;
+                running.step_started_at = now;
+                running.pending_mutation = None;
+            }
+            Err(e) => self.abort(format!("Mutation failed: {}", e), now),
+        }
+    }
+
+    fn abort(&mut self, reason: String, now: Instant) {
+        let Some(running) = self.running.take() else {
+            return;
+        };
+        tracing::warn!(
+            "Line {}: {:?} aborted in step {}: {}",
+            self.config.name,
+            running.sequence,
+            running.step,
+            reason
+        );
+        self.finished = Some(FinishedSequence {
+            sequence: running.sequence,
+            step: running.step,
+            aborted: true,
+        });
+        self.message = Some(reason);
+
+        if let Some(on_abort) = self.config.sequence(running.sequence).on_abort
+            && let Err(e) = self.begin(on_abort, now)
+        {
+            tracing::error!("Line {}: {}", self.config.name, e);
+        }
+    }
+
+    pub fn progress(&self, now: Instant) -> LineProgress {
+        let mut progress = LineProgress {
+            name: self.config.name.clone(),
+            machines: self.config.machines.clone(),
+            sequence: None,
+            status: SequenceStatus::Idle,
+            step: 0,
+            steps: 0,
+            step_name: None,
+            step_elapsed: 0.0,
+            message: self.message.clone(),
+        };
+
+        if let Some(running) = &self.running {
+            let steps = &self.config.sequence(running.sequence).steps;
+            progress.sequence = Some(running.sequence);
+            progress.status = SequenceStatus::Running;
+            progress.step = running.step;
+            progress.steps = steps.len();
+            progress.step_name = steps.get(running.step).and_then(|s| s.name.clone());
+            progress.step_elapsed = now
+                .saturating_duration_since(running.step_started_at)
+                .as_secs_f64();
+        } else if let Some(finished) = &self.finished {
+            let steps = &self.config.sequence(finished.sequence).steps;
+            progress.sequence = Some(finished.sequence);
+            progress.status = if finished.aborted {
+                SequenceStatus::Aborted
+            } else {
+                SequenceStatus::Completed
+            };
+            progress.step = finished.step;
+            progress.steps = steps.len();
+            progress.step_name = steps.get(finished.step).and_then(|s| s.name.clone());
+        }
+
+        progress
+    }
+}
+
+fn condition_holds(
+    config: &LineConfig,
+    condition: &LineCondition,
+    machines: &impl LineMachines,
+) -> bool {
+    let value = config
+        .machines
+        .get(&condition.machine)
+        .and_then(|machine| machines.value(machine, condition.source));
+    condition.matches(value.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use machines::machine_identification::MachineIdentification;
+    use machines::{MACHINE_EXTRUDER_V1, MACHINE_WINDER_V1, VENDOR_QITECH};
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct TestMachines {
+        live_values: HashMap<MachineIdentificationUnique, Value>,
+        disconnected: Vec<MachineIdentificationUnique>,
+    }
+
+    impl LineMachines for TestMachines {
+        fn is_connected(&self, machine: &MachineIdentificationUnique) -> bool {
+            !self.disconnected.contains(machine)
+        }
+
+        fn value(
+            &self,
+            machine: &MachineIdentificationUnique,
+            source: ValueSource,
+        ) -> Option<Value> {
+            match source {
+                ValueSource::LiveValues => self.live_values.get(machine).cloned(),
+                ValueSource::State => None,
+            }
+        }
+    }
+
+    fn machine(machine: u16) -> MachineIdentificationUnique {
+        MachineIdentificationUnique {
+            machine_identification: MachineIdentification {
+                vendor: VENDOR_QITECH,
+                machine,
+            },
+            serial: 1,
+        }
+    }
+
+    fn sent(mutations: &[LineMutation]) -> Vec<(MachineIdentificationUnique, Value)> {
+        mutations
+            .iter()
+            .map(|m| (m.machine.clone(), m.mutation.clone()))
+            .collect()
+    }
+
+    fn config() -> LineConfig {
+        serde_json::from_value(json!({
+            "name": "line 1",
+            "machines": {
+                "extruder": machine(MACHINE_EXTRUDER_V1),
+                "winder": machine(MACHINE_WINDER_V1),
+            },
+            "start": {
+                "step_timeout": 60.0,
+                "on_abort": "Emergency",
+                "abort_conditions": [
+                    { "machine": "extruder", "pointer": "/pressure", "op": "gt", "value": 300.0 }
+                ],
+                "steps": [
+                    { "type": "Mutate", "machine": "extruder", "mutation": { "SetExtruderMode": "Heat" } },
+                    {
+                        "type": "WaitUntil",
+                        "name": "heating",
+                        "conditions": [
+                            { "machine": "extruder", "pointer": "/nozzle_temperature", "op": "ge", "value": 200.0 }
+                        ]
+                    },
+                    { "type": "Delay", "seconds": 10.0 },
+                    { "type": "Mutate", "machine": "winder", "mutation": { "SetMode": "Wind" } },
+                ]
+            },
+            "emergency": {
+                "steps": [
+                    { "type": "Mutate", "machine": "winder", "mutation": { "SetMode": "Standby" } },
+                    { "type": "Mutate", "machine": "extruder", "mutation": { "SetExtruderMode": "Standby" } },
+                ]
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_start_sequence() {
+        let config = config();
+        config.validate().unwrap();
+        let mut runner = LineRunner::new(config);
+        let mut machines = TestMachines::default();
+        let extruder = machine(MACHINE_EXTRUDER_V1);
+        let t0 = Instant::now();
+
+        assert_eq!(runner.progress(t0).status, SequenceStatus::Idle);
+        runner.run(LineSequence::Start, t0).unwrap();
+        assert!(runner.run(LineSequence::Emergency, t0).is_ok());
+        runner.run(LineSequence::Start, t0).unwrap_err();
+
+        let mut runner = LineRunner::new(runner.config().clone());
+        runner.run(LineSequence::Start, t0).unwrap();
+        let mutations = runner.update(t0, &machines);
+        assert_eq!(
+            sent(&mutations),
+            [(extruder.clone(), json!({ "SetExtruderMode": "Heat" }))]
+        );
+        // waits for the machine to apply it
+        assert!(runner.update(t0, &machines).is_empty());
+        assert_eq!(runner.progress(t0).step, 0);
+        runner.mutation_result(mutations[0].id, Ok(()), t0);
+
+        let progress = runner.progress(t0 + Duration::from_secs(5));
+        assert_eq!(progress.status, SequenceStatus::Running);
+        assert_eq!(progress.step, 1);
+        assert_eq!(progress.steps, 4);
+        assert_eq!(progress.step_name.as_deref(), Some("heating"));
+        assert_eq!(progress.step_elapsed, 5.0);
+
+        machines
+            .live_values
+            .insert(extruder.clone(), json!({ "nozzle_temperature": 150.0 }));
+        assert!(
+            runner
+                .update(t0 + Duration::from_secs(30), &machines)
+                .is_empty()
+        );
+        assert_eq!(runner.progress(t0).step, 1);
+
+        // at temperature, then the delay
+        let t1 = t0 + Duration::from_secs(40);
+        machines
+            .live_values
+            .insert(extruder, json!({ "nozzle_temperature": 201.0 }));
+        assert!(runner.update(t1, &machines).is_empty());
+        assert_eq!(runner.progress(t1).step, 2);
+        assert!(
+            runner
+                .update(t1 + Duration::from_secs(5), &machines)
+                .is_empty()
+        );
+
+        let mutations = runner.update(t1 + Duration::from_secs(10), &machines);
+        assert_eq!(
+            sent(&mutations),
+            [(machine(MACHINE_WINDER_V1), json!({ "SetMode": "Wind" }))]
+        );
+        assert_eq!(runner.progress(t1).status, SequenceStatus::Running);
+        runner.mutation_result(mutations[0].id, Ok(()), t1);
+        assert!(runner.update(t1, &machines).is_empty());
+        let progress = runner.progress(t1);
+        assert_eq!(progress.status, SequenceStatus::Completed);
+        assert_eq!(progress.sequence, Some(LineSequence::Start));
+    }
+
+    #[test]
+    fn test_abort_runs_emergency() {
+        let mut runner = LineRunner::new(config());
+        let mut machines = TestMachines::default();
+        let extruder = machine(MACHINE_EXTRUDER_V1);
+        let winder = machine(MACHINE_WINDER_V1);
+        let t0 = Instant::now();
+
+        // step timeout
+        runner.run(LineSequence::Start, t0).unwrap();
+        let mutations = runner.update(t0, &machines);
+        runner.mutation_result(mutations[0].id, Ok(()), t0);
+        machines.disconnected.push(winder.clone());
+        let mutations = runner.update(t0 + Duration::from_secs(60), &machines);
+        // the emergency sequence continues without the winder
+        assert_eq!(
+            sent(&mutations),
+            [(extruder.clone(), json!({ "SetExtruderMode": "Standby" }))]
+        );
+        let progress = runner.progress(t0);
+        assert_eq!(progress.status, SequenceStatus::Completed);
+        assert_eq!(progress.sequence, Some(LineSequence::Emergency));
+        assert_eq!(progress.message.as_deref(), Some("Step heating timed out"));
+
+        // abort condition
+        machines.disconnected.clear();
+        runner.run(LineSequence::Start, t0).unwrap();
+        runner.update(t0, &machines);
+        machines
+            .live_values
+            .insert(extruder, json!({ "pressure": 320.0 }));
+        let mutations = runner.update(t0 + Duration::from_secs(1), &machines);
+        assert_eq!(mutations.len(), 2);
+        assert_eq!(mutations[0].machine, winder);
+        assert!(
+            runner
+                .progress(t0)
+                .message
+                .unwrap()
+                .starts_with("Abort condition extruder /pressure")
+        );
+        // the emergency sequence doesn't wait for its mutations
+        runner.mutation_result(mutations[0].id, Err("failed".to_string()), t0);
+        assert_eq!(runner.progress(t0).status, SequenceStatus::Completed);
+
+        // failed mutation
+        machines.live_values.clear();
+        runner.run(LineSequence::Start, t0).unwrap();
+        let mutations = runner.update(t0, &machines);
+        runner.mutation_result(mutations[0].id, Err("Machine is busy".to_string()), t0);
+        assert_eq!(
+            runner.progress(t0).message.as_deref(),
+            Some("Mutation failed: Machine is busy")
+        );
+        assert_eq!(
+            sent(&runner.update(t0, &machines)),
+            [
+                (winder, json!({ "SetMode": "Standby" })),
+                (
+                    machine(MACHINE_EXTRUDER_V1),
+                    json!({ "SetExtruderMode": "Standby" })
+                )
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut config = config();
+        config.machines.remove("winder");
+        assert!(config.validate().is_err());
+
+        let mut config = self::config();
+        config.emergency.on_abort = Some(LineSequence::Emergency);
+        assert!(config.validate().is_err());
+
+        // start -> emergency -> stop -> emergency
+        let mut config = self::config();
+        config.emergency.on_abort = Some(LineSequence::Stop);
+        config.validate().unwrap();
+        config.stop.on_abort = Some(LineSequence::Emergency);
+        assert!(config.validate().is_err());
+
+        let mut config = self::config();
+        config.start.step_timeout = Some(f64::NAN);
+        assert!(config.validate().is_err());
+        config.start.step_timeout = Some(-1.0);
+        assert!(config.validate().is_err());
+
+        let mut config = self::config();
+        config.start.steps[2].action = StepAction::Delay {
+            seconds: f64::INFINITY,
+        };
+        assert!(config.validate().is_err());
+        config.start.steps[1].timeout = Some(-5.0);
+        config.start.steps[2].action = StepAction::Delay { seconds: 0.0 };
+        assert!(config.validate().is_err());
+    }
+}
@@ -1,24 +1,54 @@
-use crate::app_state::{EthercatSetup, HotThreadMessage};
+use crate::app_state::{EthercatSetup, HotThreadMessage, RecreateMachineRequest};
+use crate::ethercat::recreate::{
+    is_machine_device, machine_device_group, recreate_ethercat_machine,
+};
+use crate::panic::panic_message;
 use crate::performance_metrics::EthercatPerformanceMetrics;
 use bitvec::prelude::*;
 use control_core::realtime::set_core_affinity;
 #[cfg(not(feature = "development-build"))]
 use control_core::realtime::set_realtime_priority;
-use machines::Machine;
-use machines::machine_identification::write_machine_device_identification;
-use smol::channel::Receiver;
+use machines::machine_identification::{
+    MachineIdentificationUnique, write_machine_device_identification,
+};
+use machines::{AsyncThreadMessage, Machine, ShutdownPolicy};
+use smol::channel::{Receiver, Sender};
 use spin_sleep::SpinSleeper;
+use std::panic::{AssertUnwindSafe, catch_unwind};
 use std::time::Duration;
 use std::time::Instant;
 
+use crate::config::{CycleBudgetConfig, ShutdownConfig};
+use crate::metrics::cycle_budget::{CycleProfiler, DevicePhase};
 use crate::metrics::jitter::record_machines_loop_jitter;
 use crate::metrics::preemption::set_rt_loop_tid;
+use crate::shutdown::{request_shutdown, rt_loop_finished};
+
+pub const RT_LOOP_THREAD_NAME: &str = "loop";
+
+/// EtherCAT cycles after all machines reached their safe state, so the outputs surely reached
+/// the devices
+const SHUTDOWN_FLUSH_CYCLES: u32 = 10;
+
 pub struct RtLoopInputs<'a> {
     pub machines: &'a mut Vec<Box<dyn Machine>>,
+    /// Times the machines and devices against their budgets
+    pub profiler: CycleProfiler,
     pub ethercat_setup: Option<Box<EthercatSetup>>,
     pub ethercat_perf_metrics: Option<&'a mut EthercatPerformanceMetrics>,
     pub sleeper: SpinSleeper,
     pub cycle_target: Duration,
+    /// Machines that panicked, cycled with `shutdown` instead of `act` until they are safe
+    pub quarantined: Vec<QuarantinedMachine>,
+    pub shutdown_config: ShutdownConfig,
+    /// Reports the quarantined machines to the main thread
+    pub main_sender: Sender<AsyncThreadMessage>,
+}
+
+pub struct QuarantinedMachine {
+    pub machine: Box<dyn Machine>,
+    /// Dropped after this even if it didn't reach its safe state
+    pub deadline: Instant,
 }
 
 // 300 us loop cycle target
@@ -26,17 +56,21 @@ pub struct RtLoopInputs<'a> {
 pub fn start_loop_thread(
     rt_receiver: Receiver<HotThreadMessage>,
     cycle_target: Duration,
+    core: usize,
+    shutdown_config: ShutdownConfig,
+    main_sender: Sender<AsyncThreadMessage>,
+    cycle_budget: CycleBudgetConfig,
 ) -> Result<std::thread::JoinHandle<()>, std::io::Error> {
     // Start control loop
     let res = std::thread::Builder::new()
-        .name("loop".to_owned())
+        .name(RT_LOOP_THREAD_NAME.to_owned())
         .spawn(move || {
             let rt_receiver = rt_receiver.to_owned();
             let sleeper =
                 SpinSleeper::new(3_333_333) // frequency in Hz ~ 1 / 300µs, Basically specifies the accuracy of our sleep
                     .with_spin_strategy(spin_sleep::SpinStrategy::YieldThread);
 
-            let _ = set_core_affinity(2);
+            let _ = set_core_affinity(core);
 
             // Get thread ID in a platform-specific way
             #[cfg(target_os = "linux")]
@@ -73,10 +107,14 @@ pub fn start_loop_thread(
             let mut last_iter_start: Option<Instant> = None;
             let mut rt_loop_inputs = RtLoopInputs {
                 machines: &mut machines,
+                profiler: CycleProfiler::new(cycle_budget),
                 ethercat_setup: None,
                 sleeper,
                 cycle_target,
                 ethercat_perf_metrics: Some(&mut ethercat_perf),
+                quarantined: vec![],
+                shutdown_config,
+                main_sender,
             };
 
             loop {
@@ -85,90 +123,143 @@ pub fn start_loop_thread(
                     Err(_) => HotThreadMessage::NoMsg,
                 };
 
-                match msg {
-                    HotThreadMessage::NoMsg => {}
-                    HotThreadMessage::AddEtherCatSetup(ethercat_setup) => {
-                        println!("EthercatSetup: {:?}", ethercat_setup.devices);
-                        rt_loop_inputs.ethercat_setup = Some(Box::new(ethercat_setup));
-                    }
-                    HotThreadMessage::WriteMachineDeviceInfo(info_request) => {
-                        if let Some(ethercat_setup) = &rt_loop_inputs.ethercat_setup {
-                            if let Ok(subdevice) = ethercat_setup.group.subdevice(
-                                &ethercat_setup.maindevice,
-                                info_request
-                                    .hardware_identification_ethercat
-                                    .subdevice_index,
-                            ) {
-                                let _res = smol::block_on(write_machine_device_identification(
-                                    &subdevice,
-                                    &ethercat_setup.maindevice,
-                                    &info_request.device_machine_identification,
-                                ));
-                            }
-                        }
-                    }
-                    HotThreadMessage::DeleteMachine(unique_id) => {
-                        rt_loop_inputs
-                            .machines
-                            .retain(|m| m.get_machine_identification_unique() != unique_id);
-                    }
-                    HotThreadMessage::AddMachines(machine_vec) => {
-                        tracing::info!("received machines{:?}", machine_vec);
-                        for new_machine in machine_vec {
-                            let id = new_machine.get_machine_identification_unique();
-                            if !rt_loop_inputs
-                                .machines
-                                .iter()
-                                .any(|m| m.get_machine_identification_unique() == id)
-                            {
-                                rt_loop_inputs.machines.push(new_machine);
-                            }
-                        }
+                // a panic anywhere in here, not just in a machine, must not leave the machines
+                // running with frozen outputs
+                let step = catch_unwind(AssertUnwindSafe(|| {
+                    if let Some(deadline) = handle_message(&mut rt_loop_inputs, msg)? {
+                        return Ok(Some(deadline));
                     }
-                }
-                let iter_start = Instant::now();
-                if let Some(prev) = last_iter_start {
-                    if let Some(period) = iter_start.checked_duration_since(prev) {
+
+                    let iter_start = Instant::now();
+                    if let Some(prev) = last_iter_start
+                        && let Some(period) = iter_start.checked_duration_since(prev)
+                    {
                         let jitter_ns = period.as_nanos() as i128
                             - rt_loop_inputs.cycle_target.as_nanos() as i128;
                         record_machines_loop_jitter(jitter_ns);
                     }
-                }
-                last_iter_start = Some(iter_start);
-
-                if let Err(e) = loop_once(&mut rt_loop_inputs) {
-                    tracing::error!(
-                        "Loop failed\n {:?} \n Last Loop Took: {:?}",
-                        e,
-                        rt_loop_inputs
-                            .ethercat_perf_metrics
-                            .unwrap()
-                            .last_loop_start
-                            .unwrap()
-                            .elapsed()
-                    );
-                    break;
-                }
+                    last_iter_start = Some(iter_start);
+
+                    loop_once(&mut rt_loop_inputs).map(|()| None)
+                }));
+                let error = match step {
+                    Ok(Ok(None)) => continue,
+                    Ok(Ok(Some(deadline))) => {
+                        finish_loop(&mut rt_loop_inputs, deadline);
+                        return;
+                    }
+                    Ok(Err(e)) => format!("{:?}", e),
+                    Err(_) => "panicked".to_string(),
+                };
+                tracing::error!(
+                    "Loop failed\n {} \n Last Loop Took: {:?}",
+                    error,
+                    rt_loop_inputs
+                        .ethercat_perf_metrics
+                        .as_deref()
+                        .and_then(|metrics| metrics.last_loop_start)
+                        .map(|start| start.elapsed())
+                );
+                break;
             }
 
-            // Exit the entire program if the Loop fails
+            // Exit the entire program if the Loop fails, the machines get into their safe state as far as possible
             // gets restarted by systemd if running on NixOS, or different distro wtih the same sysd service
-            std::process::exit(1);
+            let deadline = Instant::now() + rt_loop_inputs.shutdown_config.deadline();
+            finish_loop(&mut rt_loop_inputs, deadline);
+            request_shutdown(1, "Loop failed");
         });
     return res;
 }
 
+/// Handles a message of the main thread, returns the deadline of a requested shutdown
+fn handle_message(
+    inputs: &mut RtLoopInputs<'_>,
+    msg: HotThreadMessage,
+) -> Result<Option<Instant>, anyhow::Error> {
+    match msg {
+        HotThreadMessage::NoMsg => {}
+        HotThreadMessage::AddEtherCatSetup(ethercat_setup) => {
+            println!("EthercatSetup: {:?}", ethercat_setup.devices);
+            inputs.ethercat_setup = Some(Box::new(ethercat_setup));
+        }
+        HotThreadMessage::WriteMachineDeviceInfo(info_request) => {
+            if let Some(ethercat_setup) = &inputs.ethercat_setup {
+                if let Ok(subdevice) = ethercat_setup.group.subdevice(
+                    &ethercat_setup.maindevice,
+                    info_request
+                        .hardware_identification_ethercat
+                        .subdevice_index,
+                ) {
+                    let _res = smol::block_on(write_machine_device_identification(
+                        &subdevice,
+                        &ethercat_setup.maindevice,
+                        &info_request.device_machine_identification,
+                    ));
+                }
+            }
+        }
+        HotThreadMessage::DeleteMachine(unique_id) => {
+            inputs
+                .machines
+                .retain(|m| m.get_machine_identification_unique() != unique_id);
+            inputs.quarantined.retain(|quarantined| {
+                quarantined.machine.get_machine_identification_unique() != unique_id
+            });
+            inputs.profiler.remove_machine(&unique_id);
+        }
+        HotThreadMessage::AddMachines(machine_vec) => {
+            tracing::info!("received machines{:?}", machine_vec);
+            for new_machine in machine_vec {
+                let id = new_machine.get_machine_identification_unique();
+                if !inputs
+                    .machines
+                    .iter()
+                    .any(|m| m.get_machine_identification_unique() == id)
+                {
+                    inputs.machines.push(new_machine);
+                }
+            }
+        }
+        HotThreadMessage::Shutdown(deadline) => return Ok(Some(deadline)),
+        HotThreadMessage::RecreateMachine(request) => {
+            recreate_machine(inputs, &request)
+                .map_err(|e| e.context("Re-creating a machine failed"))?;
+        }
+    }
+    Ok(None)
+}
+
+/// Brings the machines into their safe state and tells the main thread the loop is done, even if
+/// something besides the machines panics on the way
+fn finish_loop(inputs: &mut RtLoopInputs<'_>, deadline: Instant) {
+    let policy = inputs.shutdown_config.policy();
+    if catch_unwind(AssertUnwindSafe(|| {
+        shutdown_machines(inputs, deadline, &policy)
+    }))
+    .is_err()
+    {
+        tracing::error!("Shutting down the machines panicked");
+    }
+    rt_loop_finished();
+}
+
+/// Returns the duration of the tx/rx cycle, zero without an ethercat setup
 pub async fn copy_ethercat_inputs(
     ethercat_setup: Option<&EthercatSetup>,
-) -> Result<(), anyhow::Error> {
+    profiler: &mut CycleProfiler,
+) -> Result<Duration, anyhow::Error> {
+    let mut txrx_time = Duration::ZERO;
     // only if we have an ethercat setup
     // - tx/rx cycle
     // - copy inputs to devices
     if let Some(ethercat_setup) = ethercat_setup {
+        let txrx_start = Instant::now();
         ethercat_setup
             .group
             .tx_rx(&ethercat_setup.maindevice)
             .await?;
+        txrx_time = txrx_start.elapsed();
 
         // copy inputs to devices
         for (i, subdevice) in ethercat_setup
@@ -200,6 +291,7 @@ pub async fn copy_ethercat_inputs(
             })?;
 
             // post process inputs
+            let post_process_start = Instant::now();
             device.input_post_process().map_err(|e| {
                 anyhow::anyhow!(
                     "[{}::loop_once] SubDevice with index {} failed to copy post_process\n{:?}",
@@ -208,13 +300,21 @@ pub async fn copy_ethercat_inputs(
                     e
                 )
             })?;
+            drop(device);
+            profiler.record_device(
+                i,
+                subdevice.name(),
+                DevicePhase::InputPostProcess,
+                post_process_start.elapsed(),
+            );
         }
     }
-    Ok(())
+    Ok(txrx_time)
 }
 
 pub async fn copy_ethercat_outputs(
     ethercat_setup: Option<&EthercatSetup>,
+    profiler: &mut CycleProfiler,
 ) -> Result<(), anyhow::Error> {
     if let Some(ethercat_setup) = ethercat_setup {
         // copy outputs from devices
@@ -237,6 +337,7 @@ pub async fn copy_ethercat_outputs(
             }
 
             // pre process outputs
+            let pre_process_start = Instant::now();
             device.output_pre_process().map_err(|e| {
                 anyhow::anyhow!(
                     "[{}::loop_once] SubDevice with index {} failed to pre process outputs \n{:?}",
@@ -245,6 +346,12 @@ pub async fn copy_ethercat_outputs(
                     e
                 )
             })?;
+            profiler.record_device(
+                i,
+                subdevice.name(),
+                DevicePhase::OutputPreProcess,
+                pre_process_start.elapsed(),
+            );
 
             // put outputs into device
             device.output_checked(output_bits).map_err(|e| {
@@ -260,12 +367,213 @@ pub async fn copy_ethercat_outputs(
     Ok(())
 }
 
-pub fn execute_machines(machines: &mut Vec<Box<dyn Machine>>) {
+/// Returns the machines that panicked, taken out of `machines`, with the panic message
+pub fn execute_machines(
+    machines: &mut Vec<Box<dyn Machine>>,
+    profiler: &mut CycleProfiler,
+) -> Vec<(Box<dyn Machine>, String)> {
     let now = Instant::now();
-    for machine in machines.iter_mut() {
-        machine.act(now);
+    let mut panicked = vec![];
+    for (i, machine) in machines.iter_mut().enumerate() {
+        let act_start = Instant::now();
+        let result = catch_unwind(AssertUnwindSafe(|| machine.act(now)));
+        let act_time = act_start.elapsed();
+
+        profiler.record_machine_act(&machine.get_machine_identification_unique(), act_time);
+
+        if let Err(payload) = result {
+            panicked.push((i, panic_message(payload.as_ref())));
+        }
+    }
+
+    panicked
+        .into_iter()
+        .rev()
+        .map(|(i, message)| (machines.remove(i), message))
+        .collect()
+}
+
+/// Moves panicked machines into quarantine and reports them, the other machines keep running
+fn quarantine_machines(inputs: &mut RtLoopInputs<'_>, panicked: Vec<(Box<dyn Machine>, String)>) {
+    let deadline = Instant::now() + inputs.shutdown_config.deadline();
+    for (machine, message) in panicked {
+        let id = machine.get_machine_identification_unique();
+        if let Err(e) = inputs
+            .main_sender
+            .try_send(AsyncThreadMessage::MachineQuarantined(id, message))
+        {
+            // the main thread is gone, at least the log has the panic
+            if let AsyncThreadMessage::MachineQuarantined(id, message) = e.into_inner() {
+                tracing::error!(
+                    "Machine {} is quarantined, reporting failed: {}",
+                    id,
+                    message
+                );
+            }
+        }
+        inputs
+            .quarantined
+            .push(QuarantinedMachine { machine, deadline });
+    }
+}
+
+/// Drops quarantined machines once they reached their safe state, their deadline passed or
+/// they panicked again, then zeroes the outputs of their devices
+fn shutdown_quarantined_machines(inputs: &mut RtLoopInputs<'_>, now: Instant) {
+    let policy = inputs.shutdown_config.policy();
+    let setup = inputs.ethercat_setup.as_deref();
+    inputs.quarantined.retain_mut(|quarantined| {
+        let shutdown = catch_unwind(AssertUnwindSafe(|| {
+            quarantined.machine.shutdown(now, &policy)
+        }));
+        let safe = matches!(shutdown, Ok(true));
+        if !safe && shutdown.is_ok() && now < quarantined.deadline {
+            return true;
+        }
+
+        let id = quarantined.machine.get_machine_identification_unique();
+        if !safe {
+            tracing::error!(
+                "Machine {} is dropped before reaching its safe state, zeroing its outputs",
+                id
+            );
+        }
+        release_machine_devices(setup, &id);
+        false
+    });
+}
+
+/// Zeroes the outputs of the devices of a dropped machine and takes them out of the output copy
+/// until the machine is created again
+fn release_machine_devices(setup: Option<&EthercatSetup>, id: &MachineIdentificationUnique) {
+    let Some(setup) = setup else {
+        return;
+    };
+    for (subdevice, (identification, device)) in
+        setup.group.iter(&setup.maindevice).zip(&setup.devices)
+    {
+        if is_machine_device(identification, id) {
+            smol::block_on(device.write()).set_used(false);
+            subdevice.outputs_raw_mut().fill(0);
+        }
+    }
+}
+
+/// Responds to the request with the api sender of the new machine or why it wasn't created
+///
+/// An `Err` means the EtherCAT bus is lost.
+fn recreate_machine(
+    inputs: &mut RtLoopInputs<'_>,
+    request: &RecreateMachineRequest,
+) -> Result<(), anyhow::Error> {
+    let id = &request.machine_identification_unique;
+    let is_id = |machine: &dyn Machine| &machine.get_machine_identification_unique() == id;
+    let device_group = if inputs.machines.iter().any(|m| is_id(m.as_ref())) {
+        Err(format!("Machine {} is running", id))
+    } else if inputs.quarantined.iter().any(|q| is_id(q.machine.as_ref())) {
+        Err(format!("Machine {} is still going to its safe state", id))
+    } else {
+        inputs
+            .ethercat_setup
+            .as_deref()
+            .and_then(|setup| machine_device_group(setup, id))
+            .ok_or_else(|| format!("Machine {} has no devices on the EtherCAT bus", id))
+    };
+    let device_group = match device_group {
+        Ok(device_group) => device_group,
+        Err(e) => {
+            let _ = request.response.try_send(Err(e));
+            return Ok(());
+        }
+    };
+
+    let machine =
+        match recreate_ethercat_machine(&mut inputs.ethercat_setup, &device_group, request) {
+            Ok(machine) => machine,
+            Err(e) => {
+                let _ = request
+                    .response
+                    .try_send(Err(format!("Machine {} wasn't created, {}", id, e)));
+                return Err(e);
+            }
+        };
+
+    let response = machine
+        .map(|machine| {
+            let api_sender = machine.api_get_sender();
+            inputs.machines.push(machine);
+            api_sender
+        })
+        .map_err(|e| e.to_string());
+    let _ = request.response.try_send(response);
+    Ok(())
+}
+
+/// Cycles the machines with [`machines::MachineAct::shutdown`] instead of `act` until all reached
+/// their safe state or the deadline passed, then flushes the outputs with a few more cycles
+pub fn shutdown_machines(
+    inputs: &mut RtLoopInputs<'_>,
+    deadline: Instant,
+    policy: &ShutdownPolicy,
+) {
+    // they might still be on their way to the safe state
+    let quarantined = inputs.quarantined.drain(..).map(|q| q.machine);
+    inputs.machines.extend(quarantined);
+
+    let mut ethercat_ok = inputs.ethercat_setup.is_some();
+    let mut flush_cycles = 0;
+    loop {
+        let cycle_start = Instant::now();
+        if ethercat_ok {
+            if let Err(e) = smol::block_on(copy_ethercat_inputs(
+                inputs.ethercat_setup.as_deref(),
+                &mut inputs.profiler,
+            )) {
+                tracing::error!("Shutdown without EtherCAT, copying inputs failed: {:?}", e);
+                ethercat_ok = false;
+            }
+        }
+
+        let mut unsafe_machines = vec![];
+        for machine in inputs.machines.iter_mut() {
+            let safe = catch_unwind(AssertUnwindSafe(|| machine.shutdown(cycle_start, policy)))
+                .unwrap_or(false);
+            if !safe {
+                unsafe_machines.push(machine.get_machine_identification_unique());
+            }
+        }
+
+        if ethercat_ok {
+            if let Err(e) = smol::block_on(copy_ethercat_outputs(
+                inputs.ethercat_setup.as_deref(),
+                &mut inputs.profiler,
+            )) {
+                tracing::error!("Shutdown without EtherCAT, copying outputs failed: {:?}", e);
+                ethercat_ok = false;
+            }
+        }
+
+        if unsafe_machines.is_empty() {
+            if !ethercat_ok || flush_cycles == SHUTDOWN_FLUSH_CYCLES {
+                tracing::info!("All machines are in their safe state");
+                return;
+            }
+            flush_cycles += 1;
+        }
+        if cycle_start >= deadline {
+            tracing::warn!(
+                "Shutdown deadline passed, machines not in their safe state: {:?}",
+                unsafe_machines
+            );
+            return;
+        }
+
+        inputs
+            .sleeper
+            .sleep_until(cycle_start + inputs.cycle_target);
     }
 }
+
 // No more logging in loop_once
 pub fn loop_once<'maindevice>(inputs: &mut RtLoopInputs<'_>) -> Result<(), anyhow::Error> {
     let loop_once_start = std::time::Instant::now();
@@ -276,19 +584,31 @@ pub fn loop_once<'maindevice>(inputs: &mut RtLoopInputs<'_>) -> Result<(), anyho
             .unwrap()
             .cycle_start();
 
-        let res = smol::block_on(copy_ethercat_inputs(inputs.ethercat_setup.as_deref()));
+        let res = smol::block_on(copy_ethercat_inputs(
+            inputs.ethercat_setup.as_deref(),
+            &mut inputs.profiler,
+        ));
         match res {
-            Ok(_) => (),
+            Ok(txrx_time) => inputs
+                .ethercat_perf_metrics
+                .as_deref_mut()
+                .unwrap()
+                .add_txrx_time(txrx_time),
             Err(e) => {
                 return Err(anyhow::anyhow!("copy_ethercat_inputs failed: {:?}", e));
             }
         };
     }
 
-    execute_machines(&mut inputs.machines);
+    let panicked = execute_machines(inputs.machines, &mut inputs.profiler);
+    quarantine_machines(inputs, panicked);
+    shutdown_quarantined_machines(inputs, Instant::now());
 
     if inputs.ethercat_setup.is_some() && inputs.ethercat_perf_metrics.is_some() {
-        let res = smol::block_on(copy_ethercat_outputs(inputs.ethercat_setup.as_deref()));
+        let res = smol::block_on(copy_ethercat_outputs(
+            inputs.ethercat_setup.as_deref(),
+            &mut inputs.profiler,
+        ));
         match res {
             Ok(_) => (),
             Err(e) => {
@@ -314,3 +634,130 @@ pub fn loop_once<'maindevice>(inputs: &mut RtLoopInputs<'_>) -> Result<(), anyho
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use control_core::socketio::namespace::Namespace;
+    use machines::machine_identification::{MachineIdentification, MachineIdentificationUnique};
+    use machines::{MachineAct, MachineApi, MachineMessage};
+
+    #[derive(Debug)]
+    struct TestMachine {
+        serial: u16,
+        panics: bool,
+        /// `shutdown` calls until the machine is safe
+        shutdown_cycles: u32,
+        api_sender: Sender<MachineMessage>,
+    }
+
+    impl TestMachine {
+        fn boxed(serial: u16, panics: bool) -> Box<dyn Machine> {
+            Box::new(Self {
+                serial,
+                panics,
+                shutdown_cycles: 2,
+                api_sender: smol::channel::unbounded().0,
+            })
+        }
+    }
+
+    impl MachineAct for TestMachine {
+        fn act_machine_message(&mut self, _msg: MachineMessage) {}
+
+        fn act(&mut self, _now: Instant) {
+            if self.panics {
+                panic!("broken controller");
+            }
+        }
+
+        fn shutdown(&mut self, _now: Instant, _policy: &ShutdownPolicy) -> bool {
+            self.shutdown_cycles = self.shutdown_cycles.saturating_sub(1);
+            self.shutdown_cycles == 0
+        }
+    }
+
+    impl MachineApi for TestMachine {
+        fn api_get_sender(&self) -> Sender<MachineMessage> {
+            self.api_sender.clone()
+        }
+
+        fn api_mutate(&mut self, _value: serde_json::Value) -> Result<(), anyhow::Error> {
+            Ok(())
+        }
+
+        fn api_event_namespace(&mut self) -> Option<Namespace> {
+            None
+        }
+    }
+
+    impl Machine for TestMachine {
+        fn get_machine_identification_unique(&self) -> MachineIdentificationUnique {
+            MachineIdentificationUnique {
+                machine_identification: MachineIdentification {
+                    vendor: 0xfff1,
+                    machine: 1,
+                },
+                serial: self.serial,
+            }
+        }
+
+        fn get_main_sender(&self) -> Option<Sender<AsyncThreadMessage>> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_panicking_machine_is_quarantined() {
+        let mut machines = vec![
+            TestMachine::boxed(1, false),
+            TestMachine::boxed(2, true),
+            TestMachine::boxed(3, false),
+        ];
+        let (main_sender, main_receiver) = smol::channel::unbounded();
+        let mut inputs = RtLoopInputs {
+            machines: &mut machines,
+            profiler: CycleProfiler::new(CycleBudgetConfig::default()),
+            ethercat_setup: None,
+            ethercat_perf_metrics: None,
+            sleeper: SpinSleeper::default(),
+            cycle_target: Duration::from_millis(1),
+            quarantined: vec![],
+            shutdown_config: ShutdownConfig::default(),
+            main_sender,
+        };
+
+        loop_once(&mut inputs).unwrap();
+        let serials = |machines: &Vec<Box<dyn Machine>>| {
+            machines
+                .iter()
+                .map(|m| m.get_machine_identification_unique().serial)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(serials(inputs.machines), vec![1, 3]);
+        assert_eq!(inputs.quarantined.len(), 1);
+        match main_receiver.try_recv() {
+            Ok(AsyncThreadMessage::MachineQuarantined(machine, message)) => {
+                assert_eq!(machine.serial, 2);
+                assert_eq!(message, "broken controller");
+            }
+            _ => panic!("quarantine not reported"),
+        }
+
+        let (response, response_rx) = smol::channel::bounded(1);
+        let request = RecreateMachineRequest {
+            machine_identification_unique: TestMachine::boxed(2, false)
+                .get_machine_identification_unique(),
+            socket_queue_tx: smol::channel::unbounded().0,
+            main_channel: smol::channel::unbounded().0,
+            response,
+        };
+        recreate_machine(&mut inputs, &request).unwrap();
+        assert!(response_rx.try_recv().unwrap().is_err());
+
+        // dropped once it reached its safe state, the others keep running
+        loop_once(&mut inputs).unwrap();
+        assert!(inputs.quarantined.is_empty());
+        assert_eq!(serials(inputs.machines), vec![1, 3]);
+    }
+}
@@ -1,36 +1,31 @@
 use crate::{
     metrics::collector::{RuntimeMetricsConfig, spawn_runtime_metrics_sampler},
+    metrics::cycle_budget::spawn_overrun_logger,
     socketio::main_namespace::machines_event::MachineObj,
 };
+use app_state::{HotThreadMessage, SharedState};
+use ethercat::ethercat_discovery_info::send_ethercat_discovering;
+use r#loop::start_loop_thread;
 use machines::{
     AsyncThreadMessage, MachineConnection, MachineNewHardware, MachineNewHardwareSerial,
-    MachineNewParams, SerialDevice, SerialDeviceIdentification, SerialDeviceNew,
-    SerialDeviceNewParams,
-    laser::LaserMachine,
+    MachineNewParams, SerialDevice,
     machine_identification::{
         DeviceIdentification, DeviceIdentificationIdentified, MachineIdentificationUnique,
     },
-    registry::{MACHINE_REGISTRY, MachineRegistry},
-    serial::{devices::laser::Laser, init::SerialDetection},
+    registry::MachineRegistry,
     winder2::api::GenericEvent,
 };
-#[cfg(feature = "development-build")]
-use std::sync::atomic::{AtomicBool, Ordering};
-
-use app_state::{HotThreadMessage, SharedState};
-use ethercat::ethercat_discovery_info::send_ethercat_discovering;
-use r#loop::start_loop_thread;
 use metrics::io::set_ethercat_iface;
 use panic::init_panic_handling;
 use rest::init::start_api_thread;
-use serialport::UsbPortInfo;
+use shutdown::{init_shutdown, setup_signal_handler};
 use smol::{
     channel::{Receiver, Sender},
     future,
     lock::RwLock,
 };
 use socketioxide::extract::SocketRef;
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::sync::Arc;
 
 #[cfg(feature = "mock-machine")]
 use mock_init::init_mock;
@@ -41,6 +36,7 @@ use crate::{
         setup::setup_loop,
     },
     modbus_tcp::start_modbus_tcp_discovery,
+    serial::start_serial_discovery,
     socketio::queue::socketio_queue_worker,
 };
 
@@ -48,14 +44,23 @@ use crate::{
 pub mod mock_init;
 
 pub mod app_state;
+pub mod audit;
+pub mod auth;
+pub mod config;
 pub mod ethercat;
+pub mod inverter_backup;
+pub mod jsonl_store;
+pub mod line;
 pub mod logging;
 pub mod r#loop;
 pub mod metrics;
 pub mod modbus_tcp;
 pub mod panic;
 pub mod performance_metrics;
+pub mod production;
 pub mod rest;
+pub mod serial;
+pub mod shutdown;
 pub mod socketio;
 
 pub async fn send_empty_machines_event(shared_state: Arc<SharedState>) {
@@ -69,7 +74,7 @@ pub async fn add_serial_device(
     device: Arc<RwLock<dyn SerialDevice>>,
     machine_registry: &MachineRegistry,
     socket_queue_tx: Sender<(SocketRef, Arc<GenericEvent>)>,
-) {
+) -> Result<(), anyhow::Error> {
     tracing::info!("add_serial_device");
     let hardware = MachineNewHardwareSerial { device };
 
@@ -91,13 +96,7 @@ pub async fn add_serial_device(
         main_thread_channel: Some(shared_state.main_channel.clone()),
     });
 
-    let machine = match new_machine {
-        Ok(machine) => machine,
-        Err(e) => {
-            tracing::error!("{:?}", e);
-            return;
-        }
-    };
+    let machine = new_machine?;
 
     shared_state
         .add_machines_if_not_exists(vec![MachineObj {
@@ -107,34 +106,22 @@ pub async fn add_serial_device(
         .await;
 
     shared_state
-        .api_machines
-        .lock()
-        .await
-        .insert(machine_identification, machine.api_get_sender());
+        .insert_api_machine(machine_identification, machine.api_get_sender())
+        .await;
 
     let _ = shared_state
         .rt_machine_creation_channel
         .send(HotThreadMessage::AddMachines(vec![machine]))
         .await;
     shared_state.clone().send_machines_event().await;
-}
-
-pub async fn start_serial_discovery(app_state: Arc<SharedState>) {
-    loop {
-        let devices = SerialDetection::detect_devices();
-
-        // This allows detection of disconnected devices
-        handle_serial_device_hotplug(app_state.clone(), devices).await;
-
-        smol::Timer::after(Duration::from_secs(1)).await;
-    }
+    Ok(())
 }
 
 pub async fn start_interface_discovery(
     app_state: Arc<SharedState>,
     sender: Sender<HotThreadMessage>,
 ) {
-    let interface = find_ethercat_interface().await;
+    let interface = find_ethercat_interface(&app_state.config).await;
     tracing::info!("Inferface found {}, setting up EtherCAT loop", interface);
     set_ethercat_iface(interface.clone());
 
@@ -158,68 +145,6 @@ pub async fn start_interface_discovery(
     send_ethercat_found(app_state.clone(), &interface).await;
 }
 
-pub async fn handle_serial_device_hotplug(
-    app_state: Arc<SharedState>,
-    map: HashMap<String, UsbPortInfo>,
-) {
-    let laser_ident = SerialDeviceIdentification {
-        vendor_id: 0x0403,
-        product_id: 0x6001,
-    };
-
-    let laser = SerialDetection::get_path_by_id(laser_ident, map);
-    let mut unique_ident: Option<MachineIdentificationUnique> = None;
-
-    {
-        let machines = app_state.current_machines_meta.lock().await;
-        for machine in machines.iter() {
-            if machine.machine_identification_unique.machine_identification
-                == LaserMachine::MACHINE_IDENTIFICATION
-            {
-                unique_ident = Some(machine.machine_identification_unique.clone());
-                break;
-            }
-        }
-    }
-
-    // Machine isnt connected, so add it back
-    if laser.is_some() && unique_ident.is_none() {
-        let serial_params = SerialDeviceNewParams {
-            path: laser.unwrap(),
-        };
-        match Laser::new_serial(&serial_params) {
-            Ok((device_identification, serial_device)) => {
-                add_serial_device(
-                    app_state.clone(),
-                    &device_identification,
-                    serial_device,
-                    &MACHINE_REGISTRY,
-                    app_state.socketio_setup.socket_queue_tx.clone(),
-                )
-                .await;
-            }
-            _ => (),
-        };
-    } else if laser.is_none() && unique_ident.is_some() {
-        let unique_ident = unique_ident.unwrap();
-        app_state
-            .clone()
-            .api_machines
-            .lock()
-            .await
-            .remove(&unique_ident);
-        app_state.clone().remove_machine(&unique_ident).await;
-
-        let _ = app_state
-            .clone()
-            .rt_machine_creation_channel
-            .send(HotThreadMessage::DeleteMachine(unique_ident))
-            .await;
-
-        app_state.clone().send_machines_event().await;
-    }
-}
-
 async fn handle_async_requests(recv: Receiver<AsyncThreadMessage>, shared_state: Arc<SharedState>) {
     while let Ok(message) = recv.recv().await {
         match message {
@@ -285,6 +210,21 @@ async fn handle_async_requests(recv: Receiver<AsyncThreadMessage>, shared_state:
                     ),
                 }
             }
+            AsyncThreadMessage::SpoolCompleted(record) => {
+                production::store_production_record(*record);
+            }
+            AsyncThreadMessage::RunLineSequence(command) => {
+                line::run_line_sequence(&shared_state, command).await;
+            }
+            AsyncThreadMessage::MachineQuarantined(machine_identification_unique, message) => {
+                shared_state
+                    .quarantine_machine(machine_identification_unique, message)
+                    .await;
+            }
+            AsyncThreadMessage::InverterBackupFinished(machine_identification_unique, backup) => {
+                inverter_backup::store_inverter_backup(machine_identification_unique, *backup)
+                    .await;
+            }
         }
     }
 
@@ -300,20 +240,6 @@ pub async fn start_socketio_queue(app_state: Arc<SharedState>) {
     }
 }
 
-#[cfg(feature = "development-build")]
-fn setup_ctrlc_handler() -> Arc<AtomicBool> {
-    let running = Arc::new(AtomicBool::new(true));
-    let r = running.clone();
-
-    ctrlc::set_handler(move || {
-        eprintln!("Ctrl-C pressed, shutting down...");
-        r.store(false, Ordering::SeqCst);
-    })
-    .expect("Error setting Ctrl-C handler");
-
-    running
-}
-
 #[cfg(feature = "heap-profile")]
 #[global_allocator]
 static ALLOC: dhat::Alloc = dhat::Alloc;
@@ -327,23 +253,44 @@ fn main() {
     #[cfg(feature = "heap-profile")]
     let _profiler = dhat::Profiler::new_heap();
 
-    #[cfg(feature = "development-build")]
-    let running = setup_ctrlc_handler();
-
-    const CYCLE_TARGET_TIME: Duration = Duration::from_micros(700);
+    let config = config::load_server_config();
+    machines::wago_power::set_wago_power_config(config.wago_power.clone())
+        .expect("WAGO power config is set once, after validation");
+    machines::frequency_inverter::set_frequency_inverter_config(config.extruder_inverter)
+        .expect("Frequency inverter config is set once, after validation");
+    machines::serial::devices::laser::config::set_laser_config_path(
+        config.laser.config_path.clone().map(Into::into),
+    )
+    .expect("Laser config path is set once");
+    audit::set_audit_log_path(&config.audit.log_path).expect("Audit log path is set once");
+    production::set_production_records_path(&config.production.records_path)
+        .expect("Production records path is set once");
+    inverter_backup::set_inverter_backups_dir(&config.inverter_backups.dir)
+        .expect("Inverter backups directory is set once");
 
     // for the "hot thread"
     let (sender, receiver) = smol::channel::unbounded();
     let (main_sender, main_receiver) = smol::channel::unbounded();
-    let shared_state = SharedState::new(sender.clone(), main_sender);
-    let app_state = Arc::new(shared_state);
-    let _loop_thread = start_loop_thread(receiver, CYCLE_TARGET_TIME);
-    let _ = start_api_thread(app_state.clone());
+    let _loop_thread = start_loop_thread(
+        receiver,
+        config.cycle_target(),
+        config.rt_loop.core,
+        config.shutdown.clone(),
+        main_sender.clone(),
+        config.cycle_budget.clone(),
+    );
+    spawn_overrun_logger();
     spawn_runtime_metrics_sampler(RuntimeMetricsConfig {
-        csv_path: "runtime_metrics.csv".to_string(),
-        interval: Duration::from_secs(1),
+        csv_path: config.runtime_metrics.csv_path.clone(),
+        interval: config.runtime_metrics_interval(),
         ethercat_iface: None,
     });
+    let shutdown_config = config.shutdown.clone();
+    let shared_state = SharedState::new(sender.clone(), main_sender, config);
+    let app_state = Arc::new(shared_state);
+    init_shutdown(app_state.clone(), main_receiver.clone(), shutdown_config);
+    setup_signal_handler();
+    let _ = start_api_thread(app_state.clone());
 
     let mut socketio_task = smol::spawn(start_socketio_queue(app_state.clone()));
     let mut serial_task = smol::spawn(start_serial_discovery(app_state.clone()));
@@ -356,6 +303,7 @@ fn main() {
     smol::spawn(start_interface_discovery(app_state.clone(), sender)).detach();
 
     smol::spawn(start_modbus_tcp_discovery(app_state.clone())).detach();
+    smol::spawn(line::start_line_orchestration(app_state.clone())).detach();
 
     smol::block_on(async {
         send_empty_machines_event(app_state.clone()).await;
@@ -367,12 +315,6 @@ fn main() {
 
     smol::block_on(async {
         loop {
-            #[cfg(feature = "development-build")]
-            if !running.load(Ordering::SeqCst) {
-                tracing::info!("Shutdown signal received, exiting main loop.");
-                break;
-            }
-
             if serial_task.is_finished() {
                 tracing::warn!("Serial task died! Restarting...");
                 serial_task.cancel().await;
@@ -0,0 +1,425 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use concurrent_queue::ConcurrentQueue;
+use machines::machine_identification::MachineIdentificationUnique;
+use schemars::JsonSchema;
+use serde::Serialize;
+use smol::Timer;
+
+use crate::config::CycleBudgetConfig;
+use crate::metrics::histogram::{Histogram, HistogramSnapshot};
+use crate::metrics::prometheus::{
+    device_histogram, device_snapshots, machine_act_histogram, machine_act_snapshots,
+    remove_machine_act_histogram,
+};
+
+/// Overruns of one component are logged at most this often, the log has all of them
+const WARNING_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Overruns the RT loop can queue before the logger takes them, further ones are only counted
+const PENDING_CAPACITY: usize = 1024;
+
+/// How often the logger takes the queued overruns
+const LOGGER_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DevicePhase {
+    InputPostProcess,
+    OutputPreProcess,
+}
+
+impl DevicePhase {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::InputPostProcess => "input_post_process",
+            Self::OutputPreProcess => "output_pre_process",
+        }
+    }
+}
+
+/// Part of the RT loop cycle that has a budget
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CycleComponent {
+    MachineAct {
+        machine: MachineIdentificationUnique,
+    },
+    Device {
+        subdevice_index: usize,
+        name: String,
+        phase: DevicePhase,
+    },
+}
+
+impl fmt::Display for CycleComponent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MachineAct { machine } => write!(f, "act of machine {}", machine),
+            Self::Device {
+                subdevice_index,
+                name,
+                phase,
+            } => write!(
+                f,
+                "{} of device {} ({})",
+                phase.as_str(),
+                subdevice_index,
+                name
+            ),
+        }
+    }
+}
+
+/// A component that took longer than its budget
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct Overrun {
+    /// Unix timestamp in ms
+    pub timestamp_ms: u64,
+    pub component: CycleComponent,
+    pub duration_us: f64,
+    pub budget_us: f64,
+}
+
+/// Timing of a component against its budget
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+pub struct ComponentBudget {
+    pub component: CycleComponent,
+    pub budget_us: f64,
+    pub count: u64,
+    pub min_us: Option<f64>,
+    pub avg_us: Option<f64>,
+    pub max_us: Option<f64>,
+    pub p99_us: Option<f64>,
+    pub overruns: u64,
+}
+
+/// Overruns since the last warning about a component
+#[derive(Default)]
+struct Warning {
+    unreported: u64,
+    last_warning: Option<Instant>,
+}
+
+struct OverrunLog {
+    entries: VecDeque<Overrun>,
+    capacity: usize,
+    /// All overruns since the start, also the ones that fell out of `entries`
+    counts: HashMap<CycleComponent, u64>,
+    warnings: HashMap<CycleComponent, Warning>,
+}
+
+impl OverrunLog {
+    /// Takes the overruns the RT loop queued, warns at most every [`WARNING_INTERVAL`] per
+    /// component
+    fn take_pending(&mut self) {
+        let dropped = DROPPED_OVERRUNS.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            tracing::warn!(
+                "{} cycle budget overruns were dropped, the queue was full",
+                dropped
+            );
+        }
+
+        let now = Instant::now();
+        while let Ok(overrun) = pending_overruns().pop() {
+            *self.counts.entry(overrun.component.clone()).or_default() += 1;
+
+            let warning = self.warnings.entry(overrun.component.clone()).or_default();
+            warning.unreported += 1;
+            if warning
+                .last_warning
+                .is_none_or(|last| now.duration_since(last) >= WARNING_INTERVAL)
+            {
+                tracing::warn!(
+                    "The {} took {:.0} µs, over its budget of {:.0} µs ({} overruns since the last warning)",
+                    overrun.component,
+                    overrun.duration_us,
+                    overrun.budget_us,
+                    warning.unreported
+                );
+                warning.unreported = 0;
+                warning.last_warning = Some(now);
+            }
+
+            while self.entries.len() >= self.capacity {
+                self.entries.pop_front();
+            }
+            self.entries.push_back(overrun);
+        }
+    }
+}
+
+static OVERRUN_LOG: OnceLock<Mutex<OverrunLog>> = OnceLock::new();
+
+fn overrun_log() -> &'static Mutex<OverrunLog> {
+    OVERRUN_LOG.get_or_init(|| {
+        Mutex::new(OverrunLog {
+            entries: VecDeque::new(),
+            capacity: CycleBudgetConfig::default().overrun_log_len,
+            counts: HashMap::new(),
+            warnings: HashMap::new(),
+        })
+    })
+}
+
+/// Overruns of the RT loop that are not in the log yet, pushing doesn't lock
+static PENDING: OnceLock<ConcurrentQueue<Overrun>> = OnceLock::new();
+
+/// Overruns that didn't fit into [`PENDING`]
+static DROPPED_OVERRUNS: AtomicU64 = AtomicU64::new(0);
+
+fn pending_overruns() -> &'static ConcurrentQueue<Overrun> {
+    PENDING.get_or_init(|| ConcurrentQueue::bounded(PENDING_CAPACITY))
+}
+
+/// Called on the RT thread, the overrun is logged by [`spawn_overrun_logger`]
+fn record_overrun(overrun: Overrun) {
+    if pending_overruns().push(overrun).is_err() {
+        DROPPED_OVERRUNS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Start a background task that moves the overruns of the RT loop into the log and warns
+/// about them, off the RT thread.
+pub fn spawn_overrun_logger() {
+    smol::spawn(async move {
+        loop {
+            overrun_log()
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .take_pending();
+            Timer::after(LOGGER_INTERVAL).await;
+        }
+    })
+    .detach();
+}
+
+/// The latest overruns, oldest first
+pub fn overruns() -> Vec<Overrun> {
+    let mut log = overrun_log().lock().unwrap_or_else(|e| e.into_inner());
+    log.take_pending();
+    log.entries.iter().cloned().collect()
+}
+
+/// Number of overruns since the start by component
+pub fn overrun_counts() -> Vec<(CycleComponent, u64)> {
+    let mut log = overrun_log().lock().unwrap_or_else(|e| e.into_inner());
+    log.take_pending();
+    log.counts
+        .iter()
+        .map(|(component, count)| (component.clone(), *count))
+        .collect()
+}
+
+/// Timings of all machines and devices with their budgets
+pub fn cycle_budget_report(config: &CycleBudgetConfig) -> Vec<ComponentBudget> {
+    let counts: HashMap<CycleComponent, u64> = overrun_counts().into_iter().collect();
+    let report = |component: CycleComponent, budget: Duration, snapshot: HistogramSnapshot| {
+        let us = |seconds: Option<f64>| seconds.map(|seconds| seconds * 1e6);
+        ComponentBudget {
+            overruns: counts.get(&component).copied().unwrap_or(0),
+            component,
+            budget_us: budget.as_secs_f64() * 1e6,
+            count: snapshot.count,
+            min_us: us(snapshot.min_seconds),
+            avg_us: us(snapshot.average_seconds()),
+            max_us: us(snapshot.max_seconds),
+            p99_us: us(snapshot.quantile_seconds(0.99)),
+        }
+    };
+
+    let machines = machine_act_snapshots()
+        .into_iter()
+        .map(|(machine, snapshot)| {
+            let budget = config.machine_act_budget(&machine);
+            report(CycleComponent::MachineAct { machine }, budget, snapshot)
+        });
+    let devices = device_snapshots()
+        .into_iter()
+        .map(|(subdevice_index, phase, name, snapshot)| {
+            let component = CycleComponent::Device {
+                subdevice_index,
+                name,
+                phase,
+            };
+            report(component, config.device_budget(), snapshot)
+        });
+    machines.chain(devices).collect()
+}
+
+/// Timing of one component in the RT loop
+struct ComponentTiming {
+    histogram: Arc<Histogram>,
+    budget: Duration,
+}
+
+impl ComponentTiming {
+    const fn new(histogram: Arc<Histogram>, budget: Duration) -> Self {
+        Self { histogram, budget }
+    }
+
+    fn record(&self, duration: Duration, component: impl FnOnce() -> CycleComponent) {
+        self.histogram.observe(duration);
+        if duration <= self.budget {
+            return;
+        }
+
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |timestamp| timestamp.as_millis() as u64);
+        record_overrun(Overrun {
+            timestamp_ms,
+            component: component(),
+            duration_us: duration.as_secs_f64() * 1e6,
+            budget_us: self.budget.as_secs_f64() * 1e6,
+        });
+    }
+}
+
+/// Times the machines and devices of the RT loop against their budgets
+///
+/// Keeps the histogram handles so the registries are only locked once per component,
+/// overruns are queued without locking.
+pub struct CycleProfiler {
+    config: CycleBudgetConfig,
+    machines: HashMap<MachineIdentificationUnique, ComponentTiming>,
+    /// By subdevice index and [`DevicePhase`]
+    devices: HashMap<(usize, DevicePhase), ComponentTiming>,
+}
+
+impl CycleProfiler {
+    pub fn new(config: CycleBudgetConfig) -> Self {
+        overrun_log()
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .capacity = config.overrun_log_len;
+        // allocated here, not on the first overrun
+        pending_overruns();
+        Self {
+            config,
+            machines: HashMap::new(),
+            devices: HashMap::new(),
+        }
+    }
+
+    pub fn record_machine_act(
+        &mut self,
+        machine: &MachineIdentificationUnique,
+        duration: Duration,
+    ) {
+        self.machines
+            .entry(machine.clone())
+            .or_insert_with(|| {
+                ComponentTiming::new(
+                    machine_act_histogram(machine),
+                    self.config.machine_act_budget(machine),
+                )
+            })
+            .record(duration, || CycleComponent::MachineAct {
+                machine: machine.clone(),
+            });
+    }
+
+    pub fn record_device(
+        &mut self,
+        subdevice_index: usize,
+        name: &str,
+        phase: DevicePhase,
+        duration: Duration,
+    ) {
+        self.devices
+            .entry((subdevice_index, phase))
+            .or_insert_with(|| {
+                ComponentTiming::new(
+                    device_histogram(subdevice_index, name, phase),
+                    self.config.device_budget(),
+                )
+            })
+            .record(duration, || CycleComponent::Device {
+                subdevice_index,
+                name: name.to_string(),
+                phase,
+            });
+    }
+
+    /// Stops timing and exporting a removed machine
+    pub fn remove_machine(&mut self, machine: &MachineIdentificationUnique) {
+        self.machines.remove(machine);
+        remove_machine_act_histogram(machine);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use machines::machine_identification::MachineIdentification;
+
+    #[test]
+    fn test_overruns() {
+        let machine = MachineIdentificationUnique {
+            machine_identification: MachineIdentification {
+                vendor: 0xfff2,
+                machine: 1,
+            },
+            serial: 1,
+        };
+        let mut config = CycleBudgetConfig::default();
+        config.machines.insert(machine.to_string(), 50);
+        let mut profiler = CycleProfiler::new(config.clone());
+
+        profiler.record_machine_act(&machine, Duration::from_micros(40));
+        profiler.record_machine_act(&machine, Duration::from_micros(60));
+        profiler.record_machine_act(&machine, Duration::from_micros(70));
+        profiler.record_device(
+            0xfff2,
+            "EL2008",
+            DevicePhase::OutputPreProcess,
+            Duration::from_micros(10),
+        );
+
+        let component = CycleComponent::MachineAct {
+            machine: machine.clone(),
+        };
+        let logged: Vec<Overrun> = overruns()
+            .into_iter()
+            .filter(|overrun| overrun.component == component)
+            .collect();
+        assert_eq!(logged.len(), 2);
+        assert_eq!(logged[0].duration_us, 60.0);
+        assert_eq!(logged[0].budget_us, 50.0);
+
+        let report = cycle_budget_report(&config);
+        let machine_report = report
+            .iter()
+            .find(|report| report.component == component)
+            .unwrap();
+        assert_eq!(machine_report.count, 3);
+        assert_eq!(machine_report.overruns, 2);
+        assert_eq!(machine_report.min_us, Some(40.0));
+        assert_eq!(machine_report.max_us, Some(70.0));
+        assert_eq!(machine_report.p99_us, Some(70.0));
+        let device_report = report
+            .iter()
+            .find(|report| {
+                matches!(
+                    &report.component,
+                    CycleComponent::Device { subdevice_index: 0xfff2, name, .. } if name == "EL2008"
+                )
+            })
+            .unwrap();
+        assert_eq!(device_report.budget_us, 20.0);
+        assert_eq!(device_report.overruns, 0);
+
+        profiler.remove_machine(&machine);
+        assert!(
+            !cycle_budget_report(&config)
+                .iter()
+                .any(|report| report.component == component)
+        );
+    }
+}
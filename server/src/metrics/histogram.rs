@@ -0,0 +1,138 @@
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Histogram with fixed buckets that the RT loop can record into without locking.
+///
+/// Values are signed nanoseconds so it also takes early (negative) jitter.
+pub struct Histogram {
+    /// Inclusive upper bounds in nanoseconds, ascending
+    bounds_ns: &'static [i64],
+    /// One count per bound plus the `+Inf` bucket, not cumulative
+    counts: Box<[AtomicU64]>,
+    sum_ns: AtomicI64,
+    count: AtomicU64,
+    min_ns: AtomicI64,
+    max_ns: AtomicI64,
+}
+
+/// Copy of a [`Histogram`], the bucket counts are cumulative as in Prometheus.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramSnapshot {
+    /// Upper bound in seconds and the number of values at or below it
+    pub buckets: Vec<(f64, u64)>,
+    pub sum_seconds: f64,
+    pub count: u64,
+    /// `None` before the first value
+    pub min_seconds: Option<f64>,
+    pub max_seconds: Option<f64>,
+}
+
+impl Histogram {
+    pub fn new(bounds_ns: &'static [i64]) -> Self {
+        debug_assert!(bounds_ns.is_sorted());
+        Self {
+            bounds_ns,
+            counts: (0..=bounds_ns.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum_ns: AtomicI64::new(0),
+            count: AtomicU64::new(0),
+            min_ns: AtomicI64::new(i64::MAX),
+            max_ns: AtomicI64::new(i64::MIN),
+        }
+    }
+
+    pub fn observe_ns(&self, value_ns: i64) {
+        let bucket = self.bounds_ns.partition_point(|bound| *bound < value_ns);
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_ns.fetch_add(value_ns, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.min_ns.fetch_min(value_ns, Ordering::Relaxed);
+        self.max_ns.fetch_max(value_ns, Ordering::Relaxed);
+    }
+
+    pub fn observe(&self, duration: Duration) {
+        self.observe_ns(i64::try_from(duration.as_nanos()).unwrap_or(i64::MAX));
+    }
+
+    /// Best-effort copy, values recorded meanwhile may be missing from some fields
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        let mut cumulative = 0;
+        let buckets = self
+            .bounds_ns
+            .iter()
+            .zip(self.counts.iter())
+            .map(|(bound, count)| {
+                cumulative += count.load(Ordering::Relaxed);
+                (*bound as f64 / 1e9, cumulative)
+            })
+            .collect();
+        let count = self.count.load(Ordering::Relaxed);
+        let seconds = |value_ns: &AtomicI64| {
+            Some(value_ns.load(Ordering::Relaxed) as f64 / 1e9).filter(|_| count > 0)
+        };
+        HistogramSnapshot {
+            buckets,
+            sum_seconds: self.sum_ns.load(Ordering::Relaxed) as f64 / 1e9,
+            count,
+            min_seconds: seconds(&self.min_ns),
+            max_seconds: seconds(&self.max_ns),
+        }
+    }
+}
+
+impl HistogramSnapshot {
+    pub fn average_seconds(&self) -> Option<f64> {
+        Some(self.sum_seconds / self.count as f64).filter(|_| self.count > 0)
+    }
+
+    /// Upper bound of the bucket the `quantile` falls into, at most the maximum
+    pub fn quantile_seconds(&self, quantile: f64) -> Option<f64> {
+        let max = self.max_seconds?;
+        let rank = (quantile * self.count as f64).ceil() as u64;
+        let bound = self
+            .buckets
+            .iter()
+            .find(|(_, cumulative)| *cumulative >= rank)
+            .map_or(max, |(bound, _)| *bound);
+        Some(bound.min(max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buckets() {
+        static BOUNDS: [i64; 3] = [-10, 0, 10];
+        let histogram = Histogram::new(&BOUNDS);
+        for value in [-20, -10, 5, 10, 11, 1000] {
+            histogram.observe_ns(value);
+        }
+
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.buckets, vec![(-10e-9, 2), (0.0, 2), (10e-9, 4)]);
+        assert_eq!(snapshot.count, 6);
+        assert!((snapshot.sum_seconds - 996e-9).abs() < 1e-15);
+        assert_eq!(snapshot.min_seconds, Some(-20e-9));
+        assert_eq!(snapshot.max_seconds, Some(1000e-9));
+    }
+
+    #[test]
+    fn test_quantile() {
+        static BOUNDS: [i64; 3] = [10, 20, 50];
+        let histogram = Histogram::new(&BOUNDS);
+        assert_eq!(histogram.snapshot().quantile_seconds(0.99), None);
+
+        for value in 1..=100 {
+            histogram.observe_ns(if value <= 90 { 5 } else { 15 });
+        }
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.quantile_seconds(0.5), Some(10e-9));
+        // the bucket bound is above the maximum
+        assert_eq!(snapshot.quantile_seconds(0.99), Some(15e-9));
+        assert!((snapshot.average_seconds().unwrap() - 6e-9).abs() < 1e-15);
+
+        histogram.observe_ns(1000);
+        assert_eq!(histogram.snapshot().quantile_seconds(1.0), Some(1000e-9));
+    }
+}
@@ -2,6 +2,8 @@ use std::cell::UnsafeCell;
 use std::sync::OnceLock;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
+use crate::metrics::prometheus::rt_loop_histograms;
+
 const JITTER_RING_LEN: usize = 1024;
 #[derive(Debug, Clone, Copy)]
 pub struct JitterSample {
@@ -56,6 +58,9 @@ fn machines_ring() -> &'static JitterRing {
 /// Record one jitter sample for the main RT loop (machines + EtherCAT).
 pub fn record_machines_loop_jitter(jitter_ns: i128) {
     machines_ring().push(JitterSample { jitter_ns });
+    rt_loop_histograms()
+        .jitter
+        .observe_ns(jitter_ns.clamp(i64::MIN as i128, i64::MAX as i128) as i64);
 }
 
 /// Get a snapshot of recent jitter samples for the machines loop.
@@ -1,7 +1,10 @@
 pub mod collector;
 pub mod csv_writer;
+pub mod cycle_budget;
+pub mod histogram;
 pub mod io;
 pub mod jitter;
 pub mod preemption;
 pub mod process;
+pub mod prometheus;
 pub mod state;
@@ -0,0 +1,536 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use machines::machine_identification::MachineIdentificationUnique;
+use machines::machine_settings::all_latest_live_values;
+use serde_json::Value;
+
+use crate::metrics::csv_writer::RuntimeSample;
+use crate::metrics::cycle_budget::{CycleComponent, DevicePhase, overrun_counts};
+use crate::metrics::histogram::{Histogram, HistogramSnapshot};
+use crate::metrics::state::get_latest_runtime_sample;
+
+const US: i64 = 1_000;
+
+const CYCLE_BOUNDS_NS: [i64; 14] = [
+    100 * US,
+    200 * US,
+    300 * US,
+    400 * US,
+    500 * US,
+    600 * US,
+    700 * US,
+    800 * US,
+    900 * US,
+    1_000 * US,
+    1_500 * US,
+    2_000 * US,
+    5_000 * US,
+    10_000 * US,
+];
+
+const TXRX_BOUNDS_NS: [i64; 10] = [
+    25 * US,
+    50 * US,
+    75 * US,
+    100 * US,
+    150 * US,
+    200 * US,
+    300 * US,
+    500 * US,
+    1_000 * US,
+    2_000 * US,
+];
+
+const JITTER_BOUNDS_NS: [i64; 13] = [
+    -100 * US,
+    -50 * US,
+    -20 * US,
+    -10 * US,
+    0,
+    10 * US,
+    20 * US,
+    50 * US,
+    100 * US,
+    200 * US,
+    500 * US,
+    1_000 * US,
+    5_000 * US,
+];
+
+const ACT_BOUNDS_NS: [i64; 10] = [
+    US,
+    2 * US,
+    5 * US,
+    10 * US,
+    20 * US,
+    50 * US,
+    100 * US,
+    200 * US,
+    500 * US,
+    1_000 * US,
+];
+
+/// Live value fields exported as gauges, matched as part of the field name
+const MACHINE_GAUGE_FIELDS: &[&str] = &["temperature", "speed", "rpm", "diameter"];
+
+/// Timings of the RT loop, recorded by [`crate::performance_metrics::EthercatPerformanceMetrics`]
+/// and the jitter measurement
+pub struct RtLoopHistograms {
+    pub cycle: Histogram,
+    pub txrx: Histogram,
+    pub jitter: Histogram,
+}
+
+static RT_LOOP_HISTOGRAMS: OnceLock<RtLoopHistograms> = OnceLock::new();
+
+pub fn rt_loop_histograms() -> &'static RtLoopHistograms {
+    RT_LOOP_HISTOGRAMS.get_or_init(|| RtLoopHistograms {
+        cycle: Histogram::new(&CYCLE_BOUNDS_NS),
+        txrx: Histogram::new(&TXRX_BOUNDS_NS),
+        jitter: Histogram::new(&JITTER_BOUNDS_NS),
+    })
+}
+
+type ActHistograms = Mutex<HashMap<MachineIdentificationUnique, Arc<Histogram>>>;
+
+/// Durations of `Machine::act` by machine
+static MACHINE_ACT_HISTOGRAMS: OnceLock<ActHistograms> = OnceLock::new();
+
+fn machine_act_histograms() -> &'static ActHistograms {
+    MACHINE_ACT_HISTOGRAMS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// The `act` histogram of a machine, created on first use
+///
+/// The RT loop keeps the returned handle so it only locks once per machine.
+pub fn machine_act_histogram(machine: &MachineIdentificationUnique) -> Arc<Histogram> {
+    let mut guard = machine_act_histograms().lock().unwrap();
+    guard
+        .entry(machine.clone())
+        .or_insert_with(|| Arc::new(Histogram::new(&ACT_BOUNDS_NS)))
+        .clone()
+}
+
+/// Stops exporting the `act` histogram of a removed machine
+pub fn remove_machine_act_histogram(machine: &MachineIdentificationUnique) {
+    machine_act_histograms().lock().unwrap().remove(machine);
+}
+
+/// Snapshots of the `act` histograms, sorted by machine
+pub fn machine_act_snapshots() -> Vec<(MachineIdentificationUnique, HistogramSnapshot)> {
+    let mut snapshots: Vec<(MachineIdentificationUnique, HistogramSnapshot)> =
+        machine_act_histograms()
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(machine, histogram)| (machine.clone(), histogram.snapshot()))
+            .collect();
+    snapshots.sort_by_key(|(machine, _)| machine.to_string());
+    snapshots
+}
+
+type DeviceHistograms = Mutex<BTreeMap<(usize, DevicePhase), (String, Arc<Histogram>)>>;
+
+/// Durations of the input post processing and output pre processing by subdevice index
+static DEVICE_HISTOGRAMS: OnceLock<DeviceHistograms> = OnceLock::new();
+
+fn device_histograms() -> &'static DeviceHistograms {
+    DEVICE_HISTOGRAMS.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+/// The histogram of a device processing phase, created on first use
+pub fn device_histogram(subdevice_index: usize, name: &str, phase: DevicePhase) -> Arc<Histogram> {
+    let mut guard = device_histograms().lock().unwrap();
+    guard
+        .entry((subdevice_index, phase))
+        .or_insert_with(|| (name.to_string(), Arc::new(Histogram::new(&ACT_BOUNDS_NS))))
+        .1
+        .clone()
+}
+
+/// Snapshots of the device histograms with subdevice index, phase and name
+pub fn device_snapshots() -> Vec<(usize, DevicePhase, String, HistogramSnapshot)> {
+    device_histograms()
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|((subdevice_index, phase), (name, histogram))| {
+            (*subdevice_index, *phase, name.clone(), histogram.snapshot())
+        })
+        .collect()
+}
+
+/// All metrics in the Prometheus text exposition format
+pub fn render_metrics() -> String {
+    let histograms = rt_loop_histograms();
+    let machine_act = machine_act_snapshots();
+    let mut live_values = all_latest_live_values();
+    live_values.sort_by_key(|(machine, _)| machine.to_string());
+
+    let mut encoder = TextEncoder::default();
+    if let Some(sample) = get_latest_runtime_sample() {
+        encode_runtime_sample(&mut encoder, &sample);
+    }
+    encoder.histogram(
+        "qitech_rt_loop_cycle_seconds",
+        "Time between the starts of two EtherCAT cycles",
+        &[(&[], histograms.cycle.snapshot())],
+    );
+    encoder.histogram(
+        "qitech_ethercat_txrx_seconds",
+        "Duration of the EtherCAT tx_rx",
+        &[(&[], histograms.txrx.snapshot())],
+    );
+    encoder.histogram(
+        "qitech_rt_loop_jitter_seconds",
+        "Deviation of the RT loop period from the cycle target, negative is early",
+        &[(&[], histograms.jitter.snapshot())],
+    );
+    encode_machines(&mut encoder, &machine_act, &live_values);
+    encode_cycle_budget(&mut encoder, &device_snapshots(), &overrun_counts());
+    encoder.output
+}
+
+fn encode_runtime_sample(encoder: &mut TextEncoder, sample: &RuntimeSample) {
+    encoder.single(
+        "qitech_process_resident_memory_bytes",
+        "gauge",
+        "Resident set size of the server",
+        sample.rss_bytes as f64,
+    );
+    encoder.single(
+        "qitech_process_cpu_seconds_total",
+        "counter",
+        "CPU time of the server",
+        sample.cpu_time_seconds,
+    );
+    encoder.single(
+        "qitech_process_minor_faults_total",
+        "counter",
+        "Minor page faults of the server",
+        sample.minor_faults as f64,
+    );
+    encoder.single(
+        "qitech_process_major_faults_total",
+        "counter",
+        "Major page faults of the server",
+        sample.major_faults as f64,
+    );
+    encoder.single(
+        "qitech_network_receive_bytes_per_second",
+        "gauge",
+        "Receive rate of the EtherCAT interface",
+        sample.rx_rate_bytes_per_sec,
+    );
+    encoder.single(
+        "qitech_network_transmit_bytes_per_second",
+        "gauge",
+        "Transmit rate of the EtherCAT interface",
+        sample.tx_rate_bytes_per_sec,
+    );
+    if let Some(cpu_time) = sample.rt_loop_cpu_time_seconds {
+        encoder.single(
+            "qitech_rt_loop_cpu_seconds_total",
+            "counter",
+            "CPU time of the RT loop thread",
+            cpu_time,
+        );
+    }
+    if let (Some(voluntary), Some(involuntary)) = (
+        sample.rt_nr_voluntary_switches,
+        sample.rt_nr_involuntary_switches,
+    ) {
+        encoder.header(
+            "qitech_rt_loop_context_switches_total",
+            "counter",
+            "Context switches of the RT loop thread, involuntary ones are preemptions",
+        );
+        encoder.sample(
+            "qitech_rt_loop_context_switches_total",
+            &[("kind", "voluntary")],
+            voluntary as f64,
+        );
+        encoder.sample(
+            "qitech_rt_loop_context_switches_total",
+            &[("kind", "involuntary")],
+            involuntary as f64,
+        );
+    }
+}
+
+fn encode_machines(
+    encoder: &mut TextEncoder,
+    machine_act: &[(MachineIdentificationUnique, HistogramSnapshot)],
+    live_values: &[(MachineIdentificationUnique, Value)],
+) {
+    let ids: Vec<String> = machine_act
+        .iter()
+        .map(|(machine, _)| machine.to_string())
+        .collect();
+    let labels: Vec<[(&str, &str); 1]> = ids.iter().map(|id| [("machine", id.as_str())]).collect();
+    let act: Vec<(&[(&str, &str)], HistogramSnapshot)> = machine_act
+        .iter()
+        .zip(&labels)
+        .map(|((_, snapshot), labels)| (labels.as_slice(), snapshot.clone()))
+        .collect();
+    encoder.histogram(
+        "qitech_machine_act_seconds",
+        "Duration of one act call of a machine",
+        &act,
+    );
+
+    encoder.header(
+        "qitech_machine_live_value",
+        "gauge",
+        "Selected live values of the machines, in the unit of the live values event",
+    );
+    for (machine, live_values) in live_values {
+        let Value::Object(fields) = live_values else {
+            continue;
+        };
+        let machine = machine.to_string();
+        for (field, value) in fields {
+            let Some(value) = value.as_f64() else {
+                continue;
+            };
+            if MACHINE_GAUGE_FIELDS
+                .iter()
+                .any(|selected| field.contains(selected))
+            {
+                encoder.sample(
+                    "qitech_machine_live_value",
+                    &[("machine", &machine), ("field", field)],
+                    value,
+                );
+            }
+        }
+    }
+}
+
+fn encode_cycle_budget(
+    encoder: &mut TextEncoder,
+    devices: &[(usize, DevicePhase, String, HistogramSnapshot)],
+    overrun_counts: &[(CycleComponent, u64)],
+) {
+    let indices: Vec<String> = devices
+        .iter()
+        .map(|(subdevice_index, ..)| subdevice_index.to_string())
+        .collect();
+    let labels: Vec<[(&str, &str); 3]> = devices
+        .iter()
+        .zip(&indices)
+        .map(|((_, phase, name, _), index)| {
+            [
+                ("subdevice", index.as_str()),
+                ("name", name.as_str()),
+                ("phase", phase.as_str()),
+            ]
+        })
+        .collect();
+    let series: Vec<(&[(&str, &str)], HistogramSnapshot)> = devices
+        .iter()
+        .zip(&labels)
+        .map(|((.., snapshot), labels)| (labels.as_slice(), snapshot.clone()))
+        .collect();
+    encoder.histogram(
+        "qitech_device_process_seconds",
+        "Duration of the input post processing or output pre processing of a device",
+        &series,
+    );
+
+    let mut overrun_counts = overrun_counts.to_vec();
+    overrun_counts.sort_by_key(|(component, _)| component.to_string());
+    encoder.header(
+        "qitech_cycle_budget_overruns_total",
+        "counter",
+        "Machine act calls and device processing phases that took longer than their budget",
+    );
+    for (component, count) in &overrun_counts {
+        let name = "qitech_cycle_budget_overruns_total";
+        match component {
+            CycleComponent::MachineAct { machine } => encoder.sample(
+                name,
+                &[("phase", "act"), ("machine", &machine.to_string())],
+                *count as f64,
+            ),
+            CycleComponent::Device {
+                subdevice_index,
+                name: device_name,
+                phase,
+            } => encoder.sample(
+                name,
+                &[
+                    ("phase", phase.as_str()),
+                    ("subdevice", &subdevice_index.to_string()),
+                    ("name", device_name),
+                ],
+                *count as f64,
+            ),
+        }
+    }
+}
+
+#[derive(Default)]
+struct TextEncoder {
+    output: String,
+}
+
+impl TextEncoder {
+    fn header(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.output, "# HELP {} {}", name, help);
+        let _ = writeln!(self.output, "# TYPE {} {}", name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.output.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(label, value)| format!("{}=\"{}\"", label, escape_label(value)))
+                .collect();
+            let _ = write!(self.output, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.output, " {}", format_value(value));
+    }
+
+    fn single(&mut self, name: &str, kind: &str, help: &str, value: f64) {
+        self.header(name, kind, help);
+        self.sample(name, &[], value);
+    }
+
+    fn histogram(
+        &mut self,
+        name: &str,
+        help: &str,
+        series: &[(&[(&str, &str)], HistogramSnapshot)],
+    ) {
+        self.header(name, "histogram", help);
+        let bucket = format!("{}_bucket", name);
+        for (labels, snapshot) in series {
+            for (bound, count) in &snapshot.buckets {
+                let le = format_value(*bound);
+                let mut bucket_labels = labels.to_vec();
+                bucket_labels.push(("le", &le));
+                self.sample(&bucket, &bucket_labels, *count as f64);
+            }
+            let mut bucket_labels = labels.to_vec();
+            bucket_labels.push(("le", "+Inf"));
+            self.sample(&bucket, &bucket_labels, snapshot.count as f64);
+            self.sample(&format!("{}_sum", name), labels, snapshot.sum_seconds);
+            self.sample(&format!("{}_count", name), labels, snapshot.count as f64);
+        }
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use machines::machine_identification::MachineIdentification;
+    use serde_json::json;
+
+    #[test]
+    fn test_encode_machines() {
+        static BOUNDS: [i64; 2] = [US, 10 * US];
+        let histogram = Histogram::new(&BOUNDS);
+        histogram.observe_ns(5 * US);
+        let machine = MachineIdentificationUnique {
+            machine_identification: MachineIdentification {
+                vendor: 1,
+                machine: 2,
+            },
+            serial: 3,
+        };
+        let live_values = json!({
+            "nozzle_temperature": 210.5,
+            "spool_rpm": 12,
+            "pressure": 80.0,
+            "motor_status": { "rpm": 3 },
+        });
+
+        let mut encoder = TextEncoder::default();
+        encode_machines(
+            &mut encoder,
+            &[(machine.clone(), histogram.snapshot())],
+            &[(machine, live_values)],
+        );
+        let output = encoder.output;
+
+        assert!(output.contains("# TYPE qitech_machine_act_seconds histogram\n"));
+        assert!(
+            output.contains(
+                "qitech_machine_act_seconds_bucket{machine=\"1/2/3\",le=\"0.000001\"} 0\n"
+            )
+        );
+        assert!(
+            output.contains(
+                "qitech_machine_act_seconds_bucket{machine=\"1/2/3\",le=\"0.00001\"} 1\n"
+            )
+        );
+        assert!(
+            output.contains("qitech_machine_act_seconds_bucket{machine=\"1/2/3\",le=\"+Inf\"} 1\n")
+        );
+        assert!(output.contains("qitech_machine_act_seconds_count{machine=\"1/2/3\"} 1\n"));
+        assert!(output.contains(
+            "qitech_machine_live_value{machine=\"1/2/3\",field=\"nozzle_temperature\"} 210.5\n"
+        ));
+        assert!(output.contains("field=\"spool_rpm\"} 12\n"));
+        assert!(!output.contains("pressure"));
+        assert!(!output.contains("motor_status"));
+    }
+
+    #[test]
+    fn test_encode_cycle_budget() {
+        static BOUNDS: [i64; 1] = [10 * US];
+        let histogram = Histogram::new(&BOUNDS);
+        histogram.observe_ns(30 * US);
+        let component = CycleComponent::Device {
+            subdevice_index: 4,
+            name: "EL7031".to_string(),
+            phase: DevicePhase::InputPostProcess,
+        };
+
+        let mut encoder = TextEncoder::default();
+        encode_cycle_budget(
+            &mut encoder,
+            &[(
+                4,
+                DevicePhase::InputPostProcess,
+                "EL7031".to_string(),
+                histogram.snapshot(),
+            )],
+            &[(component, 1)],
+        );
+        let output = encoder.output;
+
+        assert!(output.contains(
+            "qitech_device_process_seconds_count{subdevice=\"4\",name=\"EL7031\",phase=\"input_post_process\"} 1\n"
+        ));
+        assert!(output.contains(
+            "qitech_cycle_budget_overruns_total{phase=\"input_post_process\",subdevice=\"4\",name=\"EL7031\"} 1\n"
+        ));
+    }
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape_label("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
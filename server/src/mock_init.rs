@@ -20,23 +20,26 @@ pub fn init_mock(app_state: Arc<SharedState>) -> Result<(), anyhow::Error> {
         };
         let serial_params = SerialDeviceNewParams {
             path: "/dev/mock-serial".to_string(),
+            usb_serial_number: None,
         };
 
         // Create the mock serial device
         let _ = match MockSerialDevice::new_serial(&serial_params) {
-            Ok((device_identification, mock_serial_device)) => {
+            Ok((device_identifications, mock_serial_device)) => {
                 // Add the mock device to the machine manager
                 {
                     use crate::add_serial_device;
                     use machines::registry::MACHINE_REGISTRY;
-                    add_serial_device(
-                        app_state.clone(),
-                        &device_identification,
-                        mock_serial_device,
-                        &MACHINE_REGISTRY,
-                        app_state.socketio_setup.socket_queue_tx.clone(),
-                    )
-                    .await;
+                    for device_identification in &device_identifications {
+                        add_serial_device(
+                            app_state.clone(),
+                            device_identification,
+                            mock_serial_device.clone(),
+                            &MACHINE_REGISTRY,
+                            app_state.socketio_setup.socket_queue_tx.clone(),
+                        )
+                        .await?;
+                    }
                 }
                 Ok::<(), anyhow::Error>(())
             }
@@ -47,20 +50,22 @@ pub fn init_mock(app_state: Arc<SharedState>) -> Result<(), anyhow::Error> {
         };
 
         let _ = match ExtruderMockSerialDevice::new_serial(&serial_params) {
-            Ok((device_identification, mock_serial_device)) => {
+            Ok((device_identifications, mock_serial_device)) => {
                 // Add the mock device to the machine manager
                 {
                     use crate::add_serial_device;
                     use machines::registry::MACHINE_REGISTRY;
 
-                    add_serial_device(
-                        app_state.clone(),
-                        &device_identification,
-                        mock_serial_device,
-                        &MACHINE_REGISTRY,
-                        app_state.socketio_setup.socket_queue_tx.clone(),
-                    )
-                    .await;
+                    for device_identification in &device_identifications {
+                        add_serial_device(
+                            app_state.clone(),
+                            device_identification,
+                            mock_serial_device.clone(),
+                            &MACHINE_REGISTRY,
+                            app_state.socketio_setup.socket_queue_tx.clone(),
+                        )
+                        .await?;
+                    }
                 }
 
                 Ok::<(), anyhow::Error>(())
@@ -72,16 +77,18 @@ pub fn init_mock(app_state: Arc<SharedState>) -> Result<(), anyhow::Error> {
         };
 
         match WinderMockSerialDevice::new_serial(&serial_params) {
-            Ok((device_identification, mock_serial_device)) => {
+            Ok((device_identifications, mock_serial_device)) => {
                 // Add the mock device to the machine manager
-                add_serial_device(
-                    app_state.clone(),
-                    &device_identification,
-                    mock_serial_device,
-                    &MACHINE_REGISTRY,
-                    app_state.clone().socketio_setup.socket_queue_tx.clone(),
-                )
-                .await;
+                for device_identification in &device_identifications {
+                    add_serial_device(
+                        app_state.clone(),
+                        device_identification,
+                        mock_serial_device.clone(),
+                        &MACHINE_REGISTRY,
+                        app_state.clone().socketio_setup.socket_queue_tx.clone(),
+                    )
+                    .await?;
+                }
 
                 Ok(())
             }
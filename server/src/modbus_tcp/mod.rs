@@ -1,50 +1,151 @@
 use crate::app_state::SharedState;
-use control_core::ethernet::modbus_tcp_discovery::probe_modbus_tcp;
-use control_core::futures::FutureIteratorExt;
+use anyhow::Result;
+use control_core::ethernet::modbus_tcp_discovery::{
+    ModbusTcpDeviceProfile, ModbusTcpProbe, probe_modbus_tcp,
+};
 use machines::{
     MACHINE_WAGO_POWER_V1, Machine, MachineChannel, VENDOR_QITECH,
     machine_identification::{MachineIdentification, MachineIdentificationUnique},
-    wago_power::WagoPower,
+    wago_power::{self, WagoPower},
 };
 use smol::Timer;
+use smol::future::Boxed;
+use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::Duration;
+
+/// A device that didn't answer this many discovery runs in a row is removed
+#[cfg(not(feature = "mock-machine"))]
+const MISSED_SCANS_BEFORE_REMOVAL: u32 = 3;
+
+#[cfg(not(feature = "mock-machine"))]
+pub type NewModbusTcpMachineFn = fn(MachineChannel, SocketAddr) -> Boxed<Result<Box<dyn Machine>>>;
+
+/// Maps a modbus tcp device profile to the machine that is created for it
+#[cfg(not(feature = "mock-machine"))]
+pub struct ModbusTcpMachineProfile {
+    pub device: ModbusTcpDeviceProfile,
+    pub machine_identification: MachineIdentification,
+    pub new_machine: NewModbusTcpMachineFn,
+}
+
+#[cfg(not(feature = "mock-machine"))]
+pub const MODBUS_TCP_MACHINE_PROFILES: &[ModbusTcpMachineProfile] = &[ModbusTcpMachineProfile {
+    device: wago_power::MODBUS_TCP_PROFILE,
+    machine_identification: MachineIdentification {
+        vendor: VENDOR_QITECH,
+        machine: MACHINE_WAGO_POWER_V1,
+    },
+    new_machine: |channel, addr| {
+        Box::pin(async move {
+            let power = WagoPower::new(channel, addr).await?;
+            Ok(Box::new(power) as Box<dyn Machine>)
+        })
+    },
+}];
+
+#[cfg(not(feature = "mock-machine"))]
+#[derive(Debug)]
+struct DiscoveredMachine {
+    addr: SocketAddr,
+    missed_scans: u32,
+}
 
 #[cfg(not(feature = "mock-machine"))]
 pub async fn start_modbus_tcp_discovery(shared_state: Arc<SharedState>) {
+    // validated when the server config was loaded
+    let config = shared_state
+        .config
+        .modbus_tcp
+        .discovery()
+        .unwrap_or_default();
+    tracing::info!("Starting modbus tcp discovery {:?}", config);
+
+    let profiles: Vec<ModbusTcpDeviceProfile> = MODBUS_TCP_MACHINE_PROFILES
+        .iter()
+        .map(|profile| profile.device)
+        .collect();
+    let mut discovered = HashMap::new();
+
     loop {
-        let addresses = probe_modbus_tcp().await;
+        let probes = probe_modbus_tcp(&config, &profiles).await;
 
-        if addresses.is_empty() {
-            Timer::after(Duration::from_secs(1)).await;
-            continue;
-        }
+        // This allows detection of disconnected devices
+        handle_modbus_tcp_hotplug(shared_state.clone(), &mut discovered, probes).await;
 
-        let machines: Vec<Box<dyn Machine>> = addresses
+        Timer::after(config.interval).await;
+    }
+}
+
+#[cfg(not(feature = "mock-machine"))]
+async fn handle_modbus_tcp_hotplug(
+    shared_state: Arc<SharedState>,
+    discovered: &mut HashMap<MachineIdentificationUnique, DiscoveredMachine>,
+    probes: Vec<ModbusTcpProbe>,
+) {
+    let found: HashMap<MachineIdentificationUnique, (SocketAddr, &ModbusTcpMachineProfile)> =
+        probes
             .into_iter()
-            .map(|probe| {
-                smol::spawn(async move {
-                    let machine_identification_unique = MachineIdentificationUnique {
-                        machine_identification: MachineIdentification {
-                            vendor: VENDOR_QITECH,
-                            machine: MACHINE_WAGO_POWER_V1,
-                        },
-                        serial: probe.serial,
-                    };
-
-                    let channel = MachineChannel::new(machine_identification_unique);
-                    let power = WagoPower::new(channel, probe.addr)
-                        .await
-                        .expect("Failed to initialize wago power supply");
-
-                    Box::new(power) as Box<dyn Machine>
-                })
+            .filter_map(|probe| {
+                let profile = MODBUS_TCP_MACHINE_PROFILES
+                    .iter()
+                    .find(|profile| profile.device == probe.profile)?;
+                let machine_identification_unique = MachineIdentificationUnique {
+                    machine_identification: profile.machine_identification.clone(),
+                    serial: probe.serial,
+                };
+                Some((machine_identification_unique, (probe.addr, profile)))
             })
-            .join_all()
-            .await;
+            .collect();
+
+    // Machines that disappeared or moved to another address are removed
+    let mut removed = vec![];
+    for (ident, machine) in discovered.iter_mut() {
+        match found.get(ident) {
+            Some((addr, _)) if *addr == machine.addr => machine.missed_scans = 0,
+            Some(_) => removed.push(ident.clone()),
+            None => {
+                machine.missed_scans += 1;
+                if machine.missed_scans >= MISSED_SCANS_BEFORE_REMOVAL {
+                    removed.push(ident.clone());
+                }
+            }
+        }
+    }
+    for ident in removed {
+        tracing::info!("Modbus tcp device {:?} disconnected", ident);
+        discovered.remove(&ident);
+        shared_state.delete_machine(&ident).await;
+    }
 
-        shared_state.add_machines(machines).await;
-        return;
+    // Machines that are new or came back are added
+    for (ident, (addr, profile)) in found {
+        if discovered.contains_key(&ident) {
+            continue;
+        }
+
+        let channel = MachineChannel::new(ident.clone());
+        match (profile.new_machine)(channel, addr).await {
+            Ok(machine) => {
+                tracing::info!("Modbus tcp device {:?} connected at {}", ident, addr);
+                discovered.insert(
+                    ident,
+                    DiscoveredMachine {
+                        addr,
+                        missed_scans: 0,
+                    },
+                );
+                shared_state.add_machines(vec![machine]).await;
+            }
+            Err(e) => {
+                tracing::error!(
+                    "Failed to initialize {} at {}: {:?}",
+                    profile.device.name,
+                    addr,
+                    e
+                );
+            }
+        }
     }
 }
 
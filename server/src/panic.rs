@@ -1,4 +1,7 @@
-use std::{backtrace::Backtrace, panic::PanicHookInfo, thread};
+use std::{any::Any, backtrace::Backtrace, panic::PanicHookInfo, thread};
+
+use crate::r#loop::RT_LOOP_THREAD_NAME;
+use crate::shutdown::request_shutdown;
 
 fn panic_hook(panic_info: &PanicHookInfo) {
     let backtrace = Backtrace::capture().to_string();
@@ -9,23 +12,27 @@ fn panic_hook(panic_info: &PanicHookInfo) {
         .location()
         .map_or_else(|| "<unknown>".to_string(), ToString::to_string);
 
-    let message = panic_info
-        .payload()
-        .downcast_ref::<String>()
-        .cloned()
-        .or_else(|| {
-            panic_info
-                .payload()
-                .downcast_ref::<&str>()
-                .map(ToString::to_string)
-        })
-        .unwrap_or_else(|| "<unknown>".to_string());
+    let message = panic_message(panic_info.payload());
 
     tracing::error!("thread '{}' panicked at {}:", thread, locataion);
     eprintln!("{}\n", message);
     eprintln!("Backtrace:\n{}", backtrace);
 
-    std::process::exit(1);
+    // the whole loop body runs in `catch_unwind`, the loop brings the machines into their safe
+    // state and requests the shutdown itself
+    if thread == RT_LOOP_THREAD_NAME {
+        return;
+    }
+    request_shutdown(1, "Panic");
+}
+
+/// Message of a panic, from the hook or caught with [`std::panic::catch_unwind`]
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<String>()
+        .cloned()
+        .or_else(|| payload.downcast_ref::<&str>().map(ToString::to_string))
+        .unwrap_or_else(|| "<unknown>".to_string())
 }
 
 /// Initialize panic handling system
@@ -0,0 +1,130 @@
+use crate::config::ProductionConfig;
+use crate::jsonl_store::{JsonlEntry, JsonlStore, SharedJsonlStore};
+use anyhow::Result;
+use machines::production_record::ProductionRecord;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+impl JsonlEntry for ProductionRecord {
+    const NAME: &'static str = "production record";
+
+    fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Also assigns the lot number if the operator didn't set one
+    fn assign_id(&mut self, id: u64) {
+        self.id = id;
+        if self.lot_number.is_empty() {
+            self.lot_number = format!("{}-{:06}", self.winder.serial, id);
+        }
+    }
+}
+
+/// Append only store of production records
+pub type ProductionRecordStore = JsonlStore<ProductionRecord>;
+
+static PRODUCTION_RECORDS: SharedJsonlStore<ProductionRecord> =
+    SharedJsonlStore::new(|| ProductionConfig::default().records_path);
+
+/// Sets the file of the production records, `production.records_path` in the server config,
+/// called once at startup
+pub fn set_production_records_path(path: impl Into<PathBuf>) -> Result<()> {
+    PRODUCTION_RECORDS.set_path(path)
+}
+
+/// The store at the path set at startup, `None` if the file can't be read
+pub fn production_records() -> Option<&'static Mutex<ProductionRecordStore>> {
+    PRODUCTION_RECORDS.get()
+}
+
+/// Stores the record of a finished spool
+pub fn store_production_record(record: ProductionRecord) {
+    let Some(store) = production_records() else {
+        tracing::error!("Production record of {} lost, no store", record.winder);
+        return;
+    };
+    let mut guard = store.lock().unwrap();
+    let result = guard.append(record);
+    let path = guard.path().to_path_buf();
+    drop(guard);
+
+    match result {
+        Ok(record) => tracing::info!(
+            "Stored production record {} lot {} ({:.1} m)",
+            record.id,
+            record.lot_number,
+            record.length
+        ),
+        Err(e) => tracing::error!(
+            "Failed to store production record in {}: {}",
+            path.display(),
+            e
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use machines::production_record::SpoolCompletion;
+    use machines::{
+        MACHINE_WINDER_V1, VENDOR_QITECH,
+        machine_identification::{MachineIdentification, MachineIdentificationUnique},
+    };
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    fn record(lot_number: &str) -> ProductionRecord {
+        ProductionRecord {
+            id: 0,
+            lot_number: lot_number.to_string(),
+            winder: MachineIdentificationUnique {
+                machine_identification: MachineIdentification {
+                    vendor: VENDOR_QITECH,
+                    machine: MACHINE_WINDER_V1,
+                },
+                serial: 12,
+            },
+            started_at: 0,
+            finished_at: 1000,
+            length: 100.0,
+            completion: SpoolCompletion::Manual,
+            machines: vec![],
+            diameter: None,
+        }
+    }
+
+    #[test]
+    fn test_production_record_store() {
+        let path = std::env::temp_dir().join(format!(
+            "production_records_test_{}.jsonl",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let mut store = ProductionRecordStore::open(&path).unwrap();
+        assert!(store.load().unwrap().is_empty());
+
+        let first = store.append(record("")).unwrap();
+        assert_eq!(first.id, 1);
+        assert_eq!(first.lot_number, "12-000001");
+        let second = store.append(record("PLA-7")).unwrap();
+        assert_eq!(second.id, 2);
+        assert_eq!(second.lot_number, "PLA-7");
+
+        // a partially written line is skipped
+        let file = OpenOptions::new().append(true).open(&path).unwrap();
+        (&file).write_all(b"{\"id\": 3, \"lot").unwrap();
+
+        // ids continue after reopening
+        let mut store = ProductionRecordStore::open(&path).unwrap();
+        assert_eq!(store.load().unwrap(), [first, second.clone()]);
+        assert_eq!(store.append(record("")).unwrap().id, 3);
+        assert_eq!(store.load().unwrap().len(), 3);
+        assert_eq!(store.get(2).unwrap(), Some(second));
+        assert_eq!(store.get(9).unwrap(), None);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,167 @@
+use axum::{
+    body::Body,
+    extract::{Query, Request, State, rejection::QueryRejection},
+    http::Response,
+    middleware::Next,
+};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::app_state::SharedState;
+use crate::auth::{AuthError, Identity, Role, bearer_token, check_role, constant_time_eq};
+use crate::rest::handlers::stream::StreamQuery;
+use crate::rest::util::ResponseUtilError;
+
+/// Resolves the bearer token of the request to an [`Identity`] extension
+///
+/// Requests without a valid token pass on without one, the `require_*` layers reject them.
+pub async fn authenticate(
+    State(app_state): State<Arc<SharedState>>,
+    mut request: Request,
+    next: Next,
+) -> Response<Body> {
+    let token = bearer_token(request.headers()).map(str::to_owned);
+    insert_identity(&app_state, token.as_deref(), &mut request).await;
+    next.run(request).await
+}
+
+/// Like [`authenticate`] with the `token` of the [`StreamQuery`], if the request has no identity yet
+///
+/// An `EventSource` or a WebSocket of a browser can't send the `Authorization` header, so the
+/// streams take the token in the query like socket.io takes it in the handshake.
+pub(crate) async fn authenticate_stream(
+    State(app_state): State<Arc<SharedState>>,
+    query: Result<Query<StreamQuery>, QueryRejection>,
+    mut request: Request,
+    next: Next,
+) -> Response<Body> {
+    if request.extensions().get::<Identity>().is_none()
+        && let Ok(Query(query)) = &query
+        && let Some(token) = query.token()
+    {
+        insert_identity(&app_state, Some(token), &mut request).await;
+    }
+    next.run(request).await
+}
+
+async fn insert_identity(app_state: &SharedState, token: Option<&str>, request: &mut Request) {
+    let identity = app_state
+        .auth
+        .lock()
+        .await
+        .authenticate(token, Instant::now());
+    if let Some(identity) = identity {
+        request.extensions_mut().insert(identity);
+    }
+}
+
+/// The role of the request's identity, to check it within a handler
+pub fn require_role(identity: Option<&Identity>, role: Role) -> Result<(), AuthError> {
+    identity
+        .ok_or(AuthError::Unauthenticated)
+        .and_then(|identity| check_role(identity, role))
+}
+
+async fn require(role: Role, request: Request, next: Next) -> Response<Body> {
+    match require_role(request.extensions().get::<Identity>(), role) {
+        Ok(()) => next.run(request).await,
+        Err(e) => ResponseUtilError::Auth(e).into(),
+    }
+}
+
+pub async fn require_viewer(request: Request, next: Next) -> Response<Body> {
+    require(Role::Viewer, request, next).await
+}
+
+pub async fn require_operator(request: Request, next: Next) -> Response<Body> {
+    require(Role::Operator, request, next).await
+}
+
+pub async fn require_maintenance(request: Request, next: Next) -> Response<Body> {
+    require(Role::Maintenance, request, next).await
+}
+
+pub async fn require_admin(request: Request, next: Next) -> Response<Body> {
+    require(Role::Admin, request, next).await
+}
+
+/// Lets a request with `api.metrics_token` or a viewer through, everyone without the token
+pub async fn require_metrics_token(
+    State(app_state): State<Arc<SharedState>>,
+    request: Request,
+    next: Next,
+) -> Response<Body> {
+    let Some(metrics_token) = &app_state.config.api.metrics_token else {
+        return next.run(request).await;
+    };
+    let scraper = bearer_token(request.headers())
+        .is_some_and(|token| constant_time_eq(token.as_bytes(), metrics_token.expose().as_bytes()));
+    if scraper {
+        return next.run(request).await;
+    }
+    require(Role::Viewer, request, next).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServerConfig;
+    use crate::rest::init::api_router;
+    use axum::http::StatusCode;
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_stream_token_in_query() {
+        let (sender, _receiver) = smol::channel::unbounded();
+        let (main_sender, _main_receiver) = smol::channel::unbounded();
+        let mut config = ServerConfig::default();
+        let users = std::env::temp_dir().join(format!("stream_users_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&users);
+        config.auth.users_path = users.display().to_string();
+        config.lines.path = users.with_extension("lines.json").display().to_string();
+        let app_state = Arc::new(SharedState::new(sender, main_sender, config));
+        let token = {
+            let mut auth = app_state.auth.lock_blocking();
+            auth.set_user("viewer".to_string(), "secret", Role::Viewer)
+                .unwrap();
+            auth.login("viewer", "secret", Instant::now())
+                .unwrap()
+                .token
+        };
+        let router = api_router(app_state);
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let status = |uri: String| {
+            let request = axum::http::Request::builder()
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            runtime
+                .block_on(router.clone().oneshot(request))
+                .unwrap()
+                .status()
+        };
+
+        // the machine doesn't exist, so the handler answers once the token is accepted
+        let stream = "/api/v1/stream/machine/1/2/3";
+        assert_eq!(status(stream.to_string()), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            status(format!("{}?token=wrong", stream)),
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(format!("{}?events=StateEvent&token={}", stream, token)),
+            StatusCode::NOT_FOUND
+        );
+        // only the streams take the token in the query
+        assert_eq!(
+            status(format!("/api/v1/machines?token={}", token)),
+            StatusCode::UNAUTHORIZED
+        );
+
+        std::fs::remove_file(&users).unwrap();
+    }
+}
@@ -0,0 +1,105 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    body::Body,
+    extract::Query,
+    http::{Response, StatusCode},
+    middleware::from_fn,
+    routing::get,
+};
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::SharedState;
+use crate::audit::{AuditAction, AuditEntry, audit_log};
+use crate::rest::auth::require_operator;
+use crate::rest::util::{ResponseUtil, ResponseUtilError};
+use control_core::helpers::csv::to_csv;
+
+#[derive(Debug, Default, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum ExportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub(crate) struct EntriesQuery {
+    #[serde(default)]
+    format: ExportFormat,
+    user: Option<String>,
+    action: Option<AuditAction>,
+    /// Variant of the mutation, e.g. `SetMode`
+    mutation: Option<String>,
+    /// Machine serial
+    serial: Option<u16>,
+    /// unix timestamp in ms, inclusive
+    since: Option<u64>,
+    /// unix timestamp in ms, exclusive
+    until: Option<u64>,
+}
+
+impl EntriesQuery {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        self.user.as_ref().is_none_or(|user| &entry.user == user)
+            && self.action.is_none_or(|action| entry.action == action)
+            && self
+                .mutation
+                .as_ref()
+                .is_none_or(|mutation| entry.mutation.as_ref() == Some(mutation))
+            && self.serial.is_none_or(|serial| {
+                entry
+                    .machine
+                    .as_ref()
+                    .is_some_and(|machine| machine.serial == serial)
+            })
+            && self.since.is_none_or(|since| entry.timestamp >= since)
+            && self.until.is_none_or(|until| entry.timestamp < until)
+    }
+}
+
+/// Reads the file off the API thread
+async fn load_entries() -> Result<Vec<AuditEntry>, ResponseUtilError> {
+    smol::unblock(|| {
+        let log = audit_log().ok_or_else(|| anyhow::anyhow!("The audit log is not available"))?;
+        log.lock().unwrap().load()
+    })
+    .await
+    .map_err(ResponseUtilError::Error)
+}
+
+/// Audit entries, oldest first, filtered by the query.
+async fn get_audit_entries(Query(query): Query<EntriesQuery>) -> Response<Body> {
+    let entries = match load_entries().await {
+        Ok(entries) => entries,
+        Err(e) => return e.into(),
+    };
+    let entries: Vec<AuditEntry> = entries
+        .into_iter()
+        .filter(|entry| query.matches(entry))
+        .collect();
+
+    match query.format {
+        ExportFormat::Json => ResponseUtil::ok(entries),
+        ExportFormat::Csv => Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "text/csv")
+            .header(
+                "Content-Disposition",
+                "attachment; filename=\"audit_log.csv\"",
+            )
+            .body(Body::from(to_csv(&entries)))
+            .unwrap(),
+    }
+}
+
+/// Router for the audit trail REST endpoints.
+///
+/// Mounted under `/api/v1/audit`.
+pub fn audit_router() -> Router<Arc<SharedState>> {
+    Router::new()
+        .route("/entries", get(get_audit_entries))
+        .route_layer(from_fn(require_operator))
+}
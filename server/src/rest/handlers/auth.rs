@@ -0,0 +1,144 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::{
+    Extension, Json, Router,
+    body::Body,
+    extract::{Path, State},
+    http::{HeaderMap, Response},
+    middleware::from_fn,
+    routing::{delete, get, post},
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::mutation::MutationResponse;
+use crate::SharedState;
+use crate::auth::{Identity, Role, bearer_token};
+use crate::rest::auth::{require_admin, require_viewer};
+use crate::rest::util::ResponseUtil;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub(crate) struct LoginBody {
+    username: String,
+    password: String,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub(crate) struct UserBody {
+    username: String,
+    password: String,
+    role: Role,
+}
+
+#[derive(Debug, Serialize)]
+struct UserResponse {
+    username: String,
+    role: Role,
+}
+
+/// Returns the token to send as `Authorization: Bearer <token>` or as socket.io auth `{ token }`.
+async fn post_login(
+    State(app_state): State<Arc<SharedState>>,
+    Json(body): Json<LoginBody>,
+) -> Response<Body> {
+    // hashing the password would block the API thread
+    let username = body.username.clone();
+    let login = smol::unblock(move || {
+        app_state
+            .auth
+            .lock_blocking()
+            .login(&body.username, &body.password, Instant::now())
+    })
+    .await;
+    match login {
+        Ok(login) => {
+            tracing::info!("User {} logged in as {}", login.username, login.role);
+            ResponseUtil::ok(login)
+        }
+        Err(e) => {
+            tracing::warn!("Failed login of user {}: {}", username, e);
+            ResponseUtil::unauthorized(&e.to_string())
+        }
+    }
+}
+
+async fn post_logout(
+    State(app_state): State<Arc<SharedState>>,
+    headers: HeaderMap,
+) -> Response<Body> {
+    if let Some(token) = bearer_token(&headers) {
+        app_state.auth.lock().await.logout(token);
+    }
+    ResponseUtil::ok(MutationResponse::success())
+}
+
+/// The logged in user, an empty username while authentication is disabled.
+async fn get_me(Extension(identity): Extension<Identity>) -> Response<Body> {
+    ResponseUtil::ok(identity)
+}
+
+async fn get_users(State(app_state): State<Arc<SharedState>>) -> Response<Body> {
+    let users: Vec<UserResponse> = app_state
+        .auth
+        .lock()
+        .await
+        .users()
+        .iter()
+        .map(|user| UserResponse {
+            username: user.username.clone(),
+            role: user.role,
+        })
+        .collect();
+    ResponseUtil::ok(users)
+}
+
+/// Creates the user or changes its password and role.
+async fn post_user(
+    State(app_state): State<Arc<SharedState>>,
+    Json(body): Json<UserBody>,
+) -> Response<Body> {
+    let (username, role) = (body.username.clone(), body.role);
+    let result = smol::unblock(move || {
+        app_state
+            .auth
+            .lock_blocking()
+            .set_user(body.username, &body.password, body.role)
+    })
+    .await;
+    match result {
+        Ok(()) => {
+            tracing::info!("Set user {} with role {}", username, role);
+            ResponseUtil::ok(MutationResponse::success())
+        }
+        Err(e) => ResponseUtil::bad_request(&e.to_string()),
+    }
+}
+
+async fn delete_user(
+    State(app_state): State<Arc<SharedState>>,
+    Path(username): Path<String>,
+) -> Response<Body> {
+    let result = app_state.auth.lock().await.remove_user(&username);
+    match result {
+        Ok(()) => {
+            tracing::info!("Removed user {}", username);
+            ResponseUtil::ok(MutationResponse::success())
+        }
+        Err(e) => ResponseUtil::bad_request(&e.to_string()),
+    }
+}
+
+/// Router for logins and user management.
+///
+/// Mounted under `/api/v1/auth`.
+pub fn auth_router() -> Router<Arc<SharedState>> {
+    Router::new()
+        .route("/users", get(get_users).post(post_user))
+        .route("/users/{username}", delete(delete_user))
+        .route_layer(from_fn(require_admin))
+        .route("/me", get(get_me))
+        .route_layer(from_fn(require_viewer))
+        .route("/login", post(post_login))
+        .route("/logout", post(post_logout))
+}
@@ -0,0 +1,30 @@
+use std::sync::Arc;
+
+use axum::{Router, body::Body, extract::State, http::Response, routing::get};
+use serde::Serialize;
+
+use crate::SharedState;
+use crate::config::{EthercatLimits, ServerConfig};
+use crate::rest::util::ResponseUtil;
+
+#[derive(Debug, Serialize)]
+struct ConfigResponse<'a> {
+    config: &'a ServerConfig,
+    /// Compile-time sizes, changing them needs a rebuild
+    ethercat_limits: EthercatLimits,
+}
+
+/// The effective config after the file, environment and command line overrides.
+async fn get_config(State(app_state): State<Arc<SharedState>>) -> Response<Body> {
+    ResponseUtil::ok(ConfigResponse {
+        config: &app_state.config,
+        ethercat_limits: EthercatLimits::compiled(),
+    })
+}
+
+/// Router for the read-only server config.
+///
+/// Mounted under `/api/v1/config`.
+pub fn config_router() -> Router<Arc<SharedState>> {
+    Router::new().route("/", get(get_config))
+}
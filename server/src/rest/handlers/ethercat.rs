@@ -0,0 +1,29 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    body::Body,
+    extract::{Query, State},
+    http::{HeaderMap, Response},
+    routing::get,
+};
+
+use crate::SharedState;
+use crate::rest::util::{FormatQuery, ResponseFormat, ResponseUtil};
+
+/// The EtherCAT devices found on the last bus setup with their identification.
+async fn get_devices(
+    State(app_state): State<Arc<SharedState>>,
+    Query(query): Query<FormatQuery>,
+    headers: HeaderMap,
+) -> Response<Body> {
+    let devices = app_state.ethercat_meta_data.read().await.clone();
+    ResponseUtil::ok_as(devices, ResponseFormat::negotiate(&query, &headers))
+}
+
+/// Router for the EtherCAT bus.
+///
+/// Mounted under `/api/v1/ethercat`.
+pub fn ethercat_router() -> Router<Arc<SharedState>> {
+    Router::new().route("/devices", get(get_devices))
+}
@@ -0,0 +1,109 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::{
+    Extension, Json, Router,
+    body::Body,
+    extract::{ConnectInfo, Path, State},
+    http::Response,
+    middleware::from_fn,
+    routing::{get, post},
+};
+use machines::machine_identification::MachineIdentificationUnique;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::machine_mutation::audited_mutate_machine;
+use super::mutation::MutationResponse;
+use crate::SharedState;
+use crate::audit::{AuditAction, AuditEntry, record_audit};
+use crate::auth::Identity;
+use crate::inverter_backup::{InverterBackupResult, inverter_backups};
+use crate::rest::auth::require_admin;
+use crate::rest::util::{ResponseUtil, ResponseUtilError};
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub(crate) struct InverterBackups {
+    /// Names of the stored backups
+    backups: Vec<String>,
+    /// Outcome of the latest backups, latest first
+    results: Vec<InverterBackupResult>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub(crate) struct RestoreBody {
+    machine_identification_unique: MachineIdentificationUnique,
+    /// Name of the stored backup
+    name: String,
+}
+
+/// Stored backups and the outcome of the latest `BackupInverterParameters` mutations.
+async fn get_inverter_backups() -> Response<Body> {
+    let store = inverter_backups();
+    match smol::unblock(|| store.list()).await {
+        Ok(backups) => ResponseUtil::ok(InverterBackups {
+            backups,
+            results: store.results(),
+        }),
+        Err(e) => ResponseUtilError::Error(e).into(),
+    }
+}
+
+/// The parameters of a stored backup.
+async fn get_inverter_backup(Path(name): Path<String>) -> Response<Body> {
+    match smol::unblock(move || inverter_backups().load(&name)).await {
+        Ok(backup) => ResponseUtil::ok(backup),
+        Err(e) => ResponseUtilError::NotFound(e).into(),
+    }
+}
+
+/// Writes a stored backup to the inverter of an extruder, which has to be stopped.
+async fn post_restore(
+    State(app_state): State<Arc<SharedState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Extension(identity): Extension<Identity>,
+    Json(body): Json<RestoreBody>,
+) -> Response<Body> {
+    let entry = AuditEntry::new(
+        identity.username,
+        client.to_string(),
+        AuditAction::MachineMutation,
+        serde_json::json!({ "RestoreInverterParameters": body.name }),
+    )
+    .with_machine(body.machine_identification_unique.clone())
+    .with_mutation(Some("RestoreInverterParameters"));
+
+    let name = body.name.clone();
+    let backup = match smol::unblock(move || inverter_backups().load(&name)).await {
+        Ok(backup) => backup,
+        Err(e) => {
+            record_audit(entry.with_result(&Err::<(), _>(&e)));
+            return ResponseUtilError::NotFound(e).into();
+        }
+    };
+
+    let mutation = serde_json::json!({ "RestoreInverterParameters": backup });
+    let result = audited_mutate_machine(
+        &app_state,
+        &body.machine_identification_unique,
+        mutation,
+        entry,
+    )
+    .await;
+    match result {
+        Ok(()) => ResponseUtil::ok(MutationResponse::success()),
+        Err(e) => ResponseUtilError::Error(e).into(),
+    }
+}
+
+/// Router for inverter parameter backups.
+///
+/// Mounted under `/api/v1/inverter_backups`. Backups are made with the
+/// `BackupInverterParameters` mutation of an extruder and stored by name.
+pub fn inverter_backups_router() -> Router<Arc<SharedState>> {
+    Router::new()
+        .route("/restore", post(post_restore))
+        .route_layer(from_fn(require_admin))
+        .route("/", get(get_inverter_backups))
+        .route("/{name}", get(get_inverter_backup))
+}
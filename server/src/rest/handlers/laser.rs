@@ -0,0 +1,30 @@
+use std::sync::Arc;
+
+use axum::{Router, body::Body, extract::Path, http::Response, routing::get};
+use machines::{
+    laser::{LaserMachine, statistics::get_latest_statistics},
+    machine_identification::MachineIdentificationUnique,
+};
+
+use crate::SharedState;
+use crate::rest::util::ResponseUtil;
+
+/// Latest SPC statistics of the laser with the given serial.
+async fn get_laser_statistics(Path(serial): Path<u16>) -> Response<Body> {
+    let machine = MachineIdentificationUnique {
+        machine_identification: LaserMachine::MACHINE_IDENTIFICATION,
+        serial,
+    };
+
+    get_latest_statistics(&machine).map_or_else(
+        || ResponseUtil::not_found(&format!("No statistics for laser {}", machine)),
+        ResponseUtil::ok,
+    )
+}
+
+/// Router for laser-related REST endpoints.
+///
+/// Mounted under `/api/v1/machine/laser`.
+pub fn laser_router() -> Router<Arc<SharedState>> {
+    Router::new().route("/{serial}/statistics", get(get_laser_statistics))
+}
@@ -0,0 +1,96 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::{
+    Extension, Router,
+    body::Body,
+    extract::{ConnectInfo, Path, State},
+    http::Response,
+    middleware::from_fn,
+    routing::{get, post},
+};
+use machines::{AsyncThreadMessage, LineCommand, LineSequence};
+
+use super::mutation::MutationResponse;
+use crate::SharedState;
+use crate::audit::{AuditAction, AuditEntry, record_audit};
+use crate::auth::Identity;
+use crate::rest::auth::require_operator;
+use crate::rest::util::ResponseUtil;
+
+/// Progress of all production lines, the same as the latest `LinesEvent`.
+async fn get_lines(State(app_state): State<Arc<SharedState>>) -> Response<Body> {
+    ResponseUtil::ok(app_state.lines.lock().await.progress(Instant::now()))
+}
+
+/// Queues the sequence, it's rejected by the line while a conflicting one runs.
+async fn run_sequence(
+    app_state: &SharedState,
+    identity: Identity,
+    client: SocketAddr,
+    line: String,
+    sequence: LineSequence,
+) -> Response<Body> {
+    let entry = AuditEntry::new(
+        identity.username,
+        client.to_string(),
+        AuditAction::LineSequence,
+        serde_json::json!({ "line": line }),
+    )
+    .with_mutation(Some(&format!("{:?}", sequence)));
+
+    if !app_state.lines.lock().await.contains(&line) {
+        let message = format!("No production line {}", line);
+        record_audit(entry.with_result(&Err::<(), _>(&message)));
+        return ResponseUtil::not_found(&message);
+    }
+
+    tracing::info!("Running {:?} of line {}", sequence, line);
+    let message = AsyncThreadMessage::RunLineSequence(LineCommand { line, sequence });
+    let result = app_state.main_channel.send(message).await;
+    record_audit(entry.with_result(&result));
+    match result {
+        Ok(()) => ResponseUtil::ok(MutationResponse::success()),
+        Err(e) => ResponseUtil::error(&e.to_string()),
+    }
+}
+
+async fn post_start(
+    State(app_state): State<Arc<SharedState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Extension(identity): Extension<Identity>,
+    Path(line): Path<String>,
+) -> Response<Body> {
+    run_sequence(&app_state, identity, client, line, LineSequence::Start).await
+}
+
+async fn post_stop(
+    State(app_state): State<Arc<SharedState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Extension(identity): Extension<Identity>,
+    Path(line): Path<String>,
+) -> Response<Body> {
+    run_sequence(&app_state, identity, client, line, LineSequence::Stop).await
+}
+
+async fn post_emergency(
+    State(app_state): State<Arc<SharedState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Extension(identity): Extension<Identity>,
+    Path(line): Path<String>,
+) -> Response<Body> {
+    run_sequence(&app_state, identity, client, line, LineSequence::Emergency).await
+}
+
+/// Router for production line REST endpoints.
+///
+/// Mounted under `/api/v1/lines`.
+pub fn lines_router() -> Router<Arc<SharedState>> {
+    Router::new()
+        .route("/{line}/start", post(post_start))
+        .route("/{line}/stop", post(post_stop))
+        .route("/{line}/emergency", post(post_emergency))
+        .route_layer(from_fn(require_operator))
+        .route("/", get(get_lines))
+}
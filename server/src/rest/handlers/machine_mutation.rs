@@ -2,18 +2,62 @@ use super::mutation::MachineMutationBody;
 use super::mutation::MutationResponse;
 use crate::{
     app_state::SharedState,
-    rest::util::{ResponseUtil, ResponseUtilError},
+    audit::{AuditAction, AuditEntry, record_audit, record_settings_change},
+    auth::{Identity, mutation_name, mutation_role},
+    rest::{
+        auth::require_role,
+        util::{ResponseUtil, ResponseUtilError},
+    },
 };
-use axum::{Json, body::Body, extract::State, http::Response};
+use axum::{
+    Extension, Json,
+    body::Body,
+    extract::{ConnectInfo, State},
+    http::Response,
+};
+use machines::machine_identification::MachineIdentificationUnique;
+use machines::machine_settings::get_latest_settings;
+use machines::{MachineMessage, MutationReply, MutationResult};
 use serde_json::Value;
+use smol::channel::Receiver;
+use smol::future;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
+
+/// Time a machine gets to apply a mutation before the request fails
+const MUTATION_REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Time the audit waits for the result of a mutation after the request failed
+const MUTATION_AUDIT_TIMEOUT: Duration = Duration::from_secs(60);
 
 #[axum::debug_handler]
 pub async fn post_machine_mutate(
     State(app_state): State<Arc<SharedState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    identity: Option<Extension<Identity>>,
     Json(body): Json<MachineMutationBody<Value>>,
 ) -> Response<Body> {
-    let result = _post_machine_mutate(State(app_state), Json(body)).await;
+    let identity = identity.map(|Extension(identity)| identity);
+    let entry = AuditEntry::new(
+        identity
+            .as_ref()
+            .map(|identity| identity.username.clone())
+            .unwrap_or_default(),
+        client.to_string(),
+        AuditAction::MachineMutation,
+        body.data.clone(),
+    )
+    .with_machine(body.machine_identification_unique.clone())
+    .with_mutation(mutation_name(&body.data));
+
+    let allowed = require_role(identity.as_ref(), mutation_role(&body.data));
+    if let Err(e) = allowed {
+        record_audit(entry.with_result(&Err::<(), _>(&e)));
+        return ResponseUtilError::Auth(e).into();
+    }
+
+    let result = _post_machine_mutate(State(app_state), Json(body), entry).await;
     match result {
         Ok(_) => ResponseUtil::ok(MutationResponse::success()),
         Err(e) => ResponseUtilError::Error(e).into(),
@@ -23,6 +67,7 @@ pub async fn post_machine_mutate(
 async fn _post_machine_mutate(
     State(app_state): State<Arc<SharedState>>,
     Json(body): Json<MachineMutationBody<Value>>,
+    entry: AuditEntry,
 ) -> Result<(), anyhow::Error> {
     tracing::info!(
         "Mutating machine machine={} data={:?}",
@@ -33,36 +78,112 @@ async fn _post_machine_mutate(
     let span = tracing::info_span!("machine_mutate", machine = %body.machine_identification_unique);
     let _span = span.enter();
 
-    match app_state
-        .api_machines
-        .lock()
-        .await
-        .get(&body.machine_identification_unique)
-    {
-        Some(sender) => {
-            let res = sender
-                .clone()
-                .send(machines::MachineMessage::HttpApiJsonRequest(
-                    body.data.clone(),
-                ))
-                .await;
-            match res {
-                Ok(_) => (),
-                Err(e) => tracing::error!(
-                    "[{}::_post_machine_mutate] Sending MachineMessage::HttpApiJsonRequest to {} failed {}",
-                    module_path!(),
-                    body.machine_identification_unique,
-                    e
-                ),
-            };
-            Ok(())
+    audited_mutate_machine(
+        &app_state,
+        &body.machine_identification_unique,
+        body.data,
+        entry,
+    )
+    .await
+}
+
+/// Sends a mutation to the machine, waits for the result of its `api_mutate` and records the
+/// entry with it
+pub async fn audited_mutate_machine(
+    app_state: &SharedState,
+    machine: &MachineIdentificationUnique,
+    mutation: Value,
+    entry: AuditEntry,
+) -> Result<(), anyhow::Error> {
+    let previous = get_latest_settings(machine);
+    let result = match send_mutation(app_state, machine, mutation).await {
+        Ok(result) => result,
+        Err(e) => {
+            record_audit(entry.with_result(&Err::<(), _>(&e)));
+            return Err(e);
         }
+    };
+    audited_mutation_result(machine, result, entry, previous).await
+}
 
-        None => Err(anyhow::anyhow!(
-            "[{}::_post_machine_mutate] Machine api_mutate error {} {}",
+/// Sends a mutation to the machine, its result arrives on the returned receiver
+///
+/// Mutations sent one after the other reach the machine in that order.
+pub async fn send_mutation(
+    app_state: &SharedState,
+    machine: &MachineIdentificationUnique,
+    mutation: Value,
+) -> Result<Receiver<MutationResult>, anyhow::Error> {
+    let sender = app_state.api_machines.lock().await.get(machine).cloned();
+    let Some(sender) = sender else {
+        return Err(anyhow::anyhow!(
+            "[{}::mutate_machine] Machine api_mutate error {} {}",
             module_path!(),
             "No Machine found with id: ",
-            body.machine_identification_unique
-        )),
-    }
+            machine
+        ));
+    };
+
+    let (reply, result) = MutationReply::channel();
+    sender
+        .send(MachineMessage::HttpApiJsonRequest(mutation, reply))
+        .await
+        .map_err(|e| anyhow::anyhow!("Sending the mutation to {} failed: {}", machine, e))?;
+    Ok(result)
+}
+
+/// Waits for the result of a mutation from [`send_mutation`] and records the entry with it
+///
+/// The entry gets the result the machine sends, even if that takes longer than the caller
+/// waits.
+pub async fn audited_mutation_result(
+    machine: &MachineIdentificationUnique,
+    result: Receiver<MutationResult>,
+    entry: AuditEntry,
+    previous: Option<Value>,
+) -> Result<(), anyhow::Error> {
+    let (forward, forwarded) = smol::channel::bounded(1);
+    let audited_machine = machine.clone();
+    smol::spawn(async move {
+        let answer = future::or(async { Some(result.recv().await) }, async {
+            smol::Timer::after(MUTATION_AUDIT_TIMEOUT).await;
+            None
+        })
+        .await;
+        let result = match answer {
+            Some(Ok(result)) => result,
+            // the machine was removed or quarantined before it handled the mutation
+            Some(Err(_)) => Err(format!("Machine {} dropped the mutation", audited_machine)),
+            None => Err(format!(
+                "Machine {} did not answer the mutation",
+                audited_machine
+            )),
+        };
+        record_settings_change(entry.with_result(&result), previous);
+        let _ = forward.try_send(result);
+    })
+    .detach();
+    mutation_result(machine, forwarded).await
+}
+
+/// Waits for the result of a mutation from [`send_mutation`]
+pub async fn mutation_result(
+    machine: &MachineIdentificationUnique,
+    result: Receiver<MutationResult>,
+) -> Result<(), anyhow::Error> {
+    // the machine answers in the next cycle, unless it is quarantined or shutting down
+    let result = future::or(async { result.recv().await.ok() }, async {
+        smol::Timer::after(MUTATION_REPLY_TIMEOUT).await;
+        None
+    })
+    .await;
+    result.map_or_else(
+        || {
+            Err(anyhow::anyhow!(
+                "Machine {} did not answer the mutation",
+                machine
+            ))
+        },
+        |result| result.map_err(|e| anyhow::anyhow!(e)),
+    )
 }
@@ -0,0 +1,193 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::{
+    Extension, Router,
+    body::Body,
+    extract::{ConnectInfo, Path, Query, State},
+    http::{HeaderMap, Response},
+    middleware::from_fn,
+    routing::{get, post},
+};
+use control_core::socketio::event::GenericEvent;
+use machines::machine_identification::{MachineIdentification, MachineIdentificationUnique};
+
+use super::mutation::MutationResponse;
+use crate::SharedState;
+use crate::audit::{AuditAction, AuditEntry, record_audit};
+use crate::auth::Identity;
+use crate::rest::auth::require_maintenance;
+use crate::rest::util::{FormatQuery, ResponseFormat, ResponseUtil};
+use crate::socketio::namespace_id::NamespaceId;
+
+/// All machines with the error that keeps them from running, if any.
+async fn get_machines(
+    State(app_state): State<Arc<SharedState>>,
+    Query(query): Query<FormatQuery>,
+    headers: HeaderMap,
+) -> Response<Body> {
+    let machines = app_state.current_machines_meta.lock().await.clone();
+    ResponseUtil::ok_as(machines, ResponseFormat::negotiate(&query, &headers))
+}
+
+pub(crate) const fn machine_from_path(
+    (vendor, machine, serial): (u16, u16, u16),
+) -> MachineIdentificationUnique {
+    MachineIdentificationUnique {
+        machine_identification: MachineIdentification { vendor, machine },
+        serial,
+    }
+}
+
+/// The latest event with this name in the namespace cache of a machine, as reemitted to sockets
+async fn latest_event(
+    app_state: &SharedState,
+    machine: MachineIdentificationUnique,
+    name: &str,
+) -> Option<Arc<GenericEvent>> {
+    let namespaces = app_state.socketio_setup.namespaces.read().await;
+    namespaces
+        .machine_namespaces
+        .get(&NamespaceId::Machine(machine))
+        .and_then(|namespace| namespace.latest_event(name))
+}
+
+/// Data of the latest `StateEvent` of a machine.
+async fn get_machine_state(
+    State(app_state): State<Arc<SharedState>>,
+    Path(path): Path<(u16, u16, u16)>,
+    Query(query): Query<FormatQuery>,
+    headers: HeaderMap,
+) -> Response<Body> {
+    let machine = machine_from_path(path);
+    latest_event(&app_state, machine.clone(), "StateEvent")
+        .await
+        .map_or_else(
+            || ResponseUtil::not_found(&format!("No state of {}", machine)),
+            |event| ResponseUtil::ok_as(&event.data, ResponseFormat::negotiate(&query, &headers)),
+        )
+}
+
+/// Data of the latest `LiveValuesEvent` of a machine.
+async fn get_machine_live_values(
+    State(app_state): State<Arc<SharedState>>,
+    Path(path): Path<(u16, u16, u16)>,
+    Query(query): Query<FormatQuery>,
+    headers: HeaderMap,
+) -> Response<Body> {
+    let machine = machine_from_path(path);
+    latest_event(&app_state, machine.clone(), "LiveValuesEvent")
+        .await
+        .map_or_else(
+            || ResponseUtil::not_found(&format!("No live values of {}", machine)),
+            |event| ResponseUtil::ok_as(&event.data, ResponseFormat::negotiate(&query, &headers)),
+        )
+}
+
+/// Creates a machine the RT loop quarantined after a panic again.
+async fn post_recreate_machine(
+    State(app_state): State<Arc<SharedState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Extension(identity): Extension<Identity>,
+    Path(path): Path<(u16, u16, u16)>,
+) -> Response<Body> {
+    let machine = machine_from_path(path);
+    let entry = AuditEntry::new(
+        identity.username,
+        client.to_string(),
+        AuditAction::MachineRecreate,
+        serde_json::Value::Null,
+    )
+    .with_machine(machine.clone());
+
+    tracing::info!("Re-creating machine {}", machine);
+    let result = app_state.recreate_machine(&machine).await;
+    record_audit(entry.with_result(&result));
+    match result {
+        Ok(()) => ResponseUtil::ok(MutationResponse::success()),
+        Err(e) => ResponseUtil::error(&e),
+    }
+}
+
+/// Router for reading the machines and their latest events.
+///
+/// Mounted under `/api/v1/machines`.
+pub fn machines_router() -> Router<Arc<SharedState>> {
+    Router::new()
+        .route(
+            "/{vendor}/{machine}/{serial}/recreate",
+            post(post_recreate_machine),
+        )
+        .route_layer(from_fn(require_maintenance))
+        .route("/", get(get_machines))
+        .route("/{vendor}/{machine}/{serial}/state", get(get_machine_state))
+        .route(
+            "/{vendor}/{machine}/{serial}/live_values",
+            get(get_machine_live_values),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ServerConfig;
+    use axum::body::to_bytes;
+    use axum::http::{HeaderValue, StatusCode, header};
+    use control_core::socketio::event::Event;
+    use control_core::socketio::namespace::cache_one_event;
+    use serde_json::{Value, json};
+
+    #[test]
+    fn test_machine_state() {
+        smol::block_on(machine_state());
+    }
+
+    async fn machine_state() {
+        let app_state = Arc::new(SharedState::new(
+            smol::channel::unbounded().0,
+            smol::channel::unbounded().0,
+            ServerConfig::default(),
+        ));
+        let path = (0xfff0, 1, 4711);
+        let get = |headers| {
+            get_machine_state(
+                State(app_state.clone()),
+                Path(path),
+                Query(FormatQuery::default()),
+                headers,
+            )
+        };
+        let response = get(HeaderMap::new()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let state = json!({ "mode_state": { "mode": "Standby" } });
+        app_state
+            .socketio_setup
+            .namespaces
+            .write()
+            .await
+            .machine_namespace(&machine_from_path(path))
+            .cache(
+                Arc::new(Event::new("StateEvent", state.clone()).into()),
+                &cache_one_event(),
+            );
+
+        let response = get(HeaderMap::new()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), state);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("application/msgpack"),
+        );
+        let response = get(headers).await;
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "application/msgpack"
+        );
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(rmp_serde::from_slice::<Value>(&body).unwrap(), state);
+    }
+}
@@ -1,10 +1,18 @@
 use std::sync::Arc;
 
-use axum::{Json, Router, routing::get};
+use axum::{
+    Json, Router,
+    body::Body,
+    extract::State,
+    http::{Response, StatusCode},
+    routing::get,
+};
 use serde::Serialize;
 
 use crate::SharedState;
+use crate::metrics::cycle_budget::{ComponentBudget, Overrun, cycle_budget_report, overruns};
 use crate::metrics::process::ProcessMetrics;
+use crate::metrics::prometheus::render_metrics;
 use crate::metrics::state::get_latest_runtime_sample;
 
 /// Process-level metrics exposed over the REST API.
@@ -80,6 +88,28 @@ async fn get_runtime_metrics_latest() -> Json<Option<RuntimeMetricsResponse>> {
     Json(opt)
 }
 
+/// Min, average, max and p99 duration of each machine `act` and device processing phase
+/// against its budget.
+async fn get_cycle_budget(State(app_state): State<Arc<SharedState>>) -> Json<Vec<ComponentBudget>> {
+    Json(cycle_budget_report(&app_state.config.cycle_budget))
+}
+
+/// The latest cycle budget overruns, oldest first.
+async fn get_overruns() -> Json<Vec<Overrun>> {
+    Json(overruns())
+}
+
+/// Runtime, RT loop and machine metrics for Prometheus.
+///
+/// Mounted at `/metrics` as scrapers expect it there, outside the user authentication.
+pub async fn get_prometheus_metrics() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "text/plain; version=0.0.4; charset=utf-8")
+        .body(Body::from(render_metrics()))
+        .unwrap()
+}
+
 /// Router for metrics-related REST endpoints.
 ///
 /// Mounted under `/api/v1/metrics`.
@@ -87,4 +117,6 @@ pub fn metrics_router() -> Router<Arc<SharedState>> {
     Router::new()
         .route("/process/metrics", get(get_process_metrics))
         .route("/runtime/latest", get(get_runtime_metrics_latest))
+        .route("/cycle_budget", get(get_cycle_budget))
+        .route("/cycle_budget/overruns", get(get_overruns))
 }
@@ -1,4 +1,15 @@
+pub mod audit;
+pub mod auth;
+pub mod config;
+pub mod ethercat;
+pub mod inverter_backups;
+pub mod laser;
+pub mod lines;
 pub mod machine_mutation;
+pub mod machines;
 pub mod metrics;
 pub mod mutation;
+pub mod production;
+pub mod schema;
+pub mod stream;
 pub mod write_machine_device_identification;
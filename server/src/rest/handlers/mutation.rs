@@ -1,10 +1,11 @@
 use std::fmt::Debug;
 
+use schemars::JsonSchema;
 use serde::Serialize;
 
 use machines::machine_identification::MachineIdentificationUnique;
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Clone, JsonSchema)]
 pub struct MutationResponse {
     pub success: bool,
     pub error: Option<String>,
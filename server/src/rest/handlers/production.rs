@@ -0,0 +1,110 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    body::Body,
+    extract::{Path, Query},
+    http::{Response, StatusCode},
+    routing::get,
+};
+use control_core::helpers::csv::to_csv;
+use machines::production_record::ProductionRecord;
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::SharedState;
+use crate::production::production_records;
+use crate::rest::util::{ResponseUtil, ResponseUtilError};
+
+#[derive(Debug, Default, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum ExportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub(crate) struct RecordsQuery {
+    #[serde(default)]
+    format: ExportFormat,
+    lot_number: Option<String>,
+    /// Winder serial
+    winder: Option<u16>,
+}
+
+fn load_records() -> Result<Vec<ProductionRecord>, ResponseUtilError> {
+    let store = production_records().ok_or_else(|| {
+        ResponseUtilError::Error(anyhow::anyhow!("Production records are not available"))
+    })?;
+    let records = store.lock().unwrap().load();
+    records.map_err(ResponseUtilError::Error)
+}
+
+fn csv_response(records: &[ProductionRecord]) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "text/csv")
+        .header(
+            "Content-Disposition",
+            "attachment; filename=\"production_records.csv\"",
+        )
+        .body(Body::from(to_csv(records)))
+        .unwrap()
+}
+
+/// All production records, optionally filtered by lot number or winder.
+async fn get_production_records(Query(query): Query<RecordsQuery>) -> Response<Body> {
+    let records = match load_records() {
+        Ok(records) => records,
+        Err(e) => return e.into(),
+    };
+
+    let records: Vec<ProductionRecord> = records
+        .into_iter()
+        .filter(|record| {
+            query
+                .lot_number
+                .as_ref()
+                .is_none_or(|lot_number| &record.lot_number == lot_number)
+        })
+        .filter(|record| {
+            query
+                .winder
+                .is_none_or(|serial| record.winder.serial == serial)
+        })
+        .collect();
+
+    match query.format {
+        ExportFormat::Json => ResponseUtil::ok(records),
+        ExportFormat::Csv => csv_response(&records),
+    }
+}
+
+/// A single production record, e.g. to print the spool label.
+async fn get_production_record(
+    Path(id): Path<u64>,
+    Query(query): Query<RecordsQuery>,
+) -> Response<Body> {
+    let records = match load_records() {
+        Ok(records) => records,
+        Err(e) => return e.into(),
+    };
+
+    match records.into_iter().find(|record| record.id == id) {
+        Some(record) => match query.format {
+            ExportFormat::Json => ResponseUtil::ok(record),
+            ExportFormat::Csv => csv_response(&[record]),
+        },
+        None => ResponseUtil::not_found(&format!("No production record {}", id)),
+    }
+}
+
+/// Router for production record REST endpoints.
+///
+/// Mounted under `/api/v1/production`.
+pub fn production_router() -> Router<Arc<SharedState>> {
+    Router::new()
+        .route("/records", get(get_production_records))
+        .route("/records/{id}", get(get_production_record))
+}
@@ -0,0 +1,27 @@
+use std::sync::Arc;
+
+use axum::{Router, body::Body, http::Response, routing::get};
+use machines::registry::MACHINE_REGISTRY;
+
+use crate::SharedState;
+use crate::rest::openapi::openapi_document;
+use crate::rest::util::ResponseUtil;
+
+/// JSON Schemas of the mutations and events of every registered machine type.
+async fn get_machine_schemas() -> Response<Body> {
+    ResponseUtil::ok(MACHINE_REGISTRY.schemas())
+}
+
+/// OpenAPI document of the REST routes.
+async fn get_openapi() -> Response<Body> {
+    ResponseUtil::ok(openapi_document(&MACHINE_REGISTRY.schemas()))
+}
+
+/// Router for the machine-readable API description.
+///
+/// Mounted under `/api/v1/schema`.
+pub fn schema_router() -> Router<Arc<SharedState>> {
+    Router::new()
+        .route("/machines", get(get_machine_schemas))
+        .route("/openapi.json", get(get_openapi))
+}
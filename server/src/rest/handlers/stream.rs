@@ -0,0 +1,388 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use axum::{
+    Router,
+    body::Body,
+    extract::{
+        Path, Query, State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    http::Response,
+    response::{
+        IntoResponse,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::get,
+};
+use control_core::socketio::event::GenericEvent;
+use control_core::socketio::namespace::EventStream;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use smol::channel::Receiver;
+use smol::{Timer, future};
+
+use super::machines::machine_from_path;
+use crate::SharedState;
+use crate::rest::util::ResponseUtilError;
+use crate::socketio::namespace_id::NamespaceId;
+
+/// Events a stream buffers for a slow client before it drops them
+const STREAM_CAPACITY: usize = 256;
+
+/// Minimum time between two warnings about the events dropped for a slow client
+const DROPPED_REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Subscription of an event stream
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub(crate) struct StreamQuery {
+    /// Comma separated event names like `StateEvent,LiveValuesEvent`, all events if missing
+    events: Option<String>,
+    /// Minimum time between two events of the same name in ms, the latest event in between
+    /// follows once it passed
+    min_interval_ms: Option<u64>,
+    /// Session token for clients that can't send the `Authorization` header, like an
+    /// `EventSource` or a WebSocket of a browser
+    token: Option<String>,
+}
+
+impl StreamQuery {
+    pub(crate) fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    fn event_names(&self) -> Option<HashSet<String>> {
+        let events = self.events.as_deref()?;
+        Some(
+            events
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect(),
+        )
+    }
+}
+
+/// Events of a namespace for one client, at most one per name and `min_interval`
+struct EventThrottle {
+    receiver: Receiver<Arc<GenericEvent>>,
+    /// The cached events of the namespace, sent before the emitted ones
+    cached: VecDeque<Arc<GenericEvent>>,
+    min_interval: Duration,
+    last_sent: HashMap<String, Instant>,
+    /// Latest event by name that came too early
+    pending: HashMap<String, Arc<GenericEvent>>,
+    /// Counted by the namespace, warned about here instead of in the RT loop
+    dropped: Arc<AtomicU64>,
+    last_dropped_report: Instant,
+}
+
+impl EventThrottle {
+    fn new(
+        receiver: Receiver<Arc<GenericEvent>>,
+        cached: VecDeque<Arc<GenericEvent>>,
+        min_interval: Duration,
+        dropped: Arc<AtomicU64>,
+    ) -> Self {
+        Self {
+            receiver,
+            cached,
+            min_interval,
+            last_sent: HashMap::new(),
+            pending: HashMap::new(),
+            dropped,
+            last_dropped_report: Instant::now(),
+        }
+    }
+
+    fn report_dropped(&mut self) {
+        if self.last_dropped_report.elapsed() < DROPPED_REPORT_INTERVAL {
+            return;
+        }
+        self.last_dropped_report = Instant::now();
+        let dropped = self.dropped.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            tracing::warn!(
+                "Event stream is full, dropped {} events in the last {:?}",
+                dropped,
+                DROPPED_REPORT_INTERVAL
+            );
+        }
+    }
+
+    /// `None` once the namespace is gone
+    async fn next(&mut self) -> Option<Arc<GenericEvent>> {
+        self.report_dropped();
+        if let Some(event) = self.cached.pop_front() {
+            return Some(event);
+        }
+        loop {
+            let next_due = self
+                .pending
+                .keys()
+                .map(|name| (self.last_sent[name] + self.min_interval, name.clone()))
+                .min();
+            if let Some((due, name)) = &next_due
+                && *due <= Instant::now()
+            {
+                self.last_sent.insert(name.clone(), Instant::now());
+                return self.pending.remove(name);
+            }
+
+            let received = match next_due {
+                Some((due, _)) => {
+                    future::or(async { Some(self.receiver.recv().await) }, async {
+                        Timer::at(due).await;
+                        None
+                    })
+                    .await
+                }
+                None => Some(self.receiver.recv().await),
+            };
+            // the next pending event is due
+            let Some(received) = received else {
+                continue;
+            };
+            let event = received.ok()?;
+
+            let now = Instant::now();
+            if self
+                .last_sent
+                .get(&event.name)
+                .is_none_or(|last| now.duration_since(*last) >= self.min_interval)
+            {
+                self.last_sent.insert(event.name.clone(), now);
+                self.pending.remove(&event.name);
+                return Some(event);
+            }
+            self.pending.insert(event.name.clone(), event);
+        }
+    }
+}
+
+/// Subscribes a stream like a socket.io socket, the machine then emits its current state to it
+async fn subscribe(
+    app_state: &SharedState,
+    namespace_id: NamespaceId,
+    query: &StreamQuery,
+) -> Result<EventThrottle, ResponseUtilError> {
+    let machine_sender = match &namespace_id {
+        NamespaceId::Main => None,
+        NamespaceId::Machine(machine) => {
+            let sender = app_state.api_machines.lock().await.get(machine).cloned();
+            let sender = sender.ok_or_else(|| {
+                ResponseUtilError::NotFound(anyhow::anyhow!("Machine {} not found", machine))
+            })?;
+            Some(sender)
+        }
+    };
+
+    let (sender, receiver) = smol::channel::bounded(STREAM_CAPACITY);
+    let stream = EventStream::new(query.event_names(), sender);
+    let dropped = stream.dropped.clone();
+    let mut namespaces = app_state.socketio_setup.namespaces.write().await;
+    if let NamespaceId::Machine(machine) = &namespace_id {
+        namespaces.machine_namespace(machine);
+    }
+    let namespace = namespaces
+        .apply_mut(namespace_id)
+        .await
+        .map_err(ResponseUtilError::Error)?;
+    let cached = namespace.subscribe_stream(stream);
+    let namespace = namespace.clone();
+    drop(namespaces);
+
+    if let Some(machine_sender) = machine_sender {
+        let _ = machine_sender
+            .send(machines::MachineMessage::SubscribeNamespace(namespace))
+            .await;
+    }
+
+    let min_interval = Duration::from_millis(query.min_interval_ms.unwrap_or(0));
+    Ok(EventThrottle::new(
+        receiver,
+        cached.into(),
+        min_interval,
+        dropped,
+    ))
+}
+
+async fn sse_response(
+    app_state: &SharedState,
+    namespace_id: NamespaceId,
+    query: &StreamQuery,
+) -> Response<Body> {
+    let throttle = match subscribe(app_state, namespace_id, query).await {
+        Ok(throttle) => throttle,
+        Err(e) => return e.into(),
+    };
+    let events = smol::stream::unfold(throttle, |mut throttle| async move {
+        let event = throttle.next().await?;
+        let sse_event = Event::default()
+            .event(&event.name)
+            .json_data(event.as_ref());
+        Some((sse_event, throttle))
+    });
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+async fn ws_response(
+    app_state: &SharedState,
+    namespace_id: NamespaceId,
+    query: &StreamQuery,
+    upgrade: WebSocketUpgrade,
+) -> Response<Body> {
+    match subscribe(app_state, namespace_id, query).await {
+        Ok(throttle) => upgrade.on_upgrade(|socket| send_events(throttle, socket)),
+        Err(e) => e.into(),
+    }
+}
+
+/// Sends the events as JSON text frames until the client closes the socket
+async fn send_events(mut throttle: EventThrottle, mut socket: WebSocket) {
+    loop {
+        let event = future::or(throttle.next(), async {
+            while let Some(Ok(message)) = socket.recv().await {
+                if let Message::Close(_) = message {
+                    break;
+                }
+            }
+            None
+        })
+        .await;
+        let Some(event) = event else {
+            break;
+        };
+        let text = match serde_json::to_string(event.as_ref()) {
+            Ok(text) => text,
+            Err(e) => {
+                tracing::error!("Failed to serialize {} for a stream: {}", event.name, e);
+                continue;
+            }
+        };
+        if socket.send(Message::Text(text.into())).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Events of `/main` as Server-Sent Events, the event data is the JSON of the socket.io event.
+async fn get_main_sse(
+    State(app_state): State<Arc<SharedState>>,
+    Query(query): Query<StreamQuery>,
+) -> Response<Body> {
+    sse_response(&app_state, NamespaceId::Main, &query).await
+}
+
+/// Events of `/main` as JSON text frames of a WebSocket.
+async fn get_main_ws(
+    State(app_state): State<Arc<SharedState>>,
+    Query(query): Query<StreamQuery>,
+    upgrade: WebSocketUpgrade,
+) -> Response<Body> {
+    ws_response(&app_state, NamespaceId::Main, &query, upgrade).await
+}
+
+/// Events of a machine as Server-Sent Events.
+async fn get_machine_sse(
+    State(app_state): State<Arc<SharedState>>,
+    Path(path): Path<(u16, u16, u16)>,
+    Query(query): Query<StreamQuery>,
+) -> Response<Body> {
+    let namespace_id = NamespaceId::Machine(machine_from_path(path));
+    sse_response(&app_state, namespace_id, &query).await
+}
+
+/// Events of a machine as JSON text frames of a WebSocket.
+async fn get_machine_ws(
+    State(app_state): State<Arc<SharedState>>,
+    Path(path): Path<(u16, u16, u16)>,
+    Query(query): Query<StreamQuery>,
+    upgrade: WebSocketUpgrade,
+) -> Response<Body> {
+    let namespace_id = NamespaceId::Machine(machine_from_path(path));
+    ws_response(&app_state, namespace_id, &query, upgrade).await
+}
+
+/// Router for the event streams of the socket.io namespaces in plain JSON.
+///
+/// Mounted under `/api/v1/stream`.
+pub fn stream_router() -> Router<Arc<SharedState>> {
+    Router::new()
+        .route("/main", get(get_main_sse))
+        .route("/main/ws", get(get_main_ws))
+        .route("/machine/{vendor}/{machine}/{serial}", get(get_machine_sse))
+        .route(
+            "/machine/{vendor}/{machine}/{serial}/ws",
+            get(get_machine_ws),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(name: &str, ts: u64) -> Arc<GenericEvent> {
+        Arc::new(GenericEvent {
+            name: name.to_string(),
+            data: Box::new(ts),
+            ts,
+        })
+    }
+
+    #[test]
+    fn test_event_names() {
+        let query = StreamQuery {
+            events: Some("StateEvent, LiveValuesEvent,".to_string()),
+            min_interval_ms: None,
+            token: None,
+        };
+        assert_eq!(
+            query.event_names(),
+            Some(HashSet::from([
+                "StateEvent".to_string(),
+                "LiveValuesEvent".to_string()
+            ]))
+        );
+        assert_eq!(StreamQuery::default().event_names(), None);
+    }
+
+    #[test]
+    fn test_throttle() {
+        smol::block_on(throttle());
+    }
+
+    async fn throttle() {
+        let (sender, receiver) = smol::channel::unbounded();
+        let mut throttle = EventThrottle::new(
+            receiver,
+            VecDeque::from([event("LiveValuesEvent", 0), event("LiveValuesEvent", 1)]),
+            Duration::from_millis(50),
+            Arc::default(),
+        );
+        let mut next_ts = async || throttle.next().await.unwrap().ts;
+
+        // all cached events, then the first emitted one right away
+        for ts in 2..6 {
+            sender.try_send(event("LiveValuesEvent", ts)).unwrap();
+        }
+        sender.try_send(event("StateEvent", 6)).unwrap();
+        assert_eq!(next_ts().await, 0);
+        assert_eq!(next_ts().await, 1);
+        assert_eq!(next_ts().await, 2);
+        // other names are not held back
+        let start = Instant::now();
+        assert_eq!(next_ts().await, 6);
+        // only the latest of the events in between, after the interval
+        assert_eq!(next_ts().await, 5);
+        assert!(start.elapsed() >= Duration::from_millis(40));
+
+        drop(sender);
+        assert!(throttle.next().await.is_none());
+    }
+}
@@ -1,14 +1,24 @@
-use crate::{app_state::SharedState, rest::util::ResponseUtil};
-use axum::{Json, extract::State, http::Response};
+use crate::{
+    app_state::SharedState,
+    audit::{AuditAction, AuditEntry, record_audit},
+    auth::Identity,
+    rest::util::ResponseUtil,
+};
+use axum::{
+    Extension, Json,
+    extract::{ConnectInfo, State},
+    http::Response,
+};
 use machines::machine_identification::{
     DeviceHardwareIdentificationEthercat, DeviceMachineIdentification,
 };
 
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 use super::mutation::MutationResponse;
 
-#[derive(serde::Deserialize, Debug)]
+#[derive(serde::Deserialize, serde::Serialize, Debug, schemars::JsonSchema)]
 pub struct MachineDeviceInfoRequest {
     pub device_machine_identification: DeviceMachineIdentification,
     pub hardware_identification_ethercat: DeviceHardwareIdentificationEthercat,
@@ -17,8 +27,22 @@ pub struct MachineDeviceInfoRequest {
 #[axum::debug_handler]
 pub async fn post_write_machine_device_identification(
     State(app_state): State<Arc<SharedState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Extension(identity): Extension<Identity>,
     Json(body): Json<MachineDeviceInfoRequest>,
 ) -> Response<axum::body::Body> {
+    let entry = AuditEntry::new(
+        identity.username,
+        client.to_string(),
+        AuditAction::DeviceIdentificationWrite,
+        serde_json::to_value(&body).unwrap_or_default(),
+    )
+    .with_machine(
+        body.device_machine_identification
+            .machine_identification_unique
+            .clone(),
+    );
+
     let res = app_state
         .rt_machine_creation_channel
         .send(crate::app_state::HotThreadMessage::WriteMachineDeviceInfo(
@@ -26,6 +50,7 @@ pub async fn post_write_machine_device_identification(
         ))
         .await;
 
+    record_audit(entry.with_result(&res));
     match res {
         Ok(_) => (),
         Err(e) => tracing::error!(
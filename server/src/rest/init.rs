@@ -1,5 +1,7 @@
 use anyhow::Result;
-use axum::routing::post;
+use axum::middleware::{from_fn, from_fn_with_state};
+use axum::routing::{get, post};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::thread;
 use tower_http::cors::CorsLayer;
@@ -11,7 +13,58 @@ use super::handlers::write_machine_device_identification::post_write_machine_dev
 use crate::app_state::SharedState;
 use crate::socketio::init::init_socketio;
 
-use crate::rest::handlers::metrics::metrics_router;
+use crate::rest::auth::{
+    authenticate, authenticate_stream, require_admin, require_metrics_token, require_viewer,
+};
+use crate::rest::handlers::audit::audit_router;
+use crate::rest::handlers::auth::auth_router;
+use crate::rest::handlers::config::config_router;
+use crate::rest::handlers::ethercat::ethercat_router;
+use crate::rest::handlers::inverter_backups::inverter_backups_router;
+use crate::rest::handlers::laser::laser_router;
+use crate::rest::handlers::lines::lines_router;
+use crate::rest::handlers::machines::machines_router;
+use crate::rest::handlers::metrics::{get_prometheus_metrics, metrics_router};
+use crate::rest::handlers::production::production_router;
+use crate::rest::handlers::schema::schema_router;
+use crate::rest::handlers::stream::stream_router;
+
+/// The REST routes, documented in [`super::openapi`]
+pub fn api_router(app_state: Arc<SharedState>) -> axum::Router {
+    axum::Router::new()
+        .route(
+            "/api/v1/write_machine_device_identification",
+            post(post_write_machine_device_identification).route_layer(from_fn(require_admin)),
+        )
+        .route("/api/v1/machine/mutate", post(post_machine_mutate))
+        .nest("/api/v1/metrics", metrics_router())
+        .nest("/api/v1/machine/laser", laser_router())
+        .nest("/api/v1/machines", machines_router())
+        .nest("/api/v1/ethercat", ethercat_router())
+        .nest("/api/v1/inverter_backups", inverter_backups_router())
+        .nest("/api/v1/production", production_router())
+        .nest("/api/v1/lines", lines_router())
+        .nest("/api/v1/audit", audit_router())
+        .nest("/api/v1/config", config_router())
+        .nest("/api/v1/schema", schema_router())
+        .route_layer(from_fn(require_viewer))
+        // browsers can't send the bearer token with an EventSource or WebSocket
+        .nest(
+            "/api/v1/stream",
+            stream_router()
+                .route_layer(from_fn(require_viewer))
+                .route_layer(from_fn_with_state(app_state.clone(), authenticate_stream)),
+        )
+        // scrapers have no user, they send the metrics token if there is one
+        .route(
+            "/metrics",
+            get(get_prometheus_metrics)
+                .route_layer(from_fn_with_state(app_state.clone(), require_metrics_token)),
+        )
+        .nest("/api/v1/auth", auth_router())
+        .layer(from_fn_with_state(app_state.clone(), authenticate))
+        .with_state(app_state)
+}
 
 async fn init_api(app_state: Arc<SharedState>) -> Result<()> {
     let cors = CorsLayer::permissive();
@@ -22,27 +75,24 @@ async fn init_api(app_state: Arc<SharedState>) -> Result<()> {
         .on_request(DefaultOnRequest::new().level(Level::TRACE))
         .on_response(DefaultOnResponse::new().level(Level::TRACE));
 
-    let app = axum::Router::new()
-        .route(
-            "/api/v1/write_machine_device_identification",
-            post(post_write_machine_device_identification),
-        )
-        .route("/api/v1/machine/mutate", post(post_machine_mutate))
-        .nest("/api/v1/metrics", metrics_router())
+    let app = api_router(app_state.clone())
         .layer(socketio_layer)
         .layer(cors)
-        .layer(trace_layer)
-        .with_state(app_state.clone());
+        .layer(trace_layer);
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3001")
+    let address = app_state.config.bind_address();
+    let listener = tokio::net::TcpListener::bind(&address)
         .await
-        .expect("Failed to bind to port 3001");
+        .unwrap_or_else(|e| panic!("Failed to bind to {}: {}", address, e));
 
-    tracing::info!("HTTP server running on 0.0.0.0:3001");
+    tracing::info!("HTTP server running on {}", address);
 
-    axum::serve(listener, app)
-        .await
-        .map_err(|e| anyhow::anyhow!("Server error: {}", e))
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .map_err(|e| anyhow::anyhow!("Server error: {}", e))
 }
 
 /// Starts the API server in its own thread with a single-threaded Tokio runtime
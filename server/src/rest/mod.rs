@@ -1,3 +1,5 @@
+pub mod auth;
 pub mod handlers;
 pub mod init;
+pub mod openapi;
 pub mod util;
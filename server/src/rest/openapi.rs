@@ -0,0 +1,706 @@
+use machines::api_schema::MachineApiSchema;
+use machines::frequency_inverter::parameters::ParameterBackup;
+use machines::machine_identification::MachineIdentificationUnique;
+use schemars::{JsonSchema, Schema, generate::SchemaSettings};
+use serde_json::{Map, Value, json};
+
+use crate::app_state::EtherCatDeviceMetaData;
+use crate::auth::{Identity, Role};
+use crate::config::ServerConfig;
+use crate::metrics::cycle_budget::{ComponentBudget, Overrun};
+use crate::rest::handlers::audit::EntriesQuery;
+use crate::rest::handlers::auth::{LoginBody, UserBody};
+use crate::rest::handlers::inverter_backups::{InverterBackups, RestoreBody};
+use crate::rest::handlers::mutation::MutationResponse;
+use crate::rest::handlers::production::RecordsQuery;
+use crate::rest::handlers::stream::StreamQuery;
+use crate::rest::handlers::write_machine_device_identification::MachineDeviceInfoRequest;
+use crate::rest::util::FormatQuery;
+use crate::socketio::main_namespace::machines_event::MachineObj;
+
+/// Body of `/api/v1/machine/mutate`, its `data` is one of the machine mutations
+const MACHINE_MUTATION_BODY: &str = "MachineMutationBody";
+
+/// One REST route of the OpenAPI document
+struct ApiRoute {
+    method: &'static str,
+    path: &'static str,
+    summary: &'static str,
+    /// `None` if the route works without a login
+    role: Option<Role>,
+    query: Option<fn() -> Schema>,
+    body: Option<fn() -> Schema>,
+    response: Option<fn() -> Schema>,
+}
+
+impl ApiRoute {
+    const fn new(
+        method: &'static str,
+        path: &'static str,
+        summary: &'static str,
+        role: Option<Role>,
+    ) -> Self {
+        Self {
+            method,
+            path,
+            summary,
+            role,
+            query: None,
+            body: None,
+            response: None,
+        }
+    }
+
+    const fn with_query(mut self, query: fn() -> Schema) -> Self {
+        self.query = Some(query);
+        self
+    }
+
+    const fn with_body(mut self, body: fn() -> Schema) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    const fn with_response(mut self, response: fn() -> Schema) -> Self {
+        self.response = Some(response);
+        self
+    }
+}
+
+fn root_schema<T: JsonSchema>() -> Schema {
+    SchemaSettings::draft2020_12()
+        .into_generator()
+        .into_root_schema_for::<T>()
+}
+
+/// The body of the mutate route is assembled from the machine schemas, see [`openapi_document`]
+fn machine_mutation_body() -> Schema {
+    Schema::new_ref(format!("#/components/schemas/{}", MACHINE_MUTATION_BODY))
+}
+
+const VIEWER: Option<Role> = Some(Role::Viewer);
+const OPERATOR: Option<Role> = Some(Role::Operator);
+const MAINTENANCE: Option<Role> = Some(Role::Maintenance);
+const ADMIN: Option<Role> = Some(Role::Admin);
+
+fn routes() -> Vec<ApiRoute> {
+    vec![
+        ApiRoute::new(
+            "post",
+            "/api/v1/machine/mutate",
+            "Mutates a machine, the role depends on the mutation",
+            VIEWER,
+        )
+        .with_body(machine_mutation_body)
+        .with_response(root_schema::<MutationResponse>),
+        ApiRoute::new(
+            "post",
+            "/api/v1/write_machine_device_identification",
+            "Writes the machine identification to the EEPROM of an EtherCAT device",
+            ADMIN,
+        )
+        .with_body(root_schema::<MachineDeviceInfoRequest>)
+        .with_response(root_schema::<MutationResponse>),
+        ApiRoute::new(
+            "get",
+            "/api/v1/metrics/process/metrics",
+            "Process metrics of the server",
+            VIEWER,
+        ),
+        ApiRoute::new(
+            "get",
+            "/api/v1/metrics/runtime/latest",
+            "Latest runtime metrics sample",
+            VIEWER,
+        ),
+        ApiRoute::new(
+            "get",
+            "/api/v1/metrics/cycle_budget",
+            "Min, average, max and p99 duration of each machine act and device processing phase against its budget",
+            VIEWER,
+        )
+        .with_response(root_schema::<Vec<ComponentBudget>>),
+        ApiRoute::new(
+            "get",
+            "/api/v1/metrics/cycle_budget/overruns",
+            "Latest machine act calls and device processing phases that took longer than their budget",
+            VIEWER,
+        )
+        .with_response(root_schema::<Vec<Overrun>>),
+        ApiRoute::new(
+            "get",
+            "/metrics",
+            "Runtime, RT loop and machine metrics in the Prometheus text format, needs the bearer api.metrics_token or a viewer if the token is set",
+            None,
+        ),
+        ApiRoute::new(
+            "get",
+            "/api/v1/machine/laser/{serial}/statistics",
+            "SPC statistics of a laser",
+            VIEWER,
+        ),
+        ApiRoute::new(
+            "get",
+            "/api/v1/machines",
+            "All machines with their error, if any",
+            VIEWER,
+        )
+        .with_query(root_schema::<FormatQuery>)
+        .with_response(root_schema::<Vec<MachineObj>>),
+        ApiRoute::new(
+            "get",
+            "/api/v1/machines/{vendor}/{machine}/{serial}/state",
+            "Latest state event of a machine, see the schema of its StateEvent",
+            VIEWER,
+        )
+        .with_query(root_schema::<FormatQuery>),
+        ApiRoute::new(
+            "get",
+            "/api/v1/machines/{vendor}/{machine}/{serial}/live_values",
+            "Latest live values of a machine, see the schema of its LiveValuesEvent",
+            VIEWER,
+        )
+        .with_query(root_schema::<FormatQuery>),
+        ApiRoute::new(
+            "post",
+            "/api/v1/machines/{vendor}/{machine}/{serial}/recreate",
+            "Creates a machine quarantined after a panic again, the outputs of all EtherCAT machines are off for a moment",
+            MAINTENANCE,
+        )
+        .with_response(root_schema::<MutationResponse>),
+        ApiRoute::new(
+            "get",
+            "/api/v1/ethercat/devices",
+            "EtherCAT devices found on the bus",
+            VIEWER,
+        )
+        .with_query(root_schema::<FormatQuery>)
+        .with_response(root_schema::<Vec<EtherCatDeviceMetaData>>),
+        ApiRoute::new(
+            "get",
+            "/api/v1/inverter_backups",
+            "Stored inverter parameter backups and the outcome of the latest backups",
+            VIEWER,
+        )
+        .with_response(root_schema::<InverterBackups>),
+        ApiRoute::new(
+            "get",
+            "/api/v1/inverter_backups/{name}",
+            "Parameters of a stored inverter backup",
+            VIEWER,
+        )
+        .with_response(root_schema::<ParameterBackup>),
+        ApiRoute::new(
+            "post",
+            "/api/v1/inverter_backups/restore",
+            "Writes a stored backup to the inverter of an extruder",
+            ADMIN,
+        )
+        .with_body(root_schema::<RestoreBody>)
+        .with_response(root_schema::<MutationResponse>),
+        ApiRoute::new(
+            "get",
+            "/api/v1/production/records",
+            "Production records as JSON or CSV",
+            VIEWER,
+        )
+        .with_query(root_schema::<RecordsQuery>),
+        ApiRoute::new(
+            "get",
+            "/api/v1/production/records/{id}",
+            "One production record as JSON or CSV",
+            VIEWER,
+        )
+        .with_query(root_schema::<RecordsQuery>),
+        ApiRoute::new(
+            "get",
+            "/api/v1/lines",
+            "Progress of all production lines",
+            VIEWER,
+        ),
+        ApiRoute::new(
+            "post",
+            "/api/v1/lines/{line}/start",
+            "Runs the start sequence of a line",
+            OPERATOR,
+        )
+        .with_response(root_schema::<MutationResponse>),
+        ApiRoute::new(
+            "post",
+            "/api/v1/lines/{line}/stop",
+            "Runs the stop sequence of a line",
+            OPERATOR,
+        )
+        .with_response(root_schema::<MutationResponse>),
+        ApiRoute::new(
+            "post",
+            "/api/v1/lines/{line}/emergency",
+            "Runs the emergency sequence of a line",
+            OPERATOR,
+        )
+        .with_response(root_schema::<MutationResponse>),
+        ApiRoute::new(
+            "get",
+            "/api/v1/audit/entries",
+            "Audit trail as JSON or CSV",
+            OPERATOR,
+        )
+        .with_query(root_schema::<EntriesQuery>),
+        ApiRoute::new("get", "/api/v1/config", "Effective server config", VIEWER)
+            .with_response(root_schema::<ServerConfig>),
+        ApiRoute::new(
+            "get",
+            "/api/v1/schema/machines",
+            "Schemas of the mutations and events of all machine types",
+            VIEWER,
+        )
+        .with_response(root_schema::<Vec<MachineApiSchema>>),
+        ApiRoute::new(
+            "get",
+            "/api/v1/schema/openapi.json",
+            "This document",
+            VIEWER,
+        ),
+        ApiRoute::new(
+            "get",
+            "/api/v1/stream/main",
+            "Events of the main namespace as Server-Sent Events, the cached ones first",
+            VIEWER,
+        )
+        .with_query(root_schema::<StreamQuery>),
+        ApiRoute::new(
+            "get",
+            "/api/v1/stream/main/ws",
+            "Events of the main namespace as JSON text frames of a WebSocket, the cached ones first",
+            VIEWER,
+        )
+        .with_query(root_schema::<StreamQuery>),
+        ApiRoute::new(
+            "get",
+            "/api/v1/stream/machine/{vendor}/{machine}/{serial}",
+            "Events of a machine as Server-Sent Events, starting with its current state",
+            VIEWER,
+        )
+        .with_query(root_schema::<StreamQuery>),
+        ApiRoute::new(
+            "get",
+            "/api/v1/stream/machine/{vendor}/{machine}/{serial}/ws",
+            "Events of a machine as JSON text frames of a WebSocket, starting with its current state",
+            VIEWER,
+        )
+        .with_query(root_schema::<StreamQuery>),
+        ApiRoute::new("post", "/api/v1/auth/login", "Logs a user in", None)
+            .with_body(root_schema::<LoginBody>),
+        ApiRoute::new(
+            "post",
+            "/api/v1/auth/logout",
+            "Ends the session of the bearer token",
+            None,
+        )
+        .with_response(root_schema::<MutationResponse>),
+        ApiRoute::new("get", "/api/v1/auth/me", "The logged in user", VIEWER)
+            .with_response(root_schema::<Identity>),
+        ApiRoute::new("get", "/api/v1/auth/users", "All users", ADMIN),
+        ApiRoute::new(
+            "post",
+            "/api/v1/auth/users",
+            "Creates a user or changes its password and role",
+            ADMIN,
+        )
+        .with_body(root_schema::<UserBody>)
+        .with_response(root_schema::<MutationResponse>),
+        ApiRoute::new(
+            "delete",
+            "/api/v1/auth/users/{username}",
+            "Removes a user",
+            ADMIN,
+        )
+        .with_response(root_schema::<MutationResponse>),
+    ]
+}
+
+/// Moves the `$defs` of a root schema into the components, prefixed to keep the types of
+/// different machines apart, and returns the schema itself
+fn hoist_definitions(prefix: &str, schema: Schema, components: &mut Map<String, Value>) -> Value {
+    fn rewrite_refs(prefix: &str, value: &mut Value) {
+        match value {
+            Value::Object(object) => {
+                for (key, value) in object.iter_mut() {
+                    match value {
+                        Value::String(reference) if key == "$ref" => {
+                            if let Some(name) = reference.strip_prefix("#/$defs/") {
+                                *reference = format!("#/components/schemas/{}{}", prefix, name);
+                            }
+                        }
+                        value => rewrite_refs(prefix, value),
+                    }
+                }
+            }
+            Value::Array(values) => values
+                .iter_mut()
+                .for_each(|value| rewrite_refs(prefix, value)),
+            _ => (),
+        }
+    }
+
+    let mut value = schema.to_value();
+    if let Value::Object(object) = &mut value {
+        object.remove("$schema");
+        if let Some(Value::Object(definitions)) = object.remove("$defs") {
+            for (name, mut definition) in definitions {
+                rewrite_refs(prefix, &mut definition);
+                components.insert(format!("{}{}", prefix, name), definition);
+            }
+        }
+    }
+    rewrite_refs(prefix, &mut value);
+    value
+}
+
+/// Query parameters from the properties of a query struct schema
+fn query_parameters(schema: Value) -> Vec<Value> {
+    let Some(Value::Object(properties)) = schema.get("properties").cloned() else {
+        return vec![];
+    };
+    let required = schema.get("required").cloned().unwrap_or(json!([]));
+    properties
+        .into_iter()
+        .map(|(name, mut property)| {
+            let description = property
+                .as_object_mut()
+                .and_then(|property| property.remove("description"));
+            let mut parameter = json!({
+                "name": name,
+                "in": "query",
+                "required": required.as_array().is_some_and(|r| r.contains(&json!(name))),
+                "schema": property,
+            });
+            if let Some(description) = description {
+                parameter["description"] = description;
+            }
+            parameter
+        })
+        .collect()
+}
+
+/// Path parameters from the `{name}` segments of a route
+fn path_parameters(path: &str) -> Vec<Value> {
+    path.split('/')
+        .filter_map(|segment| segment.strip_prefix('{')?.strip_suffix('}'))
+        .map(|name| {
+            let schema = match name {
+                "serial" | "id" | "vendor" | "machine" => {
+                    json!({ "type": "integer", "minimum": 0 })
+                }
+                _ => json!({ "type": "string" }),
+            };
+            json!({ "name": name, "in": "path", "required": true, "schema": schema })
+        })
+        .collect()
+}
+
+/// OpenAPI 3.1 document of the REST routes, the mutations are those of `machines`
+pub fn openapi_document(machines: &[MachineApiSchema]) -> Value {
+    let mut components = Map::new();
+
+    let mutations: Vec<Value> = machines
+        .iter()
+        .map(|machine| {
+            let name = format!("{}.Mutation", machine.name);
+            let mutation = hoist_definitions(
+                &format!("{}.", machine.name),
+                machine.mutation.clone(),
+                &mut components,
+            );
+            components.insert(name.clone(), mutation);
+            json!({ "$ref": format!("#/components/schemas/{}", name) })
+        })
+        .collect();
+    let machine = hoist_definitions(
+        "",
+        root_schema::<MachineIdentificationUnique>(),
+        &mut components,
+    );
+    components.insert(
+        MACHINE_MUTATION_BODY.to_string(),
+        json!({
+            "type": "object",
+            "required": ["machine_identification_unique", "data"],
+            "properties": {
+                "machine_identification_unique": machine,
+                "data": { "oneOf": mutations },
+            },
+        }),
+    );
+
+    let mut paths = Map::new();
+    for route in routes() {
+        let mut operation = json!({ "summary": route.summary });
+        let mut parameters = path_parameters(route.path);
+        if let Some(query) = route.query {
+            parameters.extend(query_parameters(hoist_definitions(
+                "",
+                query(),
+                &mut components,
+            )));
+        }
+        if !parameters.is_empty() {
+            operation["parameters"] = Value::Array(parameters);
+        }
+        if let Some(body) = route.body {
+            let schema = hoist_definitions("", body(), &mut components);
+            operation["requestBody"] = json!({
+                "required": true,
+                "content": { "application/json": { "schema": schema } },
+            });
+        }
+        let mut ok = json!({ "description": "Success" });
+        if let Some(response) = route.response {
+            let schema = hoist_definitions("", response(), &mut components);
+            ok["content"] = json!({ "application/json": { "schema": schema } });
+        }
+        operation["responses"] = json!({
+            "200": ok,
+            "401": { "description": "Not logged in" },
+            "403": { "description": "The role of the user is too low" },
+        });
+        if let Some(role) = route.role {
+            operation["security"] = json!([{ "bearer": [] }]);
+            operation["x-required-role"] = json!(role);
+        }
+
+        let path = paths
+            .entry(route.path)
+            .or_insert_with(|| Value::Object(Map::new()));
+        path[route.method] = operation;
+    }
+
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "QiTech Control",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "schemas": components,
+            "securitySchemes": {
+                "bearer": { "type": "http", "scheme": "bearer" },
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_state::SharedState;
+    use crate::rest::init::api_router;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use machines::registry::MACHINE_REGISTRY;
+    use std::path::Path;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    fn collect_refs<'a>(value: &'a Value, refs: &mut Vec<&'a str>) {
+        match value {
+            Value::Object(object) => {
+                for (key, value) in object {
+                    match value {
+                        Value::String(reference) if key == "$ref" => refs.push(reference),
+                        value => collect_refs(value, refs),
+                    }
+                }
+            }
+            Value::Array(values) => values.iter().for_each(|value| collect_refs(value, refs)),
+            _ => (),
+        }
+    }
+
+    #[test]
+    fn test_openapi_refs_resolve() {
+        let machines = MACHINE_REGISTRY.schemas();
+        let document = openapi_document(&machines);
+
+        let mut refs = vec![];
+        collect_refs(&document, &mut refs);
+        assert!(!refs.is_empty());
+        for reference in refs {
+            let pointer = reference.strip_prefix('#').unwrap();
+            assert!(
+                document.pointer(pointer).is_some(),
+                "{} does not resolve",
+                reference
+            );
+        }
+
+        let mutations = document
+            .pointer("/components/schemas/MachineMutationBody/properties/data/oneOf")
+            .and_then(Value::as_array)
+            .unwrap();
+        assert_eq!(mutations.len(), machines.len());
+        assert!(
+            document
+                .pointer("/components/schemas/Winder2.Mutation")
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_parameters() {
+        let parameters = query_parameters(hoist_definitions(
+            "",
+            root_schema::<RecordsQuery>(),
+            &mut Map::new(),
+        ));
+        let names: Vec<&str> = parameters
+            .iter()
+            .filter_map(|parameter| parameter["name"].as_str())
+            .collect();
+        assert_eq!(names, ["format", "lot_number", "winder"]);
+
+        let parameters = path_parameters("/api/v1/machine/laser/{serial}/statistics");
+        assert_eq!(parameters.len(), 1);
+        assert_eq!(parameters[0]["schema"]["type"], "integer");
+    }
+
+    /// The path with every `{parameter}` set to 1
+    fn example_path(path: &str) -> String {
+        path.split('/')
+            .map(|part| if part.starts_with('{') { "1" } else { part })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    #[test]
+    fn test_documented_paths_are_mounted() {
+        let (sender, _receiver) = smol::channel::unbounded();
+        let (main_sender, _main_receiver) = smol::channel::unbounded();
+        let mut config = ServerConfig::default();
+        let missing = std::env::temp_dir().join(format!("openapi_test_{}", std::process::id()));
+        config.auth.users_path = missing.join("users.json").display().to_string();
+        config.lines.path = missing.join("lines.json").display().to_string();
+        let router = api_router(Arc::new(SharedState::new(sender, main_sender, config)));
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let status = |method: &str, path: &str| {
+            let request = Request::builder()
+                .method(method.to_uppercase().as_str())
+                .uri(example_path(path))
+                .body(Body::empty())
+                .unwrap();
+            runtime
+                .block_on(router.clone().oneshot(request))
+                .unwrap()
+                .status()
+        };
+
+        // without a login the routes answer 401 or run their handler, the authentication also
+        // hides a wrong method, the methods are compared with the source of the routers
+        for route in routes() {
+            assert_ne!(
+                status(route.method, route.path),
+                StatusCode::NOT_FOUND,
+                "{} is documented but not mounted",
+                route.path
+            );
+        }
+        assert_eq!(status("get", "/api/v1/undocumented"), StatusCode::NOT_FOUND);
+    }
+
+    /// Index of the `)` that closes the call whose arguments start at `start`
+    fn closing_paren(source: &str, start: usize) -> usize {
+        let mut depth = 1;
+        for (index, char) in source[start..].char_indices() {
+            match char {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                return start + index;
+            }
+        }
+        panic!("Unbalanced parentheses");
+    }
+
+    /// `(method, path)` of the `.route("path", get(..).post(..))` calls in the source
+    fn route_calls(prefix: &str, source: &str) -> Vec<(String, String)> {
+        let mut routes = vec![];
+        for (start, _) in source.match_indices(".route(\"") {
+            let path_start = start + ".route(\"".len();
+            let path_end = path_start + source[path_start..].find('"').unwrap();
+            let path = match &source[path_start..path_end] {
+                "/" if !prefix.is_empty() => String::new(),
+                path => path.to_string(),
+            };
+            let methods = &source[path_end..closing_paren(source, path_start)];
+            for method in ["get", "post", "put", "patch", "delete"] {
+                let called = [format!(",{}(", method), format!(".{}(", method)];
+                if called.iter().any(|call| methods.contains(call.as_str())) {
+                    routes.push((method.to_string(), format!("{}{}", prefix, path)));
+                }
+            }
+        }
+        routes
+    }
+
+    /// Routes of [`api_router`] and the routers it nests, read from their source
+    fn mounted_routes() -> Vec<(String, String)> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/rest");
+        // without whitespace the calls look the same however they are formatted
+        let read = |path: &Path| {
+            let source = std::fs::read_to_string(path).unwrap();
+            source.split_whitespace().collect::<String>()
+        };
+        let init = read(&dir.join("init.rs"));
+        let handlers: Vec<String> = std::fs::read_dir(dir.join("handlers"))
+            .unwrap()
+            .map(|entry| read(&entry.unwrap().path()))
+            .collect();
+
+        let mut routes = route_calls("", &init);
+        for (start, _) in init.match_indices(".nest(\"") {
+            let arguments = &init[start + ".nest(\"".len()..];
+            let (prefix, router) = arguments.split_once("\",").unwrap();
+            let router = &router[..router.find('(').unwrap()];
+            let source = handlers
+                .iter()
+                .find(|source| source.contains(&format!("pubfn{}(", router)))
+                .unwrap_or_else(|| panic!("{} not found", router));
+            routes.extend(route_calls(prefix, source));
+        }
+        routes
+    }
+
+    #[test]
+    fn test_documented_routes_match_the_routers() {
+        let documented: Vec<(String, String)> = routes()
+            .iter()
+            .map(|route| (route.method.to_string(), route.path.to_string()))
+            .collect();
+        let mounted = mounted_routes();
+        assert!(mounted.contains(&("get".to_string(), "/api/v1/machines".to_string())));
+        assert!(mounted.contains(&("post".to_string(), "/api/v1/auth/users".to_string())));
+        for route in &mounted {
+            assert!(
+                documented.contains(route),
+                "{} {} is mounted but not documented",
+                route.0,
+                route.1
+            );
+        }
+        for route in &documented {
+            assert!(
+                mounted.contains(route),
+                "{} {} is documented but not mounted",
+                route.0,
+                route.1
+            );
+        }
+    }
+}
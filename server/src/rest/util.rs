@@ -1,9 +1,13 @@
 use axum::{
     body::Body,
-    http::{Response, StatusCode},
+    http::{HeaderMap, Response, StatusCode, header},
 };
+use schemars::JsonSchema;
+use serde::Deserialize;
 use serde_json::json;
 
+use crate::auth::AuthError;
+
 pub struct ResponseUtil {}
 
 impl ResponseUtil {
@@ -41,25 +45,109 @@ impl ResponseUtil {
             .unwrap()
     }
 
+    /// Like [`ResponseUtil::ok`], but encoded as MessagePack
+    pub fn msgpack<T: serde::Serialize>(data: T) -> Response<Body> {
+        let bytes = match rmp_serde::to_vec_named(&data) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                tracing::error!("Failed to serialize response data: {}", e);
+                return Self::error("Failed to serialize response data");
+            }
+        };
+
+        Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", MSGPACK_CONTENT_TYPE)
+            .body(Body::from(bytes))
+            .unwrap()
+    }
+
+    pub fn ok_as<T: serde::Serialize>(data: T, format: ResponseFormat) -> Response<Body> {
+        match format {
+            ResponseFormat::Json => Self::ok(data),
+            ResponseFormat::Msgpack => Self::msgpack(data),
+        }
+    }
+
     pub fn not_found(message: &str) -> Response<Body> {
+        Self::client_error(StatusCode::NOT_FOUND, message)
+    }
+
+    /// The request needs a login
+    pub fn unauthorized(message: &str) -> Response<Body> {
+        Self::client_error(StatusCode::UNAUTHORIZED, message)
+    }
+
+    /// The user isn't allowed to do this
+    pub fn forbidden(message: &str) -> Response<Body> {
+        Self::client_error(StatusCode::FORBIDDEN, message)
+    }
+
+    pub fn bad_request(message: &str) -> Response<Body> {
+        Self::client_error(StatusCode::BAD_REQUEST, message)
+    }
+
+    fn client_error(status: StatusCode, message: &str) -> Response<Body> {
         let json = match serde_json::to_string(&json!({ "error": message })) {
             Ok(json) => json,
             Err(e) => {
-                tracing::error!("Failed to serialize not found message: {}", e);
-                return Self::error("Failed to serialize not found message");
+                tracing::error!("Failed to serialize {} message: {}", status, e);
+                return Self::error("Failed to serialize error message");
             }
         };
         Response::builder()
-            .status(StatusCode::NOT_FOUND)
+            .status(status)
             .header("Content-Type", "application/json")
             .body(Body::from(json))
             .unwrap()
     }
 }
 
+const MSGPACK_CONTENT_TYPE: &str = "application/msgpack";
+
+/// Encoding of a response body
+#[derive(Debug, Default, Clone, Copy, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseFormat {
+    #[default]
+    Json,
+    Msgpack,
+}
+
+/// Query of the routes that can answer in either [`ResponseFormat`]
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub struct FormatQuery {
+    /// Takes precedence over the `Accept` header
+    format: Option<ResponseFormat>,
+}
+
+impl ResponseFormat {
+    /// The format of the query, else MessagePack if the `Accept` header asks for it
+    pub fn negotiate(query: &FormatQuery, headers: &HeaderMap) -> Self {
+        if let Some(format) = query.format {
+            return format;
+        }
+        let accepts_msgpack = headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|accept| {
+                let media_type = accept.split(';').next().unwrap_or_default().trim();
+                media_type == MSGPACK_CONTENT_TYPE || media_type == "application/x-msgpack"
+            });
+        if accepts_msgpack {
+            Self::Msgpack
+        } else {
+            Self::Json
+        }
+    }
+}
+
 pub enum ResponseUtilError {
     Error(anyhow::Error),
     NotFound(anyhow::Error),
+    Auth(AuthError),
 }
 
 impl From<ResponseUtilError> for Response<Body> {
@@ -67,6 +155,45 @@ impl From<ResponseUtilError> for Response<Body> {
         match error {
             ResponseUtilError::Error(e) => ResponseUtil::error(&e.to_string()),
             ResponseUtilError::NotFound(e) => ResponseUtil::not_found(&e.to_string()),
+            ResponseUtilError::Auth(e @ AuthError::Unauthenticated) => {
+                ResponseUtil::unauthorized(&e.to_string())
+            }
+            ResponseUtilError::Auth(e @ AuthError::Forbidden { .. }) => {
+                ResponseUtil::forbidden(&e.to_string())
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_negotiate_format() {
+        let mut headers = HeaderMap::new();
+        let query = FormatQuery::default();
+        assert_eq!(
+            ResponseFormat::negotiate(&query, &headers),
+            ResponseFormat::Json
+        );
+
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("application/json;q=0.5, application/msgpack"),
+        );
+        assert_eq!(
+            ResponseFormat::negotiate(&query, &headers),
+            ResponseFormat::Msgpack
+        );
+
+        let query = FormatQuery {
+            format: Some(ResponseFormat::Json),
+        };
+        assert_eq!(
+            ResponseFormat::negotiate(&query, &headers),
+            ResponseFormat::Json
+        );
+    }
+}
@@ -0,0 +1,335 @@
+use crate::{add_serial_device, app_state::SharedState};
+use anyhow::{Result, anyhow, bail};
+use machines::{
+    SerialDeviceIdentification, SerialDeviceNewParams,
+    machine_identification::MachineIdentificationUnique,
+    registry::MACHINE_REGISTRY,
+    serial::{
+        init::SerialDetection,
+        registry::{SERIAL_DEVICE_REGISTRY, SerialDeviceRegistry},
+    },
+};
+use serialport::UsbPortInfo;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A serial device that was turned into a machine
+#[derive(Debug, Clone)]
+struct ConnectedSerialDevice {
+    identification: SerialDeviceIdentification,
+    /// Serial number reported by the USB adapter
+    usb_serial_number: Option<String>,
+    /// Machines behind the device, e.g. one per laser gauge on the bus
+    machine_identification_uniques: Vec<MachineIdentificationUnique>,
+}
+
+impl ConnectedSerialDevice {
+    fn is_on_port(&self, port: &UsbPortInfo) -> bool {
+        self.identification.vendor_id == port.vid
+            && self.identification.product_id == port.pid
+            && self.usb_serial_number == port.serial_number
+    }
+}
+
+/// A port with a known device type that has no machine yet
+struct NewSerialPort {
+    identification: SerialDeviceIdentification,
+    params: SerialDeviceNewParams,
+}
+
+#[derive(Debug, Default)]
+struct SerialHotplugState {
+    /// Port path -> device on that port
+    connected: HashMap<String, ConnectedSerialDevice>,
+    /// Ports whose device couldn't be created, they are retried after being replugged
+    failed: HashSet<String>,
+}
+
+impl SerialHotplugState {
+    /// Ports that disappeared or hold another device than before
+    fn removed_ports(&self, ports: &HashMap<String, UsbPortInfo>) -> Vec<String> {
+        self.connected
+            .iter()
+            .filter(|(path, device)| !ports.get(*path).is_some_and(|port| device.is_on_port(port)))
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    /// Ports with a registered device type that aren't connected yet, sorted by path
+    fn new_ports(
+        &self,
+        registry: &SerialDeviceRegistry,
+        ports: &HashMap<String, UsbPortInfo>,
+    ) -> Vec<NewSerialPort> {
+        let mut paths: Vec<&String> = ports
+            .keys()
+            .filter(|path| !self.connected.contains_key(*path) && !self.failed.contains(*path))
+            .collect();
+        paths.sort();
+
+        // (identification, usb serial number) that are already used as identity
+        let mut identities: HashSet<(SerialDeviceIdentification, String)> = self
+            .connected
+            .values()
+            .filter_map(|device| {
+                let serial_number = device.usb_serial_number.clone()?;
+                Some((device.identification.clone(), serial_number))
+            })
+            .collect();
+
+        let mut new_ports = vec![];
+        for path in paths {
+            let port = &ports[path];
+            let Some(identification) = registry.identify_usb_port(port) else {
+                continue;
+            };
+
+            // Some adapters share the same serial number, those fall back to the port path
+            let usb_serial_number = match &port.serial_number {
+                Some(serial_number)
+                    if identities.insert((identification.clone(), serial_number.clone())) =>
+                {
+                    Some(serial_number.clone())
+                }
+                Some(serial_number) => {
+                    tracing::warn!(
+                        "Serial number {} on {} is used by another device, identifying it by its port",
+                        serial_number,
+                        path
+                    );
+                    None
+                }
+                None => None,
+            };
+
+            new_ports.push(NewSerialPort {
+                identification: identification.clone(),
+                params: SerialDeviceNewParams {
+                    path: path.clone(),
+                    usb_serial_number,
+                },
+            });
+        }
+        new_ports
+    }
+}
+
+pub async fn start_serial_discovery(app_state: Arc<SharedState>) {
+    let mut state = SerialHotplugState::default();
+
+    loop {
+        let devices = SerialDetection::detect_devices();
+
+        // This allows detection of disconnected devices
+        handle_serial_device_hotplug(app_state.clone(), &mut state, devices).await;
+
+        smol::Timer::after(Duration::from_secs(1)).await;
+    }
+}
+
+async fn handle_serial_device_hotplug(
+    app_state: Arc<SharedState>,
+    state: &mut SerialHotplugState,
+    ports: HashMap<String, UsbPortInfo>,
+) {
+    state.failed.retain(|path| ports.contains_key(path));
+
+    // Only the machines of the port that changed are removed
+    for path in state.removed_ports(&ports) {
+        if let Some(device) = state.connected.remove(&path) {
+            tracing::info!(
+                "Serial device {:?} on {} disconnected",
+                device.machine_identification_uniques,
+                path
+            );
+            for machine_identification_unique in &device.machine_identification_uniques {
+                app_state
+                    .delete_machine(machine_identification_unique)
+                    .await;
+            }
+        }
+    }
+
+    for new_port in state.new_ports(&SERIAL_DEVICE_REGISTRY, &ports) {
+        let path = new_port.params.path.clone();
+        match connect_serial_device(app_state.clone(), &new_port).await {
+            Ok(machine_identification_uniques) => {
+                tracing::info!(
+                    "Serial device {:?} connected on {}",
+                    machine_identification_uniques,
+                    path
+                );
+                state.connected.insert(
+                    path.clone(),
+                    ConnectedSerialDevice {
+                        identification: new_port.identification,
+                        usb_serial_number: ports[&path].serial_number.clone(),
+                        machine_identification_uniques,
+                    },
+                );
+            }
+            Err(e) => {
+                tracing::error!("Failed to add serial device on {}: {:?}", path, e);
+                state.failed.insert(path);
+            }
+        }
+    }
+}
+
+async fn connect_serial_device(
+    app_state: Arc<SharedState>,
+    new_port: &NewSerialPort,
+) -> Result<Vec<MachineIdentificationUnique>> {
+    let (device_identifications, serial_device) =
+        SERIAL_DEVICE_REGISTRY.new_serial_device(&new_port.params, &new_port.identification)?;
+
+    let machine_identification_uniques = device_identifications
+        .iter()
+        .map(|device_identification| {
+            device_identification
+                .device_machine_identification
+                .as_ref()
+                .map(|identification| identification.machine_identification_unique.clone())
+                .ok_or_else(|| anyhow!("Serial device has no machine identification"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let current_machines_meta = app_state.current_machines_meta.lock().await;
+    let existing = machine_identification_uniques
+        .iter()
+        .find(|machine_identification_unique| {
+            current_machines_meta.iter().any(|machine| {
+                &machine.machine_identification_unique == *machine_identification_unique
+            })
+        })
+        .cloned();
+    drop(current_machines_meta);
+    if let Some(machine_identification_unique) = existing {
+        bail!("Machine {:?} already exists", machine_identification_unique);
+    }
+
+    for (index, device_identification) in device_identifications.iter().enumerate() {
+        let result = add_serial_device(
+            app_state.clone(),
+            device_identification,
+            serial_device.clone(),
+            &MACHINE_REGISTRY,
+            app_state.socketio_setup.socket_queue_tx.clone(),
+        )
+        .await;
+
+        // Don't leave half of the machines of a device behind
+        if let Err(e) = result {
+            for machine_identification_unique in &machine_identification_uniques[..index] {
+                app_state
+                    .delete_machine(machine_identification_unique)
+                    .await;
+            }
+            return Err(e);
+        }
+    }
+
+    Ok(machine_identification_uniques)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use machines::{
+        MACHINE_LASER_V1, VENDOR_QITECH, machine_identification::MachineIdentification,
+    };
+
+    const FTDI: SerialDeviceIdentification = SerialDeviceIdentification {
+        vendor_id: 0x0403,
+        product_id: 0x6001,
+    };
+
+    fn port(vid: u16, pid: u16, serial_number: Option<&str>) -> UsbPortInfo {
+        UsbPortInfo {
+            vid,
+            pid,
+            serial_number: serial_number.map(str::to_string),
+            manufacturer: None,
+            product: None,
+        }
+    }
+
+    fn laser(serial_number: Option<&str>, serial: u16) -> ConnectedSerialDevice {
+        ConnectedSerialDevice {
+            identification: FTDI,
+            usb_serial_number: serial_number.map(str::to_string),
+            machine_identification_uniques: vec![MachineIdentificationUnique {
+                machine_identification: MachineIdentification {
+                    vendor: VENDOR_QITECH,
+                    machine: MACHINE_LASER_V1,
+                },
+                serial,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_removed_ports() {
+        let mut state = SerialHotplugState::default();
+        state
+            .connected
+            .insert("/dev/ttyUSB0".into(), laser(Some("A1"), 1));
+        state
+            .connected
+            .insert("/dev/ttyUSB1".into(), laser(Some("B2"), 2));
+        state
+            .connected
+            .insert("/dev/ttyUSB2".into(), laser(Some("C3"), 3));
+
+        let ports = HashMap::from([
+            ("/dev/ttyUSB0".to_string(), port(0x0403, 0x6001, Some("A1"))),
+            // another adapter was plugged into the port of B2
+            ("/dev/ttyUSB1".to_string(), port(0x0403, 0x6001, Some("D4"))),
+        ]);
+
+        let mut removed = state.removed_ports(&ports);
+        removed.sort();
+        assert_eq!(removed, ["/dev/ttyUSB1", "/dev/ttyUSB2"]);
+    }
+
+    #[test]
+    fn test_new_ports() {
+        let mut state = SerialHotplugState::default();
+        state
+            .connected
+            .insert("/dev/ttyUSB0".into(), laser(Some("A1"), 1));
+        state.failed.insert("/dev/ttyUSB4".into());
+
+        let ports = HashMap::from([
+            ("/dev/ttyUSB0".to_string(), port(0x0403, 0x6001, Some("A1"))),
+            ("/dev/ttyUSB2".to_string(), port(0x0403, 0x6001, None)),
+            ("/dev/ttyUSB1".to_string(), port(0x0403, 0x6001, Some("B2"))),
+            ("/dev/ttyUSB3".to_string(), port(0x0403, 0x6001, Some("A1"))),
+            ("/dev/ttyUSB4".to_string(), port(0x0403, 0x6001, Some("E5"))),
+            ("/dev/ttyACM0".to_string(), port(0x2341, 0x0043, Some("X"))),
+        ]);
+
+        let new_ports = state.new_ports(&SERIAL_DEVICE_REGISTRY, &ports);
+        assert!(new_ports.iter().all(|p| p.identification == FTDI));
+
+        let new_ports: Vec<(&str, Option<&str>)> = new_ports
+            .iter()
+            .map(|p| {
+                (
+                    p.params.path.as_str(),
+                    p.params.usb_serial_number.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            new_ports,
+            [
+                ("/dev/ttyUSB1", Some("B2")),
+                ("/dev/ttyUSB2", None),
+                // same serial number as the laser on ttyUSB0
+                ("/dev/ttyUSB3", None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use crate::app_state::{HotThreadMessage, SharedState};
+use crate::config::ShutdownConfig;
+use crate::production::store_production_record;
+use crate::socketio::main_namespace::MainNamespaceEvents;
+use crate::socketio::main_namespace::server_shutdown_event::ServerShutdownEventBuilder;
+use control_core::socketio::namespace::NamespaceCacheingLogic;
+use machines::AsyncThreadMessage;
+use smol::channel::Receiver;
+use smol::future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Time to get the shutdown event out to the clients, independent of the machine deadline
+const NOTIFY_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// What the shutdown needs from the running server
+struct ShutdownContext {
+    app_state: Arc<SharedState>,
+    main_receiver: Receiver<AsyncThreadMessage>,
+    config: ShutdownConfig,
+}
+
+static CONTEXT: OnceLock<ShutdownContext> = OnceLock::new();
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+static RT_LOOP_FINISHED: (Mutex<bool>, Condvar) = (Mutex::new(false), Condvar::new());
+
+/// Enables the orderly shutdown, before this [`request_shutdown`] exits right away
+pub fn init_shutdown(
+    app_state: Arc<SharedState>,
+    main_receiver: Receiver<AsyncThreadMessage>,
+    config: ShutdownConfig,
+) {
+    let _ = CONTEXT.set(ShutdownContext {
+        app_state,
+        main_receiver,
+        config,
+    });
+}
+
+/// Exits on SIGINT and SIGTERM after the machines reached their safe state
+pub fn setup_signal_handler() {
+    if let Err(e) = ctrlc::set_handler(|| {
+        tracing::info!("Shutdown signal received");
+        request_shutdown(0, "Signal");
+    }) {
+        tracing::error!("Failed to set the shutdown signal handler: {}", e);
+    }
+}
+
+/// Called by the RT loop once the machines are in their safe state and it stopped cycling
+pub fn rt_loop_finished() {
+    let (finished, condvar) = &RT_LOOP_FINISHED;
+    *finished.lock().unwrap_or_else(|e| e.into_inner()) = true;
+    condvar.notify_all();
+}
+
+/// Shuts the server down and exits with `code`
+///
+/// Notifies the socket.io clients, lets the RT loop bring the machines into their safe state
+/// and flushes the production records that are still queued. All of it is bounded by
+/// `shutdown.deadline_ms`. Callers after the first wait for the first one to exit.
+pub fn request_shutdown(code: i32, reason: &str) -> ! {
+    let Some(context) = CONTEXT.get() else {
+        std::process::exit(code);
+    };
+    let deadline_duration = context.config.deadline();
+
+    if REQUESTED.swap(true, Ordering::SeqCst) {
+        std::thread::sleep(deadline_duration + 2 * NOTIFY_TIMEOUT);
+        tracing::error!("Shutdown did not finish in time, exiting");
+        std::process::exit(code);
+    }
+
+    tracing::info!(
+        "Shutting down ({}), deadline {:?}",
+        reason,
+        deadline_duration
+    );
+    notify_clients(context, reason);
+
+    let deadline = Instant::now() + deadline_duration;
+    let _ = context
+        .app_state
+        .rt_machine_creation_channel
+        .try_send(HotThreadMessage::Shutdown(deadline));
+    if !wait_for_rt_loop(deadline + NOTIFY_TIMEOUT) {
+        tracing::error!("RT loop did not finish in time, outputs may not be in their safe state");
+    }
+
+    flush_production_records(&context.main_receiver);
+
+    tracing::info!("Shutdown finished");
+    std::process::exit(code);
+}
+
+fn notify_clients(context: &ShutdownContext, reason: &str) {
+    let event = ServerShutdownEventBuilder().build(reason.to_string(), context.config.deadline_ms);
+    let emit = async {
+        context
+            .app_state
+            .socketio_setup
+            .namespaces
+            .write()
+            .await
+            .main_namespace
+            .emit(MainNamespaceEvents::ServerShutdownEvent(event));
+    };
+    // the namespaces might be locked by a panicked thread
+    smol::block_on(future::or(emit, async {
+        smol::Timer::after(NOTIFY_TIMEOUT).await;
+        tracing::warn!("Clients not notified about the shutdown, namespaces are locked");
+    }));
+}
+
+/// Returns `false` if the deadline passed first
+fn wait_for_rt_loop(deadline: Instant) -> bool {
+    let (finished, condvar) = &RT_LOOP_FINISHED;
+    let timeout = deadline.saturating_duration_since(Instant::now());
+    *condvar
+        .wait_timeout_while(
+            finished.lock().unwrap_or_else(|e| e.into_inner()),
+            timeout,
+            |finished| !*finished,
+        )
+        .unwrap_or_else(|e| e.into_inner())
+        .0
+}
+
+/// Stores the records the machines sent while shutting down, the async handler might not
+/// get to them anymore
+fn flush_production_records(main_receiver: &Receiver<AsyncThreadMessage>) {
+    while let Ok(message) = main_receiver.try_recv() {
+        if let AsyncThreadMessage::SpoolCompleted(record) = message {
+            store_production_record(*record);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wait_for_rt_loop() {
+        assert!(!wait_for_rt_loop(
+            Instant::now() + Duration::from_millis(10)
+        ));
+
+        let waiter =
+            std::thread::spawn(|| wait_for_rt_loop(Instant::now() + Duration::from_secs(10)));
+        rt_loop_finished();
+        assert!(waiter.join().unwrap());
+    }
+}
@@ -1,10 +1,47 @@
 use super::namespace_id::NamespaceId;
 use crate::app_state::SharedState;
+use crate::auth::{AuthError, Role, bearer_token};
+use serde::Deserialize;
 use socketioxide::ParserConfig;
-use socketioxide::extract::SocketRef;
+use socketioxide::extract::{SocketRef, TryData};
+use socketioxide::handler::ConnectHandler;
 use socketioxide::layer::SocketIoLayer;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Instant;
+
+/// Handshake auth of a socket, `{ token }` as returned by the login
+#[derive(Debug, Deserialize)]
+struct SocketAuth {
+    token: Option<String>,
+}
+
+/// Rejects the connection unless the handshake auth or `Authorization` header carries the
+/// token of a viewer
+async fn authorize_socket(
+    socket: &SocketRef,
+    auth: Option<SocketAuth>,
+    app_state: &SharedState,
+) -> Result<(), AuthError> {
+    let token = auth
+        .and_then(|auth| auth.token)
+        .or_else(|| bearer_token(&socket.req_parts().headers).map(str::to_owned));
+    let result =
+        app_state
+            .auth
+            .lock()
+            .await
+            .authorize(token.as_deref(), Role::Viewer, Instant::now());
+    if let Err(e) = &result {
+        tracing::info!(
+            "Refusing socket socket={:?} namespace={}: {}",
+            socket.id,
+            socket.ns(),
+            e
+        );
+    }
+    result.map(|_| ())
+}
 
 pub async fn init_socketio(app_state: Arc<SharedState>) -> SocketIoLayer {
     // create
@@ -13,22 +50,31 @@ pub async fn init_socketio(app_state: Arc<SharedState>) -> SocketIoLayer {
         .with_parser(ParserConfig::msgpack())
         .build_layer();
 
+    // every namespace needs a login
+    let app_state_auth = app_state.clone();
+    let authorize = move |socket: SocketRef, TryData(auth): TryData<SocketAuth>| {
+        let app_state = app_state_auth.clone();
+        async move { authorize_socket(&socket, auth.ok(), &app_state).await }
+    };
+
     // Clone app_state for the first handler
     let app_state_main = app_state.clone();
 
     // set the on connect handler for main namespace
-    io.ns("/main", move |socket: SocketRef| {
+    let main_handler = move |socket: SocketRef| {
         handle_socket_connection(socket, app_state_main.clone());
-    });
+    };
+    io.ns("/main", main_handler.with(authorize.clone()));
 
     // Clone app_state for the second handler
     let app_state_machine = app_state.clone();
 
+    let machine_handler = move |socket: SocketRef| {
+        handle_socket_connection(socket, app_state_machine.clone());
+    };
     if let Err(err) = io.dyn_ns(
         "/machine/{vendor}/{machine}/{serial}",
-        move |socket: SocketRef| {
-            handle_socket_connection(socket, app_state_machine.clone());
-        },
+        machine_handler.with(authorize),
     ) {
         tracing::error!("Failed to detect machine namespace: {}", err);
     }
@@ -72,7 +118,8 @@ fn setup_disconnection(socket: SocketRef, namespace_id: NamespaceId, app_state:
             // write-lock to mutate namespaces
             let mut namespaces_guard = app_state.socketio_setup.namespaces.write().await;
 
-            match namespaces_guard.apply_mut(namespace_id.clone()).await {
+            // the machine keeps emitting to the sockets and streams that are left
+            let remaining = match namespaces_guard.apply_mut(namespace_id.clone()).await {
                 Ok(namespace) => {
                     namespace.unsubscribe(socket.clone());
                     tracing::info!(
@@ -80,6 +127,7 @@ fn setup_disconnection(socket: SocketRef, namespace_id: NamespaceId, app_state:
                         socket.id,
                         namespace_id
                     );
+                    namespace.has_subscribers().then(|| namespace.clone())
                 }
                 Err(err) => {
                     tracing::info!(
@@ -88,12 +136,18 @@ fn setup_disconnection(socket: SocketRef, namespace_id: NamespaceId, app_state:
                         namespace_id,
                         err
                     );
+                    None
                 }
-            }
+            };
+            drop(namespaces_guard);
             if let NamespaceId::Machine(ident) = namespace_id.clone() {
                     match app_state.clone().api_machines.lock().await.get(&ident) {
                         Some(sender) => {
-                            let _ = sender.send(machines::MachineMessage::UnsubscribeNamespace).await;
+                            let message = remaining.map_or(
+                                machines::MachineMessage::UnsubscribeNamespace,
+                                machines::MachineMessage::SubscribeNamespace,
+                            );
+                            let _ = sender.send(message).await;
                         },
                         None => tracing::info!("sender doesnt exist for: {}",ident),
                     };
@@ -109,24 +163,10 @@ fn setup_connection(socket: SocketRef, namespace_id: NamespaceId, app_state: Arc
     let namespace_id_clone = namespace_id.clone();
     let app_state_clone = app_state.clone();
     smol::spawn(async move {
-        let guard = app_state_clone.socketio_setup.namespaces.read().await;
-        let socket_queue_tx =  guard.main_namespace.namespace.socket_queue_tx.clone();
-        drop(guard);
-
         let mut namespaces_guard = app_state_clone.socketio_setup.namespaces.write().await;
         // Ensure machine namespace exists before applying
-        if let NamespaceId::Machine(_) = namespace_id_clone {
-            let map = &mut namespaces_guard.machine_namespaces;
-            if !map.contains_key(&namespace_id_clone) {
-                tracing::info!(
-                    "Registering new machine namespace: {}",
-                    namespace_id_clone
-                );
-                // Clone the sender from your main namespace
-                // Now create the namespace
-                let ns = control_core::socketio::namespace::Namespace::new(socket_queue_tx);
-                map.insert(namespace_id_clone.clone(), ns);
-            }
+        if let NamespaceId::Machine(machine) = &namespace_id_clone {
+            namespaces_guard.machine_namespace(machine);
         }
 
         // Apply and subscribe the socket
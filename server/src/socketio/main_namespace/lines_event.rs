@@ -0,0 +1,18 @@
+use crate::line::runner::LineProgress;
+use control_core::socketio::event::Event;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LinesEvent {
+    pub lines: Vec<LineProgress>,
+}
+
+pub struct LinesEventBuilder();
+
+impl LinesEventBuilder {
+    const NAME: &'static str = "LinesEvent";
+
+    pub fn build(&self, lines: Vec<LineProgress>) -> Event<LinesEvent> {
+        Event::new(Self::NAME, LinesEvent { lines })
+    }
+}
@@ -1,5 +1,6 @@
 use control_core::socketio::event::Event;
 use machines::machine_identification::MachineIdentificationUnique;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -7,7 +8,7 @@ pub struct MachinesEvent {
     pub machines: Vec<MachineObj>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct MachineObj {
     pub machine_identification_unique: MachineIdentificationUnique,
     pub error: Option<String>,
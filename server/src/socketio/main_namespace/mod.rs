@@ -6,14 +6,18 @@ use control_core::socketio::{
 };
 use ethercat_devices_event::EthercatDevicesEvent;
 use ethercat_interface_discovery_event::EthercatInterfaceDiscoveryEvent;
+use lines_event::LinesEvent;
 use machines_event::MachinesEvent;
+use server_shutdown_event::ServerShutdownEvent;
 use smol::channel::Sender;
 use socketioxide::extract::SocketRef;
 use tracing::instrument;
 
 pub mod ethercat_devices_event;
 pub mod ethercat_interface_discovery_event;
+pub mod lines_event;
 pub mod machines_event;
+pub mod server_shutdown_event;
 
 pub struct MainRoom {
     pub namespace: Namespace,
@@ -44,6 +48,8 @@ pub enum MainNamespaceEvents {
     MachinesEvent(Event<MachinesEvent>),
     EthercatDevicesEvent(Event<EthercatDevicesEvent>),
     EthercatInterfaceDiscoveryEvent(Event<EthercatInterfaceDiscoveryEvent>),
+    LinesEvent(Event<LinesEvent>),
+    ServerShutdownEvent(Event<ServerShutdownEvent>),
 }
 
 impl CacheableEvents<Self> for MainNamespaceEvents {
@@ -52,6 +58,8 @@ impl CacheableEvents<Self> for MainNamespaceEvents {
             Self::EthercatDevicesEvent(event) => event.into(),
             Self::EthercatInterfaceDiscoveryEvent(event) => event.into(),
             Self::MachinesEvent(event) => event.into(),
+            Self::LinesEvent(event) => event.into(),
+            Self::ServerShutdownEvent(event) => event.into(),
         }
     }
 
@@ -60,6 +68,8 @@ impl CacheableEvents<Self> for MainNamespaceEvents {
             Self::EthercatDevicesEvent(_) => cache_one_event(),
             Self::EthercatInterfaceDiscoveryEvent(_) => cache_one_event(),
             Self::MachinesEvent(_) => cache_one_event(),
+            Self::LinesEvent(_) => cache_one_event(),
+            Self::ServerShutdownEvent(_) => cache_one_event(),
         }
     }
 }
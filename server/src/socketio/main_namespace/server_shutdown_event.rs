@@ -0,0 +1,27 @@
+use control_core::socketio::event::Event;
+use serde::{Deserialize, Serialize};
+
+/// Sent once when the server starts to shut down, the connection drops afterwards
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ServerShutdownEvent {
+    /// Why the server shuts down, a signal or a panic
+    pub reason: String,
+    /// Time the machines get to reach their safe state in ms
+    pub deadline_ms: u64,
+}
+
+pub struct ServerShutdownEventBuilder();
+
+impl ServerShutdownEventBuilder {
+    const NAME: &'static str = "ServerShutdownEvent";
+
+    pub fn build(&self, reason: String, deadline_ms: u64) -> Event<ServerShutdownEvent> {
+        Event::new(
+            Self::NAME,
+            ServerShutdownEvent {
+                reason,
+                deadline_ms,
+            },
+        )
+    }
+}
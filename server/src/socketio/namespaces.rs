@@ -1,12 +1,26 @@
 use super::{main_namespace::MainRoom, namespace_id::NamespaceId};
 use control_core::socketio::event::GenericEvent;
-use smol::channel::Sender;
+use control_core::socketio::namespace::{
+    CacheFn, EventStream, Namespace, cache_first_and_last_event, cache_one_event,
+};
+use machines::machine_identification::MachineIdentificationUnique;
+use machines::machine_settings::{set_latest_live_values, set_latest_settings};
+use smol::channel::{Receiver, Sender};
+use smol::lock::RwLock;
 use socketioxide::extract::SocketRef;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Weak},
+};
+
+const STATE_EVENT: &str = "StateEvent";
+const LIVE_VALUES_EVENT: &str = "LiveValuesEvent";
 
 pub struct Namespaces {
     pub main_namespace: MainRoom,
     pub machine_namespaces: HashMap<NamespaceId, control_core::socketio::namespace::Namespace>,
+    /// Handle for the snapshot collectors, which cache the emitted events in here
+    shared: Weak<RwLock<Self>>,
 }
 
 impl Namespaces {
@@ -27,10 +41,145 @@ impl Namespaces {
         }
     }
 
-    pub fn new(socket_queue_tx: Sender<(SocketRef, Arc<GenericEvent>)>) -> Self {
-        Self {
-            main_namespace: MainRoom::new(socket_queue_tx),
-            machine_namespaces: HashMap::new(),
+    /// The namespace of a machine, created on first use
+    ///
+    /// A new namespace is subscribed to the snapshot collector. The machine emits into its own
+    /// copy of the namespace, the collector caches the latest state and live values in this one
+    /// for the reemit and the REST API, and keeps the snapshots of `machines::machine_settings`
+    /// for the lines and the audit log. This way they are serialized here instead of in the RT loop.
+    pub fn machine_namespace(&mut self, machine: &MachineIdentificationUnique) -> &mut Namespace {
+        let socket_queue_tx = self.main_namespace.namespace.socket_queue_tx.clone();
+        self.machine_namespaces
+            .entry(NamespaceId::Machine(machine.clone()))
+            .or_insert_with(|| {
+                tracing::info!("Registering new machine namespace: {}", machine);
+                let mut namespace = Namespace::new(socket_queue_tx);
+                // unbounded, a dropped state event would leave a stale snapshot until the next one
+                let (sender, receiver) = smol::channel::unbounded();
+                namespace.subscribe_stream(EventStream::new(
+                    Some(HashSet::from([
+                        STATE_EVENT.to_string(),
+                        LIVE_VALUES_EVENT.to_string(),
+                    ])),
+                    sender,
+                ));
+                smol::spawn(collect_snapshots(
+                    machine.clone(),
+                    receiver,
+                    self.shared.clone(),
+                ))
+                .detach();
+                namespace
+            })
+    }
+
+    pub fn new_shared(
+        socket_queue_tx: Sender<(SocketRef, Arc<GenericEvent>)>,
+    ) -> Arc<RwLock<Self>> {
+        Arc::new_cyclic(|shared| {
+            RwLock::new(Self {
+                main_namespace: MainRoom::new(socket_queue_tx),
+                machine_namespaces: HashMap::new(),
+                shared: shared.clone(),
+            })
+        })
+    }
+}
+
+/// Caches the latest `StateEvent` and `LiveValuesEvent` of a machine in its namespace and
+/// stores them in `machines::machine_settings`
+async fn collect_snapshots(
+    machine: MachineIdentificationUnique,
+    receiver: Receiver<Arc<GenericEvent>>,
+    namespaces: Weak<RwLock<Namespaces>>,
+) {
+    let namespace_id = NamespaceId::Machine(machine.clone());
+    while let Ok(event) = receiver.recv().await {
+        let value = match serde_json::to_value(&event.data) {
+            Ok(value) => value,
+            Err(e) => {
+                tracing::error!("Failed to serialize {} of {}: {}", event.name, machine, e);
+                continue;
+            }
+        };
+        match event.name.as_str() {
+            STATE_EVENT => set_latest_settings(&machine, value),
+            LIVE_VALUES_EVENT => set_latest_live_values(&machine, value),
+            _ => continue,
+        }
+
+        let Some(namespaces) = namespaces.upgrade() else {
+            break;
+        };
+        let mut namespaces = namespaces.write().await;
+        if let Some(namespace) = namespaces.machine_namespaces.get_mut(&namespace_id) {
+            // the first state is the default state, which a reemit sends first
+            let cache_fn: CacheFn = match event.name.as_str() {
+                STATE_EVENT => cache_first_and_last_event(),
+                _ => cache_one_event(),
+            };
+            namespace.cache(event, &cache_fn);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use control_core::socketio::event::Event;
+    use machines::machine_identification::MachineIdentification;
+    use machines::machine_settings::{get_latest_live_values, get_latest_settings};
+    use serde_json::json;
+
+    #[test]
+    fn test_machine_namespace_snapshots() {
+        let machine = MachineIdentificationUnique {
+            machine_identification: MachineIdentification {
+                vendor: 0xfff0,
+                machine: 2,
+            },
+            serial: 4711,
+        };
+        let shared = Namespaces::new_shared(smol::channel::unbounded().0);
+        let mut namespaces = smol::block_on(shared.write());
+        let mut namespace = namespaces.machine_namespace(&machine).clone();
+        // the same namespace with one collector on every call
+        assert_eq!(namespaces.machine_namespace(&machine).streams.len(), 1);
+        drop(namespaces);
+
+        let state = json!({ "mode": "Standby" });
+        namespace.emit(
+            Arc::new(Event::new(STATE_EVENT, state.clone()).into()),
+            &cache_one_event(),
+        );
+        namespace.emit(
+            Arc::new(Event::new("OtherEvent", json!(1)).into()),
+            &cache_one_event(),
+        );
+
+        // the collector caches in the namespace it was created with, not only in the copy the
+        // machine emitted into
+        let namespace_id = NamespaceId::Machine(machine.clone());
+        let cached = smol::block_on(async {
+            loop {
+                let namespaces = shared.read().await;
+                if let Some(event) =
+                    namespaces.machine_namespaces[&namespace_id].latest_event(STATE_EVENT)
+                {
+                    break event;
+                }
+                drop(namespaces);
+                smol::Timer::after(std::time::Duration::from_millis(1)).await;
+            }
+        });
+        assert_eq!(serde_json::to_value(&cached.data).unwrap(), state);
+        assert_eq!(get_latest_settings(&machine), Some(state));
+        assert_eq!(get_latest_live_values(&machine), None);
+        assert!(
+            smol::block_on(shared.read()).machine_namespaces[&namespace_id]
+                .latest_event("OtherEvent")
+                .is_none()
+        );
+        machines::machine_settings::remove_latest_settings(&machine);
+    }
+}
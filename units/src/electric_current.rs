@@ -13,6 +13,7 @@ quantity! {
     units {
         @milliampere: 1.0e-3; "mA", "millampere", "millamperes";
         @centiampere: 1.0e-2; "cA", "centiampere", "centiamperes";
+        @deciampere: 1.0e-1; "dA", "deciampere", "deciamperes";
         @ampere: 1.0; "A", "ampere", "amperes";
     }
 }
@@ -13,6 +13,7 @@ quantity! {
     units {
         @millivolt: 1.0e-3; "mV", "millivolt", "millivolts";
         @centivolt: 1.0e-2; "cV", "centivolt", "centivolts";
+        @decivolt: 1.0e-1; "dV", "decivolt", "decivolts";
         @volt: 1.0; "V", "volt", "volts";
     }
 }